@@ -10,22 +10,31 @@
 #   curl --data-binary @firmware.bin \
 #     -H 'Content-Type: application/octet-stream' \
 #     http://<device-ip>/ota
+#
+# Set CHIP=esp32s3 to build the ESP32-S3 board profile (default: esp32).
 set -euo pipefail
 
 cd "$(dirname "$0")"
 
-ELF="target/xtensa-esp32-none-elf/release/access-controller"
+CHIP="${CHIP:-esp32}"
+case "${CHIP}" in
+    esp32) FEATURES=() ;;
+    esp32s3) FEATURES=(--no-default-features --features esp32s3) ;;
+    *) echo "error: unknown CHIP '${CHIP}' (esp32 or esp32s3)" >&2; exit 1 ;;
+esac
+TARGET="xtensa-${CHIP}-none-elf"
+ELF="target/${TARGET}/release/access-controller"
 OUT="firmware.bin"
 
-echo "==> cargo build --release"
-cargo build --release
+echo "==> cargo build --release (${CHIP})"
+cargo build --release --target "${TARGET}" "${FEATURES[@]+"${FEATURES[@]}"}"
 
 echo "==> espflash save-image -> ${OUT}"
 # save-image produces just the application image (no bootloader, no
 # partition table) - exactly what gets written into ota_0 / ota_1, and
 # exactly what POST /ota expects.
 espflash save-image \
-    --chip esp32 \
+    --chip "${CHIP}" \
     "${ELF}" \
     "${OUT}"
 
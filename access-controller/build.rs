@@ -5,8 +5,56 @@
 //!   CONWAY_SSID=MyWiFi \
 //!   CONWAY_PASSWORD=secret123 \
 //!   CONWAY_HOST=192.168.1.68 \
+//!   CONWAY_FALLBACK_HOSTS=192.168.1.69,192.168.1.70 \
 //!   CONWAY_PORT=8080 \
+//!   CONWAY_SYNC_PATH=/controllers/v1/fobs \
+//!   CONWAY_DEVICE_LABEL="Wood shop door" \
+//!   CONWAY_REDIRECT_CROSS_HOST=1 \
+//!   CONWAY_SYNC_PROTOCOL=binary \
+//!   CONWAY_EVENT_FIELDS=fob=credential,allowed=granted \
+//!   CONWAY_EVENT_ENCODING=auto \
+//!   CONWAY_SYNC_RETRIES=2 \
+//!   CONWAY_OFFLINE_SECS=7200 \
+//!   CONWAY_DHCP_TIMEOUT_SECS=120 \
+//!   CONWAY_REBOOT_AFTER_HOURS=168 \
+//!   CONWAY_REBOOT_WINDOW=02:00-04:00 \
+//!   CONWAY_MAX_EVENTS_PER_SYNC=10 \
+//!   CONWAY_REPORT_GRANTS=0 \
+//!   CONWAY_EVENT_DEDUP_MS=2000 \
+//!   CONWAY_FULL_RESYNC_CYCLES=360 \
+//!   CONWAY_EMPTY_LIST=keep \
+//!   CONWAY_MAX_LIST_SHRINK_PCT=50 \
+//!   CONWAY_FOB_FORMAT=normalize \
+//!   CONWAY_PUSH_PATH=/api/fobs/ws \
+//!   CONWAY_PUSH_TRANSPORT=websocket \
 //!   CONWAY_UNLOCK_SECRET=mysecret \
+//!   CONWAY_FOBS_SECRET=fobsecret \
+//!   CONWAY_SYNC_SECRET=syncsecret \
+//!   CONWAY_RESPONSE_HMAC_KEY=sharedsecret \
+//!   CONWAY_SYNC_NONCE=1 \
+//!   CONWAY_HTTP_BODY_MAX=2048 \
+//!   CONWAY_DIAG_SECRET=diagsecret \
+//!   CONWAY_ADMIN_ALLOW=192.168.10.0/24,10.0.0.5 \
+//!   CONWAY_MATCH_ORDER=nfc \
+//!   CONWAY_UID_BYTE_ORDER=msb \
+//!   CONWAY_FAIL_OPEN_SECS=3600 \
+//!   CONWAY_DENY_BACKOFF_STEPS=5 \
+//!   CONWAY_KNOWN_FACILITIES=12,34 \
+//!   CONWAY_REPORT_DENIALS=unresolved \
+//!   CONWAY_AUTHZ_PATH=/api/authz \
+//!   CONWAY_AUTHZ_TIMEOUT_MS=1000 \
+//!   CONWAY_AUTHZ_DENY_TTL_SECS=60 \
+//!   CONWAY_PASSBACK_SECS=10 \
+//!   CONWAY_DOOR_HOLD_MS=5000 \
+//!   CONWAY_DOOR_EXTENDED_HOLD_MS=15000 \
+//!   CONWAY_DOOR_RELOCK=close \
+//!   CONWAY_READER_KEEPALIVE_MS=30000 \
+//!   CONWAY_FEEDBACK_GRANT=LB:100,L:100 \
+//!   CONWAY_FEEDBACK_DENY=B:600 \
+//!   CONWAY_WIEGAND_MIN_PULSE_US=10 \
+//!   CONWAY_WIEGAND_MIN_GAP_MS=100 \
+//!   CONWAY_WIEGAND_TX_FORMAT=26 \
+//!   CONWAY_OSDP_ADDRESS=0 \
 //!   cargo build --release
 //!
 //! Or use the build.sh wrapper script.
@@ -16,6 +64,54 @@ fn main() {
     println!("cargo::rerun-if-env-changed=CONWAY_SSID");
     println!("cargo::rerun-if-env-changed=CONWAY_PASSWORD");
     println!("cargo::rerun-if-env-changed=CONWAY_HOST");
+    println!("cargo::rerun-if-env-changed=CONWAY_FALLBACK_HOSTS");
     println!("cargo::rerun-if-env-changed=CONWAY_PORT");
+    println!("cargo::rerun-if-env-changed=CONWAY_SYNC_PATH");
+    println!("cargo::rerun-if-env-changed=CONWAY_DEVICE_LABEL");
+    println!("cargo::rerun-if-env-changed=CONWAY_REDIRECT_CROSS_HOST");
+    println!("cargo::rerun-if-env-changed=CONWAY_SYNC_PROTOCOL");
+    println!("cargo::rerun-if-env-changed=CONWAY_EVENT_FIELDS");
+    println!("cargo::rerun-if-env-changed=CONWAY_EVENT_ENCODING");
+    println!("cargo::rerun-if-env-changed=CONWAY_SYNC_RETRIES");
+    println!("cargo::rerun-if-env-changed=CONWAY_OFFLINE_SECS");
+    println!("cargo::rerun-if-env-changed=CONWAY_DHCP_TIMEOUT_SECS");
+    println!("cargo::rerun-if-env-changed=CONWAY_REBOOT_AFTER_HOURS");
+    println!("cargo::rerun-if-env-changed=CONWAY_REBOOT_WINDOW");
+    println!("cargo::rerun-if-env-changed=CONWAY_MAX_EVENTS_PER_SYNC");
+    println!("cargo::rerun-if-env-changed=CONWAY_REPORT_GRANTS");
+    println!("cargo::rerun-if-env-changed=CONWAY_EVENT_DEDUP_MS");
+    println!("cargo::rerun-if-env-changed=CONWAY_FULL_RESYNC_CYCLES");
+    println!("cargo::rerun-if-env-changed=CONWAY_EMPTY_LIST");
+    println!("cargo::rerun-if-env-changed=CONWAY_MAX_LIST_SHRINK_PCT");
+    println!("cargo::rerun-if-env-changed=CONWAY_FOB_FORMAT");
+    println!("cargo::rerun-if-env-changed=CONWAY_PUSH_PATH");
+    println!("cargo::rerun-if-env-changed=CONWAY_PUSH_TRANSPORT");
     println!("cargo::rerun-if-env-changed=CONWAY_UNLOCK_SECRET");
+    println!("cargo::rerun-if-env-changed=CONWAY_FOBS_SECRET");
+    println!("cargo::rerun-if-env-changed=CONWAY_SYNC_SECRET");
+    println!("cargo::rerun-if-env-changed=CONWAY_RESPONSE_HMAC_KEY");
+    println!("cargo::rerun-if-env-changed=CONWAY_SYNC_NONCE");
+    println!("cargo::rerun-if-env-changed=CONWAY_HTTP_BODY_MAX");
+    println!("cargo::rerun-if-env-changed=CONWAY_DIAG_SECRET");
+    println!("cargo::rerun-if-env-changed=CONWAY_ADMIN_ALLOW");
+    println!("cargo::rerun-if-env-changed=CONWAY_MATCH_ORDER");
+    println!("cargo::rerun-if-env-changed=CONWAY_UID_BYTE_ORDER");
+    println!("cargo::rerun-if-env-changed=CONWAY_FAIL_OPEN_SECS");
+    println!("cargo::rerun-if-env-changed=CONWAY_DENY_BACKOFF_STEPS");
+    println!("cargo::rerun-if-env-changed=CONWAY_KNOWN_FACILITIES");
+    println!("cargo::rerun-if-env-changed=CONWAY_REPORT_DENIALS");
+    println!("cargo::rerun-if-env-changed=CONWAY_AUTHZ_PATH");
+    println!("cargo::rerun-if-env-changed=CONWAY_AUTHZ_TIMEOUT_MS");
+    println!("cargo::rerun-if-env-changed=CONWAY_AUTHZ_DENY_TTL_SECS");
+    println!("cargo::rerun-if-env-changed=CONWAY_PASSBACK_SECS");
+    println!("cargo::rerun-if-env-changed=CONWAY_DOOR_HOLD_MS");
+    println!("cargo::rerun-if-env-changed=CONWAY_DOOR_EXTENDED_HOLD_MS");
+    println!("cargo::rerun-if-env-changed=CONWAY_DOOR_RELOCK");
+    println!("cargo::rerun-if-env-changed=CONWAY_READER_KEEPALIVE_MS");
+    println!("cargo::rerun-if-env-changed=CONWAY_FEEDBACK_GRANT");
+    println!("cargo::rerun-if-env-changed=CONWAY_FEEDBACK_DENY");
+    println!("cargo::rerun-if-env-changed=CONWAY_WIEGAND_MIN_PULSE_US");
+    println!("cargo::rerun-if-env-changed=CONWAY_WIEGAND_MIN_GAP_MS");
+    println!("cargo::rerun-if-env-changed=CONWAY_WIEGAND_TX_FORMAT");
+    println!("cargo::rerun-if-env-changed=CONWAY_OSDP_ADDRESS");
 }
@@ -0,0 +1,17 @@
+//! A fob list body, JSON, binary and chunked; see
+//! `access_controller::fuzz::fob_list`.
+
+#![no_main]
+
+use access_controller::fob_cache::MAX_FOBS;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let lists = access_controller::fuzz::fob_list(data);
+    for n in [lists.json, lists.binary].into_iter().flatten() {
+        assert!(n <= MAX_FOBS);
+    }
+    if let Ok(n) = lists.dechunked {
+        assert!(n <= data.len());
+    }
+});
@@ -0,0 +1,9 @@
+//! A raw sync response; see `access_controller::fuzz::sync_response`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = access_controller::fuzz::sync_response(data);
+});
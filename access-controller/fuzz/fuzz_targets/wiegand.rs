@@ -0,0 +1,19 @@
+//! A Wiegand transmission; see `access_controller::fuzz::wiegand`.
+
+#![no_main]
+
+use access_controller::decode::encode_26;
+use access_controller::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some(read) = fuzz::wiegand(data) else {
+        return;
+    };
+    let _ = (read.to_fob(), read.to_nfc_uid());
+    // A 26-bit frame carries nothing but facility, card and parity.
+    let (count, bits) = fuzz::wiegand_frame(data);
+    if count == 26 {
+        assert_eq!(encode_26(read.facility, read.card), bits);
+    }
+});
@@ -0,0 +1,180 @@
+//! Online authorization check for credentials the cached lists miss.
+//!
+//! A site whose membership outgrows [`MAX_FOBS`](crate::fob_cache::MAX_FOBS)
+//! can't cache everyone. With `CONWAY_AUTHZ_PATH` set, a read neither
+//! list grants is not denied straight away: [`AccessCore`] emits
+//! [`Effect::QueryAuthz`] and holds the denial while the firmware asks
+//! the Conway host `GET {path}?fob=N`. A [`Verdict::Allow`] answer opens
+//! the door; anything else, including no answer within
+//! `CONWAY_AUTHZ_TIMEOUT_MS`, falls through to the usual denial.
+//!
+//! A `deny` is remembered for `CONWAY_AUTHZ_DENY_TTL_SECS` in a
+//! [`NegativeCache`], so a refused card swiped again and again is denied
+//! from memory instead of asking each time.
+//!
+//! The answer is a plain-text body, `allow` or `deny`. It is not signed,
+//! so it carries the same trust as an unsigned sync: only point this at
+//! a host the LAN can't impersonate.
+//!
+//! [`AccessCore`]: crate::core::AccessCore
+//! [`Effect::QueryAuthz`]: crate::core::Effect::QueryAuthz
+
+use core::fmt::Write;
+
+use crate::core::CardRead;
+use crate::credential_window::CredentialWindow;
+use crate::http_client;
+
+/// How long a query may take when `CONWAY_AUTHZ_TIMEOUT_MS` is unset.
+pub const DEFAULT_TIMEOUT_MS: u64 = 1_500;
+
+/// Shortest timeout [`parse_timeout_ms`] accepts.
+pub const MIN_TIMEOUT_MS: u64 = 100;
+
+/// Longest timeout [`parse_timeout_ms`] accepts. Someone is standing at
+/// the door for all of it.
+pub const MAX_TIMEOUT_MS: u64 = 5_000;
+
+/// Slack the core allows past the timeout for the answer to reach it,
+/// since the firmware's own timeout and the core's clock read are not
+/// the same instant.
+pub const ANSWER_GRACE_MS: u64 = 500;
+
+/// How long a `deny` is remembered when `CONWAY_AUTHZ_DENY_TTL_SECS` is
+/// unset.
+pub const DEFAULT_DENY_TTL_SECS: u64 = 30;
+
+/// Longest TTL [`parse_deny_ttl_secs`] accepts. Past this, a member
+/// added online only could stand refused at the door for too long.
+pub const MAX_DENY_TTL_SECS: u64 = 600;
+
+/// Refused credentials remembered at once. The oldest is forgotten first.
+pub const NEGATIVE_CACHE_SLOTS: usize = 8;
+
+/// Most of an answer kept, head included. The body is one word; this
+/// covers any sane head.
+pub const MAX_RESPONSE_BYTES: usize = 512;
+
+/// The query socket's receive buffer. The answer is copied out as it
+/// arrives, so this only bounds the TCP window.
+pub const SOCKET_RX_BYTES: usize = 256;
+
+/// The query socket's transmit buffer. The request streams through it.
+pub const SOCKET_TX_BYTES: usize = 128;
+
+/// Parse a `CONWAY_AUTHZ_TIMEOUT_MS` value, from [`MIN_TIMEOUT_MS`] to
+/// [`MAX_TIMEOUT_MS`].
+pub fn parse_timeout_ms(s: &str) -> Option<u64> {
+    s.parse()
+        .ok()
+        .filter(|ms| (MIN_TIMEOUT_MS..=MAX_TIMEOUT_MS).contains(ms))
+}
+
+/// Parse a `CONWAY_AUTHZ_DENY_TTL_SECS` value, up to
+/// [`MAX_DENY_TTL_SECS`]. `0` remembers nothing.
+pub fn parse_deny_ttl_secs(s: &str) -> Option<u64> {
+    s.parse().ok().filter(|&secs| secs <= MAX_DENY_TTL_SECS)
+}
+
+/// Parse a `CONWAY_AUTHZ_PATH` value: any path
+/// [`http_client::valid_path`] accepts. It may carry a query of its own;
+/// the credential is appended to it.
+pub fn parse_path(s: &str) -> Option<&str> {
+    http_client::valid_path(s).then_some(s)
+}
+
+/// Write the query for `read`. The NFC form is sent as `nfc=` when it
+/// differs from the fob form, so the server can match either. HTTP/1.0,
+/// so the answer comes back unchunked and the server closes after it.
+pub fn write_request<W: Write>(
+    out: &mut W,
+    path: &str,
+    host: &str,
+    read: CardRead,
+) -> core::fmt::Result {
+    let sep = if path.contains('?') { '&' } else { '?' };
+    write!(out, "GET {}{}fob={}", path, sep, read.fob)?;
+    if read.nfc != read.fob {
+        write!(out, "&nfc={}", read.nfc)?;
+    }
+    write!(
+        out,
+        " HTTP/1.0\r\n\
+         Host: {}\r\n\
+         Accept: text/plain\r\n\
+         \r\n",
+        host
+    )
+}
+
+/// What the online check concluded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The server allows the credential.
+    Allow,
+    /// The server denies it.
+    Deny,
+    /// No usable answer: timeout, connection failure, or a response that
+    /// is neither. Denied like [`Verdict::Deny`], but logged apart.
+    Unavailable,
+}
+
+impl Verdict {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Verdict::Allow => "allow",
+            Verdict::Deny => "deny",
+            Verdict::Unavailable => "unavailable",
+        }
+    }
+}
+
+/// Interpret a complete response: `200` with a body of `allow` or `deny`
+/// (any case, surrounding whitespace ignored). A `403` is a deny.
+/// Anything else, including a `404` from a misconfigured path or a
+/// truncated head, is [`Verdict::Unavailable`].
+///
+/// `raw` is taken mutably only so [`http_client::split_head`] can mask
+/// non-ASCII header bytes in place.
+pub fn parse_response(raw: &mut [u8]) -> Verdict {
+    let Some((head, body)) = http_client::split_head(raw) else {
+        return Verdict::Unavailable;
+    };
+    match http_client::parse_status_code(head) {
+        200 => match core::str::from_utf8(body).map(str::trim) {
+            Ok(b) if b.eq_ignore_ascii_case("allow") => Verdict::Allow,
+            Ok(b) if b.eq_ignore_ascii_case("deny") => Verdict::Deny,
+            _ => Verdict::Unavailable,
+        },
+        403 => Verdict::Deny,
+        _ => Verdict::Unavailable,
+    }
+}
+
+/// Credentials the server recently refused, each for `ttl_ms` from the
+/// refusal.
+#[derive(Clone, Copy, Debug)]
+pub struct NegativeCache(CredentialWindow<NEGATIVE_CACHE_SLOTS>);
+
+impl NegativeCache {
+    pub const fn new(ttl_ms: u64) -> Self {
+        Self(CredentialWindow::new(ttl_ms))
+    }
+
+    pub fn ttl_ms(&self) -> u64 {
+        self.0.span_ms()
+    }
+
+    /// Remember that `read` was refused at `now_ms`. A read already
+    /// remembered starts its TTL again; with every slot live, the one
+    /// closest to expiry makes room.
+    pub fn insert(&mut self, read: CardRead, now_ms: u64) {
+        self.0.insert(read, now_ms);
+    }
+
+    /// Whether `read` was refused within the TTL before `now_ms`, at
+    /// any reader: the server only ever hears the credential.
+    pub fn contains(&self, read: CardRead, now_ms: u64) -> bool {
+        self.0.contains(read, now_ms)
+    }
+}
@@ -0,0 +1,59 @@
+//! Exponential retry backoff.
+//!
+//! The push channel reconnects after `min_ms`, doubling on every
+//! consecutive failure up to `max_ms`, and starts over once a connection
+//! has been established. Time is passed in; see [`crate::clock`].
+
+use crate::clock;
+
+/// Retry scheduler with a doubling delay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Backoff {
+    min_ms: u64,
+    max_ms: u64,
+    delay_ms: u64,
+    /// `None` until the first [`Self::schedule`]: ready at once.
+    retry_at_ms: Option<u64>,
+}
+
+impl Backoff {
+    /// `min_ms` must be non-zero and at most `max_ms`.
+    pub const fn new(min_ms: u64, max_ms: u64) -> Self {
+        Self {
+            min_ms,
+            max_ms,
+            delay_ms: min_ms,
+            retry_at_ms: None,
+        }
+    }
+
+    /// Schedule the next attempt at `now_ms` plus the current delay, and
+    /// double the delay for the one after. Returns the deadline.
+    pub fn schedule(&mut self, now_ms: u64) -> u64 {
+        let retry_at_ms = clock::later(now_ms, self.delay_ms);
+        self.retry_at_ms = Some(retry_at_ms);
+        self.delay_ms = self.delay_ms.saturating_mul(2).min(self.max_ms);
+        retry_at_ms
+    }
+
+    /// An attempt succeeded: the next delay is `min_ms` again.
+    pub fn reset(&mut self) {
+        self.delay_ms = self.min_ms;
+    }
+
+    /// Delay the next [`Self::schedule`] will use.
+    pub fn delay_ms(&self) -> u64 {
+        self.delay_ms
+    }
+
+    /// Time from `now_ms` to the scheduled deadline, across a wrap of the
+    /// counter; zero once it has passed or if none is scheduled.
+    pub fn remaining_ms(&self, now_ms: u64) -> u64 {
+        self.retry_at_ms.map_or(0, |at| clock::elapsed(now_ms, at))
+    }
+
+    /// Whether the scheduled deadline has passed.
+    pub fn ready(&self, now_ms: u64) -> bool {
+        self.retry_at_ms.is_none_or(|at| !clock::before(now_ms, at))
+    }
+}
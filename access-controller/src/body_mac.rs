@@ -0,0 +1,64 @@
+//! HMAC-SHA256 check of `POST /api/fobs` response bodies.
+//!
+//! Sites that sync over plaintext HTTP and haven't set up
+//! [`crate::signing`] can share a secret with Conway instead. Conway then
+//! sends the HMAC-SHA256 of each `200 OK` body (after any chunked
+//! decoding) in the `X-Fob-HMAC` header, as 64 hex digits, and the
+//! controller checks it before parsing the list. A missing or wrong MAC
+//! fails the sync like a bad signature: nothing is cached or committed.
+//!
+//! Unlike a signature, a MAC proves only that the sender knows the
+//! secret, which every controller of the site holds too.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Response header carrying the MAC.
+pub const HEADER: &str = "x-fob-hmac";
+
+/// Length of the MAC in bytes.
+pub const MAC_LEN: usize = 32;
+
+/// Whether `mac_hex` is the HMAC-SHA256 of `body` under `key`.
+///
+/// `mac_hex` is the header value: 64 hex digits of either case, with
+/// surrounding whitespace ignored. Anything else is `false`. The
+/// comparison itself is constant-time.
+pub fn verify(key: &[u8], body: &[u8], mac_hex: &str) -> bool {
+    let Some(mac) = decode_hex(mac_hex.trim()) else {
+        return false;
+    };
+    let Ok(mut h) = Hmac::<Sha256>::new_from_slice(key) else {
+        return false;
+    };
+    h.update(body);
+    h.verify_slice(&mac).is_ok()
+}
+
+/// The HMAC-SHA256 of `body` under `key`, as sent in [`HEADER`]: 64
+/// lowercase hex digits.
+pub fn sign_hex(key: &[u8], body: &[u8]) -> heapless::String<{ MAC_LEN * 2 }> {
+    let mut h = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    h.update(body);
+    let mut out = heapless::String::new();
+    for b in h.finalize().into_bytes() {
+        for nibble in [b >> 4, b & 0xF] {
+            let _ = out.push(char::from_digit(u32::from(nibble), 16).unwrap_or('0'));
+        }
+    }
+    out
+}
+
+fn decode_hex(s: &str) -> Option<[u8; MAC_LEN]> {
+    let s = s.as_bytes();
+    if s.len() != MAC_LEN * 2 {
+        return None;
+    }
+    let mut out = [0u8; MAC_LEN];
+    for (i, pair) in s.chunks_exact(2).enumerate() {
+        let hi = (pair[0] as char).to_digit(16)?;
+        let lo = (pair[1] as char).to_digit(16)?;
+        out[i] = (hi << 4 | lo) as u8;
+    }
+    Some(out)
+}
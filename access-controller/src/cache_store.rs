@@ -0,0 +1,120 @@
+//! Persisted copy of the Conway fob cache (`FOBS` + `ETAG`).
+//!
+//! Without this, a reboot while Conway is unreachable leaves the device
+//! with an empty remote list until the next successful sync. The cache
+//! is written after every sync that replaces the list, and restored at
+//! boot.
+//!
+//! ## Layout
+//!
+//! Same ping-pong + monotonic-seq design as `settings.rs` and
+//! `fob_store.rs` ([`access_controller::storage`]), in the two `nvs`
+//! sectors after the settings slots (`FlashLayout::cache_slots`). Unlike
+//! those, a save leaves the other slot holding the previous generation.
+//! Records are sealed with the fobs key under a distinct domain tag, so
+//! a cache record can never be opened as a local-fob record or vice
+//! versa.
+//!
+//! The plaintext payload and the RAM-vs-flash reconcile decision live in
+//! [`access_controller::fob_cache`] so they can be tested on the host.
+//!
+//! Worst case record: 6 + 64 + 2 + 512·4 + 48 = 2168 B, inside one sector.
+
+use heapless::Vec as HVec;
+
+use crate::flash::EspFlash;
+use crate::{device_key, MAX_FOBS};
+use access_controller::crypto;
+use access_controller::etag::{HostEtag, MAX_ETAG_LEN};
+use access_controller::flash_layout::FLASH;
+use access_controller::fob_cache::{self, CacheMeta};
+use access_controller::storage::{Slot, Storage, StoreConfig};
+
+/// Ping-pong: sectors 2 and 3 of `nvs` (0 and 1 belong to `settings`).
+const SLOTS: [u32; 2] = FLASH.cache_slots();
+
+const MAGIC: u32 = 0x52_46_4F_42; // "RFOB"
+
+/// Plaintext payload upper bound.
+const MAX_PLAINTEXT: usize = 1 + 4 + 1 + MAX_ETAG_LEN + 2 + MAX_FOBS * 4;
+
+/// A cache record restored from flash.
+pub struct Cached {
+    pub seq: u64,
+    pub etag: HostEtag,
+    pub fobs: HVec<u32, MAX_FOBS>,
+}
+
+impl Cached {
+    pub fn meta(&self) -> CacheMeta {
+        CacheMeta::of(self.seq, &self.fobs)
+    }
+}
+
+fn store() -> Storage<EspFlash> {
+    Storage::new(
+        EspFlash::new(),
+        StoreConfig {
+            slots: SLOTS,
+            magic: MAGIC,
+            domain: crypto::DOMAIN_CACHE,
+            max_payload: MAX_PLAINTEXT,
+        },
+    )
+}
+
+/// Load the most recent valid cache record, or `None` if there is none
+/// (first boot, factory reset, unprovisioned device).
+///
+/// Logs a discrepancy when the newest header on flash belongs to a
+/// record that no longer opens: that is a save that was cut short, and
+/// the returned record is one generation behind what was last synced.
+pub fn load() -> Option<Cached> {
+    let key = device_key::fobs_key()?;
+    let loaded = store().load(key);
+    for (i, slot) in loaded.slots.iter().enumerate() {
+        if let Slot::Rejected { seq } = slot {
+            log::warn!("cache_store: slot {} (seq={}) failed to open", i, seq);
+        }
+    }
+    let fell_back = loaded.fell_back();
+    let record = loaded.into_record()?;
+    if let Some(h) = fell_back {
+        log::warn!(
+            "cache_store: newest record seq={} unreadable, falling back to seq={}",
+            h,
+            record.seq
+        );
+    }
+    let (etag, fobs) = fob_cache::decode::<MAX_FOBS>(&record.payload)?;
+    Some(Cached {
+        seq: record.seq,
+        etag,
+        fobs,
+    })
+}
+
+/// Persist the cache. Returns the seq of the written record. The other
+/// slot keeps the previous generation.
+pub fn save(etag: &HostEtag, fobs: &[u32]) -> Result<u64, &'static str> {
+    let Some(key) = device_key::fobs_key() else {
+        return Err("device not provisioned (eFuse BLOCK3 unset)");
+    };
+    let plaintext = fob_cache::encode(etag, fobs);
+    let saved = store().save(key, &plaintext)?;
+
+    log::debug!(
+        "cache_store: saved seq={} to slot {} ({} fobs)",
+        saved.seq,
+        saved.slot,
+        fobs.len()
+    );
+    Ok(saved.seq)
+}
+
+/// Wipe both slots. Works on unprovisioned devices too.
+pub fn erase() -> Result<(), &'static str> {
+    store().erase()?;
+    log::warn!("cache_store: wiped");
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Milliseconds-since-boot time source.
+//!
+//! The lib's state machines ([`crate::core::AccessCore`],
+//! [`crate::reader_watch::ReaderWatch`], [`crate::backoff::Backoff`])
+//! take `now_ms` as an argument rather than reading a clock. Firmware
+//! tasks read it from `main.rs`'s `BootClock` (`embassy_time::Instant`);
+//! host tests drive [`FakeClock`] instead.
+//!
+//! Calendar time is separate: [`WallClock`] only knows it once a time
+//! source has set it, and until then schedule checks fail open.
+//!
+//! A `u64` of milliseconds won't roll over in the life of the board, but
+//! the state machines don't lean on that: spans and deadlines go through
+//! [`elapsed`], [`before`] and [`later`], which treat the counter as
+//! wrapping. They are exact across the wrap for spans under half the
+//! counter's range, and work the same on a microsecond reading.
+
+use core::cell::Cell;
+
+/// Time from reading `since` to reading `now`, across a wrap of the
+/// counter. A `since` that is ahead of `now`, as when it was read after
+/// it, is no time at all rather than nearly the whole range.
+pub const fn elapsed(since: u64, now: u64) -> u64 {
+    let span = now.wrapping_sub(since);
+    if (span as i64) < 0 {
+        0
+    } else {
+        span
+    }
+}
+
+/// Whether `now` is still short of `deadline`, across a wrap of the
+/// counter.
+pub const fn before(now: u64, deadline: u64) -> bool {
+    (deadline.wrapping_sub(now) as i64) > 0
+}
+
+/// The deadline `span` after `now`, wrapping with the counter.
+pub const fn later(now: u64, span: u64) -> u64 {
+    now.wrapping_add(span)
+}
+
+/// Source of the current time, in milliseconds since boot.
+pub trait Clock {
+    fn now_ms(&self) -> u64;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now_ms(&self) -> u64 {
+        (**self).now_ms()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug, Default)]
+pub struct FakeClock {
+    now_ms: Cell<u64>,
+}
+
+impl FakeClock {
+    pub const fn new(now_ms: u64) -> Self {
+        Self {
+            now_ms: Cell::new(now_ms),
+        }
+    }
+
+    /// Move on `ms`, wrapping past `u64::MAX` as a hardware counter
+    /// would.
+    pub fn advance(&self, ms: u64) {
+        self.now_ms.set(self.now_ms.get().wrapping_add(ms));
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now_ms.set(now_ms);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.get()
+    }
+}
+
+/// Unix time, once a time source (SNTP, the Conway server) has said what
+/// it is. Stored as the Unix time at boot, so it keeps counting with the
+/// boot clock in between.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WallClock {
+    unix_at_boot_ms: Option<u64>,
+}
+
+impl WallClock {
+    /// Not yet synced.
+    pub const fn new() -> Self {
+        Self {
+            unix_at_boot_ms: None,
+        }
+    }
+
+    /// It is `unix_ms` at boot-clock `now_ms`.
+    pub fn set(&mut self, unix_ms: u64, now_ms: u64) {
+        self.unix_at_boot_ms = Some(unix_ms.saturating_sub(now_ms));
+    }
+
+    /// Whether calendar time is known.
+    pub fn synced(&self) -> bool {
+        self.unix_at_boot_ms.is_some()
+    }
+
+    /// Unix time at boot-clock `now_ms`, if known.
+    pub fn now_unix_ms(&self, now_ms: u64) -> Option<u64> {
+        self.unix_at_boot_ms
+            .map(|at_boot| at_boot.saturating_add(now_ms))
+    }
+
+    /// Whether a time-restricted credential may be used at `now_ms`.
+    /// `in_window` is asked with the Unix time once it is known. Before
+    /// that it is not consulted and the answer is `true`: the plain fob
+    /// check decides alone, so a controller that has just booted does
+    /// not lock out scheduled users.
+    pub fn permits(&self, now_ms: u64, in_window: impl FnOnce(u64) -> bool) -> bool {
+        match self.now_unix_ms(now_ms) {
+            Some(unix_ms) => in_window(unix_ms),
+            None => true,
+        }
+    }
+}
@@ -13,33 +13,107 @@
 //! changing observable runtime behavior in the firmware.
 //!
 //! The firmware adapter in `main.rs` is responsible for:
-//! - selecting on `WIEGAND_CHANNEL` / `SYNC_COMPLETE` / `WATCHDOG_FEED` and
-//!   mapping each to the corresponding `Input` variant,
-//! - calling `embassy_time::Instant::now().as_millis()` and passing it in,
+//! - selecting on `WIEGAND_CHANNEL` / `SYNC_COMPLETE` / `AUTHZ_ANSWER` /
+//!   `WATCHDOG_FEED` and mapping each to the corresponding `Input` variant,
+//! - reading the time from `BootClock` (see [`crate::clock`]) and passing
+//!   it in,
 //! - locking the `FOBS` mutex and passing a slice,
 //! - dispatching each returned `Effect` to the corresponding `Signal` or
 //!   the global `EVENT_BUFFER`.
 
 use heapless::Vec as HVec;
 
+use crate::authz::{NegativeCache, Verdict, ANSWER_GRACE_MS, DEFAULT_DENY_TTL_SECS};
+use crate::clock;
+use crate::decode::{ByteOrder, CredentialFormat, WiegandRead};
 use crate::events::AccessEvent;
+use crate::passback::RecentGrants;
 
 /// Window during which a sync completion can retroactively grant a
 /// previously-denied credential. Matches `main.rs` (10 seconds).
 pub const RECHECK_DEADLINE_MS: u64 = 10_000;
 
 /// Number of effects emitted by a single `step()` call. The current
-/// implementation emits at most 3 (Record + Feedback + OpenDoor on grant;
-/// Record + Feedback + RequestSync on denial); 4 leaves headroom.
-pub const MAX_EFFECTS_PER_STEP: usize = 4;
+/// implementation emits at most 8: each reader's expired held denial and
+/// unanswered online check's Record, then Record + FailOpenGrant +
+/// Feedback + OpenDoor on a fail-open grant (one fewer on any other grant
+/// or denial). A sync instead resolves each reader's recheck with at most
+/// Record + Feedback + OpenDoor, where an expired recheck has none.
+pub const MAX_EFFECTS_PER_STEP: usize = 8;
 
-/// A credential read off the Wiegand reader. Already decoded into both the
-/// H10301 fob form and the byte-swapped NFC UID form so the core does not
-/// need to know about Wiegand framing.
+/// Readers one controller can serve at once.
+pub const MAX_READERS: usize = 2;
+
+/// Which reader a credential was presented at. Each has its own denial
+/// backoff and online check, so a card being tried over and over at one
+/// door doesn't lock out the other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Reader {
+    /// The Wiegand reader on D0/D1.
+    #[default]
+    Wiegand,
+    /// The OSDP reader on RS-485 (`--features osdp`).
+    Osdp,
+}
+
+impl Reader {
+    pub const ALL: [Reader; MAX_READERS] = [Reader::Wiegand, Reader::Osdp];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reader::Wiegand => "wiegand",
+            Reader::Osdp => "osdp",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// A credential read off one of the readers. Already decoded into both
+/// the H10301 fob form and the byte-swapped NFC UID form (or into the
+/// one form Conway hinted; see [`CardRead::from_read`]) so the core does
+/// not need to know about Wiegand framing.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CardRead {
     pub fob: u32,
     pub nfc: u32,
+    pub reader: Reader,
+}
+
+impl CardRead {
+    /// Decode `read` for matching. With a `hint` from Conway both forms
+    /// are the hinted credential, so nothing else can match; without
+    /// one, the fob and 4-byte NFC forms are both tried. `order` is the
+    /// reader's UID byte order.
+    pub fn from_read(read: &WiegandRead, hint: Option<CredentialFormat>, order: ByteOrder) -> Self {
+        match hint {
+            Some(format) => {
+                let credential = format.extract(read, order);
+                Self {
+                    fob: credential,
+                    nfc: credential,
+                    reader: Reader::Wiegand,
+                }
+            }
+            None => Self {
+                fob: read.to_fob(),
+                nfc: read.nfc_uid(order),
+                reader: Reader::Wiegand,
+            },
+        }
+    }
+
+    /// The same read, presented at `reader`.
+    pub const fn at(self, reader: Reader) -> Self {
+        Self { reader, ..self }
+    }
+
+    /// Whether `other` is the same credential, at whichever reader.
+    pub fn same_credential(&self, other: &CardRead) -> bool {
+        self.fob == other.fob && self.nfc == other.nfc
+    }
 }
 
 /// Inputs that drive the access-control state machine.
@@ -54,6 +128,8 @@ pub enum Input {
     /// The 10-second tick that proves `access_task` is responsive; mapped
     /// to a hardware watchdog feed by the firmware adapter.
     WatchdogFeed,
+    /// The answer to an [`Effect::QueryAuthz`] for `read`.
+    AuthzAnswer { read: CardRead, verdict: Verdict },
 }
 
 /// The decision a `Card` step produced (used to drive reader LED/beeper).
@@ -63,11 +139,221 @@ pub enum Outcome {
     Denied,
 }
 
+/// Which decoded form of a credential is tried first against each list.
+///
+/// Every read is decoded both as an H10301 fob and as a byte-swapped NFC
+/// UID, and either may match. The order only matters when both do (or
+/// could collide): the form tried first is the one that grants and the
+/// one recorded in the emitted [`AccessEvent`]. Local-before-remote
+/// precedence is unaffected; the order applies within each list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchOrder {
+    /// Fob, then NFC. Original behavior.
+    #[default]
+    FobFirst,
+    /// NFC, then fob. For installs that primarily issue NFC phones/cards.
+    NfcFirst,
+}
+
+impl MatchOrder {
+    /// Parse the `CONWAY_MATCH_ORDER` build knob: `"fob"` or `"nfc"`
+    /// (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.eq_ignore_ascii_case("fob") {
+            Some(Self::FobFirst)
+        } else if s.eq_ignore_ascii_case("nfc") {
+            Some(Self::NfcFirst)
+        } else {
+            None
+        }
+    }
+
+    /// Return the credential form in `list` that matches first, if any.
+    fn first_match(self, list: &[u32], fob: u32, nfc: u32) -> Option<u32> {
+        let (a, b) = match self {
+            Self::FobFirst => (fob, nfc),
+            Self::NfcFirst => (nfc, fob),
+        };
+        if list.contains(&a) {
+            Some(a)
+        } else if list.contains(&b) {
+            Some(b)
+        } else {
+            None
+        }
+    }
+}
+
+/// Longest configurable fail-open window: one day. Fail-open is meant to
+/// bridge an outage, not to replace the list.
+pub const MAX_FAIL_OPEN_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// What to do with a credential on neither list while no Conway fob list
+/// has been loaded at all (first boot with an empty flash cache and
+/// Conway unreachable).
+///
+/// The local list always grants regardless, so it doubles as the admin
+/// list that keeps working during an outage under either policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FailPolicy {
+    /// Deny. Original behavior.
+    #[default]
+    Closed,
+    /// Grant anyone during the first `window_ms` after boot, emitting
+    /// [`Effect::FailOpenGrant`] for each such grant. Fails closed once
+    /// the window has passed, or as soon as any list has been loaded.
+    OpenFor { window_ms: u64 },
+}
+
+impl FailPolicy {
+    /// Parse the `CONWAY_FAIL_OPEN_SECS` build knob: seconds of
+    /// fail-open after boot, `0` for fail-closed, at most
+    /// [`MAX_FAIL_OPEN_MS`].
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.parse::<u64>().ok()?.checked_mul(1000)? {
+            0 => Some(Self::Closed),
+            window_ms if window_ms <= MAX_FAIL_OPEN_MS => Some(Self::OpenFor { window_ms }),
+            _ => None,
+        }
+    }
+
+    /// Whether a credential on neither list is granted anyway.
+    fn grants(self, now_ms: u64, conway_enabled: bool, list_loaded: bool) -> bool {
+        match self {
+            Self::Closed => false,
+            Self::OpenFor { window_ms } => conway_enabled && !list_loaded && now_ms < window_ms,
+        }
+    }
+}
+
+/// Most doublings [`DenyBackoff::parse_steps`] accepts.
+pub const MAX_BACKOFF_STEPS: u8 = 16;
+
+/// How long card reads are ignored after consecutive denials.
+///
+/// After `n` denials the delay is `base_ms * multiplier^min(n, steps)`,
+/// clamped to `max_ms`. The default is the original 2, 4, then 8 s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DenyBackoff {
+    pub base_ms: u64,
+    /// Growth per denial; 1 gives a flat delay.
+    pub multiplier: u64,
+    /// Denials after which the delay stops growing.
+    pub steps: u8,
+    /// Absolute cap, whatever the other fields give.
+    pub max_ms: u64,
+}
+
+impl DenyBackoff {
+    pub const DEFAULT: Self = Self {
+        base_ms: 1_000,
+        multiplier: 2,
+        steps: 3,
+        max_ms: 60_000,
+    };
+
+    /// Parse the `CONWAY_DENY_BACKOFF_STEPS` build knob: denials before
+    /// the delay stops doubling, at most [`MAX_BACKOFF_STEPS`].
+    pub fn parse_steps(s: &str) -> Option<u8> {
+        s.parse::<u8>().ok().filter(|&n| n <= MAX_BACKOFF_STEPS)
+    }
+
+    /// Delay after `failed_attempts` consecutive denials.
+    pub fn delay_ms(&self, failed_attempts: u8) -> u64 {
+        let exp = u32::from(failed_attempts.min(self.steps));
+        self.base_ms
+            .saturating_mul(self.multiplier.saturating_pow(exp))
+            .min(self.max_ms)
+    }
+}
+
+impl Default for DenyBackoff {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Which denials are recorded for Conway.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DenialReport {
+    /// Every denial, as it happens. Original behavior.
+    #[default]
+    All,
+    /// Only denials the on-demand sync did not resolve. A denial that
+    /// arms a recheck is held back until the recheck ends: a grant after
+    /// the sync supersedes it, and only the grant is recorded; a failed or
+    /// expired recheck records the denial then, stamped with the original
+    /// credential. Denials that arm no recheck are recorded at once.
+    Unresolved,
+}
+
+impl DenialReport {
+    /// Parse the `CONWAY_REPORT_DENIALS` build knob: `"all"` or
+    /// `"unresolved"` (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.eq_ignore_ascii_case("all") {
+            Some(Self::All)
+        } else if s.eq_ignore_ascii_case("unresolved") {
+            Some(Self::Unresolved)
+        } else {
+            None
+        }
+    }
+}
+
+/// Facility codes a site issues fobs under. An unmatched read from any
+/// other facility is a foreign card: it is denied on the spot, with no
+/// on-demand sync and no recheck window, since no sync would admit it.
+///
+/// The facility is the H10301 one in [`CardRead::fob`]. NFC credentials
+/// have no facility, so at a site that lists them a newly added NFC card
+/// may wait for the next regular sync instead. Empty (the default) treats
+/// every facility as known.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KnownFacilities {
+    /// One bit per 8-bit facility code.
+    bits: [u32; 8],
+}
+
+impl KnownFacilities {
+    pub const NONE: Self = Self { bits: [0; 8] };
+
+    /// Parse the `CONWAY_KNOWN_FACILITIES` build knob: comma-separated
+    /// facility codes, 0-255.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut known = Self::NONE;
+        for code in s.split(',') {
+            known.insert(code.trim().parse::<u8>().ok()?);
+        }
+        Some(known)
+    }
+
+    pub fn insert(&mut self, facility: u8) {
+        self.bits[usize::from(facility / 32)] |= 1 << (facility % 32);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == [0; 8]
+    }
+
+    /// Whether `fob` comes from a facility outside a configured set.
+    pub fn is_foreign(&self, fob: u32) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        match u8::try_from(fob / 100_000) {
+            Ok(facility) => self.bits[usize::from(facility / 32)] & (1 << (facility % 32)) == 0,
+            Err(_) => true,
+        }
+    }
+}
+
 /// Side effects emitted by `step()`. The firmware adapter is the sole
 /// consumer; tests inspect them directly.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Effect {
-    /// Pulse the door relay (200ms in firmware).
+    /// Pulse the door relay (see `door::Unlock`; 200ms by default, longer
+    /// for credentials Conway tagged, per `door::Holds`).
     OpenDoor,
     /// Drive the reader LED/beeper.
     Feedback(Outcome),
@@ -75,22 +361,73 @@ pub enum Effect {
     Record(AccessEvent),
     /// Ask the sync task to attempt an on-demand round-trip with Conway.
     RequestSync,
-    /// Feed the hardware watchdog.
+    /// The accompanying grant was made only because of
+    /// [`FailPolicy::OpenFor`]; the firmware logs it loudly.
+    FailOpenGrant,
+    /// Feed the hardware watchdog. Always the first effect of its step.
     FeedWatchdog,
+    /// Ask Conway whether to let in a credential neither list has (see
+    /// [`crate::authz`]) and answer with [`Input::AuthzAnswer`].
+    QueryAuthz(CardRead),
+}
+
+/// Decision state kept apart for each [`Reader`].
+#[derive(Clone, Copy, Debug, Default)]
+struct ReaderState {
+    /// Card reads received before this timestamp are silently dropped.
+    backoff_until: Option<u64>,
+    /// Number of consecutive denials. Drives exponential backoff per
+    /// `deny_backoff`. Reset to 0 on any grant.
+    failed_attempts: u8,
+    /// `(read, deadline_ms)` — a read waiting on [`Input::AuthzAnswer`]
+    /// before it is denied.
+    pending_query: Option<(CardRead, u64)>,
+    /// `(fob, nfc, deadline_ms)` — a previously denied credential whose
+    /// authorization will be re-checked when the next sync completes.
+    pending_recheck: Option<(u32, u32, u64)>,
+    /// Under [`DenialReport::Unresolved`], the denial that armed
+    /// `pending_recheck`, not yet recorded.
+    held_denial: Option<AccessEvent>,
+}
+
+impl ReaderState {
+    const NEW: Self = Self {
+        backoff_until: None,
+        failed_attempts: 0,
+        pending_query: None,
+        pending_recheck: None,
+        held_denial: None,
+    };
 }
 
 /// Pure decision state for the access controller. Mirrors the locals
 /// inside `access_task`.
 #[derive(Clone, Debug)]
 pub struct AccessCore {
-    /// `(fob, nfc, deadline_ms)` — a previously denied credential whose
-    /// authorization will be re-checked when the next sync completes.
-    pending_recheck: Option<(u32, u32, u64)>,
-    /// Card reads received before this timestamp are silently dropped.
-    backoff_until: u64,
-    /// Number of consecutive denials. Drives exponential backoff (1, 2, 4,
-    /// then 8s thereafter). Reset to 0 on any grant.
-    failed_attempts: u8,
+    /// Backoff, recheck and online check, per reader.
+    readers: [ReaderState; MAX_READERS],
+    /// Fob-vs-NFC matching priority. Fixed for the lifetime of the core.
+    match_order: MatchOrder,
+    /// Behavior while no Conway list is loaded. Fixed for the lifetime of
+    /// the core.
+    fail_policy: FailPolicy,
+    /// Delay curve for repeated denials. Fixed for the lifetime of the
+    /// core.
+    deny_backoff: DenyBackoff,
+    /// Facilities whose unmatched reads are worth a sync. Fixed for the
+    /// lifetime of the core.
+    known_facilities: KnownFacilities,
+    /// Which denials are recorded. Fixed for the lifetime of the core.
+    denial_report: DenialReport,
+    /// How long an online check may take, if misses are checked online
+    /// at all. Fixed for the lifetime of the core.
+    online_check_ms: Option<u64>,
+    /// Reads the online check recently refused; denied without asking
+    /// again.
+    refused: NegativeCache,
+    /// Credentials granted within the passback window; their reads are
+    /// dropped until it ends.
+    recent_grants: RecentGrants,
 }
 
 impl Default for AccessCore {
@@ -101,26 +438,160 @@ impl Default for AccessCore {
 
 impl AccessCore {
     pub const fn new() -> Self {
+        Self::with_match_order(MatchOrder::FobFirst)
+    }
+
+    pub const fn with_match_order(match_order: MatchOrder) -> Self {
         Self {
-            pending_recheck: None,
-            backoff_until: 0,
-            failed_attempts: 0,
+            readers: [ReaderState::NEW; MAX_READERS],
+            match_order,
+            fail_policy: FailPolicy::Closed,
+            deny_backoff: DenyBackoff::DEFAULT,
+            known_facilities: KnownFacilities::NONE,
+            denial_report: DenialReport::All,
+            online_check_ms: None,
+            refused: NegativeCache::new(DEFAULT_DENY_TTL_SECS * 1000),
+            recent_grants: RecentGrants::new(0),
         }
     }
 
-    /// Read-only access to the pending recheck window, for tests.
+    /// Replace the fail policy (default [`FailPolicy::Closed`]).
+    pub const fn with_fail_policy(mut self, fail_policy: FailPolicy) -> Self {
+        self.fail_policy = fail_policy;
+        self
+    }
+
+    /// Replace the denial backoff (default [`DenyBackoff::DEFAULT`]).
+    pub const fn with_deny_backoff(mut self, deny_backoff: DenyBackoff) -> Self {
+        self.deny_backoff = deny_backoff;
+        self
+    }
+
+    /// Replace the known facilities (default: all facilities known).
+    pub const fn with_known_facilities(mut self, known_facilities: KnownFacilities) -> Self {
+        self.known_facilities = known_facilities;
+        self
+    }
+
+    /// Replace the denial reporting (default [`DenialReport::All`]).
+    pub const fn with_denial_report(mut self, denial_report: DenialReport) -> Self {
+        self.denial_report = denial_report;
+        self
+    }
+
+    /// Check misses online, allowing each query `timeout_ms` (default:
+    /// no online check).
+    pub const fn with_online_check(mut self, timeout_ms: u64) -> Self {
+        self.online_check_ms = Some(timeout_ms);
+        self
+    }
+
+    /// Remember an online `deny` for `ttl_ms` (default
+    /// [`DEFAULT_DENY_TTL_SECS`]; 0 asks every time).
+    pub const fn with_refusal_ttl(mut self, ttl_ms: u64) -> Self {
+        self.refused = NegativeCache::new(ttl_ms);
+        self
+    }
+
+    /// Ignore a credential for `window_ms` after each grant (default 0:
+    /// never); see [`crate::passback`].
+    pub const fn with_passback_window(mut self, window_ms: u64) -> Self {
+        self.recent_grants = RecentGrants::new(window_ms);
+        self
+    }
+
+    /// Configured fob-vs-NFC matching priority.
+    pub fn match_order(&self) -> MatchOrder {
+        self.match_order
+    }
+
+    /// Configured fail policy.
+    pub fn fail_policy(&self) -> FailPolicy {
+        self.fail_policy
+    }
+
+    /// Configured denial backoff.
+    pub fn deny_backoff(&self) -> DenyBackoff {
+        self.deny_backoff
+    }
+
+    /// Configured known facilities.
+    pub fn known_facilities(&self) -> KnownFacilities {
+        self.known_facilities
+    }
+
+    /// Configured denial reporting.
+    pub fn denial_report(&self) -> DenialReport {
+        self.denial_report
+    }
+
+    /// Configured online check timeout, if misses are checked online.
+    pub fn online_check_ms(&self) -> Option<u64> {
+        self.online_check_ms
+    }
+
+    /// Read-only access to the recently refused reads, for tests.
+    pub fn refused(&self) -> &NegativeCache {
+        &self.refused
+    }
+
+    /// Read-only access to the credentials within their passback window,
+    /// for tests.
+    pub fn recent_grants(&self) -> &RecentGrants {
+        &self.recent_grants
+    }
+
+    /// Read-only access to the read awaiting an online answer at
+    /// `reader`, for tests.
+    pub fn pending_query(&self, reader: Reader) -> Option<(CardRead, u64)> {
+        self.readers[reader.index()].pending_query
+    }
+
+    /// Read-only access to the denial held for the Wiegand reader's
+    /// pending recheck, for tests.
+    pub fn held_denial(&self) -> Option<AccessEvent> {
+        self.held_denial_at(Reader::Wiegand)
+    }
+
+    /// Read-only access to the Wiegand reader's pending recheck window,
+    /// for tests.
     pub fn pending_recheck(&self) -> Option<(u32, u32, u64)> {
-        self.pending_recheck
+        self.pending_recheck_at(Reader::Wiegand)
+    }
+
+    /// Read-only access to the denial held for `reader`'s pending
+    /// recheck, for tests.
+    pub fn held_denial_at(&self, reader: Reader) -> Option<AccessEvent> {
+        self.readers[reader.index()].held_denial
     }
 
-    /// Read-only access to the backoff deadline, for tests.
+    /// Read-only access to `reader`'s pending recheck window, for tests.
+    pub fn pending_recheck_at(&self, reader: Reader) -> Option<(u32, u32, u64)> {
+        self.readers[reader.index()].pending_recheck
+    }
+
+    /// Read-only access to the Wiegand reader's backoff deadline, for
+    /// tests.
     pub fn backoff_until(&self) -> u64 {
-        self.backoff_until
+        self.backoff_until_at(Reader::Wiegand)
     }
 
-    /// Read-only access to the consecutive-denial counter, for tests.
+    /// Read-only access to the Wiegand reader's consecutive-denial
+    /// counter, for tests.
     pub fn failed_attempts(&self) -> u8 {
-        self.failed_attempts
+        self.failed_attempts_at(Reader::Wiegand)
+    }
+
+    /// Read-only access to `reader`'s backoff deadline, for tests; 0
+    /// when it has none.
+    pub fn backoff_until_at(&self, reader: Reader) -> u64 {
+        self.readers[reader.index()].backoff_until.unwrap_or(0)
+    }
+
+    /// Read-only access to `reader`'s consecutive-denial counter, for
+    /// tests.
+    pub fn failed_attempts_at(&self, reader: Reader) -> u8 {
+        self.readers[reader.index()].failed_attempts
     }
 
     /// Step the state machine.
@@ -134,6 +605,11 @@ impl AccessCore {
     /// - `conway_enabled`: whether a Conway host is configured. When
     ///   `false`, denials apply backoff immediately (no `RequestSync`, no
     ///   recheck window) since there is no remote authority to consult.
+    ///   Denials of a foreign card (see [`KnownFacilities`]) do the same.
+    ///   Nor is either checked online.
+    /// - `list_loaded`: whether a Conway list has been loaded since boot
+    ///   (from flash or a sync), even an empty one. Only consulted by
+    ///   [`FailPolicy::OpenFor`].
     /// - `input`: the event being delivered.
     ///
     /// Returns the ordered list of effects the firmware adapter must apply.
@@ -143,28 +619,69 @@ impl AccessCore {
         local_fobs: &[u32],
         remote_fobs: &[u32],
         conway_enabled: bool,
+        list_loaded: bool,
         input: Input,
     ) -> HVec<Effect, MAX_EFFECTS_PER_STEP> {
         let mut out: HVec<Effect, MAX_EFFECTS_PER_STEP> = HVec::new();
 
-        let contains = |slice: &[u32], v: u32| slice.contains(&v);
+        // Local list wins. Only consult the remote cache on a local miss;
+        // local can grant but cannot revoke remote. Returns the credential
+        // form that granted, per `match_order`.
+        let order = self.match_order;
+        let matched = |fob: u32, nfc: u32| {
+            order
+                .first_match(local_fobs, fob, nfc)
+                .or_else(|| order.first_match(remote_fobs, fob, nfc))
+        };
 
-        match input {
-            Input::WatchdogFeed => {
-                let _ = out.push(Effect::FeedWatchdog);
+        // The feed comes first, so the firmware feeds before it waits on
+        // anything to dispatch the records below.
+        if matches!(input, Input::WatchdogFeed) {
+            let _ = out.push(Effect::FeedWatchdog);
+        }
+
+        for state in &mut self.readers {
+            // A held denial whose recheck has run out is final: record it
+            // ahead of whatever this step does.
+            if let Some((_, _, deadline)) = state.pending_recheck {
+                if clock::before(deadline, now_ms) {
+                    if let Some(event) = state.held_denial.take() {
+                        let _ = out.push(Effect::Record(event));
+                    }
+                }
+            }
+
+            // So is a read whose online check never answered. The reader
+            // has long since moved on, so there is no feedback.
+            if let Some((read, deadline)) = state.pending_query {
+                if clock::before(deadline, now_ms) {
+                    state.pending_query = None;
+                    let _ = out.push(Effect::Record(AccessEvent {
+                        fob: read.fob,
+                        allowed: false,
+                    }));
+                }
             }
+        }
+
+        match input {
+            // Fed above.
+            Input::WatchdogFeed => {}
 
             Input::SyncComplete => {
-                if let Some((fob, nfc, deadline)) = self.pending_recheck.take() {
-                    if now_ms > deadline {
+                // Each reader's recheck is resolved on its own, so a
+                // denial at one never costs the other its retroactive
+                // grant.
+                for reader in Reader::ALL {
+                    let state = &mut self.readers[reader.index()];
+                    let Some((fob, nfc, deadline)) = state.pending_recheck.take() else {
+                        continue;
+                    };
+                    if clock::before(deadline, now_ms) {
                         // Recheck expired; do nothing.
-                        return out;
+                        continue;
                     }
-                    let fob_ok = contains(local_fobs, fob) || contains(remote_fobs, fob);
-                    let nfc_ok = !fob_ok
-                        && (contains(local_fobs, nfc) || contains(remote_fobs, nfc));
-                    let allowed = fob_ok || nfc_ok;
-                    if allowed {
+                    if let Some(credential) = matched(fob, nfc) {
                         // Defensively clear both failed_attempts and
                         // backoff_until on a grant-after-sync. The state
                         // machine currently can't reach SyncComplete-grant
@@ -173,14 +690,17 @@ impl AccessCore {
                         // required to re-arm it), but keeping the two
                         // counters in lockstep avoids surprises if that
                         // invariant ever weakens.
-                        self.failed_attempts = 0;
-                        self.backoff_until = 0;
+                        state.failed_attempts = 0;
+                        state.backoff_until = None;
+                        // The grant supersedes a held denial.
+                        state.held_denial = None;
+                        self.recent_grants
+                            .insert(CardRead { fob, nfc, reader }, now_ms);
                         // Emit an audit Record for the retroactive grant.
                         // Without this, Conway's log only ever sees the
                         // original deny event from the Card step, while
                         // the door physically opened — the exact signature
                         // of a credential-replay exploit, but caused by us.
-                        let credential = if fob_ok { fob } else { nfc };
                         let _ = out.push(Effect::Record(AccessEvent {
                             fob: credential,
                             allowed: true,
@@ -188,64 +708,155 @@ impl AccessCore {
                         let _ = out.push(Effect::Feedback(Outcome::Granted));
                         let _ = out.push(Effect::OpenDoor);
                     } else {
-                        self.failed_attempts = self.failed_attempts.saturating_add(1);
-                        let delay_ms = (1u64 << self.failed_attempts.min(3)) * 1000;
-                        self.backoff_until = now_ms + delay_ms;
+                        if let Some(event) = state.held_denial.take() {
+                            let _ = out.push(Effect::Record(event));
+                        }
+                        self.back_off(reader, now_ms);
                         let _ = out.push(Effect::Feedback(Outcome::Denied));
                     }
                 }
             }
 
             Input::Card(read) => {
-                if now_ms < self.backoff_until {
+                // Backoff and the online check are per reader: a card
+                // tried over and over at one door leaves the other alone.
+                let state = &mut self.readers[read.reader.index()];
+                if state
+                    .backoff_until
+                    .is_some_and(|until| clock::before(now_ms, until))
+                {
                     // Card ignored during backoff window; no effects.
                     return out;
                 }
+                if state.pending_query.is_some() {
+                    // One online check at a time per reader; reads while
+                    // it runs are ignored like reads during backoff, so
+                    // repeated swipes can't queue up queries.
+                    return out;
+                }
+                if self.recent_grants.contains(read, now_ms) {
+                    // Granted moments ago: most likely passed back out
+                    // for a second person. Ignored like a read during
+                    // backoff.
+                    return out;
+                }
 
                 let fob = read.fob;
                 let nfc = read.nfc;
 
-                // Local list wins. Only consult the remote cache on a
-                // local miss; local can grant but cannot revoke remote.
-                let local_fob_ok = contains(local_fobs, fob);
-                let local_nfc_ok = !local_fob_ok && contains(local_fobs, nfc);
-                let remote_fob_ok = !local_fob_ok && !local_nfc_ok && contains(remote_fobs, fob);
-                let remote_nfc_ok = !local_fob_ok
-                    && !local_nfc_ok
-                    && !remote_fob_ok
-                    && contains(remote_fobs, nfc);
-                let fob_ok = local_fob_ok || remote_fob_ok;
-                let nfc_ok = local_nfc_ok || remote_nfc_ok;
-                let allowed = fob_ok || nfc_ok;
-
-                if allowed {
-                    self.failed_attempts = 0;
-                    let credential = if fob_ok { fob } else { nfc };
+                if let Some(credential) = matched(fob, nfc) {
+                    state.failed_attempts = 0;
+                    self.recent_grants.insert(read, now_ms);
                     let _ = out.push(Effect::Record(AccessEvent {
                         fob: credential,
                         allowed: true,
                     }));
                     let _ = out.push(Effect::Feedback(Outcome::Granted));
                     let _ = out.push(Effect::OpenDoor);
+                } else if self.fail_policy.grants(now_ms, conway_enabled, list_loaded) {
+                    // No list to check against yet; the site chose to let
+                    // people in rather than lock the building. Recorded as
+                    // allowed so Conway's log matches the door.
+                    state.failed_attempts = 0;
+                    self.recent_grants.insert(read, now_ms);
+                    let _ = out.push(Effect::Record(AccessEvent { fob, allowed: true }));
+                    let _ = out.push(Effect::FailOpenGrant);
+                    let _ = out.push(Effect::Feedback(Outcome::Granted));
+                    let _ = out.push(Effect::OpenDoor);
+                } else if let Some(timeout_ms) = self
+                    .online_check_ms
+                    .filter(|_| conway_enabled && !self.known_facilities.is_foreign(fob))
+                    .filter(|_| !self.refused.contains(read, now_ms))
+                {
+                    // Too many members to cache: ask before denying.
+                    self.readers[read.reader.index()].pending_query =
+                        Some((read, clock::later(now_ms, timeout_ms + ANSWER_GRACE_MS)));
+                    let _ = out.push(Effect::QueryAuthz(read));
                 } else {
-                    let _ = out.push(Effect::Record(AccessEvent { fob, allowed: false }));
-                    let _ = out.push(Effect::Feedback(Outcome::Denied));
-                    if conway_enabled {
-                        // Ask the sync task to refresh; arm recheck window
-                        // so a freshly-synced fob can still get in.
-                        let _ = out.push(Effect::RequestSync);
-                        self.pending_recheck = Some((fob, nfc, now_ms + RECHECK_DEADLINE_MS));
-                    } else {
-                        // Standalone: no remote authority will ever grant,
-                        // so apply backoff immediately to throttle bruteforce.
-                        self.failed_attempts = self.failed_attempts.saturating_add(1);
-                        let delay_ms = (1u64 << self.failed_attempts.min(3)) * 1000;
-                        self.backoff_until = now_ms + delay_ms;
+                    self.deny(now_ms, read, conway_enabled, &mut out);
+                }
+            }
+
+            Input::AuthzAnswer { read, verdict } => {
+                // A late answer, or one for another read, changes nothing.
+                let state = &mut self.readers[read.reader.index()];
+                match state.pending_query {
+                    Some((pending, _)) if pending == read => state.pending_query = None,
+                    _ => return out,
+                }
+                if verdict == Verdict::Allow {
+                    state.failed_attempts = 0;
+                    self.recent_grants.insert(read, now_ms);
+                    let credential = match order {
+                        MatchOrder::FobFirst => read.fob,
+                        MatchOrder::NfcFirst => read.nfc,
+                    };
+                    let _ = out.push(Effect::Record(AccessEvent {
+                        fob: credential,
+                        allowed: true,
+                    }));
+                    let _ = out.push(Effect::Feedback(Outcome::Granted));
+                    let _ = out.push(Effect::OpenDoor);
+                } else {
+                    // Only a real refusal is remembered: a server that
+                    // didn't answer may let the card in next time.
+                    if verdict == Verdict::Deny {
+                        self.refused.insert(read, now_ms);
                     }
+                    self.deny(now_ms, read, conway_enabled, &mut out);
                 }
             }
         }
 
         out
     }
+
+    /// Deny `read`: record it (or hold it for the recheck), signal the
+    /// reader, and either ask for a sync or back off.
+    fn deny(
+        &mut self,
+        now_ms: u64,
+        read: CardRead,
+        conway_enabled: bool,
+        out: &mut HVec<Effect, MAX_EFFECTS_PER_STEP>,
+    ) {
+        let CardRead { fob, nfc, reader } = read;
+        let denial = AccessEvent {
+            fob,
+            allowed: false,
+        };
+        let rechecks = conway_enabled && !self.known_facilities.is_foreign(fob);
+        let state = &mut self.readers[reader.index()];
+        if rechecks {
+            // This recheck replaces any pending one at the same reader,
+            // so a denial held for that one is final.
+            if let Some(event) = state.held_denial.take() {
+                let _ = out.push(Effect::Record(event));
+            }
+        }
+        if rechecks && self.denial_report == DenialReport::Unresolved {
+            state.held_denial = Some(denial);
+        } else {
+            let _ = out.push(Effect::Record(denial));
+        }
+        let _ = out.push(Effect::Feedback(Outcome::Denied));
+        if rechecks {
+            // Ask the sync task to refresh; arm recheck window so a
+            // freshly-synced fob can still get in.
+            let _ = out.push(Effect::RequestSync);
+            state.pending_recheck = Some((fob, nfc, clock::later(now_ms, RECHECK_DEADLINE_MS)));
+        } else {
+            // Standalone, or a foreign card: no remote authority will
+            // grant, so apply backoff immediately to throttle bruteforce.
+            self.back_off(reader, now_ms);
+        }
+    }
+
+    /// Count a denial at `reader` and start its backoff.
+    fn back_off(&mut self, reader: Reader, now_ms: u64) {
+        let state = &mut self.readers[reader.index()];
+        state.failed_attempts = state.failed_attempts.saturating_add(1);
+        let delay_ms = self.deny_backoff.delay_ms(state.failed_attempts);
+        state.backoff_until = Some(clock::later(now_ms, delay_ms));
+    }
 }
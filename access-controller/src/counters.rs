@@ -0,0 +1,151 @@
+//! Event counters that monitoring can read and reset.
+//!
+//! Sync failures, WiFi reconnects, dropped reads and the like count up
+//! for as long as the board runs, so a monitor polling `/metrics` only
+//! sees rates by differencing. `GET /counters` reads every [`Name`] at
+//! once, counted from a per-counter baseline; `POST /counters/reset`
+//! reads each and moves its baseline up to the read in one atomic step,
+//! so a monitor can snapshot-and-reset without losing a count that lands
+//! between the read and the reset. The totals since boot, which
+//! `/metrics` exports as `_total` counters, never go down.
+//!
+//! The counters themselves stay with what they count; the firmware maps
+//! each [`Name`] to its [`Counter`] for [`snapshot`].
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// A count of events since boot, and since the last `/counters` reset.
+#[derive(Debug)]
+pub struct Counter {
+    count: AtomicU32,
+    /// `count` at the last reset.
+    baseline: AtomicU32,
+}
+
+impl Counter {
+    pub const fn new() -> Self {
+        Self {
+            count: AtomicU32::new(0),
+            baseline: AtomicU32::new(0),
+        }
+    }
+
+    pub fn incr(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Events since boot. Resets leave this alone.
+    pub fn get(&self) -> u32 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Events since the last reset.
+    pub fn since_reset(&self) -> u32 {
+        let baseline = self.baseline.load(Ordering::Acquire);
+        self.get().wrapping_sub(baseline)
+    }
+
+    /// Read the events since the last reset and start counting over, in
+    /// one step. The count since boot is unchanged.
+    pub fn take(&self) -> u32 {
+        let mut baseline = self.baseline.load(Ordering::Acquire);
+        loop {
+            // Read after the baseline (acquire), so the count is never
+            // behind a baseline another reset set from an earlier read.
+            let count = self.get();
+            match self.baseline.compare_exchange_weak(
+                baseline,
+                count,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return count.wrapping_sub(baseline),
+                Err(moved) => baseline = moved,
+            }
+        }
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A counter `/counters` reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Name {
+    /// Syncs in which every host failed.
+    SyncFailures,
+    /// Times WiFi was reconnected after the link had been up.
+    WifiReconnects,
+    /// Card reads dropped because `access_task` was behind.
+    ReadsDropped,
+    /// HTTP connections aborted because the client never closed.
+    SocketsForceClosed,
+    /// Connections refused by `CONWAY_ADMIN_ALLOW`.
+    AdminClientsRefused,
+    /// Card reads that held shared state past the watchdog budget.
+    LockOverBudget,
+}
+
+impl Name {
+    /// Every counter, in the order `/counters` lists them.
+    pub const ALL: [Name; 6] = [
+        Name::SyncFailures,
+        Name::WifiReconnects,
+        Name::ReadsDropped,
+        Name::SocketsForceClosed,
+        Name::AdminClientsRefused,
+        Name::LockOverBudget,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Name::SyncFailures => "sync_failures",
+            Name::WifiReconnects => "wifi_reconnects",
+            Name::ReadsDropped => "reads_dropped",
+            Name::SocketsForceClosed => "sockets_force_closed",
+            Name::AdminClientsRefused => "admin_clients_refused",
+            Name::LockOverBudget => "lock_over_budget",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Every counter's value at one read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    values: [u32; Name::ALL.len()],
+}
+
+impl Snapshot {
+    pub fn get(&self, name: Name) -> u32 {
+        self.values[name.index()]
+    }
+
+    /// Render as one JSON object, e.g. `{"sync_failures":2,...}`.
+    pub fn write_json<W: Write>(&self, out: &mut W) -> fmt::Result {
+        let mut sep = "{";
+        for name in Name::ALL {
+            write!(out, "{}\"{}\":{}", sep, name.as_str(), self.get(name))?;
+            sep = ",";
+        }
+        out.write_str("}\n")
+    }
+}
+
+/// Read every counter `counter` maps a [`Name`] to since its last
+/// reset, starting each over as it is read if `reset`.
+pub fn snapshot<'a>(counter: impl Fn(Name) -> &'a Counter, reset: bool) -> Snapshot {
+    let mut snap = Snapshot::default();
+    for name in Name::ALL {
+        let c = counter(name);
+        snap.values[name.index()] = if reset { c.take() } else { c.since_reset() };
+    }
+    snap
+}
@@ -0,0 +1,67 @@
+//! A small set of credentials, each remembered for a fixed span after it
+//! was last inserted.
+//!
+//! Backs both the online check's memory of refusals
+//! ([`crate::authz::NegativeCache`]) and anti-passback's memory of grants
+//! ([`crate::passback::RecentGrants`]). Matching is by credential, so a
+//! read at either reader hits an entry made at the other.
+
+use crate::clock;
+use crate::core::CardRead;
+
+/// Up to `N` credentials, each for `span_ms` from its insert.
+#[derive(Clone, Copy, Debug)]
+pub struct CredentialWindow<const N: usize> {
+    span_ms: u64,
+    /// `(read, until_ms)`.
+    slots: [Option<(CardRead, u64)>; N],
+}
+
+impl<const N: usize> CredentialWindow<N> {
+    /// A `span_ms` of 0 remembers nothing.
+    pub const fn new(span_ms: u64) -> Self {
+        Self {
+            span_ms,
+            slots: [None; N],
+        }
+    }
+
+    pub fn span_ms(&self) -> u64 {
+        self.span_ms
+    }
+
+    /// Remember `read` from `now_ms`. A credential already remembered
+    /// starts its span again; otherwise it takes a free or expired slot,
+    /// and with every slot live, the one closest to expiry makes room.
+    pub fn insert(&mut self, read: CardRead, now_ms: u64) {
+        if self.span_ms == 0 || N == 0 {
+            return;
+        }
+        let until = clock::later(now_ms, self.span_ms);
+        let slot = self
+            .slots
+            .iter()
+            .position(|s| s.is_some_and(|(r, _)| r.same_credential(&read)))
+            .or_else(|| {
+                self.slots
+                    .iter()
+                    .position(|s| s.is_none_or(|(_, until)| !clock::before(now_ms, until)))
+            })
+            .unwrap_or_else(|| {
+                (0..N)
+                    .min_by_key(|&i| {
+                        self.slots[i].map_or(0, |(_, until)| clock::elapsed(now_ms, until))
+                    })
+                    .unwrap_or(0)
+            });
+        self.slots[slot] = Some((read, until));
+    }
+
+    /// Whether `read`'s credential was inserted, at any reader, within
+    /// the span before `now_ms`.
+    pub fn contains(&self, read: CardRead, now_ms: u64) -> bool {
+        self.slots.iter().any(|s| {
+            s.is_some_and(|(r, until)| r.same_credential(&read) && clock::before(now_ms, until))
+        })
+    }
+}
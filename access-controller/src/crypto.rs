@@ -58,6 +58,10 @@ pub const RECORD_VERSION: u32 = 3;
 pub const DOMAIN_FOBS: [u8; 4] = *b"FOB1";
 /// Domain tag for the network settings store (4 bytes).
 pub const DOMAIN_SETTINGS: [u8; 4] = *b"CFG1";
+/// Domain tag for the persisted Conway fob cache (4 bytes).
+pub const DOMAIN_CACHE: [u8; 4] = *b"RFB1";
+/// Domain tag for the event sequence lease (4 bytes).
+pub const DOMAIN_SEQ: [u8; 4] = *b"SEQ1";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CryptoError {
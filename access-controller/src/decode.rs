@@ -4,11 +4,16 @@
 //! testable in isolation (parity checks, field extraction, credential
 //! derivation) lives here so it can be exercised from host tests.
 
+use crate::clock;
+
 /// Decoded Wiegand credential.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct WiegandRead {
     pub facility: u32,
     pub card: u32,
+    /// The frame's data bits, parity stripped, in wire order: the first
+    /// data bit received is the most significant. Byte order of a UID in
+    /// here is up to the reader; see [`ByteOrder`].
     pub raw_data: u32,
 }
 
@@ -18,9 +23,240 @@ impl WiegandRead {
         self.facility * 100_000 + self.card
     }
 
-    /// NFC UID derived by byte-reversing the raw data field.
+    /// NFC UID derived by byte-reversing the raw data field, for readers
+    /// that send the UID LSB-first ([`ByteOrder::Lsb`]).
     pub fn to_nfc_uid(&self) -> u32 {
-        self.raw_data.swap_bytes()
+        self.nfc_uid(ByteOrder::Lsb)
+    }
+
+    /// NFC UID for a reader that sends it in `order`.
+    pub fn nfc_uid(&self, order: ByteOrder) -> u32 {
+        match order {
+            ByteOrder::Lsb => self.raw_data.swap_bytes(),
+            ByteOrder::Msb => self.raw_data,
+        }
+    }
+
+    /// Leading four bytes of a 7-byte NFC UID. A 34-bit frame only
+    /// carries 32 bits, so readers cut longer UIDs down and send the
+    /// first bytes in UID order; unlike [`to_nfc_uid`](Self::to_nfc_uid)
+    /// there is nothing to reverse.
+    pub fn to_nfc7_uid(&self) -> u32 {
+        self.raw_data
+    }
+}
+
+/// Order in which a reader sends a 4-byte UID's bytes, and so where they
+/// land in [`WiegandRead::raw_data`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Last UID byte first, so `raw_data` holds the UID byte-reversed.
+    /// What most readers do, and the original behavior.
+    #[default]
+    Lsb,
+    /// First UID byte first: `raw_data` is the UID as is.
+    Msb,
+}
+
+impl ByteOrder {
+    /// Parse the `CONWAY_UID_BYTE_ORDER` build knob: `"lsb"` or `"msb"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "lsb" => Some(Self::Lsb),
+            "msb" => Some(Self::Msb),
+            _ => None,
+        }
+    }
+}
+
+/// Which credential a site's fob list holds, as hinted by Conway in the
+/// sync response's `X-Credential-Format` header. Without a hint every
+/// read is tried both as a fob and as a 4-byte NFC UID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CredentialFormat {
+    /// H10301 fob numbers ([`WiegandRead::to_fob`]).
+    Fob,
+    /// 4-byte NFC UIDs ([`WiegandRead::to_nfc_uid`]).
+    Nfc4,
+    /// 7-byte NFC UIDs, as far as the reader sends them
+    /// ([`WiegandRead::to_nfc7_uid`]).
+    Nfc7,
+}
+
+impl CredentialFormat {
+    /// Parse a header value: `"fob"`, `"nfc4"` or `"nfc7"`
+    /// (case-insensitive).
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("fob") {
+            Some(Self::Fob)
+        } else if s.eq_ignore_ascii_case("nfc4") {
+            Some(Self::Nfc4)
+        } else if s.eq_ignore_ascii_case("nfc7") {
+            Some(Self::Nfc7)
+        } else {
+            None
+        }
+    }
+
+    /// The credential `read` carries in this format, with 4-byte UIDs
+    /// taken in `order`. A truncated 7-byte UID always arrives in UID
+    /// order, so `order` does not apply to it.
+    pub fn extract(self, read: &WiegandRead, order: ByteOrder) -> u32 {
+        match self {
+            Self::Fob => read.to_fob(),
+            Self::Nfc4 => read.nfc_uid(order),
+            Self::Nfc7 => read.to_nfc7_uid(),
+        }
+    }
+}
+
+/// How fob IDs sent as strings in the server's list are read. Bare JSON
+/// numbers are always decimal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FobFormat {
+    /// Decimal digits, like a bare number.
+    #[default]
+    Decimal,
+    /// Decimal, `0x`-prefixed hex, or `facility:card` / `facility-card`,
+    /// each normalized to the number [`WiegandRead::to_fob`] gives.
+    Normalize,
+}
+
+impl FobFormat {
+    /// Parse a configuration value: `"decimal"` or `"normalize"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "decimal" => Some(Self::Decimal),
+            "normalize" => Some(Self::Normalize),
+            _ => None,
+        }
+    }
+
+    /// The fob ID `s` names, or `None` if it isn't one in this format.
+    pub fn read(self, s: &str) -> Option<u32> {
+        match self {
+            Self::Decimal => s.parse().ok(),
+            Self::Normalize => normalize_fob(s),
+        }
+    }
+}
+
+fn normalize_fob(s: &str) -> Option<u32> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        return u32::from_str_radix(hex, 16).ok();
+    }
+    if let Some((facility, card)) = s.split_once([':', '-']) {
+        let facility: u16 = decimal(facility)?;
+        let card: u16 = decimal(card)?;
+        // `to_fob` without the overflow: a 16-bit facility can exceed it.
+        return u32::from(facility)
+            .checked_mul(100_000)?
+            .checked_add(u32::from(card));
+    }
+    decimal(s)
+}
+
+/// Digits only: no sign, no whitespace.
+fn decimal<T: core::str::FromStr>(s: &str) -> Option<T> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Longest minimum pulse width [`PulseFilter::parse`] accepts. Readers
+/// hold a bit low for 20-100 µs, so a longer filter would drop every bit.
+pub const MAX_MIN_PULSE_US: u32 = 100;
+
+/// Pulse-width glitch filter for the GPIO reader: a falling edge only
+/// counts as a bit if the line is still low `min_low_us` after the reader
+/// woke for it. A noise spike is usually gone by then; a real bit is not.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PulseFilter {
+    /// 0 disables the filter: every edge is a bit, as before.
+    pub min_low_us: u32,
+}
+
+impl PulseFilter {
+    /// Parse the `CONWAY_WIEGAND_MIN_PULSE_US` build knob, at most
+    /// [`MAX_MIN_PULSE_US`].
+    pub fn parse(s: &str) -> Option<Self> {
+        let min_low_us = s.parse::<u32>().ok().filter(|&us| us <= MAX_MIN_PULSE_US)?;
+        Some(Self { min_low_us })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.min_low_us > 0
+    }
+
+    /// Whether an edge is a bit, given when the line was seen to rise
+    /// again: `Some(us)` after waking for the edge (0 if it was already
+    /// high), `None` if it stayed low for the whole filter window.
+    pub fn accept(&self, rose_after_us: Option<u64>) -> bool {
+        match rose_after_us {
+            None => true,
+            Some(us) => us >= u64::from(self.min_low_us),
+        }
+    }
+}
+
+/// Longest gap [`FrameGap::parse`] accepts. A person presenting a second
+/// card takes longer than this.
+pub const MAX_FRAME_GAP_MS: u32 = 1_000;
+
+/// Quiet time enforced after each frame the GPIO reader completes: a bit
+/// that arrives sooner is dropped rather than taken as the start of a
+/// new frame, since it is most likely the tail of the last one, split off
+/// by a pause in the reader. Each dropped bit restarts the gap, so a
+/// stray tail is dropped whole instead of becoming a short frame of its
+/// own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameGap {
+    /// 0 disables the gap: a new frame may start at once, as before.
+    pub min_gap_ms: u32,
+    quiet_since_us: Option<u64>,
+}
+
+impl FrameGap {
+    pub const fn new(min_gap_ms: u32) -> Self {
+        Self {
+            min_gap_ms,
+            quiet_since_us: None,
+        }
+    }
+
+    /// Parse the `CONWAY_WIEGAND_MIN_GAP_MS` build knob, at most
+    /// [`MAX_FRAME_GAP_MS`].
+    pub fn parse(s: &str) -> Option<Self> {
+        let ms = s.parse::<u32>().ok().filter(|&ms| ms <= MAX_FRAME_GAP_MS)?;
+        Some(Self::new(ms))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.min_gap_ms > 0
+    }
+
+    /// A frame was completed (decoded or not) at `now_us`.
+    pub fn frame_ended(&mut self, now_us: u64) {
+        self.quiet_since_us = Some(now_us);
+    }
+
+    /// Whether a bit arriving at `now_us`, with no frame in progress, may
+    /// start a new frame. A bit too soon is rejected and restarts the gap.
+    pub fn accept(&mut self, now_us: u64) -> bool {
+        let Some(since) = self.quiet_since_us else {
+            return true;
+        };
+        if clock::elapsed(since, now_us) >= u64::from(self.min_gap_ms) * 1_000 {
+            self.quiet_since_us = None;
+            return true;
+        }
+        self.quiet_since_us = Some(now_us);
+        false
     }
 }
 
@@ -87,6 +323,84 @@ pub fn decode_34(raw: u64) -> Option<WiegandRead> {
     })
 }
 
+/// Decode a frame of `count` bits, collected MSB first into `bits`, in
+/// the format its length selects. `None` for a length other than 26 or
+/// 34, or on parity failure.
+pub fn decode_frame(bits: u64, count: u32) -> Option<WiegandRead> {
+    match count {
+        26 => decode_26(bits),
+        34 => decode_34(bits),
+        _ => None,
+    }
+}
+
+/// Longest frame [`FrameBits`] keeps: the bits fit one `u64`. Neither
+/// supported format comes close, so a longer frame is noise or a reader
+/// misconfigured for some other format.
+pub const MAX_FRAME_BITS: u32 = 64;
+
+/// Why a frame did not decode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// More than [`MAX_FRAME_BITS`] bits arrived; how many in all.
+    Overlong(u32),
+    /// A length other than 26 or 34.
+    UnknownFormat(u32),
+    /// A supported length whose parity bits don't match.
+    Parity(u32),
+}
+
+/// The bits of one frame as they arrive, MSB first.
+///
+/// Bits past [`MAX_FRAME_BITS`] are counted but not kept: the frame is
+/// then over-length and [`finish`](Self::finish) rejects it whole. The
+/// reader must go on recording until the line is quiet, so the rest of
+/// an over-length frame is not taken for the start of the next one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameBits {
+    bits: u64,
+    count: u32,
+}
+
+impl FrameBits {
+    pub const fn new() -> Self {
+        Self { bits: 0, count: 0 }
+    }
+
+    /// Record the next bit (0 or 1). Returns false once the frame is
+    /// over-length.
+    pub fn record_bit(&mut self, bit: u8) -> bool {
+        if self.count < MAX_FRAME_BITS {
+            self.bits = (self.bits << 1) | u64::from(bit & 1);
+        }
+        self.count = self.count.saturating_add(1);
+        !self.overlong()
+    }
+
+    /// Bits recorded so far, kept or not.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Whether more than [`MAX_FRAME_BITS`] bits have arrived.
+    pub fn overlong(&self) -> bool {
+        self.count > MAX_FRAME_BITS
+    }
+
+    /// The line went quiet: decode the frame and start afresh, whatever
+    /// the outcome.
+    pub fn finish(&mut self) -> Result<WiegandRead, FrameError> {
+        let Self { bits, count } = core::mem::take(self);
+        if count > MAX_FRAME_BITS {
+            return Err(FrameError::Overlong(count));
+        }
+        if !matches!(count, 26 | 34) {
+            return Err(FrameError::UnknownFormat(count));
+        }
+        decode_frame(bits, count).ok_or(FrameError::Parity(count))
+    }
+}
+
 /// Build a syntactically valid 26-bit frame for a given facility/card pair,
 /// with correct parity bits. Useful for tests and for round-tripping known
 /// credentials through `decode_26`. Truncates `facility` to 8 bits and
@@ -109,7 +423,12 @@ pub fn encode_26(facility: u32, card: u32) -> u64 {
 pub fn encode_34(facility: u32, card: u32) -> u64 {
     let facility = facility & 0xFF;
     let card = card & 0xFFFF;
-    let data: u32 = (facility << 16) | card;
+    encode_34_data((facility << 16) | card)
+}
+
+/// Build a 34-bit frame around all 32 data bits, as `decode_34` returns
+/// them in `raw_data`.
+pub fn encode_34_data(data: u32) -> u64 {
     let upper = data >> 16;
     let lower = data & 0xFFFF;
     let leading = upper.count_ones() & 1;
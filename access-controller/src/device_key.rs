@@ -81,8 +81,18 @@
 //! in `OnceCell`/`once_cell` for two fixed-size byte arrays.
 //! The BLOCK3 IKM is held in a `Zeroizing<[u8; 32]>` and wiped as soon
 //! as HKDF returns.
+//!
+//! ## ESP32-S3
+//!
+//! On the S3 the same 32 bytes live in `BLOCK_USR_DATA` (BLOCK3), read
+//! through `RD_USR_DATA0..7`; derivation is unchanged. The S3 protects
+//! every eFuse block with Reed-Solomon check bytes computed at program
+//! time, which [`auto_provision`] does not implement, so it never burns
+//! on the S3: a blank block is reported as [`ProvisionOutcome::Skipped`]
+//! and the unit is provisioned with `tools/provision-device-key.sh`
+//! instead (espefuse computes the check bytes).
 
-#![cfg(feature = "esp32")]
+#![cfg(feature = "firmware")]
 
 use core::sync::atomic::{AtomicU8, Ordering};
 
@@ -154,6 +164,7 @@ fn read_block3() -> Zeroizing<Key> {
     // own efuse code accesses these registers (efuse/esp32/mod.rs).
     let efuse = esp_hal::peripherals::EFUSE::regs();
     let mut out = Zeroizing::new([0u8; 32]);
+    #[cfg(feature = "esp32")]
     let words = [
         efuse.blk3_rdata0().read().bits(),
         efuse.blk3_rdata1().read().bits(),
@@ -164,6 +175,17 @@ fn read_block3() -> Zeroizing<Key> {
         efuse.blk3_rdata6().read().bits(),
         efuse.blk3_rdata7().read().bits(),
     ];
+    #[cfg(feature = "esp32s3")]
+    let words = [
+        efuse.rd_usr_data0().read().bits(),
+        efuse.rd_usr_data1().read().bits(),
+        efuse.rd_usr_data2().read().bits(),
+        efuse.rd_usr_data3().read().bits(),
+        efuse.rd_usr_data4().read().bits(),
+        efuse.rd_usr_data5().read().bits(),
+        efuse.rd_usr_data6().read().bits(),
+        efuse.rd_usr_data7().read().bits(),
+    ];
     for (i, w) in words.iter().enumerate() {
         out[i * 4..(i + 1) * 4].copy_from_slice(&w.to_le_bytes());
     }
@@ -174,10 +196,13 @@ fn read_block3() -> Zeroizing<Key> {
 /// usable, raw word writes valid). `1` == 3/4 encoding, `2` == repeat —
 /// both shrink the usable block and require encoded writes, so we refuse
 /// to auto-burn under them.
+#[cfg(feature = "esp32")]
 const CODING_SCHEME_NONE: u8 = 0;
 
 /// eFuse controller op-codes (ESP32 TRM / esp-idf `efuse_ll.h`).
+#[cfg(feature = "esp32")]
 const EFUSE_WRITE_OP_CODE: u16 = 0x5A5A;
+#[cfg(feature = "esp32")]
 const EFUSE_READ_OP_CODE: u16 = 0x5AA5;
 
 /// Outcome of a first-boot [`auto_provision`] attempt.
@@ -190,13 +215,15 @@ pub enum ProvisionOutcome {
     Provisioned,
     /// BLOCK3 was blank but we deliberately did **not** end up with a
     /// usable key: the eFuse coding scheme is unsupported, the TRNG
-    /// returned an unusable value, or the post-burn readback did not match.
+    /// returned an unusable value, the post-burn readback did not match, or
+    /// the chip (ESP32-S3) is never self-burned.
     /// The reason is logged at `error` level. The device continues to boot
     /// unprovisioned (encryption disabled).
     Skipped,
 }
 
 /// Read the BLK0 coding scheme field (`RD_CODING_SCHEME`, 2 bits).
+#[cfg(feature = "esp32")]
 fn coding_scheme() -> u8 {
     esp_hal::peripherals::EFUSE::regs()
         .blk0_rdata6()
@@ -215,6 +242,7 @@ fn coding_scheme() -> u8 {
 /// as named ADC-calibration sub-fields instead of a single 32-bit value,
 /// and WDATA4 has no writer at all. Raw 32-bit stores sidestep that and are
 /// exactly what an eFuse program cycle consumes.
+#[cfg(feature = "esp32")]
 fn clear_program_registers() {
     let efuse = esp_hal::peripherals::EFUSE::regs();
     let zero = |p: *mut u32| unsafe { p.write_volatile(0) };
@@ -264,6 +292,7 @@ fn clear_program_registers() {
 /// access to BLOCK3 to derive the AEAD sub-keys at every boot (the ESP32
 /// classic has no BLOCK3 key-feeder peripheral — see the module threat
 /// model).
+#[cfg(feature = "esp32")]
 fn burn_block3(key: &Key) {
     let efuse = esp_hal::peripherals::EFUSE::regs();
 
@@ -328,6 +357,7 @@ fn burn_block3(key: &Key) {
 /// `rng` and `adc1` are consumed for the duration of key generation to
 /// stand up an esp-hal [`TrngSource`] (adds SAR-ADC entropy); both are
 /// released before the function returns.
+#[cfg(feature = "esp32")]
 pub fn auto_provision(
     rng: esp_hal::peripherals::RNG<'_>,
     adc1: esp_hal::peripherals::ADC1<'_>,
@@ -395,6 +425,25 @@ pub fn auto_provision(
     ProvisionOutcome::Provisioned
 }
 
+/// ESP32-S3: report the block state but never burn (see the module docs
+/// on Reed-Solomon coding). The peripherals are accepted for signature
+/// parity with the ESP32 build and dropped unused.
+#[cfg(feature = "esp32s3")]
+pub fn auto_provision(
+    _rng: esp_hal::peripherals::RNG<'_>,
+    _adc1: esp_hal::peripherals::ADC1<'_>,
+) -> ProvisionOutcome {
+    if read_block3().iter().any(|&b| b != 0) {
+        return ProvisionOutcome::AlreadyProvisioned;
+    }
+    log::error!(
+        "device_key: BLOCK_USR_DATA is blank — the ESP32-S3 needs \
+         Reed-Solomon-coded eFuse writes, which this firmware does not \
+         self-burn. Use tools/provision-device-key.sh."
+    );
+    ProvisionOutcome::Skipped
+}
+
 /// Initialize the device key. Call exactly once, after `esp_radio::init()`
 /// (so the MAC is reliably readable) and before any call to
 /// [`crate::settings::load`] / [`crate::fob_store::load`].
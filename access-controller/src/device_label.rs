@@ -0,0 +1,40 @@
+//! Operator-chosen name for a controller, e.g. "Wood shop door".
+//!
+//! Stored with the settings and shown in the title and heading of the
+//! admin index (`/` and `/status`), so an installer can tell which unit
+//! they are talking to. Empty means no label.
+
+use core::fmt::{self, Write};
+
+/// Longest label, in bytes.
+pub const MAX_DEVICE_LABEL: usize = 32;
+
+/// Whether `label` can be stored: at most [`MAX_DEVICE_LABEL`] bytes and
+/// no control characters.
+pub fn valid(label: &str) -> bool {
+    label.len() <= MAX_DEVICE_LABEL && !label.chars().any(char::is_control)
+}
+
+/// Write a page title: `page` alone, or `label · page` with the label
+/// HTML-escaped.
+pub fn write_title<W: Write>(out: &mut W, label: &str, page: &str) -> fmt::Result {
+    if label.is_empty() {
+        return out.write_str(page);
+    }
+    write_escaped(out, label)?;
+    write!(out, " &middot; {}", page)
+}
+
+fn write_escaped<W: Write>(out: &mut W, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            '"' => out.write_str("&quot;")?,
+            '\'' => out.write_str("&#39;")?,
+            c => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
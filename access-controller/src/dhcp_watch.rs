@@ -0,0 +1,185 @@
+//! Detection of a DHCP lease that never arrives.
+//!
+//! WiFi can associate while DHCP goes nowhere: the AP's DHCP server is
+//! down or out of leases, or a VLAN misconfiguration swallows the
+//! offers. The WiFi task only reconnects once the link drops, so such a
+//! controller would sit associated without an address until someone
+//! power-cycles it. [`DhcpWatch`] spots that and asks for a reconnect,
+//! which brings the link down and starts DHCP over from DISCOVER.
+//!
+//! Pure state machine: the firmware polls it with the link and address
+//! state and acts on the returned [`DhcpAction`].
+//!
+//! Once an address is held, the DHCP socket renews it on its own and
+//! says nothing. [`LeaseWatch`] follows the address the stack reports so
+//! that acquiring, changing or losing it shows up in the log and on
+//! `/status`. embassy-net keeps the lease duration to itself, and a
+//! renewal that keeps the same address changes nothing visible, so it
+//! tracks how long the address has been held rather than what is left.
+
+use core::fmt;
+
+use crate::clock;
+
+/// Default time associated without an address before reconnecting.
+pub const DEFAULT_DHCP_TIMEOUT_SECS: u64 = 60;
+
+/// Shortest timeout [`parse_timeout_secs`] accepts: a busy DHCP server
+/// may take a few retransmits to answer.
+pub const MIN_DHCP_TIMEOUT_SECS: u64 = 10;
+
+/// Longest timeout [`parse_timeout_secs`] accepts: an hour.
+pub const MAX_DHCP_TIMEOUT_SECS: u64 = 3_600;
+
+/// Parse a `CONWAY_DHCP_TIMEOUT_SECS` value: seconds associated without
+/// an address before WiFi is reconnected, from [`MIN_DHCP_TIMEOUT_SECS`]
+/// to [`MAX_DHCP_TIMEOUT_SECS`]. `0` never reconnects for DHCP.
+pub fn parse_timeout_secs(s: &str) -> Option<u64> {
+    s.parse()
+        .ok()
+        .filter(|&secs| secs == 0 || (MIN_DHCP_TIMEOUT_SECS..=MAX_DHCP_TIMEOUT_SECS).contains(&secs))
+}
+
+/// What the WiFi task should do after a [`DhcpWatch::poll`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DhcpAction {
+    /// Nothing: no link, an address, or still within the timeout.
+    Wait,
+    /// Associated for the whole timeout without an address: reconnect.
+    Reconnect,
+}
+
+#[derive(Clone, Debug)]
+pub struct DhcpWatch {
+    timeout_ms: u64,
+    /// When the current wait for an address began, while associated
+    /// without one.
+    waiting_since_ms: Option<u64>,
+    reconnects: u32,
+}
+
+impl DhcpWatch {
+    /// Watch with a `timeout_secs` timeout; 0 never asks to reconnect.
+    pub const fn new(timeout_secs: u64) -> Self {
+        Self {
+            timeout_ms: timeout_secs.saturating_mul(1000),
+            waiting_since_ms: None,
+            reconnects: 0,
+        }
+    }
+
+    /// Reconnects asked for since boot.
+    pub fn reconnects(&self) -> u32 {
+        self.reconnects
+    }
+
+    /// Whether the link is up and waiting on an address, as of the last
+    /// poll.
+    pub fn is_waiting(&self) -> bool {
+        self.waiting_since_ms.is_some()
+    }
+
+    /// Poll at `now_ms`: `associated` if WiFi is connected, `configured`
+    /// if the stack has an IPv4 address. The wait starts at the first
+    /// poll that finds the link up without an address, and ends when
+    /// either changes. Once it has run the full timeout, this asks for a
+    /// reconnect and starts the wait over, so a DHCP server that stays
+    /// silent gets a reconnect per timeout rather than one per poll.
+    pub fn poll(&mut self, now_ms: u64, associated: bool, configured: bool) -> DhcpAction {
+        if !associated || configured {
+            self.waiting_since_ms = None;
+            return DhcpAction::Wait;
+        }
+        let since = *self.waiting_since_ms.get_or_insert(now_ms);
+        if self.timeout_ms == 0 || clock::elapsed(since, now_ms) < self.timeout_ms {
+            return DhcpAction::Wait;
+        }
+        self.waiting_since_ms = Some(now_ms);
+        self.reconnects = self.reconnects.saturating_add(1);
+        DhcpAction::Reconnect
+    }
+}
+
+/// A change in the stack's IPv4 address seen by [`LeaseWatch::poll`].
+/// Displays as the line the firmware logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeaseEvent {
+    /// An address arrived where there was none.
+    Acquired([u8; 4]),
+    /// The lease moved to a different address without a gap in between.
+    Changed { from: [u8; 4], to: [u8; 4] },
+    /// The address went away: the lease expired or DHCP deconfigured.
+    Lost([u8; 4]),
+}
+
+struct Ip([u8; 4]);
+
+impl fmt::Display for Ip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d] = self.0;
+        write!(f, "{}.{}.{}.{}", a, b, c, d)
+    }
+}
+
+impl fmt::Display for LeaseEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LeaseEvent::Acquired(ip) => write!(f, "dhcp: lease acquired: {}", Ip(ip)),
+            LeaseEvent::Changed { from, to } => {
+                write!(f, "dhcp: lease changed: {} -> {}", Ip(from), Ip(to))
+            }
+            LeaseEvent::Lost(ip) => write!(f, "dhcp: lease lost: {}", Ip(ip)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LeaseWatch {
+    address: Option<[u8; 4]>,
+    /// When the current address was first seen.
+    since_ms: u64,
+    losses: u32,
+}
+
+impl LeaseWatch {
+    pub const fn new() -> Self {
+        Self {
+            address: None,
+            since_ms: 0,
+            losses: 0,
+        }
+    }
+
+    /// The address held as of the last poll.
+    pub fn address(&self) -> Option<[u8; 4]> {
+        self.address
+    }
+
+    /// How long the current address has been held at `now_ms`.
+    pub fn held_ms(&self, now_ms: u64) -> Option<u64> {
+        self.address.map(|_| clock::elapsed(self.since_ms, now_ms))
+    }
+
+    /// Leases lost since boot.
+    pub fn losses(&self) -> u32 {
+        self.losses
+    }
+
+    /// Poll at `now_ms` with the address the stack has now, if any.
+    /// Returns the transition since the last poll; an unchanged address,
+    /// renewed or not, returns `None`.
+    pub fn poll(&mut self, now_ms: u64, address: Option<[u8; 4]>) -> Option<LeaseEvent> {
+        let event = match (self.address, address) {
+            (None, Some(to)) => LeaseEvent::Acquired(to),
+            (Some(from), Some(to)) if from != to => LeaseEvent::Changed { from, to },
+            (Some(from), None) => {
+                self.losses = self.losses.saturating_add(1);
+                LeaseEvent::Lost(from)
+            }
+            _ => return None,
+        };
+        self.address = address;
+        self.since_ms = now_ms;
+        Some(event)
+    }
+}
@@ -0,0 +1,166 @@
+//! Capture of the last Conway sync response, for `GET /diag/lastsync`.
+//!
+//! When a sync fails with "not a JSON array" or a bad signature, the log
+//! line alone doesn't say what the server (or a proxy in front of it)
+//! actually sent. The firmware's `sync` module records every response
+//! here, complete or not, and the admin server renders it on request.
+//!
+//! Memory is bounded: only the first [`MAX_HEAD`] bytes of the header
+//! block and the first [`MAX_BODY`] bytes of the body are kept, alongside
+//! their full lengths. Nothing is redacted; sync responses carry no
+//! secrets, only the fob list. The endpoint is still gated behind
+//! `CONWAY_DIAG_SECRET` (see [`authorized`]) since the list is the
+//! site's membership.
+
+use core::fmt::Write;
+
+use heapless::Vec as HVec;
+
+use crate::http_client::find_head_end;
+
+/// Header bytes kept (status line and headers).
+pub const MAX_HEAD: usize = 512;
+/// Body bytes kept.
+pub const MAX_BODY: usize = 256;
+
+/// The most recent sync response, truncated.
+#[derive(Clone, Debug)]
+pub struct LastResponse {
+    host: Option<[u8; 4]>,
+    at_ms: u64,
+    head: HVec<u8, MAX_HEAD>,
+    head_len: usize,
+    body: HVec<u8, MAX_BODY>,
+    body_len: usize,
+}
+
+impl Default for LastResponse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LastResponse {
+    pub const fn new() -> Self {
+        Self {
+            host: None,
+            at_ms: 0,
+            head: HVec::new(),
+            head_len: 0,
+            body: HVec::new(),
+            body_len: 0,
+        }
+    }
+
+    /// Replace the capture with `raw`, the bytes received from `host`
+    /// (`at_ms` after boot). If `raw` has no complete header block, all
+    /// of it counts as head.
+    pub fn record(&mut self, host: [u8; 4], at_ms: u64, raw: &[u8]) {
+        let (head, body) = match find_head_end(raw) {
+            Some((head_len, body_start)) => (&raw[..head_len], &raw[body_start..]),
+            None => (raw, &raw[raw.len()..]),
+        };
+        self.host = Some(host);
+        self.at_ms = at_ms;
+        self.head.clear();
+        let _ = self
+            .head
+            .extend_from_slice(&head[..head.len().min(MAX_HEAD)]);
+        self.head_len = head.len();
+        self.body.clear();
+        let _ = self
+            .body
+            .extend_from_slice(&body[..body.len().min(MAX_BODY)]);
+        self.body_len = body.len();
+    }
+
+    /// Whether nothing has been recorded since boot.
+    pub fn is_empty(&self) -> bool {
+        self.host.is_none()
+    }
+
+    /// Kept header bytes and the full header length.
+    pub fn head(&self) -> (&[u8], usize) {
+        (&self.head, self.head_len)
+    }
+
+    /// Kept body bytes and the full body length.
+    pub fn body(&self) -> (&[u8], usize) {
+        (&self.body, self.body_len)
+    }
+
+    /// Plain-text rendering for the endpoint. Bytes outside printable
+    /// ASCII are escaped (`\r`, `\xNN`) so line endings and binary
+    /// bodies show up as they were received; `\n` is kept as a newline.
+    pub fn render<W: Write>(&self, out: &mut W) -> core::fmt::Result {
+        let Some(h) = self.host else {
+            return out.write_str("no sync response recorded since boot\n");
+        };
+        writeln!(
+            out,
+            "host {}.{}.{}.{}, {} ms after boot",
+            h[0], h[1], h[2], h[3], self.at_ms
+        )?;
+        section(out, "head", &self.head, self.head_len)?;
+        section(out, "body", &self.body, self.body_len)
+    }
+}
+
+fn section<W: Write>(out: &mut W, name: &str, kept: &[u8], len: usize) -> core::fmt::Result {
+    if kept.len() < len {
+        writeln!(
+            out,
+            "\n{} ({} bytes, first {} shown):",
+            name,
+            len,
+            kept.len()
+        )?;
+    } else {
+        writeln!(out, "\n{} ({} bytes):", name, len)?;
+    }
+    for &b in kept {
+        match b {
+            b'\n' => out.write_char('\n')?,
+            b'\r' => out.write_str("\\r")?,
+            b'\\' => out.write_str("\\\\")?,
+            0x20..=0x7E => out.write_char(b as char)?,
+            _ => write!(out, "\\x{:02x}", b)?,
+        }
+    }
+    if kept.last() != Some(&b'\n') {
+        out.write_char('\n')?;
+    }
+    Ok(())
+}
+
+/// Whether a request may read the capture: `secret` must be configured
+/// (non-empty) and `authorization` must be `Bearer <secret>`. The
+/// comparison takes the same time wherever the first mismatch is.
+pub fn authorized(secret: Option<&str>, authorization: Option<&str>) -> bool {
+    let Some(secret) = secret.filter(|s| !s.is_empty()) else {
+        return false;
+    };
+    bearer_token(authorization).is_some_and(|token| secret_matches(secret, token))
+}
+
+/// The token in an `Authorization: Bearer <token>` header value, trimmed;
+/// `None` for a missing header or another scheme.
+pub fn bearer_token(authorization: Option<&str>) -> Option<&str> {
+    authorization?
+        .split_once(' ')
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, token)| token.trim())
+}
+
+/// Whether `token` is `secret`, for secrets sent some other way than a
+/// header (a form field). An empty `secret` matches nothing. Constant-time
+/// like [`authorized`].
+pub fn secret_matches(secret: &str, token: &str) -> bool {
+    !secret.is_empty()
+        && token.len() == secret.len()
+        && token
+            .bytes()
+            .zip(secret.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
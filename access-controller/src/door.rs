@@ -0,0 +1,156 @@
+//! When to relock the door after an unlock.
+//!
+//! By default the strike is simply held for `hold_ms`. With a door-contact
+//! sensor (feature `door-contact`) and [`Relock::OnClose`], it relocks as
+//! soon as the door has opened and closed again, so a person walking
+//! through does not leave it unlocked for the rest of the hold. The hold
+//! time stays the upper bound either way, for doors that never open.
+//!
+//! Conway can tag credentials for an extended unlock (an accessibility
+//! accommodation) with an `X-Extended-Unlock` header on its sync response;
+//! those grants hold for [`Holds::extended_ms`] instead.
+
+use heapless::Vec as HVec;
+
+use crate::clock;
+
+/// Default strike hold: the original fixed pulse.
+pub const DEFAULT_HOLD_MS: u64 = 200;
+/// Longest hold [`parse_hold_ms`] accepts.
+pub const MAX_HOLD_MS: u64 = 60_000;
+
+/// Parse the `CONWAY_DOOR_HOLD_MS` build knob: 1 to [`MAX_HOLD_MS`].
+pub fn parse_hold_ms(s: &str) -> Option<u64> {
+    s.parse::<u64>().ok().filter(|ms| (1..=MAX_HOLD_MS).contains(ms))
+}
+
+/// Default hold for credentials tagged for an extended unlock.
+pub const DEFAULT_EXTENDED_HOLD_MS: u64 = 10_000;
+/// Most tagged credentials kept from one sync response.
+pub const MAX_EXTENDED_UNLOCK: usize = 32;
+
+/// Hold times per grant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Holds {
+    pub default_ms: u64,
+    /// For credentials Conway tagged for an extended unlock.
+    pub extended_ms: u64,
+}
+
+impl Holds {
+    pub const DEFAULT: Self = Self {
+        default_ms: DEFAULT_HOLD_MS,
+        extended_ms: DEFAULT_EXTENDED_HOLD_MS,
+    };
+
+    /// The hold for a grant to `credential`, given the tagged list. An
+    /// extended unlock is never shorter than the default one.
+    pub fn for_grant(&self, credential: u32, extended: &[u32]) -> u64 {
+        if extended.contains(&credential) {
+            self.extended_ms.max(self.default_ms)
+        } else {
+            self.default_ms
+        }
+    }
+}
+
+impl Default for Holds {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Parse an `X-Extended-Unlock` header value: comma-separated decimal
+/// credential IDs, empty for none. `None` if any ID is malformed or
+/// there are more than [`MAX_EXTENDED_UNLOCK`].
+pub fn parse_extended_unlock(s: &str) -> Option<HVec<u32, MAX_EXTENDED_UNLOCK>> {
+    let mut tagged = HVec::new();
+    if s.trim().is_empty() {
+        return Some(tagged);
+    }
+    for id in s.split(',') {
+        tagged.push(id.trim().parse().ok()?).ok()?;
+    }
+    Some(tagged)
+}
+
+/// Relock policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Relock {
+    /// Hold for the full time. Original behavior.
+    #[default]
+    Timeout,
+    /// Also relock once the contact shows the door opened and re-closed.
+    OnClose,
+}
+
+impl Relock {
+    /// Parse the `CONWAY_DOOR_RELOCK` build knob: `"timeout"` or `"close"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "timeout" => Some(Self::Timeout),
+            "close" => Some(Self::OnClose),
+            _ => None,
+        }
+    }
+}
+
+/// Why an unlock ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relocked {
+    /// The hold time ran out.
+    Timeout,
+    /// The door opened and closed again.
+    Closed,
+}
+
+/// One unlock in progress.
+#[derive(Clone, Copy, Debug)]
+pub struct Unlock {
+    deadline_ms: u64,
+    relock: Relock,
+    /// The contact has shown the door open since the unlock began.
+    opened: bool,
+}
+
+impl Unlock {
+    /// The strike was energised at `now_ms`.
+    pub fn start(now_ms: u64, hold_ms: u64, relock: Relock) -> Self {
+        Self {
+            deadline_ms: clock::later(now_ms, hold_ms),
+            relock,
+            opened: false,
+        }
+    }
+
+    /// When to relock if the contact says nothing more.
+    pub fn deadline_ms(&self) -> u64 {
+        self.deadline_ms
+    }
+
+    /// Time from `now_ms` to the deadline, across a wrap of the counter;
+    /// zero once it has passed.
+    pub fn remaining_ms(&self, now_ms: u64) -> u64 {
+        clock::elapsed(now_ms, self.deadline_ms)
+    }
+
+    /// The contact read `open` (debounced) at `now_ms`.
+    pub fn contact(&mut self, now_ms: u64, open: bool) -> Option<Relocked> {
+        if !clock::before(now_ms, self.deadline_ms) {
+            return Some(Relocked::Timeout);
+        }
+        if open {
+            self.opened = true;
+            None
+        } else if self.opened && self.relock == Relock::OnClose {
+            Some(Relocked::Closed)
+        } else {
+            None
+        }
+    }
+
+    /// Time passed with no contact change.
+    pub fn tick(&self, now_ms: u64) -> Option<Relocked> {
+        (!clock::before(now_ms, self.deadline_ms)).then_some(Relocked::Timeout)
+    }
+}
@@ -0,0 +1,42 @@
+//! [`RandomSource`] over the ESP32 hardware RNG.
+//!
+//! Only random once the radio is up (RF noise is the entropy source);
+//! before that it is a boot-deterministic PRNG, so create sources after
+//! `esp_radio::init()`. If the peripheral returns the same word over and
+//! over, fall back to [`SplitMix64`] seeded from the MAC and the boot
+//! time: not secret, but still different per device and per boot.
+
+use embassy_time::Instant;
+
+use access_controller::rng::{self, RandomSource, SplitMix64};
+
+pub enum Entropy {
+    Hardware(esp_hal::rng::Rng),
+    Fallback(SplitMix64),
+}
+
+impl Entropy {
+    pub fn new() -> Self {
+        let mut hw = esp_hal::rng::Rng::new();
+        let probe = [hw.random(), hw.random(), hw.random(), hw.random()];
+        if !rng::looks_stuck(&probe) {
+            return Self::Hardware(hw);
+        }
+        log::warn!("rng: hardware RNG stuck at {:08X}, using MAC-seeded fallback", probe[0]);
+        let seed = rng::mac_seed(esp_radio::wifi::sta_mac()) ^ Instant::now().as_ticks();
+        Self::Fallback(SplitMix64::new(seed))
+    }
+
+    pub fn is_hardware(&self) -> bool {
+        matches!(self, Self::Hardware(_))
+    }
+}
+
+impl RandomSource for Entropy {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            Self::Hardware(hw) => hw.random(),
+            Self::Fallback(sm) => sm.next_u32(),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! ETag bookkeeping keyed by the Conway host it came from.
+//!
+//! An ETag only describes the fob list *as served by the host that sent
+//! it*. With failover (see [`crate::failover`]) the controller can talk
+//! to several hosts, so the stored tag remembers its origin and
+//! `If-None-Match` is only sent back to that host. Any other host gets
+//! an unconditional request and answers with its own full list and tag.
+//!
+//! Only one tag is kept: it travels with the cached list, and a tag from
+//! host A is meaningless once the cache has been replaced by host B's
+//! list.
+//!
+//! A cache that silently diverged from the server (a partial write, a
+//! list that decoded short) would be kept forever by 304s to its tag, so
+//! [`FullResync`] periodically leaves the tag off to fetch the full list.
+
+use heapless::String as HString;
+
+/// Maximum stored ETag length. Longer tags are not stored at all (a
+/// truncated tag can never match).
+pub const MAX_ETAG_LEN: usize = 64;
+
+/// Whether `value` is a tag worth storing: visible ASCII only. That is
+/// the RFC 9110 `entity-tag` alphabet minus `obs-text`; a tag with opaque
+/// high bytes (or control characters) can't be echoed back reliably, so
+/// it is not kept and the next sync is simply unconditional.
+pub fn valid_tag(value: &str) -> bool {
+    value.bytes().all(|b| (0x21..=0x7E).contains(&b))
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HostEtag {
+    host: Option<[u8; 4]>,
+    value: HString<MAX_ETAG_LEN>,
+}
+
+impl HostEtag {
+    pub const fn new() -> Self {
+        Self {
+            host: None,
+            value: HString::new(),
+        }
+    }
+
+    /// Replace the stored tag with `value` from `host`. An empty,
+    /// oversized or non-[`valid_tag`] `value` clears the tag instead, and
+    /// is never cut down to fit. Returns whether the tag was stored.
+    pub fn set(&mut self, host: [u8; 4], value: &str) -> bool {
+        self.clear();
+        if value.is_empty() || value.len() > MAX_ETAG_LEN || !valid_tag(value) {
+            return false;
+        }
+        // Cannot fail: length checked above.
+        let _ = self.value.push_str(value);
+        self.host = Some(host);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.host = None;
+        self.value.clear();
+    }
+
+    /// The tag to send as `If-None-Match` to `host`, if it is the one
+    /// that issued it.
+    pub fn for_host(&self, host: [u8; 4]) -> Option<&str> {
+        match self.host {
+            Some(h) if h == host => Some(self.value.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Host that issued the stored tag.
+    pub fn host(&self) -> Option<[u8; 4]> {
+        self.host
+    }
+
+    /// Stored tag, or `""` if none.
+    pub fn as_str(&self) -> &str {
+        self.value.as_str()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.host.is_none()
+    }
+}
+
+/// Parse a `CONWAY_FULL_RESYNC_CYCLES` value: every how many syncs the
+/// tag is left off, at least 1.
+pub fn parse_full_resync(s: &str) -> Option<u32> {
+    s.parse().ok().filter(|&n| n > 0)
+}
+
+/// Counts syncs since the last full list, to force an unconditional
+/// request every so often.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FullResync {
+    since_full: u32,
+}
+
+impl FullResync {
+    pub const fn new() -> Self {
+        Self { since_full: 0 }
+    }
+
+    /// Start a sync cycle. Returns whether it should omit
+    /// `If-None-Match`: `every` cycles or more since the last full list.
+    /// `every == 0` never forces. A forced cycle that fails stays due, so
+    /// the next one forces again.
+    pub fn begin_cycle(&mut self, every: u32) -> bool {
+        self.since_full = self.since_full.saturating_add(1);
+        every != 0 && self.since_full >= every
+    }
+
+    /// A 200 replaced the list: it is the full list, whether or not the
+    /// request was conditional.
+    pub fn fetched(&mut self) {
+        self.since_full = 0;
+    }
+
+    /// Cycles since the last full list.
+    pub fn since_full(&self) -> u32 {
+        self.since_full
+    }
+}
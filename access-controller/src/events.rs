@@ -1,4 +1,14 @@
-//! Access event reported to the Conway server.
+//! Access events reported to the Conway server, and the buffer holding
+//! them until the server acknowledges them, plus a ring of recent
+//! decisions served at `GET /diag/events`.
+//!
+//! Every event the buffer takes is numbered: the sequence number is sent
+//! with it as `seq`, so Conway can order events however batches were
+//! retried, and see a gap where overflow dropped some. Numbers only
+//! increase, across reboots too: the firmware persists a bound ahead of
+//! the numbers in use (see [`SeqLease`]) and resumes the buffer there.
+
+use crate::clock;
 
 /// A single swipe event: which credential was presented and whether the
 /// local cache authorized it. Buffered locally and POSTed to Conway during
@@ -8,3 +18,360 @@ pub struct AccessEvent {
     pub fob: u32,
     pub allowed: bool,
 }
+
+/// Ring capacity. One slot stays empty to tell full from empty, so at
+/// most `MAX_EVENTS - 1` events are pending.
+pub const MAX_EVENTS: usize = 20;
+
+/// Parse a `CONWAY_MAX_EVENTS_PER_SYNC` value: how many pending events
+/// one sync sends, in `1..=MAX_EVENTS`. The rest wait for the next sync.
+pub fn parse_max_per_sync(s: &str) -> Option<usize> {
+    s.parse().ok().filter(|n| (1..=MAX_EVENTS).contains(n))
+}
+
+/// Parse a `CONWAY_REPORT_GRANTS` value: `"1"` reports granted swipes
+/// to Conway alongside denials, `"0"` reports denials only.
+pub fn parse_report_grants(s: &str) -> Option<bool> {
+    match s {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Longest window [`parse_dedup_ms`] accepts.
+pub const MAX_DEDUP_MS: u64 = 10_000;
+
+/// Parse a `CONWAY_EVENT_DEDUP_MS` value: milliseconds within which a
+/// repeat of the last event is not reported, at most [`MAX_DEDUP_MS`].
+/// `0` turns suppression off.
+pub fn parse_dedup_ms(s: &str) -> Option<u64> {
+    s.parse().ok().filter(|ms| *ms <= MAX_DEDUP_MS)
+}
+
+/// Suppression of an event identical to the one before it, for readers
+/// that send a card twice for one presentation.
+///
+/// An event repeats if it has the same fob and decision as the last one
+/// kept and comes less than `window_ms` after it. Repeats are not kept,
+/// so a card held at the reader is still reported once per window.
+/// Off (window 0) by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dedup {
+    window_ms: u64,
+    /// The last event kept, and when.
+    last: Option<(AccessEvent, u64)>,
+}
+
+impl Dedup {
+    pub const fn new(window_ms: u64) -> Self {
+        Self {
+            window_ms,
+            last: None,
+        }
+    }
+
+    pub fn window_ms(&self) -> u64 {
+        self.window_ms
+    }
+
+    /// Whether `event`, decided `at_ms` after boot, repeats the last
+    /// event kept. If not, it becomes the last event kept.
+    pub fn is_repeat(&mut self, event: AccessEvent, at_ms: u64) -> bool {
+        if self.window_ms == 0 {
+            return false;
+        }
+        if let Some((last, last_ms)) = self.last {
+            if last == event && clock::elapsed(last_ms, at_ms) < self.window_ms {
+                return true;
+            }
+        }
+        self.last = Some((event, at_ms));
+        false
+    }
+}
+
+/// What [`EventRing::commit`] removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Commit {
+    /// Exactly the peeked events.
+    Exact,
+    /// Overflow dropped some of the peeked events during the sync; the
+    /// `removed` still pending were removed.
+    Adjusted { removed: usize },
+    /// Overflow had already dropped the peeked events; nothing removed.
+    AlreadyDropped,
+}
+
+/// Pending events with peek/commit semantics: a sync peeks the pending
+/// events, and only commits (removes) them once the server has
+/// acknowledged them. When full, a push drops the oldest event.
+///
+/// Grants can be left out (see [`set_report_grants`](Self::set_report_grants))
+/// for sites that only want denials reported; denials are always kept.
+///
+/// Not synchronized; the firmware wraps it in a mutex (`sync::EventBuffer`).
+#[derive(Clone, Debug)]
+pub struct EventRing {
+    events: [AccessEvent; MAX_EVENTS],
+    head: usize,  // next write position
+    tail: usize,  // next read position
+    removed: u64, // events ever removed (committed or dropped); seq of `tail`
+    report_grants: bool,
+}
+
+impl Default for EventRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventRing {
+    pub const fn new() -> Self {
+        Self {
+            events: [AccessEvent {
+                fob: 0,
+                allowed: false,
+            }; MAX_EVENTS],
+            head: 0,
+            tail: 0,
+            removed: 0,
+            report_grants: true,
+        }
+    }
+
+    /// Whether [`push`](Self::push) keeps granted events (the default).
+    /// Events already pending are unaffected.
+    pub fn set_report_grants(&mut self, on: bool) {
+        self.report_grants = on;
+    }
+
+    pub fn report_grants(&self) -> bool {
+        self.report_grants
+    }
+
+    /// Number the next event pushed `seq`, so numbering continues from a
+    /// previous boot. Only while nothing is pending; false otherwise.
+    pub fn resume_at(&mut self, seq: u64) -> bool {
+        if !self.is_empty() {
+            return false;
+        }
+        self.removed = seq;
+        true
+    }
+
+    /// Sequence number the next event pushed will get.
+    pub fn next_seq(&self) -> u64 {
+        self.removed + self.len() as u64
+    }
+
+    /// Pending events.
+    pub fn len(&self) -> usize {
+        if self.head >= self.tail {
+            self.head - self.tail
+        } else {
+            MAX_EVENTS - self.tail + self.head
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    fn is_full(&self) -> bool {
+        (self.head + 1) % MAX_EVENTS == self.tail
+    }
+
+    /// Append an event. Returns true if the oldest event was dropped to
+    /// make room. A grant while grants are not reported is discarded
+    /// and returns false.
+    pub fn push(&mut self, event: AccessEvent) -> bool {
+        if event.allowed && !self.report_grants {
+            return false;
+        }
+        let dropped = self.is_full();
+        if dropped {
+            self.tail = (self.tail + 1) % MAX_EVENTS;
+            self.removed += 1;
+        }
+        self.events[self.head] = event;
+        self.head = (self.head + 1) % MAX_EVENTS;
+        dropped
+    }
+
+    /// Copy the pending events into `out` without removing them.
+    /// Returns (count, first_seq): `first_seq` is the absolute sequence
+    /// number of the first event, which never repeats, and is passed to
+    /// [`commit`](Self::commit) after a successful sync.
+    pub fn peek(&self, out: &mut [AccessEvent; MAX_EVENTS]) -> (usize, u64) {
+        self.peek_up_to(out, MAX_EVENTS)
+    }
+
+    /// [`peek`](Self::peek) at most the `max` oldest pending events.
+    /// Committing them leaves the rest pending for the next peek.
+    pub fn peek_up_to(&self, out: &mut [AccessEvent; MAX_EVENTS], max: usize) -> (usize, u64) {
+        let max = max.min(MAX_EVENTS);
+        let mut count = 0;
+        let mut idx = self.tail;
+        while idx != self.head && count < max {
+            out[count] = self.events[idx];
+            count += 1;
+            idx = (idx + 1) % MAX_EVENTS;
+        }
+        (count, self.removed)
+    }
+
+    /// Remove the `count` events peeked at `first_seq`. Overflow may
+    /// have dropped some or all of them during the sync; events pushed
+    /// after the peek are never removed.
+    pub fn commit(&mut self, count: usize, first_seq: u64) -> Commit {
+        if count == 0 {
+            return Commit::Exact;
+        }
+        let end = first_seq + count as u64;
+        if self.removed >= end {
+            return Commit::AlreadyDropped;
+        }
+        // Everything from `removed` up to `end` is still pending.
+        let n = (end - self.removed) as usize;
+        self.tail = (self.tail + n) % MAX_EVENTS;
+        self.removed = end;
+        if n == count {
+            Commit::Exact
+        } else {
+            Commit::Adjusted { removed: n }
+        }
+    }
+}
+
+/// Sequence numbers reserved by one flash write.
+pub const SEQ_LEASE: u64 = 1024;
+
+/// The persisted bound on event sequence numbers.
+///
+/// Writing flash for every event would wear it out and stall the
+/// decision loop, so the firmware persists a limit instead: every number
+/// below it may have been used. After a reboot numbering resumes at the
+/// stored limit, skipping whatever was left of the lease. The lease is
+/// extended well before numbering reaches the limit, from a task that
+/// may write flash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SeqLease {
+    limit: u64,
+}
+
+impl SeqLease {
+    /// The lease found in flash; 0 when there is none.
+    pub const fn resume(stored: u64) -> Self {
+        Self { limit: stored }
+    }
+
+    /// Numbers from here up have not been used; a boot resumes
+    /// numbering here.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// The limit to persist if the lease should be extended with
+    /// `next_seq` up next: once less than half a lease is left. Persist
+    /// it, then [`extended`](Self::extended).
+    pub fn renewal(&self, next_seq: u64) -> Option<u64> {
+        (self.limit.saturating_sub(next_seq) < SEQ_LEASE / 2)
+            .then(|| next_seq.saturating_add(SEQ_LEASE))
+    }
+
+    /// The limit from [`renewal`](Self::renewal) was persisted.
+    pub fn extended(&mut self, limit: u64) {
+        self.limit = self.limit.max(limit);
+    }
+}
+
+/// Decisions kept for `GET /diag/events`.
+pub const AUDIT_LEN: usize = 32;
+
+/// One decision in the audit ring, stamped with uptime.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub event: AccessEvent,
+    /// Milliseconds after boot.
+    pub at_ms: u64,
+}
+
+/// The last [`AUDIT_LEN`] access decisions, for viewing on site.
+///
+/// Unlike [`EventRing`] nothing is ever committed out of it: reading it
+/// is free, and the oldest entry is overwritten once it is full. Events
+/// land here whether or not they are still waiting to reach Conway.
+#[derive(Clone, Debug)]
+pub struct AuditRing {
+    entries: [AuditEntry; AUDIT_LEN],
+    next: usize, // next write position
+    total: u64,  // entries ever pushed
+}
+
+impl Default for AuditRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditRing {
+    pub const fn new() -> Self {
+        Self {
+            entries: [AuditEntry {
+                event: AccessEvent {
+                    fob: 0,
+                    allowed: false,
+                },
+                at_ms: 0,
+            }; AUDIT_LEN],
+            next: 0,
+            total: 0,
+        }
+    }
+
+    /// Record `event`, decided `at_ms` after boot.
+    pub fn push(&mut self, event: AccessEvent, at_ms: u64) {
+        self.entries[self.next] = AuditEntry { event, at_ms };
+        self.next = (self.next + 1) % AUDIT_LEN;
+        self.total += 1;
+    }
+
+    /// Entries held, at most [`AUDIT_LEN`].
+    pub fn len(&self) -> usize {
+        self.total.min(AUDIT_LEN as u64) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Entries pushed since boot, including overwritten ones.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Held entries, newest first.
+    pub fn iter(&self) -> impl Iterator<Item = &AuditEntry> + '_ {
+        (1..=self.len())
+            .map(move |back| &self.entries[(self.next + AUDIT_LEN - back) % AUDIT_LEN])
+    }
+
+    /// JSON body for the endpoint:
+    /// `{"total":N,"events":[{"fob":N,"allowed":B,"at_ms":N},...]}`,
+    /// newest first.
+    pub fn render_json<W: core::fmt::Write>(&self, out: &mut W) -> core::fmt::Result {
+        write!(out, r#"{{"total":{},"events":["#, self.total)?;
+        for (i, e) in self.iter().enumerate() {
+            if i > 0 {
+                out.write_str(",")?;
+            }
+            write!(
+                out,
+                r#"{{"fob":{},"allowed":{},"at_ms":{}}}"#,
+                e.event.fob, e.event.allowed, e.at_ms
+            )?;
+        }
+        out.write_str("]}\n")
+    }
+}
@@ -0,0 +1,40 @@
+//! Conway server failover selection.
+//!
+//! The controller can be configured with a primary Conway host plus a
+//! short list of fallbacks. Each sync walks the list starting from the
+//! host that last answered, wrapping around, and stops at the first one
+//! that completes a round-trip. The winner is remembered so the next
+//! sync goes straight to it rather than re-probing a dead primary
+//! (with a 10 s connect timeout) every cycle.
+//!
+//! Pure bookkeeping only; the firmware's `sync` module performs the
+//! actual attempts.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Failover {
+    last_good: usize,
+}
+
+impl Failover {
+    pub const fn new() -> Self {
+        Self { last_good: 0 }
+    }
+
+    /// Index of the host that most recently completed a sync.
+    pub fn last_good(&self) -> usize {
+        self.last_good
+    }
+
+    /// Order in which to try `n` hosts this cycle: the last-good host
+    /// first, then the rest in list order, wrapping. If the host list
+    /// shrank below the remembered index, starts over from the primary.
+    pub fn order(&self, n: usize) -> impl Iterator<Item = usize> {
+        let start = if self.last_good < n { self.last_good } else { 0 };
+        (0..n).map(move |i| (start + i) % n)
+    }
+
+    /// Record that host `idx` completed a sync.
+    pub fn succeeded(&mut self, idx: usize) {
+        self.last_good = idx;
+    }
+}
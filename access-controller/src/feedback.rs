@@ -0,0 +1,96 @@
+//! Reader LED and beeper patterns played after an access decision.
+//!
+//! The defaults are a 100 ms beep with the LED on for a grant and three
+//! short beeps for a denial. Sites can replace either at build time
+//! (`CONWAY_FEEDBACK_GRANT`, `CONWAY_FEEDBACK_DENY`) with a list of
+//! comma-separated steps, each `<outputs>:<ms>`. `outputs` names what is
+//! on for the step, `L` for the LED and `B` for the beeper, or `-` for
+//! neither; the empty string is silence. The default grant is `LB:100,L:100`.
+//!
+//! Both outputs are off once a pattern ends. The feedback task is busy
+//! while it plays, so patterns are kept short: at most [`MAX_CUES`] steps
+//! and [`MAX_PATTERN_MS`] in total.
+
+use heapless::Vec as HVec;
+
+/// Most steps in one pattern.
+pub const MAX_CUES: usize = 16;
+/// Longest one pattern may run.
+pub const MAX_PATTERN_MS: u64 = 3_000;
+
+/// One step: which outputs are on, and for how long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cue {
+    pub led: bool,
+    pub beep: bool,
+    pub ms: u64,
+}
+
+impl Cue {
+    const fn new(led: bool, beep: bool, ms: u64) -> Self {
+        Self { led, beep, ms }
+    }
+}
+
+/// A whole pattern, played once.
+pub type Cues = HVec<Cue, MAX_CUES>;
+
+/// LED on throughout, with a beep for the first half.
+pub const DEFAULT_GRANT: &[Cue] = &[Cue::new(true, true, 100), Cue::new(true, false, 100)];
+
+/// Three short beeps, LED off.
+pub const DEFAULT_DENY: &[Cue] = &[
+    Cue::new(false, true, 100),
+    Cue::new(false, false, 100),
+    Cue::new(false, true, 100),
+    Cue::new(false, false, 100),
+    Cue::new(false, true, 100),
+    Cue::new(false, false, 100),
+];
+
+/// `cues` as a pattern. Only for the defaults, which fit.
+pub fn cues(cues: &[Cue]) -> Cues {
+    HVec::from_slice(cues).unwrap_or_default()
+}
+
+/// Parse a `CONWAY_FEEDBACK_GRANT` / `CONWAY_FEEDBACK_DENY` value. `None`
+/// if a step is malformed, names an output twice, lasts 0 ms, or the
+/// pattern has too many steps or runs too long.
+pub fn parse(s: &str) -> Option<Cues> {
+    let mut out = Cues::new();
+    let s = s.trim();
+    if s.is_empty() {
+        return Some(out);
+    }
+    let mut total: u64 = 0;
+    for step in s.split(',') {
+        let (outputs, ms) = step.trim().split_once(':')?;
+        let ms: u64 = ms.trim().parse().ok().filter(|&ms| ms > 0)?;
+        let mut cue = Cue::new(false, false, ms);
+        match outputs.trim() {
+            "-" => {}
+            outputs => {
+                for c in outputs.chars() {
+                    let on = match c.to_ascii_uppercase() {
+                        'L' => &mut cue.led,
+                        'B' => &mut cue.beep,
+                        _ => return None,
+                    };
+                    if *on {
+                        return None;
+                    }
+                    *on = true;
+                }
+                if !(cue.led || cue.beep) {
+                    return None;
+                }
+            }
+        }
+        total = total.saturating_add(ms);
+        if total > MAX_PATTERN_MS {
+            return None;
+        }
+        out.push(cue).ok()?;
+    }
+    Some(out)
+}
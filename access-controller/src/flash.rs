@@ -0,0 +1,31 @@
+//! [`FlashBackend`] over the ESP SPI flash, for the record stores in
+//! `settings`, `fob_store` and `cache_store`.
+
+use embedded_storage::nor_flash::NorFlash;
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::FlashStorage;
+
+use access_controller::storage::FlashBackend;
+
+pub struct EspFlash(FlashStorage);
+
+impl EspFlash {
+    pub fn new() -> Self {
+        Self(FlashStorage::new())
+    }
+}
+
+impl FlashBackend for EspFlash {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), &'static str> {
+        ReadStorage::read(&mut self.0, offset, buf).map_err(|_| "flash read failed")
+    }
+
+    /// `FlashStorage::write` does the read-modify-erase-write itself.
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), &'static str> {
+        Storage::write(&mut self.0, offset, data).map_err(|_| "flash write failed")
+    }
+
+    fn erase(&mut self, offset: u32, len: u32) -> Result<(), &'static str> {
+        NorFlash::erase(&mut self.0, offset, offset + len).map_err(|_| "flash erase failed")
+    }
+}
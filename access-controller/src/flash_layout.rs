@@ -0,0 +1,117 @@
+//! Flash offsets per supported chip.
+//!
+//! The storage modules (`settings`, `cache_store`, `fob_store`,
+//! `swipe_log`, `seq_store`) write raw sectors at fixed offsets inside
+//! the `nvs`, `fobs` and `evseq` partitions of `partitions.csv`. Those offsets are collected
+//! here, selected by the board-profile feature (`esp32` or `esp32s3`),
+//! so the firmware and the host tests agree on them.
+//!
+//! The two chips differ only in where the ROM loads the second-stage
+//! bootloader from (0x1000 on the ESP32, 0x0 on the ESP32-S3). Both
+//! bootloaders end below the partition table at 0x8000, so one
+//! `partitions.csv` serves both and every data offset is shared.
+//!
+//! Within the partitions:
+//!
+//! ```text
+//!   nvs   sectors 0-1   settings ping-pong
+//!   nvs   sectors 2-3   Conway fob cache ping-pong
+//!   nvs   sector  4     boot-time flash check scratch
+//!   fobs  sectors 0-1   local fob list ping-pong
+//!   fobs  sectors 2-    swipe log queue, to the end of the partition
+//!   evseq sectors 0-1   event sequence lease ping-pong
+//! ```
+//!
+//! `evseq` sits in what was free space past the OTA slots, since the
+//! other partitions have no two sectors to spare.
+
+use core::ops::Range;
+
+/// Flash erase granularity and the storage modules' slot size.
+pub const SECTOR: u32 = 4096;
+
+/// Chip the firmware is built for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip {
+    Esp32,
+    Esp32S3,
+}
+
+/// The chip selected by the board-profile feature. Host builds (`sim`)
+/// without `esp32s3` use the ESP32 layout.
+#[cfg(feature = "esp32s3")]
+pub const CHIP: Chip = Chip::Esp32S3;
+#[cfg(not(feature = "esp32s3"))]
+pub const CHIP: Chip = Chip::Esp32;
+
+/// Flash layout of [`CHIP`].
+pub const FLASH: FlashLayout = FlashLayout::for_chip(CHIP);
+
+/// Offsets of the regions the firmware reads or writes directly. All
+/// are sector aligned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlashLayout {
+    /// Second-stage bootloader, written by espflash.
+    pub bootloader: u32,
+    /// ESP-IDF partition table.
+    pub partition_table: u32,
+    /// `nvs` partition: start and length.
+    pub nvs: u32,
+    pub nvs_len: u32,
+    /// `fobs` partition: start and length.
+    pub fobs: u32,
+    pub fobs_len: u32,
+    /// `evseq` partition: start and length.
+    pub evseq: u32,
+    pub evseq_len: u32,
+}
+
+impl FlashLayout {
+    pub const fn for_chip(chip: Chip) -> Self {
+        let bootloader = match chip {
+            Chip::Esp32 => 0x1000,
+            Chip::Esp32S3 => 0x0,
+        };
+        Self {
+            bootloader,
+            partition_table: 0x8000,
+            nvs: 0x9000,
+            nvs_len: 0x6000,
+            fobs: 0x11000,
+            fobs_len: 0xF000,
+            evseq: 0x3E0000,
+            evseq_len: 0x2000,
+        }
+    }
+
+    /// `settings` ping-pong sectors.
+    pub const fn settings_slots(&self) -> [u32; 2] {
+        [self.nvs, self.nvs + SECTOR]
+    }
+
+    /// `cache_store` ping-pong sectors.
+    pub const fn cache_slots(&self) -> [u32; 2] {
+        [self.nvs + 2 * SECTOR, self.nvs + 3 * SECTOR]
+    }
+
+    /// Scratch sector for the boot-time flash check
+    /// ([`crate::storage::check_writable`]). Holds nothing between boots.
+    pub const fn scratch(&self) -> u32 {
+        self.nvs + 4 * SECTOR
+    }
+
+    /// `fob_store` ping-pong sectors.
+    pub const fn fob_slots(&self) -> [u32; 2] {
+        [self.fobs, self.fobs + SECTOR]
+    }
+
+    /// `seq_store` ping-pong sectors.
+    pub const fn seq_slots(&self) -> [u32; 2] {
+        [self.evseq, self.evseq + SECTOR]
+    }
+
+    /// `swipe_log` queue: the rest of the `fobs` partition.
+    pub const fn swipe_log(&self) -> Range<u32> {
+        self.fobs + 2 * SECTOR..self.fobs + self.fobs_len
+    }
+}
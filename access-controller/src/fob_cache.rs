@@ -0,0 +1,225 @@
+//! Pure helpers for the flash-persisted copy of the Conway fob cache.
+//!
+//! The firmware serves authorization decisions from the in-RAM cache
+//! (`FOBS` in `main.rs`) and mirrors it to flash after every accepted
+//! sync (see `src/cache_store.rs`), so a reboot during a Conway outage
+//! comes back up with the last-known list instead of an empty one.
+//!
+//! The two copies can drift apart: a flash write that fails (or is cut
+//! short by a power loss) after the RAM cache was already replaced leaves
+//! flash one generation behind. Each copy therefore carries a monotonic
+//! `seq` plus a content digest ([`CacheMeta`]), and [`reconcile`] decides
+//! which one wins when they disagree. The firmware runs it at boot (RAM
+//! is empty, flash wins if present) and after each sync (RAM is newer if
+//! the last save failed, so the save is retried).
+//!
+//! ## Plaintext payload
+//!
+//! ```text
+//!   host_flag u8               (1 if an ETag is stored, else 0)
+//!   host      4 bytes          (IPv4 of the host that issued the ETag,
+//!                               only when host_flag == 1)
+//!   etag_len  u8               (0 when host_flag == 0)
+//!   etag      ascii[etag_len]  (max 64; see [`crate::etag::valid_tag`])
+//!   count     u16 LE
+//!   fobs      u32 LE * count
+//! ```
+//!
+//! The firmware wraps this in the [`crate::crypto`] envelope, exactly
+//! like `fob_store` and `settings` do.
+
+use alloc::vec::Vec;
+use heapless::Vec as HVec;
+
+use crate::etag::{HostEtag, MAX_ETAG_LEN};
+
+/// Capacity of the Conway fob list. The single source for the RAM cache
+/// (`FOBS` in `main.rs`), the flash payload and both sync response
+/// parsers; everything else is sized from it.
+pub const MAX_FOBS: usize = 512;
+
+// The payload's `count` field is a u16.
+const _: () = assert!(MAX_FOBS <= u16::MAX as usize);
+
+/// Summary of one copy of the cache, used to compare RAM against flash
+/// without holding both lists at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheMeta {
+    /// Monotonic generation. Bumped by the firmware every time a sync
+    /// replaces the RAM cache; persisted alongside the list.
+    pub seq: u64,
+    /// [`digest`] of the fob list.
+    pub digest: u32,
+    /// Number of fobs in the list.
+    pub count: usize,
+}
+
+impl CacheMeta {
+    pub fn of(seq: u64, fobs: &[u32]) -> Self {
+        Self {
+            seq,
+            digest: digest(fobs),
+            count: fobs.len(),
+        }
+    }
+}
+
+/// Outcome of comparing the RAM cache against the flash copy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reconcile {
+    /// Neither copy exists (first boot, or never synced).
+    Empty,
+    /// Both copies exist and hold the same list.
+    InSync,
+    /// RAM is authoritative: flash is missing, unreadable, or older.
+    /// The caller should re-persist RAM.
+    PreferLive,
+    /// Flash is authoritative: RAM is missing or older. The caller
+    /// should load flash into RAM.
+    PreferFlash,
+}
+
+/// Decide which copy of the cache to trust.
+///
+/// Identical content is `InSync` regardless of `seq` (a save that landed
+/// but whose bookkeeping didn't is harmless). Otherwise the higher `seq`
+/// wins, using the same wrapping signed-difference comparison as the
+/// ping-pong stores; a tie with different content prefers flash, since
+/// it has passed the AEAD check and RAM has no such guarantee.
+pub fn reconcile(live: Option<CacheMeta>, flash: Option<CacheMeta>) -> Reconcile {
+    match (live, flash) {
+        (None, None) => Reconcile::Empty,
+        (Some(_), None) => Reconcile::PreferLive,
+        (None, Some(_)) => Reconcile::PreferFlash,
+        (Some(l), Some(f)) => {
+            if l.digest == f.digest && l.count == f.count {
+                Reconcile::InSync
+            } else if (f.seq.wrapping_sub(l.seq)) as i64 >= 0 {
+                Reconcile::PreferFlash
+            } else {
+                Reconcile::PreferLive
+            }
+        }
+    }
+}
+
+/// CRC-32 (IEEE 802.3, reflected, poly 0xEDB88320) of `data`.
+///
+/// Bitwise rather than table-driven: inputs are at most a few KiB and
+/// hashed once per sync, so 1 KiB of table isn't worth the flash.
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(0xFFFF_FFFF, data)
+}
+
+/// Content digest of a fob list: CRC-32 over the little-endian bytes in
+/// list order.
+pub fn digest(fobs: &[u32]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for f in fobs {
+        crc = crc32_update(crc, &f.to_le_bytes());
+    }
+    !crc
+}
+
+/// Order-independent digest of a fob list: [`digest`] of a sorted copy.
+/// Sent as `X-Fob-CRC` so the server can tell a cache that drifted from
+/// the list its ETag names.
+pub fn sorted_digest(fobs: &[u32]) -> u32 {
+    let mut sorted = fobs.to_vec();
+    sorted.sort_unstable();
+    digest(&sorted)
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    crc
+}
+
+/// Serialize an ETag + fob list into the plaintext payload.
+pub fn encode(etag: &HostEtag, fobs: &[u32]) -> Vec<u8> {
+    let n = fobs.len().min(u16::MAX as usize);
+    let mut out = Vec::with_capacity(6 + etag.as_str().len() + 2 + n * 4);
+    match etag.host() {
+        None => out.extend_from_slice(&[0, 0]),
+        Some(h) => {
+            out.push(1);
+            out.extend_from_slice(&h);
+            out.push(etag.as_str().len() as u8);
+            out.extend_from_slice(etag.as_str().as_bytes());
+        }
+    }
+    push_list(&mut out, fobs);
+    out
+}
+
+/// Append `count u16 LE` + packed `u32 LE` fobs. Lists longer than
+/// `u16::MAX` are truncated (callers cap far below that).
+pub(crate) fn push_list(out: &mut Vec<u8>, fobs: &[u32]) {
+    let n = fobs.len().min(u16::MAX as usize);
+    out.extend_from_slice(&(n as u16).to_le_bytes());
+    for f in fobs.iter().take(n) {
+        out.extend_from_slice(&f.to_le_bytes());
+    }
+}
+
+/// Parse a list written by [`push_list`] that must span all of `buf`.
+pub(crate) fn parse_list<const N: usize>(buf: &[u8]) -> Option<HVec<u32, N>> {
+    if buf.len() < 2 {
+        return None;
+    }
+    let count = u16::from_le_bytes([buf[0], buf[1]]) as usize;
+    if count > N || 2 + count * 4 != buf.len() {
+        return None;
+    }
+    let mut fobs: HVec<u32, N> = HVec::new();
+    for chunk in buf[2..].chunks_exact(4) {
+        // Cannot fail: count <= N.
+        let _ = fobs.push(u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+    }
+    Some(fobs)
+}
+
+/// Parse a plaintext payload produced by [`encode`]. Returns `None` on
+/// any structural error, including a list longer than `N`.
+pub fn decode<const N: usize>(buf: &[u8]) -> Option<(HostEtag, HVec<u32, N>)> {
+    let mut p = 1usize;
+    let host = match *buf.first()? {
+        0 => None,
+        1 => {
+            let h = buf.get(p..p + 4)?;
+            p += 4;
+            Some([h[0], h[1], h[2], h[3]])
+        }
+        _ => return None,
+    };
+    let etag_len = *buf.get(p)? as usize;
+    p += 1;
+    if etag_len > MAX_ETAG_LEN || p + etag_len > buf.len() {
+        return None;
+    }
+    let raw = &buf[p..p + etag_len];
+    p += etag_len;
+    let mut etag = HostEtag::new();
+    match host {
+        // A stored host always comes with a non-empty tag. A tag that
+        // isn't valid (written by older firmware, or damaged) is dropped
+        // rather than failing the payload: the list is still good, and
+        // the next sync is just unconditional.
+        Some(h) if !raw.is_empty() => {
+            if let Ok(value) = core::str::from_utf8(raw) {
+                etag.set(h, value);
+            }
+        }
+        None if raw.is_empty() => {}
+        _ => return None,
+    }
+
+    let fobs = parse_list::<N>(&buf[p..])?;
+    Some((etag, fobs))
+}
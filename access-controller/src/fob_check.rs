@@ -0,0 +1,90 @@
+//! Lookup of one fob for `GET /check?fob=N`.
+//!
+//! When a member can't get in, staff can ask the controller whether it
+//! would let the fob in without anyone swiping it. The lookup follows the
+//! access decision's precedence: the local list first, then the cache
+//! synced from Conway. It answers for the lists as they are now and
+//! leaves out what depends on the moment of a swipe (backoff after
+//! denials, fail-open after boot, the recheck sync a denial triggers).
+//!
+//! The number is matched as stored, so it must be in the form the lists
+//! hold it: the H10301 fob number, or the NFC UID for NFC credentials.
+
+use core::fmt::{self, Write};
+
+/// Which list let a fob in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// The locally managed admin list (`/fobs`).
+    Local,
+    /// The list last synced from Conway.
+    Cache,
+}
+
+impl Source {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::Local => "local",
+            Source::Cache => "cache",
+        }
+    }
+}
+
+/// The answer for one fob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FobCheck {
+    pub fob: u32,
+    /// The list that grants it, or `None` if neither does.
+    pub source: Option<Source>,
+}
+
+impl FobCheck {
+    /// Look `fob` up in `local`, then `cache`.
+    pub fn lookup(fob: u32, local: &[u32], cache: &[u32]) -> Self {
+        let source = if local.contains(&fob) {
+            Some(Source::Local)
+        } else if cache.contains(&fob) {
+            Some(Source::Cache)
+        } else {
+            None
+        };
+        Self { fob, source }
+    }
+
+    pub fn allowed(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Render as `{"fob":N,"allowed":B,"source":"local"|"cache"|null}`.
+    pub fn render<W: Write>(&self, out: &mut W) -> fmt::Result {
+        write!(out, r#"{{"fob":{},"allowed":{},"source":"#, self.fob, self.allowed())?;
+        match self.source {
+            Some(source) => write!(out, r#""{}"}}"#, source.as_str()),
+            None => out.write_str("null}"),
+        }
+    }
+}
+
+/// The fob a `/check` query string asks about: its `fob` parameter, in
+/// decimal. Other parameters are ignored; a missing, repeated, empty,
+/// zero or non-numeric `fob` is an error.
+pub fn parse_query(query: &str) -> Result<u32, &'static str> {
+    let mut found = None;
+    for pair in query.split('&') {
+        let Some(value) = pair.strip_prefix("fob=") else {
+            continue;
+        };
+        if found.is_some() {
+            return Err("fob given twice");
+        }
+        found = Some(value);
+    }
+    let value = found.ok_or("missing fob")?;
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("fob must be a decimal number");
+    }
+    match value.parse() {
+        Ok(0) | Err(_) => Err("fob out of range"),
+        Ok(fob) => Ok(fob),
+    }
+}
@@ -5,6 +5,7 @@
 //! Same ping-pong + monotonic-seq design as `settings.rs`: two 4 KiB
 //! sectors at the start of the `fobs` partition, written alternately so
 //! a power loss mid-write always leaves the previous-good sector intact.
+//! The slot logic is [`access_controller::storage`].
 //!
 //! ## Confidentiality
 //!
@@ -50,19 +51,16 @@
 //! design choice to keep this module simple and to eliminate a
 //! plaintext-fallback codepath that would silently degrade security.
 
-use embedded_storage::{ReadStorage, Storage};
-use esp_storage::FlashStorage;
 use heapless::{String as HString, Vec as HVec};
 
 use crate::device_key;
+use crate::flash::EspFlash;
 use access_controller::crypto;
+use access_controller::flash_layout::FLASH;
+use access_controller::storage::{Slot, Storage, StoreConfig};
 
-/// Start of the `fobs` partition. Keep in sync with `partitions.csv`.
-const FOBS_BASE: u32 = 0x11000;
-/// Flash erase granularity / our logical slot size.
-const SECTOR: u32 = 4096;
 /// Ping-pong: first two sectors of the partition.
-const SLOTS: [u32; 2] = [FOBS_BASE, FOBS_BASE + SECTOR];
+const SLOTS: [u32; 2] = FLASH.fob_slots();
 
 /// Per-store magic (preserved across format versions for log clarity).
 const MAGIC: u32 = 0x46_4F_42_53; // "FOBS"
@@ -133,88 +131,16 @@ fn deserialize(buf: &[u8]) -> Option<HVec<LocalFob, MAX_LOCAL_FOBS>> {
 
 // ---------- sector I/O ------------------------------------------------
 
-struct Record {
-    seq: u64,
-    payload: alloc::vec::Vec<u8>,
-}
-
-fn read_slot(flash: &mut FlashStorage, base: u32, key: &[u8; 32]) -> Option<Record> {
-    // Read header first to learn payload_len, then read the rest.
-    let mut hdr = [0u8; crypto::HEADER_LEN];
-    flash.read(base, &mut hdr).ok()?;
-    let (seq, payload_len) = crypto::parse_header(&hdr, MAGIC, crypto::DOMAIN_FOBS)?;
-    let pt_len = payload_len as usize;
-    if pt_len > MAX_PLAINTEXT
-        || crypto::HEADER_LEN + pt_len + crypto::TAG_LEN > SECTOR as usize
-    {
-        return None;
-    }
-
-    let total = crypto::HEADER_LEN + pt_len + crypto::TAG_LEN;
-    let mut sealed = alloc::vec![0u8; total];
-    flash.read(base, &mut sealed).ok()?;
-
-    let mut plaintext = alloc::vec![0u8; pt_len];
-    match crypto::open(key, MAGIC, crypto::DOMAIN_FOBS, &sealed, &mut plaintext) {
-        Ok(_n) => Some(Record { seq, payload: plaintext }),
-        Err(e) => {
-            log::warn!("fob_store: slot @0x{:X} AEAD open failed: {:?}", base, e);
-            None
-        }
-    }
-}
-
-fn write_slot(
-    flash: &mut FlashStorage,
-    base: u32,
-    seq: u64,
-    fobs: &[LocalFob],
-    key: &[u8; 32],
-) -> Result<(), &'static str> {
-    let plaintext = serialize(fobs);
-    if plaintext.len() > MAX_PLAINTEXT {
-        return Err("payload too large");
-    }
-    let total = crypto::HEADER_LEN + plaintext.len() + crypto::TAG_LEN;
-    if total > SECTOR as usize {
-        return Err("payload too large");
-    }
-    if fobs.len() > u16::MAX as usize {
-        return Err("too many fobs");
-    }
-
-    // Build full sector buffer so the underlying FlashStorage write is a
-    // single sector-aligned erase+program. Unused tail stays 0xFF so a
-    // future shorter record's read past payload_len cannot leak stale
-    // ciphertext (the AEAD never reads past the declared len anyway).
-    let mut buf = alloc::vec![0xFFu8; SECTOR as usize];
-    crypto::seal(key, MAGIC, seq, crypto::DOMAIN_FOBS, &plaintext, &mut buf[..total])
-        .map_err(|_| "crypto seal failed")?;
-
-    flash.write(base, &buf).map_err(|_| "flash write failed")?;
-    Ok(())
-}
-
-fn erase_slot(flash: &mut FlashStorage, base: u32) -> Result<(), &'static str> {
-    let blank = alloc::vec![0xFFu8; SECTOR as usize];
-    flash.write(base, &blank).map_err(|_| "flash erase failed")
-}
-
-/// Read just the 32-byte envelope header from a slot and return its
-/// `seq` if the header is structurally valid (magic / version / nonce
-/// consistent), regardless of whether the AEAD body decrypts.
-///
-/// Used by [`save`] to derive `next_seq`: if a previous save was
-/// interrupted mid-write, the half-written slot's tag/ciphertext will
-/// fail to open and `read_slot` returns `None`, but the header itself
-/// is the first thing written and is almost always intact. Skipping
-/// such a slot when picking `next_seq` would let a retry reuse the same
-/// nonce with different plaintext — catastrophic for ChaCha20-Poly1305.
-/// Parsing the header recovers the seq cheaply and closes that gap.
-fn peek_slot_seq(flash: &mut FlashStorage, base: u32) -> Option<u64> {
-    let mut hdr = [0u8; crypto::HEADER_LEN];
-    flash.read(base, &mut hdr).ok()?;
-    crypto::parse_header(&hdr, MAGIC, crypto::DOMAIN_FOBS).map(|(seq, _)| seq)
+fn store() -> Storage<EspFlash> {
+    Storage::new(
+        EspFlash::new(),
+        StoreConfig {
+            slots: SLOTS,
+            magic: MAGIC,
+            domain: crypto::DOMAIN_FOBS,
+            max_payload: MAX_PLAINTEXT,
+        },
+    )
 }
 
 // ---------- public API ------------------------------------------------
@@ -229,24 +155,16 @@ pub fn load() -> HVec<LocalFob, MAX_LOCAL_FOBS> {
         }
         return HVec::new();
     };
-    let mut flash = FlashStorage::new();
-    let a = read_slot(&mut flash, SLOTS[0], key);
-    let b = read_slot(&mut flash, SLOTS[1], key);
-    let winner = match (a, b) {
-        (Some(a), Some(b)) => {
-            // Signed diff handles u64 wraparound (irrelevant in practice
-            // — saves are operator-driven — but free correctness).
-            if (a.seq.wrapping_sub(b.seq)) as i64 >= 0 {
-                a
-            } else {
-                b
-            }
+    let loaded = store().load(key);
+    for (i, slot) in loaded.slots.iter().enumerate() {
+        if let Slot::Rejected { seq } = slot {
+            log::warn!("fob_store: slot {} (seq={}) failed to open", i, seq);
         }
-        (Some(a), None) => a,
-        (None, Some(b)) => b,
-        (None, None) => return HVec::new(),
-    };
-    deserialize(&winner.payload).unwrap_or_default()
+    }
+    match loaded.into_record() {
+        Some(r) => deserialize(&r.payload).unwrap_or_default(),
+        None => HVec::new(),
+    }
 }
 
 /// Persist new fob list. Writes to the older slot, then erases the other.
@@ -255,45 +173,16 @@ pub fn save(fobs: &[LocalFob]) -> Result<(), &'static str> {
     let Some(key) = device_key::fobs_key() else {
         return Err("device not provisioned (eFuse BLOCK3 unset)");
     };
-    let mut flash = FlashStorage::new();
-    let a = read_slot(&mut flash, SLOTS[0], key);
-    let b = read_slot(&mut flash, SLOTS[1], key);
-
-    // Pick write slot based on which successfully-opened slot is older
-    // (or use slot 0 if neither opens).
-    let write_idx: u8 = match (&a, &b) {
-        (None, None) => 0,
-        (None, Some(_)) => 0,
-        (Some(_), None) => 1,
-        (Some(ra), Some(rb)) => {
-            if (ra.seq.wrapping_sub(rb.seq)) as i64 >= 0 {
-                1
-            } else {
-                0
-            }
-        }
-    };
-
-    // Compute next_seq from ANY parseable header (open success not
-    // required) to avoid nonce reuse after an interrupted prior save.
-    // See H1 in the security review and `peek_slot_seq` docs above.
-    let seq_a = peek_slot_seq(&mut flash, SLOTS[0]);
-    let seq_b = peek_slot_seq(&mut flash, SLOTS[1]);
-    let max_hdr_seq = match (seq_a, seq_b) {
-        (Some(x), Some(y)) => Some(if (x.wrapping_sub(y)) as i64 >= 0 { x } else { y }),
-        (Some(x), None) | (None, Some(x)) => Some(x),
-        (None, None) => None,
-    };
-    let next_seq = max_hdr_seq.map(|s| s.wrapping_add(1)).unwrap_or(1u64);
+    let plaintext = serialize(fobs);
 
-    write_slot(&mut flash, SLOTS[write_idx as usize], next_seq, fobs, key)?;
-    let other = (1 - write_idx) as usize;
-    let _ = erase_slot(&mut flash, SLOTS[other]);
+    let mut store = store();
+    let saved = store.save(key, &plaintext)?;
+    let _ = store.erase_slot(1 - saved.slot);
 
     log::info!(
         "fob_store: saved seq={} to slot {} ({} fobs, encrypted)",
-        next_seq,
-        write_idx,
+        saved.seq,
+        saved.slot,
         fobs.len()
     );
     Ok(())
@@ -302,9 +191,7 @@ pub fn save(fobs: &[LocalFob]) -> Result<(), &'static str> {
 /// Wipe both slots. Always succeeds even if the device is unprovisioned
 /// (factory reset must work on broken units too).
 pub fn erase() -> Result<(), &'static str> {
-    let mut flash = FlashStorage::new();
-    erase_slot(&mut flash, SLOTS[0])?;
-    erase_slot(&mut flash, SLOTS[1])?;
+    store().erase()?;
     log::warn!("fob_store: wiped");
     Ok(())
 }
@@ -0,0 +1,127 @@
+//! Fuzzing entry points for the parsers that see untrusted input.
+//!
+//! Each function takes nothing but raw bytes and drives the same code
+//! the firmware runs on them. The `cargo fuzz` targets in `fuzz/` are
+//! one-line wrappers around these, and `tests/fuzz.rs` replays the seed
+//! corpora and past crashes through them. None of them may panic,
+//! whatever the input.
+
+use alloc::vec::Vec;
+
+use crate::decode::{self, FobFormat, WiegandRead};
+use crate::fob_cache::MAX_FOBS;
+use crate::http_client::{self, RedirectPolicy, SyncTarget, MAX_REDIRECTS};
+use crate::sync_flow::{self, Outcome, Response, SyncConfig};
+use crate::wire::{self, EventEncoding, EventFields, SyncProtocol};
+
+/// Host every sync response is taken to come from; redirects are
+/// resolved against it.
+pub const SYNC_HOST: [u8; 4] = [10, 0, 0, 2];
+
+/// Key a signed response is checked against. Nobody holds its secret
+/// half, so a signed run stops at the gate, after the header lookup and
+/// base64 decode.
+const SIGNING_KEY: [u8; 32] = [0x42; 32];
+
+/// Shared secret an HMAC-checked response is verified under.
+pub const HMAC_KEY: &[u8] = b"fuzz-hmac-key";
+
+/// Nonce a run with nonces on is taken to have sent.
+pub const NONCE: &str = "0123456789abcdef";
+
+/// A complete sync response, as read off the socket.
+///
+/// The first byte picks the configuration: bit 0 requires a signature,
+/// bit 1 allows cross-host redirects, bits 2–3 are the redirect hops
+/// already taken, bit 4 reads string fob IDs in [`FobFormat::Normalize`],
+/// bit 5 requires an HMAC under [`HMAC_KEY`], bit 6 requires the
+/// response to echo [`NONCE`].
+/// The rest is the response, which goes through the same
+/// size check the read loop applies and then [`sync_flow::parse_response`].
+pub fn sync_response(data: &[u8]) -> Result<Outcome, &'static str> {
+    let Some((&flags, raw)) = data.split_first() else {
+        return Err("empty input");
+    };
+    let cfg = SyncConfig {
+        protocol: SyncProtocol::Json,
+        redirects: RedirectPolicy {
+            max_hops: MAX_REDIRECTS,
+            cross_host: flags & 0b10 != 0,
+        },
+        trusted_pubkey: (flags & 0b1 != 0).then_some(&SIGNING_KEY),
+        fob_format: if flags & 0b1_0000 != 0 {
+            FobFormat::Normalize
+        } else {
+            FobFormat::Decimal
+        },
+        hmac_key: (flags & 0b10_0000 != 0).then_some(HMAC_KEY),
+        nonce: (flags & 0b100_0000 != 0).then_some(NONCE),
+        event_fields: EventFields::DEFAULT,
+        event_encoding: EventEncoding::Verbose,
+    };
+    let hops = (flags >> 2) & 0b11;
+    let target = SyncTarget {
+        host: SYNC_HOST,
+        port: 8080,
+        path: http_client::DEFAULT_SYNC_PATH
+            .try_into()
+            .unwrap_or_default(),
+    };
+
+    http_client::check_response_size(raw)?;
+    let mut raw: Vec<u8> = raw.to_vec();
+    Ok(
+        match sync_flow::parse_response(&mut raw, &target, hops, &cfg)? {
+            Response::NotModified { .. } => Outcome::NotModified,
+            Response::Updated { fobs, .. } => Outcome::Updated { fobs: fobs.len() },
+            Response::Redirect(next) => Outcome::Redirect(next),
+        },
+    )
+}
+
+/// A fob list body in both encodings, and as a chunked body: the number
+/// of fobs each parse produced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FobLists {
+    pub json: Result<usize, &'static str>,
+    pub binary: Result<usize, &'static str>,
+    pub dechunked: Result<usize, &'static str>,
+}
+
+/// A response body, parsed as each encoding the sync client accepts,
+/// and undone as a chunked body.
+pub fn fob_list(data: &[u8]) -> FobLists {
+    FobLists {
+        json: core::str::from_utf8(data)
+            .map_err(|_| "invalid response encoding")
+            .and_then(sync_flow::parse_fob_list)
+            .map(|fobs| fobs.len()),
+        binary: wire::decode_fob_list::<MAX_FOBS>(data).map(|fobs| fobs.len()),
+        dechunked: http_client::dechunk(data).map(|body| body.len()),
+    }
+}
+
+/// A Wiegand transmission: the first byte is the bit count (at most 64,
+/// as the reader collects), the next eight the bits, little-endian,
+/// zero-padded. Bits beyond the count are cleared, as they would never
+/// have been shifted in.
+pub fn wiegand(data: &[u8]) -> Option<WiegandRead> {
+    let (count, bits) = wiegand_frame(data);
+    decode::decode_frame(bits, count)
+}
+
+/// The `(count, bits)` a [`wiegand`] input stands for.
+pub fn wiegand_frame(data: &[u8]) -> (u32, u64) {
+    let count = data.first().map_or(0, |&c| u32::from(c) % 65);
+    let mut le = [0u8; 8];
+    for (dst, src) in le.iter_mut().zip(data.iter().skip(1)) {
+        *dst = *src;
+    }
+    let bits = u64::from_le_bytes(le);
+    let bits = if count >= 64 {
+        bits
+    } else {
+        bits & ((1u64 << count) - 1)
+    };
+    (count, bits)
+}
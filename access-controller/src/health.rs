@@ -0,0 +1,172 @@
+//! One overall health value for dashboards, from every subsystem.
+//!
+//! Each [`Component`] rates itself [`Health::Ok`], [`Health::Degraded`]
+//! (the door still works, but something needs a look) or
+//! [`Health::Critical`] (members may be turned away, or the next reboot
+//! may lose state), and [`health`] is the worst of them:
+//!
+//! | Component | Degraded                                    | Critical                            |
+//! |-----------|---------------------------------------------|-------------------------------------|
+//! | WiFi      | no link or no IPv4 address                  | —                                   |
+//! | Sync      | no list loaded yet, [`SYNC_FAILURE_THRESHOLD`] failures in a row, or offline | — |
+//! | Flash     | —                                           | boot-time check failed              |
+//! | Reader    | —                                           | keep-alives stopped                 |
+//! | Heap      | under [`LOW_HEAP_BYTES`] free               | under [`CRITICAL_HEAP_BYTES`] free  |
+//!
+//! Losing the network or Conway degrades rather than fails: decisions
+//! come from the cached list either way. A subsystem that isn't
+//! monitored (no reader keep-alive configured, flash not yet checked)
+//! counts as healthy.
+
+use core::fmt::{self, Write};
+
+use crate::status_led::{NetStatus, SYNC_FAILURE_THRESHOLD};
+
+/// Free heap below which the heap is degraded: not much more than one
+/// full sync response body takes.
+pub const LOW_HEAP_BYTES: usize = 8 * 1024;
+
+/// Free heap below which the heap is critical: the next sync or admin
+/// page is likely to fail to allocate, which resets the board.
+pub const CRITICAL_HEAP_BYTES: usize = 2 * 1024;
+
+/// Overall or per-component health, in increasing order of severity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Health {
+    Ok,
+    Degraded,
+    Critical,
+}
+
+impl Health {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Health::Ok => "ok",
+            Health::Degraded => "degraded",
+            Health::Critical => "critical",
+        }
+    }
+}
+
+/// What the firmware samples for [`health`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Inputs {
+    /// WiFi link is up (station associated, or AP started).
+    pub wifi_up: bool,
+    /// The network stack has an IPv4 address.
+    pub ip_configured: bool,
+    /// Whether anything syncs from Conway; the sync fields below are
+    /// ignored when not.
+    pub syncs: bool,
+    /// A fob list is in RAM (synced this boot or restored from flash).
+    pub fobs_loaded: bool,
+    /// Syncs failed in a row since the last success.
+    pub sync_failures: u8,
+    /// No sync has succeeded for longer than the offline threshold.
+    pub offline: bool,
+    /// Result of the boot-time flash check; `None` until it has run.
+    pub flash_healthy: Option<bool>,
+    /// Reader keep-alive state; `None` when not monitored.
+    pub reader_online: Option<bool>,
+    /// Free heap now, in bytes.
+    pub heap_free: usize,
+}
+
+impl Inputs {
+    /// The status LED's view of the same sample.
+    pub fn net_status(&self) -> NetStatus {
+        NetStatus {
+            wifi_up: self.wifi_up,
+            ip_configured: self.ip_configured,
+            fobs_loaded: !self.syncs || self.fobs_loaded,
+            sync_failures: self.sync_failures,
+            offline: self.offline,
+            critical: health(self) == Health::Critical,
+        }
+    }
+}
+
+/// A subsystem that counts towards [`health`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Component {
+    Wifi,
+    Sync,
+    Flash,
+    Reader,
+    Heap,
+}
+
+impl Component {
+    pub const ALL: [Component; 5] = [
+        Component::Wifi,
+        Component::Sync,
+        Component::Flash,
+        Component::Reader,
+        Component::Heap,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Component::Wifi => "wifi",
+            Component::Sync => "sync",
+            Component::Flash => "flash",
+            Component::Reader => "reader",
+            Component::Heap => "heap",
+        }
+    }
+
+    /// This component's health under `i`, per the table in the module
+    /// docs.
+    pub fn health(self, i: &Inputs) -> Health {
+        match self {
+            Component::Wifi if !(i.wifi_up && i.ip_configured) => Health::Degraded,
+            Component::Sync
+                if i.syncs
+                    && (!i.fobs_loaded
+                        || i.offline
+                        || i.sync_failures >= SYNC_FAILURE_THRESHOLD) =>
+            {
+                Health::Degraded
+            }
+            Component::Flash if i.flash_healthy == Some(false) => Health::Critical,
+            Component::Reader if i.reader_online == Some(false) => Health::Critical,
+            Component::Heap if i.heap_free < CRITICAL_HEAP_BYTES => Health::Critical,
+            Component::Heap if i.heap_free < LOW_HEAP_BYTES => Health::Degraded,
+            _ => Health::Ok,
+        }
+    }
+}
+
+/// Overall health: the worst of the components'.
+pub fn health(i: &Inputs) -> Health {
+    Component::ALL
+        .iter()
+        .map(|c| c.health(i))
+        .max()
+        .unwrap_or(Health::Ok)
+}
+
+/// Render the overall health followed by the components that brought
+/// it down, e.g. `critical (reader, heap)`; just `ok` when all are.
+pub fn render<W: Write>(i: &Inputs, out: &mut W) -> fmt::Result {
+    let overall = health(i);
+    out.write_str(overall.as_str())?;
+    let mut sep = " (";
+    for c in Component::ALL {
+        if c.health(i) != Health::Ok {
+            write!(out, "{}{}", sep, c.as_str())?;
+            sep = ", ";
+        }
+    }
+    if overall != Health::Ok {
+        out.write_str(")")?;
+    }
+    Ok(())
+}
+
+/// Render the overall health as a Prometheus gauge: 0 ok, 1 degraded,
+/// 2 critical.
+pub fn write_metrics<W: Write>(i: &Inputs, out: &mut W) -> fmt::Result {
+    out.write_str("# TYPE conway_health gauge\n")?;
+    writeln!(out, "conway_health {}", health(i) as u8)
+}
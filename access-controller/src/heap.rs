@@ -0,0 +1,321 @@
+//! Heap budget.
+//!
+//! `main.rs` hands esp-alloc a fixed [`HEAP_SIZE`] region; running out
+//! panics and reboots the controller. The radio driver takes its share
+//! at init, and what it leaves must hold the firmware's own peak. That
+//! peak is estimated here from the same constants the allocations use,
+//! per task, assuming every task hits its worst case at once. `main.rs`
+//! asserts at compile time that the estimate fits the heap at all, and
+//! at boot compares it with what esp-alloc reports free once the radio
+//! is up.
+//!
+//! The estimate is checked against measurement by [`LowWater`], the
+//! least free heap seen since boot, sampled by the firmware at points
+//! where allocations have just peaked. When an allocation fails anyway,
+//! the panic handler recognizes it with [`alloc_failure_size`] and logs
+//! an [`AllocFailure`] before the reset. A slow leak shows up well before
+//! that, as a [`Trend`] of samples that only ever fall.
+//!
+//! Each figure is an upper bound on the task's live heap at any moment,
+//! not a sum of everything it ever allocates. Tasks that run one step
+//! after another (sync, then the flash write) count their larger step.
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::authz;
+use crate::crypto;
+use crate::etag::MAX_ETAG_LEN;
+use crate::flash_layout::SECTOR;
+use crate::fob_cache::MAX_FOBS;
+use crate::http_client::MAX_RESPONSE_BYTES;
+use crate::request_body::BODY_MAX_CEILING;
+use crate::sync_flow::EVENTS_JSON_MAX;
+
+/// Heap region size given to esp-alloc.
+pub const HEAP_SIZE: usize = 72 * 1024;
+
+/// Local (web-UI) fobs; mirrors `fob_store::MAX_LOCAL_FOBS`.
+const LOCAL_FOBS: usize = 128;
+/// A `LocalFob` in memory: id plus a 16-byte label and its length.
+const LOCAL_FOB_BYTES: usize = 24;
+/// Plaintext of the local fob store; mirrors `fob_store::MAX_PLAINTEXT`.
+const LOCAL_STORE_PLAINTEXT: usize = 2 + LOCAL_FOBS * (4 + 1 + 16);
+
+/// What a build enables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Build {
+    /// Push channel compiled in (`CONWAY_PUSH_PATH`).
+    pub push: bool,
+    /// Largest admin form body (`CONWAY_HTTP_BODY_MAX`).
+    pub body_max: usize,
+    /// `online_check_task`s compiled in (`CONWAY_AUTHZ_PATH`): one per
+    /// reader, or 0.
+    pub online_checks: usize,
+}
+
+/// `storage::Storage::save` of a `plaintext`-byte record: the serialized
+/// record, both slots read back (sealed and opened) to pick the target,
+/// and the whole-sector write buffer.
+pub const fn store_save(plaintext: usize) -> usize {
+    let sealed = crypto::HEADER_LEN + plaintext + crypto::TAG_LEN;
+    plaintext + 2 * (sealed + plaintext) + SECTOR as usize
+}
+
+/// `sync_task`: the larger of the exchange (socket buffers, response
+/// buffer, and the request with its body) and persisting the list (a copy
+/// of it and the store save), which only starts once the exchange is over.
+pub const SYNC: usize = {
+    let exchange = MAX_RESPONSE_BYTES + 1024 + (MAX_RESPONSE_BYTES + 1) + 512 + 2 * EVENTS_JSON_MAX;
+    let cache_plaintext = 1 + 4 + 1 + MAX_ETAG_LEN + 2 + MAX_FOBS * 4;
+    let persist = MAX_FOBS * 4 + store_save(cache_plaintext);
+    max(exchange, persist)
+};
+
+/// `push_task`: its receive, transmit and frame buffers.
+pub const PUSH: usize = 1024 + 512 + 1024;
+
+/// One `online_check_task` mid-query: its socket buffers and the answer.
+pub const ONLINE_CHECK: usize =
+    authz::SOCKET_RX_BYTES + authz::SOCKET_TX_BYTES + authz::MAX_RESPONSE_BYTES;
+
+/// `http_server_task`, one connection: the larger of the local fob page
+/// (list snapshot and page) and a form post that saves the local fobs
+/// (body, new list and store save). Other pages are smaller.
+pub const fn http(body_max: usize) -> usize {
+    let fobs_page = LOCAL_FOBS * LOCAL_FOB_BYTES + 2048 + LOCAL_FOBS * 96;
+    let form_post = body_max + LOCAL_FOBS * LOCAL_FOB_BYTES + store_save(LOCAL_STORE_PLAINTEXT);
+    max(fobs_page, form_post)
+}
+
+/// Worst-case heap the firmware itself needs, on top of the radio's.
+pub const fn peak(build: Build) -> usize {
+    let push = if build.push { PUSH } else { 0 };
+    SYNC + http(build.body_max) + push + build.online_checks * ONLINE_CHECK
+}
+
+/// Whether a build's peak leaves any heap at all for the radio, with the
+/// largest form body it can be configured for.
+pub const fn fits(push: bool, online_checks: usize) -> bool {
+    peak(Build {
+        push,
+        body_max: BODY_MAX_CEILING,
+        online_checks,
+    }) < HEAP_SIZE
+}
+
+/// Least free heap seen since boot (the high-water mark of use), from
+/// samples of esp-alloc's free count.
+pub struct LowWater {
+    min_free: AtomicUsize,
+}
+
+impl LowWater {
+    pub const fn new() -> Self {
+        Self {
+            min_free: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    /// Take a sample of the free heap. Returns whether it is a new low.
+    pub fn sample(&self, free: usize) -> bool {
+        self.min_free.fetch_min(free, Ordering::Relaxed) > free
+    }
+
+    /// The lowest sample so far, or `None` before the first.
+    pub fn get(&self) -> Option<usize> {
+        match self.min_free.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            free => Some(free),
+        }
+    }
+
+    /// Render `free` and the low-water mark as Prometheus gauges.
+    pub fn write_metrics<W: Write>(&self, out: &mut W, free: usize) -> fmt::Result {
+        out.write_str("# TYPE conway_heap_free_bytes gauge\n")?;
+        writeln!(out, "conway_heap_free_bytes {}", free)?;
+        out.write_str("# TYPE conway_heap_free_min_bytes gauge\n")?;
+        writeln!(
+            out,
+            "conway_heap_free_min_bytes {}",
+            self.get().unwrap_or(free)
+        )
+    }
+}
+
+impl Default for LowWater {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Samples [`Trend`] keeps: two hours at [`TREND_INTERVAL_MS`].
+pub const TREND_SAMPLES: usize = 12;
+
+/// Time between [`Trend`] samples: the firmware's heartbeat period.
+pub const TREND_INTERVAL_MS: u64 = 10 * 60 * 1000;
+
+/// Least total fall over a full [`Trend`] window that counts as a leak;
+/// less is allocator noise.
+pub const LEAK_MIN_DROP_BYTES: usize = 512;
+
+/// The last [`TREND_SAMPLES`] free-heap samples, taken every
+/// [`TREND_INTERVAL_MS`], for spotting a slow leak before it resets the
+/// board. [`LowWater`] only says how low the heap has been; a heap that
+/// keeps falling between the peaks is the tell.
+#[derive(Clone, Debug)]
+pub struct Trend {
+    samples: [usize; TREND_SAMPLES],
+    len: usize,
+    /// Where the next sample goes once the window is full.
+    next: usize,
+}
+
+impl Trend {
+    pub const fn new() -> Self {
+        Self {
+            samples: [0; TREND_SAMPLES],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Add a sample, dropping the oldest once the window is full.
+    pub fn push(&mut self, free: usize) {
+        self.samples[self.next] = free;
+        self.next = (self.next + 1) % TREND_SAMPLES;
+        self.len = (self.len + 1).min(TREND_SAMPLES);
+    }
+
+    /// Samples held, up to [`TREND_SAMPLES`].
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The samples held, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = usize> + '_ {
+        let start = if self.len < TREND_SAMPLES {
+            0
+        } else {
+            self.next
+        };
+        (0..self.len).map(move |i| self.samples[(start + i) % TREND_SAMPLES])
+    }
+
+    /// Least-squares slope of the samples in bytes per hour, rounded
+    /// toward zero; negative while free heap is falling. `None` until
+    /// there are two samples.
+    pub fn slope_per_hour(&self) -> Option<i64> {
+        if self.len < 2 {
+            return None;
+        }
+        let n = self.len as i64;
+        let (mut sx, mut sy, mut sxx, mut sxy) = (0i64, 0i64, 0i64, 0i64);
+        for (x, y) in self.samples().enumerate() {
+            let (x, y) = (x as i64, y as i64);
+            sx += x;
+            sy += y;
+            sxx += x * x;
+            sxy += x * y;
+        }
+        let per_hour = (3_600_000 / TREND_INTERVAL_MS) as i64;
+        Some((n * sxy - sx * sy) * per_hour / (n * sxx - sx * sx))
+    }
+
+    /// Whether the window is full and free heap never rose across it,
+    /// falling by at least [`LEAK_MIN_DROP_BYTES`] overall.
+    pub fn leaking(&self) -> bool {
+        if self.len < TREND_SAMPLES {
+            return false;
+        }
+        let mut samples = self.samples();
+        let first = samples.next().unwrap_or(0);
+        let mut last = first;
+        for free in samples {
+            if free > last {
+                return false;
+            }
+            last = free;
+        }
+        first - last >= LEAK_MIN_DROP_BYTES
+    }
+}
+
+impl Default for Trend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// As shown on `/status`: `collecting (3 of 12 samples)`, `-40 B/h over
+/// 110 min`, with `, falling steadily: possible leak` once
+/// [`Trend::leaking`].
+impl fmt::Display for Trend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(slope) = self.slope_per_hour() else {
+            return write!(f, "collecting ({} of {} samples)", self.len, TREND_SAMPLES);
+        };
+        let minutes = (self.len as u64 - 1) * TREND_INTERVAL_MS / 60_000;
+        write!(f, "{:+} B/h over {} min", slope, minutes)?;
+        if self.leaking() {
+            f.write_str(", falling steadily: possible leak")?;
+        }
+        Ok(())
+    }
+}
+
+/// Requested size from the panic message of a failed allocation
+/// (`memory allocation of N bytes failed`), or `None` for any other
+/// panic.
+pub fn alloc_failure_size(message: &str) -> Option<usize> {
+    message
+        .strip_prefix("memory allocation of ")?
+        .strip_suffix(" bytes failed")?
+        .parse()
+        .ok()
+}
+
+/// Heap state when an allocation failed, as logged:
+/// `allocation of 8195 bytes failed: 6020 of 73728 bytes free (lowest
+/// 5980), fragmented`.
+///
+/// "fragmented" means the request was smaller than the free total, so
+/// no single free block was big enough; "exhausted" means the heap was
+/// simply full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocFailure {
+    pub requested: usize,
+    pub free: usize,
+    /// [`LowWater`] before the failure, if it had been sampled.
+    pub lowest: Option<usize>,
+}
+
+impl fmt::Display for AllocFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "allocation of {} bytes failed: {} of {} bytes free",
+            self.requested, self.free, HEAP_SIZE
+        )?;
+        if let Some(lowest) = self.lowest {
+            write!(f, " (lowest {})", lowest)?;
+        }
+        f.write_str(if self.requested <= self.free {
+            ", fragmented"
+        } else {
+            ", exhausted"
+        })
+    }
+}
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
@@ -3,14 +3,19 @@
 //! Single-connection accept loop bound to TCP/80. Serves a small HTML status
 //! page at `GET /` and `GET /status`, accepts firmware uploads at
 //! `POST /ota`, and can flip back to the previous slot via
-//! `POST /ota/rollback`. Everything else returns 404 / 405.
+//! `POST /ota/rollback`. `GET /ping` answers `pong` for liveness checks,
+//! `GET /metrics` counts requests per route, `GET /diag/events` lists
+//! recent access decisions as JSON, and `HEAD` is served like
+//! `GET` without the body. Unknown paths return 404; known paths asked
+//! with the wrong method return 405 with `Allow`.
 //!
 //! Intentionally minimal: no keep-alive, no auth, no TLS, no concurrent
 //! connections. OTA is gated only by being on the same LAN.
 
 use core::fmt::Write as FmtWrite;
-use embassy_net::tcp::TcpSocket;
-use embassy_net::Stack;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_net::tcp::{State, TcpSocket};
+use embassy_net::{IpAddress, Stack};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex;
 use embassy_time::{Duration, Instant, Timer};
@@ -20,18 +25,65 @@ use heapless::String as HString;
 use crate::fob_store::{self, LocalFob, MAX_LABEL_LEN, MAX_LOCAL_FOBS};
 use crate::ota::{self, OtaError, OtaWriter};
 use crate::settings::{self, Settings, MAX_PASSWORD, MAX_SSID};
+use crate::sync::AccessEvent;
 use crate::{
-    DeviceMode, LastSwipe, PendingConfig, RuntimeConfig, EVENT_BUFFER, MANUAL_UNLOCK, MAX_FOBS,
-    PENDING_CONFIG, PENDING_CONFIG_TTL, WATCHDOG_FEED,
+    BootClock, DeviceMode, LastSwipe, PendingConfig, RuntimeConfig, EVENT_BUFFER, MANUAL_UNLOCK, MAX_FOBS,
+    PENDING_CONFIG, PENDING_CONFIG_TTL, REBOOTING, SYNC_SIGNAL, UNLOCK_LOCKOUT_FOB, WALL_CLOCK,
+    with_watchdog,
 };
-use access_controller::signing;
+use access_controller::clock::{self, Clock};
+use access_controller::counters::{self, Counter, Name};
+use access_controller::diag;
+use access_controller::etag::HostEtag;
+use access_controller::fob_check::{self, FobCheck};
+use access_controller::health::{self, Health};
+use access_controller::ipv4::{self, Cidr, MAX_ALLOWLIST};
+use access_controller::linger::{self, TcpState, Verdict};
+use access_controller::lockout::Lockout;
+use access_controller::request_body::{self, BodyAssembler, BodyError};
+use access_controller::routes::{self, RequestLog, Route, RouteCounts};
+use access_controller::{device_label, http_client, signing, stack_watermark};
 
 const HTTP_PORT: u16 = 80;
+/// Sockets aborted for lingering past [`linger::LINGER_MS`] after close;
+/// shown on the status page.
+pub static SOCKETS_FORCE_CLOSED: Counter = Counter::new();
+
+/// Admin requests served since boot, per route; rendered by `GET /metrics`.
+pub static REQUEST_COUNTS: RouteCounts = RouteCounts::new();
+
+/// Set while answering a `HEAD` request: responses keep their headers,
+/// `Content-Length` included, and [`write_body`] drops the body. The
+/// server handles one connection at a time, so one flag is enough.
+static HEAD_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Wrong `CONWAY_UNLOCK_SECRET` attempts, shared by every client.
+static UNLOCK_LOCKOUT: Mutex<CriticalSectionRawMutex, Lockout> = Mutex::new(Lockout::new());
+
+/// Wrong `CONWAY_FOBS_SECRET` attempts, shared by every client.
+static FOBS_LOCKOUT: Mutex<CriticalSectionRawMutex, Lockout> = Mutex::new(Lockout::new());
+
+/// Wrong `CONWAY_SYNC_SECRET` attempts, shared by every client.
+static SYNC_LOCKOUT: Mutex<CriticalSectionRawMutex, Lockout> = Mutex::new(Lockout::new());
+
+/// Wrong `CONWAY_DIAG_SECRET` attempts, shared by every client and
+/// every endpoint that takes it.
+static DIAG_LOCKOUT: Mutex<CriticalSectionRawMutex, Lockout> = Mutex::new(Lockout::new());
+
+/// Connections dropped because the client is outside
+/// `CONWAY_ADMIN_ALLOW`; shown on the status page.
+pub static ADMIN_CLIENTS_REFUSED: Counter = Counter::new();
+
 /// Timeout for normal short requests.
 const IO_TIMEOUT: Duration = Duration::from_secs(5);
 /// Timeout used while streaming an OTA payload - flash erase/write is
 /// slow and a full image can take ~30 s on a busy LAN.
 const OTA_IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long `POST /sync` waits for the sync it asked for. Every host
+/// failing with retries can take longer; the client then gets a 504 and
+/// the sync carries on.
+const SYNC_WAIT: Duration = Duration::from_secs(30);
 /// Header read buffer. Must be large enough for the request line plus
 /// any headers we care about (Content-Length).
 const REQ_BUF_LEN: usize = 2048;
@@ -40,17 +92,13 @@ const REQ_BUF_LEN: usize = 2048;
 /// enough to leave plenty of TCP rx headroom.
 const OTA_CHUNK: usize = 2048;
 
-/// Max size for a `POST /config` form body. The on-wire form is
-/// well under 256 bytes even at max field lengths.
-const CONFIG_BODY_MAX: usize = 512;
-
 /// HTTP server task. Runs forever, accepting one connection at a time.
 #[embassy_executor::task]
 pub async fn http_server_task(
     stack: &'static Stack<'static>,
     fobs: &'static Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
     local_fobs: &'static Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
-    etag: &'static Mutex<CriticalSectionRawMutex, HString<64>>,
+    etag: &'static Mutex<CriticalSectionRawMutex, HostEtag>,
     last_swipe: &'static Mutex<CriticalSectionRawMutex, Option<LastSwipe>>,
     rt: &'static RuntimeConfig,
 ) {
@@ -63,6 +111,14 @@ pub async fn http_server_task(
     }
     log::info!("http: network ready, listening on :{}", HTTP_PORT);
 
+    // The captive onboarding AP is the device's own network, and the
+    // only way in before WiFi is configured: never lock it out.
+    let allowlist = if rt.mode == DeviceMode::Onboarding {
+        heapless::Vec::new()
+    } else {
+        admin_allowlist()
+    };
+
     // Socket buffers live on the task stack and are reused for every
     // connection. 4 KiB rx gives the TCP window enough headroom to
     // sustain decent throughput during OTA uploads.
@@ -80,13 +136,82 @@ pub async fn http_server_task(
             continue;
         }
 
-        let peer = socket.remote_endpoint();
-        log::info!("http: connection from {:?}", peer);
+        let peer = peer_v4(&socket);
+        log::debug!("http: connection from {:?}", peer);
+
+        // Drop clients outside the management networks without a
+        // response, before reading anything they sent.
+        if !allowlist.is_empty() && !peer.is_some_and(|(ip, _)| ipv4::allowed(&allowlist, ip)) {
+            log::warn!("http: refusing admin client {:?}", peer);
+            ADMIN_CLIENTS_REFUSED.incr();
+            socket.abort();
+            let _ = socket.flush().await;
+            continue;
+        }
 
         handle_connection(&mut socket, fobs, local_fobs, etag, last_swipe, stack, rt).await;
 
         let _ = socket.flush().await;
-        socket.close();
+        close_socket(&mut socket).await;
+    }
+}
+
+/// Client IPv4 address and port of a connected socket.
+fn peer_v4(socket: &TcpSocket<'_>) -> Option<([u8; 4], u16)> {
+    socket.remote_endpoint().and_then(|ep| match ep.addr {
+        IpAddress::Ipv4(addr) => Some((addr.octets(), ep.port)),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    })
+}
+
+/// Networks allowed to reach the admin server: `CONWAY_ADMIN_ALLOW`, or
+/// empty (everyone) when unset or invalid.
+fn admin_allowlist() -> heapless::Vec<Cidr, MAX_ALLOWLIST> {
+    match option_env!("CONWAY_ADMIN_ALLOW") {
+        None => heapless::Vec::new(),
+        Some(s) => ipv4::parse_cidr_list(s).unwrap_or_else(|| {
+            log::error!("http: invalid CONWAY_ADMIN_ALLOW {:?}, allowing every client", s);
+            heapless::Vec::new()
+        }),
+    }
+}
+
+/// Close gracefully, then abort once the close has gone through or has
+/// lingered too long (see [`linger`]), so the socket's slot is free for
+/// the next connection.
+async fn close_socket(socket: &mut TcpSocket<'_>) {
+    socket.close();
+    let closed_at = Instant::now();
+    loop {
+        let closed_for = (Instant::now() - closed_at).as_millis();
+        match linger::after_close(tcp_state(socket.state()), closed_for, linger::LINGER_MS) {
+            Verdict::Wait => Timer::after(Duration::from_millis(50)).await,
+            Verdict::Release => break,
+            Verdict::ForceAbort => {
+                log::warn!("http: socket stuck in {:?}, aborting", socket.state());
+                SOCKETS_FORCE_CLOSED.incr();
+                break;
+            }
+        }
+    }
+    socket.abort();
+    let _ = socket.flush().await;
+}
+
+fn tcp_state(state: State) -> TcpState {
+    match state {
+        State::Closed => TcpState::Closed,
+        State::Listen => TcpState::Listen,
+        State::SynSent => TcpState::SynSent,
+        State::SynReceived => TcpState::SynReceived,
+        State::Established => TcpState::Established,
+        State::FinWait1 => TcpState::FinWait1,
+        State::FinWait2 => TcpState::FinWait2,
+        State::CloseWait => TcpState::CloseWait,
+        State::Closing => TcpState::Closing,
+        State::LastAck => TcpState::LastAck,
+        State::TimeWait => TcpState::TimeWait,
     }
 }
 
@@ -94,17 +219,20 @@ async fn handle_connection(
     socket: &mut TcpSocket<'_>,
     fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
     local_fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
-    etag: &Mutex<CriticalSectionRawMutex, HString<64>>,
+    etag: &Mutex<CriticalSectionRawMutex, HostEtag>,
     last_swipe: &Mutex<CriticalSectionRawMutex, Option<LastSwipe>>,
     stack: &Stack<'static>,
     rt: &'static RuntimeConfig,
 ) {
-    // Read until we have the request headers (terminated by \r\n\r\n).
+    HEAD_ONLY.store(false, Ordering::Relaxed);
+
+    // Read until we have the request headers (terminated by a blank line).
     let mut buf = [0u8; REQ_BUF_LEN];
     let mut len = 0usize;
     let header_end = loop {
         if len == buf.len() {
             log::warn!("http: request headers exceed {} bytes, dropping", REQ_BUF_LEN);
+            REQUEST_COUNTS.record_malformed();
             send_status_line(socket, "431 Request Header Fields Too Large", b"too large\n").await;
             return;
         }
@@ -115,8 +243,8 @@ async fn handle_connection(
             }
             Ok(n) => {
                 len += n;
-                if let Some(pos) = find_double_crlf(&buf[..len]) {
-                    break pos;
+                if let Some((_, body_start)) = http_client::find_head_end(&buf[..len]) {
+                    break body_start;
                 }
             }
             Err(e) => {
@@ -130,6 +258,7 @@ async fn handle_connection(
     let headers_str = match core::str::from_utf8(&buf[..header_end]) {
         Ok(s) => s,
         Err(_) => {
+            REQUEST_COUNTS.record_malformed();
             send_status_line(socket, "400 Bad Request", b"invalid utf-8\n").await;
             return;
         }
@@ -140,85 +269,86 @@ async fn handle_connection(
     let method = parts.next().unwrap_or("");
     let target = parts.next().unwrap_or("");
 
-    log::info!("http: {} {}", method, target);
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
 
-    let path = target.split('?').next().unwrap_or("");
+    // Audit trail: who asked for what.
+    let peer = peer_v4(socket);
+    log::info!("http: {}", RequestLog { method, path, peer });
 
     // Body bytes already read past the header terminator.
     let leftover = &buf[header_end..len];
 
-    match (method, path) {
-        // In onboarding mode, redirect "/" to the config page so that
-        // captive-portal browsers land right on the form.
-        ("GET", "/") if rt.mode == DeviceMode::Onboarding => {
-            send_redirect(socket, "/config").await;
+    HEAD_ONLY.store(!routes::has_body(method), Ordering::Relaxed);
+
+    let route = routes::route(method, path, rt.mode == DeviceMode::Onboarding);
+    REQUEST_COUNTS.record(route);
+    match route {
+        Route::Ping => {
+            send_text(socket, "200 OK", routes::PING_BODY).await;
         }
-        ("GET", "/") | ("GET", "/status") => {
+        Route::Metrics => {
+            // About 1.8 KiB with every counter at ten digits.
+            let mut body: HString<2048> = HString::new();
+            let _ = REQUEST_COUNTS.write_metrics(&mut body);
+            let _ = crate::HEAP_LOW_WATER.write_metrics(&mut body, crate::sample_heap());
+            let _ = crate::READS_DROPPED.write_metrics(&mut body);
+            let _ = crate::ACCESS_LOCK_HOLDS.write_metrics(&mut body);
+            let syncs =
+                rt.mode == DeviceMode::Station && rt.settings.lock().await.conway_enabled();
+            let _ = health::write_metrics(&crate::sample_health(stack, syncs).await, &mut body);
+            send_text(socket, "200 OK", body.as_bytes()).await;
+        }
+        Route::Status => {
             send_status_page(socket, fobs, local_fobs, etag, last_swipe, stack, rt).await;
         }
-        ("GET", "/config") => {
+        Route::ConfigPage => {
             send_config_page(socket, rt).await;
         }
-        ("POST", "/config") => {
-            let cl = match parse_content_length(headers_str) {
-                Some(n) if (n as usize) <= CONFIG_BODY_MAX => n,
-                Some(_) => {
-                    send_status_line(socket, "413 Payload Too Large", b"body too large\n").await;
-                    return;
-                }
-                None => {
-                    send_status_line(socket, "411 Length Required", b"need Content-Length\n").await;
-                    return;
-                }
+        Route::ConfigPost => {
+            let Some(cl) = form_body_length(socket, headers_str).await else {
+                return;
             };
             handle_config_post(socket, cl, leftover, rt).await;
         }
-        ("GET", "/fobs") => {
+        Route::FobsPage => {
             send_fobs_page(socket, local_fobs, rt).await;
         }
-        ("GET", "/swipes") => {
+        Route::Swipes => {
             send_swipes_page(socket).await;
         }
-        ("POST", "/fobs") => {
-            let cl = match parse_content_length(headers_str) {
-                Some(n) if (n as usize) <= CONFIG_BODY_MAX => n,
-                Some(_) => {
-                    send_status_line(socket, "413 Payload Too Large", b"body too large\n").await;
-                    return;
-                }
-                None => {
-                    send_status_line(socket, "411 Length Required", b"need Content-Length\n").await;
-                    return;
-                }
+        Route::LastSync => {
+            send_last_sync(socket, headers_str).await;
+        }
+        Route::DiagEvents => {
+            let mut body = alloc::string::String::new();
+            let _ = crate::sync::AUDIT.lock().await.render_json(&mut body);
+            send_json(socket, "200 OK", body.as_bytes()).await;
+        }
+        Route::Check => {
+            send_fob_check(socket, headers_str, query, fobs, local_fobs).await;
+        }
+        Route::Counters => {
+            send_counters(socket, headers_str, false).await;
+        }
+        Route::CountersReset => {
+            send_counters(socket, headers_str, true).await;
+        }
+        Route::FobAdd => {
+            let Some(cl) = form_body_length(socket, headers_str).await else {
+                return;
             };
-            handle_fob_add(socket, cl, leftover, local_fobs).await;
+            handle_fob_add(socket, headers_str, cl, leftover, local_fobs).await;
         }
-        ("POST", "/fobs/delete") => {
-            let cl = match parse_content_length(headers_str) {
-                Some(n) if (n as usize) <= CONFIG_BODY_MAX => n,
-                Some(_) => {
-                    send_status_line(socket, "413 Payload Too Large", b"body too large\n").await;
-                    return;
-                }
-                None => {
-                    send_status_line(socket, "411 Length Required", b"need Content-Length\n").await;
-                    return;
-                }
+        Route::FobDelete => {
+            let Some(cl) = form_body_length(socket, headers_str).await else {
+                return;
             };
-            handle_fob_delete(socket, cl, leftover, local_fobs).await;
-        }
-        // Captive portal probes - send everyone to /config.
-        ("GET", "/generate_204")
-        | ("GET", "/gen_204")
-        | ("GET", "/hotspot-detect.html")
-        | ("GET", "/library/test/success.html")
-        | ("GET", "/connecttest.txt")
-        | ("GET", "/ncsi.txt")
-        | ("GET", "/redirect")
-        | ("GET", "/success.txt") => {
+            handle_fob_delete(socket, headers_str, cl, leftover, local_fobs).await;
+        }
+        Route::RedirectToConfig => {
             send_redirect(socket, "/config").await;
         }
-        ("POST", "/ota") => {
+        Route::OtaUpload => {
             let cl = match parse_content_length(headers_str) {
                 Some(n) => n,
                 None => {
@@ -228,22 +358,20 @@ async fn handle_connection(
             };
             handle_ota_upload(socket, cl, leftover).await;
         }
-        ("POST", "/ota/rollback") => {
+        Route::OtaRollback => {
             handle_ota_rollback(socket).await;
         }
-        ("POST", "/unlock") => {
-            handle_manual_unlock(socket, rt).await;
+        Route::Unlock => {
+            handle_manual_unlock(socket, headers_str, rt).await;
         }
-        ("GET", _) if rt.mode == DeviceMode::Onboarding => {
-            // Any unknown GET while onboarding: bounce to /config so
-            // OS captive-portal heuristics fire.
-            send_redirect(socket, "/config").await;
+        Route::Sync => {
+            handle_sync_now(socket, headers_str, rt).await;
         }
-        ("GET", _) => {
+        Route::NotFound => {
             send_status_line(socket, "404 Not Found", b"not found\n").await;
         }
-        _ => {
-            send_status_line(socket, "405 Method Not Allowed", b"method not allowed\n").await;
+        Route::MethodNotAllowed(allow) => {
+            send_method_not_allowed(socket, allow).await;
         }
     }
 }
@@ -291,14 +419,19 @@ async fn handle_ota_upload(socket: &mut TcpSocket<'_>, content_length: u32, left
                 return;
             }
             Ok(n) => {
-                if let Err(e) = writer.write(&chunk[..n]) {
+                // Only a chunk that fills a sector blocks on flash; the
+                // rest leave the watchdog to `access_task`.
+                let written = if writer.flushes(n) {
+                    with_watchdog("ota write", |_| writer.write(&chunk[..n]))
+                } else {
+                    writer.write(&chunk[..n])
+                };
+                if let Err(e) = written {
                     log::warn!("ota: write failed: {}", e);
                     send_ota_error(socket, e).await;
                     return;
                 }
-                // Feed the watchdog (indirectly, via access_task) and
-                // yield so other tasks get a turn between sector erases.
-                WATCHDOG_FEED.signal(());
+                // Yield so other tasks get a turn between sector erases.
                 embassy_futures::yield_now().await;
 
                 let pct = ((writer.bytes_accepted() as u64 * 100)
@@ -321,7 +454,7 @@ async fn handle_ota_upload(socket: &mut TcpSocket<'_>, content_length: u32, left
         }
     }
 
-    let new_slot = match writer.finish() {
+    let new_slot = match with_watchdog("ota finish", |_| writer.finish()) {
         Ok(s) => s,
         Err(e) => {
             log::error!("ota: finish failed: {}", e);
@@ -379,7 +512,15 @@ async fn send_ota_error(socket: &mut TcpSocket<'_>, err: OtaError) {
 /// access_task observes `MANUAL_UNLOCK`, fires `DOOR_SIGNAL` +
 /// `READER_FEEDBACK::Granted`, and records an audit entry with the
 /// `MANUAL_UNLOCK_FOB` sentinel.
-async fn handle_manual_unlock(socket: &mut TcpSocket<'_>, rt: &'static RuntimeConfig) {
+///
+/// Builds with `CONWAY_UNLOCK_SECRET` also require
+/// `Authorization: Bearer <secret>`, and repeated wrong secrets lock the
+/// endpoint for a while (see [`access_controller::lockout`]).
+async fn handle_manual_unlock(
+    socket: &mut TcpSocket<'_>,
+    headers: &str,
+    rt: &'static RuntimeConfig,
+) {
     if rt.mode == DeviceMode::Onboarding {
         send_status_line(
             socket,
@@ -389,11 +530,247 @@ async fn handle_manual_unlock(socket: &mut TcpSocket<'_>, rt: &'static RuntimeCo
         .await;
         return;
     }
+    if let Some(secret) = unlock_secret() {
+        let token = diag::bearer_token(http_client::extract_header(headers, "authorization"));
+        if !authorize_with_lockout(socket, &UNLOCK_LOCKOUT, secret, token, "unlock").await {
+            return;
+        }
+    }
     log::warn!("http: manual unlock requested by {:?}", socket.remote_endpoint());
     MANUAL_UNLOCK.signal(());
     send_text(socket, "200 OK", b"ok: door pulsed\n").await;
 }
 
+/// `POST /sync`: start a sync with Conway and answer once it ends, or
+/// after [`SYNC_WAIT`]. Disabled (404) unless the firmware was built
+/// with `CONWAY_SYNC_SECRET`, sent as `Authorization: Bearer <secret>`;
+/// wrong secrets lock the endpoint like `/unlock`.
+///
+/// The server handles one connection at a time, so the admin pages wait
+/// behind this one until it answers.
+async fn handle_sync_now(socket: &mut TcpSocket<'_>, headers: &str, rt: &'static RuntimeConfig) {
+    let Some(secret) = option_env!("CONWAY_SYNC_SECRET").filter(|s| !s.is_empty()) else {
+        send_status_line(socket, "404 Not Found", b"not found\n").await;
+        return;
+    };
+    let token = diag::bearer_token(http_client::extract_header(headers, "authorization"));
+    if !authorize_with_lockout(socket, &SYNC_LOCKOUT, secret, token, "sync").await {
+        return;
+    }
+    // `sync_task` only runs in station mode with a server configured.
+    if rt.mode != DeviceMode::Station || !rt.settings.lock().await.conway_enabled() {
+        send_status_line(socket, "409 Conflict", b"no Conway server to sync with\n").await;
+        return;
+    }
+
+    // A sync already running may have fetched the list before whatever
+    // the caller wants picked up, so wait for the next one to start.
+    let run = crate::sync::SYNC_RUNS.lock().await.next();
+    log::info!("http: sync requested by {:?}", peer_v4(socket));
+    SYNC_SIGNAL.signal(());
+    socket.set_timeout(Some(SYNC_WAIT + IO_TIMEOUT));
+    let deadline = Instant::now() + SYNC_WAIT;
+    let outcome = loop {
+        if let Some(ok) = crate::sync::SYNC_RUNS.lock().await.outcome(run) {
+            break Some(ok);
+        }
+        if Instant::now() >= deadline {
+            break None;
+        }
+        Timer::after(Duration::from_millis(100)).await;
+    };
+    socket.set_timeout(Some(IO_TIMEOUT));
+    let (status, body) = routes::sync_reply(outcome);
+    send_text(socket, status, body).await;
+}
+
+/// Check `token` against `secret` under `lockout`, answering 429 while
+/// locked and 401 for a wrong or missing token. `what` names the
+/// secret in the log. A lock engaged on `/unlock` is also recorded in
+/// the audit trail as [`UNLOCK_LOCKOUT_FOB`].
+async fn authorize_with_lockout(
+    socket: &mut TcpSocket<'_>,
+    lockout: &Mutex<CriticalSectionRawMutex, Lockout>,
+    secret: &str,
+    token: Option<&str>,
+    what: &str,
+) -> bool {
+    let now = BootClock.now_ms();
+    let mut guard = lockout.lock().await;
+    if let Err(left_ms) = guard.check(now) {
+        drop(guard);
+        let mut body: HString<64> = HString::new();
+        let _ = write!(body, "too many attempts, retry in {} s\n", left_ms.div_ceil(1000));
+        send_text(socket, "429 Too Many Requests", body.as_bytes()).await;
+        return false;
+    }
+    if !token.is_some_and(|t| diag::secret_matches(secret, t)) {
+        let locked = guard.fail(now);
+        let failures = guard.failures();
+        drop(guard);
+        log::warn!(
+            "http: wrong {} secret from {:?} ({} in a row)",
+            what,
+            peer_v4(socket),
+            failures
+        );
+        if let Some(lock_ms) = locked {
+            log::warn!("http: {} locked out for {} s", what, lock_ms / 1000);
+            if core::ptr::eq(lockout, &UNLOCK_LOCKOUT) {
+                EVENT_BUFFER
+                    .push(AccessEvent {
+                        fob: UNLOCK_LOCKOUT_FOB,
+                        allowed: false,
+                    })
+                    .await;
+            }
+        }
+        send_status_line(socket, "401 Unauthorized", b"unauthorized\n").await;
+        return false;
+    }
+    guard.succeeded();
+    true
+}
+
+/// Answer 503 and return true once a scheduled reboot has begun (see
+/// [`REBOOTING`]). Callers check right before a blocking flash save, with
+/// no await in between, so a save either starts first and finishes before
+/// the reset or never starts.
+async fn refuse_while_rebooting(socket: &mut TcpSocket<'_>) -> bool {
+    if !REBOOTING.load(Ordering::Relaxed) {
+        return false;
+    }
+    send_status_line(socket, "503 Service Unavailable", b"rebooting, try again shortly\n").await;
+    true
+}
+
+/// Secret `POST /unlock` requires: `CONWAY_UNLOCK_SECRET`, or `None`
+/// (no secret) when unset or empty.
+fn unlock_secret() -> Option<&'static str> {
+    option_env!("CONWAY_UNLOCK_SECRET").filter(|s| !s.is_empty())
+}
+
+/// Secret that changes to the local fob list require:
+/// `CONWAY_FOBS_SECRET`, or `None` (no secret) when unset or empty.
+fn fobs_secret() -> Option<&'static str> {
+    option_env!("CONWAY_FOBS_SECRET").filter(|s| !s.is_empty())
+}
+
+/// Secret the diagnostic endpoints require: `CONWAY_DIAG_SECRET`, or
+/// `None` (endpoints disabled) when unset or empty.
+fn diag_secret() -> Option<&'static str> {
+    option_env!("CONWAY_DIAG_SECRET").filter(|s| !s.is_empty())
+}
+
+/// Whether a `POST /fobs` or `/fobs/delete` may change the list. Builds
+/// with `CONWAY_FOBS_SECRET` need it in the form's `secret` field or as
+/// `Authorization: Bearer <secret>`; wrong secrets lock the list like
+/// `/unlock`. Answers 401/429 itself when refusing.
+async fn fobs_authorized(socket: &mut TcpSocket<'_>, headers: &str, form_secret: &str) -> bool {
+    let Some(secret) = fobs_secret() else {
+        return true;
+    };
+    let token = if diag::secret_matches(secret, form_secret) {
+        Some(form_secret)
+    } else {
+        diag::bearer_token(http_client::extract_header(headers, "authorization"))
+    };
+    authorize_with_lockout(socket, &FOBS_LOCKOUT, secret, token, "fobs").await
+}
+
+/// Dump the last Conway sync response. Disabled (404) unless the
+/// firmware was built with `CONWAY_DIAG_SECRET`; requests must send it
+/// as `Authorization: Bearer <secret>`. Wrong secrets lock every
+/// endpoint that takes it, like `/unlock`.
+async fn send_last_sync(socket: &mut TcpSocket<'_>, headers: &str) {
+    let Some(secret) = diag_secret() else {
+        send_status_line(socket, "404 Not Found", b"not found\n").await;
+        return;
+    };
+    let token = diag::bearer_token(http_client::extract_header(headers, "authorization"));
+    if !authorize_with_lockout(socket, &DIAG_LOCKOUT, secret, token, "diag").await {
+        return;
+    }
+    let mut body = alloc::string::String::new();
+    let _ = crate::sync::LAST_RESPONSE.lock().await.render(&mut body);
+    send_text(socket, "200 OK", body.as_bytes()).await;
+}
+
+/// Answer whether the lists would let one fob in (see
+/// [`access_controller::fob_check`]). Gated like `/diag/lastsync`: 404
+/// unless built with `CONWAY_DIAG_SECRET`, which requests must send as
+/// `Authorization: Bearer <secret>`.
+async fn send_fob_check(
+    socket: &mut TcpSocket<'_>,
+    headers: &str,
+    query: &str,
+    fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
+    local_fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
+) {
+    let Some(secret) = diag_secret() else {
+        send_status_line(socket, "404 Not Found", b"not found\n").await;
+        return;
+    };
+    let token = diag::bearer_token(http_client::extract_header(headers, "authorization"));
+    if !authorize_with_lockout(socket, &DIAG_LOCKOUT, secret, token, "diag").await {
+        return;
+    }
+    let fob = match fob_check::parse_query(query) {
+        Ok(fob) => fob,
+        Err(e) => {
+            let mut body: HString<64> = HString::new();
+            let _ = write!(body, "{}\n", e);
+            send_status_line(socket, "400 Bad Request", body.as_bytes()).await;
+            return;
+        }
+    };
+    let local: heapless::Vec<u32, MAX_LOCAL_FOBS> =
+        local_fobs.lock().await.iter().map(|f| f.id).collect();
+    let check = FobCheck::lookup(fob, &local, &fobs.lock().await);
+    let mut body: HString<64> = HString::new();
+    let _ = check.render(&mut body);
+    send_json(socket, "200 OK", body.as_bytes()).await;
+}
+
+/// The counter behind each `/counters` name.
+fn counter(name: Name) -> &'static Counter {
+    match name {
+        Name::SyncFailures => &crate::sync::SYNC_FAILURES_TOTAL,
+        Name::WifiReconnects => &crate::WIFI_RECONNECTS,
+        Name::ReadsDropped => crate::READS_DROPPED.counter(),
+        Name::SocketsForceClosed => &SOCKETS_FORCE_CLOSED,
+        Name::AdminClientsRefused => &ADMIN_CLIENTS_REFUSED,
+        Name::LockOverBudget => crate::ACCESS_LOCK_HOLDS.over_budget_counter(),
+    }
+}
+
+/// `GET /counters`, or with `reset` `POST /counters/reset`: every
+/// counter's count since the last reset as JSON, starting each over
+/// when resetting. The totals `/metrics` exports are untouched. Resetting
+/// is gated like `/diag/lastsync`: 404 unless built with
+/// `CONWAY_DIAG_SECRET`, which requests must send as
+/// `Authorization: Bearer <secret>`.
+async fn send_counters(socket: &mut TcpSocket<'_>, headers: &str, reset: bool) {
+    if reset {
+        let Some(secret) = diag_secret() else {
+            send_status_line(socket, "404 Not Found", b"not found\n").await;
+            return;
+        };
+        let token = diag::bearer_token(http_client::extract_header(headers, "authorization"));
+        if !authorize_with_lockout(socket, &DIAG_LOCKOUT, secret, token, "diag").await {
+            return;
+        }
+    }
+    let snapshot = counters::snapshot(counter, reset);
+    if reset {
+        log::info!("http: counters reset by {:?}", peer_v4(socket));
+    }
+    // About 200 bytes with every counter at ten digits.
+    let mut body: HString<256> = HString::new();
+    let _ = snapshot.write_json(&mut body);
+    send_json(socket, "200 OK", body.as_bytes()).await;
+}
+
 /// Case-insensitive scan for `Content-Length: <decimal>` in the header block.
 fn parse_content_length(headers: &str) -> Option<u32> {
     for line in headers.lines() {
@@ -407,6 +784,17 @@ fn parse_content_length(headers: &str) -> Option<u32> {
     None
 }
 
+/// Write a response body, unless this is the answer to a `HEAD`.
+async fn write_body(
+    socket: &mut TcpSocket<'_>,
+    body: &[u8],
+) -> Result<(), embassy_net::tcp::Error> {
+    if HEAD_ONLY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    socket.write_all(body).await
+}
+
 /// Send a tiny `text/plain` response with the given status line and body.
 async fn send_status_line(socket: &mut TcpSocket<'_>, status: &str, body: &[u8]) {
     send_text(socket, status, body).await;
@@ -425,7 +813,42 @@ async fn send_text(socket: &mut TcpSocket<'_>, status: &str, body: &[u8]) {
         body.len()
     );
     let _ = socket.write_all(header.as_bytes()).await;
-    let _ = socket.write_all(body).await;
+    let _ = write_body(socket, body).await;
+}
+
+async fn send_json(socket: &mut TcpSocket<'_>, status: &str, body: &[u8]) {
+    let mut header: HString<160> = HString::new();
+    let _ = write!(
+        header,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n",
+        status,
+        body.len()
+    );
+    let _ = socket.write_all(header.as_bytes()).await;
+    let _ = write_body(socket, body).await;
+}
+
+/// `405 Method Not Allowed`, naming the methods the path does take.
+async fn send_method_not_allowed(socket: &mut TcpSocket<'_>, allow: &str) {
+    let body = b"method not allowed\n";
+    let mut header: HString<192> = HString::new();
+    let _ = write!(
+        header,
+        "HTTP/1.1 405 Method Not Allowed\r\n\
+         Allow: {}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n",
+        allow,
+        body.len()
+    );
+    let _ = socket.write_all(header.as_bytes()).await;
+    let _ = write_body(socket, body).await;
 }
 
 /// `GET /swipes` - dump the offline swipe log as CSV.
@@ -448,13 +871,13 @@ async fn send_status_page(
     socket: &mut TcpSocket<'_>,
     fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
     local_fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
-    etag: &Mutex<CriticalSectionRawMutex, HString<64>>,
+    etag: &Mutex<CriticalSectionRawMutex, HostEtag>,
     last_swipe: &Mutex<CriticalSectionRawMutex, Option<LastSwipe>>,
     stack: &Stack<'static>,
     rt: &'static RuntimeConfig,
 ) {
     // Gather state.
-    let uptime_ms = Instant::now().as_millis();
+    let uptime_ms = BootClock.now_ms();
     let uptime_secs = uptime_ms / 1000;
     let fob_count = fobs.lock().await.len();
     let local_fob_count = local_fobs.lock().await.len();
@@ -464,12 +887,23 @@ async fn send_status_page(
         g.clone()
     };
     let last_swipe_snap: Option<LastSwipe> = *last_swipe.lock().await;
+    let time_synced = WALL_CLOCK.lock().await.synced();
 
     // Snapshot live settings so the page reflects current creds and
     // Conway URL even after a /config save (which reboots, but better
     // safe than sorry if we ever support hot-reload).
-    let (cur_ssid, conway_host_str, conway_port, conway_enabled, is_onboarding) = {
+    let (
+        cur_ssid,
+        conway_host_str,
+        conway_port,
+        conway_enabled,
+        is_onboarding,
+        conway_hosts,
+        title,
+    ) = {
         let s = rt.settings.lock().await;
+        let mut title: HString<192> = HString::new();
+        let _ = device_label::write_title(&mut title, &s.device_label, "Conway Access Controller");
         let mut hs: HString<24> = HString::new();
         let _ = hs.push_str(&s.conway_host_str());
         let displayed_ssid: HString<48> = if rt.mode == DeviceMode::Onboarding {
@@ -492,6 +926,8 @@ async fn send_status_page(
             s.conway_port,
             s.conway_enabled(),
             rt.mode == DeviceMode::Onboarding,
+            s.conway_hosts(),
+            title,
         )
     };
 
@@ -561,7 +997,7 @@ async fn send_status_page(
             }
         }
     }
-    #[cfg(feature = "esp32")]
+    #[cfg(feature = "firmware")]
     if !crate::device_key::is_ready() {
         if banner
             .push_str(
@@ -586,7 +1022,7 @@ async fn send_status_page(
             let _ = last_swipe_html.push_str("(none)");
         }
         Some(ls) => {
-            let age_ms = uptime_ms.saturating_sub(ls.at_uptime_ms);
+            let age_ms = clock::elapsed(ls.at_uptime_ms, uptime_ms);
             let age_secs = age_ms / 1000;
             let (status_class, status_text) = if ls.allowed {
                 ("ok", "Granted")
@@ -615,6 +1051,25 @@ async fn send_status_page(
         }
     }
 
+    let reader_html: &str = match crate::READER_STATE.load(core::sync::atomic::Ordering::Relaxed) {
+        crate::READER_ONLINE => "<span class=\"ok\">Online</span>",
+        crate::READER_OFFLINE => "<span class=\"err\">Offline (no keep-alive)</span>",
+        _ => "(not monitored)",
+    };
+
+    let flash_html: &str = match crate::FLASH_STATE.load(core::sync::atomic::Ordering::Relaxed) {
+        crate::FLASH_HEALTHY => "<span class=\"ok\">OK</span>",
+        crate::FLASH_UNHEALTHY => "<span class=\"err\">Unhealthy (writes fail; nothing persists)</span>",
+        _ => "(not checked)",
+    };
+
+    let power_html: &str = match crate::POWER_STATE.load(core::sync::atomic::Ordering::Relaxed) {
+        crate::POWER_MAINS => "<span class=\"ok\">Mains</span>",
+        crate::POWER_BATTERY => "<span class=\"err\">On battery</span>",
+        crate::POWER_BATTERY_LOW => "<span class=\"err\">On battery, battery low</span>",
+        _ => "(not monitored)",
+    };
+
     // Manual-unlock button is hidden in onboarding mode (POST /unlock
     // returns 403 there anyway).
     let unlock_section: &str = if is_onboarding {
@@ -627,39 +1082,127 @@ async fn send_status_page(
 
     // Compose the "Conway server" row: either "host:port" or just
     // "(standalone)" with no port suffix.
-    let mut conway_row: HString<48> = HString::new();
+    let mut conway_row: HString<96> = HString::new();
     if conway_enabled {
         let _ = write!(conway_row, "{}:{}", conway_host_str.as_str(), conway_port);
+        // Name the fallback currently in use, if sync has failed over.
+        let active = crate::sync::FAILOVER.lock().await.last_good();
+        if active > 0 {
+            if let Some(h) = conway_hosts.get(active) {
+                let _ = write!(
+                    conway_row,
+                    " (failed over to {}.{}.{}.{})",
+                    h[0], h[1], h[2], h[3]
+                );
+            }
+        }
     } else {
         let _ = conway_row.push_str(conway_host_str.as_str()); // already "(standalone)"
     }
 
-    // Build body. 5 KiB is plenty for this page including the upload
-    // form, last-swipe row, and unlock button.
-    let mut body: HString<5120> = HString::new();
+    // Offline flag: no successful sync for `CONWAY_OFFLINE_SECS`.
+    let mut offline_html: HString<128> = HString::new();
+    if conway_enabled && !is_onboarding {
+        let age = *crate::sync::SYNC_AGE.lock().await;
+        if age.is_offline(uptime_ms) {
+            let _ = offline_html.push_str("<span class=\"err\">yes</span>");
+        } else {
+            let _ = offline_html.push_str("no");
+        }
+        match age.last_ok_ms() {
+            Some(at) => {
+                let _ = write!(
+                    offline_html,
+                    " &middot; last sync {}s ago",
+                    clock::elapsed(at, uptime_ms) / 1000
+                );
+            }
+            None => {
+                let _ = offline_html.push_str(" &middot; no sync since boot");
+            }
+        }
+    } else {
+        let _ = offline_html.push_str("no (nothing to sync)");
+    }
+
+    // DHCP lease: embassy-net doesn't report the lease duration, so this
+    // is how long the current address has been held.
+    let mut lease_html: HString<96> = HString::new();
+    if is_onboarding {
+        let _ = lease_html.push_str("n/a (access point)");
+    } else {
+        let lease = crate::DHCP_LEASE.lock().await;
+        match lease.held_ms(uptime_ms) {
+            Some(held) => {
+                let _ = write!(lease_html, "held {} s", held / 1000);
+            }
+            None => {
+                let _ = lease_html.push_str("<span class=\"err\">none</span>");
+            }
+        }
+        if lease.losses() > 0 {
+            let _ = write!(lease_html, " &middot; lost {} since boot", lease.losses());
+        }
+    }
+
+    let health_inputs = crate::sample_health(stack, conway_enabled && !is_onboarding).await;
+    let mut health_html: HString<96> = HString::new();
+    let health_class = match health::health(&health_inputs) {
+        Health::Ok => "ok",
+        Health::Degraded | Health::Critical => "err",
+    };
+    let _ = write!(health_html, "<span class=\"{}\">", health_class);
+    let _ = health::render(&health_inputs, &mut health_html);
+    let _ = health_html.push_str("</span>");
+
+    let heap_free = crate::sample_heap();
+    let stack_unused = crate::stack_unused();
+    let mut heap_trend: HString<80> = HString::new();
+    let heap_leaking = {
+        let trend = crate::HEAP_TREND.lock().await;
+        let _ = write!(heap_trend, "{}", *trend);
+        trend.leaking()
+    };
+
+    // Build body. 7 KiB covers this page including the upload form,
+    // last-swipe row, unlock button and every banner at once.
+    let mut body: HString<7168> = HString::new();
     let _ = write!(
         body,
         "<!doctype html>\
-<html><head><meta charset=\"utf-8\"><title>Conway Access Controller</title>\
+<html><head><meta charset=\"utf-8\"><title>{title}</title>\
 <style>body{{font-family:system-ui,sans-serif;margin:2rem;max-width:40rem}}\
 h1{{margin-bottom:0}}h2{{margin-top:2rem}}table{{border-collapse:collapse;margin-top:1rem}}\
 th,td{{text-align:left;padding:.25rem .75rem;border-bottom:1px solid #ddd}}\
 th{{background:#f3f3f3}}progress{{width:100%}}\
 .err{{color:#b00}}.ok{{color:#070}}</style></head><body>\
-<h1>Conway Access Controller</h1>\
+<h1>{title}</h1>\
 <p>Firmware v{firmware} &middot; <a href=\"/config\">Configuration</a> &middot; <a href=\"/fobs\">Local fobs</a> &middot; <a href=\"/swipes\">Swipe log</a></p>\
 {banner}\
 <table>\
+<tr title=\"Worst of WiFi, sync, flash, reader and heap; see the README for thresholds.\"><th>Health</th><td>{health}</td></tr>\
 <tr><th>Uptime</th><td>{uptime} s</td></tr>\
+<tr title=\"Until calendar time is known, schedule checks fall back to the plain fob check.\"><th>Time synced</th><td>{time_synced}</td></tr>\
 <tr><th>WiFi SSID</th><td>{ssid}</td></tr>\
 <tr><th>IPv4</th><td>{ip}</td></tr>\
+<tr title=\"Time since the DHCP address was acquired; renewals that keep it don't reset this.\"><th>DHCP lease</th><td>{lease}</td></tr>\
 <tr><th>Conway server</th><td>{conway_row}</td></tr>\
+<tr title=\"No successful sync for longer than CONWAY_OFFLINE_SECS (default an hour).\"><th>Offline</th><td>{offline}</td></tr>\
 <tr><th>Cached fobs (Conway)</th><td>{fobs}</td></tr>\
 <tr><th>Local fobs</th><td>{local_fobs} (<a href=\"/fobs\">manage</a>)</td></tr>\
 <tr title=\"Access decisions buffered locally; flushed to Conway on next sync.\"><th>Pending events (queued for Conway)</th><td>{events}</td></tr>\
 <tr><th>Last swipe</th><td>{last_swipe}</td></tr>\
+<tr><th>Reader</th><td>{reader}</td></tr>\
+<tr title=\"Card reads lost because swipes came faster than they were decided.\"><th>Reads dropped</th><td>{reads_dropped}</td></tr>\
+<tr title=\"Boot-time write/read-back check of a scratch sector.\"><th>Flash</th><td>{flash}</td></tr>\
+<tr><th>Power</th><td>{power}</td></tr>\
 <tr title=\"Opaque token returned by Conway; used to detect changes on next sync.\"><th>Last sync token</th><td>{etag}</td></tr>\
 <tr><th>OTA slot</th><td>{ota}</td></tr>\
+<tr title=\"Free now, and the least free since boot.\"><th>Heap free</th><td>{heap_free} B (lowest {heap_low} B)</td></tr>\
+<tr title=\"Slope of free heap over the last two hours, sampled every ten minutes.\"><th>Heap trend</th><td{trend_class}>{heap_trend}</td></tr>\
+<tr title=\"Main stack never reached since boot. Below 2 KiB a deeper call may overflow.\"><th>Stack headroom</th><td{stack_class}>{stack_unused} B of {stack_size} B</td></tr>\
+<tr title=\"HTTP connections aborted because the client never finished closing.\"><th>Sockets force-closed</th><td>{force_closed}</td></tr>\
+<tr title=\"Connections dropped because the client is outside CONWAY_ADMIN_ALLOW.\"><th>Admin clients refused</th><td>{refused}</td></tr>\
 </table>\
 {unlock_section}\
 <h2>Firmware update</h2>\
@@ -691,23 +1234,34 @@ rb.addEventListener('click',()=>{{if(!confirm('Roll back and reboot?'))return;\
 fetch('/ota/rollback',{{method:'POST'}}).then(r=>r.text()).then(t=>{{s.textContent=t;}})\
 .catch(e=>{{s.textContent='rollback failed';s.className='err';}});}});\
 if(ub){{ub.addEventListener('click',()=>{{if(!confirm('Unlock the door now?'))return;\
+const h={{}};if({unlock_secret}){{const k=prompt('Unlock secret');if(!k)return;\
+h.Authorization='Bearer '+k;}}\
 us.textContent='unlocking...';us.className='';\
-fetch('/unlock',{{method:'POST'}}).then(r=>r.text().then(t=>{{\
+fetch('/unlock',{{method:'POST',headers:h}}).then(r=>r.text().then(t=>{{\
 us.textContent=t.trim();us.className=r.ok?'ok':'err';\
 if(r.ok)setTimeout(()=>location.reload(),800);}}))\
 .catch(e=>{{us.textContent='unlock failed';us.className='err';}});}});}}\
 </script>\
 </body></html>",
+        title = title.as_str(),
         firmware = firmware,
         banner = banner.as_str(),
+        health = health_html.as_str(),
         uptime = uptime_secs,
+        time_synced = if time_synced { "yes" } else { "no" },
         ssid = cur_ssid.as_str(),
         ip = ip_str.as_str(),
+        lease = lease_html.as_str(),
         conway_row = conway_row.as_str(),
+        offline = offline_html.as_str(),
         fobs = fob_count,
         local_fobs = local_fob_count,
         events = pending_events,
         last_swipe = last_swipe_html.as_str(),
+        reader = reader_html,
+        reads_dropped = crate::READS_DROPPED.get(),
+        flash = flash_html,
+        power = power_html,
         etag = if current_etag.is_empty() {
             "(none)"
         } else {
@@ -716,6 +1270,20 @@ if(r.ok)setTimeout(()=>location.reload(),800);}}))\
         ota = ota_str.as_str(),
         maxk = next_slot_size / 1024,
         unlock_section = unlock_section,
+        unlock_secret = unlock_secret().is_some(),
+        heap_free = heap_free,
+        heap_low = crate::HEAP_LOW_WATER.get().unwrap_or(heap_free),
+        heap_trend = heap_trend,
+        trend_class = if heap_leaking { " class=\"err\"" } else { "" },
+        stack_unused = stack_unused,
+        stack_size = crate::stack_size(),
+        stack_class = if stack_watermark::low(stack_unused) {
+            " class=\"err\""
+        } else {
+            ""
+        },
+        force_closed = SOCKETS_FORCE_CLOSED.get(),
+        refused = ADMIN_CLIENTS_REFUSED.get(),
     );
 
     let mut header: HString<160> = HString::new();
@@ -734,19 +1302,34 @@ if(r.ok)setTimeout(()=>location.reload(),800);}}))\
         log::warn!("http: write header failed: {:?}", e);
         return;
     }
-    if let Err(e) = socket.write_all(body.as_bytes()).await {
+    if let Err(e) = write_body(socket, body.as_bytes()).await {
         log::warn!("http: write body failed: {:?}", e);
     }
 }
 
 /// Render the configuration form, pre-filled with current settings.
 async fn send_config_page(socket: &mut TcpSocket<'_>, rt: &'static RuntimeConfig) {
-    let (ssid, password, host_str, port, mode, current_pubkey_b64) = {
+    let (
+        ssid,
+        password,
+        host_str,
+        fallback_str,
+        port,
+        sync_path,
+        label,
+        mode,
+        current_pubkey_b64,
+    ) = {
         let s = rt.settings.lock().await;
         let mut hs: HString<24> = HString::new();
         if let Some(h) = s.conway_host {
             let _ = write!(hs, "{}.{}.{}.{}", h[0], h[1], h[2], h[3]);
         }
+        let mut fs: HString<64> = HString::new();
+        for (i, h) in s.fallback_hosts.iter().enumerate() {
+            let sep = if i > 0 { "," } else { "" };
+            let _ = write!(fs, "{}{}.{}.{}.{}", sep, h[0], h[1], h[2], h[3]);
+        }
         let pk_b64 = s
             .trusted_pubkey
             .as_ref()
@@ -756,7 +1339,10 @@ async fn send_config_page(socket: &mut TcpSocket<'_>, rt: &'static RuntimeConfig
             s.ssid.clone(),
             s.password.clone(),
             hs,
+            fs,
             s.conway_port,
+            s.sync_path.clone(),
+            s.device_label.clone(),
             rt.mode,
             pk_b64,
         )
@@ -809,7 +1395,7 @@ async fn send_config_page(socket: &mut TcpSocket<'_>, rt: &'static RuntimeConfig
     // page itself (not just /status) so onboarding does not dead-end on a
     // bare 500 after the operator fills in and submits the form.
     let unprovisioned_banner: alloc::string::String = {
-        #[cfg(feature = "esp32")]
+        #[cfg(feature = "firmware")]
         {
             if !crate::device_key::is_ready() {
                 alloc::string::String::from(
@@ -823,7 +1409,7 @@ async fn send_config_page(socket: &mut TcpSocket<'_>, rt: &'static RuntimeConfig
                 alloc::string::String::new()
             }
         }
-        #[cfg(not(feature = "esp32"))]
+        #[cfg(not(feature = "firmware"))]
         {
             alloc::string::String::new()
         }
@@ -858,6 +1444,10 @@ async fn send_config_page(socket: &mut TcpSocket<'_>, rt: &'static RuntimeConfig
     // setting. Keep onboarding minimal by omitting them entirely while in
     // Onboarding mode, and tuck them behind an <details> "Advanced"
     // disclosure once the device is configured.
+    let mut esc_label: HString<192> = HString::new();
+    html_escape_into(&label, &mut esc_label);
+    let mut esc_path: HString<384> = HString::new();
+    html_escape_into(&sync_path, &mut esc_path);
     let advanced_section: alloc::string::String = if mode == DeviceMode::Onboarding {
         alloc::string::String::new()
     } else {
@@ -868,6 +1458,11 @@ async fn send_config_page(socket: &mut TcpSocket<'_>, rt: &'static RuntimeConfig
                 "<details style=\"margin-top:1.5rem\">\
 <summary style=\"cursor:pointer;font-weight:600\">Advanced</summary>\
 <fieldset>\
+<legend>Sync endpoint</legend>\
+<label>Path<input type=\"text\" name=\"sync_path\" value=\"{sync_path}\" maxlength=\"{max_path}\" placeholder=\"{default_path}\"></label>\
+<p class=\"note\">Path of the Conway fob API on the server(s) above. Blank restores the default.</p>\
+</fieldset>\
+<fieldset>\
 <legend>Response signing key (Ed25519)</legend>\
 <p>Current trusted key: {pubkey_status}</p>\
 <label>Set / replace key (standard base64, 44 chars)<input type=\"text\" name=\"trusted_pubkey\" value=\"\" maxlength=\"64\" placeholder=\"leave blank for no change\"></label>\
@@ -879,6 +1474,9 @@ when this field is left untouched they save immediately and the device reboots.<
 </fieldset>\
 </details>",
                 pubkey_status = pubkey_status,
+                sync_path = esc_path.as_str(),
+                max_path = http_client::MAX_SYNC_PATH,
+                default_path = http_client::DEFAULT_SYNC_PATH,
                 ttl = PENDING_CONFIG_TTL.as_secs(),
             ),
         );
@@ -913,7 +1511,10 @@ fieldset legend{{font-weight:600;padding:0 .5rem}}\
 <div><label>Conway Host (IPv4, blank for standalone)<input type=\"text\" name=\"host\" value=\"{host}\" pattern=\"|[0-9.]+\"></label></div>\
 <div><label>Port<input type=\"number\" name=\"port\" value=\"{port}\" min=\"1\" max=\"65535\" required></label></div>\
 </div>\
+<label>Fallback Hosts (comma-separated IPv4, optional)<input type=\"text\" name=\"fallback_hosts\" value=\"{fallbacks}\" pattern=\"[0-9., ]*\"></label>\
 <p class=\"note\">Leave Conway Host blank to operate standalone. Only locally-added fobs will be accepted; events are not buffered.</p>\
+<label>Device label (optional)<input type=\"text\" name=\"device_label\" value=\"{label}\" maxlength=\"{max_label}\" placeholder=\"e.g. Wood shop door\"></label>\
+<p class=\"note\">Shown on the status page so you can tell controllers apart.</p>\
 {advanced}\
 <button type=\"submit\">Save</button>\
 </form>\
@@ -925,9 +1526,12 @@ fieldset legend{{font-weight:600;padding:0 .5rem}}\
             ssid = esc_ssid.as_str(),
             pw_ph = pw_placeholder,
             host = host_str.as_str(),
+            fallbacks = fallback_str.as_str(),
             port = port,
             max_ssid = MAX_SSID,
             max_pw = MAX_PASSWORD,
+            label = esc_label.as_str(),
+            max_label = device_label::MAX_DEVICE_LABEL,
             advanced = advanced_section.as_str(),
         ),
     );
@@ -944,7 +1548,7 @@ fieldset legend{{font-weight:600;padding:0 .5rem}}\
         body.len()
     );
     let _ = socket.write_all(header.as_bytes()).await;
-    let _ = socket.write_all(body.as_bytes()).await;
+    let _ = write_body(socket, body.as_bytes()).await;
 }
 
 /// Receive a urlencoded config form, validate, then either persist
@@ -954,28 +1558,14 @@ fieldset legend{{font-weight:600;padding:0 .5rem}}\
 /// operator to press the CONFIG button within [`PENDING_CONFIG_TTL`].
 async fn handle_config_post(
     socket: &mut TcpSocket<'_>,
-    content_length: u32,
+    content_length: usize,
     leftover: &[u8],
     rt: &'static RuntimeConfig,
 ) {
-    let mut body: alloc::vec::Vec<u8> =
-        alloc::vec::Vec::with_capacity(content_length as usize);
-    body.extend_from_slice(leftover);
-    while body.len() < content_length as usize {
-        let mut chunk = [0u8; 256];
-        let want = (content_length as usize - body.len()).min(chunk.len());
-        match socket.read(&mut chunk[..want]).await {
-            Ok(0) => {
-                send_config_error(socket, "400 Bad Request", "short body").await;
-                return;
-            }
-            Ok(n) => body.extend_from_slice(&chunk[..n]),
-            Err(_) => {
-                send_config_error(socket, "400 Bad Request", "read error").await;
-                return;
-            }
-        }
-    }
+    let Some(body) = read_form_body(socket, content_length, leftover).await else {
+        send_config_error(socket, "400 Bad Request", "short body").await;
+        return;
+    };
 
     let body_str = match core::str::from_utf8(&body) {
         Ok(s) => s,
@@ -988,6 +1578,10 @@ async fn handle_config_post(
     let mut ssid: alloc::string::String = alloc::string::String::new();
     let mut password: alloc::string::String = alloc::string::String::new();
     let mut host: alloc::string::String = alloc::string::String::new();
+    let mut fallback_str: alloc::string::String = alloc::string::String::new();
+    // `None` when the form omitted the field (onboarding): keep current.
+    let mut sync_path_field: Option<alloc::string::String> = None;
+    let mut label_field: Option<alloc::string::String> = None;
     let mut port_str: alloc::string::String = alloc::string::String::new();
     let mut trusted_pubkey_str: alloc::string::String = alloc::string::String::new();
     let mut clear_pubkey: bool = false;
@@ -1008,6 +1602,9 @@ async fn handle_config_post(
             "ssid" => ssid = decoded,
             "password" => password = decoded,
             "host" => host = decoded,
+            "fallback_hosts" => fallback_str = decoded,
+            "sync_path" => sync_path_field = Some(decoded),
+            "device_label" => label_field = Some(decoded),
             "port" => port_str = decoded,
             "trusted_pubkey" => trusted_pubkey_str = decoded,
             "clear_pubkey" => clear_pubkey = decoded == "1" || decoded == "on",
@@ -1049,6 +1646,46 @@ async fn handle_config_post(
             }
         }
     };
+    let fallback_hosts = match settings::parse_host_list(&fallback_str) {
+        Some(_) if host_octets.is_none() => heapless::Vec::new(),
+        Some(list) => list,
+        None => {
+            send_config_error(
+                socket,
+                "400 Bad Request",
+                "fallback hosts must be up to 3 comma-separated dotted-quad IPv4 addresses",
+            )
+            .await;
+            return;
+        }
+    };
+    let sync_path = match sync_path_field.as_deref().map(str::trim) {
+        None => rt.settings.lock().await.sync_path.clone(),
+        Some("") => http_client::DEFAULT_SYNC_PATH.into(),
+        Some(p) if http_client::valid_path(p) => p.into(),
+        Some(_) => {
+            send_config_error(
+                socket,
+                "400 Bad Request",
+                "sync path must start with '/' and contain no spaces (max 64 chars)",
+            )
+            .await;
+            return;
+        }
+    };
+    let device_label = match label_field.as_deref().map(str::trim) {
+        None => rt.settings.lock().await.device_label.clone(),
+        Some(l) if device_label::valid(l) => l.into(),
+        Some(_) => {
+            send_config_error(
+                socket,
+                "400 Bad Request",
+                "device label must be at most 32 bytes with no control characters",
+            )
+            .await;
+            return;
+        }
+    };
     let port: u16 = match port_str.parse() {
         Ok(p) if p > 0 => p,
         _ => {
@@ -1114,6 +1751,9 @@ async fn handle_config_post(
         conway_host: host_octets,
         conway_port: port,
         trusted_pubkey: new_pubkey,
+        fallback_hosts,
+        sync_path,
+        device_label,
     };
 
     let requires_confirmation = matches!(change, PubkeyChange::Set(_) | PubkeyChange::Clear);
@@ -1173,9 +1813,10 @@ window expires.</p>\
         *guard = new.clone();
     }
 
-    WATCHDOG_FEED.signal(());
-
-    if let Err(e) = settings::save(&new) {
+    if refuse_while_rebooting(socket).await {
+        return;
+    }
+    if let Err(e) = with_watchdog("settings save", |_| settings::save(&new)) {
         log::error!("config: save failed: {}", e);
         let mut msg: alloc::string::String = alloc::string::String::with_capacity(256);
         let _ = core::fmt::Write::write_fmt(
@@ -1310,7 +1951,7 @@ async fn send_redirect(socket: &mut TcpSocket<'_>, location: &str) {
         body.len()
     );
     let _ = socket.write_all(header.as_bytes()).await;
-    let _ = socket.write_all(body.as_bytes()).await;
+    let _ = write_body(socket, body.as_bytes()).await;
 }
 
 /// HTML-escape `src` into an `alloc::string::String`.
@@ -1340,13 +1981,7 @@ async fn send_html(socket: &mut TcpSocket<'_>, status: &str, body: &[u8]) {
         body.len()
     );
     let _ = socket.write_all(header.as_bytes()).await;
-    let _ = socket.write_all(body).await;
-}
-
-/// Find the position just past the `\r\n\r\n` that terminates an HTTP header
-/// block. Returns the index of the first byte AFTER the terminator, or `None`.
-fn find_double_crlf(buf: &[u8]) -> Option<usize> {
-    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|p| p + 4)
+    let _ = write_body(socket, body).await;
 }
 
 // ----------------------------------------------------------------------------
@@ -1407,11 +2042,20 @@ to revoke a Conway-issued fob, disable it in Conway.</div>",
         format_args!("<p>{} / {} entries.</p>", count, MAX_LOCAL_FOBS),
     );
 
-    body.push_str(
-        "<form method=\"POST\" action=\"/fobs\" class=\"add\">\
+    // Builds with a fobs secret ask for it with every change.
+    let secret_input = if fobs_secret().is_some() {
+        "<input name=\"secret\" type=\"password\" placeholder=\"Secret\" size=\"8\" required>"
+    } else {
+        ""
+    };
+    let _ = core::fmt::Write::write_fmt(
+        &mut body,
+        format_args!(
+            "<form method=\"POST\" action=\"/fobs\" class=\"add\">\
 <label>Fob ID<input name=\"id\" type=\"number\" min=\"1\" max=\"4294967295\" required></label>\
 <label>Label<input name=\"label\" maxlength=\"16\" placeholder=\"e.g. Alice\"></label>\
-<button type=\"submit\">Add</button></form>",
+{secret_input}<button type=\"submit\">Add</button></form>"
+        ),
     );
 
     if count == 0 {
@@ -1437,7 +2081,7 @@ to revoke a Conway-issued fob, disable it in Conway.</div>",
                     "<tr><td>{id}</td><td>{label}</td>\
 <td><form method=\"POST\" action=\"/fobs/delete\" class=\"inline\" \
 onsubmit=\"return confirm('Delete fob {id}?')\">\
-<input type=\"hidden\" name=\"id\" value=\"{id}\">\
+<input type=\"hidden\" name=\"id\" value=\"{id}\">{secret_input}\
 <button class=\"del\" type=\"submit\">Delete</button></form></td></tr>",
                     id = f.id,
                     label = esc,
@@ -1459,33 +2103,65 @@ onsubmit=\"return confirm('Delete fob {id}?')\">\
          \r\n",
         body.len()
     );
+    // Snapshot and page are both live: this connection's peak.
+    crate::sample_heap();
     let _ = socket.write_all(header.as_bytes()).await;
-    let _ = socket.write_all(body.as_bytes()).await;
+    let _ = write_body(socket, body.as_bytes()).await;
 }
 
-/// Read the full body of a small urlencoded form post into a Vec.
+/// Form body size limit: `CONWAY_HTTP_BODY_MAX`, or the default.
+pub fn body_max() -> usize {
+    match option_env!("CONWAY_HTTP_BODY_MAX") {
+        None => request_body::DEFAULT_BODY_MAX,
+        Some(s) => request_body::parse_body_max(s).unwrap_or_else(|| {
+            log::warn!("http: invalid CONWAY_HTTP_BODY_MAX {:?}, using default", s);
+            request_body::DEFAULT_BODY_MAX
+        }),
+    }
+}
+
+/// Body length of a form post, already checked against [`body_max`].
+/// Answers 411/413 itself and returns `None` when the body can't be
+/// accepted.
+async fn form_body_length(socket: &mut TcpSocket<'_>, headers: &str) -> Option<usize> {
+    match request_body::check_length(parse_content_length(headers), body_max()) {
+        Ok(n) => Some(n),
+        Err(e) => {
+            log::warn!("http: rejecting form body: {:?}", e);
+            let msg: &[u8] = match e {
+                BodyError::TooLarge => b"body too large\n",
+                _ => b"need Content-Length\n",
+            };
+            send_status_line(socket, e.status(), msg).await;
+            None
+        }
+    }
+}
+
+/// Read the full body of a small urlencoded form post into a Vec,
+/// assembling it from the bytes that came with the headers plus as many
+/// reads as it takes. `None` if the connection ends first.
 async fn read_form_body(
     socket: &mut TcpSocket<'_>,
-    content_length: u32,
+    content_length: usize,
     leftover: &[u8],
 ) -> Option<alloc::vec::Vec<u8>> {
-    let mut body: alloc::vec::Vec<u8> = alloc::vec::Vec::with_capacity(content_length as usize);
-    body.extend_from_slice(leftover);
-    while body.len() < content_length as usize {
+    let mut body = BodyAssembler::new(content_length, leftover);
+    while !body.is_complete() {
         let mut chunk = [0u8; 256];
-        let want = (content_length as usize - body.len()).min(chunk.len());
+        let want = body.remaining().min(chunk.len());
         match socket.read(&mut chunk[..want]).await {
-            Ok(0) => return None,
-            Ok(n) => body.extend_from_slice(&chunk[..n]),
-            Err(_) => return None,
+            Ok(0) | Err(_) => break,
+            Ok(n) => body.push(&chunk[..n]),
         }
     }
-    Some(body)
+    body.finish().ok()
 }
 
 async fn handle_fob_add(
     socket: &mut TcpSocket<'_>,
-    content_length: u32,
+    headers: &str,
+    content_length: usize,
     leftover: &[u8],
     local_fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
 ) {
@@ -1506,6 +2182,7 @@ async fn handle_fob_add(
 
     let mut id_str = alloc::string::String::new();
     let mut label = alloc::string::String::new();
+    let mut secret = alloc::string::String::new();
     for pair in body_str.split('&') {
         let (k, v) = match pair.split_once('=') {
             Some(kv) => kv,
@@ -1521,9 +2198,13 @@ async fn handle_fob_add(
         match k {
             "id" => id_str = decoded,
             "label" => label = decoded,
+            "secret" => secret = decoded,
             _ => {}
         }
     }
+    if !fobs_authorized(socket, headers, &secret).await {
+        return;
+    }
 
     let id: u32 = match id_str.trim().parse() {
         Ok(n) if n > 0 => n,
@@ -1566,8 +2247,10 @@ async fn handle_fob_add(
         g.iter().cloned().collect()
     };
 
-    WATCHDOG_FEED.signal(());
-    if let Err(e) = fob_store::save(&to_save) {
+    if refuse_while_rebooting(socket).await {
+        return;
+    }
+    if let Err(e) = with_watchdog("local fob save", |_| fob_store::save(&to_save)) {
         log::error!("fobs: save failed: {}", e);
         let mut msg: HString<96> = HString::new();
         let _ = write!(msg, "save failed: {}\n", e);
@@ -1581,7 +2264,8 @@ async fn handle_fob_add(
 
 async fn handle_fob_delete(
     socket: &mut TcpSocket<'_>,
-    content_length: u32,
+    headers: &str,
+    content_length: usize,
     leftover: &[u8],
     local_fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
 ) {
@@ -1601,13 +2285,19 @@ async fn handle_fob_delete(
     };
 
     let mut id_str = alloc::string::String::new();
+    let mut secret = alloc::string::String::new();
     for pair in body_str.split('&') {
         if let Some((k, v)) = pair.split_once('=') {
-            if k == "id" {
-                id_str = urldecode(v).unwrap_or_default();
+            match k {
+                "id" => id_str = urldecode(v).unwrap_or_default(),
+                "secret" => secret = urldecode(v).unwrap_or_default(),
+                _ => {}
             }
         }
     }
+    if !fobs_authorized(socket, headers, &secret).await {
+        return;
+    }
     let id: u32 = match id_str.trim().parse() {
         Ok(n) => n,
         Err(_) => {
@@ -1634,8 +2324,10 @@ async fn handle_fob_delete(
     if !removed {
         log::warn!("fobs: delete id={} not found", id);
     } else {
-        WATCHDOG_FEED.signal(());
-        if let Err(e) = fob_store::save(&to_save) {
+        if refuse_while_rebooting(socket).await {
+            return;
+        }
+        if let Err(e) = with_watchdog("local fob save", |_| fob_store::save(&to_save)) {
             log::error!("fobs: save failed: {}", e);
             let mut msg: HString<96> = HString::new();
             let _ = write!(msg, "save failed: {}\n", e);
@@ -0,0 +1,334 @@
+//! Pure HTTP/1.1 framing helpers for the Conway sync client.
+//!
+//! The socket belongs to the `sync_flow` transport; this module only
+//! builds and parses bytes so the framing can be exercised from host
+//! tests.
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use heapless::String as HString;
+
+use crate::fob_cache::MAX_FOBS;
+use crate::ipv4::parse_ipv4;
+
+/// Sync endpoint used when none is configured.
+pub const DEFAULT_SYNC_PATH: &str = "/api/fobs";
+
+/// Longest accepted sync path, in bytes.
+pub const MAX_SYNC_PATH: usize = 64;
+
+/// Whether `path` can be used verbatim as the request-target of the
+/// sync request: absolute (`/`-prefixed), at most [`MAX_SYNC_PATH`]
+/// bytes, and only visible ASCII, so it cannot smuggle whitespace or a
+/// CR/LF into the request line.
+pub fn valid_path(path: &str) -> bool {
+    path.starts_with('/')
+        && path.len() <= MAX_SYNC_PATH
+        && path.bytes().all(|b| (0x21..=0x7E).contains(&b))
+}
+
+/// Write the request line and headers (including the blank line) of the
+/// sync `POST`. `if_none_match`, `idempotency_key`, `fob_crc` and
+/// `nonce` are omitted when `None`.
+///
+/// `fob_crc` is `X-Fob-CRC`, the cached list's
+/// [`fob_cache::sorted_digest`](crate::fob_cache::sorted_digest) as 8
+/// lowercase hex digits. A server that finds it doesn't match the list
+/// its ETag names answers 200 with the full list instead of 304.
+///
+/// `nonce` is `X-Sync-Nonce`, which the response must echo (see
+/// [`sync_nonce`](crate::sync_nonce)).
+#[allow(clippy::too_many_arguments)]
+pub fn write_sync_request_head<W: Write>(
+    out: &mut W,
+    path: &str,
+    host: &str,
+    content_type: &str,
+    content_length: usize,
+    if_none_match: Option<&str>,
+    idempotency_key: Option<&str>,
+    fob_crc: Option<u32>,
+    nonce: Option<&str>,
+) -> core::fmt::Result {
+    write!(
+        out,
+        "POST {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Content-Type: {}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n",
+        path, host, content_type, content_length
+    )?;
+    if let Some(tag) = if_none_match {
+        write!(out, "If-None-Match: {}\r\n", tag)?;
+    }
+    if let Some(key) = idempotency_key {
+        write!(out, "Idempotency-Key: {}\r\n", key)?;
+    }
+    if let Some(crc) = fob_crc {
+        write!(out, "X-Fob-CRC: {:08x}\r\n", crc)?;
+    }
+    if let Some(nonce) = nonce {
+        write!(out, "X-Sync-Nonce: {}\r\n", nonce)?;
+    }
+    out.write_str("\r\n")
+}
+
+/// Locate the blank line that ends a header block. Returns the length of
+/// the header block (through the last header's line ending) and the
+/// offset of the first body byte.
+///
+/// Lines may end in CRLF or a bare LF, mixed freely: RFC 9112 §2.2 lets
+/// a recipient accept a lone LF, and some proxies emit one.
+pub fn find_head_end(buf: &[u8]) -> Option<(usize, usize)> {
+    let mut i = 0;
+    while let Some(n) = buf[i..].iter().position(|&b| b == b'\n') {
+        let head_len = i + n + 1;
+        match buf.get(head_len..) {
+            Some([b'\n', ..]) => return Some((head_len, head_len + 1)),
+            Some([b'\r', b'\n', ..]) => return Some((head_len, head_len + 2)),
+            _ => i = head_len,
+        }
+    }
+    None
+}
+
+/// Split a raw response at the blank line (see [`find_head_end`]) into
+/// the header block (status line and headers) and the body bytes, which
+/// may be binary. `None` if the header block is incomplete.
+///
+/// Header values may carry opaque non-ASCII bytes (`obs-text`, e.g. in an
+/// ETag). Rather than failing the whole response, those bytes are
+/// overwritten in place with DEL (0x7F), which every consumer of a header
+/// value rejects: such a tag is not stored, such a `Location` is not
+/// followed.
+pub fn split_head(response: &mut [u8]) -> Option<(&str, &[u8])> {
+    let (head_len, body_start) = find_head_end(response)?;
+    let (head, body) = response.split_at_mut(head_len);
+    for b in head.iter_mut().filter(|b| !b.is_ascii()) {
+        *b = 0x7F;
+    }
+    let head = core::str::from_utf8(head).ok()?;
+    Some((head, &body[body_start - head_len..]))
+}
+
+/// Parse the status code from the status line (`HTTP/1.1 200 OK`).
+/// Returns 0 if the line is malformed.
+pub fn parse_status_code(response: &str) -> u16 {
+    response
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Extract a header value (case-insensitive name, trimmed value). Stops
+/// at the end of the header block so body text is never matched. Lines
+/// may end in CRLF or a bare LF.
+pub fn extract_header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    for line in response.lines() {
+        if line.is_empty() || line == "\r" {
+            break; // End of headers
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if key.trim().eq_ignore_ascii_case(name) {
+                return Some(value.trim());
+            }
+        }
+    }
+    None
+}
+
+/// Check a request before it is sent: its `Content-Length` must equal
+/// the body bytes after the head plus `streamed` bytes to be written
+/// after it. A server given fewer bytes than declared waits for the rest
+/// until one side times out; given more, it reads the excess as the next
+/// request.
+pub fn check_request_length(request: &[u8], streamed: usize) -> Result<(), &'static str> {
+    let (head_len, body_start) = find_head_end(request).ok_or("request head incomplete")?;
+    let declared = core::str::from_utf8(&request[..head_len])
+        .ok()
+        .and_then(|head| extract_header(head, "content-length"))
+        .ok_or("request without Content-Length")?
+        .parse::<usize>()
+        .map_err(|_| "request Content-Length malformed")?;
+    if declared != request.len() - body_start + streamed {
+        return Err("request body length mismatch");
+    }
+    Ok(())
+}
+
+/// Redirect hops followed per sync before giving up.
+pub const MAX_REDIRECTS: u8 = 3;
+
+/// Most bytes accepted for a sync response's header block, status line
+/// and blank line included.
+pub const MAX_HEAD_BYTES: usize = 2048;
+
+/// Most bytes accepted for a sync response body: the largest JSON fob
+/// list, at up to 10 digits, a comma and a space per fob, plus brackets.
+/// The binary encoding of the same list is smaller.
+pub const MAX_BODY_BYTES: usize = MAX_FOBS * 12 + 2;
+
+/// Most bytes a whole sync response may take.
+pub const MAX_RESPONSE_BYTES: usize = MAX_HEAD_BYTES + MAX_BODY_BYTES;
+
+/// Check a response as it is being read, after every read, so the read
+/// loop can abort as soon as a limit is crossed instead of filling its
+/// buffer: headers that run past [`MAX_HEAD_BYTES`] without ending, a
+/// `Content-Length` or actual body over [`MAX_BODY_BYTES`].
+pub fn check_response_size(received: &[u8]) -> Result<(), &'static str> {
+    let Some((head_len, body_start)) = find_head_end(received) else {
+        return if received.len() > MAX_HEAD_BYTES {
+            Err("response headers too large")
+        } else {
+            Ok(())
+        };
+    };
+    if body_start > MAX_HEAD_BYTES {
+        return Err("response headers too large");
+    }
+    let declared = core::str::from_utf8(&received[..head_len])
+        .ok()
+        .and_then(|head| extract_header(head, "content-length"))
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared.is_some_and(|n| n > MAX_BODY_BYTES) || received.len() - body_start > MAX_BODY_BYTES
+    {
+        return Err("response body too large");
+    }
+    Ok(())
+}
+
+/// Whether a `Transfer-Encoding` value ends in `chunked`, the only
+/// coding the sync client undoes. The request is HTTP/1.1, so a server
+/// or proxy may stream the fob list instead of sending `Content-Length`.
+pub fn is_chunked(transfer_encoding: &str) -> bool {
+    transfer_encoding
+        .rsplit(',')
+        .next()
+        .is_some_and(|c| c.trim().eq_ignore_ascii_case("chunked"))
+}
+
+/// Decode a `chunked` body. Chunk extensions are ignored and trailers
+/// dropped. Line endings may be CRLF or a bare LF, as in the head.
+pub fn dechunk(body: &[u8]) -> Result<Vec<u8>, &'static str> {
+    const TRUNCATED: &str = "truncated chunked body";
+    let mut out = Vec::with_capacity(body.len());
+    let mut rest = body;
+    loop {
+        let nl = rest.iter().position(|&b| b == b'\n').ok_or(TRUNCATED)?;
+        let line = core::str::from_utf8(&rest[..nl]).map_err(|_| "bad chunk size")?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| "bad chunk size")?;
+        rest = &rest[nl + 1..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if rest.len() < size {
+            return Err(TRUNCATED);
+        }
+        out.extend_from_slice(&rest[..size]);
+        rest = match &rest[size..] {
+            [b'\r', b'\n', tail @ ..] | [b'\n', tail @ ..] => tail,
+            [] | [b'\r'] => return Err(TRUNCATED),
+            _ => return Err("bad chunk terminator"),
+        };
+    }
+}
+
+/// Statuses the sync client follows. All of them re-send the same
+/// `POST` to the new location: the events in the body must reach the
+/// server, so 301/302 are not downgraded to `GET` the way browsers do.
+pub fn is_redirect(status: u16) -> bool {
+    matches!(status, 301 | 302 | 307 | 308)
+}
+
+/// Where a sync request is sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncTarget {
+    pub host: [u8; 4],
+    pub port: u16,
+    pub path: HString<MAX_SYNC_PATH>,
+}
+
+/// Resolve a `Location` header value against the target that returned
+/// it. Accepts an absolute path (`/v2/fobs`) or a plain-`http` URL with
+/// an IPv4 literal host (`http://10.0.0.5:8080/v2/fobs`); the firmware
+/// has neither TLS nor DNS, so anything else is rejected. A fragment is
+/// dropped.
+pub fn parse_location(location: &str, from: &SyncTarget) -> Result<SyncTarget, &'static str> {
+    let location = location.split('#').next().unwrap_or("");
+    let (host, port, path) = if location.starts_with('/') {
+        (from.host, from.port, location)
+    } else {
+        let scheme_end = location.find("://").ok_or("unsupported Location")?;
+        if !location[..scheme_end].eq_ignore_ascii_case("http") {
+            return Err("unsupported Location scheme");
+        }
+        let rest = &location[scheme_end + 3..];
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.split_once(':') {
+            Some((h, p)) => match p.parse::<u16>() {
+                Ok(p) if p != 0 => (h, p),
+                _ => return Err("invalid Location port"),
+            },
+            None => (authority, 80),
+        };
+        let ip = parse_ipv4(host).ok_or("Location host is not an IPv4 address")?;
+        (ip, port, path)
+    };
+    if !valid_path(path) {
+        return Err("invalid Location path");
+    }
+    let mut p = HString::new();
+    let _ = p.push_str(path);
+    Ok(SyncTarget {
+        host,
+        port,
+        path: p,
+    })
+}
+
+/// How redirects are followed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RedirectPolicy {
+    pub max_hops: u8,
+    /// Follow redirects to a different host or port. Off by default: a
+    /// redirect should not be able to send swipe events (and pull the
+    /// fob list) from somewhere the device was never configured to trust.
+    pub cross_host: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_hops: MAX_REDIRECTS,
+            cross_host: false,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Next target for a redirect received from `from` after `hops`
+    /// redirects have already been followed this sync.
+    pub fn follow(
+        &self,
+        hops: u8,
+        from: &SyncTarget,
+        location: &str,
+    ) -> Result<SyncTarget, &'static str> {
+        if hops >= self.max_hops {
+            return Err("too many redirects");
+        }
+        let next = parse_location(location, from)?;
+        if !self.cross_host && (next.host != from.host || next.port != from.port) {
+            return Err("cross-host redirect refused");
+        }
+        Ok(next)
+    }
+}
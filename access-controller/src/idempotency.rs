@@ -0,0 +1,94 @@
+//! Idempotency keys for event uploads.
+//!
+//! Events are only removed from the buffer once Conway answers the sync.
+//! If the server commits them but the response is lost, the next sync
+//! sends them again. Each batch therefore carries an `Idempotency-Key`
+//! header and Conway ignores a batch whose key it has already stored.
+//!
+//! That only works if a retry sends *the same events under the same
+//! key*. Once a batch has been sent it is frozen: later syncs re-send
+//! exactly those events with the same key until one is acknowledged, and
+//! events that arrived meanwhile wait for the next batch. A batch is
+//! abandoned (and a new key issued) only if its first event is no longer
+//! at the head of the buffer, i.e. buffer overflow dropped part of it.
+//!
+//! Keys are `<mac>-<boot>-<counter>`, all lower-case hex: the MAC makes
+//! them unique per controller, a random per-boot value keeps a reboot
+//! (which resets the counter) from reusing keys, and the counter is
+//! monotonic within a boot.
+
+use core::fmt::Write;
+
+use heapless::String as HString;
+
+/// Length of a key: 12 + 1 + 8 + 1 + 8.
+pub const KEY_LEN: usize = 30;
+
+pub type Key = HString<KEY_LEN>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct InFlight {
+    counter: u32,
+    first_seq: u64,
+    count: usize,
+}
+
+/// Batch key bookkeeping for one controller.
+#[derive(Clone, Debug)]
+pub struct BatchKeys {
+    mac: [u8; 6],
+    boot: u32,
+    next: u32,
+    in_flight: Option<InFlight>,
+}
+
+impl BatchKeys {
+    pub const fn new(mac: [u8; 6], boot: u32) -> Self {
+        Self {
+            mac,
+            boot,
+            next: 0,
+            in_flight: None,
+        }
+    }
+
+    /// Pick the batch for the next sync, given the buffer's pending
+    /// events: `available` of them, the first with absolute sequence
+    /// number `first_seq`. Returns the key and how many events to send,
+    /// or `None` if there is nothing to send.
+    pub fn batch(&mut self, first_seq: u64, available: usize) -> Option<(Key, usize)> {
+        match self.in_flight {
+            Some(f) if f.first_seq == first_seq && f.count <= available => {
+                return Some((self.key(f.counter), f.count));
+            }
+            _ => self.in_flight = None,
+        }
+        if available == 0 {
+            return None;
+        }
+        let counter = self.next;
+        self.next = self.next.wrapping_add(1);
+        self.in_flight = Some(InFlight {
+            counter,
+            first_seq,
+            count: available,
+        });
+        Some((self.key(counter), available))
+    }
+
+    /// The in-flight batch was acknowledged by the server.
+    pub fn acked(&mut self) {
+        self.in_flight = None;
+    }
+
+    fn key(&self, counter: u32) -> Key {
+        let m = self.mac;
+        let mut k = Key::new();
+        let _ = write!(
+            k,
+            "{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}-{:08x}-{:08x}",
+            m[0], m[1], m[2], m[3], m[4], m[5], self.boot, counter
+        );
+        k
+    }
+}
@@ -0,0 +1,97 @@
+//! Strict IPv4 dotted-quad parsing.
+//!
+//! Used for every address the controller accepts as text: the Conway
+//! host and fallback hosts (build-time defaults and the `/config` form)
+//! and redirect `Location` targets. The grammar is deliberately narrow:
+//!
+//! ```text
+//!   address = octet "." octet "." octet "." octet
+//!   octet   = "0" | [1-9] [0-9]{0,2}        ; value 0..=255
+//! ```
+//!
+//! So: exactly four octets, no empty octets (`1..2.3`, `.1.2.3`), no
+//! leading zeros (`01`; some resolvers read those as octal), no sign
+//! (`+1`), no surrounding or embedded whitespace, and no trailing
+//! garbage (`1.2.3.4x`, `1.2.3.4.`). Callers trim user input first.
+//!
+//! Networks for the admin allowlist are `address "/" prefix`, the prefix
+//! 0–32 with the same no-leading-zero rule; a bare address means `/32`.
+
+/// Most networks an admin allowlist can hold.
+pub const MAX_ALLOWLIST: usize = 8;
+
+/// An IPv4 network, e.g. `192.168.10.0/24`. Host bits in `addr` are
+/// ignored, so `192.168.10.7/24` is the same network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    pub addr: [u8; 4],
+    pub prefix: u8,
+}
+
+impl Cidr {
+    /// Whether `ip` is inside this network.
+    pub fn contains(&self, ip: [u8; 4]) -> bool {
+        let mask = match self.prefix {
+            0 => 0,
+            p => u32::MAX << (32 - p as u32),
+        };
+        u32::from_be_bytes(self.addr) & mask == u32::from_be_bytes(ip) & mask
+    }
+}
+
+/// Parse `s` as a dotted-quad IPv4 address; see the module docs for the
+/// accepted grammar.
+pub fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
+    let mut octets = [0u8; 4];
+    let mut parts = s.split('.');
+    for octet in octets.iter_mut() {
+        *octet = parse_octet(parts.next()?)?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(octets)
+}
+
+fn parse_octet(part: &str) -> Option<u8> {
+    let digits = part.as_bytes();
+    if digits.is_empty() || digits.len() > 3 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    if digits.len() > 1 && digits[0] == b'0' {
+        return None;
+    }
+    let value = digits
+        .iter()
+        .fold(0u16, |acc, d| acc * 10 + (d - b'0') as u16);
+    u8::try_from(value).ok()
+}
+
+/// Parse `s` as a network (`a.b.c.d/n`) or a single address (`/32`).
+pub fn parse_cidr(s: &str) -> Option<Cidr> {
+    let (addr, prefix) = match s.split_once('/') {
+        Some((addr, prefix)) => (addr, parse_octet(prefix).filter(|p| *p <= 32)?),
+        None => (s, 32),
+    };
+    Some(Cidr {
+        addr: parse_ipv4(addr)?,
+        prefix,
+    })
+}
+
+/// Parse a comma-separated list of networks. Blank entries are skipped;
+/// any malformed entry, or more than [`MAX_ALLOWLIST`], rejects the
+/// whole list.
+pub fn parse_cidr_list(s: &str) -> Option<heapless::Vec<Cidr, MAX_ALLOWLIST>> {
+    let mut out = heapless::Vec::new();
+    for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        out.push(parse_cidr(part)?).ok()?;
+    }
+    Some(out)
+}
+
+/// Whether a client at `ip` may use the admin server. An empty list
+/// allows everyone.
+pub fn allowed(list: &[Cidr], ip: [u8; 4]) -> bool {
+    list.is_empty() || list.iter().any(|net| net.contains(ip))
+}
@@ -7,15 +7,66 @@
 //! Build modes:
 //! - Default (firmware): `cargo build` with `--target xtensa-esp32-none-elf`,
 //!   feature `esp32` (enabled by default). `no_std`.
+//! - ESP32-S3 firmware: `--no-default-features --features esp32s3` with
+//!   `--target xtensa-esp32s3-none-elf`. `no_std`.
 //! - Simulation/tests: `cargo test --no-default-features --features sim` on
 //!   the host; uses `std` so we can run proptest and standard `#[test]`s.
 
 #![cfg_attr(not(feature = "sim"), no_std)]
 
+#[cfg(all(feature = "esp32", feature = "esp32s3"))]
+compile_error!("board features `esp32` and `esp32s3` are mutually exclusive");
+
 extern crate alloc;
 
+pub mod authz;
+pub mod backoff;
+pub mod body_mac;
+pub mod clock;
 pub mod core;
+pub mod counters;
+pub mod credential_window;
 pub mod crypto;
 pub mod decode;
+pub mod device_label;
+pub mod dhcp_watch;
+pub mod diag;
+pub mod door;
+pub mod etag;
 pub mod events;
+pub mod failover;
+pub mod feedback;
+pub mod flash_layout;
+pub mod fob_cache;
+pub mod fob_check;
+pub mod fuzz;
+pub mod health;
+pub mod heap;
+pub mod http_client;
+pub mod idempotency;
+pub mod ipv4;
+pub mod linger;
+pub mod lockout;
+pub mod osdp;
+pub mod passback;
+pub mod power;
+pub mod read_drops;
+pub mod reader_watch;
+pub mod reboot;
+pub mod request_body;
+pub mod rmt_frame;
+pub mod rng;
+pub mod routes;
 pub mod signing;
+pub mod sockets;
+pub mod sse;
+pub mod stack_watermark;
+pub mod status_led;
+pub mod storage;
+pub mod sync_flow;
+pub mod sync_guard;
+pub mod sync_nonce;
+pub mod tx_plan;
+pub mod watchdog;
+pub mod websocket;
+pub mod wire;
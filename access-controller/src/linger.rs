@@ -0,0 +1,50 @@
+//! When to stop waiting for a closing TCP socket.
+//!
+//! After `close()` the HTTP server waits for its FIN to be acknowledged
+//! so the client gets the whole response, then aborts the socket to give
+//! its slot back to the stack. A peer that vanishes mid-close would
+//! otherwise hold the socket in `FIN-WAIT-1`, `CLOSING` or `LAST-ACK`
+//! indefinitely; past [`LINGER_MS`] it is aborted anyway and counted.
+//! `TIME-WAIT` and `FIN-WAIT-2` mean everything sent was acknowledged,
+//! so those are aborted straight away rather than left to time out.
+
+/// How long a closed socket may wait for its FIN to be acknowledged.
+pub const LINGER_MS: u64 = 2_000;
+
+/// TCP connection state, as smoltcp reports it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TcpState {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+}
+
+/// What to do with a socket `close()`d some time ago.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Nothing left to deliver: abort now. Not a leak.
+    Release,
+    /// Our FIN (or data before it) is still unacknowledged.
+    Wait,
+    /// Lingered past the limit: abort and count it.
+    ForceAbort,
+}
+
+/// Decide for a socket in `state`, `closed_for_ms` after `close()`.
+pub fn after_close(state: TcpState, closed_for_ms: u64, limit_ms: u64) -> Verdict {
+    match state {
+        TcpState::Closed | TcpState::Listen | TcpState::FinWait2 | TcpState::TimeWait => {
+            Verdict::Release
+        }
+        _ if closed_for_ms < limit_ms => Verdict::Wait,
+        _ => Verdict::ForceAbort,
+    }
+}
@@ -0,0 +1,93 @@
+//! Brute-force guard for the `POST /unlock` secret.
+//!
+//! The first [`FREE_FAILURES`] - 1 wrong secrets cost nothing. The next
+//! one locks unlock attempts for [`BASE_LOCK_MS`], and every failure after
+//! a lock has run out doubles it, up to [`MAX_LOCK_MS`]. Attempts made
+//! while locked are refused without looking at the secret, so they
+//! neither test a guess nor extend the lock. A correct secret clears the
+//! count, and so does a quiet spell of [`MAX_LOCK_MS`] after the last
+//! failure or lock.
+//!
+//! The count is global, not per client: an attacker spreading guesses
+//! over many LAN addresses gains nothing. The price is that they can keep
+//! the web-UI unlock locked for everyone; fobs are unaffected. Time is
+//! passed in; see [`crate::clock`].
+
+use crate::clock;
+
+/// Failures at which the first lock engages.
+pub const FREE_FAILURES: u32 = 5;
+/// Length of the first lock.
+pub const BASE_LOCK_MS: u64 = 30_000;
+/// Longest lock, and the quiet spell that forgets past failures.
+pub const MAX_LOCK_MS: u64 = 3_600_000;
+
+/// Failed-attempt counter and current lock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lockout {
+    failures: u32,
+    last_failure_ms: u64,
+    locked_until_ms: Option<u64>,
+}
+
+impl Default for Lockout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lockout {
+    pub const fn new() -> Self {
+        Self {
+            failures: 0,
+            last_failure_ms: 0,
+            locked_until_ms: None,
+        }
+    }
+
+    /// `Err` with the milliseconds left while attempts are locked out.
+    pub fn check(&self, now_ms: u64) -> Result<(), u64> {
+        match self
+            .locked_until_ms
+            .map(|until| clock::elapsed(now_ms, until))
+        {
+            Some(left) if left > 0 => Err(left),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a wrong secret at `now_ms`. Returns the length of the lock
+    /// it engages, if any. Callers [`check`](Self::check) first; a
+    /// failure reported while locked is not counted.
+    pub fn fail(&mut self, now_ms: u64) -> Option<u64> {
+        if self.check(now_ms).is_err() {
+            return None;
+        }
+        let quiet_since = match self.locked_until_ms {
+            Some(until) if clock::before(self.last_failure_ms, until) => until,
+            _ => self.last_failure_ms,
+        };
+        if clock::elapsed(quiet_since, now_ms) >= MAX_LOCK_MS {
+            self.failures = 0;
+        }
+        self.failures = self.failures.saturating_add(1);
+        self.last_failure_ms = now_ms;
+        let over = self.failures.checked_sub(FREE_FAILURES)?;
+        let lock_ms = 1u64
+            .checked_shl(over)
+            .map_or(MAX_LOCK_MS, |factor| BASE_LOCK_MS.saturating_mul(factor))
+            .min(MAX_LOCK_MS);
+        self.locked_until_ms = Some(clock::later(now_ms, lock_ms));
+        Some(lock_ms)
+    }
+
+    /// The right secret: forget every failure.
+    pub fn succeeded(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Consecutive failures counted so far.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
@@ -12,28 +12,43 @@
 use esp_bootloader_esp_idf::esp_app_desc;
 esp_app_desc!();
 
+mod cache_store;
 mod dhcp_server;
 mod device_key;
 mod dns_server;
+mod entropy;
+mod flash;
 mod fob_store;
 mod http;
+mod online_check;
+#[cfg(feature = "osdp")]
+mod osdp_reader;
 mod ota;
+mod push;
+mod seq_store;
 mod settings;
 mod swipe_log;
 mod sync;
 mod wiegand;
+#[cfg(feature = "wiegand-rmt")]
+mod wiegand_rmt;
+#[cfg(feature = "wiegand-tx")]
+mod wiegand_tx;
 
 extern crate alloc;
 
 use alloc::boxed::Box;
 use alloc::format;
+use core::cell::RefCell;
 use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use embassy_net::{Config as NetConfig, Stack, StackResources, StaticConfigV4};
-use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, RawMutex};
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
 use embassy_sync::channel::Channel;
-use embassy_sync::mutex::Mutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
 use embassy_sync::signal::Signal;
-use embassy_time::{Duration, Instant, Timer};
+use embassy_time::{with_deadline, Duration, Instant, Timer};
 use esp_alloc as _;
 use esp_hal::clock::CpuClock;
 use esp_hal::gpio::{Input, InputConfig, Level, Output, OutputConfig, Pull};
@@ -50,11 +65,55 @@ use crate::fob_store::{LocalFob, MAX_LOCAL_FOBS};
 use crate::settings::Settings;
 use crate::swipe_log::SwipeLogEntry;
 use crate::sync::{AccessEvent, EventBuffer};
-use crate::wiegand::{Wiegand, WiegandRead};
-use access_controller::core::{AccessCore, CardRead, Effect, Input as CoreInput, Outcome};
+#[cfg(not(feature = "wiegand-rmt"))]
+use crate::wiegand::Wiegand;
+#[cfg(not(feature = "wiegand-rmt"))]
+use access_controller::decode::{CredentialFormat, FrameGap, PulseFilter};
+use crate::wiegand::WiegandRead;
+#[cfg(feature = "wiegand-rmt")]
+use crate::wiegand_rmt::RmtWiegand as Wiegand;
+use access_controller::authz::{self, Verdict};
+use access_controller::clock::{self, Clock, WallClock};
+use access_controller::core::{
+    AccessCore, CardRead, DenialReport, DenyBackoff, Effect, FailPolicy, Input as CoreInput,
+    KnownFacilities, MatchOrder, Outcome, Reader, MAX_EFFECTS_PER_STEP, MAX_READERS,
+};
+use access_controller::counters::Counter;
+use access_controller::decode::ByteOrder;
+use access_controller::dhcp_watch::{self, DhcpAction, DhcpWatch, LeaseEvent, LeaseWatch};
+use access_controller::door::{self, Relock};
+use access_controller::feedback::{self, Cues};
+use access_controller::etag::HostEtag;
+use access_controller::fob_cache::{self, Reconcile};
+use access_controller::health::{self, Health};
+use access_controller::heap;
+use access_controller::passback;
+use access_controller::read_drops::{DroppedReads, QueuedRead, READ_QUEUE_DEPTH};
+use access_controller::reboot::{self, Decision};
+use access_controller::rng::RandomSource;
+use access_controller::sockets;
+use access_controller::stack_watermark;
+use access_controller::watchdog::{self, FeedCadence, LockHolds};
+use access_controller::status_led::{self, BootIndicator, SyncAge};
 
 // Configuration constants
-pub const MAX_FOBS: usize = 512;
+pub use access_controller::fob_cache::MAX_FOBS;
+
+/// The firmware's [`Clock`]: embassy time since boot. Tasks read time
+/// through this and hand it to the lib's state machines.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BootClock;
+
+impl Clock for BootClock {
+    fn now_ms(&self) -> u64 {
+        Instant::now().as_millis()
+    }
+}
+
+/// Calendar time, for schedule checks. Nothing sets it yet (there is no
+/// SNTP client), so it stays unsynced and every schedule check fails
+/// open to the plain fob check; `/status` shows which.
+pub static WALL_CLOCK: Mutex<CriticalSectionRawMutex, WallClock> = Mutex::new(WallClock::new());
 
 /// Runtime device mode chosen at boot. Determines which WiFi interface
 /// embassy-net is bound to and whether DHCP/DNS servers run.
@@ -79,12 +138,19 @@ static CONFIG: StaticCell<RuntimeConfig> = StaticCell::new();
 
 // Channel for Wiegand reads -> access control task
 // Bounded queue from the (interrupt-driven) Wiegand decoder to the
-// access_task. Capacity 4 was tight: any handler in http.rs that holds
-// fobs/local_fobs/settings/last_swipe across socket I/O (notably the
-// OTA upload) backpressures access_task; once 4 swipes queue up, the
-// 5th is dropped with only a warn. Bumped to 16 so a slow HTTP client
-// can't silently mask door swipes.
-static WIEGAND_CHANNEL: Channel<CriticalSectionRawMutex, WiegandRead, 16> = Channel::new();
+// access_task; see `READ_QUEUE_DEPTH` for its size. The OSDP reader
+// feeds it too, each read tagged with where it came from.
+static WIEGAND_CHANNEL: Channel<CriticalSectionRawMutex, QueuedRead, READ_QUEUE_DEPTH> =
+    Channel::new();
+
+/// Reads dropped because [`WIEGAND_CHANNEL`] was full; on `/status` and
+/// `/metrics`.
+pub static READS_DROPPED: DroppedReads = DroppedReads::new();
+
+// Reads to replay to the downstream panel. A frame takes ~70 ms to send,
+// so a few are plenty; past that, swipes are dropped rather than delayed.
+#[cfg(feature = "wiegand-tx")]
+static WIEGAND_TX_CHANNEL: Channel<CriticalSectionRawMutex, WiegandRead, 4> = Channel::new();
 
 // Channel for offline swipe logging -> swipe_log_task (standalone mode).
 // `access_task` must never block on flash, so it only `try_send`s entries
@@ -103,8 +169,16 @@ pub static SYNC_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 // Signal sent when sync completes (success or failure)
 pub static SYNC_COMPLETE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
-// Signal for door unlock (after successful auth)
-pub static DOOR_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+// Online check of a read the lists miss: access_task -> online_check_task.
+// Each reader has at most one query out, so one slot per reader is enough.
+pub static AUTHZ_QUERY: Channel<CriticalSectionRawMutex, CardRead, MAX_READERS> = Channel::new();
+
+// The verdict on that read: online_check_task -> access_task.
+pub static AUTHZ_ANSWER: Channel<CriticalSectionRawMutex, (CardRead, Verdict), MAX_READERS> =
+    Channel::new();
+
+// Signal for door unlock (after successful auth), carrying the hold in ms
+pub static DOOR_SIGNAL: Signal<CriticalSectionRawMutex, u64> = Signal::new();
 
 // Signal raised by `POST /unlock` to request a manual door pulse.
 pub static MANUAL_UNLOCK: Signal<CriticalSectionRawMutex, ()> = Signal::new();
@@ -119,6 +193,44 @@ pub static MANUAL_UNLOCK: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 /// because fob ID 0 is a legal Wiegand-26 transmission.
 pub const MANUAL_UNLOCK_FOB: u32 = u32::MAX;
 
+/// Sentinel `fob` value logged (as a denied event) when the reader stops
+/// sending keep-alives. Outside the Wiegand-26 range for the same reason
+/// as [`MANUAL_UNLOCK_FOB`].
+pub const READER_OFFLINE_FOB: u32 = u32::MAX - 1;
+
+/// Sentinel `fob` value logged (as a denied event) when repeated wrong
+/// `CONWAY_UNLOCK_SECRET` attempts lock out `POST /unlock`. Outside the
+/// Wiegand-26 range for the same reason as [`MANUAL_UNLOCK_FOB`].
+pub const UNLOCK_LOCKOUT_FOB: u32 = u32::MAX - 2;
+
+/// Sentinel `fob` value logged (as a denied event) when the supply
+/// switches to battery. Outside the Wiegand-26 range for the same reason
+/// as [`MANUAL_UNLOCK_FOB`].
+pub const ON_BATTERY_FOB: u32 = u32::MAX - 3;
+
+// Signal raised by `wiegand_task` on any D0/D1 activity (full frame or
+// not); consumed by `reader_watch_task`.
+pub static READER_ACTIVITY: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Reader presence as seen by `reader_watch_task`, for `/status`.
+pub const READER_UNMONITORED: u8 = 0;
+pub const READER_ONLINE: u8 = 1;
+pub const READER_OFFLINE: u8 = 2;
+pub static READER_STATE: AtomicU8 = AtomicU8::new(READER_UNMONITORED);
+
+/// Result of the boot-time flash check, for `/status`.
+pub const FLASH_UNCHECKED: u8 = 0;
+pub const FLASH_HEALTHY: u8 = 1;
+pub const FLASH_UNHEALTHY: u8 = 2;
+pub static FLASH_STATE: AtomicU8 = AtomicU8::new(FLASH_UNCHECKED);
+
+/// Supply state as seen by `power_task`, for `/status` and the heartbeat.
+pub const POWER_UNMONITORED: u8 = 0;
+pub const POWER_MAINS: u8 = 1;
+pub const POWER_BATTERY: u8 = 2;
+pub const POWER_BATTERY_LOW: u8 = 3;
+pub static POWER_STATE: AtomicU8 = AtomicU8::new(POWER_UNMONITORED);
+
 /// Most recent door event (swipe or manual unlock). Rendered on the
 /// HTTP status page; not persisted across reboots.
 #[derive(Debug, Clone, Copy)]
@@ -139,9 +251,115 @@ pub enum AccessOutcome {
 // Signal to drive reader LED/beeper after each access decision.
 pub static READER_FEEDBACK: Signal<CriticalSectionRawMutex, AccessOutcome> = Signal::new();
 
+// Boot status for the reader LED/beeper, published by
+// `status_and_config_task` whenever the status pattern changes.
+pub static BOOT_STATUS: Signal<CriticalSectionRawMutex, status_led::Change> = Signal::new();
+
 // Signal to request watchdog feed (proves access_task is responsive)
 pub static WATCHDOG_FEED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
+/// Time `access_task` spends on shared state per card read or sync
+/// completion; feed ticks wait behind it.
+pub static ACCESS_LOCK_HOLDS: LockHolds = LockHolds::new();
+
+/// Least free heap seen since boot. Sampled where allocations peak (a
+/// sync response being applied, the local fob page) and on every
+/// watchdog tick; shown on `/status` and `/metrics`.
+pub static HEAP_LOW_WATER: heap::LowWater = heap::LowWater::new();
+
+/// Free heap at each heartbeat, for `/status` and the leak warning.
+pub static HEAP_TREND: Mutex<CriticalSectionRawMutex, heap::Trend> = Mutex::new(heap::Trend::new());
+
+/// The DHCP address as last seen by the WiFi task, for `/status`.
+pub static DHCP_LEASE: Mutex<CriticalSectionRawMutex, LeaseWatch> = Mutex::new(LeaseWatch::new());
+
+/// WiFi reconnects after the link had been up, for `/counters`.
+pub static WIFI_RECONNECTS: Counter = Counter::new();
+
+/// Set once `reboot_task` starts shutting down; the admin pages refuse
+/// flash writes from then on.
+pub static REBOOTING: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    // Bounds of the main stack, from esp-hal's linker script. It grows
+    // down from `_stack_start_cpu0` towards `_stack_end_cpu0`.
+    static _stack_start_cpu0: u32;
+    static _stack_end_cpu0: u32;
+}
+
+/// Size of the main stack, which every task shares.
+pub fn stack_size() -> usize {
+    let top = core::ptr::addr_of!(_stack_start_cpu0) as usize;
+    top - core::ptr::addr_of!(_stack_end_cpu0) as usize
+}
+
+/// Paint the main stack below the caller's frame for [`stack_unused`].
+/// Leaves 1 KiB under the caller for its callees, and masks interrupts
+/// meanwhile since their frames land on this stack too.
+fn paint_stack() {
+    let here = 0u8;
+    let limit = (core::ptr::addr_of!(here) as usize - 1024) & !3;
+    let bottom = core::ptr::addr_of!(_stack_end_cpu0) as *mut u32;
+    CriticalSectionRawMutex::new().lock(|| {
+        let mut p = bottom;
+        while (p as usize) < limit {
+            // SAFETY: between the stack's end and a kilobyte below the
+            // live frame; nothing else runs while interrupts are masked.
+            unsafe {
+                p.write_volatile(stack_watermark::PAINT);
+                p = p.add(1);
+            }
+        }
+    });
+}
+
+/// Bytes of the main stack no frame has reached since boot.
+pub fn stack_unused() -> usize {
+    let bottom = core::ptr::addr_of!(_stack_end_cpu0) as *const u32;
+    let words = stack_size() / 4;
+    // SAFETY: every word read lies within the stack; the scan stops at
+    // the first one a frame has touched, well short of live frames.
+    let bottom_up = (0..words).map(|i| unsafe { bottom.add(i).read_volatile() });
+    stack_watermark::unused_bytes(bottom_up)
+}
+
+/// Sample the free heap into [`HEAP_LOW_WATER`] and return it.
+pub fn sample_heap() -> usize {
+    let free = esp_alloc::HEAP.free();
+    if HEAP_LOW_WATER.sample(free) {
+        log::debug!("heap: new low of {} bytes free", free);
+    }
+    free
+}
+
+/// Sample every subsystem for [`health::health`]. `syncs` is whether
+/// anything syncs from Conway, as for `status_and_config_task`.
+pub async fn sample_health(stack: &Stack<'_>, syncs: bool) -> health::Inputs {
+    let fobs_loaded = {
+        let st = sync::CACHE_STATE.lock().await;
+        st.live.is_some() || st.persisted.is_some()
+    };
+    health::Inputs {
+        wifi_up: stack.is_link_up(),
+        ip_configured: stack.config_v4().is_some(),
+        syncs,
+        fobs_loaded,
+        sync_failures: sync::SYNC_FAILURES.load(Ordering::Relaxed),
+        offline: sync::SYNC_AGE.lock().await.offline(),
+        flash_healthy: match FLASH_STATE.load(Ordering::Relaxed) {
+            FLASH_HEALTHY => Some(true),
+            FLASH_UNHEALTHY => Some(false),
+            _ => None,
+        },
+        reader_online: match READER_STATE.load(Ordering::Relaxed) {
+            READER_ONLINE => Some(true),
+            READER_OFFLINE => Some(false),
+            _ => None,
+        },
+        heap_free: sample_heap(),
+    }
+}
+
 /// Pending configuration staged by a `POST /config` that touches the
 /// `trusted_pubkey` field. Committed (written to flash + reboot) only
 /// after the operator presses the CONFIG button within
@@ -177,15 +395,100 @@ static FOBS: StaticCell<Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FO
 static LOCAL_FOBS: StaticCell<
     Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
 > = StaticCell::new();
-static ETAG: StaticCell<Mutex<CriticalSectionRawMutex, HString<64>>> = StaticCell::new();
+static ETAG: StaticCell<Mutex<CriticalSectionRawMutex, HostEtag>> = StaticCell::new();
 static LAST_SWIPE: StaticCell<Mutex<CriticalSectionRawMutex, Option<LastSwipe>>> =
     StaticCell::new();
-static STACK_RESOURCES: StaticCell<StackResources<8>> = StaticCell::new();
+static STACK_RESOURCES: StaticCell<StackResources<{ sockets::STACK_SOCKETS }>> =
+    StaticCell::new();
+// `online_check_task`s the build can spawn: one per reader.
+const ONLINE_CHECKS: usize = match option_env!("CONWAY_AUTHZ_PATH") {
+    None => 0,
+    Some(_) if cfg!(feature = "osdp") => MAX_READERS,
+    Some(_) => 1,
+};
+// Going over the socket count panics inside the stack at runtime; catch
+// it here instead when a service is added.
+const _: () = assert!(
+    sockets::fits(option_env!("CONWAY_PUSH_PATH").is_some(), ONLINE_CHECKS),
+    "enabled services need more sockets than sockets::STACK_SOCKETS"
+);
+// Likewise for the heap: the firmware's own worst case must leave room
+// for the radio. How much room is checked at boot.
+const _: () = assert!(
+    heap::fits(option_env!("CONWAY_PUSH_PATH").is_some(), ONLINE_CHECKS),
+    "enabled features need more heap than heap::HEAP_SIZE"
+);
 static STACK: StaticCell<Stack<'static>> = StaticCell::new();
 
 // Type alias for the watchdog timer
 type WdtType = Wdt<esp_hal::peripherals::TIMG1<'static>>;
-static WDT: StaticCell<Mutex<CriticalSectionRawMutex, WdtType>> = StaticCell::new();
+
+/// The hardware watchdog, once `main` has set it up. Behind a blocking
+/// mutex so that [`feed_watchdog`] works from synchronous code in the
+/// middle of a long operation.
+static WDT: BlockingMutex<CriticalSectionRawMutex, RefCell<Option<WdtType>>> =
+    BlockingMutex::new(RefCell::new(None));
+
+/// Feed the hardware watchdog now. `access_task` calls this on every
+/// `WATCHDOG_FEED` tick; long operations go through [`with_watchdog`].
+fn feed_watchdog() {
+    WDT.lock(|wdt| {
+        if let Some(wdt) = wdt.borrow_mut().as_mut() {
+            wdt.feed();
+        }
+    });
+}
+
+/// Handed to a long operation so it can keep the watchdog fed between
+/// steps. See [`watchdog::FeedCadence`].
+pub struct Feeder {
+    cadence: FeedCadence,
+}
+
+impl Feeder {
+    /// Feed now and start the cadence.
+    pub fn start() -> Self {
+        let mut feeder = Self {
+            cadence: FeedCadence::new(watchdog::LONG_OP_FEED_INTERVAL_MS),
+        };
+        feeder.tick();
+        feeder
+    }
+
+    /// Call between steps: feeds if the cadence says one is due.
+    pub fn tick(&mut self) {
+        if self.cadence.due(BootClock.now_ms()) {
+            feed_watchdog();
+        }
+    }
+
+    /// Feed once more now that the operation is over, and warn if a
+    /// single step came close to the timeout.
+    pub fn finish(mut self, what: &str) {
+        feed_watchdog();
+        self.cadence.fed(BootClock.now_ms());
+        if self.cadence.longest_gap_ms() > watchdog::TIMEOUT_MS / 2 {
+            log::warn!(
+                "watchdog: {} went {} ms between feeds (timeout {} ms)",
+                what,
+                self.cadence.longest_gap_ms(),
+                watchdog::TIMEOUT_MS
+            );
+        }
+    }
+}
+
+/// Run `op`, a long blocking operation such as a flash save or erase,
+/// with the watchdog fed before it starts and after it ends. `op` calls
+/// [`Feeder::tick`] between its own steps if it has any. `what` names
+/// it in the warning logged if a step ran close to the timeout. Wrap
+/// only the blocking flash calls themselves; see [`watchdog`] for why.
+pub fn with_watchdog<T>(what: &str, op: impl FnOnce(&mut Feeder) -> T) -> T {
+    let mut feeder = Feeder::start();
+    let out = op(&mut feeder);
+    feeder.finish(what);
+    out
+}
 
 #[esp_rtos::main]
 async fn main(spawner: embassy_executor::Spawner) {
@@ -215,14 +518,14 @@ async fn main(spawner: embassy_executor::Spawner) {
 
     init_logger(log::LevelFilter::Info);
     log::info!("Conway Access Controller starting...");
+    paint_stack();
 
     // Initialize heap
-    const HEAP_SIZE: usize = 72 * 1024;
-    static mut HEAP: MaybeUninit<[u8; HEAP_SIZE]> = MaybeUninit::uninit();
+    static mut HEAP: MaybeUninit<[u8; heap::HEAP_SIZE]> = MaybeUninit::uninit();
     unsafe {
         esp_alloc::HEAP.add_region(esp_alloc::HeapRegion::new(
             HEAP.as_mut_ptr() as *mut u8,
-            HEAP_SIZE,
+            heap::HEAP_SIZE,
             esp_alloc::MemoryCapability::Internal.into(),
         ));
     }
@@ -237,10 +540,10 @@ async fn main(spawner: embassy_executor::Spawner) {
     // Feeding is done by access_task to prove it's not blocked.
     let timg1 = TimerGroup::new(peripherals.TIMG1);
     let mut wdt = timg1.wdt;
-    wdt.set_timeout(MwdtStage::Stage0, HalDuration::from_secs(30));
+    wdt.set_timeout(MwdtStage::Stage0, HalDuration::from_millis(watchdog::TIMEOUT_MS));
     wdt.set_stage_action(MwdtStage::Stage0, MwdtStageAction::ResetSystem);
     wdt.enable();
-    let wdt = WDT.init(Mutex::new(wdt));
+    WDT.lock(|cell| *cell.borrow_mut() = Some(wdt));
     log::info!("watchdog: initialized with 30s timeout");
 
     // Load persisted settings. Empty / missing => first boot or post-
@@ -306,9 +609,33 @@ async fn main(spawner: embassy_executor::Spawner) {
         loaded.conway_port,
     );
 
-    // Initialize shared state (fobs and etag start empty, populated by sync)
-    let fobs = FOBS.init(Mutex::new(heapless::Vec::new()));
-    let etag = ETAG.init(Mutex::new(HString::new()));
+    // Initialize shared state from the flash copy of the last synced
+    // cache, if any; sync replaces it once Conway is reachable. RAM is
+    // empty here, so reconcile only ever picks flash or nothing, but
+    // going through it keeps boot and `sync::persist_cache` agreeing on
+    // which seq is current.
+    let cached = cache_store::load();
+    let flash_meta = cached.as_ref().map(|c| c.meta());
+    let (boot_fobs, boot_etag) = match (fob_cache::reconcile(None, flash_meta), cached) {
+        (Reconcile::PreferFlash, Some(c)) => {
+            log::info!(
+                "storage: restored {} cached fobs from flash (seq={})",
+                c.fobs.len(),
+                c.seq
+            );
+            if let Ok(mut st) = sync::CACHE_STATE.try_lock() {
+                st.live = flash_meta;
+                st.persisted = flash_meta;
+            }
+            (c.fobs, c.etag)
+        }
+        _ => {
+            log::info!("storage: fob cache initialized (empty, will sync from server)");
+            (heapless::Vec::new(), HostEtag::new())
+        }
+    };
+    let fobs = FOBS.init(Mutex::new(boot_fobs));
+    let etag = ETAG.init(Mutex::new(boot_etag));
     let last_swipe = LAST_SWIPE.init(Mutex::new(None));
 
     // Load locally-managed fobs from flash. Empty on first boot / after a
@@ -320,8 +647,6 @@ async fn main(spawner: embassy_executor::Spawner) {
     );
     let local_fobs = LOCAL_FOBS.init(Mutex::new(local_fobs_loaded));
 
-    log::info!("storage: fob cache initialized (empty, will sync from server)");
-
     // Leak the radio controller to get 'static lifetime before creating WiFi.
     let esp_radio_ctrl: &'static _ = Box::leak(Box::new(esp_radio_ctrl));
 
@@ -333,10 +658,39 @@ async fn main(spawner: embassy_executor::Spawner) {
     // uses our static 192.168.4.1/24 + our DHCP server.
     let stack_resources = STACK_RESOURCES.init(StackResources::new());
 
-    // MAC-derived RNG seed (also drives the onboarding SSID).
+    // The radio is up by now, so the hardware RNG is drawing on RF noise.
     let mac = esp_radio::wifi::sta_mac();
-    let seed = u64::from_le_bytes([mac[0], mac[1], mac[2], mac[3], mac[4], mac[5], 0, 0]);
-    log::info!("rng: seed={:016X}", seed);
+    let mut rng = entropy::Entropy::new();
+    log::info!(
+        "rng: {}",
+        if rng.is_hardware() { "hardware" } else { "MAC-seeded fallback" }
+    );
+    // Also picks where the stack's ephemeral ports start (49152..65535,
+    // advancing one per connect), so a fresh boot doesn't reconnect from
+    // the previous boot's ports into the server's TIME-WAIT.
+    let seed = rng.next_u64();
+
+    // Event batch keys: MAC + a random per-boot value, so the counter
+    // restarting at 0 after a reboot never reuses a key.
+    let boot_nonce = rng.next_u32();
+    if let Ok(mut keys) = sync::BATCH_KEYS.try_lock() {
+        *keys = access_controller::idempotency::BatchKeys::new(mac, boot_nonce);
+    }
+
+    // Worn-out or write-protected flash otherwise only shows up as a
+    // logged failure on the first save, after which nothing persists.
+    let scratch = access_controller::flash_layout::FLASH.scratch();
+    match access_controller::storage::check_writable(&mut flash::EspFlash::new(), scratch, boot_nonce) {
+        Ok(()) => FLASH_STATE.store(FLASH_HEALTHY, Ordering::Relaxed),
+        Err(e) => {
+            log::error!(
+                "storage: flash check at {:#x} failed ({}); settings, fobs and cache will not persist",
+                scratch,
+                e
+            );
+            FLASH_STATE.store(FLASH_UNHEALTHY, Ordering::Relaxed);
+        }
+    }
 
     // Build the AP SSID up front so both the WiFi task and the UI can
     // see it (the latter via RuntimeConfig).
@@ -354,14 +708,7 @@ async fn main(spawner: embassy_executor::Spawner) {
         DeviceMode::Station => {
             let dev: esp_radio::wifi::WifiDevice<'static> =
                 unsafe { core::mem::transmute(interfaces.sta) };
-            // Advertise the conway-XXXXXX hostname (DHCP option 12) so the
-            // unit is identifiable in the router's DHCP lease table after
-            // onboarding -- the primary way to find its new IP.
-            let mut dhcp = embassy_net::DhcpConfig::default();
-            let mut hostname = heapless::String::new();
-            let _ = hostname.push_str(&ap_ssid_str);
-            dhcp.hostname = Some(hostname);
-            (dev, NetConfig::dhcpv4(dhcp))
+            (dev, NetConfig::dhcpv4(dhcp_config(&ap_ssid_str)))
         }
         DeviceMode::Onboarding => {
             let dev: esp_radio::wifi::WifiDevice<'static> =
@@ -402,37 +749,178 @@ async fn main(spawner: embassy_executor::Spawner) {
 
     // Setup GPIO pins (see HARDWARE.md for full pin map).
     //
-    // Wiegand inputs: driven by SN74LVC2G17 non-inverting Schmitt buffer
-    // (3V3 output, actively driven), so no internal pull is required.
-    let d0 = Input::new(
+    // Board pin map (see HARDWARE.md). Same nets, same order along the
+    // left header of each DevKit.
+    #[cfg(feature = "esp32")]
+    let (d0_pin, d1_pin, door_pin, reader_led_pin, reader_beep_pin, status_led_pin, config_btn_pin) = (
         peripherals.GPIO25,
-        InputConfig::default().with_pull(Pull::None),
-    );
-    let d1 = Input::new(
         peripherals.GPIO33,
-        InputConfig::default().with_pull(Pull::None),
+        peripherals.GPIO12,
+        peripherals.GPIO26,
+        peripherals.GPIO27,
+        peripherals.GPIO14,
+        peripherals.GPIO35,
+    );
+    #[cfg(feature = "esp32s3")]
+    let (d0_pin, d1_pin, door_pin, reader_led_pin, reader_beep_pin, status_led_pin, config_btn_pin) = (
+        peripherals.GPIO7,
+        peripherals.GPIO6,
+        peripherals.GPIO5,
+        peripherals.GPIO15,
+        peripherals.GPIO16,
+        peripherals.GPIO17,
+        peripherals.GPIO4,
     );
 
+    // Wiegand inputs: driven by SN74LVC2G17 non-inverting Schmitt buffer
+    // (3V3 output, actively driven), so no internal pull is required.
+    let d0 = Input::new(d0_pin, InputConfig::default().with_pull(Pull::None));
+    let d1 = Input::new(d1_pin, InputConfig::default().with_pull(Pull::None));
+
+    // Wiegand pass-through to a downstream panel, off the carrier board
+    // (see HARDWARE.md).
+    #[cfg(all(feature = "wiegand-tx", feature = "esp32"))]
+    let (tx_d0_pin, tx_d1_pin) = (peripherals.GPIO32, peripherals.GPIO13);
+    #[cfg(all(feature = "wiegand-tx", feature = "esp32s3"))]
+    let (tx_d0_pin, tx_d1_pin) = (peripherals.GPIO18, peripherals.GPIO8);
+
+    // Door contact: reed switch to GND, LOW = closed (see HARDWARE.md).
+    // GPIO34 has no internal pull, so the ESP32 needs an external one.
+    #[cfg(all(feature = "door-contact", feature = "esp32"))]
+    let contact_pin = peripherals.GPIO34;
+    #[cfg(all(feature = "door-contact", feature = "esp32s3"))]
+    let contact_pin = peripherals.GPIO14;
+    #[cfg(feature = "door-contact")]
+    let contact: DoorContact = Input::new(contact_pin, InputConfig::default().with_pull(Pull::Up));
+    #[cfg(not(feature = "door-contact"))]
+    let contact: DoorContact = ();
+
+    // Supply status: UPS open-collector outputs to GND, LOW = asserted
+    // (see HARDWARE.md). GPIO36/39 have no internal pull either.
+    #[cfg(all(feature = "power-monitor", feature = "esp32"))]
+    let (on_battery_pin, battery_low_pin) = (peripherals.GPIO36, peripherals.GPIO39);
+    #[cfg(all(feature = "power-monitor", feature = "esp32s3"))]
+    let (on_battery_pin, battery_low_pin) = (peripherals.GPIO1, peripherals.GPIO2);
+
+    // OSDP reader on an external RS-485 transceiver (see HARDWARE.md).
+    #[cfg(all(feature = "osdp", feature = "esp32"))]
+    let (osdp_tx_pin, osdp_rx_pin, osdp_de_pin) = (peripherals.GPIO17, peripherals.GPIO16, peripherals.GPIO4);
+    #[cfg(all(feature = "osdp", feature = "esp32s3"))]
+    let (osdp_tx_pin, osdp_rx_pin, osdp_de_pin) = (peripherals.GPIO10, peripherals.GPIO11, peripherals.GPIO12);
+
     // Output drivers: SS8050 NPN low-side switches, so GPIO HIGH = load energized.
-    let door = Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default());
-    let reader_led = Output::new(peripherals.GPIO26, Level::Low, OutputConfig::default());
-    let reader_beep = Output::new(peripherals.GPIO27, Level::Low, OutputConfig::default());
-    let status_led = Output::new(peripherals.GPIO14, Level::Low, OutputConfig::default());
+    let door = Output::new(door_pin, Level::Low, OutputConfig::default());
+    let reader_led = Output::new(reader_led_pin, Level::Low, OutputConfig::default());
+    let reader_beep = Output::new(reader_beep_pin, Level::Low, OutputConfig::default());
+    let status_led = Output::new(status_led_pin, Level::Low, OutputConfig::default());
 
     // CONFIG button: external 10k pull-up to 3V3 + 100nF debounce cap.
-    // GPIO35 is input-only on the ESP32, so we rely on the external pull-up.
-    let config_btn = Input::new(
-        peripherals.GPIO35,
-        InputConfig::default().with_pull(Pull::None),
-    );
-
-    // Create Wiegand reader
-    let wiegand = Wiegand::new(d0, d1);
+    // GPIO35 is input-only on the ESP32, so we rely on the external pull-up
+    // (on both boards, to keep the wiring identical).
+    let config_btn = Input::new(config_btn_pin, InputConfig::default().with_pull(Pull::None));
+
+    // Create Wiegand reader. The pulse-width filter and inter-frame gap
+    // are for the GPIO reader only; the RMT channels filter glitches in
+    // hardware and capture each frame whole.
+    #[cfg(not(feature = "wiegand-rmt"))]
+    let pulse_filter = match option_env!("CONWAY_WIEGAND_MIN_PULSE_US") {
+        None => PulseFilter::default(),
+        Some(s) => PulseFilter::parse(s).unwrap_or_else(|| {
+            log::warn!("wiegand: invalid CONWAY_WIEGAND_MIN_PULSE_US {:?}, filter off", s);
+            PulseFilter::default()
+        }),
+    };
+    #[cfg(not(feature = "wiegand-rmt"))]
+    let frame_gap = match option_env!("CONWAY_WIEGAND_MIN_GAP_MS") {
+        None => FrameGap::default(),
+        Some(s) => FrameGap::parse(s).unwrap_or_else(|| {
+            log::warn!("wiegand: invalid CONWAY_WIEGAND_MIN_GAP_MS {:?}, no gap", s);
+            FrameGap::default()
+        }),
+    };
+    // Keep-alive pulses arrive as short frames; with monitoring on they
+    // are expected, not worth a warning each.
+    let keepalive_ms = reader_keepalive_ms();
+    #[cfg(not(feature = "wiegand-rmt"))]
+    let wiegand = Wiegand::new(d0, d1)
+        .with_pulse_filter(pulse_filter)
+        .with_frame_gap(frame_gap)
+        .with_keepalive(keepalive_ms.is_some());
+    #[cfg(feature = "wiegand-rmt")]
+    let wiegand = Wiegand::new(d0, d1, peripherals.RMT).with_keepalive(keepalive_ms.is_some());
+
+    // The radio has taken its share; what is left must cover the tasks'
+    // worst case. Short is logged, not fatal: the estimate assumes every
+    // task peaks at once, and a controller that boots still opens doors.
+    let heap_free = sample_heap();
+    let heap_peak = heap::peak(heap::Build {
+        push: option_env!("CONWAY_PUSH_PATH").is_some(),
+        body_max: http::body_max(),
+        online_checks: ONLINE_CHECKS,
+    });
+    if heap_free < heap_peak {
+        log::error!(
+            "heap: {} bytes free after radio init, tasks may need {}; expect OOM reboots",
+            heap_free,
+            heap_peak
+        );
+    } else {
+        log::info!("heap: {} bytes free, worst-case task use {}", heap_free, heap_peak);
+    }
 
     // Spawn tasks
     spawner.spawn(net_task(runner)).unwrap();
-    spawner.spawn(wifi_task(wifi_controller, rt_config)).unwrap();
+    spawner.spawn(wifi_task(wifi_controller, stack, rt_config)).unwrap();
     spawner.spawn(wiegand_task(wiegand)).unwrap();
+    #[cfg(feature = "wiegand-tx")]
+    {
+        let tx = wiegand_tx::WiegandTx::new(
+            Output::new(tx_d0_pin, Level::Low, OutputConfig::default()),
+            Output::new(tx_d1_pin, Level::Low, OutputConfig::default()),
+        );
+        // Unset sends each read in the smallest format that holds it.
+        let format = match option_env!("CONWAY_WIEGAND_TX_FORMAT") {
+            None => None,
+            Some(f) => match access_controller::tx_plan::TxFormat::parse(f) {
+                Some(format) => Some(format),
+                None => {
+                    log::warn!("wiegand: invalid CONWAY_WIEGAND_TX_FORMAT {:?}, matching each read", f);
+                    None
+                }
+            },
+        };
+        spawner.spawn(wiegand_tx_task(tx, format)).unwrap();
+    }
+    #[cfg(feature = "osdp")]
+    {
+        use access_controller::osdp;
+        use esp_hal::uart::{Config as UartConfig, Uart};
+
+        // OSDP's default line rate, 8N1.
+        let uart = Uart::new(peripherals.UART1, UartConfig::default().with_baudrate(9600))
+            .unwrap()
+            .with_tx(osdp_tx_pin)
+            .with_rx(osdp_rx_pin)
+            .into_async();
+        let de = Output::new(osdp_de_pin, Level::Low, OutputConfig::default());
+        let addr = match option_env!("CONWAY_OSDP_ADDRESS") {
+            None => osdp::BROADCAST,
+            Some(a) => osdp::parse_address(a).unwrap_or_else(|| {
+                log::warn!("osdp: invalid CONWAY_OSDP_ADDRESS {:?}, using broadcast", a);
+                osdp::BROADCAST
+            }),
+        };
+        spawner.spawn(osdp_task(osdp_reader::OsdpReader::new(uart, de, addr))).unwrap();
+    }
+    #[cfg(feature = "power-monitor")]
+    {
+        let on_battery = Input::new(on_battery_pin, InputConfig::default().with_pull(Pull::Up));
+        let battery_low = Input::new(battery_low_pin, InputConfig::default().with_pull(Pull::Up));
+        spawner.spawn(power_task(on_battery, battery_low)).unwrap();
+    }
+    if let Some(ms) = keepalive_ms {
+        spawner.spawn(reader_watch_task(ms)).unwrap();
+    }
     // Conway vs. standalone is fixed for this boot (changing the host goes
     // through settings::save() + reboot). When no Conway host is configured
     // we persist every swipe to flash instead of uploading it.
@@ -441,23 +929,117 @@ async fn main(spawner: embassy_executor::Spawner) {
         s.conway_enabled()
     };
     let log_to_flash = !conway_enabled;
+    if !sync::report_grants() {
+        log::info!("events: reporting denials only");
+        EVENT_BUFFER.set_report_grants(false).await;
+    }
+    // Number events on from wherever the last boot may have got to.
+    sync::resume_event_seq().await;
+    let dedup = sync::event_dedup();
+    if dedup.window_ms() > 0 {
+        log::info!("events: suppressing repeats within {} ms", dedup.window_ms());
+        EVENT_BUFFER.set_dedup(dedup).await;
+    }
+    let hold_ms = match option_env!("CONWAY_DOOR_HOLD_MS") {
+        None => door::DEFAULT_HOLD_MS,
+        Some(s) => door::parse_hold_ms(s).unwrap_or_else(|| {
+            log::warn!("door: invalid CONWAY_DOOR_HOLD_MS {:?}, using {} ms", s, door::DEFAULT_HOLD_MS);
+            door::DEFAULT_HOLD_MS
+        }),
+    };
+    let extended_ms = match option_env!("CONWAY_DOOR_EXTENDED_HOLD_MS") {
+        None => door::DEFAULT_EXTENDED_HOLD_MS,
+        Some(s) => door::parse_hold_ms(s).unwrap_or_else(|| {
+            log::warn!(
+                "door: invalid CONWAY_DOOR_EXTENDED_HOLD_MS {:?}, using {} ms",
+                s,
+                door::DEFAULT_EXTENDED_HOLD_MS
+            );
+            door::DEFAULT_EXTENDED_HOLD_MS
+        }),
+    };
+    let holds = door::Holds {
+        default_ms: hold_ms,
+        extended_ms,
+    };
+    // Only a controller that syncs has a host to ask.
+    let online_check = online_check().filter(|_| mode == DeviceMode::Station && conway_enabled);
     spawner
         .spawn(access_task(
-            fobs, local_fobs, last_swipe, wdt, rt_config, log_to_flash,
+            fobs,
+            local_fobs,
+            last_swipe,
+            rt_config,
+            log_to_flash,
+            holds,
+            online_check.map(|(_, timeout_ms)| timeout_ms),
         ))
         .unwrap();
-    spawner.spawn(door_task(door)).unwrap();
+    let relock = match option_env!("CONWAY_DOOR_RELOCK") {
+        None => Relock::default(),
+        Some(s) => Relock::parse(s).unwrap_or_else(|| {
+            log::warn!("door: invalid CONWAY_DOOR_RELOCK {:?}, holding for the full time", s);
+            Relock::default()
+        }),
+    };
+    if relock == Relock::OnClose && !cfg!(feature = "door-contact") {
+        log::warn!("door: CONWAY_DOOR_RELOCK=close needs the door-contact feature; holding for the full time");
+    }
+    spawner.spawn(door_task(door, contact, relock)).unwrap();
+    let grant_cues = feedback_cues(
+        "CONWAY_FEEDBACK_GRANT",
+        option_env!("CONWAY_FEEDBACK_GRANT"),
+        feedback::DEFAULT_GRANT,
+    );
+    let deny_cues = feedback_cues(
+        "CONWAY_FEEDBACK_DENY",
+        option_env!("CONWAY_FEEDBACK_DENY"),
+        feedback::DEFAULT_DENY,
+    );
     spawner
-        .spawn(reader_feedback_task(reader_led, reader_beep))
+        .spawn(reader_feedback_task(reader_led, reader_beep, grant_cues, deny_cues))
         .unwrap();
+    // Only a controller that syncs can be offline.
+    if mode == DeviceMode::Station && conway_enabled {
+        *sync::SYNC_AGE.lock().await = SyncAge::new(sync::offline_after_secs());
+    }
     spawner
-        .spawn(status_and_config_task(status_led, config_btn, stack))
+        .spawn(status_and_config_task(
+            status_led,
+            config_btn,
+            stack,
+            mode == DeviceMode::Station && conway_enabled,
+        ))
         .unwrap();
     // Sync task only makes sense in station mode AND when a Conway host
     // is configured. Standalone mode (no host) skips it entirely and
     // instead drains the offline swipe log to flash.
     if mode == DeviceMode::Station && conway_enabled {
         spawner.spawn(sync_task(stack, fobs, etag, rt_config)).unwrap();
+        if let Some((path, timeout_ms)) = online_check {
+            // One checker per reader, so a slow answer at one door doesn't
+            // hold up the other.
+            for _ in 0..ONLINE_CHECKS {
+                spawner
+                    .spawn(online_check::online_check_task(stack, rt_config, path, timeout_ms))
+                    .unwrap();
+            }
+        }
+        // Optional push channel on top of polling; see `push.rs`.
+        let transport = match option_env!("CONWAY_PUSH_TRANSPORT") {
+            None => push::Transport::WebSocket,
+            Some(t) => push::Transport::parse(t).unwrap_or_else(|| {
+                log::warn!("push: unknown CONWAY_PUSH_TRANSPORT {:?}, using websocket", t);
+                push::Transport::WebSocket
+            }),
+        };
+        match option_env!("CONWAY_PUSH_PATH") {
+            Some(p) if access_controller::http_client::valid_path(p) => {
+                spawner.spawn(push::push_task(stack, rt_config, p, transport)).unwrap()
+            }
+            Some(_) => log::warn!("push: invalid CONWAY_PUSH_PATH, push disabled"),
+            None => {}
+        }
     } else if mode == DeviceMode::Station {
         log::info!("sync: disabled (standalone mode, no Conway host configured)");
     }
@@ -470,6 +1052,16 @@ async fn main(spawner: embassy_executor::Spawner) {
         ))
         .unwrap();
     spawner.spawn(watchdog_feed_task()).unwrap();
+    let schedule = reboot_schedule();
+    if schedule.enabled() {
+        spawner
+            .spawn(reboot_task(
+                schedule,
+                last_swipe,
+                mode == DeviceMode::Station && conway_enabled,
+            ))
+            .unwrap();
+    }
 
     // Onboarding-only services.
     if mode == DeviceMode::Onboarding {
@@ -487,12 +1079,18 @@ async fn net_task(mut runner: embassy_net::Runner<'static, esp_radio::wifi::Wifi
 
 /// WiFi connection management.
 ///
-/// In `Station` mode, retries connection every 5 seconds. In `Onboarding`
+/// In `Station` mode, retries connection every 5 seconds, and reconnects
+/// a link that has gone `CONWAY_DHCP_TIMEOUT_SECS` without a DHCP lease
+/// (see [`DhcpWatch`]). In `Onboarding`
 /// mode brings up the AP exactly once and then idles - the AP is a
 /// background service that doesn't need re-application unless the radio
 /// firmware crashes (in which case the hardware watchdog will reboot us).
 #[embassy_executor::task]
-async fn wifi_task(mut controller: WifiController<'static>, rt: &'static RuntimeConfig) {
+async fn wifi_task(
+    mut controller: WifiController<'static>,
+    stack: &'static Stack<'static>,
+    rt: &'static RuntimeConfig,
+) {
     use alloc::string::ToString;
 
     match rt.mode {
@@ -527,10 +1125,47 @@ async fn wifi_task(mut controller: WifiController<'static>, rt: &'static Runtime
                 let s = rt.settings.lock().await;
                 (s.ssid.clone(), s.password.clone())
             };
+            let dhcp_timeout_secs = dhcp_timeout_secs();
+            let mut dhcp = DhcpWatch::new(dhcp_timeout_secs);
+            let mut was_connected = false;
 
             loop {
+                let associated = controller.is_connected().unwrap_or(false);
+                let configured = stack.config_v4().is_some();
+                if dhcp.poll(BootClock.now_ms(), associated, configured) == DhcpAction::Reconnect {
+                    log::warn!(
+                        "wifi: no DHCP lease after {} s, reconnecting ({} so far)",
+                        dhcp_timeout_secs,
+                        dhcp.reconnects()
+                    );
+                    // The link going down resets embassy-net's DHCP
+                    // client; the reconnect below starts it over.
+                    if let Err(e) = controller.disconnect() {
+                        log::error!("wifi: disconnect failed: {:?}", e);
+                    }
+                    Timer::after(Duration::from_millis(500)).await;
+                }
+                let address = stack.config_v4().map(|c| c.address.address().octets());
+                let event = DHCP_LEASE.lock().await.poll(BootClock.now_ms(), address);
+                if let Some(event) = event {
+                    if let LeaseEvent::Lost(_) = event {
+                        log::warn!("{}", event);
+                        // Deconfigured with the link still up: restart
+                        // DHCP from DISCOVER now instead of waiting on the
+                        // client's own retransmit backoff.
+                        if controller.is_connected().unwrap_or(false) {
+                            log::info!("dhcp: requesting a new lease");
+                            stack.set_config_v4(embassy_net::ConfigV4::Dhcp(dhcp_config(&rt.ap_ssid)));
+                        }
+                    } else {
+                        log::info!("{}", event);
+                    }
+                }
                 if !controller.is_connected().unwrap_or(false) {
                     log::info!("wifi: connecting to {}", ssid);
+                    if was_connected {
+                        WIFI_RECONNECTS.incr();
+                    }
 
                     let _ = controller.stop();
                     Timer::after(Duration::from_millis(100)).await;
@@ -552,6 +1187,7 @@ async fn wifi_task(mut controller: WifiController<'static>, rt: &'static Runtime
                     for _ in 0..100 {
                         if controller.is_connected().unwrap_or(false) {
                             log::info!("wifi: connected");
+                            was_connected = true;
                             break;
                         }
                         Timer::after(Duration::from_millis(200)).await;
@@ -564,33 +1200,250 @@ async fn wifi_task(mut controller: WifiController<'static>, rt: &'static Runtime
     }
 }
 
+/// DHCP client settings for Station mode. Advertises the conway-XXXXXX
+/// hostname (DHCP option 12) so the unit is identifiable in the router's
+/// DHCP lease table after onboarding -- the primary way to find its new IP.
+fn dhcp_config(hostname: &str) -> embassy_net::DhcpConfig {
+    let mut dhcp = embassy_net::DhcpConfig::default();
+    let mut name = heapless::String::new();
+    let _ = name.push_str(hostname);
+    dhcp.hostname = Some(name);
+    dhcp
+}
+
+/// `CONWAY_DHCP_TIMEOUT_SECS`, or the default if unset or invalid.
+fn dhcp_timeout_secs() -> u64 {
+    match option_env!("CONWAY_DHCP_TIMEOUT_SECS") {
+        None => dhcp_watch::DEFAULT_DHCP_TIMEOUT_SECS,
+        Some(s) => dhcp_watch::parse_timeout_secs(s).unwrap_or_else(|| {
+            log::warn!(
+                "wifi: invalid CONWAY_DHCP_TIMEOUT_SECS {:?}, using {} s",
+                s,
+                dhcp_watch::DEFAULT_DHCP_TIMEOUT_SECS
+            );
+            dhcp_watch::DEFAULT_DHCP_TIMEOUT_SECS
+        }),
+    }
+}
+
+/// `CONWAY_REBOOT_AFTER_HOURS` and `CONWAY_REBOOT_WINDOW`; never
+/// reboots if the hours are unset or invalid.
+fn reboot_schedule() -> reboot::Schedule {
+    let hours = match option_env!("CONWAY_REBOOT_AFTER_HOURS") {
+        None => 0,
+        Some(s) => reboot::parse_after_hours(s).unwrap_or_else(|| {
+            log::warn!("reboot: invalid CONWAY_REBOOT_AFTER_HOURS {:?}, scheduled reboot disabled", s);
+            0
+        }),
+    };
+    let window = option_env!("CONWAY_REBOOT_WINDOW").and_then(|s| {
+        let window = reboot::Window::parse(s);
+        if window.is_none() {
+            log::warn!("reboot: invalid CONWAY_REBOOT_WINDOW {:?}, rebooting whenever due", s);
+        }
+        window
+    });
+    reboot::Schedule::new(hours, window)
+}
+
+/// `CONWAY_READER_KEEPALIVE_MS`: the reader keep-alive interval, if
+/// monitoring is on. It is opt-in: only readers that emit periodic pulses
+/// can be told apart from a dead one.
+fn reader_keepalive_ms() -> Option<u64> {
+    match option_env!("CONWAY_READER_KEEPALIVE_MS").map(str::parse::<u64>) {
+        Some(Ok(ms)) if ms > 0 => Some(ms),
+        Some(_) => {
+            log::warn!("reader: invalid CONWAY_READER_KEEPALIVE_MS, monitoring disabled");
+            None
+        }
+        None => None,
+    }
+}
+
+/// `CONWAY_AUTHZ_PATH` and `CONWAY_AUTHZ_TIMEOUT_MS`: where and how long
+/// to check unlisted credentials online; `None` if the path is unset or
+/// invalid.
+fn online_check() -> Option<(&'static str, u64)> {
+    let path = option_env!("CONWAY_AUTHZ_PATH")?;
+    let Some(path) = authz::parse_path(path) else {
+        log::warn!("authz: invalid CONWAY_AUTHZ_PATH {:?}, online check disabled", path);
+        return None;
+    };
+    let timeout_ms = match option_env!("CONWAY_AUTHZ_TIMEOUT_MS") {
+        None => authz::DEFAULT_TIMEOUT_MS,
+        Some(s) => authz::parse_timeout_ms(s).unwrap_or_else(|| {
+            log::warn!(
+                "authz: invalid CONWAY_AUTHZ_TIMEOUT_MS {:?}, using {} ms",
+                s,
+                authz::DEFAULT_TIMEOUT_MS
+            );
+            authz::DEFAULT_TIMEOUT_MS
+        }),
+    };
+    Some((path, timeout_ms))
+}
+
 /// Wiegand reader task - reads cards and sends to channel.
 #[embassy_executor::task]
 async fn wiegand_task(mut wiegand: Wiegand<'static>) {
     loop {
-        if let Some(read) = wiegand.read().await {
+        let result = wiegand.read().await;
+        READER_ACTIVITY.signal(());
+        if let Some(read) = result {
             // try_send FIRST, then log. The next call to wiegand.read()
             // re-arms the edge-wait futures; anything that delays our
             // return there (UART log over 115200 baud takes multiple ms)
             // means edges from a back-to-back swipe are silently lost.
             // log::info on every scan is also a UX/perf footgun in
             // production - downgrade to debug.
-            let send_result = WIEGAND_CHANNEL.try_send(read);
+            let send_result = READS_DROPPED.note(WIEGAND_CHANNEL.try_send((Reader::Wiegand, read)));
+            #[cfg(feature = "wiegand-tx")]
+            if WIEGAND_TX_CHANNEL.try_send(read).is_err() {
+                log::warn!("wiegand: tx queue full, read not passed on");
+            }
             log::debug!("scan: fob={} nfc={:08X}", read.to_fob(), read.to_nfc_uid());
             if send_result.is_err() {
-                log::warn!("wiegand: channel full, read dropped");
+                log::warn!("wiegand: channel full, read dropped ({} so far)", READS_DROPPED.get());
+            }
+        }
+    }
+}
+
+/// OSDP reader task - polls the reader and feeds its card reads into the
+/// same channel as `wiegand_task`.
+#[cfg(feature = "osdp")]
+#[embassy_executor::task]
+async fn osdp_task(mut reader: osdp_reader::OsdpReader<'static>) {
+    loop {
+        if let Some(read) = reader.read().await {
+            let send_result = READS_DROPPED.note(WIEGAND_CHANNEL.try_send((Reader::Osdp, read)));
+            log::debug!("osdp scan: fob={} nfc={:08X}", read.to_fob(), read.to_nfc_uid());
+            if send_result.is_err() {
+                log::warn!("osdp: channel full, read dropped ({} so far)", READS_DROPPED.get());
             }
         }
     }
 }
 
+/// Replays every read to the downstream panel.
+#[cfg(feature = "wiegand-tx")]
+#[embassy_executor::task]
+async fn wiegand_tx_task(
+    mut tx: wiegand_tx::WiegandTx<'static>,
+    format: Option<access_controller::tx_plan::TxFormat>,
+) {
+    use access_controller::tx_plan::TxFormat;
+
+    loop {
+        let read = WIEGAND_TX_CHANNEL.receive().await;
+        tx.send(&read, format.unwrap_or_else(|| TxFormat::for_read(&read))).await;
+    }
+}
+
+/// Reader keep-alive monitor. Flags the reader offline (status page +
+/// a [`READER_OFFLINE_FOB`] event) when no D0/D1 activity has been seen
+/// for `keepalive_ms`, and back online on the next edge.
+#[embassy_executor::task]
+async fn reader_watch_task(keepalive_ms: u64) {
+    use access_controller::reader_watch::{ReaderEvent, ReaderWatch};
+    use embassy_futures::select::{select, Either};
+
+    let mut watch = ReaderWatch::new(keepalive_ms, BootClock.now_ms());
+    READER_STATE.store(READER_ONLINE, Ordering::Relaxed);
+    log::info!("reader: keep-alive monitoring every {} ms", keepalive_ms);
+
+    loop {
+        // Poll at a fraction of the interval so detection lags by at
+        // most ~25%, but no faster than once a second.
+        let tick = Duration::from_millis((keepalive_ms / 4).max(1000));
+        let now = match select(READER_ACTIVITY.wait(), Timer::after(tick)).await {
+            Either::First(()) => {
+                let now = BootClock.now_ms();
+                if watch.activity(now) == Some(ReaderEvent::Online) {
+                    log::info!("reader: activity seen, back online");
+                    READER_STATE.store(READER_ONLINE, Ordering::Relaxed);
+                }
+                now
+            }
+            Either::Second(()) => BootClock.now_ms(),
+        };
+        if watch.poll(now) == Some(ReaderEvent::Offline) {
+            log::warn!("reader: no activity for {} ms, flagging offline", keepalive_ms);
+            READER_STATE.store(READER_OFFLINE, Ordering::Relaxed);
+            EVENT_BUFFER
+                .push(AccessEvent {
+                    fob: READER_OFFLINE_FOB,
+                    allowed: false,
+                })
+                .await;
+        }
+    }
+}
+
+/// Supply monitor. Samples the UPS inputs, debounced by
+/// [`PowerWatch`](access_controller::power::PowerWatch), into
+/// [`POWER_STATE`], and logs an [`ON_BATTERY_FOB`] event when mains is
+/// lost.
+#[cfg(feature = "power-monitor")]
+#[embassy_executor::task]
+async fn power_task(on_battery: Input<'static>, battery_low: Input<'static>) {
+    use access_controller::power::{self, PowerEvent, PowerWatch};
+
+    const POLL_MS: u64 = 250;
+
+    let mut watch = PowerWatch::new(power::DEBOUNCE_MS);
+    POWER_STATE.store(POWER_MAINS, Ordering::Relaxed);
+    log::info!("power: monitoring supply status");
+
+    loop {
+        let now = BootClock.now_ms();
+        for event in watch.sample(now, on_battery.is_low(), battery_low.is_low()) {
+            match event {
+                PowerEvent::OnBattery => {
+                    log::warn!("power: mains lost, running on battery");
+                    EVENT_BUFFER
+                        .push(AccessEvent {
+                            fob: ON_BATTERY_FOB,
+                            allowed: false,
+                        })
+                        .await;
+                }
+                PowerEvent::MainsRestored => log::info!("power: mains restored"),
+                PowerEvent::BatteryLow => log::warn!("power: battery low"),
+                PowerEvent::BatteryOk => log::info!("power: battery no longer low"),
+            }
+        }
+        let state = watch.state();
+        let code = match (state.on_battery, state.battery_low) {
+            (false, _) => POWER_MAINS,
+            (true, false) => POWER_BATTERY,
+            (true, true) => POWER_BATTERY_LOW,
+        };
+        POWER_STATE.store(code, Ordering::Relaxed);
+        Timer::after(Duration::from_millis(POLL_MS)).await;
+    }
+}
+
+/// Supply state for the heartbeat log.
+fn power_label() -> &'static str {
+    match POWER_STATE.load(Ordering::Relaxed) {
+        POWER_MAINS => "mains",
+        POWER_BATTERY => "on battery",
+        POWER_BATTERY_LOW => "on battery, low",
+        _ => "not monitored",
+    }
+}
+
 /// Access control task - checks authorization and triggers door/events.
 ///
 /// CRITICAL: This task must NEVER block on networking. All authorization checks
 /// use only local cached data. Network sync happens asynchronously in sync_task.
 ///
 /// This task also handles watchdog feeding. When WATCHDOG_FEED is signaled,
-/// this task feeds the hardware watchdog, proving it is not blocked.
+/// this task feeds the hardware watchdog, proving it is not blocked. So
+/// that a tick never waits long behind a card read, every wait on shared
+/// state for one input ends within `watchdog::MAX_LOCK_HOLD_MS`; a
+/// decision that can't get its locks by then fails closed.
 ///
 /// All actual decision logic lives in the pure `AccessCore` state machine
 /// (`access_controller::core`) so it can be exercised deterministically from
@@ -604,101 +1457,210 @@ async fn access_task(
     fobs: &'static Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
     local_fobs: &'static Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
     last_swipe: &'static Mutex<CriticalSectionRawMutex, Option<LastSwipe>>,
-    wdt: &'static Mutex<CriticalSectionRawMutex, WdtType>,
     rt: &'static RuntimeConfig,
     // When true (standalone mode), every swipe is also handed to
     // `swipe_log_task` for durable flash logging via `SWIPE_LOG_CHANNEL`.
     log_to_flash: bool,
+    // Strike hold per grant; tagged credentials get the extended one.
+    holds: door::Holds,
+    // Timeout for checking misses online, when `online_check_task` runs.
+    online_check_ms: Option<u64>,
 ) {
-    let mut core = AccessCore::new();
+    use embassy_futures::select::{Either, Either4, select, select4};
+
+    // Fob-vs-NFC matching priority is a per-install build knob; see
+    // `access_controller::core::MatchOrder`.
+    let match_order = match option_env!("CONWAY_MATCH_ORDER") {
+        None => MatchOrder::default(),
+        Some(s) => MatchOrder::parse(s).unwrap_or_else(|| {
+            log::warn!("access: unknown CONWAY_MATCH_ORDER {:?}, using fob-first", s);
+            MatchOrder::default()
+        }),
+    };
+    log::info!("access: credential match order {:?}", match_order);
+    // Behavior while no Conway list has ever loaded; see
+    // `access_controller::core::FailPolicy`.
+    let fail_policy = match option_env!("CONWAY_FAIL_OPEN_SECS") {
+        None => FailPolicy::default(),
+        Some(s) => FailPolicy::parse(s).unwrap_or_else(|| {
+            log::warn!("access: invalid CONWAY_FAIL_OPEN_SECS {:?}, failing closed", s);
+            FailPolicy::default()
+        }),
+    };
+    if fail_policy != FailPolicy::Closed {
+        log::warn!("access: fail policy {:?}", fail_policy);
+    }
+    // How many denials the lockout keeps doubling for; see
+    // `access_controller::core::DenyBackoff`.
+    let deny_backoff = match option_env!("CONWAY_DENY_BACKOFF_STEPS") {
+        None => DenyBackoff::default(),
+        Some(s) => match DenyBackoff::parse_steps(s) {
+            Some(steps) => DenyBackoff { steps, ..DenyBackoff::DEFAULT },
+            None => {
+                log::warn!("access: invalid CONWAY_DENY_BACKOFF_STEPS {:?}, using default", s);
+                DenyBackoff::default()
+            }
+        },
+    };
+    // How the reader orders a UID's bytes in the frame.
+    let uid_order = match option_env!("CONWAY_UID_BYTE_ORDER") {
+        None => ByteOrder::default(),
+        Some(s) => ByteOrder::parse(s).unwrap_or_else(|| {
+            log::warn!("access: invalid CONWAY_UID_BYTE_ORDER {:?}, using lsb", s);
+            ByteOrder::default()
+        }),
+    };
+    // Unmatched reads from other facilities are denied without a sync.
+    let known_facilities = match option_env!("CONWAY_KNOWN_FACILITIES") {
+        None => KnownFacilities::NONE,
+        Some(s) => KnownFacilities::parse(s).unwrap_or_else(|| {
+            log::warn!("access: invalid CONWAY_KNOWN_FACILITIES {:?}, syncing on every denial", s);
+            KnownFacilities::NONE
+        }),
+    };
+    // Whether denials a sync resolves are reported at all.
+    let denial_report = match option_env!("CONWAY_REPORT_DENIALS") {
+        None => DenialReport::default(),
+        Some(s) => DenialReport::parse(s).unwrap_or_else(|| {
+            log::warn!("access: invalid CONWAY_REPORT_DENIALS {:?}, reporting all", s);
+            DenialReport::default()
+        }),
+    };
+    let mut core = AccessCore::with_match_order(match_order)
+        .with_fail_policy(fail_policy)
+        .with_deny_backoff(deny_backoff)
+        .with_known_facilities(known_facilities)
+        .with_denial_report(denial_report);
+    // Anti-passback: a card is ignored for a while after it grants.
+    let passback_secs = match option_env!("CONWAY_PASSBACK_SECS") {
+        None => 0,
+        Some(s) => passback::parse_window_secs(s).unwrap_or_else(|| {
+            log::warn!("access: invalid CONWAY_PASSBACK_SECS {:?}, passback off", s);
+            0
+        }),
+    };
+    if passback_secs > 0 {
+        log::info!("access: cards ignored for {} s after a grant", passback_secs);
+        core = core.with_passback_window(passback_secs * 1000);
+    }
+    if let Some(timeout_ms) = online_check_ms {
+        // How long an online refusal is remembered.
+        let ttl_secs = match option_env!("CONWAY_AUTHZ_DENY_TTL_SECS") {
+            None => authz::DEFAULT_DENY_TTL_SECS,
+            Some(s) => authz::parse_deny_ttl_secs(s).unwrap_or_else(|| {
+                log::warn!(
+                    "access: invalid CONWAY_AUTHZ_DENY_TTL_SECS {:?}, using {} s",
+                    s,
+                    authz::DEFAULT_DENY_TTL_SECS
+                );
+                authz::DEFAULT_DENY_TTL_SECS
+            }),
+        };
+        core = core
+            .with_online_check(timeout_ms)
+            .with_refusal_ttl(ttl_secs * 1000);
+    }
 
     loop {
         // Select across all firmware-level inputs: card reads, sync
-        // completion, watchdog feed ticks, and operator-initiated
-        // manual unlocks from the HTTP server.
-        let event = embassy_futures::select::select4(
+        // completion or an online check's answer, watchdog feed ticks,
+        // and operator-initiated manual unlocks from the HTTP server.
+        let event = select4(
             WIEGAND_CHANNEL.receive(),
-            SYNC_COMPLETE.wait(),
+            select(SYNC_COMPLETE.wait(), AUTHZ_ANSWER.receive()),
             WATCHDOG_FEED.wait(),
             MANUAL_UNLOCK.wait(),
         )
         .await;
 
-        let now = embassy_time::Instant::now().as_millis();
-
-        // Manual unlock is handled entirely in the firmware adapter -
-        // it doesn't run through AccessCore because there's no
-        // authorization decision to make.
-        if let embassy_futures::select::Either4::Fourth(()) = event {
-            log::warn!("access MANUAL UNLOCK via HTTP");
-            DOOR_SIGNAL.signal(());
-            READER_FEEDBACK.signal(AccessOutcome::Granted);
-            EVENT_BUFFER
-                .push(AccessEvent {
+        let now = BootClock.now_ms();
+        // Every wait on shared state for this input ends by here, so a
+        // feed tick behind it waits no longer than the watchdog allows.
+        let deadline = Instant::now() + Duration::from_millis(watchdog::MAX_LOCK_HOLD_MS);
+
+        let effects = match event {
+            Either4::First((reader, read)) => {
+                let snap = snapshot(now, deadline, fobs, local_fobs, rt).await;
+                // Without a snapshot the read is denied whatever it parses to.
+                let hint = snap.as_ref().and_then(|s| s.hint);
+                let input = CoreInput::Card(CardRead::from_read(&read, hint, uid_order).at(reader));
+                decide(&mut core, now, snap, input)
+            }
+            Either4::Second(Either::First(())) => {
+                let snap = snapshot(now, deadline, fobs, local_fobs, rt).await;
+                decide(&mut core, now, snap, CoreInput::SyncComplete)
+            }
+            Either4::Second(Either::Second((read, verdict))) => {
+                let snap = snapshot(now, deadline, fobs, local_fobs, rt).await;
+                decide(&mut core, now, snap, CoreInput::AuthzAnswer { read, verdict })
+            }
+            // Feed ticks take none of the decision locks: the decision
+            // state has nothing to do with them, and waiting on a list
+            // another task holds would only push the feed towards the
+            // timeout. Their effects still go through the dispatch below,
+            // since a step may record a held denial or an unanswered
+            // online check that has just run out; the feed leads them.
+            Either4::Third(()) => core.step(now, &[], &[], false, false, CoreInput::WatchdogFeed),
+            // Manual unlock is handled entirely in the firmware adapter -
+            // it doesn't run through AccessCore because there's no
+            // authorization decision to make.
+            Either4::Fourth(()) => {
+                log::warn!("access MANUAL UNLOCK via HTTP");
+                DOOR_SIGNAL.signal(holds.default_ms);
+                READER_FEEDBACK.signal(AccessOutcome::Granted);
+                let event = AccessEvent {
                     fob: MANUAL_UNLOCK_FOB,
                     allowed: true,
-                })
-                .await;
-            *last_swipe.lock().await = Some(LastSwipe {
-                fob: MANUAL_UNLOCK_FOB,
-                allowed: true,
-                at_uptime_ms: now,
-                manual: true,
-            });
-            // Standalone mode: persist to the offline flash log. Non-blocking
-            // try_send; if the queue is backed up we drop the entry rather
-            // than stall the door.
-            if log_to_flash
-                && SWIPE_LOG_CHANNEL
-                    .try_send(SwipeLogEntry {
+                };
+                if with_deadline(deadline, EVENT_BUFFER.push(event)).await.is_err() {
+                    log::warn!("events: buffer still locked, dropping manual-unlock event");
+                }
+                if let Ok(mut slot) = with_deadline(deadline, last_swipe.lock()).await {
+                    *slot = Some(LastSwipe {
                         fob: MANUAL_UNLOCK_FOB,
                         allowed: true,
-                        at_ms: now,
-                    })
-                    .is_err()
-            {
-                log::warn!("swipe_log: channel full, dropping manual-unlock entry");
-            }
-            continue;
-        }
-
-        let input = match event {
-            embassy_futures::select::Either4::First(read) => CoreInput::Card(CardRead {
-                fob: read.to_fob(),
-                nfc: read.to_nfc_uid(),
-            }),
-            embassy_futures::select::Either4::Second(()) => CoreInput::SyncComplete,
-            embassy_futures::select::Either4::Third(()) => CoreInput::WatchdogFeed,
-            embassy_futures::select::Either4::Fourth(()) => unreachable!(),
-        };
-
-        // Snapshot both caches once and pass them as slices. Local list is
-        // checked first by AccessCore; the conway_enabled flag controls
-        // whether denials trigger a RequestSync or apply backoff immediately.
-        let conway_enabled = rt.settings.lock().await.conway_enabled();
-        let effects = {
-            let fob_list = fobs.lock().await;
-            let local_list = local_fobs.lock().await;
-            // Project LocalFob -> u32 ids into a small stack buffer so
-            // AccessCore stays oblivious to label metadata.
-            let mut local_ids: heapless::Vec<u32, MAX_LOCAL_FOBS> = heapless::Vec::new();
-            for f in local_list.iter() {
-                let _ = local_ids.push(f.id);
+                        at_uptime_ms: now,
+                        manual: true,
+                    });
+                }
+                // Standalone mode: persist to the offline flash log. Non-blocking
+                // try_send; if the queue is backed up we drop the entry rather
+                // than stall the door.
+                if log_to_flash
+                    && SWIPE_LOG_CHANNEL
+                        .try_send(SwipeLogEntry {
+                            fob: MANUAL_UNLOCK_FOB,
+                            allowed: true,
+                            at_ms: now,
+                        })
+                        .is_err()
+                {
+                    log::warn!("swipe_log: channel full, dropping manual-unlock entry");
+                }
+                continue;
             }
-            core.step(
-                now,
-                local_ids.as_slice(),
-                fob_list.as_slice(),
-                conway_enabled,
-                input,
-            )
         };
 
+        // The credential that granted, from the Record ahead of OpenDoor;
+        // it picks the hold.
+        let mut granted = None;
         for effect in effects.iter() {
             match effect {
                 Effect::OpenDoor => {
-                    log::info!("access GRANTED");
-                    DOOR_SIGNAL.signal(());
+                    // Past the deadline, a tagged credential gets the
+                    // default hold.
+                    let hold_ms = match granted {
+                        Some(fob) => with_deadline(deadline, sync::EXTENDED_UNLOCK.lock())
+                            .await
+                            .map_or(holds.default_ms, |tagged| holds.for_grant(fob, &tagged)),
+                        None => holds.default_ms,
+                    };
+                    if hold_ms != holds.default_ms {
+                        log::info!("access GRANTED (extended unlock, {} ms)", hold_ms);
+                    } else {
+                        log::info!("access GRANTED");
+                    }
+                    DOOR_SIGNAL.signal(hold_ms);
                 }
                 Effect::Feedback(Outcome::Granted) => {
                     READER_FEEDBACK.signal(AccessOutcome::Granted);
@@ -708,19 +1670,25 @@ async fn access_task(
                     READER_FEEDBACK.signal(AccessOutcome::Denied);
                 }
                 Effect::Record(ev) => {
-                    EVENT_BUFFER
-                        .push(AccessEvent {
-                            fob: ev.fob,
-                            allowed: ev.allowed,
-                        })
-                        .await;
-                    // Mirror the record into the UI's last-swipe slot.
-                    *last_swipe.lock().await = Some(LastSwipe {
+                    if ev.allowed {
+                        granted = Some(ev.fob);
+                    }
+                    let event = AccessEvent {
                         fob: ev.fob,
                         allowed: ev.allowed,
-                        at_uptime_ms: now,
-                        manual: false,
-                    });
+                    };
+                    if with_deadline(deadline, EVENT_BUFFER.push(event)).await.is_err() {
+                        log::warn!("events: buffer still locked, dropping fob {} event", ev.fob);
+                    }
+                    // Mirror the record into the UI's last-swipe slot.
+                    if let Ok(mut slot) = with_deadline(deadline, last_swipe.lock()).await {
+                        *slot = Some(LastSwipe {
+                            fob: ev.fob,
+                            allowed: ev.allowed,
+                            at_uptime_ms: now,
+                            manual: false,
+                        });
+                    }
                     // Standalone mode: persist to the offline flash log.
                     // Non-blocking; the blocking flash write happens in
                     // swipe_log_task so the decision loop never stalls.
@@ -739,15 +1707,112 @@ async fn access_task(
                 Effect::RequestSync => {
                     SYNC_SIGNAL.signal(());
                 }
+                Effect::FailOpenGrant => {
+                    log::warn!("access FAIL-OPEN: no fob list loaded, granting unlisted credential");
+                }
                 Effect::FeedWatchdog => {
-                    wdt.lock().await.feed();
+                    feed_watchdog();
                     log::debug!("watchdog: fed");
                 }
+                Effect::QueryAuthz(read) => {
+                    log::info!(
+                        "access: fob {} not listed at {}, checking online",
+                        read.fob,
+                        read.reader.as_str()
+                    );
+                    // Never blocks: the core keeps one query per reader
+                    // out, and there is a slot for each.
+                    if AUTHZ_QUERY.try_send(*read).is_err() {
+                        log::warn!("authz: query queue full, fob {} left to time out", read.fob);
+                    }
+                }
             }
         }
     }
 }
 
+/// What a decision reads from shared state, taken by [`snapshot`]. The
+/// Conway list stays locked for as long as this is held.
+struct Snapshot<'a> {
+    hint: Option<CredentialFormat>,
+    conway_enabled: bool,
+    list_loaded: bool,
+    local_ids: heapless::Vec<u32, MAX_LOCAL_FOBS>,
+    fob_list: MutexGuard<'a, CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
+}
+
+/// Take what a decision needs, one lock at a time, by `deadline`. `None`
+/// if another task still holds one of them then: the caller fails closed
+/// rather than push the next watchdog feed towards the timeout.
+async fn snapshot<'a>(
+    now: u64,
+    deadline: Instant,
+    fobs: &'a Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
+    local_fobs: &Mutex<CriticalSectionRawMutex, heapless::Vec<LocalFob, MAX_LOCAL_FOBS>>,
+    rt: &RuntimeConfig,
+) -> Option<Snapshot<'a>> {
+    let taken = with_deadline(deadline, async {
+        let hint = *sync::CREDENTIAL_FORMAT.lock().await;
+        // The conway_enabled flag controls whether denials trigger a
+        // RequestSync or apply backoff immediately.
+        let conway_enabled = rt.settings.lock().await.conway_enabled();
+        let list_loaded = {
+            let st = sync::CACHE_STATE.lock().await;
+            st.live.is_some() || st.persisted.is_some()
+        };
+        // Project LocalFob -> u32 ids into a small stack buffer so
+        // AccessCore stays oblivious to label metadata. Done before taking
+        // the Conway list so that lock is never held across another wait.
+        let local_ids = local_fobs.lock().await.iter().map(|f| f.id).collect();
+        Snapshot {
+            hint,
+            conway_enabled,
+            list_loaded,
+            local_ids,
+            fob_list: fobs.lock().await,
+        }
+    })
+    .await;
+    match taken {
+        Ok(snapshot) => {
+            ACCESS_LOCK_HOLDS.record(clock::elapsed(now, BootClock.now_ms()));
+            Some(snapshot)
+        }
+        Err(_) => {
+            ACCESS_LOCK_HOLDS.gave_up();
+            log::warn!(
+                "access: shared state still locked after {} ms, failing closed",
+                watchdog::MAX_LOCK_HOLD_MS
+            );
+            None
+        }
+    }
+}
+
+/// Run `input` through `core` against `snapshot`. Without one, it runs
+/// as if both lists were empty and no Conway host were configured: a
+/// read is denied outright, with no sync, recheck, online check or
+/// fail-open grant.
+fn decide(
+    core: &mut AccessCore,
+    now: u64,
+    snapshot: Option<Snapshot<'_>>,
+    input: CoreInput,
+) -> heapless::Vec<Effect, MAX_EFFECTS_PER_STEP> {
+    match snapshot {
+        // Local list is checked first by AccessCore.
+        Some(s) => core.step(
+            now,
+            s.local_ids.as_slice(),
+            s.fob_list.as_slice(),
+            s.conway_enabled,
+            s.list_loaded,
+            input,
+        ),
+        None => core.step(now, &[], &[], false, false, input),
+    }
+}
+
 /// Offline swipe-logging task - persists fob swipes to flash.
 ///
 /// Spawned only in standalone mode (no Conway host). It is the single
@@ -767,16 +1832,54 @@ async fn swipe_log_task() {
     }
 }
 
-/// Door control task - pulses relay when signaled.
+/// Door contact sensor, when the board has one.
+#[cfg(feature = "door-contact")]
+type DoorContact = Input<'static>;
+#[cfg(not(feature = "door-contact"))]
+type DoorContact = ();
+
+/// Wait for the contact to change and settle; true if the door is open.
+#[cfg(feature = "door-contact")]
+async fn contact_change(contact: &mut DoorContact) -> bool {
+    const SETTLE_MS: u64 = 50;
+
+    contact.wait_for_any_edge().await;
+    Timer::after(Duration::from_millis(SETTLE_MS)).await;
+    contact.is_high()
+}
+
+#[cfg(not(feature = "door-contact"))]
+async fn contact_change(_: &mut DoorContact) -> bool {
+    core::future::pending().await
+}
+
+/// Door control task - holds the relay for the signaled hold, or until
+/// the door has opened and closed again under [`Relock::OnClose`].
 #[embassy_executor::task]
-async fn door_task(mut door: Output<'static>) {
-    const DOOR_PULSE_MS: u64 = 200;
+async fn door_task(mut door: Output<'static>, mut contact: DoorContact, relock: Relock) {
+    use access_controller::door::{Relocked, Unlock};
+    use embassy_futures::select::{Either, select};
 
     loop {
-        DOOR_SIGNAL.wait().await;
+        let hold_ms = DOOR_SIGNAL.wait().await;
         door.set_high();
-        Timer::after(Duration::from_millis(DOOR_PULSE_MS)).await;
+        let mut unlock = Unlock::start(BootClock.now_ms(), hold_ms, relock);
+        let why = loop {
+            let now = BootClock.now_ms();
+            if let Some(why) = unlock.tick(now) {
+                break why;
+            }
+            let wait = Timer::after(Duration::from_millis(unlock.remaining_ms(now)));
+            if let Either::Second(open) = select(wait, contact_change(&mut contact)).await {
+                if let Some(why) = unlock.contact(BootClock.now_ms(), open) {
+                    break why;
+                }
+            }
+        };
         door.set_low();
+        if why == Relocked::Closed {
+            log::info!("door: closed, relocked early");
+        }
     }
 }
 
@@ -785,9 +1888,11 @@ async fn door_task(mut door: Output<'static>) {
 async fn sync_task(
     stack: &'static Stack<'static>,
     fobs: &'static Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
-    etag: &'static Mutex<CriticalSectionRawMutex, HString<64>>,
+    etag: &'static Mutex<CriticalSectionRawMutex, HostEtag>,
     rt: &'static RuntimeConfig,
 ) {
+    const SYNC_INTERVAL_MS: u64 = 10_000;
+
     // Wait for network
     loop {
         if stack.is_link_up() && stack.config_v4().is_some() {
@@ -797,10 +1902,13 @@ async fn sync_task(
     }
     log::info!("sync: network ready");
 
+    let mut rng = entropy::Entropy::new();
     loop {
-        // Wait for periodic timer or on-demand signal
+        // Wait for periodic timer or on-demand signal. The jitter keeps a
+        // building's controllers, powered up together, from polling in
+        // lockstep.
         let _ = embassy_futures::select::select(
-            Timer::after(Duration::from_secs(10)),
+            Timer::after(Duration::from_millis(rng.jitter(SYNC_INTERVAL_MS, 10))),
             SYNC_SIGNAL.wait(),
         )
         .await;
@@ -811,9 +1919,84 @@ async fn sync_task(
         }
 
         crate::sync::sync_with_conway(stack, fobs, etag, rt).await;
+        crate::sync::persist_cache(fobs, etag).await;
     }
 }
 
+/// How often `reboot_task` asks the schedule.
+const REBOOT_CHECK_SECS: u64 = 60;
+
+/// Longest `reboot_task` waits for the final sync, a sync already
+/// running and the swipe log before rebooting regardless.
+const REBOOT_FLUSH_WAIT: Duration = Duration::from_secs(60);
+
+/// Scheduled reboot: once `reboot::Schedule` says so, flush what would
+/// otherwise be lost and reset.
+#[embassy_executor::task]
+async fn reboot_task(
+    schedule: reboot::Schedule,
+    last_swipe: &'static Mutex<CriticalSectionRawMutex, Option<LastSwipe>>,
+    syncs: bool,
+) {
+    loop {
+        Timer::after(Duration::from_secs(REBOOT_CHECK_SECS)).await;
+        let now = BootClock.now_ms();
+        let unix_ms = WALL_CLOCK.lock().await.now_unix_ms(now);
+        let last_activity = last_swipe.lock().await.map(|s| s.at_uptime_ms);
+        if schedule.decide(now, unix_ms, last_activity) == Decision::Reboot {
+            log::warn!("reboot: scheduled reboot after {} s uptime", now / 1000);
+            break;
+        }
+    }
+    reboot_cleanly(syncs).await
+}
+
+/// Report pending events, stop further flash writes and reset.
+///
+/// A sync reports the event buffer, which lives in RAM only. Holding
+/// `SYNC_RUNNING` afterwards keeps the next sync, and the sequence and
+/// cache saves it makes, from starting under the reset; a sync that is
+/// still running at [`REBOOT_FLUSH_WAIT`] is reset under. [`REBOOTING`]
+/// turns away the admin pages' fob list and settings saves. The swipe
+/// log's flash writes, like those saves, block the executor until done,
+/// so once its queue is empty none is in progress.
+async fn reboot_cleanly(syncs: bool) -> ! {
+    REBOOTING.store(true, Ordering::Relaxed);
+    let deadline = Instant::now() + REBOOT_FLUSH_WAIT;
+    if syncs {
+        let run = sync::SYNC_RUNS.lock().await.next();
+        SYNC_SIGNAL.signal(());
+        while sync::SYNC_RUNS.lock().await.outcome(run).is_none() && Instant::now() < deadline {
+            Timer::after(Duration::from_millis(100)).await;
+        }
+    }
+    let no_sync = with_deadline(deadline, async {
+        loop {
+            if let Some(running) = sync::SYNC_RUNNING.try_begin() {
+                break running;
+            }
+            Timer::after(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+    if no_sync.is_err() {
+        log::warn!("reboot: sync still running, resetting anyway");
+    }
+    let pending = EVENT_BUFFER.len().await;
+    if syncs && pending > 0 {
+        log::warn!("reboot: {} unreported events will be lost", pending);
+    }
+    while !SWIPE_LOG_CHANNEL.is_empty() && Instant::now() < deadline {
+        Timer::after(Duration::from_millis(100)).await;
+    }
+    log::warn!("reboot: rebooting");
+    Timer::after(Duration::from_millis(250)).await;
+    esp_hal::system::software_reset()
+}
+
+/// Watchdog ticks per heartbeat, which also samples the heap trend.
+const HEARTBEAT_TICKS: u32 = (heap::TREND_INTERVAL_MS / watchdog::FEED_PERIOD_MS) as u32;
+
 /// Watchdog feed task - periodically signals access_task to feed the watchdog.
 ///
 /// This task runs on a 10-second interval and sends a signal to access_task
@@ -822,51 +2005,144 @@ async fn sync_task(
 ///
 /// The 10-second interval with a 30-second watchdog timeout provides 3 feed
 /// opportunities before reset, allowing for some timing variance.
+///
+/// Each tick also samples the free heap and warns once if the stack's
+/// never-used headroom has run low. Every 10 minutes it logs both, and
+/// the supply state, as a heartbeat.
 #[embassy_executor::task]
 async fn watchdog_feed_task() {
+    let mut ticks: u32 = 0;
+    let mut stack_warned = false;
     loop {
-        Timer::after(Duration::from_secs(10)).await;
+        Timer::after(Duration::from_millis(watchdog::FEED_PERIOD_MS)).await;
         WATCHDOG_FEED.signal(());
+        let free = sample_heap();
+        let stack = stack_unused();
+        if stack_watermark::low(stack) && !stack_warned {
+            log::warn!(
+                "stack: only {} of {} bytes never used; a deeper call may overflow",
+                stack,
+                stack_size()
+            );
+            stack_warned = true;
+        }
+        ticks = ticks.wrapping_add(1);
+        if ticks % HEARTBEAT_TICKS == 0 {
+            log::info!(
+                "heartbeat: heap {} bytes free, lowest {}; stack {} bytes never used; power {}",
+                free,
+                HEAP_LOW_WATER.get().unwrap_or(free),
+                stack,
+                power_label()
+            );
+            let mut trend = HEAP_TREND.lock().await;
+            let was_leaking = trend.leaking();
+            trend.push(free);
+            if trend.leaking() && !was_leaking {
+                log::warn!("heap: trend {}", *trend);
+            }
+        }
+    }
+}
+
+/// A feedback pattern build knob, `name` with value `value`, or
+/// `default`.
+fn feedback_cues(name: &str, value: Option<&str>, default: &[feedback::Cue]) -> Cues {
+    match value {
+        None => feedback::cues(default),
+        Some(s) => feedback::parse(s).unwrap_or_else(|| {
+            log::warn!("feedback: invalid {} {:?}, using the default", name, s);
+            feedback::cues(default)
+        }),
     }
 }
 
 /// Reader-side feedback task - drives the reader LED and beeper after
-/// each access decision.
+/// each access decision, playing `grant` or `deny`.
 ///
+/// By default (see [`access_controller::feedback`]):
 /// - Granted: LED on for 200ms with a 100ms beep at the start.
 /// - Denied:  three 100ms beeps (100ms gap), LED stays off.
+///
+/// Until the controller is first ready, the reader LED also shows the
+/// boot status pattern from [`BOOT_STATUS`] and plays its chimes; see
+/// [`access_controller::status_led`].
 #[embassy_executor::task]
-async fn reader_feedback_task(mut led: Output<'static>, mut beep: Output<'static>) {
+async fn reader_feedback_task(
+    mut led: Output<'static>,
+    mut beep: Output<'static>,
+    grant: Cues,
+    deny: Cues,
+) {
+    use embassy_futures::select::{Either3, select3};
+
+    let mut boot: Option<&'static [status_led::Step]> = None;
+    let mut step = 0;
     loop {
-        match READER_FEEDBACK.wait().await {
-            AccessOutcome::Granted => {
-                led.set_high();
-                beep.set_high();
-                Timer::after(Duration::from_millis(100)).await;
-                beep.set_low();
-                Timer::after(Duration::from_millis(100)).await;
+        let hold = match boot {
+            Some(steps) => {
+                let (on, ms) = steps[step % steps.len()];
+                led.set_level(if on { Level::High } else { Level::Low });
+                ms
+            }
+            // Nothing to blink; just wait for the next event.
+            None => 60_000,
+        };
+        match select3(
+            READER_FEEDBACK.wait(),
+            BOOT_STATUS.wait(),
+            Timer::after(Duration::from_millis(hold)),
+        )
+        .await
+        {
+            Either3::First(outcome) => {
+                let cues = match outcome {
+                    AccessOutcome::Granted => &grant,
+                    AccessOutcome::Denied => &deny,
+                };
+                for cue in cues {
+                    led.set_level(if cue.led { Level::High } else { Level::Low });
+                    beep.set_level(if cue.beep { Level::High } else { Level::Low });
+                    Timer::after(Duration::from_millis(cue.ms)).await;
+                }
                 led.set_low();
+                beep.set_low();
             }
-            AccessOutcome::Denied => {
-                for _ in 0..3 {
-                    beep.set_high();
-                    Timer::after(Duration::from_millis(100)).await;
-                    beep.set_low();
-                    Timer::after(Duration::from_millis(100)).await;
+            Either3::Second(change) => {
+                led.set_low();
+                for &(on, ms) in change.chime {
+                    beep.set_level(if on { Level::High } else { Level::Low });
+                    Timer::after(Duration::from_millis(ms)).await;
                 }
+                beep.set_low();
+                boot = change.on_reader.then(|| change.pattern.steps());
+                step = 0;
             }
+            Either3::Third(()) => step += 1,
         }
     }
 }
 
-/// Combined status-LED heartbeat + CONFIG button handler.
+/// Combined status-LED pattern + CONFIG button handler.
 ///
 /// One task owns the status LED so the factory-reset acknowledgement can
 /// flash it without any cross-task synchronization.
 ///
-/// Heartbeat:
-///   - 1Hz toggle (500ms period) when the network stack has an IPv4 lease.
-///   - 5Hz toggle (100ms period) otherwise, indicating "no IP / not ready".
+/// Status pattern (see [`access_controller::status_led`]), re-evaluated
+/// every step:
+///   - Solid while WiFi or the IPv4 address is not up.
+///   - 5Hz blink while waiting for the first fob list.
+///   - 1Hz heartbeat once a fob list is loaded.
+///   - Double flash after repeated sync failures.
+///   - A short blink every 2s once no sync has succeeded for
+///     `CONWAY_OFFLINE_SECS`.
+///   - Triple flash, ahead of all the above, while overall health is
+///     critical.
+///
+/// Pattern changes are also published on [`BOOT_STATUS`] for the reader.
+/// `syncs` is false when nothing will ever sync (onboarding AP or
+/// standalone), in which case the controller is ready as soon as it is
+/// networked.
 ///
 /// CONFIG button (active-low, external pull-up):
 ///   - Short press (>=50ms, <5s): signal SYNC_SIGNAL for an on-demand sync.
@@ -877,29 +2153,56 @@ async fn status_and_config_task(
     mut led: Output<'static>,
     mut btn: Input<'static>,
     stack: &'static Stack<'static>,
+    syncs: bool,
 ) {
     use embassy_futures::select::{Either, select};
 
     const DEBOUNCE_MS: u64 = 50;
     const LONG_HOLD_MS: u64 = 5_000;
 
+    let mut indicator = BootIndicator::new();
+    let mut steps = status_led::Pattern::Connecting.steps();
+    let mut step = 0;
+    let mut last_health = Health::Ok;
     loop {
-        // Choose heartbeat cadence based on network readiness each tick.
-        let period_ms = if stack.config_v4().is_some() {
-            500
-        } else {
-            100
-        };
+        {
+            let mut age = sync::SYNC_AGE.lock().await;
+            let now = BootClock.now_ms();
+            if age.update(now) == Some(true) {
+                log::warn!("status: offline, no sync for {} s", age.since_ms(now) / 1000);
+            }
+        }
+        let inputs = sample_health(stack, syncs).await;
+        let overall = health::health(&inputs);
+        if overall != last_health {
+            let mut line: HString<64> = HString::new();
+            let _ = health::render(&inputs, &mut line);
+            if overall > last_health {
+                log::warn!("health: {}", line);
+            } else {
+                log::info!("health: {}", line);
+            }
+            last_health = overall;
+        }
+        let status = inputs.net_status();
+        if let Some(change) = indicator.update(status) {
+            log::info!("status: {:?}", change.pattern);
+            steps = change.pattern.steps();
+            step = 0;
+            BOOT_STATUS.signal(change);
+        }
+        let (on, hold_ms) = steps[step % steps.len()];
+        led.set_level(if on { Level::High } else { Level::Low });
 
         match select(
             btn.wait_for_falling_edge(),
-            Timer::after(Duration::from_millis(period_ms)),
+            Timer::after(Duration::from_millis(hold_ms)),
         )
         .await
         {
-            // Heartbeat tick.
+            // Pattern step done.
             Either::Second(()) => {
-                led.toggle();
+                step += 1;
             }
 
             // Button pressed (active-low edge).
@@ -945,8 +2248,9 @@ async fn status_and_config_task(
                             log::warn!(
                                 "config: confirming staged config via CONFIG button - saving and rebooting"
                             );
-                            WATCHDOG_FEED.signal(());
-                            if let Err(e) = settings::save(&new_settings) {
+                            if let Err(e) =
+                                with_watchdog("settings save", |_| settings::save(&new_settings))
+                            {
                                 log::error!("config: staged save failed: {}", e);
                                 // Acknowledge failure with a fast triple-flash and bail.
                                 for _ in 0..3 {
@@ -975,15 +2279,23 @@ async fn status_and_config_task(
                     // so the next boot comes up in AP onboarding mode.
                     Either::Second(()) => {
                         log::warn!("config: factory reset - wiping NVS and rebooting");
-                        if let Err(e) = settings::erase() {
-                            log::error!("config: settings::erase failed: {}", e);
-                        }
-                        if let Err(e) = fob_store::erase() {
-                            log::error!("config: fob_store::erase failed: {}", e);
-                        }
-                        if let Err(e) = swipe_log::erase() {
-                            log::error!("config: swipe_log::erase failed: {}", e);
-                        }
+                        with_watchdog("factory reset", |feeder| {
+                            if let Err(e) = settings::erase() {
+                                log::error!("config: settings::erase failed: {}", e);
+                            }
+                            feeder.tick();
+                            if let Err(e) = fob_store::erase() {
+                                log::error!("config: fob_store::erase failed: {}", e);
+                            }
+                            feeder.tick();
+                            if let Err(e) = swipe_log::erase() {
+                                log::error!("config: swipe_log::erase failed: {}", e);
+                            }
+                            feeder.tick();
+                            if let Err(e) = cache_store::erase() {
+                                log::error!("config: cache_store::erase failed: {}", e);
+                            }
+                        });
                         for _ in 0..5 {
                             led.set_high();
                             Timer::after(Duration::from_millis(100)).await;
@@ -998,8 +2310,25 @@ async fn status_and_config_task(
     }
 }
 
+/// Logs the panic and resets. A failed allocation panics too (there is
+/// no stable allocation-error hook), so its message is checked first and
+/// the heap state logged with it to tell fragmentation from exhaustion.
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
+    use core::fmt::Write as _;
+
+    let mut message: HString<64> = HString::new();
+    if write!(message, "{}", info.message()).is_ok() {
+        if let Some(requested) = heap::alloc_failure_size(&message) {
+            let failure = heap::AllocFailure {
+                requested,
+                free: esp_alloc::HEAP.free(),
+                lowest: HEAP_LOW_WATER.get(),
+            };
+            log::error!("OOM: {}", failure);
+            log::error!("{}", esp_alloc::HEAP.stats());
+        }
+    }
     log::error!("PANIC: {}", info);
     esp_hal::system::software_reset()
 }
@@ -0,0 +1,123 @@
+//! Online authorization check; see [`access_controller::authz`].
+//!
+//! `access_task` must never block on networking, so a read neither list
+//! grants is handed here through [`AUTHZ_QUERY`] and the verdict goes
+//! back through [`AUTHZ_ANSWER`]. Every query is answered within the
+//! timeout, [`Verdict::Unavailable`] if nothing better, so the door is
+//! never left waiting on the network. Queries go to whichever Conway
+//! host answered the last sync, like the push channel. One task runs per
+//! reader, so each reader's query is answered on its own clock.
+
+use embassy_net::tcp::TcpSocket;
+use embassy_net::Stack;
+use embassy_time::{with_timeout, Duration};
+use embedded_io_async::Write;
+use heapless::String as HString;
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+
+use access_controller::authz::{self, Verdict};
+use access_controller::core::{CardRead, MAX_READERS};
+
+use crate::sync::FAILOVER;
+use crate::{RuntimeConfig, AUTHZ_ANSWER, AUTHZ_QUERY};
+
+#[embassy_executor::task(pool_size = MAX_READERS)]
+pub async fn online_check_task(
+    stack: &'static Stack<'static>,
+    rt: &'static RuntimeConfig,
+    path: &'static str,
+    timeout_ms: u64,
+) {
+    log::info!(
+        "authz: checking unlisted credentials at {} ({} ms)",
+        path,
+        timeout_ms
+    );
+    loop {
+        let read = AUTHZ_QUERY.receive().await;
+        let timeout = Duration::from_millis(timeout_ms);
+        let verdict = match with_timeout(timeout, query(stack, rt, path, read)).await {
+            Ok(Ok(verdict)) => verdict,
+            Ok(Err(e)) => {
+                log::warn!("authz: {}, denying", e);
+                Verdict::Unavailable
+            }
+            Err(_) => {
+                log::warn!("authz: no answer within {} ms, denying", timeout_ms);
+                Verdict::Unavailable
+            }
+        };
+        log::info!(
+            "authz: fob {} at {} {}",
+            read.fob,
+            read.reader.as_str(),
+            verdict.as_str()
+        );
+        AUTHZ_ANSWER.send((read, verdict)).await;
+    }
+}
+
+/// One round-trip. Cut short by the caller's timeout, which drops the
+/// socket.
+async fn query(
+    stack: &'static Stack<'static>,
+    rt: &'static RuntimeConfig,
+    path: &str,
+    read: CardRead,
+) -> Result<Verdict, &'static str> {
+    if stack.config_v4().is_none() {
+        return Err("no IP");
+    }
+    let (hosts, port) = {
+        let s = rt.settings.lock().await;
+        (s.conway_hosts(), s.conway_port)
+    };
+    let idx = FAILOVER.lock().await.last_good();
+    let Some(&host) = hosts.get(idx).or(hosts.first()) else {
+        return Err("no Conway host");
+    };
+
+    let mut rx_buf = alloc::vec![0u8; authz::SOCKET_RX_BYTES];
+    let mut tx_buf = alloc::vec![0u8; authz::SOCKET_TX_BYTES];
+    let mut socket = TcpSocket::new(*stack, rx_buf.as_mut_slice(), tx_buf.as_mut_slice());
+    let addr = IpAddress::Ipv4(Ipv4Address::new(host[0], host[1], host[2], host[3]));
+    if socket.connect(IpEndpoint::new(addr, port)).await.is_err() {
+        socket.abort();
+        return Err("connect failed");
+    }
+
+    let mut host_str: HString<24> = HString::new();
+    let _ = core::fmt::write(
+        &mut host_str,
+        format_args!("{}.{}.{}.{}", host[0], host[1], host[2], host[3]),
+    );
+    let mut request: HString<256> = HString::new();
+    if authz::write_request(&mut request, path, &host_str, read).is_err() {
+        socket.abort();
+        return Err("request too large");
+    }
+    if socket.write_all(request.as_bytes()).await.is_err() {
+        socket.abort();
+        return Err("write failed");
+    }
+
+    // HTTP/1.0: the answer ends when the server closes.
+    let mut buf = alloc::vec![0u8; authz::MAX_RESPONSE_BYTES];
+    let mut filled = 0;
+    loop {
+        if filled == buf.len() {
+            socket.abort();
+            return Err("response too large");
+        }
+        match socket.read(&mut buf[filled..]).await {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(_) => {
+                socket.abort();
+                return Err("read failed");
+            }
+        }
+    }
+    socket.abort();
+    Ok(authz::parse_response(&mut buf[..filled]))
+}
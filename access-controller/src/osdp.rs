@@ -0,0 +1,230 @@
+//! OSDP framing for polling a reader over RS-485.
+//!
+//! With feature `osdp` the controller is the OSDP control panel (CP): it
+//! polls one peripheral device (PD, the reader) and turns its card-read
+//! replies into the same [`WiegandRead`] the Wiegand inputs produce. This
+//! module is the pure part: frame layout, CRC, and reply parsing. The UART
+//! side lives in `src/osdp_reader.rs`.
+//!
+//! Only plain (unsecured) packets are spoken. A reply carrying a security
+//! block is refused rather than half-understood.
+
+use crate::decode::{decode_frame, WiegandRead};
+
+/// Start of message.
+pub const SOM: u8 = 0x53;
+/// Set on the address byte of every PD → CP reply.
+pub const REPLY_BIT: u8 = 0x80;
+/// Broadcast address; a lone PD answers on it whatever its own address.
+pub const BROADCAST: u8 = 0x7F;
+
+/// Parse the `CONWAY_OSDP_ADDRESS` build knob: a PD address, 0-126.
+pub fn parse_address(s: &str) -> Option<u8> {
+    s.parse::<u8>().ok().filter(|&a| a < BROADCAST)
+}
+
+/// CP → PD: poll for events.
+pub const CMD_POLL: u8 = 0x60;
+/// PD → CP: nothing to report.
+pub const REPLY_ACK: u8 = 0x40;
+/// PD → CP: command refused.
+pub const REPLY_NAK: u8 = 0x41;
+/// PD → CP: raw card data.
+pub const REPLY_RAW: u8 = 0x50;
+
+/// CTRL bits.
+const CTRL_SQN: u8 = 0x03;
+const CTRL_CRC: u8 = 0x04;
+const CTRL_SCB: u8 = 0x08;
+
+/// SOM, ADDR, LEN (2), CTRL, code.
+const HEADER_LEN: usize = 6;
+/// Shortest legal packet: header plus one checksum byte.
+pub const MIN_FRAME: usize = HEADER_LEN + 1;
+/// Longest packet we buffer; card reads are a small fraction of this.
+pub const MAX_FRAME: usize = 128;
+
+/// `osdp_RAW` format codes.
+const FORMAT_RAW: u8 = 0x00;
+const FORMAT_WIEGAND: u8 = 0x01;
+
+/// CRC-16 as OSDP specifies it: polynomial 0x1021, initial value 0x1D0F,
+/// no reflection (CRC-16/AUG-CCITT). Sent LSB first.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0x1D0F;
+    for &byte in data {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// 8-bit checksum: the two's complement of the byte sum.
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)).wrapping_neg()
+}
+
+/// The sequence number after `sqn`. 0 only opens a session; after that
+/// the CP cycles 1, 2, 3.
+pub fn next_sqn(sqn: u8) -> u8 {
+    if sqn >= 3 {
+        1
+    } else {
+        sqn + 1
+    }
+}
+
+/// Write an `osdp_POLL` for `addr` with sequence number `sqn` into `out`,
+/// CRC-protected. Returns the packet length.
+pub fn encode_poll(addr: u8, sqn: u8, out: &mut [u8; 8]) -> usize {
+    let len = out.len();
+    out[..HEADER_LEN].copy_from_slice(&[
+        SOM,
+        addr & 0x7F,
+        len as u8,
+        0,
+        CTRL_CRC | (sqn & CTRL_SQN),
+        CMD_POLL,
+    ]);
+    let crc = crc16(&out[..HEADER_LEN]);
+    out[HEADER_LEN..].copy_from_slice(&crc.to_le_bytes());
+    len
+}
+
+/// How far a receive buffer is into a packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scan {
+    /// Not enough bytes to tell; keep reading.
+    NeedMore,
+    /// The first `n` bytes are line noise or a runt; drop them.
+    Skip(usize),
+    /// A whole packet of this length sits at the start of the buffer.
+    Frame(usize),
+}
+
+/// Find the packet at the start of `buf`, as read off the bus.
+pub fn scan(buf: &[u8]) -> Scan {
+    if buf.is_empty() {
+        return Scan::NeedMore;
+    }
+    let Some(start) = buf.iter().position(|&b| b == SOM) else {
+        return Scan::Skip(buf.len());
+    };
+    if start > 0 {
+        return Scan::Skip(start);
+    }
+    if buf.len() < 4 {
+        return Scan::NeedMore;
+    }
+    let len = usize::from(u16::from_le_bytes([buf[2], buf[3]]));
+    if !(MIN_FRAME..=MAX_FRAME).contains(&len) {
+        // Not a real header; resync on the next SOM.
+        return Scan::Skip(1);
+    }
+    if buf.len() < len {
+        Scan::NeedMore
+    } else {
+        Scan::Frame(len)
+    }
+}
+
+/// Why a packet was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Bad SOM, or a length field that disagrees with the packet.
+    Framing,
+    /// CRC or checksum mismatch.
+    Integrity,
+    /// Not a PD reply (reply bit clear).
+    NotReply,
+    /// Secure-channel packet; not supported.
+    Secure,
+}
+
+/// A checked PD → CP packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reply<'a> {
+    /// PD address, reply bit stripped.
+    pub addr: u8,
+    pub sqn: u8,
+    pub code: u8,
+    pub data: &'a [u8],
+}
+
+/// Check and split one whole packet, as [`scan`] delimited it.
+pub fn parse_reply(frame: &[u8]) -> Result<Reply<'_>, Error> {
+    if frame.len() < MIN_FRAME || frame[0] != SOM {
+        return Err(Error::Framing);
+    }
+    if usize::from(u16::from_le_bytes([frame[2], frame[3]])) != frame.len() {
+        return Err(Error::Framing);
+    }
+    let ctrl = frame[4];
+    let body_end = if ctrl & CTRL_CRC != 0 {
+        if frame.len() < HEADER_LEN + 2 {
+            return Err(Error::Framing);
+        }
+        let end = frame.len() - 2;
+        let sent = u16::from_le_bytes([frame[end], frame[end + 1]]);
+        if crc16(&frame[..end]) != sent {
+            return Err(Error::Integrity);
+        }
+        end
+    } else {
+        let end = frame.len() - 1;
+        if checksum(&frame[..end]) != frame[end] {
+            return Err(Error::Integrity);
+        }
+        end
+    };
+    if ctrl & CTRL_SCB != 0 {
+        return Err(Error::Secure);
+    }
+    if frame[1] & REPLY_BIT == 0 {
+        return Err(Error::NotReply);
+    }
+    Ok(Reply {
+        addr: frame[1] & !REPLY_BIT,
+        sqn: ctrl & CTRL_SQN,
+        code: frame[5],
+        data: &frame[HEADER_LEN..body_end],
+    })
+}
+
+/// The credential in an `osdp_RAW` reply, if it is one we can match.
+///
+/// 26- and 34-bit reads go through the Wiegand decoders, parity and all,
+/// whichever format code the reader used. A bare 32-bit read (a 4-byte
+/// UID, no parity) becomes the same `raw_data` a 34-bit frame would carry.
+/// Other lengths, and other replies, are `None`.
+pub fn card_read(reply: &Reply<'_>) -> Option<WiegandRead> {
+    if reply.code != REPLY_RAW || reply.data.len() < 4 {
+        return None;
+    }
+    let format = reply.data[1];
+    if format != FORMAT_RAW && format != FORMAT_WIEGAND {
+        return None;
+    }
+    let count = u32::from(u16::from_le_bytes([reply.data[2], reply.data[3]]));
+    let bytes = &reply.data[4..];
+    if count == 0 || count > 64 || bytes.len() != count.div_ceil(8) as usize {
+        return None;
+    }
+    // Left-justified: the first bit is the MSB of the first byte.
+    let padded = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+    let bits = padded >> (bytes.len() as u32 * 8 - count);
+    match count {
+        26 | 34 => decode_frame(bits, count),
+        32 => {
+            let data = bits as u32;
+            Some(WiegandRead {
+                facility: (data >> 16) & 0xFF,
+                card: data & 0xFFFF,
+                raw_data: data,
+            })
+        }
+        _ => None,
+    }
+}
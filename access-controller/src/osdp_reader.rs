@@ -0,0 +1,104 @@
+//! OSDP reader on RS-485 (feature `osdp`).
+//!
+//! Polls one reader as the OSDP control panel and hands its card reads to
+//! the access task through the same channel as the Wiegand inputs, which
+//! stay live. Framing and parsing are in `access_controller::osdp`.
+
+use embassy_time::{Duration, Timer, with_timeout};
+use esp_hal::Async;
+use esp_hal::gpio::Output;
+use esp_hal::uart::Uart;
+
+use access_controller::osdp::{self, MAX_FRAME, REPLY_ACK, REPLY_NAK, REPLY_RAW, Scan};
+pub use access_controller::decode::WiegandRead;
+
+/// Time between polls; OSDP expects 200 ms or better.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a PD has to start and finish its reply.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(200);
+
+pub struct OsdpReader<'a> {
+    uart: Uart<'a, Async>,
+    /// RS-485 driver enable: HIGH while we transmit.
+    de: Output<'a>,
+    addr: u8,
+    sqn: u8,
+    online: bool,
+}
+
+impl<'a> OsdpReader<'a> {
+    /// Poll the PD at `addr` (`osdp::BROADCAST` for a lone reader whose
+    /// address is unknown).
+    pub fn new(uart: Uart<'a, Async>, de: Output<'a>, addr: u8) -> Self {
+        Self { uart, de, addr, sqn: 0, online: false }
+    }
+
+    /// Poll until the reader reports a card.
+    pub async fn read(&mut self) -> Option<WiegandRead> {
+        loop {
+            Timer::after(POLL_INTERVAL).await;
+            let mut buf = [0u8; MAX_FRAME];
+            let Some(len) = self.poll(&mut buf).await else {
+                if self.online {
+                    log::warn!("osdp: reader stopped answering");
+                    self.online = false;
+                }
+                // Re-open the session from sequence 0 once it answers.
+                self.sqn = 0;
+                continue;
+            };
+            let reply = match osdp::parse_reply(&buf[..len]) {
+                Ok(reply) => reply,
+                Err(e) => {
+                    log::warn!("osdp: bad reply ({:?})", e);
+                    continue;
+                }
+            };
+            if !self.online {
+                log::info!("osdp: reader online at address {}", reply.addr);
+                self.online = true;
+            }
+            self.sqn = osdp::next_sqn(self.sqn);
+            match reply.code {
+                REPLY_ACK => {}
+                REPLY_RAW => match osdp::card_read(&reply) {
+                    Some(read) => return Some(read),
+                    None => log::warn!("osdp: card data in an unknown format"),
+                },
+                REPLY_NAK => log::warn!("osdp: poll refused ({:02X?})", reply.data),
+                code => log::debug!("osdp: ignoring reply {:02X}", code),
+            }
+        }
+    }
+
+    /// Send one poll and collect the reply packet into `buf`.
+    async fn poll(&mut self, buf: &mut [u8; MAX_FRAME]) -> Option<usize> {
+        let mut cmd = [0u8; 8];
+        let n = osdp::encode_poll(self.addr, self.sqn, &mut cmd);
+        self.de.set_high();
+        let sent = self.uart.write_async(&cmd[..n]).await.is_ok() && self.uart.flush_async().await.is_ok();
+        self.de.set_low();
+        if !sent {
+            return None;
+        }
+        with_timeout(REPLY_TIMEOUT, self.receive(buf)).await.ok().flatten()
+    }
+
+    async fn receive(&mut self, buf: &mut [u8; MAX_FRAME]) -> Option<usize> {
+        let mut have = 0;
+        loop {
+            match osdp::scan(&buf[..have]) {
+                Scan::Frame(len) => return Some(len),
+                Scan::Skip(n) => {
+                    buf.copy_within(n..have, 0);
+                    have -= n;
+                }
+                Scan::NeedMore => {}
+            }
+            if have == buf.len() {
+                return None;
+            }
+            have += self.uart.read_async(&mut buf[have..]).await.ok()?;
+        }
+    }
+}
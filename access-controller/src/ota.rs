@@ -259,6 +259,12 @@ impl OtaWriter {
         self.expected
     }
 
+    /// Whether writing a chunk of `len` bytes fills the sector buffer
+    /// and so writes to flash.
+    pub fn flushes(&self, len: usize) -> bool {
+        self.buf_len + len >= SECTOR
+    }
+
     /// Feed the next chunk of the image. Chunks may be of any size;
     /// internal buffering aligns writes to 4 KiB sector boundaries.
     pub fn write(&mut self, mut chunk: &[u8]) -> Result<(), OtaError> {
@@ -0,0 +1,56 @@
+//! Anti-passback: a credential that has just opened the door is ignored
+//! for a short while.
+//!
+//! With `CONWAY_PASSBACK_SECS` set, [`AccessCore`] remembers each
+//! credential it grants in [`RecentGrants`] and drops further reads of
+//! it, at either reader, until the window has passed. A card handed back
+//! out to a second person doesn't let them straight in behind the first.
+//! The read is dropped without effects, as during a denial backoff: it
+//! is neither a grant nor a denial, so it is not reported and costs the
+//! card no backoff. Every other credential is unaffected.
+//!
+//! [`AccessCore`]: crate::core::AccessCore
+
+use crate::core::CardRead;
+use crate::credential_window::CredentialWindow;
+
+/// Longest window [`parse_window_secs`] accepts. Past this, a member who
+/// walks back out and in again is left waiting at their own door.
+pub const MAX_WINDOW_SECS: u64 = 300;
+
+/// Credentials remembered at once. The one closest to the end of its
+/// window is forgotten first.
+pub const RECENT_GRANT_SLOTS: usize = 8;
+
+/// Parse a `CONWAY_PASSBACK_SECS` value, up to [`MAX_WINDOW_SECS`]. `0`
+/// turns the window off.
+pub fn parse_window_secs(s: &str) -> Option<u64> {
+    s.parse().ok().filter(|&secs| secs <= MAX_WINDOW_SECS)
+}
+
+/// Credentials granted within the last `window_ms`.
+#[derive(Clone, Copy, Debug)]
+pub struct RecentGrants(CredentialWindow<RECENT_GRANT_SLOTS>);
+
+impl RecentGrants {
+    /// A `window_ms` of 0 remembers nothing.
+    pub const fn new(window_ms: u64) -> Self {
+        Self(CredentialWindow::new(window_ms))
+    }
+
+    pub fn window_ms(&self) -> u64 {
+        self.0.span_ms()
+    }
+
+    /// `read` was granted at `now_ms`. A credential already remembered
+    /// starts its window again.
+    pub fn insert(&mut self, read: CardRead, now_ms: u64) {
+        self.0.insert(read, now_ms);
+    }
+
+    /// Whether `read`'s credential was granted, at any reader, within the
+    /// window before `now_ms`.
+    pub fn contains(&self, read: CardRead, now_ms: u64) -> bool {
+        self.0.contains(read, now_ms)
+    }
+}
@@ -0,0 +1,115 @@
+//! Battery-backed supply monitoring.
+//!
+//! A UPS or battery-backed PSU with status outputs gives two signals:
+//! "on battery" (mains lost) and "battery low". Relay contacts bounce and
+//! a brownout can blip the mains signal, so a level only counts once it
+//! has held for the debounce time.
+//!
+//! Pure state machine; the firmware samples both inputs periodically and
+//! acts on the returned transitions.
+
+use heapless::Vec as HVec;
+
+use crate::clock;
+
+/// How long a level must hold before it counts.
+pub const DEBOUNCE_MS: u64 = 2_000;
+
+/// A change in supply state reported by [`PowerWatch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerEvent {
+    /// Mains lost; running on battery.
+    OnBattery,
+    /// Mains back.
+    MainsRestored,
+    /// The supply flagged its battery low.
+    BatteryLow,
+    /// The low-battery flag cleared.
+    BatteryOk,
+}
+
+/// Debounced supply state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PowerState {
+    pub on_battery: bool,
+    pub battery_low: bool,
+}
+
+/// One input: its accepted level, and since when it has read otherwise.
+#[derive(Clone, Copy, Debug, Default)]
+struct Debounced {
+    level: bool,
+    changed_since: Option<u64>,
+}
+
+impl Debounced {
+    /// Sample the input; true if the accepted level flipped.
+    fn sample(&mut self, level: bool, now_ms: u64, debounce_ms: u64) -> bool {
+        if level == self.level {
+            self.changed_since = None;
+            return false;
+        }
+        let since = *self.changed_since.get_or_insert(now_ms);
+        if clock::elapsed(since, now_ms) < debounce_ms {
+            return false;
+        }
+        self.level = level;
+        self.changed_since = None;
+        true
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PowerWatch {
+    debounce_ms: u64,
+    on_battery: Debounced,
+    battery_low: Debounced,
+}
+
+impl PowerWatch {
+    /// Start on mains with the battery fine. A supply already on battery
+    /// at boot is reported once the level has held for `debounce_ms`.
+    pub const fn new(debounce_ms: u64) -> Self {
+        Self {
+            debounce_ms,
+            on_battery: Debounced {
+                level: false,
+                changed_since: None,
+            },
+            battery_low: Debounced {
+                level: false,
+                changed_since: None,
+            },
+        }
+    }
+
+    pub fn state(&self) -> PowerState {
+        PowerState {
+            on_battery: self.on_battery.level,
+            battery_low: self.battery_low.level,
+        }
+    }
+
+    /// Sample both inputs at `now_ms`. Each transition is reported once,
+    /// mains before battery level when both settle together.
+    pub fn sample(&mut self, now_ms: u64, on_battery: bool, battery_low: bool) -> HVec<PowerEvent, 2> {
+        let mut events = HVec::new();
+        if self.on_battery.sample(on_battery, now_ms, self.debounce_ms) {
+            let event = if on_battery {
+                PowerEvent::OnBattery
+            } else {
+                PowerEvent::MainsRestored
+            };
+            let _ = events.push(event);
+        }
+        if self.battery_low.sample(battery_low, now_ms, self.debounce_ms) {
+            let event = if battery_low {
+                PowerEvent::BatteryLow
+            } else {
+                PowerEvent::BatteryOk
+            };
+            let _ = events.push(event);
+        }
+        events
+    }
+}
@@ -0,0 +1,276 @@
+//! Conway push channel.
+//!
+//! Polling every 10 s means a revoked fob keeps working for up to 10 s.
+//! When `CONWAY_PUSH_PATH` is set, this task additionally holds a
+//! connection open to Conway; any event the server sends means "the fob
+//! list changed" and triggers an immediate sync via `SYNC_SIGNAL`. The
+//! push carries no fob data itself, so the regular sync path (ETag,
+//! signature, cache persistence) stays the only way the list changes.
+//!
+//! Two transports, picked with `CONWAY_PUSH_TRANSPORT`:
+//! - `websocket` (default): RFC 6455, `GET /api/fobs/ws`; see
+//!   [`access_controller::websocket`].
+//! - `sse`: server-sent events over a plain HTTP/1.0 `GET`, e.g.
+//!   `/api/events/stream`; simpler on the wire, with no masking or
+//!   control replies. See [`access_controller::sse`].
+//!
+//! Polling keeps running regardless, so a dropped connection only costs
+//! latency. Reconnects back off exponentially from 2 s to 60 s
+//! ([`access_controller::backoff`]), with ±20 % jitter so controllers
+//! that lost Conway together don't all reconnect in the same instant;
+//! every successful (re)connect also
+//! triggers a sync to pick up anything that changed while the channel
+//! was down.
+
+use embassy_net::tcp::TcpSocket;
+use embassy_net::Stack;
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_io_async::Write;
+use heapless::String as HString;
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+
+use access_controller::backoff::Backoff;
+use access_controller::clock::Clock;
+use access_controller::http_client;
+use access_controller::rng::RandomSource;
+use access_controller::signing;
+use access_controller::sse::{self, SseParser};
+use access_controller::websocket::{self, Opcode, MAX_CONTROL_PAYLOAD};
+
+use crate::entropy::Entropy;
+use crate::sync::FAILOVER;
+use crate::{BootClock, RuntimeConfig, SYNC_SIGNAL};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Conway pings (or sends an SSE keepalive comment) every 30 s; silence
+/// for three intervals means the connection is gone even if TCP hasn't
+/// noticed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const BACKOFF_MIN_MS: u64 = 2_000;
+const BACKOFF_MAX_MS: u64 = 60_000;
+const BACKOFF_JITTER_PERCENT: u8 = 20;
+/// Largest server frame accepted. Pushes are a few bytes; anything
+/// bigger is a protocol error.
+const MAX_PAYLOAD: usize = 256;
+const RX_CAP: usize = 1024;
+
+/// How the push channel is carried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    WebSocket,
+    Sse,
+}
+
+impl Transport {
+    /// Parse a `CONWAY_PUSH_TRANSPORT` value: `"websocket"` or `"sse"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "websocket" => Some(Self::WebSocket),
+            "sse" => Some(Self::Sse),
+            _ => None,
+        }
+    }
+}
+
+#[embassy_executor::task]
+pub async fn push_task(
+    stack: &'static Stack<'static>,
+    rt: &'static RuntimeConfig,
+    path: &'static str,
+    transport: Transport,
+) {
+    while !(stack.is_link_up() && stack.config_v4().is_some()) {
+        Timer::after(Duration::from_millis(100)).await;
+    }
+
+    let mut rng = Entropy::new();
+    let mut backoff = Backoff::new(BACKOFF_MIN_MS, BACKOFF_MAX_MS);
+    loop {
+        // Follow the sync failover: push from whichever host answered
+        // the last sync.
+        let (hosts, port) = {
+            let s = rt.settings.lock().await;
+            (s.conway_hosts(), s.conway_port)
+        };
+        let idx = FAILOVER.lock().await.last_good();
+        let Some(&host) = hosts.get(idx).or(hosts.first()) else {
+            Timer::after(Duration::from_millis(BACKOFF_MAX_MS)).await;
+            continue;
+        };
+
+        match session(stack, host, port, path, transport, &mut rng).await {
+            Ok(reason) => {
+                log::warn!("push: disconnected ({}), polling continues", reason);
+                backoff.reset();
+            }
+            Err(e) => log::warn!("push: {}.{}.{}.{}: {}", host[0], host[1], host[2], host[3], e),
+        }
+        let now = BootClock.now_ms();
+        backoff.schedule(now);
+        let delay = backoff.remaining_ms(now);
+        Timer::after(Duration::from_millis(rng.jitter(delay, BACKOFF_JITTER_PERCENT))).await;
+    }
+}
+
+/// One connection. `Err` if the channel never opened; `Ok(reason)` once
+/// an established channel ends.
+async fn session(
+    stack: &'static Stack<'static>,
+    host: [u8; 4],
+    port: u16,
+    path: &str,
+    transport: Transport,
+    rng: &mut Entropy,
+) -> Result<&'static str, &'static str> {
+    let mut rx_buf = alloc::vec![0u8; RX_CAP];
+    let mut tx_buf = alloc::vec![0u8; 512];
+    let mut socket = TcpSocket::new(*stack, rx_buf.as_mut_slice(), tx_buf.as_mut_slice());
+    socket.set_timeout(Some(IO_TIMEOUT));
+
+    let addr = IpAddress::Ipv4(Ipv4Address::new(host[0], host[1], host[2], host[3]));
+    if socket.connect(IpEndpoint::new(addr, port)).await.is_err() {
+        socket.abort();
+        return Err("connect failed");
+    }
+
+    let mut host_str: HString<24> = HString::new();
+    let _ = core::fmt::write(
+        &mut host_str,
+        format_args!("{}.{}.{}.{}", host[0], host[1], host[2], host[3]),
+    );
+    let mut request: HString<256> = HString::new();
+    match transport {
+        Transport::WebSocket => {
+            let mut nonce = [0u8; 16];
+            rng.fill(&mut nonce);
+            let key = signing::b64_encode(&nonce);
+            let _ = websocket::write_handshake(&mut request, path, &host_str, &key);
+        }
+        Transport::Sse => {
+            let _ = sse::write_request(&mut request, path, &host_str);
+        }
+    }
+    if socket.write_all(request.as_bytes()).await.is_err() {
+        socket.abort();
+        return Err("write failed");
+    }
+
+    // Read the response head. Frames or events may follow it in the
+    // same read.
+    let mut buf = alloc::vec![0u8; RX_CAP];
+    let mut filled = 0;
+    let body_start = loop {
+        if let Some((head, body)) = http_client::split_head(&mut buf[..filled]) {
+            let accepted = match transport {
+                Transport::WebSocket => websocket::handshake_accepted(head),
+                Transport::Sse => sse::stream_accepted(head),
+            };
+            if !accepted {
+                socket.abort();
+                return Err("server refused the push channel");
+            }
+            break filled - body.len();
+        }
+        if filled == buf.len() {
+            socket.abort();
+            return Err("response head too large");
+        }
+        match socket.read(&mut buf[filled..]).await {
+            Ok(0) | Err(_) => {
+                socket.abort();
+                return Err("no response");
+            }
+            Ok(n) => filled += n,
+        }
+    };
+    buf.copy_within(body_start..filled, 0);
+    filled -= body_start;
+
+    log::info!("push: connected to {}{} ({:?})", host_str, path, transport);
+    SYNC_SIGNAL.signal(());
+
+    let reason = match transport {
+        Transport::WebSocket => ws_loop(&mut socket, &mut buf, filled, rng).await,
+        Transport::Sse => sse_loop(&mut socket, &mut buf, filled).await,
+    };
+    socket.abort();
+    Ok(reason)
+}
+
+/// Read events until the stream ends; returns why it ended.
+async fn sse_loop(socket: &mut TcpSocket<'_>, buf: &mut [u8], filled: usize) -> &'static str {
+    let mut parser = SseParser::new();
+    let mut changed = false;
+    let mut n = filled;
+    loop {
+        parser.feed(&buf[..n], |_| changed = true);
+        if core::mem::take(&mut changed) {
+            log::info!("push: fob list changed, syncing");
+            SYNC_SIGNAL.signal(());
+        }
+        n = match with_timeout(IDLE_TIMEOUT, socket.read(buf)).await {
+            Err(_) => return "idle timeout",
+            Ok(Ok(0)) => return "closed by peer",
+            Ok(Err(_)) => return "read failed",
+            Ok(Ok(n)) => n,
+        };
+    }
+}
+
+/// Handle frames until the socket closes; returns why it ended.
+async fn ws_loop(socket: &mut TcpSocket<'_>, buf: &mut [u8], mut filled: usize, rng: &mut Entropy) -> &'static str {
+    loop {
+        // Handle every complete frame in the buffer.
+        let mut reply: Option<(Opcode, heapless::Vec<u8, MAX_CONTROL_PAYLOAD>)> = None;
+        let mut closing = false;
+        match websocket::parse_frame(&buf[..filled], MAX_PAYLOAD) {
+            Err(e) => return e,
+            Ok(Some((frame, used))) => {
+                match frame.opcode {
+                    Opcode::Text | Opcode::Binary | Opcode::Continuation => {
+                        if frame.fin {
+                            log::info!("push: fob list changed, syncing");
+                            SYNC_SIGNAL.signal(());
+                        }
+                    }
+                    Opcode::Ping => {
+                        // Length already capped by parse_frame.
+                        let payload = heapless::Vec::from_slice(frame.payload).unwrap_or_default();
+                        reply = Some((Opcode::Pong, payload));
+                    }
+                    Opcode::Pong => {}
+                    Opcode::Close => {
+                        reply = Some((Opcode::Close, heapless::Vec::new()));
+                        closing = true;
+                    }
+                }
+                buf.copy_within(used..filled, 0);
+                filled -= used;
+            }
+            Ok(None) => {
+                match with_timeout(IDLE_TIMEOUT, socket.read(&mut buf[filled..])).await {
+                    Err(_) => return "idle timeout",
+                    Ok(Ok(0)) => return "closed by peer",
+                    Ok(Err(_)) => return "read failed",
+                    Ok(Ok(n)) => filled += n,
+                }
+                continue;
+            }
+        }
+
+        if let Some((op, payload)) = reply {
+            let mut out = [0u8; MAX_CONTROL_PAYLOAD + 6];
+            let mask = rng.next_u32().to_le_bytes();
+            let n = match websocket::build_frame(op, &payload, mask, &mut out) {
+                Ok(n) => n,
+                Err(e) => return e,
+            };
+            if socket.write_all(&out[..n]).await.is_err() {
+                return "write failed";
+            }
+        }
+        if closing {
+            return "closed by server";
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! The queue of card reads between the reader tasks and `access_task`,
+//! and the count of reads lost at it.
+//!
+//! `wiegand_task` and `osdp_task` hand every read to `access_task` over a
+//! bounded channel with `try_send`, so a reader task never stalls on it
+//! (stalling would cost the edges of a back-to-back swipe). When a burst
+//! of swipes fills the channel the read is dropped; [`DroppedReads`]
+//! counts those so `/status` and `/metrics` can show how often it
+//! happens.
+
+use core::fmt::{self, Write};
+
+use crate::core::Reader;
+use crate::counters::Counter;
+use crate::decode::WiegandRead;
+
+/// A read on its way to `access_task`, tagged with the reader it came
+/// from.
+pub type QueuedRead = (Reader, WiegandRead);
+
+/// Reads the channel to `access_task` holds.
+///
+/// Any admin handler that holds the fob lists, settings or last swipe
+/// across socket I/O (the OTA upload above all) holds up `access_task`,
+/// and swipes queue meanwhile. At 4, the fifth swipe of such a stall was
+/// dropped; 16 still left a slow upload at a busy door short. Each slot
+/// is one [`QueuedRead`] of static RAM, so 32 costs
+/// [`READ_QUEUE_BYTES`] (512 bytes), next to nothing beside the heap.
+pub const READ_QUEUE_DEPTH: usize = 32;
+
+/// Static RAM the queue's slots take.
+pub const READ_QUEUE_BYTES: usize = READ_QUEUE_DEPTH * core::mem::size_of::<QueuedRead>();
+
+/// Reads dropped because the channel to `access_task` was full.
+pub struct DroppedReads {
+    count: Counter,
+}
+
+impl DroppedReads {
+    pub const fn new() -> Self {
+        Self {
+            count: Counter::new(),
+        }
+    }
+
+    /// Pass through the result of a `try_send`, counting a failure as a
+    /// dropped read.
+    pub fn note<E>(&self, sent: Result<(), E>) -> Result<(), E> {
+        if sent.is_err() {
+            self.count.incr();
+        }
+        sent
+    }
+
+    /// Reads dropped since boot.
+    pub fn get(&self) -> u32 {
+        self.count.get()
+    }
+
+    /// The underlying count, for `/counters`.
+    pub fn counter(&self) -> &Counter {
+        &self.count
+    }
+
+    /// Render the count as a Prometheus counter.
+    pub fn write_metrics<W: Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_str("# TYPE conway_reads_dropped_total counter\n")?;
+        writeln!(out, "conway_reads_dropped_total {}", self.get())
+    }
+}
+
+impl Default for DroppedReads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
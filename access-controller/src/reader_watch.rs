@@ -0,0 +1,72 @@
+//! Reader keep-alive tracking.
+//!
+//! D0/D1 idle HIGH through pull-ups whether the reader is alive or has
+//! lost power, so a quiet line says nothing on its own. Readers that emit
+//! periodic keep-alive / "hold" pulses give us something to watch: any
+//! edge on D0/D1 (a full frame or not) counts as activity, and no
+//! activity for longer than the configured interval flags the reader as
+//! offline.
+//!
+//! Pure state machine; the firmware feeds it edge activity and a
+//! periodic poll, and acts on the returned transitions.
+
+use crate::clock;
+
+/// A change in reader presence reported by [`ReaderWatch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReaderEvent {
+    /// No activity for longer than the keep-alive interval.
+    Offline,
+    /// Activity seen again after an `Offline`.
+    Online,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReaderWatch {
+    keepalive_ms: u64,
+    last_seen_ms: u64,
+    online: bool,
+}
+
+impl ReaderWatch {
+    /// Start watching at `now_ms`. The reader is presumed online until
+    /// a full `keepalive_ms` passes without activity, so boot does not
+    /// raise a spurious alarm.
+    pub const fn new(keepalive_ms: u64, now_ms: u64) -> Self {
+        Self {
+            keepalive_ms,
+            last_seen_ms: now_ms,
+            online: true,
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    /// Timestamp of the last observed activity.
+    pub fn last_seen_ms(&self) -> u64 {
+        self.last_seen_ms
+    }
+
+    /// Record an edge on D0/D1.
+    pub fn activity(&mut self, now_ms: u64) -> Option<ReaderEvent> {
+        self.last_seen_ms = now_ms;
+        if self.online {
+            None
+        } else {
+            self.online = true;
+            Some(ReaderEvent::Online)
+        }
+    }
+
+    /// Check for a keep-alive timeout. Reports `Offline` once per outage.
+    pub fn poll(&mut self, now_ms: u64) -> Option<ReaderEvent> {
+        if self.online && clock::elapsed(self.last_seen_ms, now_ms) > self.keepalive_ms {
+            self.online = false;
+            Some(ReaderEvent::Offline)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Scheduled reboot after a set uptime.
+//!
+//! Some operators reboot controllers nightly as a failsafe against slow
+//! leaks: heap fragmentation, a socket that never comes back, a stuck
+//! peripheral. `CONWAY_REBOOT_AFTER_HOURS` makes the reboot due once the
+//! board has been up that long, and `CONWAY_REBOOT_WINDOW` (optional)
+//! holds it until a quiet time of day.
+//!
+//! [`Schedule::decide`] is the pure "reboot now?" check the firmware
+//! polls. A due reboot also waits for [`IDLE_MS`] without door activity,
+//! so nobody is mid-swipe when the reader goes dark. The window is UTC
+//! and needs calendar time; as with schedule checks elsewhere, it fails
+//! open while the wall clock is unknown. Neither wait lasts more than
+//! [`GRACE_MS`] past due, so a window that never comes (a busy door, a
+//! window that is too short) only delays the reboot by a day.
+
+use crate::clock;
+
+/// Shortest uptime [`parse_after_hours`] accepts.
+pub const MIN_REBOOT_HOURS: u64 = 1;
+
+/// Longest uptime [`parse_after_hours`] accepts: thirty days.
+pub const MAX_REBOOT_HOURS: u64 = 720;
+
+/// Door activity this recent holds off a due reboot.
+pub const IDLE_MS: u64 = 2 * 60 * 1000;
+
+/// How long past due a reboot waits for the window and for the door to
+/// go idle before going ahead anyway.
+pub const GRACE_MS: u64 = 24 * 60 * 60 * 1000;
+
+const HOUR_MS: u64 = 60 * 60 * 1000;
+const DAY_MS: u64 = 24 * HOUR_MS;
+
+/// Parse a `CONWAY_REBOOT_AFTER_HOURS` value: hours of uptime before a
+/// reboot is due, from [`MIN_REBOOT_HOURS`] to [`MAX_REBOOT_HOURS`]. `0`
+/// never reboots.
+pub fn parse_after_hours(s: &str) -> Option<u64> {
+    s.parse()
+        .ok()
+        .filter(|&h| h == 0 || (MIN_REBOOT_HOURS..=MAX_REBOOT_HOURS).contains(&h))
+}
+
+/// A daily UTC time window, `start` inclusive to `end` exclusive, in
+/// minutes past midnight. May wrap past midnight (`23:00-01:00`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Window {
+    start_min: u16,
+    end_min: u16,
+}
+
+impl Window {
+    /// Parse a `CONWAY_REBOOT_WINDOW` value, `HH:MM-HH:MM`. An empty
+    /// window (start equal to end) is rejected.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (start, end) = s.split_once('-')?;
+        let start_min = parse_hhmm(start.trim())?;
+        let end_min = parse_hhmm(end.trim())?;
+        (start_min != end_min).then_some(Self { start_min, end_min })
+    }
+
+    /// Whether `minute` past midnight falls inside the window.
+    pub fn contains_minute(&self, minute: u16) -> bool {
+        if self.start_min < self.end_min {
+            (self.start_min..self.end_min).contains(&minute)
+        } else {
+            minute >= self.start_min || minute < self.end_min
+        }
+    }
+
+    /// Whether Unix time `unix_ms` falls inside the window.
+    pub fn contains_unix(&self, unix_ms: u64) -> bool {
+        self.contains_minute(((unix_ms % DAY_MS) / 60_000) as u16)
+    }
+}
+
+fn parse_hhmm(s: &str) -> Option<u16> {
+    let (h, m) = s.split_once(':')?;
+    if h.is_empty() || h.len() > 2 || m.len() != 2 {
+        return None;
+    }
+    let h: u16 = h.parse().ok()?;
+    let m: u16 = m.parse().ok()?;
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+/// What [`Schedule::decide`] found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Disabled, or not up long enough yet.
+    NotDue,
+    /// Due, but outside the window or the door was just used.
+    Wait,
+    /// Reboot now.
+    Reboot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    after_ms: u64,
+    window: Option<Window>,
+}
+
+impl Schedule {
+    /// Reboot after `after_hours` of uptime (0 never), preferably inside
+    /// `window`.
+    pub const fn new(after_hours: u64, window: Option<Window>) -> Self {
+        Self {
+            after_ms: after_hours.saturating_mul(HOUR_MS),
+            window,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.after_ms > 0
+    }
+
+    /// Whether to reboot at boot-clock `uptime_ms`, given the Unix time
+    /// if known and when the door was last used, if since boot.
+    pub fn decide(
+        &self,
+        uptime_ms: u64,
+        unix_ms: Option<u64>,
+        last_activity_ms: Option<u64>,
+    ) -> Decision {
+        if !self.enabled() || uptime_ms < self.after_ms {
+            return Decision::NotDue;
+        }
+        if uptime_ms - self.after_ms >= GRACE_MS {
+            return Decision::Reboot;
+        }
+        let busy = last_activity_ms.is_some_and(|at| clock::elapsed(at, uptime_ms) < IDLE_MS);
+        let outside = match (self.window, unix_ms) {
+            (Some(w), Some(unix_ms)) => !w.contains_unix(unix_ms),
+            _ => false,
+        };
+        if busy || outside {
+            Decision::Wait
+        } else {
+            Decision::Reboot
+        }
+    }
+}
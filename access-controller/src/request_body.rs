@@ -0,0 +1,106 @@
+//! Request body limits for the admin HTTP server.
+//!
+//! Form posts (`/config`, `/fobs`, `/fobs/delete`) are read fully into
+//! memory before parsing. The declared `Content-Length` is checked
+//! against a limit up front, so an oversized body is refused with 413
+//! instead of being read, and [`BodyAssembler`] stitches the bytes that
+//! arrived with the headers and any number of later partial reads into
+//! exactly `Content-Length` bytes.
+//!
+//! The limit defaults to [`DEFAULT_BODY_MAX`] and can be changed at build
+//! time with `CONWAY_HTTP_BODY_MAX`.
+
+use alloc::vec::Vec;
+
+/// Body limit when none is configured. The `/config` form is well under
+/// 512 bytes even with every field at its maximum length.
+pub const DEFAULT_BODY_MAX: usize = 1024;
+
+/// Largest configurable limit. Bodies are buffered on the heap.
+pub const BODY_MAX_CEILING: usize = 8192;
+
+/// Why a request body cannot be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyError {
+    /// No (parseable) `Content-Length` header.
+    LengthRequired,
+    /// `Content-Length` exceeds the limit.
+    TooLarge,
+    /// The connection ended before `Content-Length` bytes arrived.
+    Truncated,
+}
+
+impl BodyError {
+    /// HTTP status line for the error response.
+    pub fn status(self) -> &'static str {
+        match self {
+            Self::LengthRequired => "411 Length Required",
+            Self::TooLarge => "413 Payload Too Large",
+            Self::Truncated => "400 Bad Request",
+        }
+    }
+}
+
+/// Parse a `CONWAY_HTTP_BODY_MAX` value: a byte count in
+/// `1..=BODY_MAX_CEILING`.
+pub fn parse_body_max(s: &str) -> Option<usize> {
+    s.parse()
+        .ok()
+        .filter(|n| (1..=BODY_MAX_CEILING).contains(n))
+}
+
+/// Check a request's declared `Content-Length` against `max`, returning
+/// the body length to read.
+pub fn check_length(content_length: Option<u32>, max: usize) -> Result<usize, BodyError> {
+    let len = content_length.ok_or(BodyError::LengthRequired)? as usize;
+    if len > max {
+        return Err(BodyError::TooLarge);
+    }
+    Ok(len)
+}
+
+/// Accumulates a body of known length from successive reads.
+#[derive(Debug)]
+pub struct BodyAssembler {
+    body: Vec<u8>,
+    len: usize,
+}
+
+impl BodyAssembler {
+    /// Start a body of `len` bytes with whatever arrived after the
+    /// headers. Bytes past `len` are not part of the body and are
+    /// dropped.
+    pub fn new(len: usize, leftover: &[u8]) -> Self {
+        let mut a = Self {
+            body: Vec::with_capacity(len),
+            len,
+        };
+        a.push(leftover);
+        a
+    }
+
+    /// Bytes still missing.
+    pub fn remaining(&self) -> usize {
+        self.len - self.body.len()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Append the next read; anything beyond the remaining length is
+    /// dropped.
+    pub fn push(&mut self, chunk: &[u8]) {
+        let n = chunk.len().min(self.remaining());
+        self.body.extend_from_slice(&chunk[..n]);
+    }
+
+    /// The assembled body, or [`BodyError::Truncated`] if bytes are
+    /// still missing.
+    pub fn finish(self) -> Result<Vec<u8>, BodyError> {
+        if !self.is_complete() {
+            return Err(BodyError::Truncated);
+        }
+        Ok(self.body)
+    }
+}
@@ -0,0 +1,94 @@
+//! Wiegand frames from RMT captures.
+//!
+//! The RMT backend in `src/wiegand.rs` (feature `wiegand-rmt`) records each
+//! data line on its own RMT receive channel, so bit timing comes from the
+//! peripheral's clock instead of per-edge interrupt latency. An RMT channel
+//! only measures how long each level lasted from its own first edge, so the
+//! two lines carry no common time base; the firmware timestamps each line's
+//! first falling edge once per frame and this module places every later
+//! pulse relative to it, then merges D0 and D1 into the bit sequence
+//! [`decode_frame`](crate::decode::decode_frame) expects.
+
+/// Longest frame we assemble; the same as the GPIO reader's.
+pub const MAX_BITS: u32 = crate::decode::MAX_FRAME_BITS;
+
+/// One RMT sample: the line held `low` (or high) for `ticks` RMT clock
+/// ticks. A zero-length span is the RMT end marker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub low: bool,
+    pub ticks: u16,
+}
+
+/// One data line's share of a frame.
+#[derive(Clone, Copy, Debug)]
+pub struct LineCapture<'a> {
+    /// Boot-clock time of the line's first falling edge, in µs.
+    pub first_edge_us: u64,
+    /// RMT samples starting at that edge.
+    pub spans: &'a [Span],
+}
+
+impl LineCapture<'_> {
+    /// Start times (µs) of this line's pulses, in order.
+    ///
+    /// Leading high spans are the idle line before the first edge and do
+    /// not advance the clock; back-to-back low spans are one pulse the RMT
+    /// split because it outlasted a single entry.
+    pub fn pulse_starts(&self, tick_us: u32) -> impl Iterator<Item = u64> + '_ {
+        let mut at = self.first_edge_us;
+        let mut started = false;
+        let mut was_low = false;
+        self.spans
+            .iter()
+            .take_while(|span| span.ticks != 0)
+            .filter_map(move |span| {
+                let start = at;
+                let new_pulse = span.low && !was_low;
+                started |= span.low;
+                was_low = span.low;
+                if started {
+                    at += u64::from(span.ticks) * u64::from(tick_us);
+                }
+                new_pulse.then_some(start)
+            })
+    }
+}
+
+/// Merge the D0 and D1 captures of one frame into `(bits, count)`, MSB
+/// first, for [`decode_frame`](crate::decode::decode_frame). A line that
+/// never pulsed is `None`.
+///
+/// Returns `None` when nothing was captured, when the frame is longer than
+/// [`MAX_BITS`], or when both lines pulse at the same instant, which a
+/// Wiegand reader never does.
+pub fn frame_bits(
+    d0: Option<&LineCapture<'_>>,
+    d1: Option<&LineCapture<'_>>,
+    tick_us: u32,
+) -> Option<(u64, u32)> {
+    let mut zeros = d0.into_iter().flat_map(|c| c.pulse_starts(tick_us)).peekable();
+    let mut ones = d1.into_iter().flat_map(|c| c.pulse_starts(tick_us)).peekable();
+    let mut bits: u64 = 0;
+    let mut count: u32 = 0;
+    loop {
+        let bit = match (zeros.peek(), ones.peek()) {
+            (None, None) => break,
+            (Some(a), Some(b)) if a == b => return None,
+            (Some(a), Some(b)) if a < b => 0,
+            (Some(_), None) => 0,
+            _ => 1,
+        };
+        if bit == 0 {
+            zeros.next();
+        } else {
+            ones.next();
+        }
+        if count >= MAX_BITS {
+            return None;
+        }
+        bits = (bits << 1) | bit;
+        count += 1;
+    }
+    (count > 0).then_some((bits, count))
+}
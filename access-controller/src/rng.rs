@@ -0,0 +1,103 @@
+//! Random numbers.
+//!
+//! Everything that needs randomness (the network stack seed, the
+//! per-boot idempotency nonce, WebSocket masks and handshake keys, retry
+//! jitter) draws from a [`RandomSource`]. The firmware's is the ESP32
+//! hardware RNG, falling back to [`SplitMix64`] seeded from the MAC and
+//! boot time if the hardware looks stuck (`entropy.rs`); host tests seed
+//! a [`SplitMix64`] directly.
+
+/// A stream of uniformly distributed random words.
+pub trait RandomSource {
+    fn next_u32(&mut self) -> u32;
+
+    fn next_u64(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+    }
+
+    /// Fill `buf` with random bytes.
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(4) {
+            let word = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+
+    /// Uniform in `0..bound`; 0 if `bound` is 0. Words from the short
+    /// last stretch of the range are redrawn, so there is no modulo bias.
+    fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            return 0;
+        }
+        // Largest multiple of `bound` that fits, minus one.
+        let zone = u64::MAX - (u64::MAX - bound + 1) % bound;
+        loop {
+            let x = self.next_u64();
+            if x <= zone {
+                return x % bound;
+            }
+        }
+    }
+
+    /// Uniform in `lo..hi`; `lo` if the range is empty.
+    fn range(&mut self, lo: u64, hi: u64) -> u64 {
+        lo + self.below(hi.saturating_sub(lo))
+    }
+
+    /// `ms` moved by up to `percent` per cent either way, so devices
+    /// that failed together don't retry together.
+    fn jitter(&mut self, ms: u64, percent: u8) -> u64 {
+        let spread = (u128::from(ms) * u128::from(percent.min(100)) / 100) as u64;
+        self.range(ms - spread, ms.saturating_add(spread).saturating_add(1))
+    }
+}
+
+impl<R: RandomSource + ?Sized> RandomSource for &mut R {
+    fn next_u32(&mut self) -> u32 {
+        (**self).next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (**self).next_u64()
+    }
+}
+
+/// SplitMix64: small, fast and seedable. Not cryptographically secure;
+/// the firmware only uses it when the hardware RNG is unusable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Fallback seed from the station MAC: unique per device, but fixed, so
+/// mix in something that varies per boot.
+pub fn mac_seed(mac: [u8; 6]) -> u64 {
+    u64::from_le_bytes([mac[0], mac[1], mac[2], mac[3], mac[4], mac[5], 0, 0])
+}
+
+/// Whether `words`, drawn back to back, look like a stuck generator:
+/// all the same value. A working 32-bit source repeats a word four times
+/// in a row with probability 2^-96.
+pub fn looks_stuck(words: &[u32]) -> bool {
+    words.windows(2).all(|w| w[0] == w[1])
+}
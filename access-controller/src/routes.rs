@@ -0,0 +1,267 @@
+//! Request routing for the admin HTTP server.
+//!
+//! `http.rs` reads the request line and dispatches on the [`Route`]
+//! chosen here, so which paths answer in which device mode can be checked
+//! on the host. Handlers, auth and bodies stay in the firmware.
+//!
+//! Each request is counted per route in [`RouteCounts`], served as
+//! `GET /metrics`, and logged with the client address via [`RequestLog`].
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Body of the `GET /ping` response.
+pub const PING_BODY: &[u8] = b"pong\n";
+
+/// Status line and body answering `POST /sync`, given how the sync it
+/// asked for ended, or `None` if it had not by the deadline.
+pub fn sync_reply(outcome: Option<bool>) -> (&'static str, &'static [u8]) {
+    match outcome {
+        Some(true) => ("200 OK", b"ok: synced\n"),
+        Some(false) => ("502 Bad Gateway", b"sync failed\n"),
+        None => ("504 Gateway Timeout", b"sync still running\n"),
+    }
+}
+
+/// Where a request goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Route {
+    /// `GET /ping`: a fixed `200 pong` for liveness checks. Touches no
+    /// shared state, so it answers even while `/status` would block.
+    Ping,
+    /// `GET /` or `GET /status`.
+    Status,
+    /// `GET /metrics`: request counts per route, Prometheus text format.
+    Metrics,
+    ConfigPage,
+    ConfigPost,
+    FobsPage,
+    FobAdd,
+    FobDelete,
+    Swipes,
+    LastSync,
+    /// `GET /diag/events`: the recent-decisions ring as JSON.
+    DiagEvents,
+    OtaUpload,
+    OtaRollback,
+    Unlock,
+    /// `POST /sync`: sync with Conway now and answer with how it went.
+    Sync,
+    /// `GET /check?fob=N`: whether the lists would let a fob in.
+    Check,
+    /// `GET /counters`: the resettable event counters as JSON.
+    Counters,
+    /// `POST /counters/reset`: read the counters and start them over.
+    CountersReset,
+    /// Send the client to `/config`: `/` and captive-portal probes, and
+    /// any unknown `GET` while onboarding.
+    RedirectToConfig,
+    NotFound,
+    /// The path exists but not for this method. Carries the value of
+    /// the `Allow` header the 405 must send.
+    MethodNotAllowed(&'static str),
+}
+
+/// Captive-portal probe paths of the common OSes.
+const CAPTIVE_PROBES: [&str; 8] = [
+    "/generate_204",
+    "/gen_204",
+    "/hotspot-detect.html",
+    "/library/test/success.html",
+    "/connecttest.txt",
+    "/ncsi.txt",
+    "/redirect",
+    "/success.txt",
+];
+
+/// Methods a path answers to, as an `Allow` header value, or `None` for
+/// a path that does not exist. Must agree with [`route`].
+pub fn allowed(path: &str) -> Option<&'static str> {
+    match path {
+        "/config" | "/fobs" => Some("GET, HEAD, POST"),
+        "/fobs/delete" | "/ota" | "/ota/rollback" | "/unlock" | "/sync" | "/counters/reset" => {
+            Some("POST")
+        }
+        "/" | "/status" | "/ping" | "/metrics" | "/swipes" | "/diag/lastsync"
+        | "/diag/events" | "/check" | "/counters" => Some("GET, HEAD"),
+        p if CAPTIVE_PROBES.contains(&p) => Some("GET, HEAD"),
+        _ => None,
+    }
+}
+
+/// Whether a response to `method` carries its body. `HEAD` gets the
+/// same status and headers as `GET`, `Content-Length` included.
+pub fn has_body(method: &str) -> bool {
+    method != "HEAD"
+}
+
+/// Route `method` and `path` (the target without its query string).
+/// `HEAD` routes like `GET`. An unknown path is 404 whatever the method;
+/// a known one asked with the wrong method is 405.
+pub fn route(method: &str, path: &str, onboarding: bool) -> Route {
+    let method = if method == "HEAD" { "GET" } else { method };
+    match (method, path) {
+        ("GET", "/ping") => Route::Ping,
+        // Captive-portal browsers land right on the form.
+        ("GET", "/") if onboarding => Route::RedirectToConfig,
+        ("GET", "/") | ("GET", "/status") => Route::Status,
+        ("GET", "/metrics") => Route::Metrics,
+        ("GET", "/config") => Route::ConfigPage,
+        ("POST", "/config") => Route::ConfigPost,
+        ("GET", "/fobs") => Route::FobsPage,
+        ("GET", "/swipes") => Route::Swipes,
+        ("GET", "/diag/lastsync") => Route::LastSync,
+        ("GET", "/diag/events") => Route::DiagEvents,
+        ("GET", "/check") => Route::Check,
+        ("GET", "/counters") => Route::Counters,
+        ("POST", "/fobs") => Route::FobAdd,
+        ("POST", "/fobs/delete") => Route::FobDelete,
+        ("GET", p) if CAPTIVE_PROBES.contains(&p) => Route::RedirectToConfig,
+        ("POST", "/ota") => Route::OtaUpload,
+        ("POST", "/ota/rollback") => Route::OtaRollback,
+        ("POST", "/unlock") => Route::Unlock,
+        ("POST", "/sync") => Route::Sync,
+        ("POST", "/counters/reset") => Route::CountersReset,
+        _ => match allowed(path) {
+            Some(allow) => Route::MethodNotAllowed(allow),
+            // Bounce unknown GETs so OS captive-portal heuristics fire.
+            None if method == "GET" && onboarding => Route::RedirectToConfig,
+            None => Route::NotFound,
+        },
+    }
+}
+
+impl Route {
+    /// Stable position among the routes, from 0; `MethodNotAllowed`
+    /// counts once whatever its `Allow`.
+    pub fn index(self) -> usize {
+        match self {
+            Route::Ping => 0,
+            Route::Status => 1,
+            Route::Metrics => 2,
+            Route::ConfigPage => 3,
+            Route::ConfigPost => 4,
+            Route::FobsPage => 5,
+            Route::FobAdd => 6,
+            Route::FobDelete => 7,
+            Route::Swipes => 8,
+            Route::LastSync => 9,
+            Route::DiagEvents => 10,
+            Route::OtaUpload => 11,
+            Route::OtaRollback => 12,
+            Route::Unlock => 13,
+            Route::Sync => 14,
+            Route::Check => 15,
+            Route::Counters => 16,
+            Route::CountersReset => 17,
+            Route::RedirectToConfig => 18,
+            Route::NotFound => 19,
+            Route::MethodNotAllowed(_) => 20,
+        }
+    }
+}
+
+/// Counter slot for requests that never got as far as routing (bad
+/// request line, oversized headers), after the routes' own.
+const MALFORMED: usize = 21;
+
+/// Metric label for each counter slot: the routes by [`Route::index`],
+/// then [`MALFORMED`].
+const ROUTE_NAMES: [&str; MALFORMED + 1] = [
+    "ping",
+    "status",
+    "metrics",
+    "config_page",
+    "config_post",
+    "fobs_page",
+    "fob_add",
+    "fob_delete",
+    "swipes",
+    "last_sync",
+    "diag_events",
+    "ota_upload",
+    "ota_rollback",
+    "unlock",
+    "sync",
+    "check",
+    "counters",
+    "counters_reset",
+    "redirect",
+    "not_found",
+    "method_not_allowed",
+    "malformed",
+];
+
+/// Requests served since boot, per route.
+pub struct RouteCounts {
+    counts: [AtomicU32; MALFORMED + 1],
+}
+
+impl RouteCounts {
+    pub const fn new() -> Self {
+        Self {
+            counts: [const { AtomicU32::new(0) }; MALFORMED + 1],
+        }
+    }
+
+    pub fn record(&self, route: Route) {
+        self.counts[route.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a request rejected before it could be routed.
+    pub fn record_malformed(&self) {
+        self.counts[MALFORMED].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, route: Route) -> u32 {
+        self.counts[route.index()].load(Ordering::Relaxed)
+    }
+
+    pub fn malformed(&self) -> u32 {
+        self.counts[MALFORMED].load(Ordering::Relaxed)
+    }
+
+    /// Render every count as a Prometheus counter, one line per route.
+    pub fn write_metrics<W: Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_str("# TYPE conway_http_requests_total counter\n")?;
+        for (name, count) in ROUTE_NAMES.iter().zip(&self.counts) {
+            writeln!(
+                out,
+                "conway_http_requests_total{{route=\"{}\"}} {}",
+                name,
+                count.load(Ordering::Relaxed)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for RouteCounts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One admin request, as logged: `GET /unlock from 192.168.1.20:51234`.
+/// Control characters in the path are written as `?` so a crafted
+/// request cannot forge log lines.
+pub struct RequestLog<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    /// Client IPv4 address and port, when the socket still knows it.
+    pub peer: Option<([u8; 4], u16)>,
+}
+
+impl fmt::Display for RequestLog<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for s in [self.method, " ", self.path] {
+            for c in s.chars() {
+                f.write_char(if c.is_control() { '?' } else { c })?;
+            }
+        }
+        match self.peer {
+            Some(([a, b, c, d], port)) => write!(f, " from {}.{}.{}.{}:{}", a, b, c, d, port),
+            None => f.write_str(" from unknown"),
+        }
+    }
+}
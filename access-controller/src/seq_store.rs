@@ -0,0 +1,67 @@
+//! Persisted event sequence lease (see [`access_controller::events::SeqLease`]).
+//!
+//! ## Layout
+//!
+//! Same ping-pong + monotonic-seq design as `settings.rs`
+//! ([`access_controller::storage`]), in the two sectors of the `evseq`
+//! partition (`FlashLayout::seq_slots`). The payload is the lease limit,
+//! a `u64` LE, sealed with the fobs key under its own domain tag.
+//!
+//! `evseq` was free space before it had a partition, so units updated
+//! over the air, which keep their old partition table, use the same
+//! sectors.
+//!
+//! Factory reset leaves the lease alone: numbering must not go back for
+//! Conway, and the lease holds nothing about the site.
+
+use crate::device_key;
+use crate::flash::EspFlash;
+use access_controller::crypto;
+use access_controller::flash_layout::FLASH;
+use access_controller::storage::{Slot, Storage, StoreConfig};
+
+/// Ping-pong: both sectors of `evseq`.
+const SLOTS: [u32; 2] = FLASH.seq_slots();
+
+const MAGIC: u32 = 0x51_45_53_45; // "ESEQ"
+
+const PAYLOAD_LEN: usize = 8;
+
+fn store() -> Storage<EspFlash> {
+    Storage::new(
+        EspFlash::new(),
+        StoreConfig {
+            slots: SLOTS,
+            magic: MAGIC,
+            domain: crypto::DOMAIN_SEQ,
+            max_payload: PAYLOAD_LEN,
+        },
+    )
+}
+
+/// The lease limit last persisted, or `None` if there is none (first
+/// boot, unprovisioned device).
+pub fn load() -> Option<u64> {
+    let key = device_key::fobs_key()?;
+    let loaded = store().load(key);
+    for (i, slot) in loaded.slots.iter().enumerate() {
+        if let Slot::Rejected { seq } = slot {
+            log::warn!("seq_store: slot {} (seq={}) failed to open", i, seq);
+        }
+    }
+    let record = loaded.into_record()?;
+    let limit: [u8; PAYLOAD_LEN] = record.payload.as_slice().try_into().ok()?;
+    Some(u64::from_le_bytes(limit))
+}
+
+/// Persist `limit`. The other slot keeps the previous limit, so a save
+/// cut short by power loss falls back to it; callers rely on the new
+/// limit only once this returns `Ok`.
+pub fn save(limit: u64) -> Result<(), &'static str> {
+    let Some(key) = device_key::fobs_key() else {
+        return Err("device not provisioned (eFuse BLOCK3 unset)");
+    };
+    let saved = store().save(key, &limit.to_le_bytes())?;
+    log::debug!("seq_store: saved limit {} to slot {}", limit, saved.slot);
+    Ok(())
+}
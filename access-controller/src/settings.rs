@@ -1,12 +1,14 @@
 //! Persistent device settings stored in the `nvs` partition.
 //!
 //! Layout: the 24 KiB `nvs` partition (`partitions.csv`) is treated as two
-//! independent 4 KiB sectors at offsets `NVS_BASE + 0` and `NVS_BASE + 4096`.
+//! independent 4 KiB sectors at its start (`FlashLayout::settings_slots`).
 //! Each write goes to the sector whose last record had the lower sequence
 //! number (or the one that is blank/invalid), then the previous sector is
 //! erased. On boot, both sectors are read and the valid record with the
 //! highest sequence number wins. This gives single-shot wear leveling and
-//! crash-safe atomic updates without pulling in a full K/V store.
+//! crash-safe atomic updates without pulling in a full K/V store. The
+//! slot logic is shared with the other stores in
+//! [`access_controller::storage`].
 //!
 //! ## Encryption (v3)
 //!
@@ -30,6 +32,13 @@
 //!                          the record is treated as having no pubkey,
 //!                          preserving v3.0 compatibility.
 //!   pubkey:         32 bytes (Ed25519 public key, only when flag == 1)
+//!   --- optional tail, present in v3.2+ records ---
+//!   fallback_count: u8     (0..=MAX_FALLBACK_HOSTS; missing => 0)
+//!   fallbacks:      4 bytes each (IPv4 octets, same port as `host`)
+//!   --- optional tail, present in v3.3+ records ---
+//!   path:           u8 length, then bytes (max 64; missing => "/api/fobs")
+//!   --- optional tail, present in v3.4+ records ---
+//!   label:          u8 length, then bytes (max 32; missing => "")
 //! ```
 //!
 //! ## Migration note
@@ -47,28 +56,34 @@
 //! `tools/provision-device-key.sh` once per unit.
 
 use alloc::string::String;
-use embedded_storage::{ReadStorage, Storage};
-use esp_storage::FlashStorage;
 
 use crate::device_key;
-use access_controller::crypto;
+use crate::flash::EspFlash;
+use access_controller::flash_layout::FLASH;
+use access_controller::storage::{Slot, Storage, StoreConfig};
+use access_controller::{crypto, device_label, http_client};
+
+/// Dotted-quad parser for host settings; the grammar lives in
+/// [`access_controller::ipv4`].
+pub use access_controller::ipv4::parse_ipv4;
 
-/// First byte of the `nvs` partition (see `partitions.csv`).
-const NVS_BASE: u32 = 0x9000;
-/// Flash erase granularity / our sector size.
-const SECTOR: u32 = 4096;
 /// We use the first two sectors of the `nvs` partition for ping-pong.
-const SLOTS: [u32; 2] = [NVS_BASE, NVS_BASE + SECTOR];
+const SLOTS: [u32; 2] = FLASH.settings_slots();
 
 const MAGIC: u32 = 0x434F4E57; // "CONW"
 
 pub const MAX_SSID: usize = 32;
 pub const MAX_PASSWORD: usize = 64;
 
+/// Maximum number of fallback Conway hosts tried after the primary.
+pub const MAX_FALLBACK_HOSTS: usize = 3;
+
 /// Plaintext payload upper bound: 1+32 (ssid) + 1+64 (pw) + 1 (flag)
-/// + 4 (host) + 2 (port) + 1 (pubkey_flag) + 32 (pubkey) = 138.
+/// + 4 (host) + 2 (port) + 1 (pubkey_flag) + 32 (pubkey)
+/// + 1 (fallback_count) + 3·4 (fallbacks) + 1+64 (path) + 1+32 (label)
+/// = 249.
 /// Round up for safety/headroom.
-const MAX_PLAINTEXT: usize = 192;
+const MAX_PLAINTEXT: usize = 256;
 
 #[derive(Clone, Debug)]
 pub struct Settings {
@@ -93,6 +108,16 @@ pub struct Settings {
     /// can never recover from without a factory reset) nor silently
     /// clear it (which would lift signature enforcement).
     pub trusted_pubkey: Option<[u8; 32]>,
+    /// Additional Conway servers (sharing `conway_port`) that
+    /// [`crate::sync`] fails over to when the primary is unreachable.
+    /// Ignored in standalone mode.
+    pub fallback_hosts: heapless::Vec<[u8; 4], MAX_FALLBACK_HOSTS>,
+    /// Request-target of the sync `POST`, for Conway deployments behind
+    /// a path prefix. Always passes [`http_client::valid_path`].
+    pub sync_path: String,
+    /// Name shown on the admin index so installers can tell units apart.
+    /// Empty when unset. Always passes [`device_label::valid`].
+    pub device_label: String,
 }
 
 impl Settings {
@@ -105,6 +130,17 @@ impl Settings {
             conway_host: host,
             conway_port: 8080,
             trusted_pubkey: None,
+            fallback_hosts: option_env!("CONWAY_FALLBACK_HOSTS")
+                .and_then(parse_host_list)
+                .unwrap_or_default(),
+            sync_path: option_env!("CONWAY_SYNC_PATH")
+                .filter(|p| http_client::valid_path(p))
+                .unwrap_or(http_client::DEFAULT_SYNC_PATH)
+                .into(),
+            device_label: option_env!("CONWAY_DEVICE_LABEL")
+                .filter(|l| device_label::valid(l))
+                .unwrap_or("")
+                .into(),
         }
     }
 
@@ -117,6 +153,19 @@ impl Settings {
         self.conway_host.is_some()
     }
 
+    /// Primary host followed by the fallbacks, in failover order. Empty
+    /// in standalone mode.
+    pub fn conway_hosts(&self) -> heapless::Vec<[u8; 4], { 1 + MAX_FALLBACK_HOSTS }> {
+        let mut out = heapless::Vec::new();
+        if let Some(h) = self.conway_host {
+            let _ = out.push(h);
+            for &f in self.fallback_hosts.iter() {
+                let _ = out.push(f);
+            }
+        }
+        out
+    }
+
     pub fn conway_host_str(&self) -> alloc::string::String {
         use core::fmt::Write;
         let mut s = alloc::string::String::new();
@@ -161,6 +210,23 @@ impl Settings {
                 out.extend_from_slice(k);
             }
         }
+        // Tail (v3.2): fallback hosts. Same back-compat story as above.
+        out.push(self.fallback_hosts.len() as u8);
+        for h in self.fallback_hosts.iter() {
+            out.extend_from_slice(h);
+        }
+        // Tail (v3.3): sync path.
+        if !http_client::valid_path(&self.sync_path) {
+            return Err("invalid sync path");
+        }
+        out.push(self.sync_path.len() as u8);
+        out.extend_from_slice(self.sync_path.as_bytes());
+        // Tail (v3.4): device label.
+        if !device_label::valid(&self.device_label) {
+            return Err("invalid device label");
+        }
+        out.push(self.device_label.len() as u8);
+        out.extend_from_slice(self.device_label.as_bytes());
         Ok(())
     }
 
@@ -209,7 +275,10 @@ impl Settings {
         // a hard reject so we don't silently install an empty key.
         let trusted_pubkey = match buf.get(p) {
             None => None,
-            Some(&0) => None,
+            Some(&0) => {
+                p += 1;
+                None
+            }
             Some(&1) => {
                 p += 1;
                 if p + 32 > buf.len() {
@@ -217,86 +286,84 @@ impl Settings {
                 }
                 let mut k = [0u8; 32];
                 k.copy_from_slice(&buf[p..p + 32]);
+                p += 32;
                 Some(k)
             }
             Some(_) => return None,
         };
 
+        // Optional fallback-host tail (v3.2). Missing => no fallbacks.
+        let mut fallback_hosts = heapless::Vec::new();
+        if let Some(&n) = buf.get(p) {
+            p += 1;
+            let n = n as usize;
+            if n > MAX_FALLBACK_HOSTS || p + n * 4 > buf.len() {
+                return None;
+            }
+            for c in buf[p..p + n * 4].chunks_exact(4) {
+                let _ = fallback_hosts.push([c[0], c[1], c[2], c[3]]);
+            }
+            p += n * 4;
+        }
+
+        // Optional sync-path tail (v3.3). Missing => default path.
+        let sync_path = match buf.get(p) {
+            None => http_client::DEFAULT_SYNC_PATH.into(),
+            Some(&n) => {
+                p += 1;
+                let n = n as usize;
+                if p + n > buf.len() {
+                    return None;
+                }
+                let path = core::str::from_utf8(&buf[p..p + n]).ok()?;
+                if !http_client::valid_path(path) {
+                    return None;
+                }
+                p += n;
+                path.into()
+            }
+        };
+
+        // Optional device-label tail (v3.4). Missing => no label.
+        let device_label = match buf.get(p) {
+            None => String::new(),
+            Some(&n) => {
+                p += 1;
+                let n = n as usize;
+                if p + n > buf.len() {
+                    return None;
+                }
+                let label = core::str::from_utf8(&buf[p..p + n]).ok()?;
+                if !device_label::valid(label) {
+                    return None;
+                }
+                label.into()
+            }
+        };
+
         Some(Self {
             ssid,
             password,
             conway_host,
             conway_port: port,
             trusted_pubkey,
+            fallback_hosts,
+            sync_path,
+            device_label,
         })
     }
 }
 
-struct Record {
-    seq: u64,
-    payload: alloc::vec::Vec<u8>,
-}
-
-fn read_slot(flash: &mut FlashStorage, base: u32, key: &[u8; 32]) -> Option<Record> {
-    let mut hdr = [0u8; crypto::HEADER_LEN];
-    flash.read(base, &mut hdr).ok()?;
-    let (seq, payload_len) = crypto::parse_header(&hdr, MAGIC, crypto::DOMAIN_SETTINGS)?;
-    let pt_len = payload_len as usize;
-    if pt_len > MAX_PLAINTEXT
-        || crypto::HEADER_LEN + pt_len + crypto::TAG_LEN > SECTOR as usize
-    {
-        return None;
-    }
-
-    let total = crypto::HEADER_LEN + pt_len + crypto::TAG_LEN;
-    let mut sealed = alloc::vec![0u8; total];
-    flash.read(base, &mut sealed).ok()?;
-
-    let mut plaintext = alloc::vec![0u8; pt_len];
-    match crypto::open(key, MAGIC, crypto::DOMAIN_SETTINGS, &sealed, &mut plaintext) {
-        Ok(_n) => Some(Record { seq, payload: plaintext }),
-        Err(e) => {
-            log::warn!("settings: slot @0x{:X} AEAD open failed: {:?}", base, e);
-            None
-        }
-    }
-}
-
-fn write_slot(
-    flash: &mut FlashStorage,
-    base: u32,
-    seq: u64,
-    payload: &[u8],
-    key: &[u8; 32],
-) -> Result<(), &'static str> {
-    let total = crypto::HEADER_LEN + payload.len() + crypto::TAG_LEN;
-    if total > SECTOR as usize {
-        return Err("payload too large");
-    }
-    let mut buf = alloc::vec![0xFFu8; SECTOR as usize];
-    crypto::seal(key, MAGIC, seq, crypto::DOMAIN_SETTINGS, payload, &mut buf[..total])
-        .map_err(|_| "crypto seal failed")?;
-    flash.write(base, &buf).map_err(|_| "flash write failed")
-}
-
-fn erase_slot(flash: &mut FlashStorage, base: u32) -> Result<(), &'static str> {
-    let blank = alloc::vec![0xFFu8; SECTOR as usize];
-    flash.write(base, &blank).map_err(|_| "flash erase failed")
-}
-
-/// Read just the 32-byte envelope header from a slot and return its
-/// `seq` if the header is structurally valid (magic / version / nonce
-/// consistent), regardless of whether the AEAD body decrypts.
-///
-/// Used by [`save`] to derive `next_seq` so an interrupted prior save's
-/// half-written slot — whose body fails to open but whose header is
-/// intact — still bumps the counter. Without this, the retry could
-/// reuse the same `(seq, domain)` nonce with different plaintext, which
-/// is fatal for ChaCha20-Poly1305. See H1 in the security review.
-fn peek_slot_seq(flash: &mut FlashStorage, base: u32) -> Option<u64> {
-    let mut hdr = [0u8; crypto::HEADER_LEN];
-    flash.read(base, &mut hdr).ok()?;
-    crypto::parse_header(&hdr, MAGIC, crypto::DOMAIN_SETTINGS).map(|(seq, _)| seq)
+fn store() -> Storage<EspFlash> {
+    Storage::new(
+        EspFlash::new(),
+        StoreConfig {
+            slots: SLOTS,
+            magic: MAGIC,
+            domain: crypto::DOMAIN_SETTINGS,
+            max_payload: MAX_PLAINTEXT,
+        },
+    )
 }
 
 /// Load the most recent valid settings record. Returns `None` if neither
@@ -304,22 +371,13 @@ fn peek_slot_seq(flash: &mut FlashStorage, base: u32) -> Option<u64> {
 /// if the device key is unavailable.
 pub fn load() -> Option<Settings> {
     let key = device_key::settings_key()?;
-    let mut flash = FlashStorage::new();
-    let a = read_slot(&mut flash, SLOTS[0], key);
-    let b = read_slot(&mut flash, SLOTS[1], key);
-    let winner = match (a, b) {
-        (Some(a), Some(b)) => {
-            if (a.seq.wrapping_sub(b.seq)) as i64 >= 0 {
-                a
-            } else {
-                b
-            }
+    let loaded = store().load(key);
+    for (i, slot) in loaded.slots.iter().enumerate() {
+        if let Slot::Rejected { seq } = slot {
+            log::warn!("settings: slot {} (seq={}) failed to open", i, seq);
         }
-        (Some(a), None) => a,
-        (None, Some(b)) => b,
-        (None, None) => return None,
-    };
-    Settings::deserialize(&winner.payload)
+    }
+    Settings::deserialize(&loaded.into_record()?.payload)
 }
 
 /// Persist new settings. Writes to the older slot, then erases the other.
@@ -328,47 +386,17 @@ pub fn save(s: &Settings) -> Result<(), &'static str> {
     let Some(key) = device_key::settings_key() else {
         return Err("device not provisioned (eFuse BLOCK3 unset)");
     };
-    let mut flash = FlashStorage::new();
-    let a = read_slot(&mut flash, SLOTS[0], key);
-    let b = read_slot(&mut flash, SLOTS[1], key);
-
-    // Pick write slot from successfully-opened slots only (older one).
-    let write_idx: u8 = match (&a, &b) {
-        (None, None) => 0,
-        (None, Some(_)) => 0,
-        (Some(_), None) => 1,
-        (Some(ra), Some(rb)) => {
-            if (ra.seq.wrapping_sub(rb.seq)) as i64 >= 0 {
-                1
-            } else {
-                0
-            }
-        }
-    };
-
-    // next_seq: max over ANY parseable header (even if AEAD open fails)
-    // + 1. Defends against nonce reuse after an interrupted prior save.
-    // See H1 in the security review and `peek_slot_seq` docs above.
-    let seq_a = peek_slot_seq(&mut flash, SLOTS[0]);
-    let seq_b = peek_slot_seq(&mut flash, SLOTS[1]);
-    let max_hdr_seq = match (seq_a, seq_b) {
-        (Some(x), Some(y)) => Some(if (x.wrapping_sub(y)) as i64 >= 0 { x } else { y }),
-        (Some(x), None) | (None, Some(x)) => Some(x),
-        (None, None) => None,
-    };
-    let next_seq = max_hdr_seq.map(|s| s.wrapping_add(1)).unwrap_or(1u64);
-
     let mut payload = alloc::vec::Vec::with_capacity(128);
     s.serialize(&mut payload)?;
 
-    write_slot(&mut flash, SLOTS[write_idx as usize], next_seq, &payload, key)?;
-    let other = (1 - write_idx) as usize;
-    let _ = erase_slot(&mut flash, SLOTS[other]);
+    let mut store = store();
+    let saved = store.save(key, &payload)?;
+    let _ = store.erase_slot(1 - saved.slot);
 
     log::info!(
         "settings: saved seq={} to slot {} ({} bytes plaintext, encrypted)",
-        next_seq,
-        write_idx,
+        saved.seq,
+        saved.slot,
         payload.len()
     );
     Ok(())
@@ -377,27 +405,22 @@ pub fn save(s: &Settings) -> Result<(), &'static str> {
 /// Wipe both sectors. Next `load()` will return `None` and the device
 /// will boot into onboarding (AP) mode.
 pub fn erase() -> Result<(), &'static str> {
-    let mut flash = FlashStorage::new();
-    erase_slot(&mut flash, SLOTS[0])?;
-    erase_slot(&mut flash, SLOTS[1])?;
+    store().erase()?;
     log::warn!("settings: NVS wiped (factory reset)");
     Ok(())
 }
 
-/// Parse an IPv4 dotted-quad string into 4 octets.
-pub fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
-    let mut octets = [0u8; 4];
-    let mut idx = 0;
-    for part in s.split('.') {
-        if idx >= 4 {
-            return None;
+/// Parse a comma-separated list of IPv4 fallback hosts. Blank entries
+/// are skipped; any malformed entry, or more than
+/// [`MAX_FALLBACK_HOSTS`], rejects the whole list.
+pub fn parse_host_list(s: &str) -> Option<heapless::Vec<[u8; 4], MAX_FALLBACK_HOSTS>> {
+    let mut out = heapless::Vec::new();
+    for part in s.split(',') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
         }
-        octets[idx] = part.parse().ok()?;
-        idx += 1;
-    }
-    if idx == 4 {
-        Some(octets)
-    } else {
-        None
+        out.push(parse_ipv4(part)?).ok()?;
     }
+    Some(out)
 }
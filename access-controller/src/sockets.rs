@@ -0,0 +1,99 @@
+//! Socket budget for the embassy-net stack.
+//!
+//! `StackResources<N>` backs a fixed smoltcp `SocketSet`; creating one
+//! socket more than `N` panics inside the stack. Every socket the
+//! firmware can hold open at the same time is counted here, per device
+//! mode, and `main.rs` asserts at compile time that the worst case for
+//! the build's options fits [`STACK_SOCKETS`].
+//!
+//! Sockets are freed when dropped, so a task that opens one per attempt
+//! (sync, push, an online check) counts once.
+
+/// Socket count passed to `StackResources`. Reviewed against
+/// [`required`]: the worst case today is 7, with push and an online
+/// check at each of two readers, and the rest is headroom for the next
+/// service (mDNS, a second admin connection).
+pub const STACK_SOCKETS: usize = 8;
+
+/// Sockets embassy-net allocates itself.
+pub mod stack {
+    /// The `dns` feature's query socket, always present.
+    pub const DNS_CLIENT: usize = 1;
+    /// The `dhcpv4` client, only when the config asks for DHCP.
+    pub const DHCP_CLIENT: usize = 1;
+}
+
+/// Sockets the firmware's tasks open.
+pub mod tasks {
+    /// `http_server_task`: one connection at a time, both modes.
+    pub const HTTP_SERVER: usize = 1;
+    /// `sync_task`, station mode with a Conway host configured.
+    pub const SYNC: usize = 1;
+    /// `push_task`, when sync runs and `CONWAY_PUSH_PATH` is set.
+    pub const PUSH: usize = 1;
+    /// Each `online_check_task`, when sync runs and `CONWAY_AUTHZ_PATH`
+    /// is set. One runs per reader.
+    pub const ONLINE_CHECK: usize = 1;
+    /// Onboarding's captive DHCP and DNS servers, one UDP socket each.
+    pub const DHCP_SERVER: usize = 1;
+    pub const DNS_SERVER: usize = 1;
+}
+
+/// What a build (or a boot) runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Services {
+    /// Station mode; otherwise the onboarding access point.
+    pub station: bool,
+    /// A Conway host is configured, so `sync_task` runs.
+    pub sync: bool,
+    /// Push channel compiled in (`CONWAY_PUSH_PATH`).
+    pub push: bool,
+    /// `online_check_task`s compiled in (`CONWAY_AUTHZ_PATH`): one per
+    /// reader, or 0.
+    pub online_checks: usize,
+}
+
+/// Sockets open at once with `services` running.
+pub const fn sockets(services: Services) -> usize {
+    let mut n = stack::DNS_CLIENT + tasks::HTTP_SERVER;
+    if services.station {
+        n += stack::DHCP_CLIENT;
+        if services.sync {
+            n += tasks::SYNC;
+            if services.push {
+                n += tasks::PUSH;
+            }
+            n += services.online_checks * tasks::ONLINE_CHECK;
+        }
+    } else {
+        n += tasks::DHCP_SERVER + tasks::DNS_SERVER;
+    }
+    n
+}
+
+/// Worst case over everything a build can do at runtime: either mode,
+/// with or without a Conway host. `push` and `online_checks` are fixed
+/// at build time.
+pub const fn required(push: bool, online_checks: usize) -> usize {
+    let mut worst = 0;
+    let mut i = 0;
+    while i < 4 {
+        let n = sockets(Services {
+            station: i & 1 != 0,
+            sync: i & 2 != 0,
+            push,
+            online_checks,
+        });
+        if n > worst {
+            worst = n;
+        }
+        i += 1;
+    }
+    worst
+}
+
+/// Whether a build with (or without) the push channel and with
+/// `online_checks` checkers fits the stack.
+pub const fn fits(push: bool, online_checks: usize) -> bool {
+    required(push, online_checks) <= STACK_SOCKETS
+}
@@ -0,0 +1,139 @@
+//! Incremental server-sent events (SSE) parser for the Conway push
+//! channel.
+//!
+//! The lighter alternative to [`crate::websocket`]: a plain HTTP `GET`
+//! whose response never ends, carrying `data:` events. Bytes arrive in
+//! whatever pieces TCP delivers, so the parser is fed chunk by chunk and
+//! keeps a partial line (and a pending CR, for a CRLF split across reads)
+//! between calls.
+//!
+//! Follows the WHATWG event-stream rules that matter here: lines end in
+//! CRLF, LF or CR; `:` starts a comment; `data` lines accumulate
+//! (joined by `\n`) until a blank line dispatches the event; `event` sets
+//! its type; other fields are ignored. Lines longer than the buffer are
+//! dropped rather than truncated.
+
+use core::fmt::Write;
+
+use heapless::{String as HString, Vec as HVec};
+
+/// Longest line kept, in bytes.
+pub const MAX_LINE: usize = 128;
+/// Longest accumulated `data` per event, in bytes.
+pub const MAX_DATA: usize = 128;
+const MAX_EVENT_TYPE: usize = 32;
+
+/// A dispatched event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SseEvent<'a> {
+    /// `event` field, or `"message"` if none was given.
+    pub event: &'a str,
+    pub data: &'a str,
+}
+
+#[derive(Default)]
+pub struct SseParser {
+    line: HVec<u8, MAX_LINE>,
+    line_overflow: bool,
+    after_cr: bool,
+    event: HString<MAX_EVENT_TYPE>,
+    data: HString<MAX_DATA>,
+    has_data: bool,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next chunk of the stream, calling `on_event` for each
+    /// event completed by it.
+    pub fn feed(&mut self, chunk: &[u8], mut on_event: impl FnMut(SseEvent<'_>)) {
+        for &b in chunk {
+            let after_cr = core::mem::replace(&mut self.after_cr, b == b'\r');
+            match b {
+                b'\n' if after_cr => {}
+                b'\r' | b'\n' => self.end_line(&mut on_event),
+                _ => {
+                    if self.line.push(b).is_err() {
+                        self.line_overflow = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn end_line(&mut self, on_event: &mut impl FnMut(SseEvent<'_>)) {
+        if core::mem::take(&mut self.line_overflow) {
+            self.line.clear();
+            return;
+        }
+        if self.line.is_empty() {
+            if core::mem::take(&mut self.has_data) {
+                let event = if self.event.is_empty() {
+                    "message"
+                } else {
+                    self.event.as_str()
+                };
+                on_event(SseEvent {
+                    event,
+                    data: self.data.as_str(),
+                });
+            }
+            self.data.clear();
+            self.event.clear();
+            return;
+        }
+
+        let line = core::mem::take(&mut self.line);
+        let Ok(line) = core::str::from_utf8(&line) else {
+            return;
+        };
+        let (field, value) = match line.split_once(':') {
+            Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+            None => (line, ""),
+        };
+        match field {
+            // Comment (leading ':').
+            "" => {}
+            "data" => {
+                if self.has_data {
+                    let _ = self.data.push('\n');
+                }
+                self.has_data = true;
+                for c in value.chars() {
+                    if self.data.push(c).is_err() {
+                        break;
+                    }
+                }
+            }
+            "event" => {
+                self.event.clear();
+                let _ = self.event.push_str(value);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Write the stream request. HTTP/1.0 so the server streams the body
+/// raw until it closes the connection instead of chunk-encoding it.
+pub fn write_request<W: Write>(out: &mut W, path: &str, host: &str) -> core::fmt::Result {
+    write!(
+        out,
+        "GET {} HTTP/1.0\r\n\
+         Host: {}\r\n\
+         Accept: text/event-stream\r\n\
+         \r\n",
+        path, host
+    )
+}
+
+/// Whether the response head opens an event stream: status 200 with
+/// `Content-Type: text/event-stream`.
+pub fn stream_accepted(head: &str) -> bool {
+    crate::http_client::parse_status_code(head) == 200
+        && crate::http_client::extract_header(head, "content-type")
+            .and_then(|v| v.split(';').next())
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("text/event-stream"))
+}
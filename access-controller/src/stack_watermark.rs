@@ -0,0 +1,36 @@
+//! Stack high-water mark.
+//!
+//! Every task runs on the one executor, so they all share the main stack,
+//! and an overflow runs silently into the statics below it. At boot the
+//! firmware paints the free part of the stack with [`PAINT`]; a frame
+//! that ever reached a word overwrote it. Scanning up from the bottom for
+//! the first word that no longer holds the paint gives how much stack has
+//! never been used, which `/status` shows and the watchdog tick checks
+//! against [`LOW_STACK_BYTES`].
+//!
+//! A frame that happens to store the paint value itself reads as unused,
+//! so the figure can overstate the headroom by the odd word. It never
+//! understates it.
+
+/// Fill word for unused stack.
+pub const PAINT: u32 = 0xA5A5_A5A5;
+
+/// Unused stack below which the firmware warns.
+pub const LOW_STACK_BYTES: usize = 2048;
+
+/// Words of the stack never used, given its words from the bottom
+/// (lowest address, where a downward-growing stack ends) upwards: the
+/// run of [`PAINT`] they start with.
+pub fn unused_words<I: IntoIterator<Item = u32>>(bottom_up: I) -> usize {
+    bottom_up.into_iter().take_while(|&w| w == PAINT).count()
+}
+
+/// [`unused_words`], in bytes.
+pub fn unused_bytes<I: IntoIterator<Item = u32>>(bottom_up: I) -> usize {
+    unused_words(bottom_up) * 4
+}
+
+/// Whether `unused` bytes of stack is too little headroom.
+pub fn low(unused: usize) -> bool {
+    unused < LOW_STACK_BYTES
+}
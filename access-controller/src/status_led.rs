@@ -0,0 +1,249 @@
+//! Boot and network status patterns for the LEDs and beeper.
+//!
+//! Installers mounting a reader can't see the serial log, so the
+//! controller shows where it is in bring-up with blink patterns:
+//!
+//! - [`Pattern::Connecting`], solid: WiFi has no link or no IPv4 address.
+//! - [`Pattern::Syncing`], 5 Hz blink: networked, no fob list loaded yet.
+//! - [`Pattern::Ready`], 1 Hz heartbeat: a fob list is loaded.
+//! - [`Pattern::SyncError`], double flash: [`SYNC_FAILURE_THRESHOLD`]
+//!   syncs in a row failed.
+//! - [`Pattern::Offline`], a short blink every two seconds: no sync has
+//!   succeeded for longer than the offline threshold (see [`SyncAge`]).
+//! - [`Pattern::Fault`], triple flash: a subsystem is critical (see
+//!   [`crate::health`]).
+//!
+//! The STATUS LED always shows the current pattern. The reader LED, the
+//! one visible from the door, mirrors it only during boot: from power-up
+//! until the first time the controller is ready, at which point the
+//! reader beeps once and goes back to access feedback only. A sync error
+//! reached before that plays a distinct beep so the installer knows to
+//! check the server rather than the wiring.
+//!
+//! Pure state; the firmware samples the network and sync state, feeds
+//! it to [`BootIndicator`], and plays the returned steps.
+
+use crate::clock;
+
+/// Consecutive failed syncs before the error pattern is shown. A single
+/// failure is normal (a server restart, a dropped packet); three in a
+/// row is half a minute of not reaching Conway.
+pub const SYNC_FAILURE_THRESHOLD: u8 = 3;
+
+/// Default offline threshold: an hour without a successful sync.
+pub const DEFAULT_OFFLINE_SECS: u64 = 3_600;
+
+/// Longest offline threshold [`parse_offline_secs`] accepts: a week.
+pub const MAX_OFFLINE_SECS: u64 = 7 * 24 * 3_600;
+
+/// Parse a `CONWAY_OFFLINE_SECS` value: seconds without a successful
+/// sync before the controller counts as offline, at most
+/// [`MAX_OFFLINE_SECS`]. `0` turns the offline state off.
+pub fn parse_offline_secs(s: &str) -> Option<u64> {
+    s.parse().ok().filter(|secs| *secs <= MAX_OFFLINE_SECS)
+}
+
+/// Time since the last successful sync, and whether that is past the
+/// offline threshold.
+///
+/// Counted from boot until the first sync succeeds, so a controller that
+/// never reaches Conway goes offline too. A threshold of 0 never does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyncAge {
+    threshold_ms: u64,
+    last_ok_ms: Option<u64>,
+    offline: bool,
+}
+
+impl SyncAge {
+    pub const fn new(threshold_secs: u64) -> Self {
+        Self {
+            threshold_ms: threshold_secs.saturating_mul(1_000),
+            last_ok_ms: None,
+            offline: false,
+        }
+    }
+
+    /// A sync succeeded at `now_ms`. Ends the offline state at once;
+    /// returns whether it did.
+    pub fn synced(&mut self, now_ms: u64) -> bool {
+        self.last_ok_ms = Some(now_ms);
+        core::mem::replace(&mut self.offline, false)
+    }
+
+    /// When the last sync succeeded, or `None` if none has since boot.
+    pub fn last_ok_ms(&self) -> Option<u64> {
+        self.last_ok_ms
+    }
+
+    /// Milliseconds since the last successful sync, or since boot if
+    /// there has been none.
+    pub fn since_ms(&self, now_ms: u64) -> u64 {
+        clock::elapsed(self.last_ok_ms.unwrap_or(0), now_ms)
+    }
+
+    /// Whether the controller is offline at `now_ms`.
+    pub fn is_offline(&self, now_ms: u64) -> bool {
+        self.threshold_ms > 0 && self.since_ms(now_ms) >= self.threshold_ms
+    }
+
+    /// Re-evaluate at `now_ms`. Returns the new state if it changed.
+    pub fn update(&mut self, now_ms: u64) -> Option<bool> {
+        let offline = self.is_offline(now_ms);
+        if offline == self.offline {
+            return None;
+        }
+        self.offline = offline;
+        Some(offline)
+    }
+
+    /// Whether the last [`update`](Self::update) found the controller
+    /// offline.
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+}
+
+/// One LED or beeper step: output level and how long to hold it.
+pub type Step = (bool, u64);
+
+/// Inputs to pattern selection, sampled by the firmware.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetStatus {
+    /// WiFi link is up (station associated, or AP started).
+    pub wifi_up: bool,
+    /// The network stack has an IPv4 address.
+    pub ip_configured: bool,
+    /// A fob list is in RAM (synced this boot or restored from flash).
+    /// Always true when there is nothing to sync from.
+    pub fobs_loaded: bool,
+    /// Syncs failed in a row since the last success.
+    pub sync_failures: u8,
+    /// No sync has succeeded for longer than the offline threshold.
+    pub offline: bool,
+    /// Overall health is [`crate::health::Health::Critical`].
+    pub critical: bool,
+}
+
+/// What the status LEDs show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    /// Solid on.
+    Connecting,
+    /// 5 Hz blink.
+    Syncing,
+    /// 1 Hz heartbeat.
+    Ready,
+    /// Double flash once a second.
+    SyncError,
+    /// Short blink every two seconds.
+    Offline,
+    /// Triple flash once a second.
+    Fault,
+}
+
+impl Pattern {
+    /// Pick the pattern for `s`. A critical fault beats everything else,
+    /// since it can turn members away; then no network, then being
+    /// offline. A run of failed syncs is shown even with a list loaded,
+    /// since the list is then going stale.
+    pub fn select(s: NetStatus) -> Self {
+        if s.critical {
+            Self::Fault
+        } else if !(s.wifi_up && s.ip_configured) {
+            Self::Connecting
+        } else if s.offline {
+            Self::Offline
+        } else if s.sync_failures >= SYNC_FAILURE_THRESHOLD {
+            Self::SyncError
+        } else if !s.fobs_loaded {
+            Self::Syncing
+        } else {
+            Self::Ready
+        }
+    }
+
+    /// LED steps for one cycle of the pattern, repeated while it is
+    /// shown.
+    pub fn steps(self) -> &'static [Step] {
+        match self {
+            Self::Connecting => &[(true, 250)],
+            Self::Syncing => &[(true, 100), (false, 100)],
+            Self::Ready => &[(true, 500), (false, 500)],
+            Self::SyncError => &[(true, 100), (false, 100), (true, 100), (false, 700)],
+            Self::Offline => &[(true, 100), (false, 1_900)],
+            Self::Fault => &[
+                (true, 100),
+                (false, 100),
+                (true, 100),
+                (false, 100),
+                (true, 100),
+                (false, 500),
+            ],
+        }
+    }
+}
+
+/// Beeper steps played once when the reader leaves boot indication
+/// ([`Pattern::Ready`]: one short chirp), or hits [`Pattern::SyncError`]
+/// (two long beeps) or [`Pattern::Fault`] (three) during boot. The
+/// beeper is off afterwards.
+pub fn chime(pattern: Pattern) -> &'static [Step] {
+    match pattern {
+        Pattern::Ready => &[(true, 50)],
+        Pattern::SyncError => &[(true, 400), (false, 200), (true, 400)],
+        Pattern::Fault => &[(true, 400), (false, 200), (true, 400), (false, 200), (true, 400)],
+        Pattern::Connecting | Pattern::Syncing | Pattern::Offline => &[],
+    }
+}
+
+/// A pattern change reported by [`BootIndicator::update`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub pattern: Pattern,
+    /// Whether the reader LED shows the pattern (boot is still in
+    /// progress). Once false it stays false until reboot.
+    pub on_reader: bool,
+    /// Beeper steps to play now; empty for none.
+    pub chime: &'static [Step],
+}
+
+/// Tracks the shown pattern and the end of boot.
+#[derive(Clone, Debug, Default)]
+pub struct BootIndicator {
+    shown: Option<Pattern>,
+    booted: bool,
+}
+
+impl BootIndicator {
+    pub const fn new() -> Self {
+        Self {
+            shown: None,
+            booted: false,
+        }
+    }
+
+    /// Feed the current status. Returns the change to apply, or `None`
+    /// if the pattern is the one already shown.
+    pub fn update(&mut self, s: NetStatus) -> Option<Change> {
+        let pattern = Pattern::select(s);
+        if self.shown == Some(pattern) {
+            return None;
+        }
+        self.shown = Some(pattern);
+        let chime = if self.booted { &[][..] } else { chime(pattern) };
+        if pattern == Pattern::Ready {
+            self.booted = true;
+        }
+        Some(Change {
+            pattern,
+            on_reader: !self.booted,
+            chime,
+        })
+    }
+
+    /// Whether the controller has been ready at least once.
+    pub fn booted(&self) -> bool {
+        self.booted
+    }
+}
@@ -0,0 +1,381 @@
+//! Encrypted ping-pong record store over a flash backend.
+//!
+//! `settings`, `fob_store` and `cache_store` each keep one record in a
+//! pair of 4 KiB sectors: a save writes the slot not holding the newest
+//! readable record, so a power loss mid-write always leaves the
+//! previous record intact, and a load takes the readable record with the
+//! highest sequence number. Records are sealed with [`crate::crypto`];
+//! the Poly1305 tag is the integrity check (it replaced the v1 CRC-32),
+//! so a torn or corrupted slot fails to open and is skipped.
+//!
+//! The logic lives here, generic over [`FlashBackend`], so it can be
+//! exercised on the host against [`MemFlash`]. The firmware implements
+//! the trait over `esp_storage::FlashStorage`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::crypto;
+use crate::flash_layout::SECTOR;
+
+/// Raw flash access at absolute offsets.
+pub trait FlashBackend {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), &'static str>;
+    /// Replace `data.len()` bytes at `offset`. Erasing first, if the
+    /// medium needs it, is the backend's job.
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), &'static str>;
+    /// Set `len` bytes at `offset` to 0xFF. Both must be sector aligned.
+    fn erase(&mut self, offset: u32, len: u32) -> Result<(), &'static str>;
+}
+
+/// Where and how one store keeps its record.
+#[derive(Clone, Copy, Debug)]
+pub struct StoreConfig {
+    /// The two sector-aligned slots.
+    pub slots: [u32; 2],
+    pub magic: u32,
+    pub domain: [u8; 4],
+    /// Largest plaintext accepted on save or load.
+    pub max_payload: usize,
+}
+
+/// A record that opened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub seq: u64,
+    pub payload: Vec<u8>,
+}
+
+/// What one slot holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Slot {
+    /// No parseable header: erased, never written, or another store's.
+    Empty,
+    /// The header parses but the record does not open: a torn write,
+    /// corruption, or a different key.
+    Rejected {
+        seq: u64,
+    },
+    Valid(Record),
+}
+
+impl Slot {
+    /// Sequence number from the header, readable or not.
+    pub fn seq(&self) -> Option<u64> {
+        match self {
+            Self::Empty => None,
+            Self::Rejected { seq } => Some(*seq),
+            Self::Valid(r) => Some(r.seq),
+        }
+    }
+}
+
+/// Result of [`Storage::load`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Loaded {
+    /// Both slots, in slot order.
+    pub slots: [Slot; 2],
+    /// Index into `slots` of the newest valid record.
+    pub newest: Option<usize>,
+}
+
+impl Loaded {
+    /// The newest valid record.
+    pub fn record(&self) -> Option<&Record> {
+        match &self.slots[self.newest?] {
+            Slot::Valid(r) => Some(r),
+            _ => None,
+        }
+    }
+
+    pub fn into_record(self) -> Option<Record> {
+        let i = self.newest?;
+        match self.slots.into_iter().nth(i) {
+            Some(Slot::Valid(r)) => Some(r),
+            _ => None,
+        }
+    }
+
+    /// If a rejected slot has a newer header than the returned record
+    /// (a save that was cut short), that header's seq: the record is one
+    /// generation behind what was last written.
+    pub fn fell_back(&self) -> Option<u64> {
+        let newest = self.record().map(|r| r.seq);
+        self.slots
+            .iter()
+            .filter_map(|s| match s {
+                Slot::Rejected { seq } => Some(*seq),
+                _ => None,
+            })
+            .filter(|&seq| newest.is_none_or(|n| seq != n && newer(seq, n)))
+            .reduce(|x, y| if newer(x, y) { x } else { y })
+    }
+}
+
+/// Result of [`Storage::save`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Saved {
+    pub seq: u64,
+    /// Index of the slot written.
+    pub slot: usize,
+}
+
+/// Whether `a` is at or after `b`. The signed difference handles u64
+/// wraparound (irrelevant in practice, but free).
+pub fn newer(a: u64, b: u64) -> bool {
+    (a.wrapping_sub(b)) as i64 >= 0
+}
+
+/// One ping-pong record store.
+pub struct Storage<F: FlashBackend> {
+    flash: F,
+    cfg: StoreConfig,
+}
+
+impl<F: FlashBackend> Storage<F> {
+    pub fn new(flash: F, cfg: StoreConfig) -> Self {
+        Self { flash, cfg }
+    }
+
+    pub fn flash_mut(&mut self) -> &mut F {
+        &mut self.flash
+    }
+
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    fn header(&mut self, idx: usize) -> Option<(u64, usize)> {
+        let mut hdr = [0u8; crypto::HEADER_LEN];
+        self.flash.read(self.cfg.slots[idx], &mut hdr).ok()?;
+        crypto::parse_header(&hdr, self.cfg.magic, self.cfg.domain)
+            .map(|(seq, len)| (seq, len as usize))
+    }
+
+    /// Read and open slot `idx` (0 or 1).
+    pub fn read_slot(&mut self, idx: usize, key: &[u8; 32]) -> Slot {
+        let Some((seq, pt_len)) = self.header(idx) else {
+            return Slot::Empty;
+        };
+        let total = crypto::HEADER_LEN + pt_len + crypto::TAG_LEN;
+        if pt_len > self.cfg.max_payload || total > SECTOR as usize {
+            return Slot::Rejected { seq };
+        }
+        let mut sealed = vec![0u8; total];
+        if self.flash.read(self.cfg.slots[idx], &mut sealed).is_err() {
+            return Slot::Rejected { seq };
+        }
+        let mut payload = vec![0u8; pt_len];
+        match crypto::open(key, self.cfg.magic, self.cfg.domain, &sealed, &mut payload) {
+            Ok(_) => Slot::Valid(Record { seq, payload }),
+            Err(_) => Slot::Rejected { seq },
+        }
+    }
+
+    /// Read both slots and pick the newest valid record.
+    pub fn load(&mut self, key: &[u8; 32]) -> Loaded {
+        let slots = [self.read_slot(0, key), self.read_slot(1, key)];
+        let newest = match (&slots[0], &slots[1]) {
+            (Slot::Valid(a), Slot::Valid(b)) => Some(if newer(a.seq, b.seq) { 0 } else { 1 }),
+            (Slot::Valid(_), _) => Some(0),
+            (_, Slot::Valid(_)) => Some(1),
+            _ => None,
+        };
+        Loaded { slots, newest }
+    }
+
+    /// Seal `payload` into the slot not holding the newest valid record.
+    ///
+    /// The seq is one past the newest parseable header, whether or not
+    /// that record opens: an interrupted save leaves a torn slot whose
+    /// header is intact, and reusing its seq would reuse the
+    /// `(seq, domain)` nonce with different plaintext, which is fatal for
+    /// ChaCha20-Poly1305 (H1 in the security review).
+    ///
+    /// The other slot is left alone; callers that want a single copy
+    /// erase it with [`Self::erase_slot`].
+    pub fn save(&mut self, key: &[u8; 32], payload: &[u8]) -> Result<Saved, &'static str> {
+        let total = crypto::HEADER_LEN + payload.len() + crypto::TAG_LEN;
+        if payload.len() > self.cfg.max_payload || total > SECTOR as usize {
+            return Err("payload too large");
+        }
+        let loaded = self.load(key);
+        let slot = match loaded.newest {
+            Some(i) => 1 - i,
+            None => 0,
+        };
+        let seq = loaded
+            .slots
+            .iter()
+            .filter_map(Slot::seq)
+            .reduce(|x, y| if newer(x, y) { x } else { y })
+            .map_or(1, |s| s.wrapping_add(1));
+
+        // Whole-sector buffer so the write is one sector-aligned
+        // erase+program. The unused tail stays 0xFF.
+        let mut buf = vec![0xFFu8; SECTOR as usize];
+        crypto::seal(
+            key,
+            self.cfg.magic,
+            seq,
+            self.cfg.domain,
+            payload,
+            &mut buf[..total],
+        )
+        .map_err(|_| "crypto seal failed")?;
+        self.flash.write(self.cfg.slots[slot], &buf)?;
+        Ok(Saved { seq, slot })
+    }
+
+    pub fn erase_slot(&mut self, idx: usize) -> Result<(), &'static str> {
+        self.flash.erase(self.cfg.slots[idx], SECTOR)
+    }
+
+    /// Erase both slots. Needs no key, so a factory reset works on an
+    /// unprovisioned device.
+    pub fn erase(&mut self) -> Result<(), &'static str> {
+        self.erase_slot(0)?;
+        self.erase_slot(1)
+    }
+}
+
+/// Check that the sector at `offset` can be written and erased: program
+/// a pattern derived from `seed`, read it back, erase, and read back
+/// 0xFF. Run once at boot on a scratch sector, since the stores only find
+/// out on their first save, and a failed save is merely logged.
+///
+/// Vary `seed` per boot, so a sector still holding the last boot's
+/// pattern can't pass for a write that never landed. The sector is left
+/// erased.
+pub fn check_writable<F: FlashBackend>(
+    flash: &mut F,
+    offset: u32,
+    seed: u32,
+) -> Result<(), &'static str> {
+    // xorshift32; any nonzero state gives a sequence with no repeats
+    // within a sector.
+    let mut x = seed | 1;
+    let mut pattern = vec![0u8; SECTOR as usize];
+    for word in pattern.chunks_exact_mut(4) {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        word.copy_from_slice(&x.to_le_bytes());
+    }
+    flash.write(offset, &pattern)?;
+    let mut back = vec![0u8; SECTOR as usize];
+    flash.read(offset, &mut back)?;
+    if back != pattern {
+        return Err("flash read-back mismatch");
+    }
+    flash.erase(offset, SECTOR)?;
+    flash.read(offset, &mut back)?;
+    if back.iter().any(|&b| b != 0xFF) {
+        return Err("flash erase did not take");
+    }
+    Ok(())
+}
+
+/// In-memory flash for host tests, covering `len` bytes from `base`.
+///
+/// [`Self::cut_power_after`] simulates a power loss partway through a
+/// later write or erase.
+#[derive(Clone, Debug)]
+pub struct MemFlash {
+    base: u32,
+    bytes: Vec<u8>,
+    budget: Option<usize>,
+    off: bool,
+}
+
+impl MemFlash {
+    /// Erased flash.
+    pub fn new(base: u32, len: u32) -> Self {
+        Self {
+            base,
+            bytes: vec![0xFF; len as usize],
+            budget: None,
+            off: false,
+        }
+    }
+
+    /// Lose power once `bytes` more bytes have been programmed or erased.
+    /// A write erases its whole range first, as `FlashStorage` does, so
+    /// a write cut short leaves 0xFF after the last byte programmed. Once
+    /// off, every operation fails until [`Self::restore_power`].
+    pub fn cut_power_after(&mut self, bytes: usize) {
+        self.budget = Some(bytes);
+    }
+
+    pub fn restore_power(&mut self) {
+        self.budget = None;
+        self.off = false;
+    }
+
+    /// Whether a cut has happened.
+    pub fn is_off(&self) -> bool {
+        self.off
+    }
+
+    /// Direct access to the byte at `offset`, for corrupting records.
+    pub fn byte_mut(&mut self, offset: u32) -> &mut u8 {
+        &mut self.bytes[(offset - self.base) as usize]
+    }
+
+    fn range(&self, offset: u32, len: usize) -> Result<core::ops::Range<usize>, &'static str> {
+        let start = offset
+            .checked_sub(self.base)
+            .ok_or("flash offset out of range")? as usize;
+        let end = start + len;
+        if end > self.bytes.len() {
+            return Err("flash offset out of range");
+        }
+        Ok(start..end)
+    }
+
+    /// Program `data` into `range`, honouring the power budget.
+    fn program(&mut self, range: core::ops::Range<usize>, data: &[u8]) -> Result<(), &'static str> {
+        if self.off {
+            return Err("power lost");
+        }
+        let n = self.budget.map_or(data.len(), |b| b.min(data.len()));
+        self.bytes[range.start..range.start + n].copy_from_slice(&data[..n]);
+        if let Some(b) = self.budget.as_mut() {
+            *b -= n;
+            if n < data.len() {
+                self.off = true;
+                return Err("power lost");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FlashBackend for MemFlash {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), &'static str> {
+        if self.off {
+            return Err("power lost");
+        }
+        let r = self.range(offset, buf.len())?;
+        buf.copy_from_slice(&self.bytes[r]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), &'static str> {
+        let r = self.range(offset, data.len())?;
+        if self.off {
+            return Err("power lost");
+        }
+        self.bytes[r.clone()].fill(0xFF);
+        self.program(r, data)
+    }
+
+    fn erase(&mut self, offset: u32, len: u32) -> Result<(), &'static str> {
+        if !offset.is_multiple_of(SECTOR) || !len.is_multiple_of(SECTOR) {
+            return Err("erase not sector aligned");
+        }
+        let r = self.range(offset, len as usize)?;
+        let blank = vec![0xFFu8; len as usize];
+        self.program(r, &blank)
+    }
+}
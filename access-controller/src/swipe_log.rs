@@ -63,17 +63,11 @@ use esp_storage::FlashStorage;
 use sequential_storage::cache::NoCache;
 use sequential_storage::queue;
 
-/// Start of the swipe-log region (first sector after `fob_store`'s two
-/// ping-pong slots). Keep in sync with `partitions.csv` and
-/// [`crate::fob_store`].
-const SWIPE_LOG_BASE: u32 = 0x13000;
-/// End of the `fobs` partition.
-const SWIPE_LOG_END: u32 = 0x20000;
-
-/// Flash range owned by the queue. `sequential_storage` requires the
+/// Flash range owned by the queue: the `fobs` partition after
+/// `fob_store`'s two ping-pong slots. `sequential_storage` requires the
 /// bounds to be erase-sector (4 KiB) aligned, which both are.
 const fn region() -> Range<u32> {
-    SWIPE_LOG_BASE..SWIPE_LOG_END
+    FLASH.swipe_log()
 }
 
 /// Serialised size of one [`SwipeLogEntry`]: `fob`(4) + `allowed`(1) +
@@ -191,7 +185,7 @@ pub async fn read_recent<const N: usize>() -> heapless::Vec<SwipeLogEntry, N> {
 /// step failed, so a reset works on damaged units.
 pub fn erase() -> Result<(), &'static str> {
     let mut flash = FlashStorage::new();
-    NorFlash::erase(&mut flash, SWIPE_LOG_BASE, SWIPE_LOG_END).map_err(|_| "swipe_log erase failed")?;
+    NorFlash::erase(&mut flash, region().start, region().end).map_err(|_| "swipe_log erase failed")?;
     log::warn!("swipe_log: wiped");
     Ok(())
 }
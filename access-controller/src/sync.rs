@@ -6,8 +6,12 @@
 //!
 //! Each request can include fob swipe events to be stored.
 //! A bounded set of events are held in-memory.
+//!
+//! The round-trip itself is [`access_controller::sync_flow`]; this module
+//! supplies its TCP transport and shared state, and handles failover and
+//! redirects.
 
-use core::fmt::Write as FmtWrite;
+use core::sync::atomic::{AtomicU8, Ordering};
 use embassy_net::tcp::TcpSocket;
 use embassy_net::Stack;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
@@ -15,491 +19,767 @@ use embassy_sync::mutex::Mutex;
 use embassy_time::Duration;
 use embedded_io_async::Write;
 use heapless::String as HString;
-use smoltcp::wire::IpAddress;
-
-use crate::{EVENT_BUFFER, MAX_FOBS, RuntimeConfig, SYNC_COMPLETE};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+
+use access_controller::clock::Clock;
+use access_controller::counters::Counter;
+use access_controller::decode::{CredentialFormat, FobFormat};
+use access_controller::diag::LastResponse;
+use access_controller::door::MAX_EXTENDED_UNLOCK;
+use access_controller::etag::{self, FullResync, HostEtag, MAX_ETAG_LEN};
+use access_controller::events::{self, AuditRing, Commit, Dedup, EventRing, SeqLease};
+use access_controller::failover::Failover;
+use access_controller::fob_cache::{self, CacheMeta, Reconcile};
+use access_controller::http_client::{self, RedirectPolicy, SyncTarget};
+use access_controller::idempotency::BatchKeys;
+use access_controller::sync_flow::{
+    self, Batch, EmptyListPolicy, ListGuard, ListRules, Outcome, Retries, Suspect, SyncConfig,
+    SyncContext, Transport,
+};
+use access_controller::rng::RandomSource;
+use access_controller::status_led::{self, SyncAge};
+use access_controller::sync_guard::{SyncGuard, SyncRuns};
+use access_controller::sync_nonce;
+use access_controller::wire::{EncodingChoice, EventEncoding, EventFields, SyncProtocol};
+
+use crate::{cache_store, seq_store, BootClock, EVENT_BUFFER, MAX_FOBS, RuntimeConfig, SYNC_COMPLETE};
 
 const IO_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Socket rx window, sized for the largest response accepted (~8 KiB
+/// with MAX_FOBS=512). Heap-allocated so it doesn't blow the task stack.
+const RESPONSE_CAP: usize = http_client::MAX_RESPONSE_BYTES;
+
+/// Idempotency keys for event batches. Replaced in `main` once the MAC
+/// and boot nonce are known, before `sync_task` starts.
+pub static BATCH_KEYS: Mutex<CriticalSectionRawMutex, BatchKeys> =
+    Mutex::new(BatchKeys::new([0; 6], 0));
+
+/// Sequence numbers persisted as possibly used; see [`SeqLease`].
+static EVENT_SEQ: Mutex<CriticalSectionRawMutex, SeqLease> = Mutex::new(SeqLease::resume(0));
+
+/// Which entry of `Settings::conway_hosts()` answered last. Persisted
+/// only in RAM; after a reboot the primary is tried first again.
+pub static FAILOVER: Mutex<CriticalSectionRawMutex, Failover> = Mutex::new(Failover::new());
+
+/// Syncs failed in a row (every host) since the last success; drives the
+/// status LED error pattern. Saturates rather than wrapping.
+pub static SYNC_FAILURES: AtomicU8 = AtomicU8::new(0);
+
+/// Syncs failed (every host) since boot.
+pub static SYNC_FAILURES_TOTAL: Counter = Counter::new();
+
+/// Time since the last successful sync; drives the offline pattern and
+/// the offline row on `/status`. Off until `main` sets the threshold.
+pub static SYNC_AGE: Mutex<CriticalSectionRawMutex, SyncAge> = Mutex::new(SyncAge::new(0));
+
+/// Truncated copy of the last sync response, served at `/diag/lastsync`.
+pub static LAST_RESPONSE: Mutex<CriticalSectionRawMutex, LastResponse> =
+    Mutex::new(LastResponse::new());
+
+/// Credential format Conway last hinted; `None` tries both the fob and
+/// the 4-byte NFC form. RAM only: until the first sync after boot, reads
+/// are matched both ways.
+pub static CREDENTIAL_FORMAT: Mutex<CriticalSectionRawMutex, Option<CredentialFormat>> =
+    Mutex::new(None);
+
+/// Credentials Conway last tagged for an extended unlock. RAM only, like
+/// [`CREDENTIAL_FORMAT`]: until the first sync after boot every grant
+/// gets the default hold.
+pub static EXTENDED_UNLOCK: Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_EXTENDED_UNLOCK>> =
+    Mutex::new(heapless::Vec::new());
+
+/// Recent access decisions, served at `/diag/events`. Fed by
+/// [`EventBuffer::push`] but never drained by a sync.
+pub static AUDIT: Mutex<CriticalSectionRawMutex, AuditRing> = Mutex::new(AuditRing::new());
+
+/// Shared secret a 200's `X-Fob-HMAC` is checked under (see
+/// [`access_controller::body_mac`]). The feature without a key fails
+/// the build rather than quietly skipping the check.
+#[cfg(feature = "response-hmac")]
+const RESPONSE_HMAC_KEY: Option<&[u8]> = match option_env!("CONWAY_RESPONSE_HMAC_KEY") {
+    Some(k) if !k.is_empty() => Some(k.as_bytes()),
+    _ => panic!("the response-hmac feature needs CONWAY_RESPONSE_HMAC_KEY"),
+};
+#[cfg(not(feature = "response-hmac"))]
+const RESPONSE_HMAC_KEY: Option<&[u8]> = None;
+
+/// A suspect list held for confirmation; see [`list_rules`].
+static LIST_GUARD: Mutex<CriticalSectionRawMutex, ListGuard> = Mutex::new(ListGuard::new());
+
+/// Syncs since the last full list; see [`full_resync_every`].
+pub static FULL_RESYNC: Mutex<CriticalSectionRawMutex, FullResync> =
+    Mutex::new(FullResync::new());
+
+/// Resume event numbering at the persisted lease, at boot before any
+/// event is pushed, and take the first lease of this boot.
+pub async fn resume_event_seq() {
+    let lease = SeqLease::resume(seq_store::load().unwrap_or(0));
+    EVENT_BUFFER.resume_at(lease.limit()).await;
+    *EVENT_SEQ.lock().await = lease;
+    renew_event_seq().await;
+    log::info!("events: numbering from {}", lease.limit());
+}
+
+/// Extend the lease once numbering gets near its end. Writes flash, so
+/// never from `access_task`.
+async fn renew_event_seq() {
+    let next = EVENT_BUFFER.next_seq().await;
+    let mut lease = EVENT_SEQ.lock().await;
+    let Some(limit) = lease.renewal(next) else {
+        return;
+    };
+    match crate::with_watchdog("sequence lease save", |_| seq_store::save(limit)) {
+        Ok(()) => lease.extended(limit),
+        // Retried on the next sync. Numbers past the old limit could be
+        // reused after a reboot until one lands.
+        Err(e) => log::error!("events: saving sequence lease failed: {}", e),
+    }
+}
+
+/// Set while [`sync_with_conway`] runs.
+pub static SYNC_RUNNING: SyncGuard = SyncGuard::new();
+
+/// The event encoding the last server to send `Accept-Post` takes; only
+/// consulted with `CONWAY_EVENT_ENCODING=auto`. Not persisted: after a
+/// reboot events go out verbose until a server says otherwise.
+pub static ACCEPTED_EVENT_ENCODING: Mutex<CriticalSectionRawMutex, Option<EventEncoding>> =
+    Mutex::new(None);
+
+/// Syncs run so far and how the last ended, for `POST /sync`.
+pub static SYNC_RUNS: Mutex<CriticalSectionRawMutex, SyncRuns> = Mutex::new(SyncRuns::new());
+
 /// Sync with Conway server using raw TCP HTTP.
 /// Events are only removed from the buffer after successful server acknowledgment.
+///
+/// Tries each configured host in [`Failover`] order until one completes
+/// the round-trip. A call while another sync is running returns at once;
+/// see [`SyncGuard`].
 pub async fn sync_with_conway(
     stack: &'static Stack<'static>,
     fobs: &'static Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
-    etag: &'static Mutex<CriticalSectionRawMutex, HString<64>>,
+    etag: &'static Mutex<CriticalSectionRawMutex, HostEtag>,
     rt: &'static RuntimeConfig,
 ) {
-    // Snapshot host + port from the live config so a `/config` POST that
+    // Skip rather than overlap. No SYNC_COMPLETE here: the running sync
+    // signals it once its list is in place, and a swipe waiting on a
+    // recheck must not be answered from the list before that.
+    let Some(_running) = SYNC_RUNNING.try_begin() else {
+        log::debug!("sync: already running, coalesced");
+        return;
+    };
+    let run = SYNC_RUNS.lock().await.start();
+    renew_event_seq().await;
+
+    // Snapshot hosts + port from the live config so a `/config` POST that
     // updates them takes effect on the next sync without restart. If the
     // host has been cleared (standalone mode), there is nothing to sync.
     // Also snapshot the optional trusted public key here so we don't
     // have to re-lock `settings` after the response arrives.
-    let (host_octets, host_port, trusted_pubkey) = {
+    let (hosts, host_port, sync_path, trusted_pubkey) = {
         let s = rt.settings.lock().await;
-        match s.conway_host {
-            Some(h) => (h, s.conway_port, s.trusted_pubkey),
-            None => {
-                // Shouldn't happen normally - sync_task isn't spawned
-                // when host is None - but a hot config change could land
-                // us here. Drop pending events on the floor to avoid
-                // unbounded growth.
-                log::debug!("sync: standalone mode, skipping");
-                SYNC_COMPLETE.signal(());
-                return;
-            }
-        }
-    };
-    let host_str = {
-        use core::fmt::Write;
-        let mut s: HString<24> = HString::new();
-        let _ = write!(
-            s,
-            "{}.{}.{}.{}",
-            host_octets[0], host_octets[1], host_octets[2], host_octets[3]
-        );
-        s
+        (s.conway_hosts(), s.conway_port, s.sync_path.clone(), s.trusted_pubkey)
     };
-
-    // Peek at pending events without removing them from the buffer.
-    // They will only be removed after the server acknowledges receipt.
-    let mut events: [AccessEvent; MAX_EVENTS] = [AccessEvent::default(); MAX_EVENTS];
-    let (event_count, event_tail) = EVENT_BUFFER.peek(&mut events).await;
-
-    // Build request body with events
-    let mut body: HString<512> = HString::new();
-    let _ = body.push_str("[");
-    for i in 0..event_count {
-        if i > 0 {
-            let _ = body.push_str(",");
-        }
-        let _ = write!(
-            body,
-            r#"{{"fob":{},"allowed":{}}}"#,
-            events[i].fob, events[i].allowed
-        );
-    }
-    let _ = body.push_str("]");
-
-    // Get current ETag for If-None-Match header
-    let current_etag = {
-        let guard = etag.lock().await;
-        guard.clone()
-    };
-
-    // Build IP endpoint directly from settings octets.
-    let remote_addr = IpAddress::Ipv4(smoltcp::wire::Ipv4Address::new(
-        host_octets[0],
-        host_octets[1],
-        host_octets[2],
-        host_octets[3],
-    ));
-
-    // Create TCP socket. Size buffers from MAX_FOBS: each fob serializes
-    // to up to 10 decimal digits + ',' = 11 bytes, plus '[' / ']' and
-    // ~1 KiB of HTTP response headers. With MAX_FOBS=512 this is ~7 KiB;
-    // a fixed 2 KiB buffer truncates silently and the cache goes stale.
-    // Heap-allocated so we don't blow the task stack.
-    const RESPONSE_CAP: usize = MAX_FOBS * 12 + 1024;
-    let mut rx_buf = alloc::vec![0u8; RESPONSE_CAP];
-    let mut tx_buf = alloc::vec![0u8; 1024];
-    let mut socket = TcpSocket::new(*stack, rx_buf.as_mut_slice(), tx_buf.as_mut_slice());
-    socket.set_timeout(Some(IO_TIMEOUT));
-
-    // Connect to server
-    let remote = smoltcp::wire::IpEndpoint::new(remote_addr, host_port);
-    log::debug!("sync: connecting to {:?}", remote);
-
-    if let Err(e) = socket.connect(remote).await {
-        log::error!("sync: connect failed: {:?}", e);
-        socket.abort();
-        SYNC_COMPLETE.signal(());
-        return;
-    }
-
-    // Build and send HTTP request
-    let mut request: HString<512> = HString::new();
-    let _ = write!(
-        request,
-        "POST /api/fobs HTTP/1.1\r\n\
-         Host: {}\r\n\
-         Content-Type: application/json\r\n\
-         Content-Length: {}\r\n\
-         Connection: close\r\n",
-        host_str.as_str(),
-        body.len()
-    );
-    if !current_etag.is_empty() {
-        let _ = write!(request, "If-None-Match: {}\r\n", current_etag);
-    }
-    let _ = request.push_str("\r\n");
-
-    // Send request headers
-    if let Err(e) = socket.write_all(request.as_bytes()).await {
-        log::error!("sync: write headers failed: {:?}", e);
-        socket.abort();
+    if hosts.is_empty() {
+        // Shouldn't happen normally - sync_task isn't spawned when host
+        // is None - but a hot config change could land us here. Drop
+        // pending events on the floor to avoid unbounded growth.
+        log::debug!("sync: standalone mode, skipping");
+        SYNC_RUNS.lock().await.finish(run, false);
         SYNC_COMPLETE.signal(());
         return;
     }
 
-    // Send request body
-    if let Err(e) = socket.write_all(body.as_bytes()).await {
-        log::error!("sync: write body failed: {:?}", e);
-        socket.abort();
-        SYNC_COMPLETE.signal(());
-        return;
+    let redirects = RedirectPolicy {
+        cross_host: matches!(option_env!("CONWAY_REDIRECT_CROSS_HOST"), Some("1")),
+        ..RedirectPolicy::default()
+    };
+    let protocol = match option_env!("CONWAY_SYNC_PROTOCOL") {
+        None => SyncProtocol::default(),
+        Some(s) => SyncProtocol::parse(s).unwrap_or_else(|| {
+            log::warn!("sync: unknown CONWAY_SYNC_PROTOCOL {:?}, using json", s);
+            SyncProtocol::default()
+        }),
+    };
+    let fob_format = match option_env!("CONWAY_FOB_FORMAT") {
+        None => FobFormat::default(),
+        Some(s) => FobFormat::parse(s).unwrap_or_else(|| {
+            log::warn!("sync: unknown CONWAY_FOB_FORMAT {:?}, using decimal", s);
+            FobFormat::default()
+        }),
+    };
+    let event_fields = match option_env!("CONWAY_EVENT_FIELDS") {
+        None => EventFields::DEFAULT,
+        Some(s) => EventFields::parse(s).unwrap_or_else(|| {
+            log::warn!("sync: invalid CONWAY_EVENT_FIELDS {:?}, using fob,allowed,seq", s);
+            EventFields::DEFAULT
+        }),
+    };
+    let encoding_choice = match option_env!("CONWAY_EVENT_ENCODING") {
+        None => EncodingChoice::default(),
+        Some(s) => EncodingChoice::parse(s).unwrap_or_else(|| {
+            log::warn!("sync: unknown CONWAY_EVENT_ENCODING {:?}, using verbose", s);
+            EncodingChoice::default()
+        }),
+    };
+    let cfg = SyncConfig {
+        protocol,
+        redirects,
+        trusted_pubkey: trusted_pubkey.as_ref(),
+        fob_format,
+        hmac_key: RESPONSE_HMAC_KEY,
+        nonce: None,
+        event_fields,
+        event_encoding: encoding_choice.pick(*ACCEPTED_EVENT_ENCODING.lock().await),
+    };
+    // Off by default: a server that doesn't echo the nonce would fail
+    // every sync.
+    let send_nonce = matches!(option_env!("CONWAY_SYNC_NONCE"), Some("1"));
+    let mut rng = crate::entropy::Entropy::new();
+    let path = HString::try_from(sync_path.as_str())
+        .unwrap_or_else(|_| HString::try_from(http_client::DEFAULT_SYNC_PATH).unwrap());
+    let unconditional = FULL_RESYNC.lock().await.begin_cycle(full_resync_every());
+    if unconditional {
+        log::info!("sync: periodic full resync, not sending If-None-Match");
     }
 
-    // Read response. Buffer is sized for the worst-case fob list above.
-    // If the server somehow sends more, treat it as a hard error: do NOT
-    // replace the cache and do NOT commit events.
-    let mut response_buf = alloc::vec![0u8; RESPONSE_CAP];
-    let mut total_read = 0;
-    let mut truncated = false;
-
-    loop {
-        match socket.read(&mut response_buf[total_read..]).await {
-            Ok(0) => break, // Connection closed
-            Ok(n) => {
-                total_read += n;
-                if total_read >= response_buf.len() {
-                    truncated = true;
-                    break;
+    let failover = *FAILOVER.lock().await;
+    let mut synced = false;
+    for idx in failover.order(hosts.len()) {
+        let h = hosts[idx];
+        let mut target = SyncTarget {
+            host: h,
+            port: host_port,
+            path: path.clone(),
+        };
+        // Redirects are followed per cycle and never persisted; the
+        // configured host is asked first again on the next sync.
+        let mut hops = 0;
+        let mut retries = Retries::new(sync_retries());
+        let result = loop {
+            let mut rx_buf = alloc::vec![0u8; RESPONSE_CAP];
+            let mut tx_buf = alloc::vec![0u8; 1024];
+            let mut transport = TcpTransport::new(stack, &mut rx_buf, &mut tx_buf);
+            let mut ctx = FirmwareSync {
+                fobs,
+                etag,
+                max_events: max_events_per_sync(),
+                unconditional,
+                list_rules: list_rules(),
+            };
+            // A fresh socket per attempt, so a retry reconnects, and a
+            // fresh nonce, so no earlier response answers it.
+            let nonce = sync_nonce::generate(&mut rng);
+            let cfg = SyncConfig {
+                nonce: send_nonce.then_some(nonce.as_str()),
+                ..cfg
+            };
+            let attempt =
+                sync_flow::try_sync_with_host(&mut transport, &mut ctx, &target, hops, &cfg);
+            match attempt.await {
+                Ok(Outcome::Redirect(next)) => {
+                    log::info!(
+                        "sync: redirected to {}.{}.{}.{}:{}{}",
+                        next.host[0], next.host[1], next.host[2], next.host[3], next.port, next.path
+                    );
+                    target = next;
+                    hops += 1;
                 }
+                Ok(Outcome::NotModified) => {
+                    log::debug!("sync: not modified");
+                    break Ok(());
+                }
+                Ok(Outcome::Updated { fobs: n }) => {
+                    log::info!("sync: received {} fobs", n);
+                    FULL_RESYNC.lock().await.fetched();
+                    break Ok(());
+                }
+                Ok(Outcome::ListHeld) => break Ok(()),
+                Err(failure) if retries.retry(&failure) => {
+                    log::info!("sync: {}, retrying", failure.error);
+                }
+                Err(failure) => break Err(failure.error),
+            }
+        };
+        match result {
+            Ok(()) => {
+                if idx != failover.last_good() {
+                    log::warn!(
+                        "sync: failed over to {}.{}.{}.{} (host #{})",
+                        h[0], h[1], h[2], h[3], idx
+                    );
+                }
+                FAILOVER.lock().await.succeeded(idx);
+                synced = true;
+                break;
             }
             Err(e) => {
-                log::error!("sync: read failed: {:?}", e);
-                socket.abort();
-                SYNC_COMPLETE.signal(());
-                return;
+                log::warn!("sync: {}.{}.{}.{} failed: {}", h[0], h[1], h[2], h[3], e);
             }
         }
     }
-
-    socket.abort();
-
-    if truncated {
-        log::error!(
-            "sync: response exceeded {} bytes, refusing to update cache",
-            RESPONSE_CAP
-        );
-        SYNC_COMPLETE.signal(());
-        return;
+    if synced {
+        SYNC_FAILURES.store(0, Ordering::Relaxed);
+        if SYNC_AGE.lock().await.synced(BootClock.now_ms()) {
+            log::info!("sync: back online");
+        }
+    } else {
+        let n = SYNC_FAILURES.load(Ordering::Relaxed);
+        SYNC_FAILURES.store(n.saturating_add(1), Ordering::Relaxed);
+        SYNC_FAILURES_TOTAL.incr();
     }
+    SYNC_RUNS.lock().await.finish(run, synced);
 
-    // Parse HTTP response
-    let response = match core::str::from_utf8(&response_buf[..total_read]) {
-        Ok(s) => s,
-        Err(_) => {
-            log::error!("sync: invalid response encoding");
-            SYNC_COMPLETE.signal(());
-            return;
-        }
-    };
+    // Signal that sync is complete (success or failure)
+    SYNC_COMPLETE.signal(());
+}
 
-    // Parse status code
-    let status = parse_status_code(response);
-    log::debug!("sync: status {}", status);
+/// [`Transport`] over an embassy TCP socket.
+struct TcpTransport<'a> {
+    socket: TcpSocket<'a>,
+}
 
-    match status {
-        304 => {
-            log::debug!("sync: not modified");
-            // Server acknowledged the request - safe to remove events from buffer
-            EVENT_BUFFER.commit(event_count, event_tail).await;
-        }
-        200 => {
-            // Extract ETag from headers
-            let new_etag = extract_header(response, "etag");
-            // X-Fob-Signature must be present and verify against the
-            // body bytes whenever the device has been provisioned with
-            // a trusted_pubkey. Until a key is configured, the header
-            // is ignored — see RFC in `signing.rs` module docs.
-            let sig_header = extract_header(response, "x-fob-signature");
-
-            // Find body (after \r\n\r\n)
-            let body_start = response.find("\r\n\r\n").map(|i| i + 4);
-            let response_body = body_start.map(|i| &response[i..]).unwrap_or("");
-
-            // Signature gate: must come BEFORE we replace the cache or
-            // commit events. A failed verify is treated identically to
-            // an unparseable body — events are kept buffered for retry
-            // against (presumably) the legitimate server later.
-            if let Some(pk) = trusted_pubkey.as_ref() {
-                let sig = match sig_header {
-                    Some(s) => s,
-                    None => {
-                        log::error!(
-                            "sync: trusted_pubkey configured but server omitted X-Fob-Signature; refusing update"
-                        );
-                        SYNC_COMPLETE.signal(());
-                        return;
-                    }
-                };
-                if !access_controller::signing::verify(pk, response_body.as_bytes(), sig) {
-                    log::error!(
-                        "sync: X-Fob-Signature failed to verify against trusted_pubkey; refusing update"
-                    );
-                    SYNC_COMPLETE.signal(());
-                    return;
-                }
-                log::debug!("sync: signature verified");
-            }
+impl<'a> TcpTransport<'a> {
+    fn new(stack: &'static Stack<'static>, rx: &'a mut [u8], tx: &'a mut [u8]) -> Self {
+        let mut socket = TcpSocket::new(*stack, rx, tx);
+        socket.set_timeout(Some(IO_TIMEOUT));
+        Self { socket }
+    }
+}
 
-            // Parse fob list
-            let new_fobs = match parse_fob_list(response_body) {
-                Ok(f) => f,
-                Err(e) => {
-                    log::error!("sync: {}", e);
-                    // Don't commit events - they will be retried on next sync
-                    SYNC_COMPLETE.signal(());
-                    return;
-                }
-            };
+impl Transport for TcpTransport<'_> {
+    async fn connect(&mut self, host: [u8; 4], port: u16) -> Result<(), &'static str> {
+        let addr = IpAddress::Ipv4(Ipv4Address::new(host[0], host[1], host[2], host[3]));
+        let remote = IpEndpoint::new(addr, port);
+        // embassy-net assigns the local port, rotating through its
+        // ephemeral range (seeded per boot in `main`), so back-to-back
+        // attempts never reuse a 4-tuple.
+        log::debug!("sync: connecting to {:?}", remote);
+        self.socket.connect(remote).await.map_err(|e| {
+            log::error!("sync: connect failed: {:?}", e);
+            "connect failed"
+        })
+    }
 
-            log::info!("sync: received {} fobs", new_fobs.len());
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        self.socket.write_all(data).await.map_err(|e| {
+            log::error!("sync: write failed: {:?}", e);
+            "write failed"
+        })
+    }
 
-            // Update shared fob list
-            {
-                let mut guard = fobs.lock().await;
-                guard.clear();
-                for &f in new_fobs.iter() {
-                    let _ = guard.push(f);
-                }
-            }
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        self.socket.read(buf).await.map_err(|e| {
+            log::error!("sync: read failed: {:?}", e);
+            "read failed"
+        })
+    }
 
-            // Update etag
-            if let Some(etag_value) = new_etag {
-                let mut guard = etag.lock().await;
-                guard.clear();
-                let _ = guard.push_str(etag_value);
-            }
+    fn close(&mut self) {
+        self.socket.abort();
+    }
+}
 
-            // Server acknowledged the request - safe to remove events from buffer
-            EVENT_BUFFER.commit(event_count, event_tail).await;
-        }
-        _ => {
-            log::error!("sync: unexpected status: {}", status);
-            // Don't commit events - they will be retried on next sync
+/// [`SyncContext`] over the shared fob list and ETag, the global event
+/// buffer, batch keys and cache bookkeeping.
+struct FirmwareSync {
+    fobs: &'static Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
+    etag: &'static Mutex<CriticalSectionRawMutex, HostEtag>,
+    /// Cap on events per request; see [`max_events_per_sync`].
+    max_events: usize,
+    /// Leave off `If-None-Match` this cycle; see [`FullResync`].
+    unconditional: bool,
+    list_rules: ListRules,
+}
+
+/// Which lists wait for confirmation: an empty one replacing a
+/// non-empty one as `CONWAY_EMPTY_LIST` says (default: confirm), and one
+/// shorter by more than `CONWAY_MAX_LIST_SHRINK_PCT` (default: no limit).
+fn list_rules() -> ListRules {
+    let empty = match option_env!("CONWAY_EMPTY_LIST") {
+        None => EmptyListPolicy::default(),
+        Some(s) => EmptyListPolicy::parse(s).unwrap_or_else(|| {
+            log::warn!("sync: unknown CONWAY_EMPTY_LIST {:?}, confirming", s);
+            EmptyListPolicy::default()
+        }),
+    };
+    let max_shrink_pct = option_env!("CONWAY_MAX_LIST_SHRINK_PCT").and_then(|s| {
+        let pct = sync_flow::parse_shrink_pct(s);
+        if pct.is_none() {
+            log::warn!("sync: invalid CONWAY_MAX_LIST_SHRINK_PCT {:?}, no limit", s);
         }
+        pct
+    });
+    ListRules {
+        empty,
+        max_shrink_pct,
     }
+}
 
-    // Signal that sync is complete (success or failure)
-    SYNC_COMPLETE.signal(());
+/// Force a full list every `CONWAY_FULL_RESYNC_CYCLES` syncs; unset
+/// (or invalid) trusts the ETag indefinitely.
+fn full_resync_every() -> u32 {
+    match option_env!("CONWAY_FULL_RESYNC_CYCLES") {
+        None => 0,
+        Some(s) => etag::parse_full_resync(s).unwrap_or_else(|| {
+            log::warn!("sync: invalid CONWAY_FULL_RESYNC_CYCLES {:?}, ignoring", s);
+            0
+        }),
+    }
 }
 
-/// Parse HTTP status code from response.
-fn parse_status_code(response: &str) -> u16 {
-    // Format: "HTTP/1.1 200 OK\r\n..."
-    response
-        .lines()
-        .next()
-        .and_then(|line| line.split_whitespace().nth(1))
-        .and_then(|code| code.parse().ok())
-        .unwrap_or(0)
+/// Events sent per sync: `CONWAY_MAX_EVENTS_PER_SYNC`, or all pending.
+/// A longer backlog drains over consecutive syncs.
+fn max_events_per_sync() -> usize {
+    match option_env!("CONWAY_MAX_EVENTS_PER_SYNC") {
+        None => MAX_EVENTS,
+        Some(s) => events::parse_max_per_sync(s).unwrap_or_else(|| {
+            log::warn!("sync: invalid CONWAY_MAX_EVENTS_PER_SYNC {:?}, sending all", s);
+            MAX_EVENTS
+        }),
+    }
 }
 
-/// Extract header value (case-insensitive).
-fn extract_header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
-    for line in response.lines() {
-        if line.is_empty() || line == "\r" {
-            break; // End of headers
-        }
-        if let Some((key, value)) = line.split_once(':') {
-            if key.trim().eq_ignore_ascii_case(name) {
-                return Some(value.trim());
-            }
-        }
+/// Immediate retries of a host after a socket error:
+/// `CONWAY_SYNC_RETRIES`, or one.
+fn sync_retries() -> u8 {
+    match option_env!("CONWAY_SYNC_RETRIES") {
+        None => sync_flow::DEFAULT_RETRIES,
+        Some(s) => sync_flow::parse_retries(s).unwrap_or_else(|| {
+            log::warn!("sync: invalid CONWAY_SYNC_RETRIES {:?}, retrying once", s);
+            sync_flow::DEFAULT_RETRIES
+        }),
     }
-    None
 }
 
-/// Parse IPv4 address string. Currently unused inside this module but
-/// kept for tests / potential future callers.
-#[allow(dead_code)]
-fn parse_ipv4(s: &str) -> Option<smoltcp::wire::Ipv4Address> {
-    let mut octets = [0u8; 4];
-    let mut octet_idx = 0;
+/// Whether granted swipes are reported: `CONWAY_REPORT_GRANTS`, or yes.
+/// Denials are reported regardless.
+pub fn report_grants() -> bool {
+    match option_env!("CONWAY_REPORT_GRANTS") {
+        None => true,
+        Some(s) => events::parse_report_grants(s).unwrap_or_else(|| {
+            log::warn!("sync: invalid CONWAY_REPORT_GRANTS {:?}, reporting grants", s);
+            true
+        }),
+    }
+}
 
-    for part in s.split('.') {
-        if octet_idx >= 4 {
-            return None;
-        }
-        octets[octet_idx] = part.parse().ok()?;
-        octet_idx += 1;
+/// Seconds without a successful sync before the controller counts as
+/// offline: `CONWAY_OFFLINE_SECS`, or an hour. 0 never does.
+pub fn offline_after_secs() -> u64 {
+    match option_env!("CONWAY_OFFLINE_SECS") {
+        None => status_led::DEFAULT_OFFLINE_SECS,
+        Some(s) => status_led::parse_offline_secs(s).unwrap_or_else(|| {
+            log::warn!(
+                "sync: invalid CONWAY_OFFLINE_SECS {:?}, using {} s",
+                s,
+                status_led::DEFAULT_OFFLINE_SECS
+            );
+            status_led::DEFAULT_OFFLINE_SECS
+        }),
     }
+}
 
-    if octet_idx == 4 {
-        Some(smoltcp::wire::Ipv4Address::new(
-            octets[0], octets[1], octets[2], octets[3],
-        ))
-    } else {
-        None
+/// Repeat suppression for the event buffer: `CONWAY_EVENT_DEDUP_MS`, or
+/// off.
+pub fn event_dedup() -> Dedup {
+    match option_env!("CONWAY_EVENT_DEDUP_MS") {
+        None => Dedup::default(),
+        Some(s) => Dedup::new(events::parse_dedup_ms(s).unwrap_or_else(|| {
+            log::warn!("sync: invalid CONWAY_EVENT_DEDUP_MS {:?}, not suppressing repeats", s);
+            0
+        })),
     }
 }
 
-fn parse_fob_list(json: &str) -> Result<heapless::Vec<u32, MAX_FOBS>, &'static str> {
-    let trimmed = json.trim();
-    if !trimmed.starts_with('[') || !trimmed.ends_with(']') {
-        return Err("not a JSON array");
+impl SyncContext for FirmwareSync {
+    async fn batch(&mut self) -> Batch {
+        // Peek at pending events without removing them from the buffer.
+        // They will only be removed after the server acknowledges receipt.
+        let mut events = [AccessEvent::default(); MAX_EVENTS];
+        let (pending, first_seq) = EVENT_BUFFER.peek_up_to(&mut events, self.max_events).await;
+        // Frozen batch + idempotency key, so a retry after a lost response
+        // re-sends the same events under the same key.
+        match BATCH_KEYS.lock().await.batch(first_seq, pending) {
+            Some((key, n)) => Batch {
+                events: heapless::Vec::from_slice(&events[..n]).unwrap_or_default(),
+                first_seq,
+                key: Some(key),
+            },
+            None => Batch {
+                first_seq,
+                ..Batch::default()
+            },
+        }
     }
 
-    let inner = &trimmed[1..trimmed.len() - 1];
-    let mut fobs = heapless::Vec::new();
+    async fn etag(&mut self) -> HostEtag {
+        if self.unconditional {
+            return HostEtag::new();
+        }
+        self.etag.lock().await.clone()
+    }
+
+    async fn fob_crc(&mut self) -> u32 {
+        fob_cache::sorted_digest(&self.fobs.lock().await)
+    }
 
-    for part in inner.split(',') {
-        let part = part.trim();
-        if part.is_empty() {
-            // Tolerate `[]` and a single trailing comma so the cache
-            // doesn't get nuked by a stylistic server change. Embedded
-            // empties (e.g. `1,,2`) still parse as empty and are skipped.
-            continue;
+    async fn replace_list(&mut self, host: [u8; 4], new_fobs: &[u32], new_etag: Option<&str>) {
+        // The exchange's buffers are all still live here.
+        crate::sample_heap();
+        {
+            let mut guard = self.fobs.lock().await;
+            guard.clear();
+            for &f in new_fobs {
+                let _ = guard.push(f);
+            }
         }
-        // Strict: any non-empty element that does NOT parse as a bare
-        // u32 is a hard error. Previously this silently dropped the
-        // element, so a pretty-printed body or any schema evolution
-        // (e.g. `[{"id":1}, ...]`) yielded an empty list that was then
-        // committed as the live cache -> mass lockout with no signal.
-        let fob: u32 = part
-            .parse()
-            .map_err(|_| "fob list element is not a u32")?;
-        if fobs.push(fob).is_err() {
-            return Err("fob list exceeds MAX_FOBS");
+
+        // The new list replaces whatever the old tag described, so a
+        // response without one clears it.
+        {
+            let mut guard = self.etag.lock().await;
+            match new_etag {
+                Some(v) => {
+                    if !guard.set(host, v) && !v.is_empty() {
+                        // Sending it back truncated would never match;
+                        // go unconditional instead.
+                        log::warn!(
+                            "sync: ETag not cached ({} bytes, max {} visible ASCII), \
+                             syncs will be unconditional",
+                            v.len(),
+                            MAX_ETAG_LEN
+                        );
+                    }
+                }
+                None => guard.clear(),
+            }
         }
-    }
 
-    Ok(fobs)
-}
+        // RAM is now one generation ahead of flash until `persist_cache`
+        // lands the write.
+        let mut st = CACHE_STATE.lock().await;
+        let base = st.live.or(st.persisted).map(|m| m.seq).unwrap_or(0);
+        st.live = Some(CacheMeta::of(base.wrapping_add(1), new_fobs));
+    }
 
-pub const MAX_EVENTS: usize = 20;
+    async fn set_credential_format(&mut self, format: Option<CredentialFormat>) {
+        let mut guard = CREDENTIAL_FORMAT.lock().await;
+        if *guard != format {
+            log::info!("sync: credential format {:?} -> {:?}", *guard, format);
+            *guard = format;
+        }
+    }
 
-/// Re-export so existing `use crate::sync::AccessEvent` call sites keep
-/// compiling. The struct itself lives in the pure `events` module so the
-/// host-side simulation tests can use it without pulling in HAL deps.
-pub use access_controller::events::AccessEvent;
-
-/// Event buffer state.
-struct EventBufferInner {
-    events: [AccessEvent; MAX_EVENTS],
-    head: usize, // next write position
-    tail: usize, // next read position
-}
+    async fn set_event_encoding(&mut self, encoding: EventEncoding) {
+        let mut accepted = ACCEPTED_EVENT_ENCODING.lock().await;
+        if *accepted != Some(encoding) {
+            log::info!("sync: server takes {:?} events", encoding);
+            *accepted = Some(encoding);
+        }
+    }
 
-impl EventBufferInner {
-    const fn new() -> Self {
-        Self {
-            events: [AccessEvent { fob: 0, allowed: false }; MAX_EVENTS],
-            head: 0,
-            tail: 0,
+    async fn set_extended_unlock(&mut self, tagged: &[u32]) {
+        let mut guard = EXTENDED_UNLOCK.lock().await;
+        if guard.as_slice() != tagged {
+            log::info!("sync: {} credential(s) tagged for extended unlock", tagged.len());
+            guard.clear();
+            // Cannot fail: the parser caps the list at the same length.
+            let _ = guard.extend_from_slice(tagged);
         }
     }
 
-    fn len(&self) -> usize {
-        if self.head >= self.tail {
-            self.head - self.tail
-        } else {
-            MAX_EVENTS - self.tail + self.head
+    async fn accept_list(&mut self, new: &[u32]) -> bool {
+        let current = self.fobs.lock().await.len();
+        let suspect = self.list_rules.suspect(current, new.len());
+        let accepted = LIST_GUARD
+            .lock()
+            .await
+            .accept(&self.list_rules, current, new);
+        match (suspect, accepted) {
+            (None, _) => {}
+            (Some(_), true) => log::warn!(
+                "sync: applying a list of {} fobs in place of {}",
+                new.len(),
+                current
+            ),
+            (Some(Suspect::Empty), false) if self.list_rules.empty == EmptyListPolicy::Keep => {
+                log::error!(
+                    "sync: Conway sent an empty fob list; keeping {} fobs (CONWAY_EMPTY_LIST=keep)",
+                    current
+                )
+            }
+            (Some(_), false) => log::error!(
+                "sync: Conway sent {} fobs in place of {}; keeping the old list until a second sync confirms it",
+                new.len(),
+                current
+            ),
         }
+        accepted
+    }
+
+    async fn acked(&mut self, batch: &Batch) {
+        EVENT_BUFFER.commit(batch.events.len(), batch.first_seq).await;
+        BATCH_KEYS.lock().await.acked();
     }
 
-    fn is_full(&self) -> bool {
-        (self.head + 1) % MAX_EVENTS == self.tail
+    async fn record_response(&mut self, host: [u8; 4], raw: &[u8]) {
+        let at_ms = BootClock.now_ms();
+        LAST_RESPONSE.lock().await.record(host, at_ms, raw);
     }
 }
 
-/// Thread-safe event buffer with peek/commit semantics.
+/// Generation bookkeeping for the RAM fob cache and its flash copy in
+/// [`cache_store`]. `persisted` is `None` until something has been
+/// loaded from or written to flash.
+pub struct CacheState {
+    pub live: Option<CacheMeta>,
+    pub persisted: Option<CacheMeta>,
+}
+
+pub static CACHE_STATE: Mutex<CriticalSectionRawMutex, CacheState> = Mutex::new(CacheState {
+    live: None,
+    persisted: None,
+});
+
+/// Compare the RAM cache against flash and repair whichever side is
+/// behind. Called after every sync attempt, so a flash write that failed
+/// last time is retried instead of leaving the two copies diverged.
+pub async fn persist_cache(
+    fobs: &'static Mutex<CriticalSectionRawMutex, heapless::Vec<u32, MAX_FOBS>>,
+    etag: &'static Mutex<CriticalSectionRawMutex, HostEtag>,
+) {
+    let (live, persisted) = {
+        let st = CACHE_STATE.lock().await;
+        (st.live, st.persisted)
+    };
+    match fob_cache::reconcile(live, persisted) {
+        Reconcile::Empty | Reconcile::InSync => {}
+        Reconcile::PreferLive => {
+            let list = fobs.lock().await.clone();
+            let tag = etag.lock().await.clone();
+            match crate::with_watchdog("fob cache save", |_| cache_store::save(&tag, &list)) {
+                Ok(seq) => {
+                    let meta = CacheMeta::of(seq, &list);
+                    let mut st = CACHE_STATE.lock().await;
+                    st.live = Some(meta);
+                    st.persisted = Some(meta);
+                }
+                Err(e) => log::warn!("sync: fob cache not persisted, will retry: {}", e),
+            }
+        }
+        Reconcile::PreferFlash => {
+            // Only reachable if RAM was replaced behind our back with an
+            // older list; flash has the higher seq, so it wins.
+            let Some(cached) = cache_store::load() else {
+                return;
+            };
+            log::warn!(
+                "sync: fob cache mismatch (ram seq={:?}, flash seq={}), restoring {} fobs from flash",
+                live.map(|m| m.seq),
+                cached.seq,
+                cached.fobs.len()
+            );
+            let meta = cached.meta();
+            *fobs.lock().await = cached.fobs;
+            *etag.lock().await = cached.etag;
+            let mut st = CACHE_STATE.lock().await;
+            st.live = Some(meta);
+            st.persisted = Some(meta);
+        }
+    }
+}
+
+/// Re-export so existing `use crate::sync::AccessEvent` call sites keep
+/// compiling. The struct and the ring behind [`EventBuffer`] live in the
+/// pure `events` module so host tests exercise the same code.
+pub use access_controller::events::{AccessEvent, MAX_EVENTS};
+
+/// Thread-safe event buffer with peek/commit semantics; see [`EventRing`].
 pub struct EventBuffer {
-    inner: Mutex<CriticalSectionRawMutex, EventBufferInner>,
+    inner: Mutex<CriticalSectionRawMutex, EventRing>,
+    dedup: Mutex<CriticalSectionRawMutex, Dedup>,
 }
 
 impl EventBuffer {
     pub const fn new() -> Self {
         Self {
-            inner: Mutex::new(EventBufferInner::new()),
+            inner: Mutex::new(EventRing::new()),
+            dedup: Mutex::new(Dedup::new(0)),
         }
     }
 
-    /// Push an event to the buffer.
-    /// If the buffer is full, the oldest event is discarded.
+    /// Push an event to the buffer, and record it in [`AUDIT`].
+    /// If the buffer is full, the oldest event is discarded. A repeat
+    /// (see [`set_dedup`](Self::set_dedup)) is only recorded in [`AUDIT`].
     pub async fn push(&self, event: AccessEvent) {
-        let mut guard = self.inner.lock().await;
-
-        // If buffer is full, advance tail to discard oldest event
-        if guard.is_full() {
+        let now = BootClock.now_ms();
+        AUDIT.lock().await.push(event, now);
+        if self.dedup.lock().await.is_repeat(event, now) {
+            log::debug!("events: repeat of fob {}, not reported", event.fob);
+            return;
+        }
+        if self.inner.lock().await.push(event) {
             log::warn!("events: buffer full, dropping oldest event");
-            guard.tail = (guard.tail + 1) % MAX_EVENTS;
         }
+    }
+
+    /// Whether granted events are buffered for Conway; see
+    /// [`EventRing::set_report_grants`]. [`AUDIT`] keeps them either way.
+    pub async fn set_report_grants(&self, on: bool) {
+        self.inner.lock().await.set_report_grants(on);
+    }
 
-        let head = guard.head;
-        guard.events[head] = event;
-        guard.head = (head + 1) % MAX_EVENTS;
+    /// Suppress repeats of the last event; see [`Dedup`].
+    pub async fn set_dedup(&self, dedup: Dedup) {
+        *self.dedup.lock().await = dedup;
     }
 
     /// Peek at pending events without removing them.
-    /// Returns (events, count, tail_snapshot).
-    /// The tail_snapshot should be passed to commit() after successful sync.
-    pub async fn peek(&self, out: &mut [AccessEvent; MAX_EVENTS]) -> (usize, usize) {
-        let guard = self.inner.lock().await;
-        let tail = guard.tail;
-        let head = guard.head;
-
-        let mut count = 0;
-        let mut idx = tail;
-        while idx != head && count < MAX_EVENTS {
-            out[count] = guard.events[idx];
-            count += 1;
-            idx = (idx + 1) % MAX_EVENTS;
-        }
+    /// Returns (count, first_seq); see [`EventRing::peek`].
+    pub async fn peek(&self, out: &mut [AccessEvent; MAX_EVENTS]) -> (usize, u64) {
+        self.inner.lock().await.peek(out)
+    }
 
-        (count, tail)
+    /// Peek at no more than the `max` oldest pending events; see
+    /// [`EventRing::peek_up_to`].
+    pub async fn peek_up_to(
+        &self,
+        out: &mut [AccessEvent; MAX_EVENTS],
+        max: usize,
+    ) -> (usize, u64) {
+        self.inner.lock().await.peek_up_to(out, max)
     }
 
     /// Commit (remove) events from the buffer after successful transmission.
-    /// Takes the tail_snapshot from peek(). If tail has changed (buffer overflow
-    /// occurred during sync), this adjusts accordingly.
-    pub async fn commit(&self, count: usize, expected_tail: usize) {
-        let mut guard = self.inner.lock().await;
-
-        // Calculate where tail should be after committing
-        let new_tail = (expected_tail + count) % MAX_EVENTS;
-
-        // Only update if tail hasn't been modified by overflow handling
-        if guard.tail == expected_tail {
-            guard.tail = new_tail;
-            log::debug!("events: committed {} events", count);
-        } else {
-            // Tail was moved by overflow - only advance if we would move it forward
-            // Calculate the distance from current tail to new_tail in circular space
-            let distance_forward = if new_tail >= guard.tail {
-                new_tail - guard.tail
-            } else {
-                MAX_EVENTS - guard.tail + new_tail
-            };
-
-            // If new_tail is ahead of current tail in circular space, advance to it
-            // Otherwise, overflow already moved tail past where we would commit to
-            if distance_forward < MAX_EVENTS / 2 {
-                // new_tail is ahead - advance tail
-                guard.tail = new_tail;
-                log::debug!(
-                    "events: committed {} events (adjusted after overflow moved tail from {} to {})",
-                    count,
-                    expected_tail,
-                    new_tail
-                );
-            } else {
-                // new_tail is behind or equal - overflow already discarded our events
-                log::debug!(
-                    "events: peeked events already removed by overflow (tail moved from {} to {}, would commit to {})",
-                    expected_tail,
-                    guard.tail,
-                    new_tail
-                );
+    /// Takes the first_seq from peek(). Events that buffer overflow already
+    /// dropped during the sync are skipped; later events are kept.
+    pub async fn commit(&self, count: usize, first_seq: u64) {
+        match self.inner.lock().await.commit(count, first_seq) {
+            Commit::Exact => log::debug!("events: committed {} events", count),
+            Commit::Adjusted { removed } => log::debug!(
+                "events: committed {} of {} events (the rest were dropped by overflow)",
+                removed,
+                count
+            ),
+            Commit::AlreadyDropped => {
+                log::debug!("events: peeked events already removed by overflow")
             }
         }
     }
 
     /// Get current event count (for status display).
     pub async fn len(&self) -> usize {
-        let guard = self.inner.lock().await;
-        guard.len()
+        self.inner.lock().await.len()
+    }
+
+    /// See [`EventRing::resume_at`].
+    pub async fn resume_at(&self, seq: u64) -> bool {
+        self.inner.lock().await.resume_at(seq)
+    }
+
+    /// See [`EventRing::next_seq`].
+    pub async fn next_seq(&self) -> u64 {
+        self.inner.lock().await.next_seq()
     }
 }
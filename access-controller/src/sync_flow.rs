@@ -0,0 +1,837 @@
+//! One sync round-trip against one Conway host.
+//!
+//! The exchange is generic over two seams so host tests can run it end
+//! to end:
+//!
+//! - [`Transport`]: the byte stream. The firmware's is an embassy TCP
+//!   socket; a test's replays a canned response.
+//! - [`SyncContext`]: the state a sync reads and commits (pending events,
+//!   batch keys, ETag, fob list, credential-format hint, extended-unlock
+//!   tags, the `/diag/lastsync` capture). The firmware's locks its shared
+//!   mutexes; a test's holds plain fields.
+//!
+//! Everything in between is here: building the request, the response
+//! size limits, status handling, nonce and signature checks, body
+//! parsing, the guard against suspect lists, and the rule that events are
+//! acknowledged only once the server answered 200 (with a list that
+//! parsed and verified) or 304.
+//!
+//! Failover across hosts and the redirect loop stay in the firmware's
+//! `sync` module; [`sync_with_host`] reports a redirect and leaves
+//! following it to the caller.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write as _;
+
+use heapless::{String as HString, Vec as HVec};
+
+use crate::body_mac;
+use crate::decode::{CredentialFormat, FobFormat};
+use crate::door::{self, MAX_EXTENDED_UNLOCK};
+use crate::etag::HostEtag;
+use crate::events::{AccessEvent, MAX_EVENTS};
+use crate::fob_cache::{self, MAX_FOBS};
+use crate::http_client::{self, RedirectPolicy, SyncTarget, MAX_RESPONSE_BYTES};
+use crate::idempotency::Key;
+use crate::signing;
+use crate::sync_nonce;
+use crate::wire::{self, EventEncoding, EventFields, EventFormat, SyncProtocol};
+
+/// A connection to a Conway host.
+///
+/// The firmware runs on a single-threaded executor, so the futures need
+/// not be `Send`.
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    async fn connect(&mut self, host: [u8; 4], port: u16) -> Result<(), &'static str>;
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), &'static str>;
+    /// Read into `buf`; `Ok(0)` once the peer has closed.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, &'static str>;
+    /// Drop the connection. Called once per attempt, on every path.
+    fn close(&mut self);
+}
+
+/// Events frozen for one round-trip: `events` are the first pending
+/// events, starting at absolute sequence number `first_seq`, sent under
+/// idempotency key `key`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Batch {
+    pub events: HVec<AccessEvent, MAX_EVENTS>,
+    pub first_seq: u64,
+    pub key: Option<Key>,
+}
+
+/// State a sync reads and updates.
+#[allow(async_fn_in_trait)]
+pub trait SyncContext {
+    /// Pick the events to send (see `idempotency::BatchKeys::batch`).
+    async fn batch(&mut self) -> Batch;
+    /// The cached ETag; only sent back to the host that issued it.
+    async fn etag(&mut self) -> HostEtag;
+    /// [`crate::fob_cache::sorted_digest`] of the cached list, sent as
+    /// `X-Fob-CRC`.
+    async fn fob_crc(&mut self) -> u32;
+    /// The server answered with a new list, already verified and
+    /// parsed. `etag` is the response's `ETag`, if any.
+    async fn replace_list(&mut self, host: [u8; 4], fobs: &[u32], etag: Option<&str>);
+    /// The credential format the host hinted; `None` when a new list
+    /// came without a hint. Not called for a 304 without one, which
+    /// keeps the current hint.
+    async fn set_credential_format(&mut self, format: Option<CredentialFormat>);
+    /// The credentials the host tagged for an extended unlock; empty when
+    /// a new list came without tags. Like the hint, not called for a 304
+    /// without the header.
+    async fn set_extended_unlock(&mut self, tagged: &[u32]);
+    /// The event encoding the host's `Accept-Post` says it takes. Only
+    /// called when a 200 or 304 carries the header.
+    async fn set_event_encoding(&mut self, encoding: EventEncoding);
+    /// A verified 200 carries `fobs`: whether to apply them (see
+    /// [`ListGuard`]). The batch is acknowledged either way.
+    async fn accept_list(&mut self, fobs: &[u32]) -> bool;
+    /// The server took `batch` (200 or 304): remove its events.
+    async fn acked(&mut self, batch: &Batch);
+    /// Everything received from `host`, complete or not.
+    async fn record_response(&mut self, host: [u8; 4], raw: &[u8]);
+}
+
+/// Per-sync settings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncConfig<'a> {
+    pub protocol: SyncProtocol,
+    pub redirects: RedirectPolicy,
+    /// When set, a 200 must carry an `X-Fob-Signature` over the body
+    /// that verifies under this key.
+    pub trusted_pubkey: Option<&'a [u8; 32]>,
+    /// How string fob IDs in a JSON list are read.
+    pub fob_format: FobFormat,
+    /// When set, a 200 must carry an `X-Fob-HMAC` over the body under
+    /// this shared secret (see [`body_mac`]).
+    pub hmac_key: Option<&'a [u8]>,
+    /// When set, sent as `X-Sync-Nonce`; a 200 or 304 must echo it, and
+    /// a 200's signature and HMAC cover it (see [`sync_nonce`]).
+    pub nonce: Option<&'a str>,
+    /// Field names in the event objects sent (see [`EventFields`]).
+    pub event_fields: EventFields<'a>,
+    /// How the events are written (see [`EventEncoding`]).
+    pub event_encoding: EventEncoding,
+}
+
+impl<'a> SyncConfig<'a> {
+    pub fn event_format(&self) -> EventFormat<'a> {
+        EventFormat {
+            encoding: self.event_encoding,
+            fields: self.event_fields,
+        }
+    }
+}
+
+/// How a round-trip ended, when the host answered usefully.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// 304: the cached list is current; events acknowledged.
+    NotModified,
+    /// 200: the list was replaced with `fobs` entries; events
+    /// acknowledged.
+    Updated { fobs: usize },
+    /// A redirect, already vetted against the policy. Nothing was
+    /// committed; the caller retries at the target with `hops + 1`.
+    Redirect(SyncTarget),
+    /// 200 with a list that [`SyncContext::accept_list`] turned down;
+    /// events acknowledged, the cached list and ETag kept.
+    ListHeld,
+}
+
+/// What to do when a sync would replace a non-empty fob list with an
+/// empty one, which locks out everyone but local fobs. A misconfigured
+/// server is a likelier cause than a site revoking every member.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyListPolicy {
+    /// Take it like any other list. Original behavior.
+    Apply,
+    /// Take it once the next sync returns it again.
+    #[default]
+    Confirm,
+    /// Never take it; keep the prior list.
+    Keep,
+}
+
+impl EmptyListPolicy {
+    /// Parse the `CONWAY_EMPTY_LIST` build knob: `"apply"`, `"confirm"`
+    /// or `"keep"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "apply" => Some(Self::Apply),
+            "confirm" => Some(Self::Confirm),
+            "keep" => Some(Self::Keep),
+            _ => None,
+        }
+    }
+}
+
+/// A drop of fewer fobs than this is never suspicious, however small the
+/// list, so a small site can still remove a few members at once.
+pub const SHRINK_MIN_DROP: usize = 10;
+
+/// Parse the `CONWAY_MAX_LIST_SHRINK_PCT` build knob: the largest share
+/// of the cached list, 1 to 99 percent, one sync may remove unconfirmed.
+pub fn parse_shrink_pct(s: &str) -> Option<u8> {
+    s.trim().parse().ok().filter(|pct| (1..=99).contains(pct))
+}
+
+/// Why a new list looks wrong.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Suspect {
+    /// Empty, replacing a non-empty list.
+    Empty,
+    /// Shorter than the cached list by more than the shrink limit.
+    Shrunk,
+}
+
+/// Which new lists [`ListGuard`] holds back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListRules {
+    pub empty: EmptyListPolicy,
+    /// Most of the cached list, in percent, one sync may remove before
+    /// the new list needs confirming; `None` for no limit.
+    pub max_shrink_pct: Option<u8>,
+}
+
+impl ListRules {
+    /// Whether a new list of `new` fobs replacing `current` looks wrong.
+    /// A controller with nothing cached takes any list.
+    pub fn suspect(&self, current: usize, new: usize) -> Option<Suspect> {
+        if current == 0 {
+            return None;
+        }
+        if new == 0 {
+            return Some(Suspect::Empty);
+        }
+        let dropped = current.saturating_sub(new);
+        let pct = usize::from(self.max_shrink_pct?);
+        (dropped >= SHRINK_MIN_DROP && dropped * 100 > current * pct).then_some(Suspect::Shrunk)
+    }
+}
+
+/// Remembers a suspect list held for confirmation across syncs.
+///
+/// A held list keeps the old ETag, so the next sync fetches the list in
+/// full again instead of getting a 304. It confirms the held one if it is
+/// the same list ([`fob_cache::sorted_digest`]); a truncated download is
+/// unlikely to come out the same twice.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListGuard {
+    held: Option<u32>,
+}
+
+impl ListGuard {
+    pub const fn new() -> Self {
+        Self { held: None }
+    }
+
+    /// Whether a list is held waiting for confirmation.
+    pub fn holding(&self) -> bool {
+        self.held.is_some()
+    }
+
+    /// A 200 carried `new` while `current` fobs are cached: whether to
+    /// apply it under `rules`. A list that isn't suspect clears any held
+    /// one.
+    pub fn accept(&mut self, rules: &ListRules, current: usize, new: &[u32]) -> bool {
+        match rules.suspect(current, new.len()) {
+            None => {
+                self.held = None;
+                true
+            }
+            Some(Suspect::Empty) if rules.empty == EmptyListPolicy::Apply => true,
+            Some(Suspect::Empty) if rules.empty == EmptyListPolicy::Keep => false,
+            Some(_) => {
+                let digest = fob_cache::sorted_digest(new);
+                if self.held == Some(digest) {
+                    self.held = None;
+                    true
+                } else {
+                    self.held = Some(digest);
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// A round-trip that went wrong; nothing was committed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub error: &'static str,
+    /// The connection itself failed (connect, write or read) rather than
+    /// the host answering badly. Worth an immediate retry on a fresh
+    /// socket: a resent batch keeps its idempotency key.
+    pub transient: bool,
+}
+
+impl Failure {
+    const fn fatal(error: &'static str) -> Self {
+        Self {
+            error,
+            transient: false,
+        }
+    }
+}
+
+/// Most immediate retries [`parse_retries`] accepts. Each costs up to a
+/// socket timeout, and the access task waits on the sync.
+pub const MAX_RETRIES: u8 = 3;
+
+/// Immediate retries per host when `CONWAY_SYNC_RETRIES` is unset.
+pub const DEFAULT_RETRIES: u8 = 1;
+
+/// Parse `CONWAY_SYNC_RETRIES`: `0` (fail over at the first socket error)
+/// up to [`MAX_RETRIES`].
+pub fn parse_retries(s: &str) -> Option<u8> {
+    s.trim().parse().ok().filter(|&n| n <= MAX_RETRIES)
+}
+
+/// The immediate retries left for one host this sync. Separate from the
+/// backoff between syncs: this only covers a socket error mid-exchange.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retries {
+    left: u8,
+}
+
+impl Retries {
+    pub const fn new(max: u8) -> Self {
+        Self { left: max }
+    }
+
+    /// Whether to try again after `failure`, using up a retry if so.
+    /// Only transient failures are retried.
+    pub fn retry(&mut self, failure: &Failure) -> bool {
+        if !failure.transient || self.left == 0 {
+            return false;
+        }
+        self.left -= 1;
+        true
+    }
+}
+
+/// One request/response round-trip against `target`, `hops` redirects
+/// into this sync. `Err` means the host should be considered
+/// unavailable this cycle; nothing was committed.
+pub async fn sync_with_host<T: Transport, C: SyncContext>(
+    transport: &mut T,
+    ctx: &mut C,
+    target: &SyncTarget,
+    hops: u8,
+    cfg: &SyncConfig<'_>,
+) -> Result<Outcome, &'static str> {
+    try_sync_with_host(transport, ctx, target, hops, cfg)
+        .await
+        .map_err(|f| f.error)
+}
+
+/// [`sync_with_host`], saying whether a failure is worth retrying.
+pub async fn try_sync_with_host<T: Transport, C: SyncContext>(
+    transport: &mut T,
+    ctx: &mut C,
+    target: &SyncTarget,
+    hops: u8,
+    cfg: &SyncConfig<'_>,
+) -> Result<Outcome, Failure> {
+    let batch = ctx.batch().await;
+    let etag = ctx.etag().await;
+    let fob_crc = ctx.fob_crc().await;
+    let request = match build_request(
+        target,
+        cfg.protocol,
+        &batch,
+        etag.for_host(target.host),
+        Some(fob_crc),
+        cfg.nonce,
+        &cfg.event_format(),
+    ) {
+        Ok(request) => request,
+        Err(e) => {
+            transport.close();
+            return Err(Failure::fatal(e));
+        }
+    };
+    let streamed: &[AccessEvent] = match cfg.protocol {
+        SyncProtocol::Ndjson => &batch.events,
+        _ => &[],
+    };
+    let streamed_len = wire::ndjson_len(streamed, batch.first_seq, &cfg.event_format());
+    if let Err(e) = http_client::check_request_length(&request, streamed_len) {
+        transport.close();
+        return Err(Failure::fatal(e));
+    }
+
+    // One byte of slack so the buffer can never fill up without a size
+    // limit having tripped first.
+    let mut response = vec![0u8; MAX_RESPONSE_BYTES + 1];
+    let result = exchange(
+        transport,
+        target,
+        &request,
+        streamed,
+        batch.first_seq,
+        &cfg.event_format(),
+        &mut response,
+    )
+    .await;
+    transport.close();
+    let received = match result {
+        Ok(n) => n,
+        Err((failure, n)) => {
+            if n > 0 {
+                ctx.record_response(target.host, &response[..n]).await;
+            }
+            return Err(failure);
+        }
+    };
+    ctx.record_response(target.host, &response[..received])
+        .await;
+
+    match parse_response(&mut response[..received], target, hops, cfg).map_err(Failure::fatal)? {
+        Response::NotModified {
+            credential_format,
+            extended_unlock,
+            event_encoding,
+        } => {
+            if let Some(encoding) = event_encoding {
+                ctx.set_event_encoding(encoding).await;
+            }
+            if credential_format.is_some() {
+                ctx.set_credential_format(credential_format).await;
+            }
+            if let Some(tagged) = extended_unlock {
+                ctx.set_extended_unlock(&tagged).await;
+            }
+            ctx.acked(&batch).await;
+            Ok(Outcome::NotModified)
+        }
+        Response::Updated {
+            fobs,
+            etag,
+            credential_format,
+            extended_unlock,
+            event_encoding,
+        } => {
+            // Acknowledged events were read, so the host takes what it
+            // announces even if its list is held back.
+            if let Some(encoding) = event_encoding {
+                ctx.set_event_encoding(encoding).await;
+            }
+            // Keeping the old ETag too means the next sync gets the list
+            // again rather than a 304, so it can confirm it.
+            if !ctx.accept_list(&fobs).await {
+                ctx.acked(&batch).await;
+                return Ok(Outcome::ListHeld);
+            }
+            ctx.replace_list(target.host, &fobs, etag).await;
+            ctx.set_credential_format(credential_format).await;
+            ctx.set_extended_unlock(extended_unlock.as_deref().unwrap_or(&[]))
+                .await;
+            ctx.acked(&batch).await;
+            Ok(Outcome::Updated { fobs: fobs.len() })
+        }
+        // Don't commit events: the server that answered didn't process
+        // them; they go along with the retry.
+        Response::Redirect(next) => Ok(Outcome::Redirect(next)),
+    }
+}
+
+/// Longest JSON event body: a full batch of the longest objects,
+/// `{"fob":4294967295,"allowed":false,"seq":18446744073709551615}` with
+/// every field name [`wire::MAX_FIELD_NAME`] long, comma-separated in
+/// brackets.
+pub const EVENTS_JSON_MAX: usize = 2 + MAX_EVENTS * (wire::NDJSON_LINE_MAX - 1) + MAX_EVENTS - 1;
+
+/// The sync `POST`: head and body. For [`SyncProtocol::Ndjson`] only the
+/// head; the body is streamed from the batch a line at a time.
+///
+/// `Err` if the request doesn't fit its buffers. It is never sent cut
+/// short, since a 200 to a truncated body would commit events the server
+/// never saw.
+pub fn build_request(
+    target: &SyncTarget,
+    protocol: SyncProtocol,
+    batch: &Batch,
+    if_none_match: Option<&str>,
+    fob_crc: Option<u32>,
+    nonce: Option<&str>,
+    format: &EventFormat<'_>,
+) -> Result<Vec<u8>, &'static str> {
+    let body: Vec<u8> = match protocol {
+        SyncProtocol::Json => {
+            let mut json: HString<EVENTS_JSON_MAX> = HString::new();
+            events_json(&mut json, &batch.events, batch.first_seq, format)
+                .map_err(|_| "event body too large")?;
+            json.as_bytes().into()
+        }
+        SyncProtocol::Binary => wire::encode_events(&batch.events, batch.first_seq),
+        SyncProtocol::Ndjson => Vec::new(),
+    };
+    let body_len = match protocol {
+        SyncProtocol::Ndjson => wire::ndjson_len(&batch.events, batch.first_seq, format),
+        _ => body.len(),
+    };
+
+    let h = target.host;
+    let mut host: HString<24> = HString::new();
+    let _ = write!(host, "{}.{}.{}.{}", h[0], h[1], h[2], h[3]);
+    let mut head: HString<512> = HString::new();
+    http_client::write_sync_request_head(
+        &mut head,
+        target.path.as_str(),
+        host.as_str(),
+        format.encoding.content_type(protocol),
+        body_len,
+        if_none_match,
+        batch.key.as_ref().map(|k| k.as_str()),
+        fob_crc,
+        nonce,
+    )
+    .map_err(|_| "request head too large")?;
+
+    let mut request = Vec::with_capacity(head.len() + body.len());
+    request.extend_from_slice(head.as_bytes());
+    request.extend_from_slice(&body);
+    Ok(request)
+}
+
+/// `events` as a JSON array of `{"fob":N,"allowed":B,"seq":N}` objects
+/// (or `[N,B,N]` arrays), numbered from `first_seq`, in `format`.
+fn events_json<W: core::fmt::Write>(
+    out: &mut W,
+    events: &[AccessEvent],
+    first_seq: u64,
+    format: &EventFormat<'_>,
+) -> core::fmt::Result {
+    out.write_str("[")?;
+    for (seq, e) in wire::numbered(events, first_seq) {
+        if seq > first_seq {
+            out.write_str(",")?;
+        }
+        wire::write_event(out, e, seq, format)?;
+    }
+    out.write_str("]")
+}
+
+/// Send `request`, then `streamed` as NDJSON lines numbered from
+/// `first_seq` and written in `format`, and read the
+/// response into `buf` until the peer closes, checking the size limits
+/// after every read. On error, returns how many bytes had been read.
+/// Socket errors are transient; a response over the limits is not.
+async fn exchange<T: Transport>(
+    transport: &mut T,
+    target: &SyncTarget,
+    request: &[u8],
+    streamed: &[AccessEvent],
+    first_seq: u64,
+    format: &EventFormat<'_>,
+    buf: &mut [u8],
+) -> Result<usize, (Failure, usize)> {
+    let socket = |error| {
+        (
+            Failure {
+                error,
+                transient: true,
+            },
+            0,
+        )
+    };
+    transport
+        .connect(target.host, target.port)
+        .await
+        .map_err(socket)?;
+    transport.write_all(request).await.map_err(socket)?;
+    for (seq, event) in wire::numbered(streamed, first_seq) {
+        let line = wire::ndjson_line(event, seq, format);
+        transport
+            .write_all(line.as_bytes())
+            .await
+            .map_err(socket)?;
+    }
+    let mut total = 0;
+    loop {
+        match transport.read(&mut buf[total..]).await {
+            Ok(0) => return Ok(total),
+            Ok(n) => {
+                total += n;
+                http_client::check_response_size(&buf[..total])
+                    .map_err(|e| (Failure::fatal(e), total))?;
+            }
+            Err(error) => {
+                let failure = Failure {
+                    error,
+                    transient: true,
+                };
+                return Err((failure, total));
+            }
+        }
+    }
+}
+
+/// A response that was understood. Lives only until it is applied.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Response<'a> {
+    /// The cached list is current, and the credential-format hint,
+    /// extended-unlock tags and event encoding if the response carried
+    /// them.
+    NotModified {
+        credential_format: Option<CredentialFormat>,
+        extended_unlock: Option<HVec<u32, MAX_EXTENDED_UNLOCK>>,
+        event_encoding: Option<EventEncoding>,
+    },
+    /// A verified, parsed list, the response's `ETag`, its
+    /// credential-format hint, its extended-unlock tags and its event
+    /// encoding, if any.
+    Updated {
+        fobs: HVec<u32, MAX_FOBS>,
+        etag: Option<&'a str>,
+        credential_format: Option<CredentialFormat>,
+        extended_unlock: Option<HVec<u32, MAX_EXTENDED_UNLOCK>>,
+        event_encoding: Option<EventEncoding>,
+    },
+    /// A redirect, already vetted against `cfg.redirects`.
+    Redirect(SyncTarget),
+}
+
+/// Interpret a complete raw response from `target`, `hops` redirects
+/// into this sync. Pure: nothing is committed, so this is also the entry
+/// point for fuzzing (see [`crate::fuzz`]).
+///
+/// `raw` is taken mutably only so [`http_client::split_head`] can mask
+/// non-ASCII header bytes in place.
+pub fn parse_response<'a>(
+    raw: &'a mut [u8],
+    target: &SyncTarget,
+    hops: u8,
+    cfg: &SyncConfig<'_>,
+) -> Result<Response<'a>, &'static str> {
+    // Only the header block has to be text; the body may be binary.
+    let (head, body) = http_client::split_head(raw).ok_or("malformed response headers")?;
+
+    // An unknown value counts as no hint: both forms are tried.
+    let credential_format = http_client::extract_header(head, "x-credential-format")
+        .and_then(CredentialFormat::parse);
+    // Likewise a malformed tag list counts as none.
+    let extended_unlock = http_client::extract_header(head, "x-extended-unlock")
+        .and_then(door::parse_extended_unlock);
+    let event_encoding =
+        EventEncoding::from_accept_post(http_client::extract_header(head, "accept-post"));
+
+    let status = http_client::parse_status_code(head);
+    // A replayed 304 would acknowledge events the server never saw, so
+    // it has to echo the nonce as much as a 200 does.
+    if let (Some(nonce), 200 | 304) = (cfg.nonce, status) {
+        sync_nonce::check(nonce, http_client::extract_header(head, sync_nonce::HEADER))?;
+    }
+
+    match status {
+        304 => Ok(Response::NotModified {
+            credential_format,
+            extended_unlock,
+            event_encoding,
+        }),
+        200 => {
+            let chunked = http_client::extract_header(head, "transfer-encoding")
+                .is_some_and(http_client::is_chunked);
+            let decoded;
+            let body = if chunked {
+                decoded = http_client::dechunk(body)?;
+                &decoded[..]
+            } else {
+                body
+            };
+
+            // Signature and HMAC gates: must come before anything is
+            // replaced or committed. A failed verify is treated like an
+            // unparseable body; events stay buffered for the legitimate
+            // server.
+            let bound;
+            let signed = match cfg.nonce {
+                Some(nonce) if cfg.trusted_pubkey.is_some() || cfg.hmac_key.is_some() => {
+                    bound = sync_nonce::bind(nonce, body);
+                    &bound[..]
+                }
+                _ => body,
+            };
+            if let Some(pk) = cfg.trusted_pubkey {
+                let sig = http_client::extract_header(head, "x-fob-signature")
+                    .ok_or("missing signature")?;
+                if !signing::verify(pk, signed, sig) {
+                    return Err("bad signature");
+                }
+            }
+            if let Some(key) = cfg.hmac_key {
+                let mac = http_client::extract_header(head, body_mac::HEADER).ok_or("missing HMAC")?;
+                if !body_mac::verify(key, signed, mac) {
+                    return Err("bad HMAC");
+                }
+            }
+
+            // Parse the fob list in whichever encoding the server chose.
+            let content_type = http_client::extract_header(head, "content-type");
+            let fobs = match SyncProtocol::from_content_type(content_type) {
+                SyncProtocol::Json | SyncProtocol::Ndjson => core::str::from_utf8(body)
+                    .map_err(|_| "invalid response encoding")
+                    .and_then(|json| parse_fob_list_as(json, cfg.fob_format))?,
+                SyncProtocol::Binary => wire::decode_fob_list::<MAX_FOBS>(body)?,
+            };
+            Ok(Response::Updated {
+                fobs,
+                etag: http_client::extract_header(head, "etag"),
+                credential_format,
+                extended_unlock,
+                event_encoding,
+            })
+        }
+        code if http_client::is_redirect(code) => {
+            let location =
+                http_client::extract_header(head, "location").ok_or("redirect without Location")?;
+            cfg.redirects
+                .follow(hops, target, location)
+                .map(Response::Redirect)
+        }
+        _ => Err("unexpected status"),
+    }
+}
+
+/// Parse a JSON fob list. Elements may be bare `u32`s (the usual form,
+/// parsed directly), numeric strings (`"123"`), or objects carrying the
+/// number as `id` (`{"id":123,"type":"fob"}`); other object fields are
+/// ignored.
+pub fn parse_fob_list(json: &str) -> Result<HVec<u32, MAX_FOBS>, &'static str> {
+    parse_fob_list_as(json, FobFormat::Decimal)
+}
+
+/// [`parse_fob_list`], reading string IDs in `format`.
+pub fn parse_fob_list_as(
+    json: &str,
+    format: FobFormat,
+) -> Result<HVec<u32, MAX_FOBS>, &'static str> {
+    let trimmed = json.trim();
+    if !trimmed.starts_with('[') || !trimmed.ends_with(']') {
+        return Err("not a JSON array");
+    }
+
+    let inner = &trimmed[1..trimmed.len() - 1];
+    let mut fobs = HVec::new();
+
+    for part in split_top_level(inner, b',') {
+        let part = part.trim();
+        if part.is_empty() {
+            // Tolerate `[]` and a single trailing comma so the cache
+            // doesn't get nuked by a stylistic server change. Embedded
+            // empties (e.g. `1,,2`) still parse as empty and are skipped.
+            continue;
+        }
+        // Strict: any non-empty element that does NOT yield a u32 is a
+        // hard error. Previously this silently dropped the element, so a
+        // pretty-printed body or any schema evolution yielded an empty
+        // list that was then committed as the live cache -> mass lockout
+        // with no signal.
+        let fob = if part.starts_with('{') {
+            fob_object_id(part, format)?
+        } else {
+            fob_number(part, format).ok_or("fob list element is not a u32")?
+        };
+        if fobs.push(fob).is_err() {
+            return Err("fob list exceeds MAX_FOBS");
+        }
+    }
+
+    Ok(fobs)
+}
+
+/// A bare `u32`, or a string ID in `format`.
+fn fob_number(value: &str, format: FobFormat) -> Option<u32> {
+    match value.strip_prefix('"') {
+        Some(quoted) => format.read(quoted.strip_suffix('"')?),
+        None => value.parse().ok(),
+    }
+}
+
+/// The `id` of a fob object. Every other member is skipped unread, but
+/// must still be a `"key": value` pair.
+fn fob_object_id(object: &str, format: FobFormat) -> Result<u32, &'static str> {
+    let members = object
+        .strip_prefix('{')
+        .and_then(|o| o.strip_suffix('}'))
+        .ok_or("malformed fob object")?;
+    let mut id = None;
+    for member in split_top_level(members, b',') {
+        if member.trim().is_empty() {
+            continue;
+        }
+        let (key, value) = split_top_level(member, b':')
+            .collect_pair()
+            .ok_or("malformed fob object")?;
+        let key = key.trim();
+        if key.len() < 2 || !key.starts_with('"') || !key.ends_with('"') {
+            return Err("malformed fob object");
+        }
+        if key == "\"id\"" {
+            if id.is_some() {
+                return Err("fob object has two ids");
+            }
+            id = Some(fob_number(value.trim(), format).ok_or("fob object id is not a u32")?);
+        }
+    }
+    id.ok_or("fob object has no id")
+}
+
+/// Split `s` at each `sep` outside strings, objects and arrays.
+fn split_top_level(s: &str, sep: u8) -> TopLevel<'_> {
+    TopLevel { rest: Some(s), sep }
+}
+
+struct TopLevel<'a> {
+    rest: Option<&'a str>,
+    sep: u8,
+}
+
+impl<'a> TopLevel<'a> {
+    /// Exactly two pieces: a `"key": value` split at its first colon.
+    fn collect_pair(mut self) -> Option<(&'a str, &'a str)> {
+        let first = self.next()?;
+        let second = self.rest.take()?;
+        Some((first, second))
+    }
+}
+
+impl<'a> Iterator for TopLevel<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let s = self.rest?;
+        let mut depth = 0i32;
+        let mut in_string = false;
+        let mut escaped = false;
+        for (i, b) in s.bytes().enumerate() {
+            if in_string {
+                match b {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match b {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => depth -= 1,
+                _ if b == self.sep && depth == 0 => {
+                    self.rest = Some(&s[i + 1..]);
+                    return Some(&s[..i]);
+                }
+                _ => {}
+            }
+        }
+        self.rest = None;
+        Some(s)
+    }
+}
@@ -0,0 +1,97 @@
+//! At most one sync at a time.
+//!
+//! A sync freezes the pending events as its batch and commits them,
+//! the fob list and the ETag when the server answers. Two running at
+//! once would send the same batch twice and race to replace the list.
+//! `sync_task` is the only caller today and runs syncs back to back; the
+//! guard keeps it that way if another trigger ever calls in directly. An
+//! entry that finds a sync running is coalesced into it: it returns at
+//! once and the running sync's result stands for both.
+//!
+//! [`SyncRuns`] numbers the syncs that do run, so `POST /sync` can wait
+//! for one that started after its request and report how it ended.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// The "sync running" flag.
+#[derive(Debug, Default)]
+pub struct SyncGuard {
+    running: AtomicBool,
+}
+
+impl SyncGuard {
+    pub const fn new() -> Self {
+        Self {
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Start a sync, or `None` if one is already running. The sync runs
+    /// until the returned token is dropped, on whatever path it ends.
+    pub fn try_begin(&self) -> Option<Running<'_>> {
+        self.running
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| Running { guard: self })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+}
+
+/// A sync in progress; dropping it lets the next one start.
+#[derive(Debug)]
+pub struct Running<'a> {
+    guard: &'a SyncGuard,
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.guard.running.store(false, Ordering::Release);
+    }
+}
+
+/// Syncs started and finished since boot, and how the last one ended.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncRuns {
+    started: u32,
+    finished: u32,
+    last_ok: bool,
+}
+
+impl SyncRuns {
+    pub const fn new() -> Self {
+        Self {
+            started: 0,
+            finished: 0,
+            last_ok: false,
+        }
+    }
+
+    /// A sync starts; returns its number, from 1.
+    pub fn start(&mut self) -> u32 {
+        self.started = self.started.saturating_add(1);
+        self.started
+    }
+
+    /// Sync `run` ended; `ok` if a host answered it.
+    pub fn finish(&mut self, run: u32, ok: bool) {
+        self.finished = run;
+        self.last_ok = ok;
+    }
+
+    /// The number the next sync to start will get. A request for a fresh
+    /// sync made now is answered by that one, not by a sync already
+    /// running, which may have fetched the list before the change the
+    /// caller wants.
+    pub fn next(&self) -> u32 {
+        self.started.saturating_add(1)
+    }
+
+    /// How the latest sync ended, once sync `run` or a later one has;
+    /// `None` until then.
+    pub fn outcome(&self, run: u32) -> Option<bool> {
+        (self.finished >= run).then_some(self.last_ok)
+    }
+}
@@ -0,0 +1,56 @@
+//! Per-request nonces for the sync exchange.
+//!
+//! A signature or HMAC proves a fob list came from Conway, but not that it
+//! answers *this* request: someone who recorded an old signed response can
+//! play it back later and restore a list that has since had members
+//! revoked. With nonces on (`CONWAY_SYNC_NONCE=1`), every sync request
+//! carries a fresh random `X-Sync-Nonce`, and the controller only accepts
+//! a 200 or 304 that echoes it in the same header. Redirects need not echo
+//! it; they commit nothing.
+//!
+//! On a 200, the signature and HMAC are then checked over
+//! [`bind`]`(nonce, body)` instead of the bare body, so the echo can't be
+//! pasted onto an old signed response. A 304 has no body to sign, so its
+//! echo only stops a recording made for another request.
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use heapless::String as HString;
+
+use crate::rng::RandomSource;
+
+/// Request and response header carrying the nonce.
+pub const HEADER: &str = "x-sync-nonce";
+
+/// Length of a nonce: 16 lowercase hex digits (64 random bits).
+pub const NONCE_LEN: usize = 16;
+
+pub type Nonce = HString<NONCE_LEN>;
+
+/// A fresh nonce from `rng`.
+pub fn generate<R: RandomSource>(rng: &mut R) -> Nonce {
+    let mut nonce = Nonce::new();
+    let _ = write!(nonce, "{:016x}", rng.next_u64());
+    nonce
+}
+
+/// Check the `X-Sync-Nonce` a response echoed against the one sent.
+/// Surrounding whitespace is ignored; the digits must match exactly.
+pub fn check(sent: &str, echoed: Option<&str>) -> Result<(), &'static str> {
+    match echoed {
+        None => Err("missing nonce"),
+        Some(echoed) if echoed.trim() == sent => Ok(()),
+        Some(_) => Err("stale nonce"),
+    }
+}
+
+/// The bytes a 200 is signed over when a nonce was sent: the nonce, a
+/// newline, then the body (after any chunked decoding).
+pub fn bind(nonce: &str, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(nonce.len() + 1 + body.len());
+    message.extend_from_slice(nonce.as_bytes());
+    message.push(b'\n');
+    message.extend_from_slice(body);
+    message
+}
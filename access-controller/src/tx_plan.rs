@@ -0,0 +1,95 @@
+//! Pulse plans for re-emitting a credential as Wiegand.
+//!
+//! `src/wiegand_tx.rs` (feature `wiegand-tx`) replays every read to a
+//! downstream panel on two output pins. Which pulses to send, and when, is
+//! decided here so frame layout and timing can be checked on the host.
+
+use crate::decode::{encode_26, encode_34_data, WiegandRead};
+
+/// Frame length sent downstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxFormat {
+    /// 26-bit H10301: the low 24 bits of `raw_data`.
+    W26,
+    /// 34-bit: all 32 bits of `raw_data`.
+    W34,
+}
+
+impl TxFormat {
+    /// Parse the `CONWAY_WIEGAND_TX_FORMAT` build knob: `"26"` or `"34"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "26" => Some(Self::W26),
+            "34" => Some(Self::W34),
+            _ => None,
+        }
+    }
+
+    /// The smallest format that carries all of `read`'s data, which is
+    /// the one it arrived in for any 26-bit read.
+    pub fn for_read(read: &WiegandRead) -> Self {
+        if read.raw_data > 0xFF_FFFF {
+            Self::W34
+        } else {
+            Self::W26
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::W26 => 26,
+            Self::W34 => 34,
+        }
+    }
+
+    /// `read` as a frame of [`bits`](Self::bits) bits, MSB first, parity
+    /// included. 26-bit drops anything above the low 24 data bits.
+    pub fn frame(self, read: &WiegandRead) -> u64 {
+        match self {
+            Self::W26 => encode_26(read.raw_data >> 16, read.raw_data & 0xFFFF),
+            Self::W34 => encode_34_data(read.raw_data),
+        }
+    }
+}
+
+/// Pulse width and bit-to-bit spacing. Panels accept pulses of 20-100 µs
+/// every 200 µs to 20 ms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TxTiming {
+    pub pulse_us: u32,
+    /// Start of one pulse to the start of the next.
+    pub interval_us: u32,
+}
+
+impl TxTiming {
+    /// 50 µs pulses every 2 ms, the common reader timing.
+    pub const DEFAULT: Self = Self {
+        pulse_us: 50,
+        interval_us: 2_000,
+    };
+}
+
+impl Default for TxTiming {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// One bit on the wire: pulse D`bit` for `width_us`, `start_us` after the
+/// frame's first pulse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pulse {
+    pub bit: u8,
+    pub start_us: u64,
+    pub width_us: u32,
+}
+
+/// The pulses for `count` bits of `bits`, MSB first; at most 64.
+pub fn plan(bits: u64, count: u32, timing: TxTiming) -> impl Iterator<Item = Pulse> {
+    let count = count.min(64);
+    (0..count).map(move |i| Pulse {
+        bit: ((bits >> (count - 1 - i)) & 1) as u8,
+        start_us: u64::from(i) * u64::from(timing.interval_us),
+        width_us: timing.pulse_us,
+    })
+}
@@ -0,0 +1,169 @@
+//! Feed cadence for long operations.
+//!
+//! The hardware watchdog resets the board if it goes [`TIMEOUT_MS`]
+//! without a feed. In normal running `access_task` feeds it every ten
+//! seconds, when `watchdog_feed_task` signals it, proving both tasks and
+//! the executor are alive. A long blocking operation (a flash save, an
+//! erase, an OTA sector write) holds the executor for the whole time, so
+//! it has to feed on its own: before starting, between steps, and after
+//! finishing. [`FeedCadence`] decides when a step boundary should feed,
+//! so that an operation can check it cheaply after every chunk without
+//! hammering the peripheral.
+//!
+//! A direct feed proves only that the feeding task is alive: while it
+//! feeds, a wedged `access_task` goes unnoticed. That costs nothing
+//! during a blocking flash call, when no other task can run anyway, so
+//! direct feeds stay within those calls. Everything that awaits, such as
+//! an OTA upload between sector writes or a sync waiting on the network,
+//! leaves feeding to `access_task`, so a reset still catches it stuck.
+//!
+//! A feed tick that reaches `access_task` while it is busy with a card
+//! read waits until the read is done, and the read may in turn wait on
+//! a lock another task holds. That wait gives up at [`MAX_LOCK_HOLD_MS`],
+//! the most the feed period leaves before the timeout, and the read is
+//! denied. [`LockHolds`] keeps the longest wait and counts the give-ups.
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::clock;
+use crate::counters::Counter;
+
+/// Hardware watchdog timeout.
+pub const TIMEOUT_MS: u64 = 30_000;
+
+/// How often `watchdog_feed_task` asks `access_task` to feed.
+pub const FEED_PERIOD_MS: u64 = 10_000;
+
+/// Longest `access_task` waits on the fob lists and the rest of its
+/// shared state for one input before failing closed. A feed tick that
+/// arrives meanwhile waits behind it, so the watchdog sees up to
+/// [`FEED_PERIOD_MS`] plus this between feeds.
+pub const MAX_LOCK_HOLD_MS: u64 = 5_000;
+
+const _: () = assert!(FEED_PERIOD_MS + MAX_LOCK_HOLD_MS < TIMEOUT_MS);
+
+/// How often a long operation feeds: a sixth of the timeout, so a step
+/// that runs several times longer than expected still lands well inside
+/// it.
+pub const LONG_OP_FEED_INTERVAL_MS: u64 = 5_000;
+
+#[derive(Clone, Debug)]
+pub struct FeedCadence {
+    interval_ms: u64,
+    last_feed_ms: Option<u64>,
+    feeds: u32,
+    longest_gap_ms: u64,
+}
+
+impl FeedCadence {
+    pub const fn new(interval_ms: u64) -> Self {
+        Self {
+            interval_ms,
+            last_feed_ms: None,
+            feeds: 0,
+            longest_gap_ms: 0,
+        }
+    }
+
+    /// Whether to feed at `now_ms`, counting it as fed if so. The first
+    /// check always feeds; after that, once `interval_ms` has passed
+    /// since the last feed.
+    pub fn due(&mut self, now_ms: u64) -> bool {
+        if let Some(last) = self.last_feed_ms {
+            if clock::elapsed(last, now_ms) < self.interval_ms {
+                return false;
+            }
+        }
+        self.fed(now_ms);
+        true
+    }
+
+    /// Record a feed at `now_ms` regardless of the interval, as the
+    /// wrapper does once the operation has finished.
+    pub fn fed(&mut self, now_ms: u64) {
+        if let Some(last) = self.last_feed_ms {
+            self.longest_gap_ms = self.longest_gap_ms.max(clock::elapsed(last, now_ms));
+        }
+        self.last_feed_ms = Some(now_ms);
+        self.feeds = self.feeds.saturating_add(1);
+    }
+
+    /// Feeds so far.
+    pub fn feeds(&self) -> u32 {
+        self.feeds
+    }
+
+    /// Longest stretch between two feeds so far. A step that took most
+    /// of [`TIMEOUT_MS`] on its own shows up here before it causes a
+    /// reset.
+    pub fn longest_gap_ms(&self) -> u64 {
+        self.longest_gap_ms
+    }
+}
+
+/// Time `access_task` waits on shared state per input, and how often it
+/// gave up at [`MAX_LOCK_HOLD_MS`].
+pub struct LockHolds {
+    longest_ms: AtomicU32,
+    over_budget: Counter,
+}
+
+impl LockHolds {
+    pub const fn new() -> Self {
+        Self {
+            longest_ms: AtomicU32::new(0),
+            over_budget: Counter::new(),
+        }
+    }
+
+    /// Record one input that spent `held_ms` on shared state. Returns
+    /// whether that went over [`MAX_LOCK_HOLD_MS`].
+    pub fn record(&self, held_ms: u64) -> bool {
+        let held = held_ms.min(u32::MAX as u64) as u32;
+        self.longest_ms.fetch_max(held, Ordering::Relaxed);
+        if held_ms <= MAX_LOCK_HOLD_MS {
+            return false;
+        }
+        self.over_budget.incr();
+        true
+    }
+
+    /// Record one input that gave up waiting at [`MAX_LOCK_HOLD_MS`].
+    pub fn gave_up(&self) {
+        self.longest_ms
+            .fetch_max(MAX_LOCK_HOLD_MS as u32, Ordering::Relaxed);
+        self.over_budget.incr();
+    }
+
+    /// Longest single hold since boot.
+    pub fn longest_ms(&self) -> u32 {
+        self.longest_ms.load(Ordering::Relaxed)
+    }
+
+    /// Inputs that went over or gave up at [`MAX_LOCK_HOLD_MS`] since
+    /// boot.
+    pub fn over_budget(&self) -> u32 {
+        self.over_budget.get()
+    }
+
+    /// The overrun count, for `/counters`.
+    pub fn over_budget_counter(&self) -> &Counter {
+        &self.over_budget
+    }
+
+    /// Render the longest hold as a Prometheus gauge and the overruns as
+    /// a counter.
+    pub fn write_metrics<W: Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_str("# TYPE conway_access_lock_hold_max_ms gauge\n")?;
+        writeln!(out, "conway_access_lock_hold_max_ms {}", self.longest_ms())?;
+        out.write_str("# TYPE conway_access_lock_over_budget_total counter\n")?;
+        writeln!(out, "conway_access_lock_over_budget_total {}", self.over_budget())
+    }
+}
+
+impl Default for LockHolds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,195 @@
+//! Minimal RFC 6455 WebSocket client framing for the Conway push channel.
+//!
+//! The controller holds one WebSocket open to Conway and treats any data
+//! frame as "the fob list changed, sync now". Only what that needs is
+//! here: the upgrade request, a check of the server's 101 response, frame
+//! parsing for server→client frames and frame building for the (masked)
+//! client→server control replies.
+//!
+//! `Sec-WebSocket-Accept` is not verified. The channel carries no data
+//! of its own, only a trigger for the regular sync, which does its own
+//! ETag and signature checks; a forged push can at most cause one extra
+//! sync. Skipping the check saves a SHA-1 implementation.
+//!
+//! Pure; the firmware's `push` module owns the socket.
+
+use core::fmt::Write;
+
+/// Control frame payloads are capped by the RFC.
+pub const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// Frame opcodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_bits(b: u8) -> Option<Self> {
+        match b {
+            0x0 => Some(Self::Continuation),
+            0x1 => Some(Self::Text),
+            0x2 => Some(Self::Binary),
+            0x8 => Some(Self::Close),
+            0x9 => Some(Self::Ping),
+            0xA => Some(Self::Pong),
+            _ => None,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xA,
+        }
+    }
+
+    pub fn is_control(self) -> bool {
+        matches!(self, Self::Close | Self::Ping | Self::Pong)
+    }
+}
+
+/// A parsed server→client frame borrowing its payload from the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: &'a [u8],
+}
+
+/// Parse one frame from the front of `buf`.
+///
+/// Returns `Ok(None)` if `buf` does not yet hold a whole frame, or
+/// `Ok(Some((frame, consumed)))`. Errors are protocol violations after
+/// which the connection must be dropped: reserved bits set, unknown
+/// opcode, a masked frame (servers must not mask), a fragmented or
+/// oversized control frame, or a payload longer than `max_payload`.
+pub fn parse_frame(
+    buf: &[u8],
+    max_payload: usize,
+) -> Result<Option<(Frame<'_>, usize)>, &'static str> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let fin = buf[0] & 0x80 != 0;
+    if buf[0] & 0x70 != 0 {
+        return Err("websocket reserved bits set");
+    }
+    let opcode = Opcode::from_bits(buf[0] & 0x0F).ok_or("websocket unknown opcode")?;
+    if buf[1] & 0x80 != 0 {
+        return Err("websocket frame from server is masked");
+    }
+
+    let (len, header) = match buf[1] & 0x7F {
+        126 => {
+            if buf.len() < 4 {
+                return Ok(None);
+            }
+            (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4)
+        }
+        127 => {
+            if buf.len() < 10 {
+                return Ok(None);
+            }
+            let mut b = [0u8; 8];
+            b.copy_from_slice(&buf[2..10]);
+            (u64::from_be_bytes(b), 10)
+        }
+        n => (n as u64, 2),
+    };
+
+    if opcode.is_control() && (!fin || len > MAX_CONTROL_PAYLOAD as u64) {
+        return Err("websocket control frame fragmented or too long");
+    }
+    if len > max_payload as u64 {
+        return Err("websocket frame too large");
+    }
+    let end = header + len as usize;
+    if buf.len() < end {
+        return Ok(None);
+    }
+    let frame = Frame {
+        fin,
+        opcode,
+        payload: &buf[header..end],
+    };
+    Ok(Some((frame, end)))
+}
+
+/// Build a single, final, masked client→server frame into `out` and
+/// return its length. Client frames must always be masked.
+pub fn build_frame(
+    opcode: Opcode,
+    payload: &[u8],
+    mask: [u8; 4],
+    out: &mut [u8],
+) -> Result<usize, &'static str> {
+    if opcode.is_control() && payload.len() > MAX_CONTROL_PAYLOAD {
+        return Err("websocket control payload too long");
+    }
+    let header = match payload.len() {
+        0..=125 => 2,
+        126..=0xFFFF => 4,
+        _ => 10,
+    };
+    let total = header + 4 + payload.len();
+    if out.len() < total {
+        return Err("websocket frame buffer too small");
+    }
+
+    out[0] = 0x80 | opcode.bits();
+    match header {
+        2 => out[1] = 0x80 | payload.len() as u8,
+        4 => {
+            out[1] = 0x80 | 126;
+            out[2..4].copy_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        _ => {
+            out[1] = 0x80 | 127;
+            out[2..10].copy_from_slice(&(payload.len() as u64).to_be_bytes());
+        }
+    }
+    out[header..header + 4].copy_from_slice(&mask);
+    for (i, (dst, &src)) in out[header + 4..total].iter_mut().zip(payload).enumerate() {
+        *dst = src ^ mask[i % 4];
+    }
+    Ok(total)
+}
+
+/// Write the HTTP upgrade request. `key` is the base64 of 16 random
+/// bytes.
+pub fn write_handshake<W: Write>(
+    out: &mut W,
+    path: &str,
+    host: &str,
+    key: &str,
+) -> core::fmt::Result {
+    write!(
+        out,
+        "GET {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\
+         \r\n",
+        path, host, key
+    )
+}
+
+/// Whether the response head (status line and headers) accepts the
+/// upgrade: status 101 with `Upgrade: websocket`.
+pub fn handshake_accepted(head: &str) -> bool {
+    crate::http_client::parse_status_code(head) == 101
+        && crate::http_client::extract_header(head, "upgrade")
+            .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
@@ -7,7 +7,9 @@ use esp_hal::gpio::Input;
 
 // Re-export the pure decoder types so existing callers (`use crate::wiegand::WiegandRead`)
 // continue to compile unchanged.
-pub use access_controller::decode::{decode_26, decode_34, WiegandRead};
+pub use access_controller::decode::WiegandRead;
+use access_controller::clock;
+use access_controller::decode::{FrameBits, FrameError, FrameGap, PulseFilter};
 
 const DEBOUNCE: Duration = Duration::from_micros(500);
 const BIT_TIMEOUT: Duration = Duration::from_millis(25);
@@ -15,53 +17,94 @@ const BIT_TIMEOUT: Duration = Duration::from_millis(25);
 pub struct Wiegand<'a> {
     d0: Input<'a>,
     d1: Input<'a>,
+    filter: PulseFilter,
+    gap: FrameGap,
+    keepalive: bool,
 }
 
 impl<'a> Wiegand<'a> {
     pub fn new(d0: Input<'a>, d1: Input<'a>) -> Self {
-        Self { d0, d1 }
+        Self {
+            d0,
+            d1,
+            filter: PulseFilter::default(),
+            gap: FrameGap::default(),
+            keepalive: false,
+        }
+    }
+
+    /// Drop edges whose low pulse is shorter than the filter's minimum.
+    pub fn with_pulse_filter(mut self, filter: PulseFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Drop bits arriving within the gap after each completed frame.
+    pub fn with_frame_gap(mut self, gap: FrameGap) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Log short frames at debug level: the reader's keep-alive pulses
+    /// arrive as such and are expected.
+    pub fn with_keepalive(mut self, keepalive: bool) -> Self {
+        self.keepalive = keepalive;
+        self
     }
 
     /// Read a complete Wiegand transmission asynchronously.
     ///
     /// Waits for the first bit, then collects bits until no more arrive
-    /// within the timeout period.
+    /// within the timeout period. An over-length frame is drained to the
+    /// end and dropped, so none of it leaks into the next read; so are
+    /// bits arriving within the [`FrameGap`] after the last frame.
     pub async fn read(&mut self) -> Option<WiegandRead> {
-        let first_bit = self.wait_for_bit().await;
+        let first_bit = loop {
+            let bit = self.wait_for_bit().await;
+            if self.gap.accept(Instant::now().as_micros()) {
+                break bit;
+            }
+            log::debug!("wiegand: D{} within the inter-frame gap ignored", bit);
+        };
+        let mut frame = FrameBits::new();
+        frame.record_bit(first_bit);
 
         // Set timestamp after first bit for debouncing subsequent bits
         let mut last_bit = Instant::now();
-        let mut bits: u64 = first_bit as u64;
-        let mut count: u32 = 1;
 
         // Collect remaining bits until timeout
         loop {
             match with_timeout(BIT_TIMEOUT, self.wait_for_bit()).await {
                 Ok(bit) => {
                     let now = Instant::now();
-                    if now.duration_since(last_bit) < DEBOUNCE {
+                    if clock::elapsed(last_bit.as_micros(), now.as_micros()) < DEBOUNCE.as_micros() {
                         continue; // Debounce
                     }
                     last_bit = now;
-
-                    if count >= 64 {
-                        break; // Buffer full
-                    }
-                    bits = (bits << 1) | (bit as u64);
-                    count += 1;
+                    frame.record_bit(bit);
                 }
                 Err(_) => break, // Timeout - transmission complete
             }
         }
+        if self.gap.enabled() {
+            self.gap.frame_ended(Instant::now().as_micros());
+        }
 
-        // Decode based on bit count
-        match count {
-            26 => decode_26(bits),
-            34 => decode_34(bits),
-            _ => {
+        match frame.finish() {
+            Ok(read) => Some(read),
+            Err(FrameError::Overlong(count)) => {
+                log::warn!("wiegand: over-length frame ({} bits) dropped", count);
+                None
+            }
+            Err(FrameError::UnknownFormat(count)) if self.keepalive => {
+                log::debug!("wiegand: unknown format ({} bits), keep-alive?", count);
+                None
+            }
+            Err(FrameError::UnknownFormat(count)) => {
                 log::warn!("wiegand: unknown format ({} bits)", count);
                 None
             }
+            Err(FrameError::Parity(_)) => None,
         }
     }
 
@@ -72,18 +115,44 @@ impl<'a> Wiegand<'a> {
     /// reader's native Wiegand signaling is idle-HIGH with a brief LOW pulse
     /// per bit, and the buffer preserves that polarity, so the ESP32 sees the
     /// reader's true falling edges directly.
+    ///
+    /// With a [`PulseFilter`], edges whose line does not stay low for the
+    /// minimum width are skipped as glitches.
     async fn wait_for_bit(&mut self) -> u8 {
         use embassy_futures::select::Either;
 
-        // D0 falling edge = 0 bit, D1 falling edge = 1 bit.
-        match embassy_futures::select::select(
-            self.d0.wait_for_falling_edge(),
-            self.d1.wait_for_falling_edge(),
-        )
-        .await
-        {
-            Either::First(()) => 0,
-            Either::Second(()) => 1,
+        loop {
+            // D0 falling edge = 0 bit, D1 falling edge = 1 bit.
+            let bit = match embassy_futures::select::select(
+                self.d0.wait_for_falling_edge(),
+                self.d1.wait_for_falling_edge(),
+            )
+            .await
+            {
+                Either::First(()) => 0,
+                Either::Second(()) => 1,
+            };
+            if !self.filter.enabled() || self.held_low(bit).await {
+                return bit;
+            }
+            log::debug!("wiegand: glitch on D{} ignored", bit);
         }
     }
+
+    /// Watch the line that just fell for the filter window and judge the
+    /// pulse by when (if at all) it rose again.
+    async fn held_low(&mut self, bit: u8) -> bool {
+        let woke = Instant::now();
+        let line = if bit == 0 { &mut self.d0 } else { &mut self.d1 };
+        let window = Duration::from_micros(self.filter.min_low_us.into());
+        let rose_after_us = if line.is_high() {
+            Some(0)
+        } else {
+            match with_timeout(window, line.wait_for_rising_edge()).await {
+                Ok(()) => Some(clock::elapsed(woke.as_micros(), Instant::now().as_micros())),
+                Err(_) => None,
+            }
+        };
+        self.filter.accept(rose_after_us)
+    }
 }
@@ -0,0 +1,167 @@
+//! Wiegand reader on the RMT peripheral (feature `wiegand-rmt`).
+//!
+//! Drop-in for [`crate::wiegand::Wiegand`]: same `read()`, same
+//! [`WiegandRead`]. Each data line feeds its own RMT receive channel, which
+//! times every pulse in hardware, so a busy executor no longer costs bits.
+//! The CPU only takes one GPIO edge per line per frame, to anchor that
+//! channel's timeline (see `access_controller::rmt_frame`).
+
+use core::cell::Cell;
+
+use embassy_futures::join::join4;
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::Async;
+use esp_hal::gpio::{Input, Level};
+use esp_hal::peripherals::RMT;
+use esp_hal::rmt::{Channel, PulseCode, Rmt, Rx, RxChannelAsync, RxChannelConfig, RxChannelCreator};
+use esp_hal::time::Rate;
+
+use access_controller::decode::decode_frame;
+use access_controller::rmt_frame::{LineCapture, Span, frame_bits};
+pub use access_controller::decode::WiegandRead;
+
+/// RMT source clock / divider: one tick per 3 µs, coarse enough for the
+/// idle threshold below to fit the S3's 15-bit register and still resolve
+/// a 20 µs pulse.
+const RMT_CLOCK: Rate = Rate::from_mhz(80);
+const CLK_DIVIDER: u8 = 240;
+const TICK_US: u32 = 3;
+
+/// A line quiet for ~96 ms has finished its frame. This must outlast the
+/// longest gap *within* a frame on one line (a run of the other bit), so it
+/// is far longer than the GPIO reader's 25 ms: a 34-bit frame with 33 bits
+/// on one line fits at bit periods up to ~2.9 ms.
+const IDLE_TICKS: u16 = 32_000;
+
+/// Pulses shorter than this many RMT source clocks (~3 µs) are dropped
+/// by the channel's input filter.
+const FILTER_TICKS: u8 = 255;
+
+/// Upper bound on a whole frame from its first edge: 34 bits at the
+/// slowest gap we accept, plus the idle tail.
+const FRAME_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Entries per channel: a 34-bit frame is at most 34 pulses on one line,
+/// two spans per entry, plus the end marker.
+const CODES: usize = 48;
+
+pub struct RmtWiegand<'a> {
+    d0: Input<'a>,
+    d1: Input<'a>,
+    rx0: Channel<'a, Async, Rx>,
+    rx1: Channel<'a, Async, Rx>,
+    keepalive: bool,
+}
+
+impl<'a> RmtWiegand<'a> {
+    /// Route D0/D1 into two RMT receive channels. The pins stay plain
+    /// inputs too, for the per-frame edge timestamps.
+    pub fn new(d0: Input<'a>, d1: Input<'a>, rmt: RMT<'a>) -> Self {
+        let rmt = Rmt::new(rmt, RMT_CLOCK).expect("rmt: init").into_async();
+        let config = RxChannelConfig::default()
+            .with_clk_divider(CLK_DIVIDER)
+            .with_idle_threshold(IDLE_TICKS)
+            .with_filter_threshold(FILTER_TICKS);
+        // The S3 only receives on channels 4-7.
+        #[cfg(feature = "esp32")]
+        let (ch0, ch1) = (rmt.channel0, rmt.channel1);
+        #[cfg(feature = "esp32s3")]
+        let (ch0, ch1) = (rmt.channel4, rmt.channel5);
+        let rx0 = ch0.configure_rx(d0.peripheral_input(), config).expect("rmt: D0 channel");
+        let rx1 = ch1.configure_rx(d1.peripheral_input(), config).expect("rmt: D1 channel");
+        Self { d0, d1, rx0, rx1, keepalive: false }
+    }
+
+    /// Log short and one-line frames at debug level: the reader's
+    /// keep-alive pulses arrive as such and are expected.
+    pub fn with_keepalive(mut self, keepalive: bool) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// Read a complete Wiegand transmission asynchronously.
+    ///
+    /// Arms both channels, then waits until each line has gone idle. A
+    /// valid 26- or 34-bit frame always pulses both lines (all zeros fails
+    /// the odd parity bit, all ones the even one), so a frame that leaves a
+    /// line silent is abandoned after [`FRAME_TIMEOUT`].
+    pub async fn read(&mut self) -> Option<WiegandRead> {
+        let mut codes0 = [PulseCode::default(); CODES];
+        let mut codes1 = [PulseCode::default(); CODES];
+        let started = Signal::<NoopRawMutex, ()>::new();
+        let first0 = Cell::new(Instant::MIN);
+        let first1 = Cell::new(Instant::MIN);
+
+        let frame = join4(
+            self.rx0.receive(&mut codes0),
+            self.rx1.receive(&mut codes1),
+            first_edge(&mut self.d0, &first0, &started),
+            first_edge(&mut self.d1, &first1, &started),
+        );
+        let deadline = async {
+            started.wait().await;
+            Timer::after(FRAME_TIMEOUT).await;
+        };
+        let (r0, r1, (), ()) = match select(frame, deadline).await {
+            Either::First(done) => done,
+            Either::Second(()) if self.keepalive => {
+                log::debug!("wiegand: frame left a line silent, keep-alive?");
+                return None;
+            }
+            Either::Second(()) => {
+                log::warn!("wiegand: frame left a line silent, dropped");
+                return None;
+            }
+        };
+        if r0.is_err() || r1.is_err() {
+            log::warn!("wiegand: rmt receive failed");
+            return None;
+        }
+
+        let mut spans0 = [Span { low: false, ticks: 0 }; 2 * CODES];
+        let mut spans1 = [Span { low: false, ticks: 0 }; 2 * CODES];
+        let d0 = LineCapture {
+            first_edge_us: first0.get().as_micros(),
+            spans: to_spans(&codes0, &mut spans0),
+        };
+        let d1 = LineCapture {
+            first_edge_us: first1.get().as_micros(),
+            spans: to_spans(&codes1, &mut spans1),
+        };
+        let (bits, count) = frame_bits(Some(&d0), Some(&d1), TICK_US)?;
+        if !matches!(count, 26 | 34) {
+            if self.keepalive {
+                log::debug!("wiegand: unknown format ({} bits), keep-alive?", count);
+            } else {
+                log::warn!("wiegand: unknown format ({} bits)", count);
+            }
+        }
+        decode_frame(bits, count)
+    }
+}
+
+/// Timestamp a line's first falling edge of the frame and flag the frame
+/// as started.
+async fn first_edge(line: &mut Input<'_>, at: &Cell<Instant>, started: &Signal<NoopRawMutex, ()>) {
+    line.wait_for_falling_edge().await;
+    at.set(Instant::now());
+    started.signal(());
+}
+
+/// Unpack RMT entries into spans, up to and including the end marker.
+fn to_spans<'s>(codes: &[PulseCode], spans: &'s mut [Span]) -> &'s [Span] {
+    let mut n = 0;
+    for code in codes {
+        for (level, ticks) in [(code.level1(), code.length1()), (code.level2(), code.length2())] {
+            spans[n] = Span { low: level == Level::Low, ticks };
+            n += 1;
+            if ticks == 0 {
+                return &spans[..n];
+            }
+        }
+    }
+    &spans[..n]
+}
@@ -0,0 +1,53 @@
+//! Wiegand transmitter for chaining to a downstream panel (feature
+//! `wiegand-tx`).
+//!
+//! Each output pin drives an NPN that pulls the panel's D0 or D1 line low,
+//! like the board's other outputs, so GPIO HIGH is a pulse and LOW is idle.
+//! The pulse plan comes from `access_controller::tx_plan`.
+
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::delay::Delay;
+use esp_hal::gpio::Output;
+
+use access_controller::decode::WiegandRead;
+use access_controller::tx_plan::{TxFormat, TxTiming, plan};
+
+pub struct WiegandTx<'a> {
+    d0: Output<'a>,
+    d1: Output<'a>,
+    timing: TxTiming,
+}
+
+impl<'a> WiegandTx<'a> {
+    /// Both pins should start LOW (lines idle).
+    pub fn new(d0: Output<'a>, d1: Output<'a>) -> Self {
+        Self { d0, d1, timing: TxTiming::DEFAULT }
+    }
+
+    pub fn with_timing(mut self, timing: TxTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Send `read` as a `format` frame with fresh parity.
+    pub async fn send(&mut self, read: &WiegandRead, format: TxFormat) {
+        self.send_raw(format.frame(read), format.bits()).await;
+    }
+
+    /// Send `count` bits of `bits`, MSB first, as they are.
+    ///
+    /// Gaps are awaited; the pulse itself is a busy wait, since tens of µs
+    /// is below what the timer queue resolves reliably. An interrupt can
+    /// stretch a pulse, which panels tolerate far better than a short one.
+    pub async fn send_raw(&mut self, bits: u64, count: u32) {
+        let delay = Delay::new();
+        let start = Instant::now();
+        for pulse in plan(bits, count, self.timing) {
+            Timer::at(start + Duration::from_micros(pulse.start_us)).await;
+            let line = if pulse.bit == 0 { &mut self.d0 } else { &mut self.d1 };
+            line.set_high();
+            delay.delay_micros(pulse.width_us);
+            line.set_low();
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! Alternative encodings of the Conway sync exchange.
+//!
+//! The binary encoding is an alternative to the JSON bodies of
+//! `POST /api/fobs` for deployments that would rather not parse text on
+//! the device. The controller opts in by sending its events with
+//! `Content-Type: application/x-conway-fobs`; the server answers in kind
+//! and labels the response the same way. The response `Content-Type`
+//! decides how it is parsed, so a server that answers JSON anyway still
+//! works.
+//!
+//! ## Framing
+//!
+//! Each body is a single gRPC-style length-prefixed message:
+//!
+//! ```text
+//!   flag      u8               (0; compression is not supported)
+//!   length    u32 BE           (bytes of message that follow)
+//!   message   u8[length]
+//! ```
+//!
+//! ## Messages
+//!
+//! ```text
+//!   request (events):
+//!     count      u16 LE
+//!     first_seq  u64 LE
+//!     events     (fob u32 LE, allowed u8) * count
+//!
+//!   response (fob list):
+//!     count   u16 LE
+//!     fobs    u32 LE * count
+//! ```
+//!
+//! The events of a batch are numbered one after another, so the request
+//! carries only the first one's sequence number (see [`crate::events`]).
+//! The fob list is the same encoding as the tail of the
+//! [`crate::fob_cache`] payload. ETag and signature stay in the HTTP
+//! headers; the signature covers the framed body bytes.
+//!
+//! ## NDJSON
+//!
+//! `Content-Type: application/x-ndjson` sends the events as one JSON
+//! object per line instead of a JSON array:
+//!
+//! ```text
+//!   {"fob":1234,"allowed":true,"seq":41}\n
+//!   {"fob":5678,"allowed":false,"seq":42}\n
+//! ```
+//!
+//! `seq` is the event's sequence number (see [`crate::events`]).
+//!
+//! Backends that name the fields differently get them renamed with
+//! [`EventFields`]; the JSON array body uses the same names.
+//!
+//! ## Compact events
+//!
+//! [`EventEncoding::Compact`] drops the names and sends each event as an
+//! array of the same values in the same order, `[1234,true,41]`, in both
+//! the JSON array and NDJSON bodies. Such a body says so with an
+//! `events=compact` parameter on its `Content-Type`. A server announces
+//! that it takes compact events by listing such a media type in an
+//! `Accept-Post` response header (`CONWAY_EVENT_ENCODING=auto`).
+//!
+//! Lines are formatted one at a time and written straight to the socket,
+//! so no body buffer bounds the batch; [`ndjson_len`] gives the
+//! `Content-Length` up front. The server still answers with a JSON or
+//! binary fob list.
+
+use alloc::vec::Vec;
+use heapless::{String as HString, Vec as HVec};
+
+use crate::events::AccessEvent;
+use crate::fob_cache;
+
+/// Media type that selects the binary encoding.
+pub const CONTENT_TYPE: &str = "application/x-conway-fobs";
+
+/// Media type of the JSON encoding.
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Media type of the newline-delimited JSON event encoding.
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Media type of a JSON body of compact events.
+pub const COMPACT_JSON_CONTENT_TYPE: &str = "application/json; events=compact";
+
+/// Media type of an NDJSON body of compact events.
+pub const COMPACT_NDJSON_CONTENT_TYPE: &str = "application/x-ndjson; events=compact";
+
+/// Longest name [`EventFields`] accepts for a field.
+pub const MAX_FIELD_NAME: usize = 16;
+
+/// Longest NDJSON event line, `{"fob":4294967295,"allowed":false,"seq":18446744073709551615}\n`
+/// with every field name [`MAX_FIELD_NAME`] long.
+pub const NDJSON_LINE_MAX: usize = 49 + 3 * MAX_FIELD_NAME;
+
+/// Names of the fields of an event object, `fob`, `allowed` and `seq`
+/// unless a backend expects others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventFields<'a> {
+    pub fob: &'a str,
+    pub allowed: &'a str,
+    pub seq: &'a str,
+}
+
+impl Default for EventFields<'_> {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl<'a> EventFields<'a> {
+    pub const DEFAULT: EventFields<'static> = EventFields {
+        fob: "fob",
+        allowed: "allowed",
+        seq: "seq",
+    };
+
+    /// Parse the `CONWAY_EVENT_FIELDS` build knob: comma-separated
+    /// `<field>=<name>` renames, e.g. `fob=credential,allowed=granted`.
+    /// Fields left out keep their names. `None` for an unknown or
+    /// repeated field, a name that isn't 1 to [`MAX_FIELD_NAME`] ASCII
+    /// letters, digits, `_` or `-`, or two fields given the same name.
+    pub fn parse(s: &'a str) -> Option<Self> {
+        let mut fields = EventFields::DEFAULT;
+        let mut renamed = [false; 3];
+        let s = s.trim();
+        if s.is_empty() {
+            return Some(fields);
+        }
+        for rename in s.split(',') {
+            let (field, name) = rename.split_once('=')?;
+            let name = name.trim();
+            let valid = (1..=MAX_FIELD_NAME).contains(&name.len())
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+            if !valid {
+                return None;
+            }
+            let (slot, i) = match field.trim() {
+                "fob" => (&mut fields.fob, 0),
+                "allowed" => (&mut fields.allowed, 1),
+                "seq" => (&mut fields.seq, 2),
+                _ => return None,
+            };
+            if renamed[i] {
+                return None;
+            }
+            renamed[i] = true;
+            *slot = name;
+        }
+        let distinct = fields.fob != fields.allowed
+            && fields.fob != fields.seq
+            && fields.allowed != fields.seq;
+        distinct.then_some(fields)
+    }
+}
+
+const FRAME_HEADER_LEN: usize = 5;
+
+/// Body encoding used for the sync exchange.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncProtocol {
+    #[default]
+    Json,
+    Binary,
+    /// Events as NDJSON, streamed; the response is parsed as JSON.
+    Ndjson,
+}
+
+impl SyncProtocol {
+    /// Parse a configuration value: `"json"`, `"binary"` or `"ndjson"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "json" => Some(Self::Json),
+            "binary" => Some(Self::Binary),
+            "ndjson" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+
+    /// Protocol of a response, from its `Content-Type` header value.
+    /// Parameters (`; charset=...`) are ignored; anything that isn't the
+    /// binary media type is treated as JSON. Never `Ndjson`: only
+    /// requests use it.
+    pub fn from_content_type(value: Option<&str>) -> Self {
+        let media = value.and_then(|v| v.split(';').next()).unwrap_or("");
+        if media.trim().eq_ignore_ascii_case(CONTENT_TYPE) {
+            Self::Binary
+        } else {
+            Self::Json
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => JSON_CONTENT_TYPE,
+            Self::Binary => CONTENT_TYPE,
+            Self::Ndjson => NDJSON_CONTENT_TYPE,
+        }
+    }
+}
+
+fn frame(msg: Vec<u8>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(FRAME_HEADER_LEN + msg.len());
+    out.push(0);
+    out.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    out.extend_from_slice(&msg);
+    out
+}
+
+fn unframe(buf: &[u8]) -> Result<&[u8], &'static str> {
+    if buf.len() < FRAME_HEADER_LEN {
+        return Err("binary frame truncated");
+    }
+    if buf[0] != 0 {
+        return Err("compressed binary frame not supported");
+    }
+    let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+    let msg = &buf[FRAME_HEADER_LEN..];
+    if msg.len() != len {
+        return Err("binary frame length mismatch");
+    }
+    Ok(msg)
+}
+
+/// Bytes of an event request message ahead of its records.
+const EVENTS_HEADER_LEN: usize = 2 + 8;
+
+/// Encode a request body carrying `events`, numbered from `first_seq`.
+pub fn encode_events(events: &[AccessEvent], first_seq: u64) -> Vec<u8> {
+    let n = events.len().min(u16::MAX as usize);
+    let mut msg = Vec::with_capacity(EVENTS_HEADER_LEN + n * 5);
+    msg.extend_from_slice(&(n as u16).to_le_bytes());
+    msg.extend_from_slice(&first_seq.to_le_bytes());
+    for e in events.iter().take(n) {
+        msg.extend_from_slice(&e.fob.to_le_bytes());
+        msg.push(e.allowed as u8);
+    }
+    frame(msg)
+}
+
+/// Decode a request body produced by [`encode_events`]: the first
+/// event's sequence number and the events.
+pub fn decode_events<const N: usize>(
+    buf: &[u8],
+) -> Result<(u64, HVec<AccessEvent, N>), &'static str> {
+    let msg = unframe(buf)?;
+    if msg.len() < EVENTS_HEADER_LEN {
+        return Err("binary event list truncated");
+    }
+    let count = u16::from_le_bytes([msg[0], msg[1]]) as usize;
+    if EVENTS_HEADER_LEN + count * 5 != msg.len() {
+        return Err("binary event list length mismatch");
+    }
+    let mut first_seq = [0; 8];
+    first_seq.copy_from_slice(&msg[2..EVENTS_HEADER_LEN]);
+    let mut events = HVec::new();
+    for rec in msg[EVENTS_HEADER_LEN..].chunks_exact(5) {
+        let allowed = match rec[4] {
+            0 => false,
+            1 => true,
+            _ => return Err("binary event flag is not 0 or 1"),
+        };
+        let fob = u32::from_le_bytes([rec[0], rec[1], rec[2], rec[3]]);
+        events
+            .push(AccessEvent { fob, allowed })
+            .map_err(|_| "binary event list too long")?;
+    }
+    Ok((u64::from_le_bytes(first_seq), events))
+}
+
+/// Encode a response body carrying `fobs`.
+pub fn encode_fob_list(fobs: &[u32]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(2 + fobs.len() * 4);
+    fob_cache::push_list(&mut msg, fobs);
+    frame(msg)
+}
+
+/// Decode a response body produced by [`encode_fob_list`]. A list longer
+/// than `N` is an error, like its JSON counterpart.
+pub fn decode_fob_list<const N: usize>(buf: &[u8]) -> Result<HVec<u32, N>, &'static str> {
+    let msg = unframe(buf)?;
+    fob_cache::parse_list::<N>(msg).ok_or("binary fob list malformed or exceeds MAX_FOBS")
+}
+
+/// How each event is written in a JSON or NDJSON body.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EventEncoding {
+    /// `{"fob":N,"allowed":B,"seq":N}`, named by [`EventFields`].
+    #[default]
+    Verbose,
+    /// `[N,B,N]`: the same values in the same order, unnamed.
+    Compact,
+}
+
+impl EventEncoding {
+    /// Media type of a `protocol` body with events in this encoding. The
+    /// binary encoding has only one form.
+    pub fn content_type(self, protocol: SyncProtocol) -> &'static str {
+        match (self, protocol) {
+            (Self::Compact, SyncProtocol::Json) => COMPACT_JSON_CONTENT_TYPE,
+            (Self::Compact, SyncProtocol::Ndjson) => COMPACT_NDJSON_CONTENT_TYPE,
+            _ => protocol.content_type(),
+        }
+    }
+
+    /// What a response's `Accept-Post` header says the server takes:
+    /// `Compact` if any media type it lists has an `events=compact`
+    /// parameter, otherwise `Verbose`. `None` without the header, which
+    /// says nothing either way.
+    pub fn from_accept_post(value: Option<&str>) -> Option<Self> {
+        let compact = value?.split(',').any(|media| {
+            media.split(';').skip(1).any(|param| {
+                param.split_once('=').is_some_and(|(name, value)| {
+                    name.trim().eq_ignore_ascii_case("events")
+                        && value.trim().trim_matches('"').eq_ignore_ascii_case("compact")
+                })
+            })
+        });
+        Some(if compact { Self::Compact } else { Self::Verbose })
+    }
+}
+
+/// How the controller picks an [`EventEncoding`]: the
+/// `CONWAY_EVENT_ENCODING` build knob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodingChoice {
+    /// Always this encoding.
+    Fixed(EventEncoding),
+    /// Verbose until a server's `Accept-Post` lists compact events, then
+    /// whatever the last server to send the header takes.
+    Negotiate,
+}
+
+impl Default for EncodingChoice {
+    fn default() -> Self {
+        Self::Fixed(EventEncoding::Verbose)
+    }
+}
+
+impl EncodingChoice {
+    /// Parse a configuration value: `"verbose"`, `"compact"` or `"auto"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "verbose" => Some(Self::Fixed(EventEncoding::Verbose)),
+            "compact" => Some(Self::Fixed(EventEncoding::Compact)),
+            "auto" => Some(Self::Negotiate),
+            _ => None,
+        }
+    }
+
+    /// The encoding to send, given what a server last announced.
+    pub fn pick(self, accepted: Option<EventEncoding>) -> EventEncoding {
+        match self {
+            Self::Fixed(encoding) => encoding,
+            Self::Negotiate => accepted.unwrap_or_default(),
+        }
+    }
+}
+
+/// Everything that shapes an event object: its encoding and, for
+/// verbose events, the field names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventFormat<'a> {
+    pub encoding: EventEncoding,
+    pub fields: EventFields<'a>,
+}
+
+impl EventFormat<'_> {
+    /// Verbose events with the usual names.
+    pub const DEFAULT: EventFormat<'static> = EventFormat {
+        encoding: EventEncoding::Verbose,
+        fields: EventFields::DEFAULT,
+    };
+}
+
+/// `event`, numbered `seq`, as one JSON value in `format`.
+pub fn write_event<W: core::fmt::Write>(
+    out: &mut W,
+    event: &AccessEvent,
+    seq: u64,
+    format: &EventFormat<'_>,
+) -> core::fmt::Result {
+    match format.encoding {
+        EventEncoding::Verbose => {
+            let fields = &format.fields;
+            write!(
+                out,
+                r#"{{"{}":{},"{}":{},"{}":{}}}"#,
+                fields.fob, event.fob, fields.allowed, event.allowed, fields.seq, seq
+            )
+        }
+        EventEncoding::Compact => write!(out, "[{},{},{}]", event.fob, event.allowed, seq),
+    }
+}
+
+/// One NDJSON event line for `event`, numbered `seq`, newline included.
+pub fn ndjson_line(event: &AccessEvent, seq: u64, format: &EventFormat<'_>) -> HString<NDJSON_LINE_MAX> {
+    let mut line = HString::new();
+    // Cannot fail: NDJSON_LINE_MAX fits the longest line, and `parse`
+    // caps the names.
+    let _ = write_event(&mut line, event, seq, format);
+    let _ = line.push('\n');
+    line
+}
+
+/// Length of the NDJSON body carrying `events`, numbered from
+/// `first_seq`, for `Content-Length`.
+pub fn ndjson_len(events: &[AccessEvent], first_seq: u64, format: &EventFormat<'_>) -> usize {
+    numbered(events, first_seq)
+        .map(|(seq, e)| ndjson_line(e, seq, format).len())
+        .sum()
+}
+
+/// `events` with their sequence numbers, counting from `first_seq`.
+pub fn numbered(events: &[AccessEvent], first_seq: u64) -> impl Iterator<Item = (u64, &AccessEvent)> {
+    (first_seq..).zip(events)
+}
@@ -11,6 +11,19 @@
 //! - **A4.** Backoff prevents brute force (handwritten + property test).
 //! - **A5.** Recheck deadline of 10 s (handwritten + property test).
 //! - **A6.** LAN-only on the server: enforced server-side, out of scope.
+//! - **A7.** Fail-open only while no list has loaded, only within the
+//!   configured window after boot, and always flagged (handwritten +
+//!   property test). Fail-closed (the default) never grants off-list.
+//! - **A8.** With known facilities configured, an unmatched card from any
+//!   other facility is denied with no `RequestSync` and no recheck window
+//!   (handwritten + property test); listed cards still grant.
+//! - **A9.** Under `DenialReport::Unresolved`, a denial that arms a recheck
+//!   is recorded only if the recheck fails or expires; a grant after the
+//!   sync supersedes it. Grants, and every other decision, are unchanged
+//!   (handwritten + property test).
+//! - **A10.** Backoff is per reader: denials at one reader never delay or
+//!   change the decisions at the other, and a failed recheck backs off
+//!   the reader that presented the card (handwritten + property test).
 //!
 //! Run with:
 //!   cargo test --no-default-features --features sim \
@@ -20,87 +33,21 @@
 #![cfg(feature = "sim")]
 
 use access_controller::core::{
-    AccessCore, CardRead, Effect, Input, Outcome, RECHECK_DEADLINE_MS,
+    AccessCore, DenialReport, DenyBackoff, Effect, FailPolicy, Input, KnownFacilities,
+    MatchOrder, Outcome, Reader, MAX_BACKOFF_STEPS, MAX_FAIL_OPEN_MS, RECHECK_DEADLINE_MS,
 };
+use access_controller::decode::{CredentialFormat, WiegandRead};
 use access_controller::events::AccessEvent;
 use proptest::prelude::*;
 
+mod sim;
+use sim::Sim;
+
 // ---------------------------------------------------------------------------
-// Test harness
+// Test harness (the `Sim` itself is in tests/sim/, shared with the
+// passback and online-check tests)
 // ---------------------------------------------------------------------------
 
-/// Convenience wrapper around `AccessCore` that tracks virtual time and the
-/// fob cache and records the full history of (time, input, effects).
-struct Sim {
-    core: AccessCore,
-    fobs: Vec<u32>,
-    local_fobs: Vec<u32>,
-    conway_enabled: bool,
-    now_ms: u64,
-    history: Vec<(u64, Input, Vec<Effect>)>,
-}
-
-impl Sim {
-    fn new() -> Self {
-        Self {
-            core: AccessCore::new(),
-            fobs: Vec::new(),
-            local_fobs: Vec::new(),
-            conway_enabled: true,
-            now_ms: 0,
-            history: Vec::new(),
-        }
-    }
-
-    /// Construct a sim for standalone-mode tests: no Conway host configured.
-    fn new_standalone() -> Self {
-        let mut s = Self::new();
-        s.conway_enabled = false;
-        s
-    }
-
-    fn add_fob(&mut self, f: u32) {
-        if !self.fobs.contains(&f) {
-            self.fobs.push(f);
-        }
-    }
-
-    fn remove_fob(&mut self, f: u32) {
-        self.fobs.retain(|&x| x != f);
-    }
-
-    fn add_local_fob(&mut self, f: u32) {
-        if !self.local_fobs.contains(&f) {
-            self.local_fobs.push(f);
-        }
-    }
-
-    fn tick(&mut self, dt_ms: u64) {
-        self.now_ms = self.now_ms.saturating_add(dt_ms);
-    }
-
-    fn input(&mut self, i: Input) -> Vec<Effect> {
-        let eff = self.core.step(
-            self.now_ms,
-            &self.local_fobs,
-            &self.fobs,
-            self.conway_enabled,
-            i,
-        );
-        let v: Vec<Effect> = eff.iter().copied().collect();
-        self.history.push((self.now_ms, i, v.clone()));
-        v
-    }
-
-    fn card(&mut self, fob: u32, nfc: u32) -> Vec<Effect> {
-        self.input(Input::Card(CardRead { fob, nfc }))
-    }
-
-    fn sync(&mut self) -> Vec<Effect> {
-        self.input(Input::SyncComplete)
-    }
-}
-
 fn contains_open_door(effects: &[Effect]) -> bool {
     effects.iter().any(|e| matches!(e, Effect::OpenDoor))
 }
@@ -328,6 +275,88 @@ fn grant_after_sync_resets_failed_attempts() {
         "grant-after-sync must clear backoff_until alongside failed_attempts");
 }
 
+#[test]
+fn default_backoff_curve() {
+    let curve: Vec<u64> = (0..=10).map(|n| DenyBackoff::DEFAULT.delay_ms(n)).collect();
+    assert_eq!(
+        curve,
+        [1_000, 2_000, 4_000, 8_000, 8_000, 8_000, 8_000, 8_000, 8_000, 8_000, 8_000]
+    );
+    assert_eq!(AccessCore::new().deny_backoff(), DenyBackoff::default());
+}
+
+#[test]
+fn configured_backoff_curve() {
+    // Tripling from 500 ms for five denials, held to 60 s by the cap.
+    let backoff = DenyBackoff { base_ms: 500, multiplier: 3, steps: 5, max_ms: 60_000 };
+    let curve: Vec<u64> = (0..=10).map(|n| backoff.delay_ms(n)).collect();
+    assert_eq!(
+        curve,
+        [500, 1_500, 4_500, 13_500, 40_500, 60_000, 60_000, 60_000, 60_000, 60_000, 60_000]
+    );
+
+    // Flat delay, and a cap below the base.
+    let flat = DenyBackoff { base_ms: 3_000, multiplier: 1, steps: 10, max_ms: 60_000 };
+    assert!((0..=10).all(|n| flat.delay_ms(n) == 3_000));
+    let capped = DenyBackoff { max_ms: 250, ..DenyBackoff::DEFAULT };
+    assert!((0..=10).all(|n| capped.delay_ms(n) == 250));
+
+    // Huge settings saturate instead of overflowing.
+    let huge = DenyBackoff { base_ms: u64::MAX / 2, multiplier: u64::MAX, steps: 16, max_ms: u64::MAX };
+    assert_eq!(huge.delay_ms(255), u64::MAX);
+}
+
+#[test]
+fn core_applies_configured_backoff() {
+    let backoff = DenyBackoff { base_ms: 500, multiplier: 3, steps: 2, max_ms: 60_000 };
+    let mut s = Sim::new_standalone();
+    s.core = AccessCore::new().with_deny_backoff(backoff);
+    for want in [1_500u64, 4_500, 4_500, 4_500] {
+        s.tick(60_000);
+        s.card(42, 0);
+        assert_eq!(s.core.backoff_until() - s.now_ms, want);
+    }
+}
+
+#[test]
+fn deny_backoff_steps_knob() {
+    assert_eq!(DenyBackoff::parse_steps("0"), Some(0));
+    assert_eq!(DenyBackoff::parse_steps("5"), Some(5));
+    assert_eq!(DenyBackoff::parse_steps("16"), Some(MAX_BACKOFF_STEPS));
+    assert_eq!(DenyBackoff::parse_steps("17"), None);
+    assert_eq!(DenyBackoff::parse_steps("-1"), None);
+    assert_eq!(DenyBackoff::parse_steps(""), None);
+}
+
+#[test]
+fn credential_hint_selects_the_compared_form() {
+    let w = WiegandRead { facility: 12, card: 3456, raw_data: 0x0012_3456 };
+    for (hint, granted) in [
+        (None, [true, true, false]),
+        (Some(CredentialFormat::Fob), [true, false, false]),
+        (Some(CredentialFormat::Nfc4), [false, true, false]),
+        (Some(CredentialFormat::Nfc7), [false, false, true]),
+    ] {
+        for (listed, want) in [w.to_fob(), w.to_nfc_uid(), w.to_nfc7_uid()]
+            .into_iter()
+            .zip(granted)
+        {
+            let mut s = Sim::new();
+            s.add_fob(listed);
+            let eff = s.read(&w, hint);
+            assert_eq!(contains_open_door(&eff), want, "{:?} listing {:#x}", hint, listed);
+        }
+    }
+}
+
+#[test]
+fn credential_hint_records_the_hinted_form() {
+    let w = WiegandRead { facility: 12, card: 3456, raw_data: 0x0012_3456 };
+    let mut s = Sim::new();
+    let eff = s.read(&w, Some(CredentialFormat::Nfc7));
+    assert!(eff.contains(&Effect::Record(AccessEvent { fob: 0x0012_3456, allowed: false })));
+}
+
 // ---------------------------------------------------------------------------
 // WatchdogFeed sanity
 // ---------------------------------------------------------------------------
@@ -383,6 +412,27 @@ fn local_miss_falls_through_to_remote_grant() {
     assert!(contains_outcome(&eff, Outcome::Granted));
 }
 
+#[test]
+fn local_fob_survives_a_sync_that_drops_it() {
+    // The local list is the admin list: a sync replaces only the remote
+    // cache, so a fob Conway revokes (or never had) keeps working.
+    let mut s = Sim::new();
+    s.add_local_fob(42);
+    s.add_fob(42);
+    s.add_fob(43);
+    assert!(contains_open_door(&s.card(42, 0)));
+
+    s.tick(1_000);
+    s.fobs.clear();
+    s.sync();
+    let eff = s.card(42, 0);
+    assert!(contains_open_door(&eff), "a sync must not revoke a local fob");
+    assert!(!contains_request_sync(&eff));
+    s.tick(1_000);
+    let eff = s.card(43, 0);
+    assert!(!contains_open_door(&eff), "remote-only fob is gone with the list");
+}
+
 #[test]
 fn local_grant_works_in_standalone_mode() {
     let mut s = Sim::new_standalone();
@@ -429,6 +479,552 @@ fn standalone_sync_complete_is_a_noop_even_with_no_pending() {
     assert!(eff.is_empty());
 }
 
+// ---------------------------------------------------------------------------
+// Fob-vs-NFC matching priority
+// ---------------------------------------------------------------------------
+
+fn granted_credential(effects: &[Effect]) -> Option<u32> {
+    effects.iter().find_map(|e| match e {
+        Effect::Record(AccessEvent { fob, allowed: true }) => Some(*fob),
+        _ => None,
+    })
+}
+
+#[test]
+fn match_order_defaults_to_fob_first() {
+    assert_eq!(AccessCore::new().match_order(), MatchOrder::FobFirst);
+    assert_eq!(MatchOrder::parse("NFC"), Some(MatchOrder::NfcFirst));
+    assert_eq!(MatchOrder::parse("fob"), Some(MatchOrder::FobFirst));
+    assert_eq!(MatchOrder::parse("phone"), None);
+}
+
+#[test]
+fn fob_first_records_fob_when_both_forms_match() {
+    let mut s = Sim::with_match_order(MatchOrder::FobFirst);
+    s.add_fob(100);
+    s.add_fob(0xCAFEBABE);
+    let eff = s.card(100, 0xCAFEBABE);
+    assert!(contains_open_door(&eff));
+    assert_eq!(granted_credential(&eff), Some(100));
+}
+
+#[test]
+fn nfc_first_records_nfc_when_both_forms_match() {
+    let mut s = Sim::with_match_order(MatchOrder::NfcFirst);
+    s.add_fob(100);
+    s.add_fob(0xCAFEBABE);
+    let eff = s.card(100, 0xCAFEBABE);
+    assert!(contains_open_door(&eff));
+    assert_eq!(granted_credential(&eff), Some(0xCAFEBABE));
+}
+
+#[test]
+fn nfc_first_still_grants_on_fob_only_match() {
+    let mut s = Sim::with_match_order(MatchOrder::NfcFirst);
+    s.add_fob(100);
+    let eff = s.card(100, 0xCAFEBABE);
+    assert_eq!(granted_credential(&eff), Some(100));
+}
+
+#[test]
+fn nfc_first_does_not_override_local_precedence() {
+    // Local fob beats remote NFC: the order applies within each list.
+    let mut s = Sim::with_match_order(MatchOrder::NfcFirst);
+    s.add_local_fob(100);
+    s.add_fob(0xCAFEBABE);
+    let eff = s.card(100, 0xCAFEBABE);
+    assert_eq!(granted_credential(&eff), Some(100));
+}
+
+#[test]
+fn sync_grant_respects_match_order() {
+    for (order, want) in [(MatchOrder::FobFirst, 100), (MatchOrder::NfcFirst, 0xCAFEBABE)] {
+        let mut s = Sim::with_match_order(order);
+        s.card(100, 0xCAFEBABE); // denied
+        s.add_fob(100);
+        s.add_fob(0xCAFEBABE);
+        s.tick(1_000);
+        let eff = s.sync();
+        assert_eq!(granted_credential(&eff), Some(want), "order {:?}", order);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// A7: fail policy when no list has loaded
+// ---------------------------------------------------------------------------
+
+const HOUR_MS: u64 = 60 * 60 * 1000;
+
+fn contains_fail_open(effects: &[Effect]) -> bool {
+    effects.iter().any(|e| matches!(e, Effect::FailOpenGrant))
+}
+
+#[test]
+fn fail_closed_denies_without_list() {
+    let mut s = Sim::with_fail_policy(FailPolicy::Closed);
+    let eff = s.card(42, 0);
+    assert!(!contains_open_door(&eff));
+    assert!(contains_outcome(&eff, Outcome::Denied));
+    assert!(contains_request_sync(&eff));
+}
+
+#[test]
+fn fail_open_grants_within_window() {
+    let mut s = Sim::with_fail_policy(FailPolicy::OpenFor { window_ms: HOUR_MS });
+    s.tick(HOUR_MS - 1);
+    let eff = s.card(42, 0);
+    assert_eq!(
+        eff,
+        vec![
+            Effect::Record(AccessEvent { fob: 42, allowed: true }),
+            Effect::FailOpenGrant,
+            Effect::Feedback(Outcome::Granted),
+            Effect::OpenDoor,
+        ]
+    );
+    assert_eq!(s.core.failed_attempts(), 0);
+    assert_eq!(s.core.pending_recheck(), None);
+}
+
+#[test]
+fn fail_open_ends_with_window() {
+    let mut s = Sim::with_fail_policy(FailPolicy::OpenFor { window_ms: HOUR_MS });
+    s.tick(HOUR_MS);
+    let eff = s.card(42, 0);
+    assert!(!contains_open_door(&eff));
+    assert!(!contains_fail_open(&eff));
+    assert!(contains_outcome(&eff, Outcome::Denied));
+}
+
+#[test]
+fn fail_open_ends_once_list_loads() {
+    // Even an empty list from Conway is authoritative.
+    let mut s = Sim::with_fail_policy(FailPolicy::OpenFor { window_ms: HOUR_MS });
+    assert!(contains_fail_open(&s.card(42, 0)));
+    s.list_loaded = true;
+    s.tick(1_000);
+    let eff = s.card(42, 0);
+    assert!(!contains_open_door(&eff));
+    assert!(contains_outcome(&eff, Outcome::Denied));
+}
+
+#[test]
+fn fail_open_does_not_apply_standalone() {
+    // Standalone has no list to lose: local fobs are the whole truth.
+    let mut s = Sim::with_fail_policy(FailPolicy::OpenFor { window_ms: HOUR_MS });
+    s.conway_enabled = false;
+    let eff = s.card(42, 0);
+    assert!(!contains_open_door(&eff));
+}
+
+#[test]
+fn local_fob_is_not_a_fail_open_grant() {
+    // The local list is the outage admin list under either policy.
+    for policy in [FailPolicy::Closed, FailPolicy::OpenFor { window_ms: HOUR_MS }] {
+        let mut s = Sim::with_fail_policy(policy);
+        s.add_local_fob(7);
+        let eff = s.card(7, 0);
+        assert!(contains_open_door(&eff));
+        assert!(!contains_fail_open(&eff), "{:?}", policy);
+    }
+}
+
+#[test]
+fn fail_policy_knob() {
+    assert_eq!(FailPolicy::parse("0"), Some(FailPolicy::Closed));
+    assert_eq!(
+        FailPolicy::parse("3600"),
+        Some(FailPolicy::OpenFor { window_ms: HOUR_MS })
+    );
+    assert_eq!(
+        FailPolicy::parse("86400"),
+        Some(FailPolicy::OpenFor { window_ms: MAX_FAIL_OPEN_MS })
+    );
+    assert_eq!(FailPolicy::parse("86401"), None);
+    assert_eq!(FailPolicy::parse("-1"), None);
+    assert_eq!(FailPolicy::parse("1h"), None);
+    assert_eq!(FailPolicy::parse(&u64::MAX.to_string()), None);
+    assert_eq!(FailPolicy::default(), FailPolicy::Closed);
+}
+
+// ---------------------------------------------------------------------------
+// A8: foreign-facility fast deny
+// ---------------------------------------------------------------------------
+
+/// H10301 fob number for `facility`/`card`.
+fn h10301(facility: u32, card: u32) -> u32 {
+    facility * 100_000 + card
+}
+
+fn with_facilities(codes: &str) -> Sim {
+    let mut s = Sim::new();
+    s.core = AccessCore::new().with_known_facilities(KnownFacilities::parse(codes).unwrap());
+    s
+}
+
+#[test]
+fn foreign_facility_denies_without_sync() {
+    let mut s = with_facilities("12,34");
+    let eff = s.card(h10301(99, 1234), 0xDEAD_BEEF);
+    assert!(contains_outcome(&eff, Outcome::Denied));
+    assert!(!contains_request_sync(&eff));
+    assert_eq!(s.core.pending_recheck(), None);
+    // Backoff applies at once, as in standalone mode.
+    assert_eq!(s.core.failed_attempts(), 1);
+    assert!(s.core.backoff_until() > s.now_ms);
+    // Still reported to Conway.
+    assert!(eff.contains(&Effect::Record(AccessEvent { fob: h10301(99, 1234), allowed: false })));
+}
+
+#[test]
+fn known_facility_still_requests_sync() {
+    let mut s = with_facilities("12,34");
+    let eff = s.card(h10301(34, 1234), 0);
+    assert!(contains_request_sync(&eff));
+    assert!(s.core.pending_recheck().is_some());
+}
+
+#[test]
+fn foreign_facility_listed_still_grants() {
+    // The facility set only skips syncs; it never overrides a list.
+    let mut s = with_facilities("12");
+    s.add_fob(h10301(99, 1));
+    assert!(contains_open_door(&s.card(h10301(99, 1), 0)));
+    s.add_local_fob(0xDEAD_BEEF);
+    assert!(contains_open_door(&s.card(h10301(200, 5), 0xDEAD_BEEF)));
+}
+
+#[test]
+fn no_facilities_configured_syncs_every_denial() {
+    let mut s = Sim::new();
+    assert!(s.core.known_facilities().is_empty());
+    assert!(contains_request_sync(&s.card(h10301(99, 1234), 0)));
+}
+
+#[test]
+fn known_facilities_parse() {
+    let k = KnownFacilities::parse("0, 12,255").unwrap();
+    assert!(!k.is_foreign(h10301(0, 1)));
+    assert!(!k.is_foreign(h10301(12, 65535)));
+    assert!(!k.is_foreign(h10301(255, 0)));
+    assert!(k.is_foreign(h10301(13, 0)));
+    // Past any 8-bit facility: not an H10301 number from this site.
+    assert!(k.is_foreign(h10301(256, 0)));
+    assert!(k.is_foreign(u32::MAX));
+    for bad in ["", "12,", "256", "a", "1;2"] {
+        assert_eq!(KnownFacilities::parse(bad), None, "{:?}", bad);
+    }
+    assert!(!KnownFacilities::NONE.is_foreign(u32::MAX));
+}
+
+proptest! {
+    /// A8: an unlisted card from a facility outside the set never asks for
+    /// a sync or arms a recheck; one inside the set always does.
+    #[test]
+    fn prop_foreign_never_syncs(facility in 0u32..300, card in 0u32..65536, known in 0u8..=255) {
+        let mut s = with_facilities(&known.to_string());
+        let fob = h10301(facility, card);
+        let eff = s.card(fob, fob.swap_bytes());
+        prop_assert!(contains_outcome(&eff, Outcome::Denied));
+        let foreign = facility != u32::from(known);
+        prop_assert_eq!(contains_request_sync(&eff), !foreign);
+        prop_assert_eq!(s.core.pending_recheck().is_some(), !foreign);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// A9: unresolved-denials-only reporting
+// ---------------------------------------------------------------------------
+
+fn unresolved() -> Sim {
+    let mut s = Sim::new();
+    s.core = AccessCore::new().with_denial_report(DenialReport::Unresolved);
+    s
+}
+
+fn records(effects: &[Effect]) -> Vec<AccessEvent> {
+    effects
+        .iter()
+        .filter_map(|e| match e {
+            Effect::Record(ev) => Some(*ev),
+            _ => None,
+        })
+        .collect()
+}
+
+const DENIED_11: AccessEvent = AccessEvent { fob: 11, allowed: false };
+
+#[test]
+fn denial_superseded_by_grant_after_sync() {
+    let mut s = unresolved();
+    let eff = s.card(11, 22);
+    assert!(contains_outcome(&eff, Outcome::Denied));
+    assert!(contains_request_sync(&eff));
+    assert!(records(&eff).is_empty());
+    assert_eq!(s.core.held_denial(), Some(DENIED_11));
+
+    s.add_fob(11);
+    s.tick(500);
+    let eff = s.sync();
+    assert!(contains_open_door(&eff));
+    assert_eq!(records(&eff), [AccessEvent { fob: 11, allowed: true }]);
+    assert_eq!(s.core.held_denial(), None);
+
+    // Nothing left to record later.
+    s.tick(RECHECK_DEADLINE_MS);
+    assert!(records(&s.input(Input::WatchdogFeed)).is_empty());
+}
+
+#[test]
+fn denial_recorded_when_recheck_fails() {
+    let mut s = unresolved();
+    s.card(11, 22);
+    s.tick(500);
+    let eff = s.sync();
+    assert_eq!(records(&eff), [DENIED_11]);
+    assert!(contains_outcome(&eff, Outcome::Denied));
+    assert_eq!(s.core.held_denial(), None);
+}
+
+#[test]
+fn denial_recorded_when_recheck_expires() {
+    let mut s = unresolved();
+    s.card(11, 22);
+    s.tick(RECHECK_DEADLINE_MS);
+    assert!(records(&s.input(Input::WatchdogFeed)).is_empty());
+    s.tick(1);
+    let eff = s.input(Input::WatchdogFeed);
+    assert_eq!(eff, [Effect::FeedWatchdog, Effect::Record(DENIED_11)]);
+    // A late sync grants nothing and records nothing more.
+    s.add_fob(11);
+    let eff = s.sync();
+    assert!(eff.is_empty());
+}
+
+#[test]
+fn new_recheck_finalizes_held_denial() {
+    let mut s = unresolved();
+    s.card(11, 22);
+    s.tick(100);
+    let eff = s.card(33, 44);
+    assert_eq!(records(&eff), [DENIED_11]);
+    assert_eq!(s.core.held_denial(), Some(AccessEvent { fob: 33, allowed: false }));
+}
+
+#[test]
+fn denial_without_recheck_recorded_at_once() {
+    let mut s = unresolved();
+    s.conway_enabled = false;
+    assert_eq!(records(&s.card(11, 22)), [DENIED_11]);
+    assert_eq!(s.core.held_denial(), None);
+
+    let mut s = unresolved();
+    s.core = s.core.with_known_facilities(KnownFacilities::parse("12").unwrap());
+    // A foreign card leaves the held denial of a pending recheck alone.
+    s.card(h10301(12, 1), 0);
+    let eff = s.card(h10301(99, 1), 0);
+    assert_eq!(records(&eff), [AccessEvent { fob: h10301(99, 1), allowed: false }]);
+    assert_eq!(s.core.held_denial(), Some(AccessEvent { fob: h10301(12, 1), allowed: false }));
+}
+
+#[test]
+fn denial_report_parse() {
+    assert_eq!(DenialReport::parse("all"), Some(DenialReport::All));
+    assert_eq!(DenialReport::parse("Unresolved"), Some(DenialReport::Unresolved));
+    assert_eq!(DenialReport::parse("denied"), None);
+    assert_eq!(AccessCore::new().denial_report(), DenialReport::All);
+}
+
+#[derive(Clone, Debug)]
+enum ReportStep {
+    Card(u32),
+    Sync,
+    Tick(u64),
+    AddFob(u32),
+}
+
+fn report_step() -> impl Strategy<Value = ReportStep> {
+    prop_oneof![
+        (0u32..4).prop_map(ReportStep::Card),
+        Just(ReportStep::Sync),
+        (0u64..15_000).prop_map(ReportStep::Tick),
+        (0u32..4).prop_map(ReportStep::AddFob),
+    ]
+}
+
+proptest! {
+    /// A9: run the same trace under both reportings. Decisions match,
+    /// grants are recorded alike, and once every recheck has run out the
+    /// denials missing under `Unresolved` are exactly those a sync turned
+    /// into grants.
+    #[test]
+    fn prop_unresolved_drops_only_superseded(steps in proptest::collection::vec(report_step(), 0..40)) {
+        let mut all = Sim::new();
+        let mut un = unresolved();
+        let (mut all_recs, mut un_recs) = (Vec::new(), Vec::new());
+        let mut sync_grants = 0;
+        for step in steps.iter().chain([ReportStep::Tick(RECHECK_DEADLINE_MS + 1), ReportStep::Sync].iter()) {
+            match *step {
+                ReportStep::Card(f) => {
+                    let a = all.card(f, f + 100);
+                    let u = un.card(f, f + 100);
+                    prop_assert_eq!(contains_open_door(&a), contains_open_door(&u));
+                    all_recs.extend(records(&a));
+                    un_recs.extend(records(&u));
+                }
+                ReportStep::Sync => {
+                    let a = all.sync();
+                    let u = un.sync();
+                    prop_assert_eq!(contains_open_door(&a), contains_open_door(&u));
+                    if contains_open_door(&a) {
+                        sync_grants += 1;
+                    }
+                    all_recs.extend(records(&a));
+                    un_recs.extend(records(&u));
+                }
+                ReportStep::Tick(dt) => {
+                    all.tick(dt);
+                    un.tick(dt);
+                }
+                ReportStep::AddFob(f) => {
+                    all.add_fob(f);
+                    un.add_fob(f);
+                }
+            }
+        }
+        prop_assert_eq!(un.core.held_denial(), None);
+        let grants = |recs: &[AccessEvent]| recs.iter().filter(|e| e.allowed).copied().collect::<Vec<_>>();
+        prop_assert_eq!(grants(&all_recs), grants(&un_recs));
+        let denials = |recs: &[AccessEvent]| recs.iter().filter(|e| !e.allowed).count();
+        prop_assert_eq!(denials(&all_recs) - denials(&un_recs), sync_grants);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// A10: per-reader backoff
+// ---------------------------------------------------------------------------
+
+#[test]
+fn one_readers_denials_leave_the_other_responsive() {
+    let mut s = Sim::new_standalone();
+    s.add_local_fob(7);
+    for _ in 0..5 {
+        let eff = s.card_at(Reader::Wiegand, 99, 99);
+        assert!(contains_outcome(&eff, Outcome::Denied));
+        s.tick(s.core.backoff_until_at(Reader::Wiegand) - s.now_ms);
+    }
+    let _ = s.card_at(Reader::Wiegand, 99, 99);
+    assert_eq!(s.core.failed_attempts_at(Reader::Wiegand), 6);
+    assert_eq!(s.core.backoff_until_at(Reader::Wiegand), s.now_ms + 8_000);
+    // The Wiegand reader is locked out...
+    assert!(s.card_at(Reader::Wiegand, 7, 7).is_empty());
+    // ...but the OSDP reader answers at once, with no backoff of its own.
+    assert_eq!(s.core.failed_attempts_at(Reader::Osdp), 0);
+    assert_eq!(s.core.backoff_until_at(Reader::Osdp), 0);
+    assert!(contains_open_door(&s.card_at(Reader::Osdp, 7, 7)));
+    let eff = s.card_at(Reader::Osdp, 99, 99);
+    assert!(contains_outcome(&eff, Outcome::Denied));
+    assert_eq!(s.core.failed_attempts_at(Reader::Osdp), 1);
+    assert_eq!(s.core.backoff_until_at(Reader::Osdp), s.now_ms + 2_000);
+
+    // A grant at one reader doesn't forgive the other.
+    s.tick(2_000);
+    assert!(contains_open_door(&s.card_at(Reader::Osdp, 7, 7)));
+    assert_eq!(s.core.failed_attempts_at(Reader::Osdp), 0);
+    assert_eq!(s.core.failed_attempts_at(Reader::Wiegand), 6);
+}
+
+#[test]
+fn failed_recheck_backs_off_the_presenting_reader() {
+    let mut s = Sim::new();
+    let eff = s.card_at(Reader::Osdp, 99, 99);
+    assert!(contains_request_sync(&eff));
+    s.tick(500);
+    let eff = s.sync();
+    assert!(contains_outcome(&eff, Outcome::Denied));
+    assert_eq!(s.core.failed_attempts_at(Reader::Osdp), 1);
+    assert_eq!(s.core.backoff_until_at(Reader::Osdp), s.now_ms + 2_000);
+    assert_eq!(s.core.failed_attempts_at(Reader::Wiegand), 0);
+    s.add_fob(7);
+    assert!(contains_open_door(&s.card_at(Reader::Wiegand, 7, 7)));
+}
+
+#[test]
+fn recheck_grant_clears_the_presenting_readers_backoff() {
+    let mut s = Sim::new();
+    // Two failed rechecks at the OSDP reader.
+    for _ in 0..2 {
+        s.tick(
+            s.core
+                .backoff_until_at(Reader::Osdp)
+                .saturating_sub(s.now_ms),
+        );
+        let _ = s.card_at(Reader::Osdp, 42, 42);
+        let _ = s.sync();
+    }
+    assert_eq!(s.core.failed_attempts_at(Reader::Osdp), 2);
+    s.tick(s.core.backoff_until_at(Reader::Osdp) - s.now_ms);
+    let _ = s.card_at(Reader::Osdp, 42, 42);
+    s.add_fob(42);
+    assert!(contains_open_door(&s.sync()));
+    assert_eq!(s.core.failed_attempts_at(Reader::Osdp), 0);
+}
+
+#[test]
+fn a_denial_at_one_reader_keeps_the_others_recheck() {
+    let mut s = Sim::new();
+    let _ = s.card_at(Reader::Osdp, 42, 42);
+    s.tick(200);
+    let _ = s.card_at(Reader::Wiegand, 99, 99);
+    assert_eq!(s.core.pending_recheck_at(Reader::Osdp).map(|p| p.0), Some(42));
+    s.add_fob(42);
+    s.tick(300);
+    let eff = s.sync();
+    // OSDP gets its retroactive grant; Wiegand's recheck fails and backs
+    // off only there.
+    assert!(contains_open_door(&eff));
+    assert!(contains_outcome(&eff, Outcome::Granted));
+    assert!(contains_outcome(&eff, Outcome::Denied));
+    assert_eq!(s.core.failed_attempts_at(Reader::Osdp), 0);
+    assert_eq!(s.core.failed_attempts_at(Reader::Wiegand), 1);
+    assert_eq!(s.core.pending_recheck_at(Reader::Osdp), None);
+    assert_eq!(s.core.pending_recheck_at(Reader::Wiegand), None);
+}
+
+proptest! {
+    /// Whatever happens at the Wiegand reader, every OSDP step has the
+    /// effects it would have had with the Wiegand reader unused.
+    #[test]
+    fn readers_are_isolated(
+        steps in prop::collection::vec(
+            (any::<bool>(), prop_oneof![Just(7u32), Just(8u32), 0u32..4], 0u32..6_000),
+            1..80,
+        ),
+    ) {
+        let mut both = Sim::new_standalone();
+        let mut osdp_only = Sim::new_standalone();
+        for s in [&mut both, &mut osdp_only] {
+            s.add_local_fob(7);
+            s.add_local_fob(8);
+        }
+        for (at_osdp, fob, dt_ms) in steps {
+            both.tick(u64::from(dt_ms));
+            osdp_only.tick(u64::from(dt_ms));
+            if at_osdp {
+                let got = both.card_at(Reader::Osdp, fob, fob);
+                let want = osdp_only.card_at(Reader::Osdp, fob, fob);
+                prop_assert_eq!(got, want);
+            } else {
+                let _ = both.card_at(Reader::Wiegand, fob, fob);
+            }
+            prop_assert_eq!(
+                both.core.backoff_until_at(Reader::Osdp),
+                osdp_only.core.backoff_until_at(Reader::Osdp)
+            );
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Property tests (A1, A2, A3, A4, A5 together)
 // ---------------------------------------------------------------------------
@@ -636,4 +1232,40 @@ proptest! {
             }
         }
     }
+
+    /// A7: under fail-open, every door opening is either a list hit or a
+    /// flagged fail-open grant made while no list was loaded and inside
+    /// the window.
+    #[test]
+    fn prop_fail_open_is_bounded_and_flagged(
+        trace in arb_trace(),
+        window_s in 1u64..120,
+        load_at in 0usize..80,
+    ) {
+        let window_ms = window_s * 1000;
+        let mut s = Sim::with_fail_policy(FailPolicy::OpenFor { window_ms });
+        for (i, step) in trace.into_iter().enumerate() {
+            if i == load_at {
+                s.list_loaded = true;
+            }
+            match step {
+                Step::AddFob { fob } => s.add_fob(fob),
+                Step::RemoveFob { fob } => s.remove_fob(fob),
+                Step::Card { fob, nfc, dt_ms } => {
+                    s.tick(dt_ms as u64);
+                    let listed = s.fobs.contains(&fob) || s.fobs.contains(&nfc);
+                    let eff = s.card(fob, nfc);
+                    if contains_fail_open(&eff) {
+                        prop_assert!(!s.list_loaded && s.now_ms < window_ms);
+                        prop_assert!(!listed);
+                        prop_assert!(contains_open_door(&eff));
+                    } else if contains_open_door(&eff) {
+                        prop_assert!(listed);
+                    }
+                }
+                Step::Sync { dt_ms } => { s.tick(dt_ms as u64); let _ = s.sync(); }
+                Step::Watchdog { dt_ms } => { s.tick(dt_ms as u64); let _ = s.input(Input::WatchdogFeed); }
+            }
+        }
+    }
 }
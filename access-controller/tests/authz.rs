@@ -0,0 +1,587 @@
+//! Tests for the online authorization check (invariants U1–U7).
+//!
+//!   U1: the path, timeout and TTL knobs accept exactly the documented
+//!       forms.
+//!   U2: the query names the fob form, and the NFC form only when it
+//!       differs, appended to any query the path already has.
+//!   U3: only a `200` whose body is `allow` allows; a `deny` body or a
+//!       `403` denies; anything else is unavailable.
+//!   U4: with the check on, a miss is held for the answer instead of
+//!       denied; `allow` grants and is recorded as a grant, while `deny`
+//!       or no usable answer takes the usual denial path.
+//!   U5: an answer that comes too late, or for another read, grants
+//!       nothing; a query nobody answers is recorded as a denial once it
+//!       times out, and reads at its reader are ignored only until then.
+//!       The other reader carries on, with a check of its own.
+//!   U6: a read the server refused is denied without another query until
+//!       the TTL runs out, and asked about again after, at either reader;
+//!       a missing answer is not remembered, and a TTL of 0 remembers
+//!       nothing.
+//!   U7: the negative cache remembers up to its slot count; a repeat
+//!       refusal restarts the TTL, and a full cache forgets the entry
+//!       closest to expiry.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test authz
+
+#![cfg(feature = "sim")]
+
+use access_controller::authz::{
+    self, NegativeCache, Verdict, ANSWER_GRACE_MS, DEFAULT_DENY_TTL_SECS, DEFAULT_TIMEOUT_MS,
+    MAX_DENY_TTL_SECS, MAX_TIMEOUT_MS, MIN_TIMEOUT_MS, NEGATIVE_CACHE_SLOTS,
+};
+use access_controller::core::{
+    AccessCore, CardRead, Effect, Input, KnownFacilities, MatchOrder, Outcome, Reader,
+    MAX_EFFECTS_PER_STEP,
+};
+use access_controller::events::AccessEvent;
+use proptest::prelude::*;
+
+mod sim;
+use sim::Sim;
+
+const TIMEOUT_MS: u64 = 1_000;
+const LISTED: CardRead = CardRead {
+    fob: 11,
+    nfc: 12,
+    reader: Reader::Wiegand,
+};
+const UNLISTED: CardRead = CardRead {
+    fob: 21,
+    nfc: 22,
+    reader: Reader::Wiegand,
+};
+
+fn checking() -> Sim {
+    let mut s = Sim::new().with_online_check(TIMEOUT_MS);
+    s.add_fob(LISTED.fob);
+    s
+}
+
+fn answer(read: CardRead, verdict: Verdict) -> Input {
+    Input::AuthzAnswer { read, verdict }
+}
+
+fn request(path: &str, read: CardRead) -> String {
+    let mut out = String::new();
+    authz::write_request(&mut out, path, "10.0.0.1", read).unwrap();
+    out
+}
+
+fn verdict(raw: &str) -> Verdict {
+    authz::parse_response(&mut raw.as_bytes().to_vec())
+}
+
+fn denied(read: CardRead) -> Effect {
+    Effect::Record(AccessEvent {
+        fob: read.fob,
+        allowed: false,
+    })
+}
+
+// ---------- U1 ----------
+
+#[test]
+fn u1_timeout() {
+    assert_eq!(authz::parse_timeout_ms("100"), Some(MIN_TIMEOUT_MS));
+    assert_eq!(authz::parse_timeout_ms("1500"), Some(DEFAULT_TIMEOUT_MS));
+    assert_eq!(authz::parse_timeout_ms("5000"), Some(MAX_TIMEOUT_MS));
+    for bad in ["99", "5001", "0", "", "-1", "1.5", "1s"] {
+        assert_eq!(authz::parse_timeout_ms(bad), None, "{:?}", bad);
+    }
+}
+
+#[test]
+fn u1_deny_ttl() {
+    assert_eq!(authz::parse_deny_ttl_secs("0"), Some(0));
+    assert_eq!(
+        authz::parse_deny_ttl_secs("30"),
+        Some(DEFAULT_DENY_TTL_SECS)
+    );
+    assert_eq!(authz::parse_deny_ttl_secs("600"), Some(MAX_DENY_TTL_SECS));
+    for bad in ["601", "-1", "", "30s", "1.5"] {
+        assert_eq!(authz::parse_deny_ttl_secs(bad), None, "{:?}", bad);
+    }
+}
+
+#[test]
+fn u1_path() {
+    assert_eq!(authz::parse_path("/api/authz"), Some("/api/authz"));
+    assert_eq!(
+        authz::parse_path("/api/authz?door=3"),
+        Some("/api/authz?door=3")
+    );
+    for bad in ["", "api/authz", "/api authz", "/api/\r\nX: 1"] {
+        assert_eq!(authz::parse_path(bad), None, "{:?}", bad);
+    }
+}
+
+// ---------- U2 ----------
+
+#[test]
+fn u2_request() {
+    assert_eq!(
+        request("/api/authz", UNLISTED),
+        "GET /api/authz?fob=21&nfc=22 HTTP/1.0\r\n\
+         Host: 10.0.0.1\r\n\
+         Accept: text/plain\r\n\
+         \r\n"
+    );
+    let hinted = CardRead {
+        fob: 7,
+        nfc: 7,
+        ..LISTED
+    };
+    assert!(request("/api/authz", hinted).starts_with("GET /api/authz?fob=7 HTTP/1.0\r\n"));
+    assert!(request("/api/authz?door=3", hinted)
+        .starts_with("GET /api/authz?door=3&fob=7 HTTP/1.0\r\n"));
+}
+
+// ---------- U3 ----------
+
+#[test]
+fn u3_parse() {
+    let ok = |body: &str| {
+        format!(
+            "HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\n{}",
+            body
+        )
+    };
+    assert_eq!(verdict(&ok("allow")), Verdict::Allow);
+    assert_eq!(verdict(&ok(" ALLOW\r\n")), Verdict::Allow);
+    assert_eq!(verdict(&ok("deny")), Verdict::Deny);
+    assert_eq!(verdict(&ok("Deny\n")), Verdict::Deny);
+    assert_eq!(
+        verdict("HTTP/1.1 403 Forbidden\r\n\r\nallow"),
+        Verdict::Deny
+    );
+    for unusable in [
+        ok(""),
+        ok("allowed"),
+        ok("{\"allow\":true}"),
+        "HTTP/1.0 404 Not Found\r\n\r\n".to_string(),
+        "HTTP/1.0 500 Internal Server Error\r\n\r\nallow".to_string(),
+        "HTTP/1.0 302 Found\r\nLocation: /x\r\n\r\n".to_string(),
+        "HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n".to_string(),
+        String::new(),
+        "garbage".to_string(),
+    ] {
+        assert_eq!(verdict(&unusable), Verdict::Unavailable, "{:?}", unusable);
+    }
+}
+
+proptest! {
+    #[test]
+    fn u3_only_allow_allows(raw in prop::collection::vec(any::<u8>(), 0..256)) {
+        let mut buf = raw.clone();
+        if authz::parse_response(&mut buf) == Verdict::Allow {
+            let text = String::from_utf8_lossy(&raw);
+            prop_assert!(text.contains(" 200"));
+            prop_assert!(text.to_ascii_lowercase().contains("allow"));
+        }
+    }
+}
+
+// ---------- U4 ----------
+
+#[test]
+fn u4_miss_waits_for_the_answer() {
+    let mut s = checking();
+    assert_eq!(s.core.online_check_ms(), Some(TIMEOUT_MS));
+    let eff = s.input_at(1_000, Input::Card(UNLISTED));
+    assert_eq!(eff, vec![Effect::QueryAuthz(UNLISTED)]);
+    assert_eq!(
+        s.core.pending_query(Reader::Wiegand),
+        Some((UNLISTED, 1_000 + TIMEOUT_MS + ANSWER_GRACE_MS))
+    );
+
+    let eff = s.input_at(1_400, answer(UNLISTED, Verdict::Allow));
+    assert_eq!(
+        eff,
+        vec![
+            Effect::Record(AccessEvent {
+                fob: UNLISTED.fob,
+                allowed: true,
+            }),
+            Effect::Feedback(Outcome::Granted),
+            Effect::OpenDoor,
+        ]
+    );
+    assert_eq!(s.core.pending_query(Reader::Wiegand), None);
+    assert_eq!(s.core.failed_attempts(), 0);
+}
+
+#[test]
+fn u4_allow_records_the_preferred_form() {
+    let mut s = Sim::with_match_order(MatchOrder::NfcFirst).with_online_check(TIMEOUT_MS);
+    s.input_at(0, Input::Card(UNLISTED));
+    let eff = s.input_at(10, answer(UNLISTED, Verdict::Allow));
+    assert_eq!(
+        eff[0],
+        Effect::Record(AccessEvent {
+            fob: UNLISTED.nfc,
+            allowed: true,
+        })
+    );
+}
+
+#[test]
+fn u4_deny_and_unavailable_deny_as_usual() {
+    for v in [Verdict::Deny, Verdict::Unavailable] {
+        let mut s = checking();
+        s.input_at(0, Input::Card(UNLISTED));
+        let eff = s.input_at(500, answer(UNLISTED, v));
+        assert_eq!(
+            eff,
+            vec![
+                denied(UNLISTED),
+                Effect::Feedback(Outcome::Denied),
+                Effect::RequestSync,
+            ],
+            "{:?}",
+            v
+        );
+        assert_eq!(s.core.pending_query(Reader::Wiegand), None);
+        assert!(s.core.pending_recheck().is_some());
+    }
+}
+
+#[test]
+fn u4_only_misses_are_checked() {
+    // Listed: granted without asking.
+    let mut s = checking();
+    let eff = s.input_at(0, Input::Card(LISTED));
+    assert!(eff.contains(&Effect::OpenDoor));
+    assert!(!eff.iter().any(|e| matches!(e, Effect::QueryAuthz(_))));
+
+    // Check off: denied at once.
+    let mut s = Sim::new();
+    assert_eq!(s.core.online_check_ms(), None);
+    let eff = s.input_at(0, Input::Card(UNLISTED));
+    assert!(eff.contains(&Effect::Feedback(Outcome::Denied)));
+    assert!(!eff.iter().any(|e| matches!(e, Effect::QueryAuthz(_))));
+
+    // Standalone: nobody to ask.
+    let mut s = Sim::new_standalone().with_online_check(TIMEOUT_MS);
+    let eff = s.input_at(0, Input::Card(UNLISTED));
+    assert!(eff.contains(&Effect::Feedback(Outcome::Denied)));
+    assert_eq!(s.core.pending_query(Reader::Wiegand), None);
+
+    // A foreign facility: denied on the spot, as without the check.
+    let foreign = CardRead {
+        fob: 99 * 100_000 + 5,
+        nfc: 1,
+        reader: Reader::Wiegand,
+    };
+    let mut s = checking();
+    s.core = AccessCore::new()
+        .with_online_check(TIMEOUT_MS)
+        .with_known_facilities(KnownFacilities::parse("12").unwrap());
+    let eff = s.input_at(0, Input::Card(foreign));
+    assert!(eff.contains(&Effect::Feedback(Outcome::Denied)));
+    assert_eq!(s.core.pending_query(Reader::Wiegand), None);
+}
+
+// ---------- U5 ----------
+
+#[test]
+fn u5_late_or_mismatched_answers_grant_nothing() {
+    let deadline = TIMEOUT_MS + ANSWER_GRACE_MS;
+
+    let mut s = checking();
+    s.input_at(0, Input::Card(UNLISTED));
+    assert!(s.input_at(10, answer(LISTED, Verdict::Allow)).is_empty());
+    assert_eq!(
+        s.core.pending_query(Reader::Wiegand).map(|(r, _)| r),
+        Some(UNLISTED)
+    );
+    // On time, to the millisecond, still counts.
+    let eff = s.input_at(deadline, answer(UNLISTED, Verdict::Allow));
+    assert!(eff.contains(&Effect::OpenDoor));
+
+    let mut s = checking();
+    s.input_at(0, Input::Card(UNLISTED));
+    let eff = s.input_at(deadline + 1, answer(UNLISTED, Verdict::Allow));
+    assert_eq!(eff, vec![denied(UNLISTED)]);
+    assert_eq!(s.core.pending_query(Reader::Wiegand), None);
+}
+
+#[test]
+fn u5_unanswered_query_times_out_to_a_denial() {
+    let deadline = TIMEOUT_MS + ANSWER_GRACE_MS;
+    let mut s = checking();
+    s.input_at(0, Input::Card(UNLISTED));
+
+    // Swipes while it runs are ignored, so they can't pile up queries.
+    assert!(s.input_at(200, Input::Card(UNLISTED)).is_empty());
+    assert!(s.input_at(deadline, Input::Card(LISTED)).is_empty());
+    assert_eq!(
+        s.core.pending_query(Reader::Wiegand).map(|(r, _)| r),
+        Some(UNLISTED)
+    );
+
+    // Past the deadline the query is a recorded denial, and the next
+    // read is decided as normal.
+    let eff = s.input_at(deadline + 1, Input::Card(LISTED));
+    assert_eq!(eff[0], denied(UNLISTED));
+    assert!(eff.contains(&Effect::OpenDoor));
+    assert_eq!(s.core.pending_query(Reader::Wiegand), None);
+
+    // Any input ends an expired query.
+    let mut s = checking();
+    s.input_at(0, Input::Card(UNLISTED));
+    let eff = s.input_at(deadline + 1, Input::SyncComplete);
+    assert_eq!(eff, vec![denied(UNLISTED)]);
+}
+
+#[test]
+fn u5_each_reader_has_its_own_check() {
+    let at_osdp = UNLISTED.at(Reader::Osdp);
+    let mut s = checking();
+    s.input_at(0, Input::Card(UNLISTED));
+
+    // The OSDP reader still grants, and asks about a miss of its own.
+    let eff = s.input_at(10, Input::Card(LISTED.at(Reader::Osdp)));
+    assert!(eff.contains(&Effect::OpenDoor));
+    assert_eq!(
+        s.input_at(20, Input::Card(at_osdp)),
+        vec![Effect::QueryAuthz(at_osdp)]
+    );
+
+    // Each answer goes to its own reader's read.
+    let eff = s.input_at(30, answer(at_osdp, Verdict::Allow));
+    assert!(eff.contains(&Effect::OpenDoor));
+    assert_eq!(s.core.pending_query(Reader::Osdp), None);
+    assert_eq!(
+        s.core.pending_query(Reader::Wiegand).map(|(r, _)| r),
+        Some(UNLISTED)
+    );
+    let eff = s.input_at(40, answer(UNLISTED, Verdict::Deny));
+    assert!(eff.contains(&Effect::Feedback(Outcome::Denied)));
+}
+
+proptest! {
+    #[test]
+    fn u5_grants_only_from_the_list_or_a_timely_allow(
+        events in prop::collection::vec(
+            (0u64..2_000, 0u8..4, any::<bool>(), 0u8..3),
+            1..40,
+        ),
+    ) {
+        let mut s = checking();
+        let mut now = 0;
+        for (dt, kind, which, v) in events {
+            now += dt;
+            let read = if which { UNLISTED } else { LISTED };
+            let pending = s.core.pending_query(Reader::Wiegand);
+            let verdict = [Verdict::Allow, Verdict::Deny, Verdict::Unavailable][usize::from(v)];
+            let input = match kind {
+                0 | 1 => Input::Card(read),
+                2 => answer(read, verdict),
+                _ => Input::SyncComplete,
+            };
+            let eff = s.input_at(now, input);
+            prop_assert!(eff.len() <= MAX_EFFECTS_PER_STEP);
+            if eff.contains(&Effect::OpenDoor) {
+                let listed = matches!(input, Input::Card(r) if r == LISTED);
+                let timely_allow = matches!(input, Input::AuthzAnswer { read: r, verdict: Verdict::Allow }
+                    if pending.is_some_and(|(p, deadline)| p == r && now <= deadline));
+                let recheck = matches!(input, Input::SyncComplete);
+                prop_assert!(listed || timely_allow || recheck, "{:?}", input);
+            }
+            if let Input::Card(_) = input {
+                if pending.is_some_and(|(_, deadline)| now <= deadline) {
+                    prop_assert!(eff.is_empty());
+                }
+            }
+        }
+    }
+}
+
+// ---------- U6 ----------
+
+/// Swipe `read` and have the server refuse it with `verdict`.
+fn refuse(s: &mut Sim, now_ms: u64, read: CardRead, verdict: Verdict) {
+    assert_eq!(
+        s.input_at(now_ms, Input::Card(read)),
+        vec![Effect::QueryAuthz(read)]
+    );
+    let eff = s.input_at(now_ms + 100, answer(read, verdict));
+    assert!(eff.contains(&Effect::Feedback(Outcome::Denied)));
+}
+
+fn queries(eff: &[Effect]) -> bool {
+    eff.iter().any(|e| matches!(e, Effect::QueryAuthz(_)))
+}
+
+#[test]
+fn u6_refusal_is_remembered_for_the_ttl() {
+    const TTL_MS: u64 = 5_000;
+    let mut s = checking();
+    s.core = AccessCore::new()
+        .with_online_check(TIMEOUT_MS)
+        .with_refusal_ttl(TTL_MS);
+    assert_eq!(s.core.refused().ttl_ms(), TTL_MS);
+    refuse(&mut s, 0, UNLISTED, Verdict::Deny);
+    assert!(s.core.refused().contains(UNLISTED, 100));
+
+    // Swiped again within the TTL: denied at once, nobody asked.
+    let eff = s.input_at(2_000, Input::Card(UNLISTED));
+    assert!(!queries(&eff));
+    assert!(eff.contains(&Effect::Feedback(Outcome::Denied)));
+    assert_eq!(s.core.pending_query(Reader::Wiegand), None);
+    let eff = s.input_at(100 + TTL_MS - 1, Input::Card(UNLISTED));
+    assert!(!queries(&eff));
+
+    // The same card at the other reader is not asked about either.
+    let eff = s.input_at(100 + TTL_MS - 1, Input::Card(UNLISTED.at(Reader::Osdp)));
+    assert!(!queries(&eff));
+
+    // Another unknown card is still asked about.
+    let other = CardRead {
+        fob: 31,
+        nfc: 32,
+        ..LISTED
+    };
+    assert_eq!(
+        s.input_at(100 + TTL_MS - 1, Input::Card(other)),
+        vec![Effect::QueryAuthz(other)]
+    );
+    s.input_at(100 + TTL_MS, answer(other, Verdict::Allow));
+
+    // Once the TTL runs out it is asked about again.
+    let eff = s.input_at(100 + TTL_MS, Input::Card(UNLISTED));
+    assert_eq!(eff, vec![Effect::QueryAuthz(UNLISTED)]);
+}
+
+#[test]
+fn u6_default_ttl() {
+    let s = checking();
+    assert_eq!(s.core.refused().ttl_ms(), DEFAULT_DENY_TTL_SECS * 1000);
+}
+
+#[test]
+fn u6_unanswered_and_zero_ttl_are_not_remembered() {
+    let mut s = checking();
+    refuse(&mut s, 0, UNLISTED, Verdict::Unavailable);
+    assert!(!s.core.refused().contains(UNLISTED, 200));
+    assert_eq!(
+        s.input_at(200, Input::Card(UNLISTED)),
+        vec![Effect::QueryAuthz(UNLISTED)]
+    );
+
+    let mut s = checking();
+    s.core = AccessCore::new()
+        .with_online_check(TIMEOUT_MS)
+        .with_refusal_ttl(0);
+    refuse(&mut s, 0, UNLISTED, Verdict::Deny);
+    assert_eq!(
+        s.input_at(200, Input::Card(UNLISTED)),
+        vec![Effect::QueryAuthz(UNLISTED)]
+    );
+}
+
+#[test]
+fn u6_listed_cards_ignore_the_cache() {
+    // A card refused online and then synced in is granted from the list.
+    let mut s = checking();
+    refuse(&mut s, 0, UNLISTED, Verdict::Deny);
+    s.add_fob(UNLISTED.fob);
+    let eff = s.input_at(200, Input::Card(UNLISTED));
+    assert!(eff.contains(&Effect::OpenDoor));
+}
+
+// ---------- U7 ----------
+
+fn card(n: u32) -> CardRead {
+    CardRead {
+        fob: n,
+        nfc: n + 1,
+        ..LISTED
+    }
+}
+
+#[test]
+fn u7_cache_expiry_and_refresh() {
+    let mut cache = NegativeCache::new(1_000);
+    cache.insert(card(1), 0);
+    assert!(cache.contains(card(1), 0));
+    assert!(cache.contains(card(1), 999));
+    assert!(!cache.contains(card(1), 1_000));
+    // The NFC form alone is a different read.
+    assert!(!cache.contains(
+        CardRead {
+            fob: 1,
+            nfc: 1,
+            ..LISTED
+        },
+        0
+    ));
+
+    cache.insert(card(1), 800);
+    assert!(cache.contains(card(1), 1_799));
+    assert!(!cache.contains(card(1), 1_800));
+
+    let mut off = NegativeCache::new(0);
+    off.insert(card(1), 0);
+    assert!(!off.contains(card(1), 0));
+}
+
+#[test]
+fn u7_full_cache_forgets_the_oldest() {
+    let mut cache = NegativeCache::new(10_000);
+    for n in 0..NEGATIVE_CACHE_SLOTS as u32 {
+        cache.insert(card(n * 10), u64::from(n));
+    }
+    // Refreshing the first makes the second the closest to expiry.
+    cache.insert(card(0), 100);
+    cache.insert(card(1_000), 101);
+    assert!(cache.contains(card(1_000), 101));
+    assert!(cache.contains(card(0), 101));
+    assert!(!cache.contains(card(10), 101));
+    for n in 2..NEGATIVE_CACHE_SLOTS as u32 {
+        assert!(cache.contains(card(n * 10), 101));
+    }
+}
+
+proptest! {
+    #[test]
+    fn u7_cache_matches_a_model(
+        ops in prop::collection::vec((0u32..12, 0u64..3_000, any::<bool>()), 1..60),
+    ) {
+        const TTL: u64 = 2_000;
+        let mut cache = NegativeCache::new(TTL);
+        // Every refusal ever made: a remembered read was refused within
+        // the TTL, and the most recent refusals are never forgotten.
+        let mut refused: Vec<(CardRead, u64)> = Vec::new();
+        let mut now = 0;
+        for (n, dt, insert) in ops {
+            now += dt;
+            let read = card(n);
+            if insert {
+                cache.insert(read, now);
+                refused.push((read, now));
+            }
+            for n in 0..12 {
+                let r = card(n);
+                let last = refused.iter().rev().find(|(x, _)| *x == r).map(|&(_, at)| at);
+                let live = last.is_some_and(|at| now < at + TTL);
+                if cache.contains(r, now) {
+                    prop_assert!(live);
+                }
+                // Among live reads, the newest slot-count are kept.
+                let mut live_reads: Vec<(CardRead, u64)> = Vec::new();
+                for &(x, at) in refused.iter().rev() {
+                    if now < at + TTL && !live_reads.iter().any(|(y, _)| *y == x) {
+                        live_reads.push((x, at));
+                    }
+                }
+                if live_reads.iter().take(NEGATIVE_CACHE_SLOTS).any(|(x, _)| *x == r) {
+                    prop_assert!(cache.contains(r, now));
+                }
+            }
+        }
+    }
+}
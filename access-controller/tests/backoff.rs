@@ -0,0 +1,146 @@
+//! Tests for the retry backoff and the fake clock (invariants B1–B4).
+//!
+//!   B1: delays double from `min_ms` and stay at `max_ms` once reached.
+//!   B2: `reset` starts the progression over from `min_ms`.
+//!   B3: `ready` is false until the scheduled deadline and true from it.
+//!   B4: the access core's denial backoff, driven by a fake clock, grows
+//!       2 s, 4 s, then 8 s and drops reads until each window ends.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test backoff
+
+#![cfg(feature = "sim")]
+
+use access_controller::backoff::Backoff;
+use access_controller::clock::{Clock, FakeClock};
+use access_controller::core::{AccessCore, CardRead, Input, Reader};
+use proptest::prelude::*;
+
+#[test]
+fn push_progression_doubles_to_cap() {
+    // B1
+    let clock = FakeClock::new(0);
+    let mut b = Backoff::new(2_000, 60_000);
+    let mut waits = Vec::new();
+    for _ in 0..8 {
+        let at = b.schedule(clock.now_ms());
+        waits.push(at - clock.now_ms());
+        clock.set(at);
+    }
+    assert_eq!(
+        waits,
+        [2_000, 4_000, 8_000, 16_000, 32_000, 60_000, 60_000, 60_000]
+    );
+}
+
+#[test]
+fn reset_starts_over() {
+    // B2
+    let clock = FakeClock::new(1_000);
+    let mut b = Backoff::new(2_000, 60_000);
+    b.schedule(clock.now_ms());
+    b.schedule(clock.now_ms());
+    assert_eq!(b.delay_ms(), 8_000);
+    b.reset();
+    assert_eq!(b.schedule(clock.now_ms()), 3_000);
+}
+
+#[test]
+fn ready_at_deadline() {
+    // B3
+    let clock = FakeClock::new(500);
+    let mut b = Backoff::new(2_000, 60_000);
+    assert!(b.ready(clock.now_ms()));
+    b.schedule(clock.now_ms());
+    clock.advance(1_999);
+    assert!(!b.ready(clock.now_ms()));
+    clock.advance(1);
+    assert!(b.ready(clock.now_ms()));
+}
+
+#[test]
+fn reconnect_delay_spans_the_counter_wrap() {
+    // B3, on the push task's reconnect wait
+    let clock = FakeClock::new(u64::MAX - 500);
+    let mut b = Backoff::new(2_000, 60_000);
+    assert_eq!(b.remaining_ms(clock.now_ms()), 0);
+    assert_eq!(b.schedule(clock.now_ms()), 1_499);
+    assert_eq!(b.remaining_ms(clock.now_ms()), 2_000);
+    clock.advance(1_000);
+    assert_eq!(b.remaining_ms(clock.now_ms()), 1_000);
+    assert!(!b.ready(clock.now_ms()));
+    clock.advance(1_500);
+    assert_eq!(b.remaining_ms(clock.now_ms()), 0);
+    assert!(b.ready(clock.now_ms()));
+}
+
+#[test]
+fn clock_by_reference() {
+    fn read(c: impl Clock) -> u64 {
+        c.now_ms()
+    }
+    let clock = FakeClock::new(7);
+    clock.advance(3);
+    assert_eq!(read(&clock), 10);
+}
+
+#[test]
+fn access_core_denial_backoff_under_fake_clock() {
+    // B4: standalone, so each miss applies backoff right away.
+    let clock = FakeClock::new(10_000);
+    let mut core = AccessCore::new();
+    let card = Input::Card(CardRead {
+        fob: 42,
+        nfc: 42,
+        reader: Reader::Wiegand,
+    });
+    for want in [2_000u64, 4_000, 8_000, 8_000] {
+        let eff = core.step(clock.now_ms(), &[], &[], false, false, card);
+        assert!(!eff.is_empty());
+        assert_eq!(core.backoff_until() - clock.now_ms(), want);
+
+        clock.advance(want - 1);
+        assert!(core
+            .step(clock.now_ms(), &[], &[], false, false, card)
+            .is_empty());
+        clock.advance(1);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// B1/B2 against a model, over any mix of failures and successes.
+    #[test]
+    fn matches_model(
+        min in 1u64..10_000,
+        factor in 1u64..100,
+        start in 0u64..1_000_000,
+        ops in prop::collection::vec((any::<bool>(), 0u64..100_000), 0..64),
+    ) {
+        let max = min * factor;
+        let clock = FakeClock::new(start);
+        let mut b = Backoff::new(min, max);
+        let mut delay = min;
+        for (ok, gap) in ops {
+            clock.advance(gap);
+            if ok {
+                b.reset();
+                delay = min;
+            } else {
+                let at = b.schedule(clock.now_ms());
+                prop_assert_eq!(at, clock.now_ms() + delay);
+                prop_assert!(!b.ready(at - 1));
+                prop_assert!(b.ready(at));
+                delay = (delay * 2).min(max);
+            }
+            prop_assert_eq!(b.delay_ms(), delay);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Tests for the `X-Fob-HMAC` response check (invariants M1–M3).
+//!
+//!   M1: the RFC 4231 HMAC-SHA256 vectors verify, in either hex case and
+//!       with surrounding whitespace, and `sign_hex` reproduces them.
+//!   M2: a MAC fails under any other key or over any other body.
+//!   M3: a header that is not exactly 64 hex digits fails.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test body_mac
+
+#![cfg(feature = "sim")]
+
+use access_controller::body_mac::{sign_hex, verify};
+use proptest::prelude::*;
+
+/// RFC 4231 test cases 1, 2, 6 and 7: (key, data, HMAC-SHA256).
+fn vectors() -> Vec<(Vec<u8>, Vec<u8>, &'static str)> {
+    vec![
+        (
+            vec![0x0b; 20],
+            b"Hi There".to_vec(),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+        ),
+        (
+            b"Jefe".to_vec(),
+            b"what do ya want for nothing?".to_vec(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        ),
+        (
+            vec![0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+        ),
+        (
+            vec![0xaa; 131],
+            b"This is a test using a larger than block-size key and a larger than \
+              block-size data. The key needs to be hashed before being used by the \
+              HMAC algorithm."
+                .to_vec(),
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+        ),
+    ]
+}
+
+// ---------- M1 ----------
+
+#[test]
+fn m1_known_vectors_verify() {
+    for (key, data, mac) in vectors() {
+        assert!(verify(&key, &data, mac), "{}", mac);
+        assert!(verify(&key, &data, &mac.to_uppercase()), "{}", mac);
+        assert!(verify(&key, &data, &format!("  {}\t", mac)), "{}", mac);
+        assert_eq!(sign_hex(&key, &data).as_str(), mac);
+    }
+}
+
+// ---------- M2 ----------
+
+#[test]
+fn m2_wrong_key_or_body_fails() {
+    for (key, data, mac) in vectors() {
+        let mut other_key = key.clone();
+        other_key[0] ^= 1;
+        assert!(!verify(&other_key, &data, mac));
+        assert!(!verify(&key[1..], &data, mac));
+
+        let mut other_data = data.clone();
+        other_data.push(b' ');
+        assert!(!verify(&key, &other_data, mac));
+        assert!(!verify(&key, &data[..data.len() - 1], mac));
+    }
+}
+
+// ---------- M3 ----------
+
+#[test]
+fn m3_malformed_header_fails() {
+    let (key, data, mac) = vectors().swap_remove(1);
+    for bad in [
+        String::new(),
+        mac[..62].to_string(),
+        format!("{}00", mac),
+        format!("sha256={}", mac),
+        format!("{}g", &mac[..63]),
+        format!("{} {}", &mac[..32], &mac[32..]),
+        // The raw MAC, base64 encoded.
+        "W9zBRr9gdU5qBCQmCJV1x1oAPwidJzmDnexYuWTsOEM=".to_string(),
+    ] {
+        assert!(!verify(&key, &data, &bad), "{:?}", bad);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// M1/M2: a MAC made with `sign_hex` verifies, and flipping any bit
+    /// of the body makes it fail.
+    #[test]
+    fn prop_sign_verify_round_trip(
+        key in prop::collection::vec(any::<u8>(), 0..96),
+        body in prop::collection::vec(any::<u8>(), 1..256),
+        flip in any::<prop::sample::Index>(),
+        bit in 0u8..8,
+    ) {
+        let mac = sign_hex(&key, &body);
+        prop_assert!(verify(&key, &body, &mac));
+        let mut tampered = body.clone();
+        tampered[flip.index(body.len())] ^= 1 << bit;
+        prop_assert!(!verify(&key, &tampered, &mac));
+    }
+}
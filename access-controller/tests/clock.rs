@@ -0,0 +1,267 @@
+//! Tests for calendar time and the schedule fallback (invariants T1–T3).
+//!
+//!   T1: before a time source sets it, the wall clock is unsynced and
+//!       knows no Unix time.
+//!   T2: while time is unknown, schedule checks fail open without asking
+//!       the schedule; once known, the schedule alone decides.
+//!   T3: once set, Unix time advances with the boot clock.
+//!   T4: spans and deadlines come out the same on either side of the
+//!       counter's wrap; a reading taken after `now` is no time at all.
+//!   T5: the state machines that time things (denial backoff, relock,
+//!       retry backoff, watchdog feeds, the frame gap, the unlock
+//!       lockout, refused credentials) behave the same across the wrap
+//!       as away from it.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test clock
+
+#![cfg(feature = "sim")]
+
+use access_controller::authz::NegativeCache;
+use access_controller::backoff::Backoff;
+use access_controller::clock::{self, Clock, FakeClock, WallClock};
+use access_controller::core::{AccessCore, CardRead, Input, Reader};
+use access_controller::decode::FrameGap;
+use access_controller::door::{Relock, Relocked, Unlock};
+use access_controller::lockout::{Lockout, BASE_LOCK_MS, FREE_FAILURES};
+use access_controller::watchdog::FeedCadence;
+use proptest::prelude::*;
+
+/// 2026-01-01T00:00:00Z.
+const NEW_YEAR_MS: u64 = 1_767_225_600_000;
+
+// ---------- T1 ----------
+
+#[test]
+fn t1_unsynced_at_boot() {
+    let wall = WallClock::new();
+    assert!(!wall.synced());
+    assert_eq!(wall.now_unix_ms(0), None);
+    assert_eq!(wall.now_unix_ms(86_400_000), None);
+    assert_eq!(wall, WallClock::default());
+}
+
+// ---------- T2 ----------
+
+#[test]
+fn t2_time_not_yet_available_fails_open() {
+    let clock = FakeClock::new(1_500);
+    let wall = WallClock::new();
+    // A schedule that would refuse everyone is not even consulted.
+    let mut asked = false;
+    let permitted = wall.permits(clock.now_ms(), |_| {
+        asked = true;
+        false
+    });
+    assert!(permitted);
+    assert!(!asked);
+}
+
+#[test]
+fn t2_schedule_decides_once_synced() {
+    let clock = FakeClock::new(1_500);
+    let mut wall = WallClock::new();
+    wall.set(NEW_YEAR_MS, clock.now_ms());
+    assert!(wall.synced());
+    assert!(!wall.permits(clock.now_ms(), |_| false));
+    assert!(wall.permits(clock.now_ms(), |_| true));
+    let mut seen = None;
+    wall.permits(clock.now_ms(), |t| {
+        seen = Some(t);
+        true
+    });
+    assert_eq!(seen, Some(NEW_YEAR_MS));
+}
+
+// ---------- T3 ----------
+
+#[test]
+fn t3_advances_with_the_boot_clock() {
+    let clock = FakeClock::new(10_000);
+    let mut wall = WallClock::new();
+    wall.set(NEW_YEAR_MS, clock.now_ms());
+    clock.advance(3_600_000);
+    assert_eq!(
+        wall.now_unix_ms(clock.now_ms()),
+        Some(NEW_YEAR_MS + 3_600_000)
+    );
+    // A later sync replaces the earlier one.
+    wall.set(NEW_YEAR_MS + 3_600_500, clock.now_ms());
+    assert_eq!(
+        wall.now_unix_ms(clock.now_ms()),
+        Some(NEW_YEAR_MS + 3_600_500)
+    );
+}
+
+// ---------- T4 ----------
+
+#[test]
+fn t4_spans_across_the_wrap() {
+    assert_eq!(clock::elapsed(u64::MAX - 9, 5), 15);
+    assert_eq!(clock::elapsed(u64::MAX, 0), 1);
+    assert_eq!(clock::elapsed(7, 7), 0);
+    // Read after `now`: no time has passed, not nearly all of it.
+    assert_eq!(clock::elapsed(10, 9), 0);
+    assert_eq!(clock::elapsed(3, u64::MAX - 3), 0);
+
+    let deadline = clock::later(u64::MAX - 1, 3);
+    assert_eq!(deadline, 1);
+    assert!(clock::before(u64::MAX - 1, deadline));
+    assert!(clock::before(u64::MAX, deadline));
+    assert!(clock::before(0, deadline));
+    assert!(!clock::before(1, deadline));
+    assert!(!clock::before(2, deadline));
+}
+
+#[test]
+fn t4_fake_clock_wraps() {
+    let clock = FakeClock::new(u64::MAX - 1);
+    clock.advance(3);
+    assert_eq!(clock.now_ms(), 1);
+}
+
+// ---------- T5 ----------
+
+/// Just short of the wrap, so the interesting part of each test straddles
+/// it.
+const NEAR_WRAP: u64 = u64::MAX - 5_000;
+
+#[test]
+fn t5_denial_backoff_across_the_wrap() {
+    let clock = FakeClock::new(NEAR_WRAP);
+    let mut core = AccessCore::new();
+    let card = Input::Card(CardRead {
+        fob: 42,
+        nfc: 42,
+        reader: Reader::Wiegand,
+    });
+    for want in [2_000u64, 4_000, 8_000] {
+        assert!(!core
+            .step(clock.now_ms(), &[], &[], false, false, card)
+            .is_empty());
+        assert_eq!(clock::elapsed(clock.now_ms(), core.backoff_until()), want);
+        clock.advance(want - 1);
+        assert!(core
+            .step(clock.now_ms(), &[], &[], false, false, card)
+            .is_empty());
+        clock.advance(1);
+    }
+    assert!(clock.now_ms() < NEAR_WRAP, "the run crossed the wrap");
+}
+
+#[test]
+fn t5_relock_across_the_wrap() {
+    let unlock = Unlock::start(NEAR_WRAP, 6_000, Relock::Timeout);
+    assert_eq!(unlock.tick(NEAR_WRAP), None);
+    assert_eq!(unlock.tick(u64::MAX), None);
+    assert_eq!(unlock.tick(998), None);
+    assert_eq!(unlock.tick(999), Some(Relocked::Timeout));
+}
+
+#[test]
+fn t5_retry_backoff_across_the_wrap() {
+    let mut b = Backoff::new(2_000, 60_000);
+    assert!(b.ready(NEAR_WRAP));
+    let at = b.schedule(NEAR_WRAP);
+    assert_eq!(clock::elapsed(NEAR_WRAP, at), 2_000);
+    assert!(!b.ready(at.wrapping_sub(1)));
+    assert!(b.ready(at));
+    let at = b.schedule(at);
+    assert!(at < NEAR_WRAP, "the deadline wrapped");
+    assert!(!b.ready(u64::MAX));
+    assert!(!b.ready(at - 1));
+    assert!(b.ready(at));
+}
+
+#[test]
+fn t5_watchdog_feeds_across_the_wrap() {
+    let mut cadence = FeedCadence::new(1_000);
+    assert!(cadence.due(u64::MAX - 500));
+    assert!(!cadence.due(u64::MAX));
+    assert!(!cadence.due(498));
+    assert!(cadence.due(499));
+    assert_eq!(cadence.longest_gap_ms(), 1_000);
+}
+
+#[test]
+fn t5_frame_gap_across_the_wrap() {
+    let mut gap = FrameGap::new(20);
+    gap.frame_ended(u64::MAX - 10_000);
+    // 15 ms on, 5 of them past the wrap: still inside the gap.
+    assert!(!gap.accept(4_999));
+    // 25 ms after the frame, but the rejected bit restarted the gap.
+    assert!(!gap.accept(14_999));
+    assert!(gap.accept(34_999));
+}
+
+#[test]
+fn t5_unlock_lockout_across_the_wrap() {
+    let mut lockout = Lockout::new();
+    let mut now = NEAR_WRAP;
+    for _ in 1..FREE_FAILURES {
+        assert_eq!(lockout.fail(now), None);
+    }
+    assert_eq!(lockout.fail(now), Some(BASE_LOCK_MS));
+    now = clock::later(now, BASE_LOCK_MS - 1);
+    assert!(now < NEAR_WRAP, "the lock ends past the wrap");
+    assert_eq!(lockout.check(now), Err(1));
+    assert_eq!(lockout.check(now + 1), Ok(()));
+}
+
+#[test]
+fn t5_refusals_expire_across_the_wrap() {
+    let read = CardRead {
+        fob: 42,
+        nfc: 42,
+        reader: Reader::Wiegand,
+    };
+    let mut cache = NegativeCache::new(10_000);
+    cache.insert(read, NEAR_WRAP);
+    assert!(cache.contains(read, u64::MAX));
+    assert!(cache.contains(read, 4_998));
+    assert!(!cache.contains(read, 4_999));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn t2_t3_any_sync_point(
+        unix in NEW_YEAR_MS..NEW_YEAR_MS * 2,
+        at in 0u64..1 << 40,
+        later in 0u64..1 << 40,
+        open_from in any::<u64>(),
+    ) {
+        let mut wall = WallClock::new();
+        prop_assert!(wall.permits(at + later, |t| t >= open_from));
+        wall.set(unix, at);
+        prop_assert_eq!(wall.now_unix_ms(at), Some(unix));
+        prop_assert_eq!(wall.now_unix_ms(at + later), Some(unix + later));
+        prop_assert_eq!(wall.permits(at + later, |t| t >= open_from), unix + later >= open_from);
+    }
+
+    /// T4: shifting every reading by the same amount, wrap or not,
+    /// changes no span and no comparison.
+    #[test]
+    fn t4_shift_invariant(
+        since in 0u64..1 << 40,
+        span in 0u64..1 << 40,
+        wait in 0u64..1 << 40,
+        shift in any::<u64>(),
+    ) {
+        let now = since + wait;
+        let deadline = since + span;
+        let s = |t: u64| t.wrapping_add(shift);
+        prop_assert_eq!(clock::elapsed(s(since), s(now)), wait);
+        prop_assert_eq!(clock::elapsed(s(now), s(since)), 0);
+        prop_assert_eq!(clock::later(s(since), span), s(deadline));
+        prop_assert_eq!(clock::before(s(now), s(deadline)), now < deadline);
+    }
+}
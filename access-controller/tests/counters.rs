@@ -0,0 +1,213 @@
+//! Tests for the resettable counters behind `/counters` (invariants
+//! C1–C4).
+//!
+//!   C1: a counter counts up; taking it returns the count since the last
+//!       take and starts that over, while the count since boot never goes
+//!       down.
+//!   C2: a plain snapshot reads every counter and changes none; a reset
+//!       snapshot returns the same values and starts every counter over.
+//!   C3: no event is lost or counted twice across resets, even with
+//!       other threads counting meanwhile.
+//!   C4: the JSON names every counter once, in order, and the wrapped
+//!       counters (dropped reads, lock overruns) reset with the rest
+//!       without losing their totals.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test counters
+
+#![cfg(feature = "sim")]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use access_controller::counters::{self, Counter, Name, Snapshot};
+use access_controller::read_drops::DroppedReads;
+use access_controller::watchdog::{LockHolds, MAX_LOCK_HOLD_MS};
+use proptest::prelude::*;
+
+/// One counter per name, as the firmware's statics are.
+struct Bank([Counter; Name::ALL.len()]);
+
+impl Bank {
+    fn new() -> Self {
+        Bank(std::array::from_fn(|_| Counter::new()))
+    }
+
+    fn get(&self, name: Name) -> &Counter {
+        &self.0[Name::ALL.iter().position(|&n| n == name).unwrap()]
+    }
+
+    fn snapshot(&self, reset: bool) -> Snapshot {
+        counters::snapshot(|n| self.get(n), reset)
+    }
+}
+
+fn json(s: &Snapshot) -> String {
+    let mut out = String::new();
+    s.write_json(&mut out).unwrap();
+    out
+}
+
+// ---------- C1 ----------
+
+#[test]
+fn c1_take_starts_over() {
+    let c = Counter::new();
+    assert_eq!(c.get(), 0);
+    c.incr();
+    c.incr();
+    c.incr();
+    assert_eq!(c.get(), 3);
+    assert_eq!(c.since_reset(), 3);
+    assert_eq!(c.take(), 3);
+    assert_eq!(c.since_reset(), 0);
+    assert_eq!(c.take(), 0);
+    c.incr();
+    assert_eq!(c.since_reset(), 1);
+    // `/metrics` exports the count since boot as a `_total` counter.
+    assert_eq!(c.get(), 4);
+}
+
+// ---------- C2 ----------
+
+#[test]
+fn c2_read_then_reset() {
+    let bank = Bank::new();
+    for (i, name) in Name::ALL.into_iter().enumerate() {
+        for _ in 0..=i {
+            bank.get(name).incr();
+        }
+    }
+    let read = bank.snapshot(false);
+    assert_eq!(bank.snapshot(false), read, "reading changes nothing");
+    for (i, name) in Name::ALL.into_iter().enumerate() {
+        assert_eq!(read.get(name), i as u32 + 1);
+    }
+
+    let taken = bank.snapshot(true);
+    assert_eq!(taken, read);
+    assert_eq!(bank.snapshot(false), Snapshot::default());
+    assert_eq!(bank.snapshot(true), Snapshot::default());
+    for (i, name) in Name::ALL.into_iter().enumerate() {
+        assert_eq!(bank.get(name).get(), i as u32 + 1, "totals survive a reset");
+    }
+}
+
+proptest! {
+    #[test]
+    fn c2_reset_returns_what_accrued(
+        rounds in prop::collection::vec(
+            (prop::collection::vec(0usize..Name::ALL.len(), 0..40), any::<bool>()),
+            1..12,
+        ),
+    ) {
+        let bank = Bank::new();
+        let mut expected = [0u32; Name::ALL.len()];
+        for (events, reset) in rounds {
+            for i in events {
+                bank.get(Name::ALL[i]).incr();
+                expected[i] += 1;
+            }
+            let snap = bank.snapshot(reset);
+            for (i, name) in Name::ALL.into_iter().enumerate() {
+                prop_assert_eq!(snap.get(name), expected[i]);
+            }
+            if reset {
+                expected = [0; Name::ALL.len()];
+            }
+        }
+    }
+}
+
+// ---------- C3 ----------
+
+#[test]
+fn c3_no_event_lost_across_resets() {
+    const THREADS: usize = 4;
+    const PER_THREAD: u32 = 20_000;
+    let bank = Arc::new(Bank::new());
+    let done = Arc::new(AtomicBool::new(false));
+    let workers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let bank = Arc::clone(&bank);
+            thread::spawn(move || {
+                let name = Name::ALL[t % Name::ALL.len()];
+                for _ in 0..PER_THREAD {
+                    bank.get(name).incr();
+                }
+            })
+        })
+        .collect();
+    let monitor = {
+        let bank = Arc::clone(&bank);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            let mut totals = [0u64; Name::ALL.len()];
+            while !done.load(Ordering::Relaxed) {
+                let snap = bank.snapshot(true);
+                for (i, name) in Name::ALL.into_iter().enumerate() {
+                    totals[i] += u64::from(snap.get(name));
+                }
+            }
+            totals
+        })
+    };
+    for w in workers {
+        w.join().unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    let mut totals = monitor.join().unwrap();
+    let last = bank.snapshot(true);
+    for (i, name) in Name::ALL.into_iter().enumerate() {
+        totals[i] += u64::from(last.get(name));
+    }
+    let mut want = [0u64; Name::ALL.len()];
+    for t in 0..THREADS {
+        want[t % Name::ALL.len()] += u64::from(PER_THREAD);
+    }
+    assert_eq!(totals, want);
+}
+
+// ---------- C4 ----------
+
+#[test]
+fn c4_json() {
+    let bank = Bank::new();
+    assert_eq!(
+        json(&bank.snapshot(false)),
+        "{\"sync_failures\":0,\"wifi_reconnects\":0,\"reads_dropped\":0,\
+         \"sockets_force_closed\":0,\"admin_clients_refused\":0,\"lock_over_budget\":0}\n"
+    );
+    bank.get(Name::SyncFailures).incr();
+    bank.get(Name::LockOverBudget).incr();
+    bank.get(Name::LockOverBudget).incr();
+    let out = json(&bank.snapshot(true));
+    assert!(out.starts_with("{\"sync_failures\":1,"));
+    assert!(out.ends_with(",\"lock_over_budget\":2}\n"));
+    for name in Name::ALL {
+        assert_eq!(out.matches(&format!("\"{}\":", name.as_str())).count(), 1);
+    }
+}
+
+#[test]
+fn c4_wrapped_counters_reset() {
+    let drops = DroppedReads::new();
+    let _ = drops.note(Err::<(), ()>(()));
+    let _ = drops.note(Ok::<(), ()>(()));
+    let _ = drops.note(Err::<(), ()>(()));
+    assert_eq!(drops.counter().take(), 2);
+    assert_eq!(drops.counter().since_reset(), 0);
+    assert_eq!(drops.get(), 2, "the total since boot stays");
+
+    let holds = LockHolds::new();
+    holds.record(MAX_LOCK_HOLD_MS + 1);
+    holds.record(1);
+    assert_eq!(holds.over_budget_counter().take(), 1);
+    assert_eq!(holds.over_budget_counter().since_reset(), 0);
+    assert_eq!(holds.over_budget(), 1, "the total since boot stays");
+    // The longest hold is a gauge, not a count: a reset leaves it.
+    assert_eq!(holds.longest_ms() as u64, MAX_LOCK_HOLD_MS + 1);
+}
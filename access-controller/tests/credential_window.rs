@@ -0,0 +1,83 @@
+//! Tests for the expiring credential set shared by the online check's
+//! refusals and anti-passback (invariants K1–K3).
+//!
+//!   K1: a credential (both its forms) is remembered from its insert
+//!       until the span has passed, at either reader; a span of 0
+//!       remembers nothing.
+//!   K2: inserting a remembered credential starts its span again in the
+//!       same slot rather than taking another.
+//!   K3: a full set reuses an expired slot if there is one, and otherwise
+//!       forgets the entry closest to expiry, across a counter wrap too.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test credential_window
+
+#![cfg(feature = "sim")]
+
+use access_controller::core::{CardRead, Reader};
+use access_controller::credential_window::CredentialWindow;
+
+fn card(i: u32) -> CardRead {
+    CardRead {
+        fob: 100 + i,
+        nfc: 200 + i,
+        reader: Reader::Wiegand,
+    }
+}
+
+#[test]
+fn k1_remembered_for_the_span() {
+    let mut w = CredentialWindow::<2>::new(1_000);
+    w.insert(card(0), 5_000);
+    assert!(w.contains(card(0), 5_999));
+    assert!(w.contains(card(0).at(Reader::Osdp), 5_000));
+    assert!(!w.contains(CardRead { fob: 0, ..card(0) }, 5_000));
+    assert!(!w.contains(card(0), 6_000));
+    assert!(!w.contains(card(1), 5_000));
+
+    let mut off = CredentialWindow::<2>::new(0);
+    off.insert(card(0), 5_000);
+    assert!(!off.contains(card(0), 5_000));
+    assert_eq!(off.span_ms(), 0);
+}
+
+#[test]
+fn k2_reinsert_restarts_in_place() {
+    let mut w = CredentialWindow::<2>::new(1_000);
+    w.insert(card(0), 0);
+    w.insert(card(1), 100);
+    w.insert(card(0), 900);
+    assert!(w.contains(card(0), 1_800));
+    assert!(w.contains(card(1), 1_000));
+}
+
+#[test]
+fn k3_full_set_evicts_closest_to_expiry() {
+    let mut w = CredentialWindow::<2>::new(1_000);
+    w.insert(card(0), 0);
+    w.insert(card(1), 500);
+    w.insert(card(2), 600);
+    assert!(!w.contains(card(0), 600));
+    assert!(w.contains(card(1), 600));
+    assert!(w.contains(card(2), 600));
+
+    // An expired slot goes before any live one.
+    w.insert(card(3), 1_500);
+    assert!(w.contains(card(2), 1_500));
+    assert!(w.contains(card(3), 1_500));
+}
+
+#[test]
+fn k3_eviction_across_the_wrap() {
+    let start = u64::MAX - 700;
+    let mut w = CredentialWindow::<2>::new(1_000);
+    w.insert(card(0), start);
+    w.insert(card(1), start + 500);
+    let now = start.wrapping_add(900);
+    w.insert(card(2), now);
+    assert!(!w.contains(card(0), now));
+    assert!(w.contains(card(1), now));
+    assert!(w.contains(card(2), now));
+}
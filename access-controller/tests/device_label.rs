@@ -0,0 +1,95 @@
+//! Tests for the device label (invariants D1–D3).
+//!
+//!   D1: a label is valid iff it fits `MAX_DEVICE_LABEL` bytes and has
+//!       no control characters; empty is valid (no label).
+//!   D2: the rendered title carries the label ahead of the page name,
+//!       and is just the page name without one.
+//!   D3: the label is HTML-escaped, so no label can open a tag.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test device_label
+
+#![cfg(feature = "sim")]
+
+use access_controller::device_label::{self, MAX_DEVICE_LABEL};
+use proptest::prelude::*;
+
+const PAGE: &str = "Conway Access Controller";
+
+fn title(label: &str) -> String {
+    let mut out = String::new();
+    device_label::write_title(&mut out, label, PAGE).unwrap();
+    out
+}
+
+// ---------- D1 ----------
+
+#[test]
+fn d1_validity() {
+    let table = [
+        ("", true),
+        ("Wood shop door", true),
+        ("Électronique 2", true),
+        (&"x".repeat(MAX_DEVICE_LABEL), true),
+        (&"x".repeat(MAX_DEVICE_LABEL + 1), false),
+        // 16 two-byte chars fit; 17 do not.
+        (&"é".repeat(16), true),
+        (&"é".repeat(17), false),
+        ("front\ndoor", false),
+        ("tab\there", false),
+        ("nul\0", false),
+    ];
+    for (label, want) in table {
+        assert_eq!(device_label::valid(label), want, "{:?}", label);
+    }
+}
+
+// ---------- D2 ----------
+
+#[test]
+fn d2_label_in_rendered_title() {
+    assert_eq!(title(""), PAGE);
+    assert_eq!(
+        title("Wood shop door"),
+        "Wood shop door &middot; Conway Access Controller"
+    );
+}
+
+// ---------- D3 ----------
+
+#[test]
+fn d3_label_is_escaped() {
+    assert_eq!(
+        title("<b>\"A&B's\"</b>"),
+        "&lt;b&gt;&quot;A&amp;B&#39;s&quot;&lt;/b&gt; &middot; Conway Access Controller"
+    );
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn d2_d3_any_label(label in "\\PC{0,32}") {
+        let out = title(&label);
+        prop_assert!(out.ends_with(PAGE));
+        let head = &out[..out.len() - PAGE.len()];
+        prop_assert!(!head.contains('<') && !head.contains('>') && !head.contains('"'));
+        if !label.is_empty() {
+            prop_assert!(head.ends_with(" &middot; "));
+        }
+        let plain: String = label
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == ' ')
+            .collect();
+        if plain == label {
+            prop_assert!(out.starts_with(&label));
+        }
+    }
+}
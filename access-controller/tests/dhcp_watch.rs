@@ -0,0 +1,206 @@
+//! Tests for the stuck-DHCP detection and lease tracking (invariants
+//! I1–I6).
+//!
+//!   I1: no reconnect until the link has been up without an address for
+//!       the full timeout; an address or a dropped link ends the wait.
+//!   I2: a DHCP server that stays silent gets one reconnect per timeout,
+//!       not one per poll.
+//!   I3: a timeout of 0 never reconnects; the knob takes 0 or 10 s to
+//!       an hour.
+//!   I4: over any sequence of polls, a reconnect comes only after a full
+//!       timeout of unbroken polls associated without an address.
+//!   I5: acquiring, changing and losing the address each produce one
+//!       event, logged as a readable line; an unchanged address, renewed
+//!       or not, produces none.
+//!   I6: over any sequence of polls, events replay the address history
+//!       and the held time restarts at each change.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test dhcp_watch
+
+#![cfg(feature = "sim")]
+
+use access_controller::dhcp_watch::{
+    parse_timeout_secs, DhcpAction, DhcpWatch, LeaseEvent, LeaseWatch, DEFAULT_DHCP_TIMEOUT_SECS,
+    MAX_DHCP_TIMEOUT_SECS, MIN_DHCP_TIMEOUT_SECS,
+};
+use proptest::prelude::*;
+
+// ---------- I1 ----------
+
+#[test]
+fn i1_reconnects_after_full_timeout() {
+    let mut w = DhcpWatch::new(60);
+    assert_eq!(w.poll(5_000, true, false), DhcpAction::Wait);
+    assert!(w.is_waiting());
+    assert_eq!(w.poll(64_999, true, false), DhcpAction::Wait);
+    assert_eq!(w.poll(65_000, true, false), DhcpAction::Reconnect);
+    assert_eq!(w.reconnects(), 1);
+}
+
+#[test]
+fn i1_address_or_link_loss_ends_the_wait() {
+    let mut w = DhcpWatch::new(60);
+    assert_eq!(w.poll(0, true, false), DhcpAction::Wait);
+    // Lease arrives, then expires later: the wait starts again from there.
+    assert_eq!(w.poll(30_000, true, true), DhcpAction::Wait);
+    assert!(!w.is_waiting());
+    assert_eq!(w.poll(40_000, true, false), DhcpAction::Wait);
+    assert_eq!(w.poll(99_999, true, false), DhcpAction::Wait);
+    // Link drops: the WiFi task reconnects on its own, no DHCP reset.
+    assert_eq!(w.poll(100_000, false, false), DhcpAction::Wait);
+    assert!(!w.is_waiting());
+    assert_eq!(w.poll(1_000_000, false, false), DhcpAction::Wait);
+    assert_eq!(w.reconnects(), 0);
+}
+
+#[test]
+fn i1_configured_link_never_reconnects() {
+    let mut w = DhcpWatch::new(10);
+    for t in 0..100 {
+        assert_eq!(w.poll(t * 5_000, true, true), DhcpAction::Wait);
+    }
+}
+
+// ---------- I2 ----------
+
+#[test]
+fn i2_one_reconnect_per_timeout() {
+    let mut w = DhcpWatch::new(60);
+    let reconnects: Vec<u64> = (0..=60)
+        .map(|i| i * 5_000)
+        .filter(|&t| w.poll(t, true, false) == DhcpAction::Reconnect)
+        .collect();
+    assert_eq!(reconnects, [60_000, 120_000, 180_000, 240_000, 300_000]);
+    assert_eq!(w.reconnects(), 5);
+}
+
+// ---------- I3 ----------
+
+#[test]
+fn i3_zero_disables() {
+    let mut w = DhcpWatch::new(0);
+    for t in [0, 60_000, u64::MAX / 2, u64::MAX] {
+        assert_eq!(w.poll(t, true, false), DhcpAction::Wait);
+    }
+    assert_eq!(w.reconnects(), 0);
+}
+
+#[test]
+fn i3_parse_timeout() {
+    assert_eq!(parse_timeout_secs("0"), Some(0));
+    assert_eq!(parse_timeout_secs("10"), Some(MIN_DHCP_TIMEOUT_SECS));
+    assert_eq!(parse_timeout_secs("3600"), Some(MAX_DHCP_TIMEOUT_SECS));
+    assert_eq!(parse_timeout_secs("60"), Some(DEFAULT_DHCP_TIMEOUT_SECS));
+    for bad in ["", "5", "9", "3601", "-1", "1m", " 60"] {
+        assert_eq!(parse_timeout_secs(bad), None, "{:?}", bad);
+    }
+}
+
+// ---------- I4 ----------
+
+proptest! {
+    #[test]
+    fn i4_reconnect_only_after_unbroken_wait(
+        timeout_secs in MIN_DHCP_TIMEOUT_SECS..120,
+        polls in prop::collection::vec((1u64..20_000, any::<bool>(), prop::bool::weighted(0.2)), 0..200),
+    ) {
+        let timeout_ms = timeout_secs * 1000;
+        let mut w = DhcpWatch::new(timeout_secs);
+        let mut now = 0u64;
+        // Start of the current unbroken stretch, by this test's reckoning.
+        let mut stuck_since: Option<u64> = None;
+        for (step, associated, configured) in polls {
+            now += step;
+            let stuck = associated && !configured;
+            let due = if stuck {
+                now - *stuck_since.get_or_insert(now) >= timeout_ms
+            } else {
+                stuck_since = None;
+                false
+            };
+            let action = w.poll(now, associated, configured);
+            prop_assert_eq!(action == DhcpAction::Reconnect, due);
+            if due {
+                stuck_since = Some(now);
+            }
+            prop_assert_eq!(w.is_waiting(), stuck);
+        }
+    }
+}
+
+// ---------- I5 ----------
+
+#[test]
+fn i5_transitions_are_logged() {
+    let a = [192, 168, 1, 20];
+    let b = [192, 168, 1, 21];
+    let mut w = LeaseWatch::new();
+    assert_eq!(w.poll(0, None), None);
+    assert_eq!(w.held_ms(0), None);
+
+    let acquired = w.poll(1_000, Some(a)).unwrap();
+    assert_eq!(acquired, LeaseEvent::Acquired(a));
+    assert_eq!(acquired.to_string(), "dhcp: lease acquired: 192.168.1.20");
+    // Renewals keep the address: nothing to log.
+    assert_eq!(w.poll(5_000, Some(a)), None);
+    assert_eq!(w.held_ms(6_000), Some(5_000));
+
+    let changed = w.poll(10_000, Some(b)).unwrap();
+    assert_eq!(changed, LeaseEvent::Changed { from: a, to: b });
+    assert_eq!(changed.to_string(), "dhcp: lease changed: 192.168.1.20 -> 192.168.1.21");
+    assert_eq!(w.held_ms(10_000), Some(0));
+
+    let lost = w.poll(20_000, None).unwrap();
+    assert_eq!(lost, LeaseEvent::Lost(b));
+    assert_eq!(lost.to_string(), "dhcp: lease lost: 192.168.1.21");
+    assert_eq!(w.poll(25_000, None), None);
+    assert_eq!(w.address(), None);
+    assert_eq!(w.losses(), 1);
+}
+
+// ---------- I6 ----------
+
+proptest! {
+    #[test]
+    fn i6_events_replay_the_address_history(
+        polls in prop::collection::vec((1u64..10_000, prop::option::of(0u8..3)), 0..100),
+    ) {
+        let mut w = LeaseWatch::new();
+        let mut now = 0u64;
+        let mut replayed: Option<[u8; 4]> = None;
+        let mut since = 0u64;
+        let mut losses = 0u32;
+        for (step, host) in polls {
+            now += step;
+            let address = host.map(|h| [10, 0, 0, h]);
+            let event = w.poll(now, address);
+            if event.is_some() {
+                since = now;
+            }
+            match event {
+                None => prop_assert_eq!(replayed, address),
+                Some(LeaseEvent::Acquired(to)) => {
+                    prop_assert_eq!(replayed, None);
+                    replayed = Some(to);
+                }
+                Some(LeaseEvent::Changed { from, to }) => {
+                    prop_assert_eq!(replayed, Some(from));
+                    prop_assert_ne!(from, to);
+                    replayed = Some(to);
+                }
+                Some(LeaseEvent::Lost(from)) => {
+                    prop_assert_eq!(replayed, Some(from));
+                    replayed = None;
+                    losses += 1;
+                }
+            }
+            prop_assert_eq!(replayed, address);
+            prop_assert_eq!(w.held_ms(now + 1), replayed.map(|_| now + 1 - since));
+            prop_assert_eq!(w.address(), address);
+            prop_assert_eq!(w.losses(), losses);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Tests for the last-sync-response capture (invariants D1–D3).
+//!
+//!   D1: head and body are kept up to MAX_HEAD / MAX_BODY bytes, never
+//!       more, and their full lengths are reported.
+//!   D2: the rendering escapes everything but printable ASCII and `\n`.
+//!   D3: the endpoint needs a configured secret sent as a bearer token;
+//!       a secret sent in a form field must match it exactly.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test diag
+
+#![cfg(feature = "sim")]
+
+use access_controller::diag::{
+    authorized, bearer_token, secret_matches, LastResponse, MAX_BODY, MAX_HEAD,
+};
+use proptest::prelude::*;
+
+const HOST: [u8; 4] = [10, 0, 0, 1];
+
+fn render(r: &LastResponse) -> String {
+    let mut s = String::new();
+    r.render(&mut s).unwrap();
+    s
+}
+
+#[test]
+fn nothing_recorded() {
+    let r = LastResponse::new();
+    assert!(r.is_empty());
+    assert_eq!(render(&r), "no sync response recorded since boot\n");
+}
+
+#[test]
+fn small_response_is_kept_whole() {
+    let mut r = LastResponse::new();
+    r.record(HOST, 1234, b"HTTP/1.1 200 OK\r\nETag: v1\r\n\r\nnot json");
+    assert_eq!(r.head(), (&b"HTTP/1.1 200 OK\r\nETag: v1\r\n"[..], 27));
+    assert_eq!(r.body(), (&b"not json"[..], 8));
+    assert_eq!(
+        render(&r),
+        "host 10.0.0.1, 1234 ms after boot\n\
+         \n\
+         head (27 bytes):\n\
+         HTTP/1.1 200 OK\\r\n\
+         ETag: v1\\r\n\
+         \n\
+         body (8 bytes):\n\
+         not json\n"
+    );
+}
+
+#[test]
+fn long_body_is_truncated() {
+    // D1
+    let mut raw = b"HTTP/1.1 200 OK\r\n\r\n[".to_vec();
+    raw.extend(std::iter::repeat_n(b'1', 5000));
+    let mut r = LastResponse::new();
+    r.record(HOST, 0, &raw);
+    let (body, len) = r.body();
+    assert_eq!(len, 5001);
+    assert_eq!(body.len(), MAX_BODY);
+    assert_eq!(body, &raw[19..19 + MAX_BODY]);
+    assert!(render(&r).contains(&format!("body (5001 bytes, first {} shown):", MAX_BODY)));
+}
+
+#[test]
+fn long_head_is_truncated() {
+    // D1: a head that never ended (e.g. aborted by the size limit).
+    let mut raw = b"HTTP/1.1 200 OK\r\n".to_vec();
+    raw.resize(3000, b'x');
+    let mut r = LastResponse::new();
+    r.record(HOST, 0, &raw);
+    let (head, len) = r.head();
+    assert_eq!(len, 3000);
+    assert_eq!(head, &raw[..MAX_HEAD]);
+    assert_eq!(r.body(), (&b""[..], 0));
+}
+
+#[test]
+fn exact_fit_is_not_marked_truncated() {
+    let mut raw = b"HTTP/1.1 200 OK\r\n\r\n".to_vec();
+    raw.resize(raw.len() + MAX_BODY, b'7');
+    let mut r = LastResponse::new();
+    r.record(HOST, 0, &raw);
+    assert_eq!(r.body().0.len(), MAX_BODY);
+    assert!(render(&r).contains(&format!("body ({} bytes):", MAX_BODY)));
+}
+
+#[test]
+fn new_record_replaces_old() {
+    let mut r = LastResponse::new();
+    r.record(HOST, 1, &[b'a'; 600]);
+    r.record([10, 0, 0, 2], 2, b"HTTP/1.1 304 Not Modified\n\n");
+    assert_eq!(r.head(), (&b"HTTP/1.1 304 Not Modified\n"[..], 26));
+    assert_eq!(r.body(), (&b""[..], 0));
+    assert!(render(&r).starts_with("host 10.0.0.2, 2 ms after boot\n"));
+}
+
+#[test]
+fn binary_is_escaped() {
+    // D2
+    let mut r = LastResponse::new();
+    r.record(HOST, 0, b"HTTP/1.1 200 OK\n\n\x00\x05\xff\\ok\t");
+    assert!(render(&r).ends_with("body (7 bytes):\n\\x00\\x05\\xff\\\\ok\\x09\n"));
+}
+
+#[test]
+fn bearer_secret_required() {
+    // D3
+    assert!(authorized(Some("s3cret"), Some("Bearer s3cret")));
+    assert!(authorized(Some("s3cret"), Some("bearer  s3cret ")));
+    assert!(!authorized(Some("s3cret"), Some("Bearer s3cre")));
+    assert!(!authorized(Some("s3cret"), Some("Bearer s3cretx")));
+    assert!(!authorized(Some("s3cret"), Some("Basic s3cret")));
+    assert!(!authorized(Some("s3cret"), Some("s3cret")));
+    assert!(!authorized(Some("s3cret"), None));
+    // Not configured: nothing gets in, not even an empty token.
+    assert!(!authorized(None, Some("Bearer ")));
+    assert!(!authorized(Some(""), Some("Bearer ")));
+}
+
+#[test]
+fn bearer_token_extracted() {
+    // D3
+    assert_eq!(bearer_token(Some("Bearer s3cret")), Some("s3cret"));
+    assert_eq!(bearer_token(Some("bearer  s3cret ")), Some("s3cret"));
+    assert_eq!(bearer_token(Some("Bearer ")), Some(""));
+    assert_eq!(bearer_token(Some("Basic s3cret")), None);
+    assert_eq!(bearer_token(Some("s3cret")), None);
+    assert_eq!(bearer_token(None), None);
+}
+
+#[test]
+fn form_secret_must_match() {
+    // D3
+    assert!(secret_matches("s3cret", "s3cret"));
+    assert!(!secret_matches("s3cret", "s3cre"));
+    assert!(!secret_matches("s3cret", "s3cretx"));
+    assert!(!secret_matches("s3cret", "S3CRET"));
+    assert!(!secret_matches("s3cret", ""));
+    assert!(!secret_matches("", ""));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// D1: kept bytes are a prefix of what was received, within bounds.
+    #[test]
+    fn capture_is_bounded_prefix(
+        head in proptest::collection::vec(any::<u8>(), 0..1024),
+        body in proptest::collection::vec(any::<u8>(), 0..1024),
+    ) {
+        let head: Vec<u8> = head.into_iter().filter(|&b| b != b'\n').collect();
+        let mut raw = head.clone();
+        raw.extend_from_slice(b"\r\n\r\n");
+        raw.extend_from_slice(&body);
+        let mut r = LastResponse::new();
+        r.record(HOST, 0, &raw);
+
+        let (kept, len) = r.head();
+        prop_assert_eq!(len, head.len() + 2);
+        prop_assert!(kept.len() <= MAX_HEAD);
+        prop_assert_eq!(kept, &raw[..kept.len()]);
+
+        let (kept, len) = r.body();
+        prop_assert_eq!(len, body.len());
+        prop_assert_eq!(kept.len(), body.len().min(MAX_BODY));
+        prop_assert_eq!(kept, &body[..kept.len()]);
+        prop_assert!(render(&r).is_ascii());
+    }
+}
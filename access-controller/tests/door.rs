@@ -0,0 +1,176 @@
+//! Tests for the door relock decision (invariants U1–U4).
+//!
+//!   U1: with no contact activity the strike relocks exactly at the
+//!       hold deadline, under either policy.
+//!   U2: under `Relock::OnClose`, the door opening and then closing
+//!       relocks at once; closing without having opened does not.
+//!   U3: under `Relock::Timeout`, contact changes never end the hold early.
+//!   U4: the knobs parse their documented values only.
+//!   U5: a grant to a credential Conway tagged holds for the extended
+//!       time (never less than the default); any other grant for the
+//!       default.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test door
+
+#![cfg(feature = "sim")]
+
+use access_controller::door::{
+    parse_extended_unlock, parse_hold_ms, Holds, Relock, Relocked, Unlock, DEFAULT_HOLD_MS,
+    MAX_EXTENDED_UNLOCK, MAX_HOLD_MS,
+};
+use proptest::prelude::*;
+
+// ---------------------------------------------------------------------------
+// U1: timeout
+// ---------------------------------------------------------------------------
+
+#[test]
+fn relocks_at_deadline() {
+    for relock in [Relock::Timeout, Relock::OnClose] {
+        let u = Unlock::start(1_000, 5_000, relock);
+        assert_eq!(u.deadline_ms(), 6_000);
+        assert_eq!(u.tick(5_999), None);
+        assert_eq!(u.tick(6_000), Some(Relocked::Timeout));
+    }
+}
+
+#[test]
+fn hold_spans_the_counter_wrap() {
+    let u = Unlock::start(u64::MAX - 1_000, 5_000, Relock::Timeout);
+    assert_eq!(u.deadline_ms(), 3_999);
+    assert_eq!(u.remaining_ms(u64::MAX - 1_000), 5_000);
+    assert_eq!(u.remaining_ms(u64::MAX), 4_000);
+    assert_eq!(u.tick(3_998), None);
+    assert_eq!(u.remaining_ms(3_999), 0);
+    assert_eq!(u.remaining_ms(4_500), 0);
+    assert_eq!(u.tick(3_999), Some(Relocked::Timeout));
+}
+
+#[test]
+fn contact_after_deadline_is_timeout() {
+    let mut u = Unlock::start(0, 1_000, Relock::OnClose);
+    assert_eq!(u.contact(400, true), None);
+    assert_eq!(u.contact(1_000, false), Some(Relocked::Timeout));
+}
+
+// ---------------------------------------------------------------------------
+// U2 / U3: contact
+// ---------------------------------------------------------------------------
+
+#[test]
+fn open_then_close_relocks_early() {
+    let mut u = Unlock::start(0, 5_000, Relock::OnClose);
+    assert_eq!(u.contact(800, true), None);
+    assert_eq!(u.contact(2_500, false), Some(Relocked::Closed));
+}
+
+#[test]
+fn close_without_open_keeps_holding() {
+    // A bounce on a shut door reads "closed" without the door moving.
+    let mut u = Unlock::start(0, 5_000, Relock::OnClose);
+    assert_eq!(u.contact(100, false), None);
+    assert_eq!(u.tick(4_999), None);
+}
+
+#[test]
+fn timeout_policy_ignores_contact() {
+    let mut u = Unlock::start(0, 5_000, Relock::Timeout);
+    assert_eq!(u.contact(800, true), None);
+    assert_eq!(u.contact(2_500, false), None);
+    assert_eq!(u.tick(5_000), Some(Relocked::Timeout));
+}
+
+proptest! {
+    /// U2/U3: over any contact trace, the hold ends early only under
+    /// OnClose, only on a close after an open, and never past the deadline.
+    #[test]
+    fn relock_decision(
+        hold in 1u64..60_000,
+        on_close in any::<bool>(),
+        trace in proptest::collection::vec((1u64..5_000, any::<bool>()), 0..20),
+    ) {
+        let relock = if on_close { Relock::OnClose } else { Relock::Timeout };
+        let mut u = Unlock::start(0, hold, relock);
+        let mut now = 0;
+        let mut opened = false;
+        for (dt, open) in trace {
+            now += dt;
+            let got = u.contact(now, open);
+            let want = if now >= hold {
+                Some(Relocked::Timeout)
+            } else if !open && opened && on_close {
+                Some(Relocked::Closed)
+            } else {
+                None
+            };
+            prop_assert_eq!(got, want);
+            if got.is_some() {
+                break;
+            }
+            opened |= open;
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// U4: knobs
+// ---------------------------------------------------------------------------
+
+#[test]
+fn parse_knobs() {
+    assert_eq!(Relock::parse("close"), Some(Relock::OnClose));
+    assert_eq!(Relock::parse("timeout"), Some(Relock::Timeout));
+    assert_eq!(Relock::parse("Close"), None);
+    assert_eq!(Relock::default(), Relock::Timeout);
+
+    assert_eq!(parse_hold_ms("5000"), Some(5_000));
+    assert_eq!(parse_hold_ms(&MAX_HOLD_MS.to_string()), Some(MAX_HOLD_MS));
+    assert_eq!(parse_hold_ms(&(MAX_HOLD_MS + 1).to_string()), None);
+    assert_eq!(parse_hold_ms("0"), None);
+    assert_eq!(parse_hold_ms("5s"), None);
+    assert_eq!(DEFAULT_HOLD_MS, 200);
+}
+
+// ---------------------------------------------------------------------------
+// U5: extended unlock
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tagged_credential_gets_extended_hold() {
+    let holds = Holds {
+        default_ms: 200,
+        extended_ms: 8_000,
+    };
+    let tagged = [1_200_345, 0xDEAD_BEEF];
+    assert_eq!(holds.for_grant(1_200_345, &tagged), 8_000);
+    assert_eq!(holds.for_grant(0xDEAD_BEEF, &tagged), 8_000);
+    assert_eq!(holds.for_grant(1_200_346, &tagged), 200);
+    assert_eq!(holds.for_grant(1_200_345, &[]), 200);
+    assert_eq!(Holds::default(), Holds::DEFAULT);
+}
+
+#[test]
+fn extended_hold_is_never_shorter() {
+    let holds = Holds {
+        default_ms: 5_000,
+        extended_ms: 1_000,
+    };
+    assert_eq!(holds.for_grant(7, &[7]), 5_000);
+}
+
+#[test]
+fn parse_extended_unlock_header() {
+    assert_eq!(parse_extended_unlock("7, 12,4294967295").unwrap(), [7, 12, u32::MAX]);
+    assert!(parse_extended_unlock("").unwrap().is_empty());
+    assert!(parse_extended_unlock(" ").unwrap().is_empty());
+    assert_eq!(parse_extended_unlock("7,,12"), None);
+    assert_eq!(parse_extended_unlock("0x10"), None);
+    assert_eq!(parse_extended_unlock("4294967296"), None);
+
+    let full = vec!["1"; MAX_EXTENDED_UNLOCK].join(",");
+    assert_eq!(parse_extended_unlock(&full).unwrap().len(), MAX_EXTENDED_UNLOCK);
+    assert_eq!(parse_extended_unlock(&format!("{},1", full)), None);
+}
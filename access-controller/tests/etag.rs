@@ -0,0 +1,198 @@
+//! Tests for per-host ETag selection (invariants E1–E3).
+//!
+//!   E1: `If-None-Match` is only offered to the host that issued the tag.
+//!   E2: unknown hosts (and an empty store) get no tag.
+//!   E3: a new tag replaces the old one and its origin.
+//!   E4: only visible-ASCII tags are stored; anything else clears.
+//!   E5: a tag longer than MAX_ETAG_LEN is never stored or sent back
+//!       truncated.
+//!   E6: with a full resync every N cycles, exactly every N-th cycle
+//!       after a full list goes out unconditional; a forced cycle that
+//!       fails forces again, and N == 0 never forces.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test etag
+
+#![cfg(feature = "sim")]
+
+use access_controller::etag::{parse_full_resync, valid_tag, FullResync, HostEtag, MAX_ETAG_LEN};
+use proptest::prelude::*;
+
+const A: [u8; 4] = [10, 0, 0, 1];
+const B: [u8; 4] = [10, 0, 0, 2];
+
+#[test]
+fn tag_is_offered_only_to_its_issuer() {
+    // E1 / E2
+    let mut e = HostEtag::new();
+    e.set(A, "\"abc\"");
+    assert_eq!(e.for_host(A), Some("\"abc\""));
+    assert_eq!(e.for_host(B), None);
+    assert_eq!(e.host(), Some(A));
+}
+
+#[test]
+fn empty_store_offers_nothing() {
+    // E2
+    let e = HostEtag::new();
+    assert!(e.is_empty());
+    assert_eq!(e.for_host(A), None);
+    assert_eq!(e.as_str(), "");
+}
+
+#[test]
+fn failover_replaces_tag_and_origin() {
+    // E3: after failing over to B, A's old tag must not be sent to A.
+    let mut e = HostEtag::new();
+    e.set(A, "v1");
+    e.set(B, "v2");
+    assert_eq!(e.for_host(A), None);
+    assert_eq!(e.for_host(B), Some("v2"));
+}
+
+#[test]
+fn empty_or_oversized_tag_clears() {
+    let mut e = HostEtag::new();
+    e.set(A, "v1");
+    e.set(A, "");
+    assert!(e.is_empty());
+
+    e.set(A, "v1");
+    e.set(B, &"x".repeat(MAX_ETAG_LEN + 1));
+    assert!(e.is_empty());
+    assert_eq!(e.for_host(A), None);
+
+    e.set(B, &"x".repeat(MAX_ETAG_LEN));
+    assert_eq!(e.for_host(B).map(str::len), Some(MAX_ETAG_LEN));
+}
+
+#[test]
+fn non_ascii_tag_clears() {
+    // E4
+    let mut e = HostEtag::new();
+    e.set(A, "W/\"v1\"");
+    assert_eq!(e.for_host(A), Some("W/\"v1\""));
+    for bad in ["\"caf\u{e9}\"", "\"a\u{7f}\"", "a b", "\"v1\"\r\nX: y"] {
+        assert!(!valid_tag(bad), "{bad:?}");
+        e.set(A, "v1");
+        e.set(A, bad);
+        assert!(e.is_empty(), "{bad:?}");
+    }
+}
+
+#[test]
+fn long_tag_is_not_truncated() {
+    // E5: a cut-down tag would never match, forcing a 200 every sync.
+    let long = format!("\"{}\"", "a".repeat(MAX_ETAG_LEN));
+    let mut e = HostEtag::new();
+    assert!(e.set(A, "v1"));
+    assert!(!e.set(A, &long));
+    assert!(e.is_empty());
+    assert_eq!(e.for_host(A), None);
+    assert_eq!(e.as_str(), "");
+
+    let fits = "b".repeat(MAX_ETAG_LEN);
+    assert!(e.set(A, &fits));
+    assert_eq!(e.for_host(A), Some(fits.as_str()));
+}
+
+#[test]
+fn full_resync_every_n_cycles() {
+    // E6
+    let mut f = FullResync::new();
+    let forced: Vec<bool> = (0..9)
+        .map(|_| {
+            let force = f.begin_cycle(3);
+            if force {
+                f.fetched();
+            }
+            force
+        })
+        .collect();
+    assert_eq!(
+        forced,
+        [false, false, true, false, false, true, false, false, true]
+    );
+}
+
+#[test]
+fn failed_full_resync_stays_due() {
+    // E6
+    let mut f = FullResync::new();
+    assert!(!f.begin_cycle(2));
+    assert!(f.begin_cycle(2));
+    // The forced cycle failed: no fetched(), so the next forces too.
+    assert!(f.begin_cycle(2));
+    assert_eq!(f.since_full(), 3);
+    f.fetched();
+    assert!(!f.begin_cycle(2));
+}
+
+#[test]
+fn ordinary_200_restarts_the_count() {
+    // E6: a list the server changed is just as full as a forced one.
+    let mut f = FullResync::new();
+    assert!(!f.begin_cycle(3));
+    assert!(!f.begin_cycle(3));
+    f.fetched();
+    assert!(!f.begin_cycle(3));
+    assert!(!f.begin_cycle(3));
+    assert!(f.begin_cycle(3));
+}
+
+#[test]
+fn full_resync_disabled_or_every_cycle() {
+    // E6
+    let mut f = FullResync::new();
+    for _ in 0..1000 {
+        assert!(!f.begin_cycle(0));
+    }
+    assert!(f.begin_cycle(1));
+    f.fetched();
+    assert!(f.begin_cycle(1));
+
+    assert_eq!(parse_full_resync("360"), Some(360));
+    assert_eq!(parse_full_resync("1"), Some(1));
+    assert_eq!(parse_full_resync("0"), None);
+    assert_eq!(parse_full_resync("-5"), None);
+    assert_eq!(parse_full_resync("hourly"), None);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// E5: stored tags are always exactly what the server sent.
+    #[test]
+    fn stored_tag_is_verbatim(tag in "[!-~]{1,128}") {
+        let mut e = HostEtag::new();
+        let stored = e.set(A, &tag);
+        prop_assert_eq!(stored, tag.len() <= MAX_ETAG_LEN);
+        if stored {
+            prop_assert_eq!(e.for_host(A), Some(tag.as_str()));
+        } else {
+            prop_assert_eq!(e.for_host(A), None);
+        }
+    }
+
+    /// E6: cycles between forced fetches are exactly `every` apart
+    /// when every fetch succeeds.
+    #[test]
+    fn forced_cycles_are_evenly_spaced(every in 1u32..50, cycles in 1usize..500) {
+        let mut f = FullResync::new();
+        let mut last = 0usize;
+        for cycle in 1..=cycles {
+            if f.begin_cycle(every) {
+                prop_assert_eq!(cycle - last, every as usize);
+                last = cycle;
+                f.fetched();
+            }
+        }
+        prop_assert!(cycles - last < every as usize);
+    }
+}
@@ -0,0 +1,674 @@
+//! Tests for the pending-event ring behind `sync::EventBuffer`
+//! (invariants V1–V10).
+//!
+//!   V1: events come out in push order; at most MAX_EVENTS - 1 pend, and
+//!       a push into a full ring drops exactly the oldest.
+//!   V2: peek never removes; commit of an undisturbed peek removes
+//!       exactly the peeked events and advances first_seq by as many.
+//!   V3: a commit after overflow removes whatever is left of the peeked
+//!       events and never an event pushed after the peek.
+//!   V4: under concurrent producers and a syncing consumer, nothing is
+//!       delivered twice, each producer's events arrive in order, and
+//!       pushes == removed + pending.
+//!   V5: under any interleaving of pushes (overflowing or not), peeks
+//!       and commits of any earlier peek, in any order and any number of
+//!       times: at most MAX_EVENTS - 1 pend, first_seq + len == pushes
+//!       (the tail never passes the head), a committed event is never
+//!       peeked again, and an event pushed after a peek survives its
+//!       commit.
+//!   V6: `peek_up_to(max)` peeks the `max` oldest events; committing
+//!       them advances first_seq by exactly that many, so a backlog
+//!       drains over successive syncs in order, none lost or repeated.
+//!   V7: with grants not reported, push keeps denials and discards
+//!       grants without disturbing what is pending; the default keeps
+//!       both.
+//!   V8: the audit ring keeps the last AUDIT_LEN decisions, renders them
+//!       newest first, and is untouched by the pending ring's commits.
+//!   V9: with a dedup window, an event identical to the last one kept
+//!       within the window is a repeat; after the window, or once a
+//!       different event intervenes, it is kept again.
+//!   V10: sequence numbers increase by one per push, overflow included,
+//!       and never repeat across a reboot: a ring resumed at the
+//!       persisted lease limit numbers on past every number the previous
+//!       boot used, as long as the lease was renewed on schedule.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test events
+
+#![cfg(feature = "sim")]
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use access_controller::events::{
+    self, AccessEvent, AuditRing, Commit, Dedup, EventRing, SeqLease, AUDIT_LEN, MAX_DEDUP_MS,
+    MAX_EVENTS, SEQ_LEASE,
+};
+use proptest::prelude::*;
+
+const CAPACITY: usize = MAX_EVENTS - 1;
+
+fn ev(fob: u32) -> AccessEvent {
+    AccessEvent { fob, allowed: true }
+}
+
+fn pending(r: &EventRing) -> Vec<u32> {
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    let (n, _) = r.peek(&mut out);
+    out[..n].iter().map(|e| e.fob).collect()
+}
+
+#[test]
+fn fifo_until_full_then_drops_oldest() {
+    let mut r = EventRing::new();
+    assert!(r.is_empty());
+    for i in 0..CAPACITY as u32 {
+        assert!(!r.push(ev(i)));
+    }
+    assert_eq!(r.len(), CAPACITY);
+    assert!(r.push(ev(100)));
+    let got = pending(&r);
+    assert_eq!(got.len(), CAPACITY);
+    assert_eq!(got[0], 1);
+    assert_eq!(*got.last().unwrap(), 100);
+}
+
+#[test]
+fn commit_removes_exactly_the_peek() {
+    let mut r = EventRing::new();
+    for i in 0..5 {
+        r.push(ev(i));
+    }
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    let (n, seq) = r.peek(&mut out);
+    assert_eq!((n, seq), (5, 0));
+    r.push(ev(5));
+    assert_eq!(r.commit(n, seq), Commit::Exact);
+    assert_eq!(pending(&r), vec![5]);
+    assert_eq!(r.peek(&mut out).1, 5);
+}
+
+#[test]
+fn commit_after_overflow_adjusts() {
+    let mut r = EventRing::new();
+    for i in 0..CAPACITY as u32 {
+        r.push(ev(i));
+    }
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    let (n, seq) = r.peek(&mut out);
+    // Two more swipes during the sync drop events 0 and 1.
+    r.push(ev(100));
+    r.push(ev(101));
+    assert_eq!(
+        r.commit(n, seq),
+        Commit::Adjusted {
+            removed: CAPACITY - 2
+        }
+    );
+    assert_eq!(pending(&r), vec![100, 101]);
+}
+
+#[test]
+fn commit_after_overflow_past_the_peek_keeps_new_events() {
+    let mut r = EventRing::new();
+    for i in 0..3 {
+        r.push(ev(i));
+    }
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    let (n, seq) = r.peek(&mut out);
+    for i in 0..CAPACITY as u32 {
+        r.push(ev(100 + i));
+    }
+    let before = pending(&r);
+    assert_eq!(r.commit(n, seq), Commit::AlreadyDropped);
+    assert_eq!(pending(&r), before);
+}
+
+/// V4: real ring, real threads. Each producer pushes `PER` tagged
+/// events; the consumer plays `sync_with_conway`: peek, "send", commit.
+#[test]
+fn concurrent_producers_and_syncer() {
+    const PRODUCERS: u32 = 4;
+    const PER: u32 = 2_000;
+
+    let ring = Arc::new(Mutex::new(EventRing::new()));
+    let done = Arc::new(Mutex::new(0u32));
+
+    let producers: Vec<_> = (0..PRODUCERS)
+        .map(|p| {
+            let ring = Arc::clone(&ring);
+            let done = Arc::clone(&done);
+            thread::spawn(move || {
+                for i in 0..PER {
+                    ring.lock().unwrap().push(ev(p << 16 | i));
+                    if i % 64 == 0 {
+                        thread::yield_now();
+                    }
+                }
+                *done.lock().unwrap() += 1;
+            })
+        })
+        .collect();
+
+    let consumer = {
+        let ring = Arc::clone(&ring);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            let mut delivered = Vec::new();
+            let mut out = [AccessEvent::default(); MAX_EVENTS];
+            loop {
+                let finished = *done.lock().unwrap() == PRODUCERS;
+                let (n, seq) = ring.lock().unwrap().peek(&mut out);
+                // The upload happens without the lock held.
+                delivered.extend(out[..n].iter().map(|e| e.fob));
+                thread::yield_now();
+                ring.lock().unwrap().commit(n, seq);
+                if finished && ring.lock().unwrap().is_empty() {
+                    return delivered;
+                }
+            }
+        })
+    };
+
+    for p in producers {
+        p.join().unwrap();
+    }
+    let delivered = consumer.join().unwrap();
+
+    let mut seen = HashSet::new();
+    let mut last = vec![None; PRODUCERS as usize];
+    for fob in &delivered {
+        let (p, i) = ((fob >> 16) as usize, fob & 0xFFFF);
+        assert!(last[p] < Some(i), "producer {} out of order at {}", p, i);
+        last[p] = Some(i);
+        // A sync may re-send an event only if its commit was dropped,
+        // which this consumer never does.
+        assert!(seen.insert(*fob), "delivered twice: {:#x}", fob);
+    }
+    let r = ring.lock().unwrap();
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    let (n, removed) = r.peek(&mut out);
+    assert_eq!(removed + n as u64, u64::from(PRODUCERS * PER));
+}
+
+/// V5 steps. `Commit(i)` commits the `i`-th most recent peek still
+/// remembered, so stale and repeated commits are covered.
+#[derive(Clone, Debug)]
+enum Step {
+    Push,
+    Peek,
+    Commit(usize),
+    Forget,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Push,
+    Sync { acked: bool },
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// V1/V2 against a VecDeque model, with syncs that run to completion
+    /// between pushes (acked or not).
+    #[test]
+    fn prop_matches_model(ops in prop::collection::vec(
+        prop_oneof![
+            3 => Just(Op::Push),
+            1 => any::<bool>().prop_map(|acked| Op::Sync { acked }),
+        ],
+        0..200,
+    )) {
+        let mut r = EventRing::new();
+        let mut model: VecDeque<u32> = VecDeque::new();
+        let mut removed = 0u64;
+        let mut next = 0u32;
+        for op in ops {
+            match op {
+                Op::Push => {
+                    let dropped = model.len() == CAPACITY;
+                    if dropped {
+                        model.pop_front();
+                        removed += 1;
+                    }
+                    model.push_back(next);
+                    prop_assert_eq!(r.push(ev(next)), dropped);
+                    next += 1;
+                }
+                Op::Sync { acked } => {
+                    let mut out = [AccessEvent::default(); MAX_EVENTS];
+                    let (n, seq) = r.peek(&mut out);
+                    prop_assert_eq!(seq, removed);
+                    prop_assert_eq!(
+                        out[..n].iter().map(|e| e.fob).collect::<Vec<_>>(),
+                        model.iter().copied().collect::<Vec<_>>()
+                    );
+                    if acked {
+                        prop_assert_eq!(r.commit(n, seq), Commit::Exact);
+                        model.clear();
+                        removed += n as u64;
+                    }
+                }
+            }
+            prop_assert_eq!(r.len(), model.len());
+            prop_assert_eq!(r.is_empty(), model.is_empty());
+        }
+    }
+
+    /// V3: with `k` pushes landing during a sync of `pre` events, the
+    /// commit removes every peeked event and none of the `k`.
+    #[test]
+    fn prop_commit_spares_new_events(pre in 0..MAX_EVENTS, k in 0..MAX_EVENTS) {
+        let mut r = EventRing::new();
+        for i in 0..pre as u32 {
+            r.push(ev(i));
+        }
+        let mut out = [AccessEvent::default(); MAX_EVENTS];
+        let (n, seq) = r.peek(&mut out);
+        for i in 0..k as u32 {
+            r.push(ev(1000 + i));
+        }
+        r.commit(n, seq);
+        let kept = pending(&r);
+        let new_kept = kept.iter().filter(|&&f| f >= 1000).count();
+        prop_assert_eq!(new_kept, k.min(CAPACITY));
+        prop_assert!(kept.iter().all(|&f| f >= 1000));
+    }
+
+    /// V5: pushes, peeks and commits interleaved at random, with several
+    /// peeks outstanding at once. Fobs are the push index, so an event's
+    /// fob is also its absolute sequence number.
+    #[test]
+    fn prop_interleaved_commits(steps in prop::collection::vec(
+        prop_oneof![
+            6 => Just(Step::Push),
+            2 => Just(Step::Peek),
+            2 => (0usize..4).prop_map(Step::Commit),
+            1 => Just(Step::Forget),
+        ],
+        0..300,
+    )) {
+        let mut r = EventRing::new();
+        let mut pushed = 0u32;
+        let mut peeks: Vec<(usize, u64, Vec<u32>)> = Vec::new();
+        let mut committed: HashSet<u32> = HashSet::new();
+        for step in steps {
+            match step {
+                Step::Push => {
+                    r.push(ev(pushed));
+                    pushed += 1;
+                }
+                Step::Peek => {
+                    let mut out = [AccessEvent::default(); MAX_EVENTS];
+                    let (n, seq) = r.peek(&mut out);
+                    let fobs: Vec<u32> = out[..n].iter().map(|e| e.fob).collect();
+                    for f in &fobs {
+                        prop_assert!(!committed.contains(f), "{} peeked after commit", f);
+                    }
+                    prop_assert!(fobs.iter().copied().eq(seq as u32..seq as u32 + n as u32));
+                    peeks.push((n, seq, fobs));
+                }
+                Step::Commit(i) if i < peeks.len() => {
+                    let (n, seq, fobs) = peeks[peeks.len() - 1 - i].clone();
+                    let before = pending(&r);
+                    r.commit(n, seq);
+                    let after = pending(&r);
+                    // Exactly the peeked events still pending went.
+                    let gone: Vec<u32> =
+                        before.iter().copied().filter(|f| !after.contains(f)).collect();
+                    prop_assert!(gone.iter().all(|f| fobs.contains(f)));
+                    prop_assert_eq!(
+                        after,
+                        before.iter().copied().filter(|f| !fobs.contains(f)).collect::<Vec<_>>()
+                    );
+                    committed.extend(gone);
+                }
+                Step::Commit(_) => {}
+                Step::Forget => {
+                    peeks.clear();
+                }
+            }
+            let mut out = [AccessEvent::default(); MAX_EVENTS];
+            let (n, first_seq) = r.peek(&mut out);
+            prop_assert!(r.len() <= CAPACITY);
+            prop_assert_eq!(n, r.len());
+            prop_assert_eq!(first_seq + n as u64, u64::from(pushed));
+        }
+    }
+}
+
+// ---------- V6 ----------
+
+/// One capped sync: peek, "send", commit. Returns the fobs sent.
+fn capped_sync(r: &mut EventRing, max: usize) -> Vec<u32> {
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    let mut scratch = [AccessEvent::default(); MAX_EVENTS];
+    let (n, seq) = r.peek_up_to(&mut out, max);
+    assert_eq!(r.commit(n, seq), Commit::Exact);
+    assert_eq!(r.peek(&mut scratch).1, seq + n as u64);
+    out[..n].iter().map(|e| e.fob).collect()
+}
+
+#[test]
+fn capped_peek_drains_across_syncs() {
+    let mut r = EventRing::new();
+    for i in 0..12 {
+        r.push(ev(i));
+    }
+    assert_eq!(capped_sync(&mut r, 5), [0, 1, 2, 3, 4]);
+    // A swipe between syncs queues behind the backlog.
+    r.push(ev(12));
+    assert_eq!(capped_sync(&mut r, 5), [5, 6, 7, 8, 9]);
+    assert_eq!(capped_sync(&mut r, 5), [10, 11, 12]);
+    assert!(r.is_empty());
+    assert_eq!(capped_sync(&mut r, 5), [] as [u32; 0]);
+
+    // Caps at or past the ring's size are the same as no cap.
+    for i in 0..3 {
+        r.push(ev(i));
+    }
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    assert_eq!(r.peek_up_to(&mut out, usize::MAX), r.peek(&mut out));
+    assert_eq!(r.peek_up_to(&mut out, 0), (0, 13));
+}
+
+#[test]
+fn max_per_sync_knob() {
+    assert_eq!(events::parse_max_per_sync("1"), Some(1));
+    assert_eq!(events::parse_max_per_sync("10"), Some(10));
+    assert_eq!(events::parse_max_per_sync("20"), Some(MAX_EVENTS));
+    assert_eq!(events::parse_max_per_sync("21"), None);
+    assert_eq!(events::parse_max_per_sync("0"), None);
+    assert_eq!(events::parse_max_per_sync("ten"), None);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// V6: a capped peek is a prefix of the full peek, and capped syncs
+    /// interleaved with swipes deliver every surviving event once, in
+    /// push order.
+    #[test]
+    fn capped_syncs_deliver_in_order(
+        max in 1usize..=MAX_EVENTS,
+        swipes in prop::collection::vec(0usize..8, 1..20),
+    ) {
+        let mut r = EventRing::new();
+        let mut next = 0u32;
+        let mut sent = Vec::new();
+        for burst in swipes {
+            for _ in 0..burst {
+                r.push(ev(next));
+                next += 1;
+            }
+            let mut all = [AccessEvent::default(); MAX_EVENTS];
+            let (total, _) = r.peek(&mut all);
+            let mut some = [AccessEvent::default(); MAX_EVENTS];
+            let (n, _) = r.peek_up_to(&mut some, max);
+            prop_assert_eq!(n, total.min(max));
+            prop_assert_eq!(&some[..n], &all[..n]);
+            sent.extend(capped_sync(&mut r, max));
+        }
+        while !r.is_empty() {
+            sent.extend(capped_sync(&mut r, max));
+        }
+        // Overflow may drop some; what went out is strictly increasing.
+        prop_assert!(sent.windows(2).all(|w| w[0] < w[1]));
+        prop_assert_eq!(sent.last().copied(), next.checked_sub(1));
+    }
+}
+
+// ---------- V7 ----------
+
+#[test]
+fn grants_reported_by_default() {
+    let mut r = EventRing::new();
+    assert!(r.report_grants());
+    r.push(AccessEvent { fob: 1, allowed: true });
+    r.push(AccessEvent { fob: 2, allowed: false });
+    assert_eq!(r.len(), 2);
+}
+
+#[test]
+fn denials_only_skips_grants() {
+    let mut r = EventRing::new();
+    r.set_report_grants(false);
+    r.push(AccessEvent { fob: 1, allowed: false });
+    assert!(!r.push(AccessEvent { fob: 2, allowed: true }));
+    r.push(AccessEvent { fob: 3, allowed: false });
+    assert_eq!(pending(&r), [1, 3]);
+    // A grant never pushes a pending denial out of a full ring.
+    for fob in 4..(2 + CAPACITY as u32) {
+        r.push(AccessEvent { fob, allowed: false });
+    }
+    assert_eq!(r.len(), CAPACITY);
+    assert!(!r.push(AccessEvent { fob: 99, allowed: true }));
+    assert_eq!(pending(&r)[0], 1);
+}
+
+#[test]
+fn report_grants_knob() {
+    assert_eq!(events::parse_report_grants("1"), Some(true));
+    assert_eq!(events::parse_report_grants("0"), Some(false));
+    assert_eq!(events::parse_report_grants("yes"), None);
+    assert_eq!(events::parse_report_grants(""), None);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// V7: with grants off, the pending events are the newest denials,
+    /// in push order.
+    #[test]
+    fn denials_only_keeps_every_denial(
+        allowed in prop::collection::vec(any::<bool>(), 0..(2 * MAX_EVENTS)),
+    ) {
+        let mut r = EventRing::new();
+        r.set_report_grants(false);
+        for (fob, &allowed) in allowed.iter().enumerate() {
+            r.push(AccessEvent { fob: fob as u32, allowed });
+        }
+        let denied: Vec<u32> = allowed
+            .iter()
+            .enumerate()
+            .filter(|(_, &a)| !a)
+            .map(|(fob, _)| fob as u32)
+            .collect();
+        let kept = denied.len().min(CAPACITY);
+        prop_assert_eq!(pending(&r), &denied[denied.len() - kept..]);
+    }
+}
+
+// ---------- V8 ----------
+
+fn audit_json(a: &AuditRing) -> String {
+    let mut s = String::new();
+    a.render_json(&mut s).unwrap();
+    s
+}
+
+#[test]
+fn audit_renders_newest_first() {
+    let mut a = AuditRing::new();
+    assert!(a.is_empty());
+    assert_eq!(audit_json(&a), "{\"total\":0,\"events\":[]}\n");
+    a.push(AccessEvent { fob: 7, allowed: true }, 100);
+    a.push(AccessEvent { fob: 9, allowed: false }, 2_500);
+    assert_eq!(a.len(), 2);
+    assert_eq!(
+        audit_json(&a),
+        "{\"total\":2,\"events\":[\
+         {\"fob\":9,\"allowed\":false,\"at_ms\":2500},\
+         {\"fob\":7,\"allowed\":true,\"at_ms\":100}]}\n"
+    );
+}
+
+#[test]
+fn audit_keeps_the_last_entries() {
+    let mut a = AuditRing::new();
+    let n = AUDIT_LEN as u32 + 5;
+    for fob in 0..n {
+        a.push(ev(fob), u64::from(fob) * 10);
+    }
+    assert_eq!(a.len(), AUDIT_LEN);
+    assert_eq!(a.total(), u64::from(n));
+    let fobs: Vec<u32> = a.iter().map(|e| e.event.fob).collect();
+    let want: Vec<u32> = (5..n).rev().collect();
+    assert_eq!(fobs, want);
+    assert!(a.iter().all(|e| e.at_ms == u64::from(e.event.fob) * 10));
+}
+
+#[test]
+fn audit_survives_commit() {
+    // The firmware feeds both rings from the same push; committing the
+    // pending events must leave the audit view as it was.
+    let mut r = EventRing::new();
+    let mut a = AuditRing::new();
+    for fob in 0..3 {
+        r.push(ev(fob));
+        a.push(ev(fob), 0);
+    }
+    let before = audit_json(&a);
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    let (count, first_seq) = r.peek(&mut out);
+    r.commit(count, first_seq);
+    assert!(r.is_empty());
+    assert_eq!(a.len(), 3);
+    assert_eq!(audit_json(&a), before);
+}
+
+// ---------- V9 ----------
+
+#[test]
+fn dedup_suppresses_within_window() {
+    let denied = AccessEvent { fob: 7, allowed: false };
+    let mut d = Dedup::new(1_000);
+    assert!(!d.is_repeat(denied, 5_000));
+    assert!(d.is_repeat(denied, 5_300));
+    assert!(d.is_repeat(denied, 5_999));
+    // Measured from the last event kept, not the last repeat.
+    assert!(!d.is_repeat(denied, 6_000));
+    assert!(d.is_repeat(denied, 6_100));
+}
+
+#[test]
+fn dedup_keeps_different_events() {
+    let mut d = Dedup::new(1_000);
+    assert!(!d.is_repeat(AccessEvent { fob: 7, allowed: false }, 0));
+    // Same fob, other decision (granted after a sync).
+    assert!(!d.is_repeat(AccessEvent { fob: 7, allowed: true }, 10));
+    assert!(!d.is_repeat(AccessEvent { fob: 8, allowed: true }, 20));
+    // Not consecutive any more.
+    assert!(!d.is_repeat(AccessEvent { fob: 7, allowed: true }, 30));
+}
+
+#[test]
+fn dedup_off_by_default() {
+    let mut d = Dedup::default();
+    assert_eq!(d.window_ms(), 0);
+    assert!(!d.is_repeat(ev(1), 0));
+    assert!(!d.is_repeat(ev(1), 0));
+}
+
+#[test]
+fn dedup_knob() {
+    assert_eq!(events::parse_dedup_ms("0"), Some(0));
+    assert_eq!(events::parse_dedup_ms("2000"), Some(2_000));
+    assert_eq!(events::parse_dedup_ms(&MAX_DEDUP_MS.to_string()), Some(MAX_DEDUP_MS));
+    assert_eq!(events::parse_dedup_ms(&(MAX_DEDUP_MS + 1).to_string()), None);
+    assert_eq!(events::parse_dedup_ms("2s"), None);
+}
+
+// ---------- V10 ----------
+
+#[test]
+fn seq_numbers_each_push() {
+    let mut r = EventRing::new();
+    assert_eq!(r.next_seq(), 0);
+    let pushes = CAPACITY as u64 + 10;
+    for fob in 0..pushes as u32 {
+        r.push(ev(fob));
+    }
+    // Overflow dropped the oldest, but their numbers stay used.
+    assert_eq!(r.next_seq(), pushes);
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    let (n, first_seq) = r.peek(&mut out);
+    assert_eq!(first_seq, pushes - n as u64);
+    r.commit(n, first_seq);
+    assert_eq!(r.next_seq(), pushes);
+}
+
+#[test]
+fn seq_resume_only_when_empty() {
+    let mut r = EventRing::new();
+    assert!(r.resume_at(5_000));
+    assert_eq!(r.next_seq(), 5_000);
+    r.push(ev(1));
+    assert!(!r.resume_at(9_000));
+    let mut out = [AccessEvent::default(); MAX_EVENTS];
+    assert_eq!(r.peek(&mut out), (1, 5_000));
+    assert_eq!(r.next_seq(), 5_001);
+}
+
+#[test]
+fn seq_lease_renews_at_half() {
+    let mut lease = SeqLease::resume(0);
+    assert_eq!(lease.renewal(0), Some(SEQ_LEASE));
+    lease.extended(SEQ_LEASE);
+    assert_eq!(lease.renewal(SEQ_LEASE / 2), None);
+    assert_eq!(lease.renewal(SEQ_LEASE / 2 + 1), Some(SEQ_LEASE * 3 / 2 + 1));
+    // A late, smaller write never shrinks the lease.
+    lease.extended(10);
+    assert_eq!(lease.limit(), SEQ_LEASE);
+}
+
+proptest! {
+    /// Boots that each push some events, renewing the lease every
+    /// `renew_every` pushes (a sync), with power cut anywhere: the
+    /// numbers handed out over all boots never repeat or go back.
+    #[test]
+    fn seq_monotonic_across_reboots(
+        boots in prop::collection::vec(0u32..2_000, 1..8),
+        renew_every in 1u32..(SEQ_LEASE / 2) as u32,
+    ) {
+        let mut flash = 0u64;
+        let mut last: Option<u64> = None;
+        for pushes in boots {
+            let mut lease = SeqLease::resume(flash);
+            let mut r = EventRing::new();
+            prop_assert!(r.resume_at(lease.limit()));
+            for i in 0..pushes {
+                if i % renew_every == 0 {
+                    if let Some(limit) = lease.renewal(r.next_seq()) {
+                        flash = limit;
+                        lease.extended(limit);
+                    }
+                }
+                let seq = r.next_seq();
+                prop_assert!(seq < lease.limit());
+                prop_assert!(last.is_none_or(|last| seq > last));
+                last = Some(seq);
+                r.push(ev(i));
+                let mut out = [AccessEvent::default(); MAX_EVENTS];
+                let (n, first_seq) = r.peek(&mut out);
+                prop_assert_eq!(first_seq + n as u64, seq + 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Tests for Conway server failover selection (invariants F1–F3).
+//!
+//!   F1: every host is tried at most once per cycle, last-good first.
+//!   F2: the first reachable host in that order wins and is remembered.
+//!   F3: a shrunken host list falls back to starting at the primary.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test failover
+
+#![cfg(feature = "sim")]
+
+use access_controller::failover::Failover;
+use proptest::prelude::*;
+
+/// Run one sync cycle against `up` (reachability per host). Returns the
+/// hosts attempted and the one that answered, like `sync_with_conway`.
+fn cycle(f: &mut Failover, up: &[bool]) -> (Vec<usize>, Option<usize>) {
+    let mut tried = Vec::new();
+    for idx in f.order(up.len()) {
+        tried.push(idx);
+        if up[idx] {
+            f.succeeded(idx);
+            return (tried, Some(idx));
+        }
+    }
+    (tried, None)
+}
+
+#[test]
+fn primary_first_when_healthy() {
+    let mut f = Failover::new();
+    assert_eq!(cycle(&mut f, &[true, true, true]), (vec![0], Some(0)));
+    assert_eq!(f.last_good(), 0);
+}
+
+#[test]
+fn fails_over_in_list_order_and_sticks() {
+    let mut f = Failover::new();
+    // Primary down: fall through to #1.
+    assert_eq!(cycle(&mut f, &[false, true, true]), (vec![0, 1], Some(1)));
+    // Next cycle goes straight to #1, even if the primary came back.
+    assert_eq!(cycle(&mut f, &[true, true, true]), (vec![1], Some(1)));
+    // #1 dies: #2 is next, not the primary.
+    assert_eq!(cycle(&mut f, &[true, false, true]), (vec![1, 2], Some(2)));
+    // #2 dies: wrap around to the primary.
+    assert_eq!(cycle(&mut f, &[true, false, false]), (vec![2, 0], Some(0)));
+}
+
+#[test]
+fn all_down_keeps_last_good() {
+    let mut f = Failover::new();
+    cycle(&mut f, &[false, true]);
+    assert_eq!(cycle(&mut f, &[false, false]), (vec![1, 0], None));
+    assert_eq!(f.last_good(), 1);
+}
+
+#[test]
+fn shrunken_host_list_restarts_at_primary() {
+    // F3: operator removed the fallback we were using.
+    let mut f = Failover::new();
+    cycle(&mut f, &[false, false, true]);
+    assert_eq!(f.last_good(), 2);
+    assert_eq!(f.order(2).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(f.order(0).count(), 0);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 1024,
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn prop_cycle_invariants(
+        trace in proptest::collection::vec(proptest::collection::vec(any::<bool>(), 4), 1..32),
+    ) {
+        let mut f = Failover::new();
+        for up in trace {
+            let before = f.last_good();
+            let (tried, winner) = cycle(&mut f, &up);
+            // F1: starts at last-good, no host tried twice.
+            prop_assert_eq!(tried[0], before);
+            let mut sorted = tried.clone();
+            sorted.sort();
+            sorted.dedup();
+            prop_assert_eq!(sorted.len(), tried.len());
+            match winner {
+                // F2: winner is reachable, everything before it was not.
+                Some(w) => {
+                    prop_assert!(up[w]);
+                    prop_assert!(tried[..tried.len() - 1].iter().all(|&i| !up[i]));
+                    prop_assert_eq!(f.last_good(), w);
+                }
+                None => {
+                    prop_assert!(up.iter().all(|&u| !u));
+                    prop_assert_eq!(tried.len(), 4);
+                    prop_assert_eq!(f.last_good(), before);
+                }
+            }
+        }
+    }
+}
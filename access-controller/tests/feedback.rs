@@ -0,0 +1,146 @@
+//! Tests for the reader feedback pattern knobs (invariants N1–N3).
+//!
+//!   N1: a pattern string decodes step by step into cues, in order, with
+//!       the outputs it names on and the others off; empty is silence.
+//!   N2: malformed steps, repeated or unknown outputs, zero-length steps,
+//!       and patterns too long in steps or time are rejected whole.
+//!   N3: the defaults are the original fixed patterns and are within the
+//!       limits.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test feedback
+
+#![cfg(feature = "sim")]
+
+use access_controller::feedback::{
+    cues, parse, Cue, DEFAULT_DENY, DEFAULT_GRANT, MAX_CUES, MAX_PATTERN_MS,
+};
+use proptest::prelude::*;
+
+fn cue(led: bool, beep: bool, ms: u64) -> Cue {
+    Cue { led, beep, ms }
+}
+
+// ---------- N1 ----------
+
+#[test]
+fn n1_decodes_steps() {
+    assert_eq!(
+        parse("LB:100,L:100").unwrap(),
+        [cue(true, true, 100), cue(true, false, 100)]
+    );
+    assert_eq!(
+        parse(" B:50 , - : 200, bl:75 ").unwrap(),
+        [cue(false, true, 50), cue(false, false, 200), cue(true, true, 75)]
+    );
+    assert_eq!(parse("B:600").unwrap(), [cue(false, true, 600)]);
+    assert_eq!(parse("L:500").unwrap(), [cue(true, false, 500)]);
+}
+
+#[test]
+fn n1_empty_is_silent() {
+    assert!(parse("").unwrap().is_empty());
+    assert!(parse("  ").unwrap().is_empty());
+}
+
+// ---------- N2 ----------
+
+#[test]
+fn n2_rejects_malformed_patterns() {
+    for bad in [
+        "L",           // no duration
+        "L:",          // empty duration
+        ":100",        // no outputs
+        "L:0",         // zero-length step
+        "L:-5",        // negative
+        "L:1e3",       // not an integer
+        "X:100",       // unknown output
+        "LL:100",      // output named twice
+        "L-:100",      // '-' mixed with an output
+        "--:100",      // '-' twice
+        "L:100,",      // trailing empty step
+        "L:100,,B:50", // empty step
+        "L:100;B:50",  // wrong separator
+        "L:100:5",     // extra field
+    ] {
+        assert_eq!(parse(bad), None, "{:?}", bad);
+    }
+}
+
+#[test]
+fn n2_rejects_patterns_over_the_limits() {
+    let at_max = vec!["B:10"; MAX_CUES].join(",");
+    assert_eq!(parse(&at_max).unwrap().len(), MAX_CUES);
+    let over = vec!["B:10"; MAX_CUES + 1].join(",");
+    assert_eq!(parse(&over), None);
+
+    assert!(parse(&format!("L:{}", MAX_PATTERN_MS)).is_some());
+    assert_eq!(parse(&format!("L:{}", MAX_PATTERN_MS + 1)), None);
+    assert_eq!(parse(&format!("L:{},B:1", MAX_PATTERN_MS)), None);
+    assert_eq!(parse("L:18446744073709551615,B:1"), None);
+}
+
+// ---------- N3 ----------
+
+#[test]
+fn n3_defaults() {
+    assert_eq!(parse("LB:100,L:100").unwrap(), DEFAULT_GRANT);
+    assert_eq!(
+        parse("B:100,-:100,B:100,-:100,B:100,-:100").unwrap(),
+        DEFAULT_DENY
+    );
+    for default in [DEFAULT_GRANT, DEFAULT_DENY] {
+        assert_eq!(cues(default), default);
+        assert!(default.len() <= MAX_CUES);
+        assert!(default.iter().map(|c| c.ms).sum::<u64>() <= MAX_PATTERN_MS);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 1024,
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// N1/N2: a pattern rendered in the knob syntax parses back to the
+    /// same cues exactly when it is within the limits.
+    #[test]
+    fn prop_render_round_trips(
+        steps in proptest::collection::vec((any::<bool>(), any::<bool>(), 1u64..600), 0..24),
+    ) {
+        let rendered: Vec<String> = steps
+            .iter()
+            .map(|&(led, beep, ms)| {
+                let outputs = match (led, beep) {
+                    (false, false) => "-",
+                    (true, false) => "L",
+                    (false, true) => "B",
+                    (true, true) => "LB",
+                };
+                format!("{}:{}", outputs, ms)
+            })
+            .collect();
+        let total: u64 = steps.iter().map(|s| s.2).sum();
+        let parsed = parse(&rendered.join(","));
+        if steps.len() <= MAX_CUES && total <= MAX_PATTERN_MS {
+            let want: Vec<Cue> = steps.iter().map(|&(l, b, ms)| cue(l, b, ms)).collect();
+            prop_assert_eq!(parsed.unwrap().to_vec(), want);
+        } else {
+            prop_assert_eq!(parsed, None);
+        }
+    }
+
+    /// N2: parsing arbitrary input never panics, and what it accepts is
+    /// within the limits.
+    #[test]
+    fn prop_accepted_within_limits(s in "[LBlb:, 0-9-]{0,64}") {
+        if let Some(p) = parse(&s) {
+            prop_assert!(p.len() <= MAX_CUES);
+            prop_assert!(p.iter().map(|c| c.ms).sum::<u64>() <= MAX_PATTERN_MS);
+            prop_assert!(p.iter().all(|c| c.ms > 0));
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Tests for per-chip flash offset selection (invariants L1–L4).
+//!
+//!   L1: the board feature picks the layout; host builds default to ESP32.
+//!   L2: only the bootloader offset differs between chips.
+//!   L3: the `nvs`, `fobs` and `evseq` offsets match `partitions.csv`.
+//!   L4: every storage region is sector aligned, inside its partition,
+//!       and disjoint from the others.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test flash_layout
+
+#![cfg(feature = "sim")]
+
+use std::ops::Range;
+
+use access_controller::flash_layout::{Chip, FlashLayout, CHIP, FLASH, SECTOR};
+
+const CHIPS: [Chip; 2] = [Chip::Esp32, Chip::Esp32S3];
+
+/// `(offset, size)` of a partition in `partitions.csv`.
+fn partition(name: &str) -> (u32, u32) {
+    let hex = |s: &str| u32::from_str_radix(s.trim().trim_start_matches("0x"), 16).unwrap();
+    include_str!("../partitions.csv")
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .map(|l| l.split(',').collect::<Vec<_>>())
+        .find(|f| f[0].trim() == name)
+        .map(|f| (hex(f[3]), hex(f[4])))
+        .unwrap_or_else(|| panic!("no partition {}", name))
+}
+
+/// Every region the storage modules write, with the partition that
+/// must contain it.
+fn regions(l: &FlashLayout) -> Vec<(&'static str, Range<u32>, Range<u32>)> {
+    let nvs = l.nvs..l.nvs + l.nvs_len;
+    let fobs = l.fobs..l.fobs + l.fobs_len;
+    let evseq = l.evseq..l.evseq + l.evseq_len;
+    let slot = |base: u32| base..base + SECTOR;
+    let [s0, s1] = l.settings_slots();
+    let [c0, c1] = l.cache_slots();
+    let [f0, f1] = l.fob_slots();
+    let [q0, q1] = l.seq_slots();
+    vec![
+        ("settings[0]", slot(s0), nvs.clone()),
+        ("settings[1]", slot(s1), nvs.clone()),
+        ("cache[0]", slot(c0), nvs.clone()),
+        ("cache[1]", slot(c1), nvs.clone()),
+        ("scratch", slot(l.scratch()), nvs),
+        ("fob_store[0]", slot(f0), fobs.clone()),
+        ("fob_store[1]", slot(f1), fobs.clone()),
+        ("swipe_log", l.swipe_log(), fobs),
+        ("seq_store[0]", slot(q0), evseq.clone()),
+        ("seq_store[1]", slot(q1), evseq),
+    ]
+}
+
+#[test]
+fn feature_selects_layout() {
+    #[cfg(feature = "esp32s3")]
+    assert_eq!(CHIP, Chip::Esp32S3);
+    #[cfg(not(feature = "esp32s3"))]
+    assert_eq!(CHIP, Chip::Esp32);
+    assert_eq!(FLASH, FlashLayout::for_chip(CHIP));
+}
+
+#[test]
+fn only_the_bootloader_moves() {
+    let a = FlashLayout::for_chip(Chip::Esp32);
+    let b = FlashLayout::for_chip(Chip::Esp32S3);
+    assert_eq!(a.bootloader, 0x1000);
+    assert_eq!(b.bootloader, 0x0);
+    assert_eq!(
+        FlashLayout {
+            bootloader: a.bootloader,
+            ..b
+        },
+        a
+    );
+    for l in [a, b] {
+        assert!(l.bootloader < l.partition_table);
+        assert!(l.partition_table + 0x1000 <= l.nvs);
+    }
+}
+
+#[test]
+fn partitions_match_csv() {
+    for chip in CHIPS {
+        let l = FlashLayout::for_chip(chip);
+        assert_eq!((l.nvs, l.nvs_len), partition("nvs"), "{:?}", chip);
+        assert_eq!((l.fobs, l.fobs_len), partition("fobs"), "{:?}", chip);
+        assert_eq!((l.evseq, l.evseq_len), partition("evseq"), "{:?}", chip);
+    }
+}
+
+#[test]
+fn storage_offsets_are_unchanged() {
+    // Moving any of these orphans data already on deployed units.
+    assert_eq!(FLASH.settings_slots(), [0x9000, 0xA000]);
+    assert_eq!(FLASH.cache_slots(), [0xB000, 0xC000]);
+    assert_eq!(FLASH.scratch(), 0xD000);
+    assert_eq!(FLASH.fob_slots(), [0x11000, 0x12000]);
+    assert_eq!(FLASH.swipe_log(), 0x13000..0x20000);
+    assert_eq!(FLASH.seq_slots(), [0x3E0000, 0x3E1000]);
+}
+
+#[test]
+fn regions_are_aligned_contained_and_disjoint() {
+    for chip in CHIPS {
+        let all = regions(&FlashLayout::for_chip(chip));
+        for (name, r, part) in &all {
+            assert!(!r.is_empty(), "{:?} {}", chip, name);
+            assert_eq!(r.start % SECTOR, 0, "{:?} {}", chip, name);
+            assert_eq!(r.end % SECTOR, 0, "{:?} {}", chip, name);
+            assert!(
+                part.start <= r.start && r.end <= part.end,
+                "{:?} {} outside its partition",
+                chip,
+                name
+            );
+        }
+        for (i, (a, ra, _)) in all.iter().enumerate() {
+            for (b, rb, _) in &all[i + 1..] {
+                assert!(
+                    ra.end <= rb.start || rb.end <= ra.start,
+                    "{:?} {} overlaps {}",
+                    chip,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
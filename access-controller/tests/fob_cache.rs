@@ -0,0 +1,205 @@
+//! Tests for the persisted fob cache helpers (invariants C1–C3).
+//!
+//!   C1: identical content is InSync regardless of seq.
+//!   C2: on mismatch the higher seq wins (wrapping), ties go to flash.
+//!   C3: decode(encode(etag, fobs)) round-trips, including the ETag's
+//!       origin host; malformed payloads are rejected.
+//!   C4: a full cache of MAX_FOBS entries persists and reloads intact;
+//!       one more is rejected.
+//!   C5: the `X-Fob-CRC` digest depends only on which fobs are cached,
+//!       not their order in memory.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test fob_cache
+
+#![cfg(feature = "sim")]
+
+use access_controller::etag::HostEtag;
+use access_controller::fob_cache::{
+    crc32, decode, digest, encode, reconcile, sorted_digest, CacheMeta, Reconcile, MAX_FOBS,
+};
+use proptest::prelude::*;
+
+// ---------------------------------------------------------------------------
+// C1 / C2: reconcile decision
+// ---------------------------------------------------------------------------
+
+#[test]
+fn reconcile_missing_sides() {
+    let m = CacheMeta::of(1, &[1, 2, 3]);
+    assert_eq!(reconcile(None, None), Reconcile::Empty);
+    assert_eq!(reconcile(Some(m), None), Reconcile::PreferLive);
+    assert_eq!(reconcile(None, Some(m)), Reconcile::PreferFlash);
+}
+
+#[test]
+fn reconcile_same_content_is_in_sync() {
+    // C1: a save that landed but whose seq bookkeeping didn't is harmless.
+    let live = CacheMeta::of(7, &[10, 20]);
+    let flash = CacheMeta::of(3, &[10, 20]);
+    assert_eq!(reconcile(Some(live), Some(flash)), Reconcile::InSync);
+}
+
+#[test]
+fn reconcile_mismatch_prefers_higher_seq() {
+    // C2: flash write failed after a live sync -> RAM is newer.
+    let live = CacheMeta::of(5, &[1, 2, 3]);
+    let flash = CacheMeta::of(4, &[1, 2]);
+    assert_eq!(reconcile(Some(live), Some(flash)), Reconcile::PreferLive);
+
+    // C2: RAM is stale relative to flash.
+    let live = CacheMeta::of(4, &[1, 2]);
+    let flash = CacheMeta::of(5, &[1, 2, 3]);
+    assert_eq!(reconcile(Some(live), Some(flash)), Reconcile::PreferFlash);
+}
+
+#[test]
+fn reconcile_mismatch_tie_prefers_flash() {
+    let live = CacheMeta::of(9, &[1]);
+    let flash = CacheMeta::of(9, &[2]);
+    assert_eq!(reconcile(Some(live), Some(flash)), Reconcile::PreferFlash);
+}
+
+#[test]
+fn reconcile_seq_wraps() {
+    // C2: u64::MAX -> 0 is "newer", same as the ping-pong stores.
+    let live = CacheMeta::of(0, &[1, 2]);
+    let flash = CacheMeta::of(u64::MAX, &[1]);
+    assert_eq!(reconcile(Some(live), Some(flash)), Reconcile::PreferLive);
+}
+
+#[test]
+fn crc32_known_vector() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b""), 0);
+}
+
+#[test]
+fn digest_is_crc_of_le_bytes() {
+    let fobs = [0x0403_0201u32, 0x0807_0605];
+    assert_eq!(digest(&fobs), crc32(&[1, 2, 3, 4, 5, 6, 7, 8]));
+}
+
+#[test]
+fn sorted_digest_ignores_order() {
+    // C5
+    let sorted = [3u32, 10, 200, 0xFFFF_FFFF];
+    assert_eq!(sorted_digest(&[200, 3, 0xFFFF_FFFF, 10]), digest(&sorted));
+    assert_eq!(sorted_digest(&sorted), digest(&sorted));
+    // Content still matters: a missing or duplicated fob changes it.
+    assert_ne!(sorted_digest(&[3, 10, 200]), digest(&sorted));
+    assert_ne!(
+        sorted_digest(&[3, 3, 10, 200]),
+        sorted_digest(&[3, 10, 200])
+    );
+    assert_eq!(sorted_digest(&[]), crc32(&[]));
+}
+
+// ---------------------------------------------------------------------------
+// C3: payload encoding
+// ---------------------------------------------------------------------------
+
+#[test]
+fn decode_rejects_malformed() {
+    assert!(decode::<8>(&[]).is_none());
+    // etag_len runs past the buffer.
+    assert!(decode::<8>(&[1, 10, 0, 0, 1, 5, b'a']).is_none());
+    // host flag set but no tag, and tag without a host.
+    assert!(decode::<8>(&[1, 10, 0, 0, 1, 0, 0, 0]).is_none());
+    assert!(decode::<8>(&[0, 1, b'a', 0, 0]).is_none());
+    // bad host flag.
+    assert!(decode::<8>(&[2, 0, 0, 0]).is_none());
+    // count says 2 fobs, only one present.
+    assert!(decode::<8>(&[0, 0, 2, 0, 1, 0, 0, 0]).is_none());
+    // trailing garbage.
+    assert!(decode::<8>(&[0, 0, 0, 0, 0xFF]).is_none());
+    // more fobs than the caller's capacity.
+    assert!(decode::<1>(&encode(&HostEtag::new(), &[1, 2])).is_none());
+}
+
+#[test]
+fn invalid_stored_tag_keeps_the_list() {
+    // C3: a damaged or legacy tag only loses the tag, never the slot.
+    for tag in [&b"\xff\xfe"[..], b"caf\xc3\xa9", b"a b"] {
+        let mut buf = vec![1, 10, 0, 0, 1, tag.len() as u8];
+        buf.extend_from_slice(tag);
+        buf.extend_from_slice(&[2, 0, 7, 0, 0, 0, 9, 0, 0, 0]);
+        let (etag, fobs) = decode::<8>(&buf).unwrap();
+        assert!(etag.is_empty(), "{tag:?}");
+        assert_eq!(fobs.as_slice(), &[7, 9]);
+    }
+}
+
+#[test]
+fn empty_etag_round_trips() {
+    let (etag, fobs) = decode::<4>(&encode(&HostEtag::new(), &[42])).unwrap();
+    assert!(etag.is_empty());
+    assert_eq!(fobs.as_slice(), &[42]);
+}
+
+// ---------------------------------------------------------------------------
+// C4: capacity
+// ---------------------------------------------------------------------------
+
+#[test]
+fn full_cache_round_trips() {
+    let fobs: Vec<u32> = (0..MAX_FOBS as u32).collect();
+    let (_, decoded) = decode::<MAX_FOBS>(&encode(&HostEtag::new(), &fobs)).unwrap();
+    assert_eq!(decoded.len(), MAX_FOBS);
+    assert_eq!(decoded.as_slice(), fobs.as_slice());
+
+    let over: Vec<u32> = (0..=MAX_FOBS as u32).collect();
+    assert!(decode::<MAX_FOBS>(&encode(&HostEtag::new(), &over)).is_none());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 1024,
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn prop_encode_decode_round_trip(
+        host in any::<[u8; 4]>(),
+        tag in "[ -~]{0,64}",
+        fobs in proptest::collection::vec(any::<u32>(), 0..64),
+    ) {
+        let mut etag = HostEtag::new();
+        etag.set(host, &tag);
+        let (e, f) = decode::<64>(&encode(&etag, &fobs)).unwrap();
+        prop_assert_eq!(e, etag);
+        prop_assert_eq!(f.as_slice(), fobs.as_slice());
+    }
+
+    #[test]
+    fn prop_different_lists_are_not_in_sync(
+        a in proptest::collection::vec(any::<u32>(), 0..32),
+        b in proptest::collection::vec(any::<u32>(), 0..32),
+        seq in any::<u64>(),
+    ) {
+        prop_assume!(a != b);
+        let r = reconcile(Some(CacheMeta::of(seq.wrapping_add(1), &a)), Some(CacheMeta::of(seq, &b)));
+        prop_assert_eq!(r, Reconcile::PreferLive);
+    }
+
+    /// C5
+    #[test]
+    fn prop_sorted_digest_is_order_independent(
+        fobs in proptest::collection::vec(any::<u32>(), 0..MAX_FOBS),
+        rotate in any::<usize>(),
+    ) {
+        let mut shuffled = fobs.clone();
+        shuffled.reverse();
+        if !shuffled.is_empty() {
+            let k = rotate % shuffled.len();
+            shuffled.rotate_left(k);
+        }
+        prop_assert_eq!(sorted_digest(&shuffled), sorted_digest(&fobs));
+        let mut sorted = fobs.clone();
+        sorted.sort_unstable();
+        prop_assert_eq!(sorted_digest(&fobs), digest(&sorted));
+    }
+}
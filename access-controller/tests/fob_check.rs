@@ -0,0 +1,121 @@
+//! Tests for the `/check` fob lookup (invariants J1–J3).
+//!
+//!   J1: the query names exactly one decimal, non-zero `fob`; anything
+//!       else is refused, and other parameters are ignored.
+//!   J2: the lookup grants what a swipe of the stored number would: the
+//!       local list first, then the cache, otherwise denied.
+//!   J3: the answer renders as one JSON object naming fob, decision and
+//!       source.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test fob_check
+
+#![cfg(feature = "sim")]
+
+use access_controller::core::{AccessCore, CardRead, Effect, Input, Reader};
+use access_controller::fob_check::{self, FobCheck, Source};
+use proptest::prelude::*;
+
+// ---------- J1 ----------
+
+#[test]
+fn j1_parses_fob() {
+    assert_eq!(fob_check::parse_query("fob=12345"), Ok(12345));
+    assert_eq!(fob_check::parse_query("x=1&fob=7&y"), Ok(7));
+    assert_eq!(fob_check::parse_query("fob=4294967295"), Ok(u32::MAX));
+}
+
+#[test]
+fn j1_refuses_bad_queries() {
+    for query in [
+        "",
+        "fob",
+        "fob=",
+        "fob=0",
+        "fob=-1",
+        "fob=+1",
+        "fob=12a",
+        "fob=0x10",
+        "fob=%31",
+        "fob=4294967296",
+        "fob=1&fob=1",
+        "fobs=1",
+        "nfc=1",
+    ] {
+        assert!(fob_check::parse_query(query).is_err(), "{:?}", query);
+    }
+}
+
+// ---------- J2 ----------
+
+#[test]
+fn j2_local_list_first() {
+    let check = FobCheck::lookup(5, &[5], &[5, 6]);
+    assert_eq!(check.source, Some(Source::Local));
+    assert!(check.allowed());
+    assert_eq!(
+        FobCheck::lookup(6, &[5], &[5, 6]).source,
+        Some(Source::Cache)
+    );
+    let denied = FobCheck::lookup(7, &[5], &[5, 6]);
+    assert_eq!(denied.source, None);
+    assert!(!denied.allowed());
+}
+
+proptest! {
+    /// The lookup agrees with the access decision for a swipe whose
+    /// fob form is the queried number.
+    #[test]
+    fn j2_agrees_with_a_swipe(
+        fob in 1u32..64,
+        local in prop::collection::vec(1u32..64, 0..8),
+        cache in prop::collection::vec(1u32..64, 0..16),
+    ) {
+        let check = FobCheck::lookup(fob, &local, &cache);
+        prop_assert_eq!(check.fob, fob);
+        prop_assert_eq!(
+            check.source == Some(Source::Local),
+            local.contains(&fob)
+        );
+
+        let mut core = AccessCore::default();
+        // An NFC form no list holds, so only the fob form can match.
+        let read = CardRead {
+            fob,
+            nfc: 0,
+            reader: Reader::Wiegand,
+        };
+        let effects = core.step(0, &local, &cache, true, true, Input::Card(read));
+        prop_assert_eq!(check.allowed(), effects.contains(&Effect::OpenDoor));
+    }
+
+    #[test]
+    fn j1_any_decimal_roundtrips(fob in 1u32..) {
+        prop_assert_eq!(fob_check::parse_query(&format!("fob={}", fob)), Ok(fob));
+    }
+}
+
+// ---------- J3 ----------
+
+#[test]
+fn j3_renders_json() {
+    let render = |check: FobCheck| {
+        let mut out = String::new();
+        check.render(&mut out).unwrap();
+        out
+    };
+    assert_eq!(
+        render(FobCheck::lookup(5, &[5], &[])),
+        r#"{"fob":5,"allowed":true,"source":"local"}"#
+    );
+    assert_eq!(
+        render(FobCheck::lookup(u32::MAX, &[], &[u32::MAX])),
+        r#"{"fob":4294967295,"allowed":true,"source":"cache"}"#
+    );
+    assert_eq!(
+        render(FobCheck::lookup(9, &[], &[])),
+        r#"{"fob":9,"allowed":false,"source":null}"#
+    );
+}
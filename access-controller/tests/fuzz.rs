@@ -0,0 +1,246 @@
+//! Tests for the fuzzing entry points (invariants F1–F4).
+//!
+//!   F1: every seed in `fuzz/corpus` runs through its entry point, and
+//!       the well-formed ones parse as intended.
+//!   F2: malformed JSON, truncated or missing headers and broken chunk
+//!       framing are errors, not panics.
+//!   F3: no input panics any entry point.
+//!   F4: a 26-bit frame that decodes re-encodes to the same bits.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test fuzz
+
+#![cfg(feature = "sim")]
+
+use std::fs;
+use std::path::Path;
+
+use access_controller::decode::encode_26;
+use access_controller::fuzz::{self, FobLists, SYNC_HOST};
+use access_controller::sync_flow::Outcome;
+use proptest::prelude::*;
+
+fn corpus(target: &str) -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz/corpus")
+        .join(target);
+    let mut seeds: Vec<_> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e))
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read(&path).unwrap())
+        })
+        .collect();
+    seeds.sort();
+    assert!(!seeds.is_empty(), "no seeds in {}", dir.display());
+    seeds
+}
+
+fn seed(target: &str, name: &str) -> Vec<u8> {
+    corpus(target)
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .unwrap_or_else(|| panic!("no seed {}/{}", target, name))
+        .1
+}
+
+/// A sync response with default flags: JSON, unsigned, no hops taken.
+fn response(raw: &[u8]) -> Result<Outcome, &'static str> {
+    let mut data = vec![0u8];
+    data.extend_from_slice(raw);
+    fuzz::sync_response(&data)
+}
+
+// ---------- F1 ----------
+
+#[test]
+fn f1_corpora_replay() {
+    for (_, data) in corpus("sync_response") {
+        let _ = fuzz::sync_response(&data);
+    }
+    for (_, data) in corpus("fob_list") {
+        let _ = fuzz::fob_list(&data);
+    }
+    for (_, data) in corpus("wiegand") {
+        let _ = fuzz::wiegand(&data);
+    }
+}
+
+#[test]
+fn f1_sync_response_seeds() {
+    let sync = |name| fuzz::sync_response(&seed("sync_response", name));
+    assert_eq!(sync("ok_json"), Ok(Outcome::Updated { fobs: 3 }));
+    assert_eq!(sync("ok_binary"), Ok(Outcome::Updated { fobs: 3 }));
+    assert_eq!(sync("ok_chunked"), Ok(Outcome::Updated { fobs: 2 }));
+    assert_eq!(sync("ok_lf_only"), Ok(Outcome::Updated { fobs: 2 }));
+    assert_eq!(sync("non_ascii_etag"), Ok(Outcome::Updated { fobs: 0 }));
+    assert_eq!(sync("not_modified"), Ok(Outcome::NotModified));
+    let Ok(Outcome::Redirect(next)) = sync("redirect_path") else {
+        panic!("expected a redirect");
+    };
+    assert_eq!((next.host, next.path.as_str()), (SYNC_HOST, "/v2/fobs"));
+    let Ok(Outcome::Redirect(next)) = sync("redirect_cross_host") else {
+        panic!("expected a redirect");
+    };
+    assert_eq!((next.host, next.port), ([10, 0, 0, 9], 8081));
+    assert_eq!(sync("signed"), Err("bad signature"));
+    assert_eq!(sync("server_error"), Err("unexpected status"));
+    assert_eq!(sync("truncated_head"), Err("malformed response headers"));
+}
+
+#[test]
+fn f1_fob_list_and_wiegand_seeds() {
+    let lists = |name| fuzz::fob_list(&seed("fob_list", name));
+    assert_eq!(lists("json").json, Ok(3));
+    assert_eq!(lists("json_pretty").json, Ok(2));
+    assert_eq!(lists("json_objects").json, Ok(1));
+    assert_eq!(lists("binary").binary, Ok(3));
+    assert_eq!(lists("chunked").dechunked, Ok(3));
+
+    let read = fuzz::wiegand(&seed("wiegand", "h10301")).unwrap();
+    assert_eq!((read.facility, read.card), (123, 45678));
+    assert_eq!(fuzz::wiegand(&seed("wiegand", "h10301_bad_parity")), None);
+    assert_eq!(fuzz::wiegand(&seed("wiegand", "bits64")), None);
+}
+
+// ---------- F2 ----------
+
+#[test]
+fn f2_malformed_json_is_an_error() {
+    for body in [
+        "",
+        "[",
+        "]",
+        "[1,2",
+        "1,2]",
+        "[1,,x]",
+        "[-1]",
+        "[4294967296]",
+        "[1 2]",
+        "[\"1]",
+        "[\"x1\"]",
+        "[{\"type\":\"fob\"}]",
+        "[{\"id\":-1}]",
+        "[{\"id\":1,\"id\":2}]",
+        "[{\"id\":1]",
+        "[{id:1}]",
+        "[{\"id\"}]",
+        "[[1]]",
+        "\u{feff}[1]",
+    ] {
+        let FobLists { json, .. } = fuzz::fob_list(body.as_bytes());
+        assert!(json.is_err(), "{:?} parsed as {:?}", body, json);
+        let raw = format!("HTTP/1.1 200 OK\r\n\r\n{}", body);
+        assert!(response(raw.as_bytes()).is_err(), "{:?}", body);
+    }
+    assert_eq!(
+        fuzz::fob_list(b"[1, \xff]").json,
+        Err("invalid response encoding")
+    );
+}
+
+#[test]
+fn f2_truncated_headers_are_an_error() {
+    let full = b"HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 3\r\n\r\n[1]";
+    let head_len = full.len() - 3;
+    for cut in 0..head_len - 1 {
+        assert_eq!(
+            response(&full[..cut]),
+            Err("malformed response headers"),
+            "cut at {}",
+            cut
+        );
+    }
+    assert_eq!(fuzz::sync_response(&[]), Err("empty input"));
+    // A header block with no status line.
+    assert_eq!(response(b"\r\n\r\n[1]"), Err("unexpected status"));
+    assert_eq!(response(b"HTTP/1.1\r\n\r\n"), Err("unexpected status"));
+    assert_eq!(
+        response(b"HTTP/1.1 302 Found\r\n\r\n"),
+        Err("redirect without Location")
+    );
+}
+
+#[test]
+fn f2_broken_chunks_are_an_error() {
+    for (body, want) in [
+        ("", "truncated chunked body"),
+        ("3\r\n[1]", "truncated chunked body"),
+        ("3\r\n[1]\r", "truncated chunked body"),
+        ("ffffffffffffffff\r\n[]", "truncated chunked body"),
+        ("1ffffffffffffffff\r\n[]", "bad chunk size"),
+        ("x\r\n[]", "bad chunk size"),
+        ("\r\n", "bad chunk size"),
+        ("3\r\n[1]xx", "bad chunk terminator"),
+    ] {
+        assert_eq!(
+            fuzz::fob_list(body.as_bytes()).dechunked,
+            Err(want),
+            "{:?}",
+            body
+        );
+    }
+}
+
+// ---------- F3 / F4 ----------
+
+/// Response-shaped input: fragments of real responses mixed with noise,
+/// so the parsers get past the first check often enough to matter.
+fn response_bytes() -> impl Strategy<Value = Vec<u8>> {
+    let piece = prop_oneof![
+        Just(b"HTTP/1.1 200 OK\r\n".to_vec()),
+        Just(b"HTTP/1.1 304 Not Modified\r\n".to_vec()),
+        Just(b"HTTP/1.1 307 Temporary Redirect\r\n".to_vec()),
+        Just(b"Location: http://10.0.0.2:".to_vec()),
+        Just(b"Transfer-Encoding: chunked\r\n".to_vec()),
+        Just(b"Content-Type: application/x-conway-fobs\r\n".to_vec()),
+        Just(b"X-Fob-Signature: ".to_vec()),
+        Just(b"\r\n".to_vec()),
+        Just(b"[".to_vec()),
+        Just(b"]".to_vec()),
+        Just(b",".to_vec()),
+        "[0-9a-f/;:# ]{0,6}".prop_map(String::into_bytes),
+        prop::collection::vec(any::<u8>(), 0..6),
+    ];
+    (any::<u8>(), prop::collection::vec(piece, 0..16)).prop_map(|(flags, pieces)| {
+        let mut data = vec![flags];
+        data.extend(pieces.concat());
+        data
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn f3_no_input_panics(data in prop::collection::vec(any::<u8>(), 0..256)) {
+        let _ = fuzz::sync_response(&data);
+        let _ = fuzz::fob_list(&data);
+        let _ = fuzz::wiegand(&data);
+    }
+
+    #[test]
+    fn f3_no_response_shaped_input_panics(data in response_bytes()) {
+        let _ = fuzz::sync_response(&data);
+        let _ = fuzz::fob_list(&data[1..]);
+    }
+
+    #[test]
+    fn f4_h10301_round_trips(data in prop::collection::vec(any::<u8>(), 9)) {
+        let mut data = data;
+        data[0] = 26;
+        let (count, bits) = fuzz::wiegand_frame(&data);
+        prop_assert_eq!(count, 26);
+        if let Some(read) = fuzz::wiegand(&data) {
+            prop_assert_eq!(encode_26(read.facility, read.card), bits);
+        }
+    }
+}
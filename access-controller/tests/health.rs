@@ -0,0 +1,303 @@
+//! Tests for the overall health summary (invariants Q1–Q5).
+//!
+//!   Q1: with every subsystem fine, or not monitored, health is ok.
+//!   Q2: each component rates itself per the documented thresholds.
+//!   Q3: overall health is the worst component's, whatever the mix.
+//!   Q4: the rendered summary names the overall state and exactly the
+//!       components that brought it down; the gauge reads 0/1/2.
+//!   Q5: the LED shows Fault exactly when health is critical, and sees
+//!       the same network and sync state otherwise.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test health
+
+#![cfg(feature = "sim")]
+
+use access_controller::health::{
+    self, Component, Health, Inputs, CRITICAL_HEAP_BYTES, LOW_HEAP_BYTES,
+};
+use access_controller::status_led::{Pattern, SYNC_FAILURE_THRESHOLD};
+use proptest::prelude::*;
+
+fn fine() -> Inputs {
+    Inputs {
+        wifi_up: true,
+        ip_configured: true,
+        syncs: true,
+        fobs_loaded: true,
+        sync_failures: 0,
+        offline: false,
+        flash_healthy: Some(true),
+        reader_online: Some(true),
+        heap_free: 20_000,
+    }
+}
+
+fn rendered(i: &Inputs) -> String {
+    let mut out = String::new();
+    health::render(i, &mut out).unwrap();
+    out
+}
+
+fn arb_inputs() -> impl Strategy<Value = Inputs> {
+    (
+        (any::<bool>(), any::<bool>(), any::<bool>(), any::<bool>()),
+        (0u8..6, any::<bool>()),
+        (any::<Option<bool>>(), any::<Option<bool>>()),
+        prop_oneof![0usize..LOW_HEAP_BYTES + 1024, 0usize..64 * 1024],
+    )
+        .prop_map(
+            |(
+                (wifi_up, ip_configured, syncs, fobs_loaded),
+                (sync_failures, offline),
+                (flash_healthy, reader_online),
+                heap_free,
+            )| Inputs {
+                wifi_up,
+                ip_configured,
+                syncs,
+                fobs_loaded,
+                sync_failures,
+                offline,
+                flash_healthy,
+                reader_online,
+                heap_free,
+            },
+        )
+}
+
+// ---------- Q1 ----------
+
+#[test]
+fn q1_all_fine_is_ok() {
+    assert_eq!(health::health(&fine()), Health::Ok);
+    let unmonitored = Inputs {
+        flash_healthy: None,
+        reader_online: None,
+        ..fine()
+    };
+    assert_eq!(health::health(&unmonitored), Health::Ok);
+    // Nothing to sync: the sync fields don't count.
+    let standalone = Inputs {
+        syncs: false,
+        fobs_loaded: false,
+        sync_failures: 9,
+        offline: true,
+        ..fine()
+    };
+    assert_eq!(health::health(&standalone), Health::Ok);
+}
+
+// ---------- Q2 ----------
+
+#[test]
+fn q2_component_thresholds() {
+    let cases: [(Inputs, Component, Health); 10] = [
+        (
+            Inputs {
+                wifi_up: false,
+                ..fine()
+            },
+            Component::Wifi,
+            Health::Degraded,
+        ),
+        (
+            Inputs {
+                ip_configured: false,
+                ..fine()
+            },
+            Component::Wifi,
+            Health::Degraded,
+        ),
+        (
+            Inputs {
+                fobs_loaded: false,
+                ..fine()
+            },
+            Component::Sync,
+            Health::Degraded,
+        ),
+        (
+            Inputs {
+                offline: true,
+                ..fine()
+            },
+            Component::Sync,
+            Health::Degraded,
+        ),
+        (
+            Inputs {
+                sync_failures: SYNC_FAILURE_THRESHOLD,
+                ..fine()
+            },
+            Component::Sync,
+            Health::Degraded,
+        ),
+        (
+            Inputs {
+                sync_failures: SYNC_FAILURE_THRESHOLD - 1,
+                ..fine()
+            },
+            Component::Sync,
+            Health::Ok,
+        ),
+        (
+            Inputs {
+                flash_healthy: Some(false),
+                ..fine()
+            },
+            Component::Flash,
+            Health::Critical,
+        ),
+        (
+            Inputs {
+                reader_online: Some(false),
+                ..fine()
+            },
+            Component::Reader,
+            Health::Critical,
+        ),
+        (
+            Inputs {
+                heap_free: LOW_HEAP_BYTES - 1,
+                ..fine()
+            },
+            Component::Heap,
+            Health::Degraded,
+        ),
+        (
+            Inputs {
+                heap_free: CRITICAL_HEAP_BYTES - 1,
+                ..fine()
+            },
+            Component::Heap,
+            Health::Critical,
+        ),
+    ];
+    for (inputs, component, want) in cases {
+        assert_eq!(component.health(&inputs), want, "{:?}", inputs);
+        assert_eq!(health::health(&inputs), want, "{:?}", inputs);
+        // Only the named component moved.
+        for other in Component::ALL.into_iter().filter(|&c| c != component) {
+            assert_eq!(
+                other.health(&inputs),
+                Health::Ok,
+                "{:?} {:?}",
+                other,
+                inputs
+            );
+        }
+    }
+    // The boundaries themselves are still the better side.
+    assert_eq!(
+        Component::Heap.health(&Inputs {
+            heap_free: LOW_HEAP_BYTES,
+            ..fine()
+        }),
+        Health::Ok
+    );
+    assert_eq!(
+        Component::Heap.health(&Inputs {
+            heap_free: CRITICAL_HEAP_BYTES,
+            ..fine()
+        }),
+        Health::Degraded
+    );
+}
+
+// ---------- Q3 ----------
+
+#[test]
+fn q3_worst_component_wins() {
+    let degraded_and_critical = Inputs {
+        wifi_up: false,
+        reader_online: Some(false),
+        ..fine()
+    };
+    assert_eq!(health::health(&degraded_and_critical), Health::Critical);
+    let two_degraded = Inputs {
+        offline: true,
+        heap_free: LOW_HEAP_BYTES - 1,
+        ..fine()
+    };
+    assert_eq!(health::health(&two_degraded), Health::Degraded);
+}
+
+proptest! {
+    #[test]
+    fn q3_overall_is_the_max(i in arb_inputs()) {
+        let worst = Component::ALL.iter().map(|c| c.health(&i)).max().unwrap();
+        prop_assert_eq!(health::health(&i), worst);
+        prop_assert!(Component::ALL.iter().all(|c| c.health(&i) <= health::health(&i)));
+    }
+}
+
+// ---------- Q4 ----------
+
+#[test]
+fn q4_render() {
+    assert_eq!(rendered(&fine()), "ok");
+    assert_eq!(
+        rendered(&Inputs {
+            offline: true,
+            ..fine()
+        }),
+        "degraded (sync)"
+    );
+    assert_eq!(
+        rendered(&Inputs {
+            wifi_up: false,
+            reader_online: Some(false),
+            heap_free: 0,
+            ..fine()
+        }),
+        "critical (wifi, reader, heap)"
+    );
+
+    let mut gauge = String::new();
+    health::write_metrics(
+        &Inputs {
+            flash_healthy: Some(false),
+            ..fine()
+        },
+        &mut gauge,
+    )
+    .unwrap();
+    assert_eq!(gauge, "# TYPE conway_health gauge\nconway_health 2\n");
+}
+
+proptest! {
+    #[test]
+    fn q4_render_names_the_failing_components(i in arb_inputs()) {
+        let out = rendered(&i);
+        let overall = health::health(&i);
+        prop_assert!(out.starts_with(overall.as_str()));
+        let failing: Vec<&str> = Component::ALL
+            .iter()
+            .filter(|c| c.health(&i) != Health::Ok)
+            .map(|c| c.as_str())
+            .collect();
+        if failing.is_empty() {
+            prop_assert_eq!(out, "ok");
+        } else {
+            prop_assert_eq!(out, format!("{} ({})", overall.as_str(), failing.join(", ")));
+        }
+    }
+}
+
+// ---------- Q5 ----------
+
+proptest! {
+    #[test]
+    fn q5_led_reflects_health(i in arb_inputs()) {
+        let s = i.net_status();
+        prop_assert_eq!(s.critical, health::health(&i) == Health::Critical);
+        prop_assert_eq!(Pattern::select(s) == Pattern::Fault, s.critical);
+        prop_assert_eq!(s.wifi_up, i.wifi_up);
+        prop_assert_eq!(s.ip_configured, i.ip_configured);
+        prop_assert_eq!(s.fobs_loaded, !i.syncs || i.fobs_loaded);
+        prop_assert_eq!(s.offline, i.offline);
+    }
+}
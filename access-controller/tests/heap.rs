@@ -0,0 +1,364 @@
+//! Tests for the heap budget and its low-water mark (invariants H1–H5).
+//!
+//!   H1: each feature set's estimated peak is the figure in the table
+//!       below; changing an allocation means revisiting this table.
+//!   H2: the estimate grows with the form body limit, with push and with
+//!       each online check, and never shrinks.
+//!   H3: every build option fits `HEAP_SIZE`, with room left over.
+//!   H4: the low-water mark is the least free heap ever sampled, and a
+//!       sample reports a new low exactly when it is below every earlier
+//!       one.
+//!   H5: a failed allocation's panic message yields its size, no other
+//!       panic does, and the logged context tells fragmentation from
+//!       exhaustion.
+//!   H6: the trend keeps the last `TREND_SAMPLES` samples and its slope
+//!       is the least-squares fit in bytes per hour.
+//!   H7: a leak is flagged exactly when a full window never rose and
+//!       fell by at least `LEAK_MIN_DROP_BYTES`.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test heap
+
+#![cfg(feature = "sim")]
+
+use access_controller::core::MAX_READERS;
+use access_controller::heap::{
+    self, AllocFailure, Build, LowWater, Trend, HEAP_SIZE, LEAK_MIN_DROP_BYTES, TREND_INTERVAL_MS,
+    TREND_SAMPLES,
+};
+use access_controller::request_body::{BODY_MAX_CEILING, DEFAULT_BODY_MAX};
+use proptest::prelude::*;
+
+fn build(push: bool, body_max: usize) -> Build {
+    Build {
+        push,
+        body_max,
+        online_checks: 0,
+    }
+}
+
+fn checking(online_checks: usize, build: Build) -> Build {
+    Build {
+        online_checks,
+        ..build
+    }
+}
+
+#[test]
+fn h1_peak_per_feature_set() {
+    // (push, body_max) -> bytes
+    let table = [
+        ((false, DEFAULT_BODY_MAX), 43_545),
+        ((true, DEFAULT_BODY_MAX), 46_105),
+        ((false, BODY_MAX_CEILING), 50_713),
+        ((true, BODY_MAX_CEILING), 53_273),
+    ];
+    for ((push, body_max), want) in table {
+        assert_eq!(
+            heap::peak(build(push, body_max)),
+            want,
+            "push={} body_max={}",
+            push,
+            body_max
+        );
+    }
+    // With an online check at one reader, and at both.
+    assert_eq!(
+        heap::peak(checking(1, build(false, DEFAULT_BODY_MAX))),
+        44_441
+    );
+    assert_eq!(
+        heap::peak(checking(MAX_READERS, build(true, BODY_MAX_CEILING))),
+        55_065
+    );
+}
+
+#[test]
+fn h2_peak_is_monotonic() {
+    for push in [false, true] {
+        let mut last = 0;
+        for body_max in (0..=BODY_MAX_CEILING).step_by(256) {
+            let peak = heap::peak(build(push, body_max));
+            assert!(peak >= last, "push={} body_max={}", push, body_max);
+            last = peak;
+        }
+    }
+    for body_max in [0, DEFAULT_BODY_MAX, BODY_MAX_CEILING] {
+        assert_eq!(
+            heap::peak(build(true, body_max)),
+            heap::peak(build(false, body_max)) + heap::PUSH
+        );
+        for n in 1..=MAX_READERS {
+            assert_eq!(
+                heap::peak(checking(n, build(true, body_max))),
+                heap::peak(build(true, body_max)) + n * heap::ONLINE_CHECK
+            );
+        }
+    }
+    // Past the fob page, every extra body byte is one more heap byte.
+    assert_eq!(
+        heap::peak(build(false, BODY_MAX_CEILING)) - heap::peak(build(false, DEFAULT_BODY_MAX)),
+        BODY_MAX_CEILING - DEFAULT_BODY_MAX
+    );
+}
+
+#[test]
+fn h3_every_build_fits() {
+    for online_checks in 0..=MAX_READERS {
+        assert!(heap::fits(false, online_checks));
+        assert!(heap::fits(true, online_checks));
+    }
+    // The radio needs its share too; keep at least a quarter free.
+    assert!(heap::peak(checking(MAX_READERS, build(true, BODY_MAX_CEILING))) <= HEAP_SIZE * 3 / 4);
+}
+
+#[test]
+fn h4_unsampled_has_no_mark() {
+    let lw = LowWater::new();
+    assert_eq!(lw.get(), None);
+    let mut out = String::new();
+    lw.write_metrics(&mut out, 30_000).unwrap();
+    assert_eq!(
+        out,
+        "# TYPE conway_heap_free_bytes gauge\n\
+         conway_heap_free_bytes 30000\n\
+         # TYPE conway_heap_free_min_bytes gauge\n\
+         conway_heap_free_min_bytes 30000\n"
+    );
+}
+
+#[test]
+fn h4_only_lower_samples_move_the_mark() {
+    let lw = LowWater::new();
+    assert!(lw.sample(40_000));
+    assert!(!lw.sample(45_000));
+    assert!(!lw.sample(40_000));
+    assert!(lw.sample(12_000));
+    assert!(!lw.sample(50_000));
+    assert_eq!(lw.get(), Some(12_000));
+    assert!(lw.sample(0));
+    assert_eq!(lw.get(), Some(0));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn h4_mark_is_the_running_minimum(samples in prop::collection::vec(0usize..HEAP_SIZE, 1..64)) {
+        let lw = LowWater::new();
+        let mut min = usize::MAX;
+        for free in samples {
+            prop_assert_eq!(lw.sample(free), free < min);
+            min = min.min(free);
+            prop_assert_eq!(lw.get(), Some(min));
+        }
+    }
+}
+
+#[test]
+fn h5_alloc_failure_is_recognized() {
+    assert_eq!(
+        heap::alloc_failure_size("memory allocation of 8195 bytes failed"),
+        Some(8195)
+    );
+    assert_eq!(
+        heap::alloc_failure_size("memory allocation of 0 bytes failed"),
+        Some(0)
+    );
+    for other in [
+        "",
+        "index out of bounds: the len is 3 but the index is 5",
+        "memory allocation of bytes failed",
+        "memory allocation of -1 bytes failed",
+        "memory allocation of 8195 bytes failed!",
+        "UART0 console reconfig",
+    ] {
+        assert_eq!(heap::alloc_failure_size(other), None, "{:?}", other);
+    }
+}
+
+#[test]
+fn h5_alloc_failure_context() {
+    let fragmented = AllocFailure {
+        requested: 8195,
+        free: 9000,
+        lowest: Some(5980),
+    };
+    assert_eq!(
+        fragmented.to_string(),
+        "allocation of 8195 bytes failed: 9000 of 73728 bytes free (lowest 5980), fragmented"
+    );
+    let exhausted = AllocFailure {
+        requested: 1024,
+        free: 512,
+        lowest: None,
+    };
+    assert_eq!(
+        exhausted.to_string(),
+        "allocation of 1024 bytes failed: 512 of 73728 bytes free, exhausted"
+    );
+}
+
+fn trend(samples: &[usize]) -> Trend {
+    let mut t = Trend::new();
+    for &free in samples {
+        t.push(free);
+    }
+    t
+}
+
+/// Samples per hour at the trend's cadence.
+const PER_HOUR: i64 = (3_600_000 / TREND_INTERVAL_MS) as i64;
+
+#[test]
+fn h6_window_keeps_the_newest() {
+    let t = trend(&[]);
+    assert!(t.is_empty());
+    assert_eq!(t.slope_per_hour(), None);
+    assert_eq!(t.to_string(), "collecting (0 of 12 samples)");
+    let t = trend(&[30_000]);
+    assert_eq!(t.slope_per_hour(), None);
+    assert_eq!(t.to_string(), "collecting (1 of 12 samples)");
+
+    let all: Vec<usize> = (0..TREND_SAMPLES + 5).map(|i| 1_000 * i).collect();
+    let t = trend(&all);
+    assert_eq!(t.len(), TREND_SAMPLES);
+    assert_eq!(
+        t.samples().collect::<Vec<_>>(),
+        all[5..].to_vec(),
+        "oldest first, the first five evicted"
+    );
+}
+
+#[test]
+fn h6_slope() {
+    assert_eq!(trend(&[30_000, 30_000]).slope_per_hour(), Some(0));
+    assert_eq!(
+        trend(&[30_000, 29_900]).slope_per_hour(),
+        Some(-100 * PER_HOUR)
+    );
+    assert_eq!(
+        trend(&[30_000, 30_100, 30_200]).slope_per_hour(),
+        Some(100 * PER_HOUR)
+    );
+    // A one-off dip in a flat series barely moves the fit.
+    let dip = trend(&[30_000, 30_000, 24_000, 30_000, 30_000]);
+    assert_eq!(dip.slope_per_hour(), Some(0));
+    // Noise around a slow fall: the fit follows the fall, not the noise.
+    let noisy = trend(&[
+        30_000, 30_060, 29_880, 29_900, 29_760, 29_790, 29_640, 29_660, 29_520, 29_560, 29_400,
+        29_430,
+    ]);
+    let slope = noisy.slope_per_hour().unwrap();
+    assert!(
+        (-60 * PER_HOUR..=-50 * PER_HOUR).contains(&slope),
+        "{}",
+        slope
+    );
+    assert_eq!(noisy.to_string(), format!("{:+} B/h over 110 min", slope));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn h6_linear_slope_is_exact(
+        start in 20_000usize..40_000,
+        step in -1_000i64..1_000,
+        n in 2usize..=TREND_SAMPLES + 4,
+    ) {
+        let samples: Vec<usize> = (0..n as i64).map(|i| (start as i64 + step * i) as usize).collect();
+        let t = trend(&samples);
+        prop_assert_eq!(t.slope_per_hour(), Some(step * PER_HOUR));
+    }
+
+    #[test]
+    fn h6_slope_sign_follows_the_ends_of_a_monotone_run(
+        start in 20_000usize..40_000,
+        steps in prop::collection::vec(0usize..500, 1..TREND_SAMPLES),
+        falling in any::<bool>(),
+    ) {
+        let mut samples = vec![start];
+        for s in &steps {
+            let last = *samples.last().unwrap();
+            samples.push(if falling { last - s } else { last + s });
+        }
+        let slope = trend(&samples).slope_per_hour().unwrap();
+        let first = samples[0];
+        let last = *samples.last().unwrap();
+        if first == last {
+            prop_assert_eq!(slope, 0);
+        } else if falling {
+            prop_assert!(slope <= 0);
+        } else {
+            prop_assert!(slope >= 0);
+        }
+    }
+}
+
+#[test]
+fn h7_leak_needs_a_full_window() {
+    let falling: Vec<usize> = (0..TREND_SAMPLES).map(|i| 30_000 - 100 * i).collect();
+    assert!(!trend(&falling[..TREND_SAMPLES - 1]).leaking());
+    let t = trend(&falling);
+    assert!(t.leaking());
+    assert_eq!(
+        t.to_string(),
+        "-600 B/h over 110 min, falling steadily: possible leak"
+    );
+}
+
+#[test]
+fn h7_leak_cases() {
+    let n = TREND_SAMPLES;
+    // Flat: never rose, but never fell either.
+    assert!(!trend(&vec![30_000; n]).leaking());
+    // Plateaus in a fall still count.
+    let mut stepped: Vec<usize> = (0..n).map(|i| 30_000 - 200 * (i / 2)).collect();
+    assert!(trend(&stepped).leaking());
+    // One rise clears it.
+    stepped[n / 2] += 1_000;
+    assert!(!trend(&stepped).leaking());
+    // A fall just short of the minimum is noise.
+    let mut slow: Vec<usize> = vec![30_000; n];
+    slow[n - 1] = 30_000 - (LEAK_MIN_DROP_BYTES - 1);
+    assert!(!trend(&slow).leaking());
+    slow[n - 1] = 30_000 - LEAK_MIN_DROP_BYTES;
+    assert!(trend(&slow).leaking());
+    // A leak that stops: once a rise is back in the window, it clears.
+    let mut t = trend(&(0..n).map(|i| 30_000 - 100 * i).collect::<Vec<_>>());
+    assert!(t.leaking());
+    t.push(31_000);
+    assert!(!t.leaking());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn h7_leaking_matches_the_rule(samples in prop::collection::vec(20_000usize..21_000, 0..2 * TREND_SAMPLES)) {
+        let t = trend(&samples);
+        let window = &samples[samples.len().saturating_sub(TREND_SAMPLES)..];
+        let expect = window.len() == TREND_SAMPLES
+            && window.windows(2).all(|w| w[1] <= w[0])
+            && window[0] - window[TREND_SAMPLES - 1] >= LEAK_MIN_DROP_BYTES;
+        prop_assert_eq!(t.leaking(), expect);
+    }
+}
@@ -0,0 +1,391 @@
+//! Tests for the sync client's HTTP framing helpers: request head,
+//! response header parsing, redirect handling and response size limits.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test http_client
+
+#![cfg(feature = "sim")]
+
+use access_controller::etag::valid_tag;
+use access_controller::http_client::{
+    check_request_length, check_response_size, extract_header, find_head_end, is_redirect,
+    parse_location, parse_status_code, split_head, valid_path, write_sync_request_head,
+    RedirectPolicy, SyncTarget, DEFAULT_SYNC_PATH, MAX_BODY_BYTES, MAX_HEAD_BYTES, MAX_REDIRECTS,
+    MAX_RESPONSE_BYTES, MAX_SYNC_PATH,
+};
+
+fn head(path: &str, etag: Option<&str>) -> String {
+    let mut s = String::new();
+    write_sync_request_head(
+        &mut s,
+        path,
+        "10.0.0.1",
+        "application/json",
+        2,
+        etag,
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    s
+}
+
+#[test]
+fn default_request_line() {
+    assert_eq!(
+        head(DEFAULT_SYNC_PATH, None),
+        "POST /api/fobs HTTP/1.1\r\n\
+         Host: 10.0.0.1\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: 2\r\n\
+         Connection: close\r\n\
+         \r\n"
+    );
+}
+
+#[test]
+fn request_length_must_match_the_body() {
+    // `head` declares two body bytes.
+    let request = |body: &str| format!("{}{}", head(DEFAULT_SYNC_PATH, None), body);
+    assert_eq!(check_request_length(request("[]").as_bytes(), 0), Ok(()));
+    assert_eq!(check_request_length(request("").as_bytes(), 2), Ok(()));
+    assert_eq!(check_request_length(request("[").as_bytes(), 1), Ok(()));
+    // A truncated body leaves the server waiting; a longer one spills
+    // into what it reads as the next request.
+    for (body, streamed) in [("[", 0), ("", 0), ("[]", 1), ("[1]", 0), ("", 3)] {
+        assert_eq!(
+            check_request_length(request(body).as_bytes(), streamed),
+            Err("request body length mismatch"),
+            "{:?} + {}",
+            body,
+            streamed
+        );
+    }
+    assert!(check_request_length(b"POST / HTTP/1.1\r\n\r\n[]", 0).is_err());
+    assert!(check_request_length(b"POST / HTTP/1.1\r\nContent-Length: x\r\n\r\n", 0).is_err());
+    assert!(check_request_length(b"POST / HTTP/1.1\r\nContent-Length: 0\r\n", 0).is_err());
+}
+
+#[test]
+fn custom_path_request_line() {
+    let h = head("/controllers/v1/fobs", Some("\"abc\""));
+    assert!(h.starts_with("POST /controllers/v1/fobs HTTP/1.1\r\n"));
+    assert!(h.contains("\r\nIf-None-Match: \"abc\"\r\n"));
+    assert!(h.ends_with("\r\n\r\n"));
+}
+
+#[test]
+fn idempotency_key_header() {
+    let mut s = String::new();
+    write_sync_request_head(
+        &mut s,
+        "/api/fobs",
+        "10.0.0.1",
+        "application/json",
+        2,
+        None,
+        Some("k-1"),
+        None,
+        None,
+    )
+    .unwrap();
+    assert!(s.contains("\r\nIdempotency-Key: k-1\r\n"));
+    assert!(!head(DEFAULT_SYNC_PATH, None).contains("Idempotency-Key"));
+}
+
+#[test]
+fn fob_crc_header() {
+    let mut s = String::new();
+    write_sync_request_head(
+        &mut s,
+        "/api/fobs",
+        "10.0.0.1",
+        "application/json",
+        2,
+        Some("\"v1\""),
+        None,
+        Some(0x00AB_CDEF),
+        None,
+    )
+    .unwrap();
+    assert!(s.ends_with("If-None-Match: \"v1\"\r\nX-Fob-CRC: 00abcdef\r\n\r\n"));
+    assert!(!head(DEFAULT_SYNC_PATH, None).contains("X-Fob-CRC"));
+}
+
+#[test]
+fn sync_nonce_header() {
+    let mut s = String::new();
+    write_sync_request_head(
+        &mut s,
+        "/api/fobs",
+        "10.0.0.1",
+        "application/json",
+        2,
+        None,
+        None,
+        Some(0x00AB_CDEF),
+        Some("0123456789abcdef"),
+    )
+    .unwrap();
+    assert!(s.ends_with("X-Fob-CRC: 00abcdef\r\nX-Sync-Nonce: 0123456789abcdef\r\n\r\n"));
+    assert!(!head(DEFAULT_SYNC_PATH, None).contains("X-Sync-Nonce"));
+}
+
+#[test]
+fn path_grammar() {
+    assert!(valid_path("/api/fobs"));
+    assert!(valid_path("/controllers/v1/fobs?door=front"));
+    assert!(valid_path("/"));
+    assert!(valid_path(&format!("/{}", "a".repeat(MAX_SYNC_PATH - 1))));
+
+    assert!(!valid_path(""));
+    assert!(!valid_path("api/fobs"));
+    assert!(!valid_path("http://10.0.0.1/api/fobs"));
+    assert!(!valid_path("/api fobs"));
+    assert!(!valid_path("/api/fobs HTTP/1.1\r\nX-Evil: 1"));
+    assert!(!valid_path("/api/f\u{f6}bs"));
+    assert!(!valid_path(&format!("/{}", "a".repeat(MAX_SYNC_PATH))));
+}
+
+fn target(host: [u8; 4], port: u16, path: &str) -> SyncTarget {
+    SyncTarget {
+        host,
+        port,
+        path: path.try_into().unwrap(),
+    }
+}
+
+const RESP_301: &str = "HTTP/1.1 301 Moved Permanently\r\n\
+                        Content-Length: 0\r\n\
+                        location:  /controllers/v1/fobs \r\n\
+                        \r\n\
+                        Location: http://6.6.6.6/evil";
+
+#[test]
+fn lf_only_response() {
+    let mut raw =
+        b"HTTP/1.1 200 OK\nETag: \"v1\"\nContent-Type: application/json\n\n[1,2]".to_vec();
+    let (head, body) = split_head(&mut raw).unwrap();
+    assert_eq!(parse_status_code(head), 200);
+    assert_eq!(extract_header(head, "etag"), Some("\"v1\""));
+    assert_eq!(
+        extract_header(head, "content-type"),
+        Some("application/json")
+    );
+    assert_eq!(body, b"[1,2]");
+}
+
+#[test]
+fn mixed_line_endings() {
+    for raw in [
+        &b"HTTP/1.1 200 OK\r\nETag: v1\nX-A: a\r\n\n[1]"[..],
+        b"HTTP/1.1 200 OK\nETag: v1\r\nX-A: a\n\r\n[1]",
+        b"HTTP/1.1 200 OK\r\nETag: v1\r\nX-A: a\r\n\r\n[1]",
+    ] {
+        let mut raw = raw.to_vec();
+        let (head, body) = split_head(&mut raw).unwrap();
+        assert_eq!(parse_status_code(head), 200);
+        assert_eq!(extract_header(head, "etag"), Some("v1"));
+        assert_eq!(extract_header(head, "x-a"), Some("a"));
+        assert_eq!(body, b"[1]");
+    }
+}
+
+#[test]
+fn head_end_offsets() {
+    assert_eq!(find_head_end(b"A\r\n\r\nbody"), Some((3, 5)));
+    assert_eq!(find_head_end(b"A\n\nbody"), Some((2, 3)));
+    assert_eq!(find_head_end(b"A\r\n\nbody"), Some((3, 4)));
+    assert_eq!(find_head_end(b"A\n\r\nbody"), Some((2, 4)));
+    // Incomplete: no blank line yet, or a CR still waiting for its LF.
+    assert_eq!(find_head_end(b""), None);
+    assert_eq!(find_head_end(b"A\r\nB: c\r\n"), None);
+    assert_eq!(find_head_end(b"A\r\nB: c\r\n\r"), None);
+    // A body that happens to contain blank lines doesn't move the split.
+    assert_eq!(find_head_end(b"A\n\nx\n\ny"), Some((2, 3)));
+}
+
+#[test]
+fn non_ascii_header_bytes_do_not_fail_the_response() {
+    let mut raw =
+        b"HTTP/1.1 200 OK\r\nETag: \"caf\xe9\"\r\nContent-Length: 2\r\n\r\n\xff\x01".to_vec();
+    let (head, body) = split_head(&mut raw).unwrap();
+    assert_eq!(parse_status_code(head), 200);
+    assert_eq!(extract_header(head, "content-length"), Some("2"));
+    // The opaque byte is neutralised, so the tag is rejected downstream.
+    assert_eq!(extract_header(head, "etag"), Some("\"caf\x7f\""));
+    assert!(!valid_tag(extract_header(head, "etag").unwrap()));
+    // The body is binary and left untouched.
+    assert_eq!(body, b"\xff\x01");
+
+    let mut raw = b"HTTP/1.1 302 Found\r\nLocation: /caf\xc3\xa9\r\n\r\n".to_vec();
+    let (head, _) = split_head(&mut raw).unwrap();
+    let location = extract_header(head, "location").unwrap();
+    assert!(parse_location(location, &target([10, 0, 0, 1], 80, "/api/fobs")).is_err());
+
+    assert!(split_head(&mut b"HTTP/1.1 200 OK\r\n".to_vec()).is_none());
+}
+
+#[test]
+fn status_and_location_headers() {
+    assert_eq!(parse_status_code(RESP_301), 301);
+    assert_eq!(parse_status_code("garbage"), 0);
+    assert!(is_redirect(301) && is_redirect(302) && is_redirect(307) && is_redirect(308));
+    assert!(!is_redirect(200) && !is_redirect(304) && !is_redirect(303));
+    // Case-insensitive, trimmed, and never read from the body.
+    assert_eq!(
+        extract_header(RESP_301, "Location"),
+        Some("/controllers/v1/fobs")
+    );
+    assert_eq!(
+        extract_header("HTTP/1.1 302 Found\r\n\r\nLocation: /x", "location"),
+        None
+    );
+}
+
+#[test]
+fn location_forms() {
+    let from = target([10, 0, 0, 1], 8080, "/api/fobs");
+
+    assert_eq!(
+        parse_location("/v2/fobs?door=1#frag", &from),
+        Ok(target([10, 0, 0, 1], 8080, "/v2/fobs?door=1"))
+    );
+    assert_eq!(
+        parse_location("http://10.0.0.1:8080/v2/fobs", &from),
+        Ok(target([10, 0, 0, 1], 8080, "/v2/fobs"))
+    );
+    assert_eq!(
+        parse_location("HTTP://10.0.0.2", &from),
+        Ok(target([10, 0, 0, 2], 80, "/"))
+    );
+
+    assert!(parse_location("https://10.0.0.1/api/fobs", &from).is_err());
+    assert!(parse_location("http://conway.local/api/fobs", &from).is_err());
+    assert!(parse_location("http://10.0.0.1:0/api/fobs", &from).is_err());
+    assert!(parse_location("http://10.0.0.1:99999/api/fobs", &from).is_err());
+    assert!(parse_location("v2/fobs", &from).is_err());
+    assert!(parse_location("", &from).is_err());
+    assert!(parse_location("/a b", &from).is_err());
+}
+
+#[test]
+fn redirect_limit() {
+    let policy = RedirectPolicy::default();
+    let mut at = target([10, 0, 0, 1], 8080, "/api/fobs");
+    for hops in 0..MAX_REDIRECTS {
+        at = policy.follow(hops, &at, "/api/fobs").unwrap();
+    }
+    assert_eq!(
+        policy.follow(MAX_REDIRECTS, &at, "/api/fobs"),
+        Err("too many redirects")
+    );
+
+    let none = RedirectPolicy {
+        max_hops: 0,
+        ..policy
+    };
+    assert!(none.follow(0, &at, "/api/fobs").is_err());
+}
+
+#[test]
+fn same_host_unless_configured() {
+    let from = target([10, 0, 0, 1], 8080, "/api/fobs");
+    let strict = RedirectPolicy::default();
+    assert!(!strict.cross_host);
+
+    assert!(strict.follow(0, &from, "http://10.0.0.1:8080/v2").is_ok());
+    assert_eq!(
+        strict.follow(0, &from, "http://10.0.0.2:8080/v2"),
+        Err("cross-host redirect refused")
+    );
+    // A different port on the same address is a different server.
+    assert!(strict.follow(0, &from, "http://10.0.0.1/v2").is_err());
+
+    let open = RedirectPolicy {
+        cross_host: true,
+        ..strict
+    };
+    assert_eq!(
+        open.follow(0, &from, "http://10.0.0.2:8080/v2"),
+        Ok(target([10, 0, 0, 2], 8080, "/v2"))
+    );
+}
+
+/// Drive `check_response_size` the way the sync read loop does: after
+/// each chunk. Returns how many bytes had been received when it aborted.
+fn read_loop(response: &[u8], chunk: usize) -> Result<(), usize> {
+    let mut received = Vec::new();
+    for c in response.chunks(chunk) {
+        received.extend_from_slice(c);
+        if check_response_size(&received).is_err() {
+            return Err(received.len());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn endless_headers_abort_the_read() {
+    let mut response = b"HTTP/1.1 200 OK\r\n".to_vec();
+    while response.len() < 4 * MAX_HEAD_BYTES {
+        response.extend_from_slice(b"X-Padding: aaaaaaaaaaaaaaaa\r\n");
+    }
+    let aborted_at = read_loop(&response, 100).unwrap_err();
+    assert!(aborted_at > MAX_HEAD_BYTES);
+    assert!(aborted_at <= MAX_HEAD_BYTES + 100);
+}
+
+#[test]
+fn head_at_the_limit_is_accepted() {
+    let mut head = b"HTTP/1.1 200 OK\r\nX-Pad: ".to_vec();
+    head.resize(MAX_HEAD_BYTES - 4, b'a');
+    head.extend_from_slice(b"\r\n\r\n");
+    assert_eq!(head.len(), MAX_HEAD_BYTES);
+    assert_eq!(check_response_size(&head), Ok(()));
+
+    // One more byte of header is over.
+    head.insert(20, b'a');
+    assert_eq!(
+        check_response_size(&head),
+        Err("response headers too large")
+    );
+}
+
+#[test]
+fn oversized_body_aborts() {
+    let mut response = b"HTTP/1.1 200 OK\r\n\r\n".to_vec();
+    response.resize(response.len() + MAX_BODY_BYTES, b'1');
+    assert_eq!(check_response_size(&response), Ok(()));
+    response.push(b'1');
+    assert_eq!(
+        check_response_size(&response),
+        Err("response body too large")
+    );
+}
+
+#[test]
+fn largest_response_fits() {
+    let mut response = b"HTTP/1.1 200 OK\r\nX-Pad: ".to_vec();
+    response.resize(MAX_HEAD_BYTES - 4, b'a');
+    response.extend_from_slice(b"\r\n\r\n");
+    response.resize(MAX_RESPONSE_BYTES, b'1');
+    assert_eq!(read_loop(&response, 1460), Ok(()));
+}
+
+#[test]
+fn declared_length_aborts_early() {
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+        MAX_BODY_BYTES + 1
+    );
+    assert_eq!(
+        check_response_size(head.as_bytes()),
+        Err("response body too large")
+    );
+    let head = format!("HTTP/1.1 200 OK\nContent-Length: {}\n\n[]", MAX_BODY_BYTES);
+    assert_eq!(check_response_size(head.as_bytes()), Ok(()));
+}
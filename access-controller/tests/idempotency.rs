@@ -0,0 +1,139 @@
+//! Tests for event batch idempotency keys (invariants K1–K4).
+//!
+//!   K1: keys are unique and increase monotonically within a boot.
+//!   K2: until acknowledged, a batch is re-issued with the same key and
+//!       the same event count, even if more events arrive.
+//!   K3: a batch whose head was dropped by overflow is abandoned for a
+//!       new key.
+//!   K4: keys differ across boots and controllers.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test idempotency
+
+#![cfg(feature = "sim")]
+
+use access_controller::idempotency::{BatchKeys, KEY_LEN};
+use proptest::prelude::*;
+
+const MAC: [u8; 6] = [0x24, 0x0a, 0xc4, 0x12, 0x34, 0x56];
+
+#[test]
+fn key_format() {
+    let mut k = BatchKeys::new(MAC, 0xdead_beef);
+    let (key, n) = k.batch(0, 3).unwrap();
+    assert_eq!(key.as_str(), "240ac4123456-deadbeef-00000000");
+    assert_eq!(key.len(), KEY_LEN);
+    assert_eq!(n, 3);
+}
+
+#[test]
+fn nothing_to_send() {
+    let mut k = BatchKeys::new(MAC, 1);
+    assert_eq!(k.batch(0, 0), None);
+    // An empty sync doesn't burn a counter value.
+    let (key, _) = k.batch(0, 1).unwrap();
+    assert!(key.ends_with("-00000000"));
+}
+
+#[test]
+fn monotonic() {
+    // K1
+    let mut k = BatchKeys::new(MAC, 1);
+    let mut seq = 0u64;
+    let mut last = None;
+    for _ in 0..100 {
+        let (key, n) = k.batch(seq, 2).unwrap();
+        if let Some(prev) = last.replace(key.clone()) {
+            assert!(key > prev, "{key} !> {prev}");
+        }
+        k.acked();
+        seq += n as u64;
+    }
+}
+
+#[test]
+fn stable_within_retry() {
+    // K2
+    let mut k = BatchKeys::new(MAC, 1);
+    let (key, n) = k.batch(10, 2).unwrap();
+    // Response lost; two more swipes arrive before the retry.
+    assert_eq!(k.batch(10, 4), Some((key.clone(), n)));
+    assert_eq!(k.batch(10, 4), Some((key.clone(), n)));
+    k.acked();
+    // Next batch starts after the acknowledged events, with a new key.
+    let (next, m) = k.batch(12, 2).unwrap();
+    assert_ne!(next, key);
+    assert_eq!(m, 2);
+}
+
+#[test]
+fn overflow_abandons_batch() {
+    // K3
+    let mut k = BatchKeys::new(MAC, 1);
+    let (key, _) = k.batch(10, 5).unwrap();
+    // Oldest event dropped: the batch no longer starts at seq 10.
+    let (next, n) = k.batch(11, 5).unwrap();
+    assert_ne!(next, key);
+    assert_eq!(n, 5);
+}
+
+#[test]
+fn unique_across_boots_and_devices() {
+    // K4
+    let a = BatchKeys::new(MAC, 1).batch(0, 1).unwrap().0;
+    let b = BatchKeys::new(MAC, 2).batch(0, 1).unwrap().0;
+    let mut other = MAC;
+    other[5] ^= 1;
+    let c = BatchKeys::new(other, 1).batch(0, 1).unwrap().0;
+    assert!(a != b && a != c && b != c);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// K1 + K2 under an arbitrary mix of lost responses, new swipes and
+    /// acknowledgements: a key is only ever reused for the identical
+    /// batch, and every new key is larger than all before it.
+    #[test]
+    fn retries_reuse_only_identical_batches(ops in prop::collection::vec((0u8..3, 1usize..5), 1..60)) {
+        let mut k = BatchKeys::new(MAC, 7);
+        let mut first_seq = 0u64;
+        let mut pending = 0usize;
+        let mut seen: Vec<(String, u64, usize)> = Vec::new();
+        for (op, n) in ops {
+            match op {
+                // New swipes.
+                0 => pending += n,
+                // Sync whose response is lost.
+                1 => {
+                    if let Some((key, count)) = k.batch(first_seq, pending) {
+                        let key = key.to_string();
+                        match seen.iter().find(|(k, ..)| *k == key) {
+                            Some(&(_, s, c)) => prop_assert_eq!((s, c), (first_seq, count)),
+                            None => {
+                                if let Some((last, ..)) = seen.last() {
+                                    prop_assert!(key > *last);
+                                }
+                                seen.push((key, first_seq, count));
+                            }
+                        }
+                    }
+                }
+                // Sync that succeeds.
+                _ => {
+                    if let Some((_, count)) = k.batch(first_seq, pending) {
+                        k.acked();
+                        first_seq += count as u64;
+                        pending -= count;
+                    }
+                }
+            }
+        }
+    }
+}
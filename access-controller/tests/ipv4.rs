@@ -0,0 +1,203 @@
+//! Tests for the dotted-quad parser and admin allowlist (invariants A1–A5).
+//!
+//!   A1: exactly the documented grammar is accepted: four decimal octets
+//!       0–255, no leading zeros, no sign, whitespace or empty octets.
+//!   A2: every address round-trips through its canonical text form, and
+//!       agrees with `core::net::Ipv4Addr` on arbitrary input.
+//!   A3: redirect `Location` hosts use the same grammar.
+//!   A4: a network is an address and a 0–32 prefix, a bare address is a
+//!       /32, and a list is all-or-nothing up to `MAX_ALLOWLIST`.
+//!   A5: an address is in a network iff its first `prefix` bits match;
+//!       an empty allowlist admits everyone.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test ipv4
+
+#![cfg(feature = "sim")]
+
+use std::net::Ipv4Addr;
+
+use access_controller::http_client::{parse_location, SyncTarget};
+use access_controller::ipv4::{self, parse_ipv4, Cidr, MAX_ALLOWLIST};
+use proptest::prelude::*;
+
+#[test]
+fn accepts_canonical() {
+    assert_eq!(parse_ipv4("192.168.1.10"), Some([192, 168, 1, 10]));
+    assert_eq!(parse_ipv4("0.0.0.0"), Some([0, 0, 0, 0]));
+    assert_eq!(parse_ipv4("255.255.255.255"), Some([255; 4]));
+    assert_eq!(parse_ipv4("10.0.100.9"), Some([10, 0, 100, 9]));
+}
+
+#[test]
+fn rejects_out_of_range() {
+    assert_eq!(parse_ipv4("256.0.0.1"), None);
+    assert_eq!(parse_ipv4("1.2.3.999"), None);
+    assert_eq!(parse_ipv4("1.2.3.1000"), None);
+}
+
+#[test]
+fn rejects_leading_zeros() {
+    assert_eq!(parse_ipv4("1.2.3.04"), None);
+    assert_eq!(parse_ipv4("010.0.0.1"), None);
+    assert_eq!(parse_ipv4("1.00.3.4"), None);
+    assert_eq!(parse_ipv4("1.2.3.0004"), None);
+}
+
+#[test]
+fn rejects_empty_octets() {
+    for s in [
+        "", ".", "...", "1..2.3", ".1.2.3", "1.2.3.", "1.2.3.4.", "1.2.3",
+    ] {
+        assert_eq!(parse_ipv4(s), None, "{s:?}");
+    }
+}
+
+#[test]
+fn rejects_wrong_octet_count() {
+    assert_eq!(parse_ipv4("1.2.3.4.5"), None);
+    assert_eq!(parse_ipv4("1"), None);
+    assert_eq!(parse_ipv4("16909060"), None);
+}
+
+#[test]
+fn rejects_garbage() {
+    for s in [
+        "1.2.3.4x",
+        "x1.2.3.4",
+        "+1.2.3.4",
+        "1.2.3.-4",
+        " 1.2.3.4",
+        "1.2.3.4 ",
+        "1.2. 3.4",
+        "1.2.3.4\n",
+        "0x1.2.3.4",
+        "1.2.3.4:80",
+        "1.2.3.٤",
+    ] {
+        assert_eq!(parse_ipv4(s), None, "{s:?}");
+    }
+}
+
+fn target() -> SyncTarget {
+    SyncTarget {
+        host: [10, 0, 0, 1],
+        port: 80,
+        path: "/api/fobs".try_into().unwrap(),
+    }
+}
+
+/// A3
+#[test]
+fn location_host_grammar() {
+    let t = parse_location("http://10.0.0.5:8080/v2", &target()).unwrap();
+    assert_eq!(t.host, [10, 0, 0, 5]);
+    assert!(parse_location("http://10.0.0.05/v2", &target()).is_err());
+    assert!(parse_location("http://10.0..5/v2", &target()).is_err());
+    assert!(parse_location("http://+10.0.0.5/v2", &target()).is_err());
+}
+
+fn net(addr: [u8; 4], prefix: u8) -> Cidr {
+    Cidr { addr, prefix }
+}
+
+/// A4
+#[test]
+fn cidr_grammar() {
+    let table = [
+        ("192.168.10.0/24", Some(net([192, 168, 10, 0], 24))),
+        ("10.0.0.5", Some(net([10, 0, 0, 5], 32))),
+        ("0.0.0.0/0", Some(net([0, 0, 0, 0], 0))),
+        ("10.1.2.3/32", Some(net([10, 1, 2, 3], 32))),
+        ("10.1.2.3/33", None),
+        ("10.1.2.3/08", None),
+        ("10.1.2.3/", None),
+        ("10.1.2.3/-1", None),
+        ("10.1.2/24", None),
+        ("/24", None),
+        ("10.1.2.3/24/8", None),
+        (" 10.1.2.3/24", None),
+    ];
+    for (s, want) in table {
+        assert_eq!(ipv4::parse_cidr(s), want, "{:?}", s);
+    }
+}
+
+/// A4
+#[test]
+fn cidr_list() {
+    let list = ipv4::parse_cidr_list(" 192.168.10.0/24 , 10.0.0.5,,").unwrap();
+    assert_eq!(
+        list.as_slice(),
+        [net([192, 168, 10, 0], 24), net([10, 0, 0, 5], 32)]
+    );
+    assert_eq!(ipv4::parse_cidr_list("").unwrap().len(), 0);
+    assert_eq!(ipv4::parse_cidr_list("10.0.0.0/8,bogus"), None);
+    let full = ["10.0.0.1"; MAX_ALLOWLIST].join(",");
+    assert_eq!(ipv4::parse_cidr_list(&full).unwrap().len(), MAX_ALLOWLIST);
+    let over = ["10.0.0.1"; MAX_ALLOWLIST + 1].join(",");
+    assert_eq!(ipv4::parse_cidr_list(&over), None);
+}
+
+/// A5
+#[test]
+fn cidr_membership() {
+    let lan = net([192, 168, 10, 0], 24);
+    assert!(lan.contains([192, 168, 10, 0]));
+    assert!(lan.contains([192, 168, 10, 255]));
+    assert!(!lan.contains([192, 168, 11, 1]));
+    assert!(!lan.contains([10, 168, 10, 1]));
+    // Host bits in the network address are ignored.
+    assert!(net([192, 168, 10, 77], 24).contains([192, 168, 10, 1]));
+    let odd = net([172, 16, 0, 0], 12);
+    assert!(odd.contains([172, 31, 255, 255]));
+    assert!(!odd.contains([172, 32, 0, 0]));
+    assert!(net([0, 0, 0, 0], 0).contains([203, 0, 113, 9]));
+    assert!(net([10, 0, 0, 5], 32).contains([10, 0, 0, 5]));
+    assert!(!net([10, 0, 0, 5], 32).contains([10, 0, 0, 4]));
+
+    let list = [lan, net([10, 0, 0, 5], 32)];
+    assert!(ipv4::allowed(&list, [192, 168, 10, 42]));
+    assert!(ipv4::allowed(&list, [10, 0, 0, 5]));
+    assert!(!ipv4::allowed(&list, [10, 0, 0, 6]));
+    assert!(ipv4::allowed(&[], [203, 0, 113, 9]));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// A2
+    #[test]
+    fn canonical_round_trip(octets in any::<[u8; 4]>()) {
+        let s = Ipv4Addr::from(octets).to_string();
+        prop_assert_eq!(parse_ipv4(&s), Some(octets));
+    }
+
+    /// A1/A2: same grammar as the standard library's strict parser.
+    #[test]
+    fn agrees_with_std(s in "[0-9.+ x]{0,20}") {
+        let std = s.parse::<Ipv4Addr>().ok().map(|a| a.octets());
+        prop_assert_eq!(parse_ipv4(&s), std);
+    }
+
+    /// A4/A5
+    #[test]
+    fn cidr_membership_is_a_prefix_match(
+        addr in any::<[u8; 4]>(),
+        ip in any::<[u8; 4]>(),
+        prefix in 0u8..=32,
+    ) {
+        let n = net(addr, prefix);
+        let diff = u32::from_be_bytes(addr) ^ u32::from_be_bytes(ip);
+        prop_assert_eq!(n.contains(ip), diff.leading_zeros() >= prefix as u32);
+        prop_assert!(n.contains(addr));
+        let text = format!("{}.{}.{}.{}/{}", addr[0], addr[1], addr[2], addr[3], prefix);
+        prop_assert_eq!(ipv4::parse_cidr(&text), Some(n));
+    }
+}
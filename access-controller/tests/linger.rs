@@ -0,0 +1,120 @@
+//! Tests for the closing-socket guard (invariants L1–L3).
+//!
+//!   L1: a socket with nothing left to deliver (closed, FIN-WAIT-2,
+//!       TIME-WAIT) is released at once, however recently it closed.
+//!   L2: a socket still waiting on its FIN waits until the limit, and is
+//!       force-aborted from the limit on.
+//!   L3: the verdict only moves forward with age: wait, then abort.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test linger
+
+#![cfg(feature = "sim")]
+
+use access_controller::linger::{self, TcpState, Verdict, LINGER_MS};
+use proptest::prelude::*;
+
+const ALL: [TcpState; 11] = [
+    TcpState::Closed,
+    TcpState::Listen,
+    TcpState::SynSent,
+    TcpState::SynReceived,
+    TcpState::Established,
+    TcpState::FinWait1,
+    TcpState::FinWait2,
+    TcpState::CloseWait,
+    TcpState::Closing,
+    TcpState::LastAck,
+    TcpState::TimeWait,
+];
+
+fn done(state: TcpState) -> bool {
+    matches!(
+        state,
+        TcpState::Closed | TcpState::Listen | TcpState::FinWait2 | TcpState::TimeWait
+    )
+}
+
+// ---------- L1 / L2 ----------
+
+#[test]
+fn l1_l2_state_and_age_table() {
+    use TcpState::*;
+    use Verdict::*;
+    // (state, ms since close) -> verdict
+    let table = [
+        ((TimeWait, 0), Release),
+        ((TimeWait, 60_000), Release),
+        ((FinWait2, 10), Release),
+        ((Closed, 0), Release),
+        ((FinWait1, 0), Wait),
+        ((FinWait1, LINGER_MS - 1), Wait),
+        ((FinWait1, LINGER_MS), ForceAbort),
+        ((Closing, 500), Wait),
+        ((Closing, 30_000), ForceAbort),
+        ((LastAck, 1_999), Wait),
+        ((LastAck, u64::MAX), ForceAbort),
+        // close() hasn't taken effect yet.
+        ((Established, 0), Wait),
+        ((Established, LINGER_MS), ForceAbort),
+    ];
+    for ((state, age), want) in table {
+        assert_eq!(
+            linger::after_close(state, age, LINGER_MS),
+            want,
+            "{:?} after {} ms",
+            state,
+            age
+        );
+    }
+}
+
+#[test]
+fn l2_zero_limit_aborts_at_once() {
+    for state in ALL {
+        let want = if done(state) {
+            Verdict::Release
+        } else {
+            Verdict::ForceAbort
+        };
+        assert_eq!(linger::after_close(state, 0, 0), want, "{:?}", state);
+    }
+}
+
+// ---------- L3 ----------
+
+fn rank(v: Verdict) -> u8 {
+    match v {
+        Verdict::Wait => 0,
+        Verdict::ForceAbort => 1,
+        Verdict::Release => 2,
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn l1_l2_l3_verdict_by_age(
+        state in prop::sample::select(&ALL[..]),
+        age in any::<u64>(),
+        later in any::<u64>(),
+        limit in 0u64..1 << 20,
+    ) {
+        let now = linger::after_close(state, age, limit);
+        if done(state) {
+            prop_assert_eq!(now, Verdict::Release);
+        } else {
+            prop_assert_eq!(now == Verdict::Wait, age < limit);
+            let then = linger::after_close(state, age.saturating_add(later), limit);
+            prop_assert!(rank(now) <= rank(then));
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Tests for the `/unlock` brute-force guard (invariants O1–O4).
+//!
+//!   O1: fewer than `FREE_FAILURES` wrong secrets never lock.
+//!   O2: the `FREE_FAILURES`-th failure locks for `BASE_LOCK_MS`; each
+//!       failure after a lock runs out doubles it, capped at `MAX_LOCK_MS`.
+//!   O3: while locked, attempts are refused with the time left, and a
+//!       failure reported anyway neither counts nor extends the lock.
+//!   O4: the lock releases exactly at its end; a correct secret, or a
+//!       quiet spell of `MAX_LOCK_MS`, forgets every failure.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test lockout
+
+#![cfg(feature = "sim")]
+
+use access_controller::lockout::{Lockout, BASE_LOCK_MS, FREE_FAILURES, MAX_LOCK_MS};
+use proptest::prelude::*;
+
+/// Fail `n` times at `now`, checking first like the firmware does.
+fn fail_n(l: &mut Lockout, now: u64, n: u32) -> Option<u64> {
+    let mut last = None;
+    for _ in 0..n {
+        assert_eq!(l.check(now), Ok(()));
+        last = l.fail(now);
+    }
+    last
+}
+
+// ---------- O1 / O2 ----------
+
+#[test]
+fn o1_o2_lockout_engages_after_n_failures() {
+    let mut l = Lockout::new();
+    assert_eq!(fail_n(&mut l, 1_000, FREE_FAILURES - 1), None);
+    assert_eq!(l.check(1_000), Ok(()));
+    assert_eq!(l.failures(), FREE_FAILURES - 1);
+
+    assert_eq!(l.fail(1_000), Some(BASE_LOCK_MS));
+    assert_eq!(l.check(1_000), Err(BASE_LOCK_MS));
+}
+
+#[test]
+fn o2_cool_down_doubles_to_the_cap() {
+    let mut l = Lockout::new();
+    let mut now = 0;
+    fail_n(&mut l, now, FREE_FAILURES - 1);
+    let mut want = BASE_LOCK_MS;
+    for _ in 0..12 {
+        assert_eq!(l.fail(now), Some(want));
+        now += want;
+        want = (want * 2).min(MAX_LOCK_MS);
+    }
+    assert_eq!(want, MAX_LOCK_MS);
+}
+
+// ---------- O3 ----------
+
+#[test]
+fn o3_locked_attempts_do_not_count() {
+    let mut l = Lockout::new();
+    fail_n(&mut l, 0, FREE_FAILURES);
+    let failures = l.failures();
+    assert_eq!(l.check(10_000), Err(BASE_LOCK_MS - 10_000));
+    assert_eq!(l.fail(10_000), None);
+    assert_eq!(l.failures(), failures);
+    assert_eq!(l.check(10_000), Err(BASE_LOCK_MS - 10_000));
+}
+
+// ---------- O4 ----------
+
+#[test]
+fn o4_releases_after_the_cool_down() {
+    let mut l = Lockout::new();
+    fail_n(&mut l, 5_000, FREE_FAILURES);
+    assert_eq!(l.check(5_000 + BASE_LOCK_MS - 1), Err(1));
+    assert_eq!(l.check(5_000 + BASE_LOCK_MS), Ok(()));
+    // One more wrong guess after release locks again, for twice as long.
+    assert_eq!(l.fail(5_000 + BASE_LOCK_MS), Some(2 * BASE_LOCK_MS));
+}
+
+#[test]
+fn o4_success_and_quiet_spell_forget() {
+    let mut l = Lockout::new();
+    fail_n(&mut l, 0, FREE_FAILURES);
+    let after = BASE_LOCK_MS;
+    assert_eq!(l.check(after), Ok(()));
+    l.succeeded();
+    assert_eq!(l.failures(), 0);
+    assert_eq!(fail_n(&mut l, after, FREE_FAILURES - 1), None);
+
+    let mut l = Lockout::new();
+    fail_n(&mut l, 0, FREE_FAILURES);
+    // Quiet for MAX_LOCK_MS after the lock ended: the next failure is
+    // the first again.
+    let later = BASE_LOCK_MS + MAX_LOCK_MS;
+    assert_eq!(l.fail(later), None);
+    assert_eq!(l.failures(), 1);
+
+    let mut l = Lockout::new();
+    fail_n(&mut l, 0, FREE_FAILURES);
+    // Not quite quiet long enough: the count carries on.
+    assert_eq!(l.fail(later - 1), Some(2 * BASE_LOCK_MS));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn o1_o4_any_attempt_sequence(
+        steps in prop::collection::vec((0u64..200_000, any::<bool>()), 1..60),
+    ) {
+        let mut l = Lockout::new();
+        let mut now = 0u64;
+        for (gap, right) in steps {
+            now += gap;
+            match l.check(now) {
+                Err(left) => {
+                    prop_assert!(left > 0 && left <= MAX_LOCK_MS);
+                    let before = l;
+                    prop_assert_eq!(l.fail(now), None);
+                    prop_assert_eq!(l, before);
+                }
+                Ok(()) if right => {
+                    l.succeeded();
+                    prop_assert_eq!(l.failures(), 0);
+                }
+                Ok(()) => match l.fail(now) {
+                    None => prop_assert!(l.failures() < FREE_FAILURES),
+                    Some(ms) => {
+                        prop_assert!(l.failures() >= FREE_FAILURES);
+                        prop_assert!((BASE_LOCK_MS..=MAX_LOCK_MS).contains(&ms));
+                        prop_assert_eq!(l.check(now), Err(ms));
+                        prop_assert_eq!(l.check(now + ms), Ok(()));
+                    }
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Tests for OSDP framing and card-read extraction (invariants P1–P5).
+//!
+//!   P1: the CRC is CRC-16/AUG-CCITT and the checksum makes the packet
+//!       sum to zero; a POLL we build carries a valid CRC.
+//!   P2: `scan` skips noise before SOM, waits for a whole packet and
+//!       resyncs past a header with an impossible length.
+//!   P3: `parse_reply` accepts CRC and checksum replies and refuses bad
+//!       integrity, length mismatch, CP commands and secure-channel packets.
+//!   P4: an `osdp_RAW` 26/34-bit read decodes like the same Wiegand frame
+//!       (parity enforced); a 32-bit read becomes `raw_data`.
+//!   P5: sequence numbers open at 0 and then cycle 1, 2, 3.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test osdp
+
+#![cfg(feature = "sim")]
+
+use access_controller::decode::{decode_26, decode_34, encode_26, encode_34};
+use access_controller::osdp::{
+    card_read, checksum, crc16, encode_poll, next_sqn, parse_address, parse_reply, scan, Error,
+    Scan, BROADCAST, CMD_POLL, REPLY_ACK, REPLY_RAW, SOM,
+};
+use proptest::prelude::*;
+
+/// A PD reply from `addr` with a CRC (or checksum) trailer.
+fn reply(addr: u8, ctrl: u8, code: u8, data: &[u8]) -> Vec<u8> {
+    let crc = ctrl & 0x04 != 0;
+    let len = 6 + data.len() + if crc { 2 } else { 1 };
+    let mut out = vec![SOM, addr | 0x80, len as u8, (len >> 8) as u8, ctrl, code];
+    out.extend_from_slice(data);
+    if crc {
+        let c = crc16(&out);
+        out.extend_from_slice(&c.to_le_bytes());
+    } else {
+        out.push(checksum(&out));
+    }
+    out
+}
+
+/// `osdp_RAW` payload: reader 0, `format`, `count` bits left-justified.
+fn raw(format: u8, bits: u64, count: u32) -> Vec<u8> {
+    let nbytes = count.div_ceil(8);
+    let padded = bits << (nbytes * 8 - count);
+    let mut out = vec![0, format, count as u8, (count >> 8) as u8];
+    out.extend((0..nbytes).rev().map(|i| (padded >> (i * 8)) as u8));
+    out
+}
+
+// ---------------------------------------------------------------------------
+// P1: integrity
+// ---------------------------------------------------------------------------
+
+#[test]
+fn crc_matches_aug_ccitt_check_value() {
+    assert_eq!(crc16(b"123456789"), 0xE5CC);
+    assert_eq!(crc16(&[]), 0x1D0F);
+}
+
+#[test]
+fn checksum_zeroes_the_sum() {
+    let data = [SOM, 0x80, 7, 0, 0, REPLY_ACK];
+    let sum = data.iter().fold(checksum(&data), |s, &b| s.wrapping_add(b));
+    assert_eq!(sum, 0);
+}
+
+#[test]
+fn poll_is_well_formed() {
+    let mut buf = [0u8; 8];
+    let n = encode_poll(5, 2, &mut buf);
+    assert_eq!(n, 8);
+    assert_eq!(&buf[..6], &[SOM, 5, 8, 0, 0x04 | 2, CMD_POLL]);
+    assert_eq!(u16::from_le_bytes([buf[6], buf[7]]), crc16(&buf[..6]));
+    assert_eq!(scan(&buf), Scan::Frame(8));
+    // A command, not a reply.
+    assert_eq!(parse_reply(&buf), Err(Error::NotReply));
+}
+
+// ---------------------------------------------------------------------------
+// P2: scan
+// ---------------------------------------------------------------------------
+
+#[test]
+fn scan_skips_noise_and_waits() {
+    let frame = reply(0, 0x04, REPLY_ACK, &[]);
+    assert_eq!(scan(&[]), Scan::NeedMore);
+    assert_eq!(scan(&[0xFF, 0x00]), Scan::Skip(2));
+    let mut noisy = vec![0xFF, 0x12];
+    noisy.extend_from_slice(&frame);
+    assert_eq!(scan(&noisy), Scan::Skip(2));
+    for cut in 1..frame.len() {
+        assert_eq!(scan(&frame[..cut]), Scan::NeedMore, "cut at {}", cut);
+    }
+    assert_eq!(scan(&frame), Scan::Frame(frame.len()));
+}
+
+#[test]
+fn scan_resyncs_past_bogus_length() {
+    // SOM followed by a length no packet has.
+    assert_eq!(scan(&[SOM, 0x80, 2, 0, 0]), Scan::Skip(1));
+    assert_eq!(scan(&[SOM, 0x80, 0xFF, 0xFF, 0]), Scan::Skip(1));
+}
+
+// ---------------------------------------------------------------------------
+// P3: parse_reply
+// ---------------------------------------------------------------------------
+
+#[test]
+fn parses_crc_and_checksum_replies() {
+    for ctrl in [0x04 | 1, 1] {
+        let frame = reply(3, ctrl, REPLY_ACK, &[]);
+        let r = parse_reply(&frame).unwrap();
+        assert_eq!((r.addr, r.sqn, r.code, r.data), (3, 1, REPLY_ACK, &[][..]));
+    }
+}
+
+#[test]
+fn refuses_bad_packets() {
+    let good = reply(0, 0x04, REPLY_RAW, &raw(1, encode_26(1, 2), 26));
+    assert!(parse_reply(&good).is_ok());
+
+    let mut flipped = good.clone();
+    flipped[7] ^= 0x01;
+    assert_eq!(parse_reply(&flipped), Err(Error::Integrity));
+
+    let mut short = good.clone();
+    short.pop();
+    assert_eq!(parse_reply(&short), Err(Error::Framing));
+
+    let mut bad_som = good.clone();
+    bad_som[0] = 0x00;
+    assert_eq!(parse_reply(&bad_som), Err(Error::Framing));
+
+    let secure = reply(0, 0x04 | 0x08, REPLY_ACK, &[2, 0x11]);
+    assert_eq!(parse_reply(&secure), Err(Error::Secure));
+}
+
+proptest! {
+    /// P3: parsing arbitrary bytes never panics.
+    #[test]
+    fn parse_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..64)) {
+        let _ = parse_reply(&bytes);
+        let _ = scan(&bytes);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// P4: card reads
+// ---------------------------------------------------------------------------
+
+proptest! {
+    /// P4: a 26-bit osdp_RAW read is the Wiegand read of the same bits.
+    #[test]
+    fn raw_26_matches_wiegand(facility in 0u32..256, card in 0u32..65536, format in 0u8..2) {
+        let bits = encode_26(facility, card);
+        let frame = reply(0, 0x04, REPLY_RAW, &raw(format, bits, 26));
+        let r = parse_reply(&frame).unwrap();
+        prop_assert_eq!(card_read(&r), decode_26(bits));
+    }
+
+    /// P4: same for 34 bits.
+    #[test]
+    fn raw_34_matches_wiegand(facility in 0u32..256, card in 0u32..65536) {
+        let bits = encode_34(facility, card);
+        let frame = reply(0, 0x04, REPLY_RAW, &raw(1, bits, 34));
+        let r = parse_reply(&frame).unwrap();
+        prop_assert_eq!(card_read(&r), decode_34(bits));
+    }
+
+    /// P4: a bare 32-bit read carries its bits as raw_data.
+    #[test]
+    fn raw_32_is_raw_data(data in any::<u32>()) {
+        let frame = reply(0, 0x04, REPLY_RAW, &raw(0, u64::from(data), 32));
+        let read = card_read(&parse_reply(&frame).unwrap()).unwrap();
+        prop_assert_eq!(read.raw_data, data);
+        prop_assert_eq!(read.card, data & 0xFFFF);
+    }
+}
+
+#[test]
+fn raw_parity_failure_is_none() {
+    let frame = reply(0, 0x04, REPLY_RAW, &raw(1, encode_26(1, 2) ^ 1, 26));
+    assert_eq!(card_read(&parse_reply(&frame).unwrap()), None);
+}
+
+#[test]
+fn unsupported_reads_are_none() {
+    // Other lengths, other format codes, truncated data, other replies.
+    let cases = [
+        reply(0, 0x04, REPLY_RAW, &raw(0, 0x1234, 16)),
+        reply(0, 0x04, REPLY_RAW, &raw(2, encode_26(1, 2), 26)),
+        reply(0, 0x04, REPLY_RAW, &raw(1, encode_26(1, 2), 26)[..6]),
+        reply(0, 0x04, REPLY_ACK, &[]),
+    ];
+    for frame in &cases {
+        assert_eq!(card_read(&parse_reply(frame).unwrap()), None);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// P5: sequence numbers, addresses
+// ---------------------------------------------------------------------------
+
+#[test]
+fn sequence_cycles_after_zero() {
+    let seq: Vec<u8> = std::iter::successors(Some(0), |&s| Some(next_sqn(s))).take(8).collect();
+    assert_eq!(seq, [0, 1, 2, 3, 1, 2, 3, 1]);
+}
+
+#[test]
+fn address_knob() {
+    assert_eq!(parse_address("0"), Some(0));
+    assert_eq!(parse_address("126"), Some(126));
+    assert_eq!(parse_address(&BROADCAST.to_string()), None);
+    assert_eq!(parse_address("x"), None);
+}
@@ -0,0 +1,253 @@
+//! Tests for the anti-passback window (invariants I1–I5).
+//!
+//!   I1: the window knob accepts 0 to `MAX_WINDOW_SECS` only.
+//!   I2: a credential that was just granted is ignored, with no effects
+//!       at all, until the window has passed, at either reader; after
+//!       that it is granted again and starts a new window.
+//!   I3: other credentials are unaffected, a denial starts no window, an
+//!       ignored read costs no backoff, and a window of 0 (the default)
+//!       ignores nothing.
+//!   I4: every kind of grant starts the window: a list match, a recheck
+//!       after sync, a fail-open grant and an online `allow`.
+//!   I5: the window is remembered for up to `RECENT_GRANT_SLOTS`
+//!       credentials; a full set forgets the one closest to the end of
+//!       its window.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test passback
+
+#![cfg(feature = "sim")]
+
+use access_controller::authz::Verdict;
+use access_controller::core::{CardRead, Effect, FailPolicy, Input, Outcome, Reader};
+use access_controller::passback::{self, RecentGrants, MAX_WINDOW_SECS, RECENT_GRANT_SLOTS};
+use proptest::prelude::*;
+
+mod sim;
+use sim::Sim;
+
+const WINDOW_MS: u64 = 10_000;
+const MEMBER: CardRead = CardRead {
+    fob: 11,
+    nfc: 12,
+    reader: Reader::Wiegand,
+};
+const OTHER_MEMBER: CardRead = CardRead {
+    fob: 31,
+    nfc: 32,
+    reader: Reader::Wiegand,
+};
+const STRANGER: CardRead = CardRead {
+    fob: 21,
+    nfc: 22,
+    reader: Reader::Wiegand,
+};
+
+/// `s` with both members on the Conway list.
+fn listed(mut s: Sim) -> Sim {
+    s.add_fob(MEMBER.fob);
+    s.add_fob(OTHER_MEMBER.fob);
+    s
+}
+
+fn guarded() -> Sim {
+    listed(Sim::new().with_passback_window(WINDOW_MS))
+}
+
+fn swipe(s: &mut Sim, now_ms: u64, read: CardRead) -> Vec<Effect> {
+    s.input_at(now_ms, Input::Card(read))
+}
+
+fn granted(effects: &[Effect]) -> bool {
+    effects.contains(&Effect::OpenDoor)
+}
+
+// ---------- I1 ----------
+
+#[test]
+fn i1_window() {
+    assert_eq!(passback::parse_window_secs("0"), Some(0));
+    assert_eq!(passback::parse_window_secs("10"), Some(10));
+    assert_eq!(passback::parse_window_secs("300"), Some(MAX_WINDOW_SECS));
+    for bad in ["301", "-1", "", "10s", "1.5"] {
+        assert_eq!(passback::parse_window_secs(bad), None, "{:?}", bad);
+    }
+}
+
+// ---------- I2 ----------
+
+#[test]
+fn i2_reswipe_within_the_window_is_ignored() {
+    let mut s = guarded();
+    assert!(granted(&swipe(&mut s, 1_000, MEMBER)));
+    assert!(swipe(&mut s, 1_001, MEMBER).is_empty());
+    assert!(swipe(&mut s, 1_000 + WINDOW_MS - 1, MEMBER).is_empty());
+    // Handed to the other reader: still the same card.
+    let at_osdp = MEMBER.at(Reader::Osdp);
+    assert!(swipe(&mut s, 5_000, at_osdp).is_empty());
+}
+
+#[test]
+fn i2_allowed_once_the_window_has_passed() {
+    let mut s = guarded();
+    assert!(granted(&swipe(&mut s, 1_000, MEMBER)));
+    let later = 1_000 + WINDOW_MS;
+    assert!(granted(&swipe(&mut s, later, MEMBER)));
+    // That grant started a window of its own.
+    assert!(swipe(&mut s, later + 1, MEMBER).is_empty());
+    assert!(granted(&swipe(&mut s, later + WINDOW_MS, MEMBER)));
+}
+
+// ---------- I3 ----------
+
+#[test]
+fn i3_other_credentials_unaffected() {
+    let mut s = guarded();
+    assert!(granted(&swipe(&mut s, 1_000, MEMBER)));
+    assert!(granted(&swipe(&mut s, 1_001, OTHER_MEMBER)));
+    let eff = swipe(&mut s, 1_002, STRANGER);
+    assert!(eff.contains(&Effect::Feedback(Outcome::Denied)));
+}
+
+#[test]
+fn i3_a_denial_starts_no_window() {
+    let mut s = guarded();
+    assert!(!granted(&swipe(&mut s, 1_000, STRANGER)));
+    assert!(!s.core.recent_grants().contains(STRANGER, 1_001));
+}
+
+#[test]
+fn i3_an_ignored_read_costs_no_backoff() {
+    let mut s = guarded();
+    assert!(granted(&swipe(&mut s, 1_000, MEMBER)));
+    for t in 1..10 {
+        assert!(swipe(&mut s, 1_000 + t * 100, MEMBER).is_empty());
+    }
+    assert_eq!(s.core.failed_attempts(), 0);
+    assert_eq!(s.core.backoff_until(), 0);
+}
+
+#[test]
+fn i3_off_by_default() {
+    let mut s = listed(Sim::new());
+    assert_eq!(s.core.recent_grants().window_ms(), 0);
+    for t in 0..5 {
+        assert!(granted(&swipe(&mut s, 1_000 + t, MEMBER)));
+    }
+    let mut zero = listed(Sim::new().with_passback_window(0));
+    assert!(granted(&swipe(&mut zero, 1_000, MEMBER)));
+    assert!(granted(&swipe(&mut zero, 1_001, MEMBER)));
+}
+
+// ---------- I4 ----------
+
+#[test]
+fn i4_recheck_grant_starts_the_window() {
+    let mut s = Sim::new().with_passback_window(WINDOW_MS);
+    // Not listed yet: denied, and the on-demand sync brings it in.
+    let eff = swipe(&mut s, 1_000, MEMBER);
+    assert!(eff.contains(&Effect::RequestSync));
+    s.add_fob(MEMBER.fob);
+    let eff = s.input_at(1_500, Input::SyncComplete);
+    assert!(granted(&eff));
+    assert!(s.core.recent_grants().contains(MEMBER, 1_501));
+    assert!(swipe(&mut s, 2_000, MEMBER).is_empty());
+}
+
+#[test]
+fn i4_fail_open_grant_starts_the_window() {
+    // Booted with no list loaded yet.
+    let mut s = Sim::with_fail_policy(FailPolicy::OpenFor { window_ms: 60_000 })
+        .with_passback_window(WINDOW_MS);
+    let eff = swipe(&mut s, 1_000, STRANGER);
+    assert!(eff.contains(&Effect::FailOpenGrant));
+    let eff = swipe(&mut s, 1_001, STRANGER);
+    assert!(eff.is_empty());
+}
+
+#[test]
+fn i4_online_allow_starts_the_window() {
+    let mut s = guarded().with_online_check(1_000);
+    let eff = swipe(&mut s, 1_000, STRANGER);
+    assert!(eff.contains(&Effect::QueryAuthz(STRANGER)));
+    let answer = Input::AuthzAnswer {
+        read: STRANGER,
+        verdict: Verdict::Allow,
+    };
+    let eff = s.input_at(1_200, answer);
+    assert!(granted(&eff));
+    assert!(swipe(&mut s, 1_300, STRANGER).is_empty());
+}
+
+// ---------- I5 ----------
+
+fn member(i: u32) -> CardRead {
+    CardRead {
+        fob: 100 + i,
+        nfc: 200 + i,
+        reader: Reader::Wiegand,
+    }
+}
+
+#[test]
+fn i5_remembers_up_to_its_slots() {
+    let mut recent = RecentGrants::new(WINDOW_MS);
+    for i in 0..RECENT_GRANT_SLOTS as u32 {
+        recent.insert(member(i), 1_000 + u64::from(i));
+    }
+    for i in 0..RECENT_GRANT_SLOTS as u32 {
+        assert!(recent.contains(member(i), 2_000));
+    }
+    // Full: the first granted, closest to the end of its window, goes.
+    recent.insert(member(99), 2_000);
+    assert!(recent.contains(member(99), 2_001));
+    assert!(!recent.contains(member(0), 2_001));
+    assert!(recent.contains(member(1), 2_001));
+}
+
+#[test]
+fn i5_expired_slots_are_reused() {
+    let mut recent = RecentGrants::new(WINDOW_MS);
+    for i in 0..RECENT_GRANT_SLOTS as u32 {
+        recent.insert(member(i), 1_000);
+    }
+    recent.insert(member(99), 1_000 + WINDOW_MS);
+    assert!(recent.contains(member(99), 1_000 + WINDOW_MS));
+    assert!(!recent.contains(member(0), 1_000 + WINDOW_MS));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// I2/I3 against a model: a listed card is granted exactly when its
+    /// last grant is at least a window ago, and ignored otherwise.
+    #[test]
+    fn i2_matches_model(
+        window_secs in 0u64..=MAX_WINDOW_SECS,
+        gaps in prop::collection::vec((0u64..20_000, any::<bool>()), 1..64),
+    ) {
+        let window_ms = window_secs * 1000;
+        let mut s = listed(Sim::new().with_passback_window(window_ms));
+        let mut now = 1_000u64;
+        let mut last_grant: [Option<u64>; 2] = [None, None];
+        for (gap, other) in gaps {
+            now += gap;
+            let (read, i) = if other { (OTHER_MEMBER, 1) } else { (MEMBER, 0) };
+            let reader = if gap % 2 == 0 { Reader::Wiegand } else { Reader::Osdp };
+            let eff = swipe(&mut s, now, read.at(reader));
+            let ignored = last_grant[i].is_some_and(|at| now < at + window_ms);
+            if ignored {
+                prop_assert!(eff.is_empty());
+            } else {
+                prop_assert!(granted(&eff));
+                last_grant[i] = Some(now);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Tests for the supply-monitor debounce (invariants B1–B3).
+//!
+//!   B1: a level counts only once it has held for the debounce time; a
+//!       blip shorter than that reports nothing.
+//!   B2: each transition is reported once, `OnBattery` once per outage.
+//!   B3: the two inputs are debounced independently, and the state
+//!       follows the reported transitions.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test power
+
+#![cfg(feature = "sim")]
+
+use access_controller::power::{PowerEvent, PowerState, PowerWatch, DEBOUNCE_MS};
+use proptest::prelude::*;
+
+fn events(w: &mut PowerWatch, now: u64, on_battery: bool, low: bool) -> Vec<PowerEvent> {
+    w.sample(now, on_battery, low).to_vec()
+}
+
+#[test]
+fn starts_on_mains() {
+    let w = PowerWatch::new(DEBOUNCE_MS);
+    assert_eq!(w.state(), PowerState::default());
+}
+
+#[test]
+fn mains_loss_reported_after_debounce() {
+    // B1
+    let mut w = PowerWatch::new(1_000);
+    assert_eq!(events(&mut w, 0, false, false), []);
+    assert_eq!(events(&mut w, 100, true, false), []);
+    assert_eq!(events(&mut w, 1_099, true, false), []);
+    assert!(!w.state().on_battery);
+    assert_eq!(events(&mut w, 1_100, true, false), [PowerEvent::OnBattery]);
+    assert!(w.state().on_battery);
+}
+
+#[test]
+fn blip_is_ignored_and_restarts_debounce() {
+    // B1
+    let mut w = PowerWatch::new(1_000);
+    assert_eq!(events(&mut w, 0, true, false), []);
+    assert_eq!(events(&mut w, 900, false, false), []);
+    // The blip reset the timer: a second low starts counting afresh.
+    assert_eq!(events(&mut w, 1_000, true, false), []);
+    assert_eq!(events(&mut w, 1_999, true, false), []);
+    assert_eq!(events(&mut w, 2_000, true, false), [PowerEvent::OnBattery]);
+}
+
+#[test]
+fn on_battery_reported_once_per_outage() {
+    // B2
+    let mut w = PowerWatch::new(1_000);
+    events(&mut w, 0, true, false);
+    assert_eq!(events(&mut w, 1_000, true, false), [PowerEvent::OnBattery]);
+    assert_eq!(events(&mut w, 5_000, true, false), []);
+    assert_eq!(events(&mut w, 60_000, true, false), []);
+    events(&mut w, 61_000, false, false);
+    assert_eq!(events(&mut w, 62_000, false, false), [PowerEvent::MainsRestored]);
+    assert_eq!(events(&mut w, 63_000, false, false), []);
+    events(&mut w, 64_000, true, false);
+    assert_eq!(events(&mut w, 65_000, true, false), [PowerEvent::OnBattery]);
+}
+
+#[test]
+fn inputs_debounce_independently() {
+    // B3
+    let mut w = PowerWatch::new(1_000);
+    events(&mut w, 0, true, false);
+    assert_eq!(events(&mut w, 500, true, true), []);
+    assert_eq!(events(&mut w, 1_000, true, true), [PowerEvent::OnBattery]);
+    assert_eq!(events(&mut w, 1_500, true, true), [PowerEvent::BatteryLow]);
+    assert_eq!(
+        w.state(),
+        PowerState {
+            on_battery: true,
+            battery_low: true
+        }
+    );
+    // Both clearing together settle together, mains first.
+    events(&mut w, 2_000, false, false);
+    assert_eq!(
+        events(&mut w, 3_000, false, false),
+        [PowerEvent::MainsRestored, PowerEvent::BatteryOk]
+    );
+    assert_eq!(w.state(), PowerState::default());
+}
+
+#[test]
+fn zero_debounce_reports_at_once() {
+    let mut w = PowerWatch::new(0);
+    assert_eq!(events(&mut w, 0, true, false), [PowerEvent::OnBattery]);
+    assert_eq!(events(&mut w, 0, false, false), [PowerEvent::MainsRestored]);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 1024,
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// B1/B2: over any sampled trace, `OnBattery` and `MainsRestored`
+    /// alternate starting with `OnBattery`, each follows a level held for
+    /// the debounce time, and the state matches the last one reported.
+    #[test]
+    fn prop_transitions_alternate_and_are_debounced(
+        debounce in 0u64..5_000,
+        trace in proptest::collection::vec((1u64..2_000, any::<bool>()), 1..128),
+    ) {
+        let mut w = PowerWatch::new(debounce);
+        let mut now = 0u64;
+        let mut on_battery = false;
+        // When the current input level was first sampled.
+        let mut level: Option<(bool, u64)> = None;
+        for (step, input) in trace {
+            now += step;
+            let since = match level {
+                Some((l, t)) if l == input => t,
+                _ => now,
+            };
+            level = Some((input, since));
+            for event in w.sample(now, input, false) {
+                match event {
+                    PowerEvent::OnBattery => prop_assert!(!on_battery),
+                    PowerEvent::MainsRestored => prop_assert!(on_battery),
+                    other => prop_assert!(false, "unexpected {:?}", other),
+                }
+                prop_assert!(now - since >= debounce);
+                on_battery = !on_battery;
+            }
+            prop_assert_eq!(w.state().on_battery, on_battery);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Tests for the dropped-read counter (invariants Q1–Q4).
+//!
+//!   Q1: a read offered to a full channel is counted once and the send
+//!       error passed back unchanged; a read that fits is not counted.
+//!   Q2: over any burst of reads against a consumer that drains some of
+//!       them, the count is exactly the reads the channel refused.
+//!   Q3: `/metrics` renders the count as a Prometheus counter.
+//!   Q4: a burst of READ_QUEUE_DEPTH reads while `access_task` is stalled
+//!       all queue, where the old depth-4 channel dropped all but 4; the
+//!       queue stays within its RAM budget.
+//!
+//! The firmware's channel is embassy's; `std::sync::mpsc::sync_channel`
+//! stands in for it here, with the same non-blocking `try_send`.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test read_drops
+
+#![cfg(feature = "sim")]
+
+use std::sync::mpsc::{sync_channel, TrySendError};
+
+use access_controller::decode::WiegandRead;
+use access_controller::read_drops::{DroppedReads, QueuedRead, READ_QUEUE_BYTES, READ_QUEUE_DEPTH};
+use proptest::prelude::*;
+
+// ---------- Q1 ----------
+
+#[test]
+fn full_channel_counts_a_drop() {
+    let drops = DroppedReads::new();
+    let (tx, rx) = sync_channel::<u32>(2);
+    assert_eq!(drops.note(tx.try_send(1)), Ok(()));
+    assert_eq!(drops.note(tx.try_send(2)), Ok(()));
+    assert_eq!(drops.get(), 0);
+    assert_eq!(drops.note(tx.try_send(3)), Err(TrySendError::Full(3)));
+    assert_eq!(drops.get(), 1);
+    assert_eq!(drops.note(tx.try_send(4)), Err(TrySendError::Full(4)));
+    assert_eq!(drops.get(), 2);
+    // Room again once the consumer takes one.
+    assert_eq!(rx.recv(), Ok(1));
+    assert_eq!(drops.note(tx.try_send(5)), Ok(()));
+    assert_eq!(drops.get(), 2);
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), [2, 5]);
+}
+
+// ---------- Q2 ----------
+
+proptest! {
+    /// Each step offers `reads` reads, then the consumer takes up to
+    /// `taken`.
+    #[test]
+    fn count_matches_refused_reads(
+        cap in 1usize..17,
+        steps in prop::collection::vec((0usize..20, 0usize..20), 1..30),
+    ) {
+        let drops = DroppedReads::new();
+        let (tx, rx) = sync_channel::<usize>(cap);
+        let mut queued = 0;
+        let mut refused = 0;
+        for (reads, taken) in steps {
+            for read in 0..reads {
+                if drops.note(tx.try_send(read)).is_err() {
+                    refused += 1;
+                } else {
+                    queued += 1;
+                }
+                prop_assert!(queued <= cap);
+            }
+            for _ in 0..taken.min(queued) {
+                rx.recv().unwrap();
+                queued -= 1;
+            }
+        }
+        prop_assert_eq!(drops.get(), refused);
+    }
+}
+
+// ---------- Q3 ----------
+
+#[test]
+fn metrics_render() {
+    let drops = DroppedReads::default();
+    assert!(drops.note(Err(())).is_err());
+    let mut out = String::new();
+    drops.write_metrics(&mut out).unwrap();
+    assert_eq!(
+        out,
+        "# TYPE conway_reads_dropped_total counter\nconway_reads_dropped_total 1\n"
+    );
+}
+
+// ---------- Q4 ----------
+
+/// Offer `burst` reads to a stalled consumer over a channel of `depth`;
+/// returns how many were dropped.
+fn stalled_burst(depth: usize, burst: usize) -> u32 {
+    let drops = DroppedReads::new();
+    let (tx, _rx) = sync_channel::<WiegandRead>(depth);
+    for card in 0..burst as u32 {
+        let read = WiegandRead {
+            facility: 1,
+            card,
+            raw_data: card,
+        };
+        let _ = drops.note(tx.try_send(read));
+    }
+    drops.get()
+}
+
+#[test]
+fn deeper_queue_absorbs_a_burst() {
+    let burst = 20;
+    assert_eq!(stalled_burst(4, burst), 16);
+    assert_eq!(stalled_burst(READ_QUEUE_DEPTH, burst), 0);
+    assert_eq!(stalled_burst(READ_QUEUE_DEPTH, READ_QUEUE_DEPTH), 0);
+    assert_eq!(stalled_burst(READ_QUEUE_DEPTH, READ_QUEUE_DEPTH + 1), 1);
+}
+
+#[test]
+fn queue_ram_budget() {
+    const { assert!(READ_QUEUE_DEPTH >= 16) };
+    assert_eq!(
+        READ_QUEUE_BYTES,
+        READ_QUEUE_DEPTH * std::mem::size_of::<QueuedRead>()
+    );
+    const { assert!(READ_QUEUE_BYTES <= 512) };
+}
@@ -0,0 +1,80 @@
+//! Tests for the reader keep-alive timeout logic (invariants R1–R3).
+//!
+//!   R1: no `Offline` until a full keep-alive interval passes with no
+//!       activity (including right after boot).
+//!   R2: `Offline` is reported once per outage.
+//!   R3: any activity after `Offline` reports `Online` and restarts
+//!       the timer.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test reader_watch
+
+#![cfg(feature = "sim")]
+
+use access_controller::reader_watch::{ReaderEvent, ReaderWatch};
+use proptest::prelude::*;
+
+#[test]
+fn starts_online_with_boot_grace() {
+    // R1
+    let mut w = ReaderWatch::new(30_000, 5_000);
+    assert!(w.is_online());
+    assert_eq!(w.poll(35_000), None);
+    assert_eq!(w.poll(35_001), Some(ReaderEvent::Offline));
+    assert!(!w.is_online());
+}
+
+#[test]
+fn activity_resets_timer() {
+    let mut w = ReaderWatch::new(1_000, 0);
+    assert_eq!(w.activity(900), None);
+    assert_eq!(w.poll(1_500), None);
+    assert_eq!(w.poll(1_901), Some(ReaderEvent::Offline));
+}
+
+#[test]
+fn offline_reported_once() {
+    // R2
+    let mut w = ReaderWatch::new(1_000, 0);
+    assert_eq!(w.poll(2_000), Some(ReaderEvent::Offline));
+    assert_eq!(w.poll(3_000), None);
+    assert_eq!(w.poll(60_000), None);
+}
+
+#[test]
+fn activity_after_offline_comes_back_online() {
+    // R3
+    let mut w = ReaderWatch::new(1_000, 0);
+    assert_eq!(w.poll(2_000), Some(ReaderEvent::Offline));
+    assert_eq!(w.activity(2_500), Some(ReaderEvent::Online));
+    assert_eq!(w.last_seen_ms(), 2_500);
+    assert_eq!(w.activity(2_600), None);
+    assert_eq!(w.poll(3_500), None);
+    assert_eq!(w.poll(3_601), Some(ReaderEvent::Offline));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 1024,
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// R1: with activity at least every `keepalive` ms, never offline.
+    #[test]
+    fn prop_regular_keepalive_never_offline(
+        keepalive in 1u64..100_000,
+        permille in proptest::collection::vec(0u64..=1000, 1..64),
+    ) {
+        let mut w = ReaderWatch::new(keepalive, 0);
+        let mut now = 0u64;
+        for p in permille {
+            // Poll right before the next pulse, up to the latest legal time.
+            now += keepalive * p / 1000;
+            prop_assert_eq!(w.poll(now), None);
+            prop_assert_eq!(w.activity(now), None);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Tests for the scheduled reboot decision (invariants B1–B4).
+//!
+//!   B1: the uptime and window knobs accept exactly the documented
+//!       forms.
+//!   B2: a window contains its start, not its end, and wraps midnight;
+//!       Unix times map onto it by UTC time of day.
+//!   B3: a reboot is never due before the configured uptime, and once
+//!       due waits only for the window (when the time is known) and a
+//!       quiet door.
+//!   B4: however busy the door or far the window, a reboot goes ahead
+//!       once it is a grace period overdue.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test reboot
+
+#![cfg(feature = "sim")]
+
+use access_controller::reboot::{
+    parse_after_hours, Decision, Schedule, Window, GRACE_MS, IDLE_MS, MAX_REBOOT_HOURS,
+    MIN_REBOOT_HOURS,
+};
+use proptest::prelude::*;
+
+const HOUR_MS: u64 = 3_600_000;
+const DAY_MS: u64 = 24 * HOUR_MS;
+
+/// Unix time at `h:m` UTC on some day well after the epoch.
+fn at(h: u64, m: u64) -> u64 {
+    19_000 * DAY_MS + h * HOUR_MS + m * 60_000
+}
+
+fn window(s: &str) -> Window {
+    Window::parse(s).unwrap()
+}
+
+// ---------- B1 ----------
+
+#[test]
+fn b1_after_hours() {
+    assert_eq!(parse_after_hours("0"), Some(0));
+    assert_eq!(parse_after_hours("1"), Some(MIN_REBOOT_HOURS));
+    assert_eq!(parse_after_hours("168"), Some(168));
+    assert_eq!(parse_after_hours("720"), Some(MAX_REBOOT_HOURS));
+    for bad in ["721", "-1", "", "24h", "1.5"] {
+        assert_eq!(parse_after_hours(bad), None, "{:?}", bad);
+    }
+}
+
+#[test]
+fn b1_window() {
+    assert!(Window::parse("02:00-04:00").is_some());
+    assert!(Window::parse("23:30-00:30").is_some());
+    assert!(Window::parse("2:00 - 4:00").is_some());
+    assert!(Window::parse("00:00-23:59").is_some());
+    for bad in [
+        "",
+        "02:00",
+        "02:00-02:00",
+        "24:00-01:00",
+        "02:60-03:00",
+        "0200-0400",
+        "2:0-4:00",
+        "002:00-04:00",
+        "02:00-04:00-05:00",
+        "aa:bb-cc:dd",
+    ] {
+        assert_eq!(Window::parse(bad), None, "{:?}", bad);
+    }
+}
+
+// ---------- B2 ----------
+
+#[test]
+fn b2_contains() {
+    let w = window("02:00-04:00");
+    assert!(!w.contains_minute(119));
+    assert!(w.contains_minute(120));
+    assert!(w.contains_minute(239));
+    assert!(!w.contains_minute(240));
+
+    let wraps = window("23:00-01:00");
+    assert!(wraps.contains_minute(23 * 60));
+    assert!(wraps.contains_minute(0));
+    assert!(wraps.contains_minute(59));
+    assert!(!wraps.contains_minute(60));
+    assert!(!wraps.contains_minute(12 * 60));
+    assert!(!wraps.contains_minute(22 * 60 + 59));
+
+    assert!(w.contains_unix(at(3, 15)));
+    assert!(!w.contains_unix(at(4, 0)));
+    assert!(wraps.contains_unix(at(0, 30)));
+    assert!(!wraps.contains_unix(at(1, 0)));
+}
+
+proptest! {
+    #[test]
+    fn b2_wrapped_window_is_the_complement(start in 0u16..1440, end in 0u16..1440, minute in 0u16..1440) {
+        prop_assume!(start != end);
+        let fmt = |m: u16| format!("{:02}:{:02}", m / 60, m % 60);
+        let w = window(&format!("{}-{}", fmt(start), fmt(end)));
+        let rev = window(&format!("{}-{}", fmt(end), fmt(start)));
+        prop_assert_ne!(w.contains_minute(minute), rev.contains_minute(minute));
+    }
+}
+
+// ---------- B3 ----------
+
+#[test]
+fn b3_disabled_never_reboots() {
+    let s = Schedule::new(0, None);
+    assert!(!s.enabled());
+    for uptime in [0, HOUR_MS, 365 * DAY_MS] {
+        assert_eq!(s.decide(uptime, Some(at(3, 0)), None), Decision::NotDue);
+    }
+}
+
+#[test]
+fn b3_due_after_the_uptime() {
+    let s = Schedule::new(24, None);
+    assert!(s.enabled());
+    assert_eq!(s.decide(24 * HOUR_MS - 1, None, None), Decision::NotDue);
+    assert_eq!(s.decide(24 * HOUR_MS, None, None), Decision::Reboot);
+}
+
+#[test]
+fn b3_waits_for_a_quiet_door() {
+    let s = Schedule::new(24, None);
+    let now = 30 * HOUR_MS;
+    assert_eq!(s.decide(now, None, Some(now - 1_000)), Decision::Wait);
+    assert_eq!(s.decide(now, None, Some(now - IDLE_MS + 1)), Decision::Wait);
+    assert_eq!(s.decide(now, None, Some(now - IDLE_MS)), Decision::Reboot);
+    // Activity before the reboot was due counts the same.
+    assert_eq!(s.decide(now, None, Some(HOUR_MS)), Decision::Reboot);
+}
+
+#[test]
+fn b3_waits_for_the_window_once_the_time_is_known() {
+    let s = Schedule::new(24, Some(window("02:00-04:00")));
+    let due = 25 * HOUR_MS;
+    assert_eq!(s.decide(due, Some(at(13, 0)), None), Decision::Wait);
+    assert_eq!(s.decide(due, Some(at(1, 59)), None), Decision::Wait);
+    assert_eq!(s.decide(due, Some(at(2, 0)), None), Decision::Reboot);
+    assert_eq!(s.decide(due, Some(at(3, 59)), None), Decision::Reboot);
+    // Inside the window, the door still has to be quiet.
+    assert_eq!(s.decide(due, Some(at(3, 0)), Some(due - 1)), Decision::Wait);
+    // Unknown time of day: the window fails open.
+    assert_eq!(s.decide(due, None, None), Decision::Reboot);
+    // Not due yet, window or not.
+    assert_eq!(
+        s.decide(23 * HOUR_MS, Some(at(3, 0)), None),
+        Decision::NotDue
+    );
+}
+
+// ---------- B4 ----------
+
+#[test]
+fn b4_grace_ends_the_wait() {
+    let s = Schedule::new(24, Some(window("02:00-02:01")));
+    let due = 24 * HOUR_MS;
+    let last = due + GRACE_MS - 1;
+    assert_eq!(s.decide(last, Some(at(12, 0)), Some(last)), Decision::Wait);
+    let over = due + GRACE_MS;
+    assert_eq!(
+        s.decide(over, Some(at(12, 0)), Some(over)),
+        Decision::Reboot
+    );
+}
+
+proptest! {
+    #[test]
+    fn b4_decision_matches_the_rules(
+        hours in 0u64..=MAX_REBOOT_HOURS,
+        uptime in 0u64..800 * HOUR_MS,
+        unix in any::<Option<u64>>().prop_map(|u| u.map(|u| u % (100 * 365 * DAY_MS))),
+        since_activity in any::<Option<u64>>().prop_map(|a| a.map(|a| a % (2 * IDLE_MS))),
+        start in 0u16..1440,
+        len in 1u16..1440,
+    ) {
+        let w = Window::parse(&format!(
+            "{:02}:{:02}-{:02}:{:02}",
+            start / 60, start % 60, (start + len) % 1440 / 60, (start + len) % 60,
+        )).unwrap();
+        let s = Schedule::new(hours, Some(w));
+        let last_activity = since_activity.map(|a| uptime.saturating_sub(a));
+        let d = s.decide(uptime, unix, last_activity);
+
+        let after = hours * HOUR_MS;
+        if hours == 0 || uptime < after {
+            prop_assert_eq!(d, Decision::NotDue);
+        } else if uptime - after >= GRACE_MS {
+            prop_assert_eq!(d, Decision::Reboot);
+        } else {
+            let busy = last_activity.is_some_and(|at| uptime - at < IDLE_MS);
+            let outside = unix.is_some_and(|u| !w.contains_unix(u));
+            prop_assert_eq!(d == Decision::Wait, busy || outside);
+            prop_assert_eq!(d == Decision::Reboot, !busy && !outside);
+        }
+    }
+}
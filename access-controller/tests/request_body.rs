@@ -0,0 +1,129 @@
+//! Tests for admin server request body handling (invariants B1–B3).
+//!
+//!   B1: a declared length above the limit is rejected with 413, one at
+//!       the limit is accepted; a missing length is 411.
+//!   B2: a body split across any number of reads is assembled to exactly
+//!       the declared length; bytes beyond it are dropped.
+//!   B3: a body that ends early is reported as truncated.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test request_body
+
+#![cfg(feature = "sim")]
+
+use access_controller::request_body::{
+    check_length, parse_body_max, BodyAssembler, BodyError, BODY_MAX_CEILING, DEFAULT_BODY_MAX,
+};
+use proptest::prelude::*;
+
+#[test]
+fn oversize_rejected() {
+    let max = DEFAULT_BODY_MAX;
+    assert_eq!(
+        check_length(Some(max as u32 + 1), max),
+        Err(BodyError::TooLarge)
+    );
+    assert_eq!(BodyError::TooLarge.status(), "413 Payload Too Large");
+    assert_eq!(check_length(Some(u32::MAX), max), Err(BodyError::TooLarge));
+}
+
+#[test]
+fn exact_fit_accepted() {
+    let max = 64;
+    assert_eq!(check_length(Some(64), max), Ok(64));
+
+    let body = [b'x'; 64];
+    let mut a = BodyAssembler::new(64, &body[..10]);
+    assert_eq!(a.remaining(), 54);
+    a.push(&body[10..]);
+    assert!(a.is_complete());
+    assert_eq!(a.finish().unwrap(), body);
+}
+
+#[test]
+fn missing_length() {
+    assert_eq!(check_length(None, 512), Err(BodyError::LengthRequired));
+    assert_eq!(BodyError::LengthRequired.status(), "411 Length Required");
+}
+
+#[test]
+fn empty_body() {
+    assert_eq!(check_length(Some(0), 512), Ok(0));
+    assert_eq!(BodyAssembler::new(0, b"").finish().unwrap(), b"");
+}
+
+#[test]
+fn leftover_beyond_length_dropped() {
+    let a = BodyAssembler::new(5, b"a=b&cGET / HTTP/1.1\r\n");
+    assert_eq!(a.finish().unwrap(), b"a=b&c");
+}
+
+#[test]
+fn truncated_body() {
+    let mut a = BodyAssembler::new(10, b"abc");
+    a.push(b"de");
+    assert_eq!(a.remaining(), 5);
+    assert_eq!(a.finish(), Err(BodyError::Truncated));
+}
+
+#[test]
+fn body_max_knob() {
+    assert_eq!(parse_body_max("2048"), Some(2048));
+    assert_eq!(parse_body_max("8192"), Some(BODY_MAX_CEILING));
+    assert_eq!(parse_body_max("8193"), None);
+    assert_eq!(parse_body_max("0"), None);
+    assert_eq!(parse_body_max("-1"), None);
+    assert_eq!(parse_body_max("1k"), None);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// B1
+    #[test]
+    fn limit_is_inclusive(len in 0u32..4096, max in 0usize..4096) {
+        let r = check_length(Some(len), max);
+        if len as usize <= max {
+            prop_assert_eq!(r, Ok(len as usize));
+        } else {
+            prop_assert_eq!(r, Err(BodyError::TooLarge));
+        }
+    }
+
+    /// B2/B3
+    #[test]
+    fn partial_reads_assembled(
+        body in proptest::collection::vec(any::<u8>(), 0..512),
+        extra in proptest::collection::vec(any::<u8>(), 0..32),
+        cuts in proptest::collection::vec(any::<usize>(), 0..8),
+        short in 0usize..4,
+    ) {
+        let mut stream = body.clone();
+        stream.extend_from_slice(&extra);
+        // The peer sends everything, or stops `short` bytes into the body.
+        let sent = if short == 0 {
+            stream.len()
+        } else {
+            body.len().saturating_sub(short)
+        };
+        let mut cuts: Vec<usize> = cuts.iter().map(|c| c % (sent + 1)).collect();
+        cuts.push(sent);
+        cuts.sort_unstable();
+
+        let mut a = BodyAssembler::new(body.len(), &stream[..cuts[0]]);
+        for w in cuts.windows(2) {
+            a.push(&stream[w[0]..w[1]]);
+        }
+        if sent < body.len() {
+            prop_assert_eq!(a.finish(), Err(BodyError::Truncated));
+        } else {
+            prop_assert_eq!(a.finish().unwrap(), body);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Tests for assembling Wiegand frames from RMT captures (invariants M1–M3).
+//!
+//!   M1: a frame split across D0 and D1 captures merges back into the
+//!       sent bits, whatever each line's first-edge offset.
+//!   M2: leading idle spans and pulses the RMT split over several entries
+//!       do not add or shift bits; the end marker stops the walk.
+//!   M3: nothing captured, simultaneous pulses on both lines and frames
+//!       longer than `MAX_BITS` yield `None`.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test rmt_frame
+
+#![cfg(feature = "sim")]
+
+use access_controller::decode::{decode_frame, encode_26, encode_34};
+use access_controller::rmt_frame::{frame_bits, LineCapture, Span, MAX_BITS};
+use proptest::prelude::*;
+
+const LOW: bool = true;
+const HIGH: bool = false;
+
+fn span(low: bool, ticks: u16) -> Span {
+    Span { low, ticks }
+}
+
+/// Render `count` bits of `bits` (MSB first) the way the two RMT channels
+/// see them: a `width`-tick low pulse every `period` ticks on the bit's
+/// line, each capture starting at that line's first edge. Returns the
+/// spans per line and each line's first-edge time.
+fn capture(bits: u64, count: u32, start_us: u64, period: u16, width: u16) -> [(Vec<Span>, Option<u64>); 2] {
+    let mut lines: [(Vec<Span>, Option<u64>); 2] = [(Vec::new(), None), (Vec::new(), None)];
+    let mut last: [u64; 2] = [0; 2];
+    for i in 0..count {
+        let bit = (bits.checked_shr(count - 1 - i).unwrap_or(0) & 1) as usize;
+        let at = start_us + u64::from(i) * u64::from(period);
+        let (spans, first) = &mut lines[bit];
+        if first.is_none() {
+            *first = Some(at);
+        } else {
+            // Gaps past one entry's reach are split, as the RMT does.
+            let mut gap = at - last[bit];
+            while gap > 0 {
+                let ticks = gap.min(u64::from(u16::MAX));
+                spans.push(span(HIGH, ticks as u16));
+                gap -= ticks;
+            }
+        }
+        spans.push(span(LOW, width));
+        last[bit] = at + u64::from(width);
+    }
+    for (spans, first) in &mut lines {
+        if first.is_some() {
+            spans.push(span(HIGH, 0));
+        }
+    }
+    lines
+}
+
+fn line((spans, first): &(Vec<Span>, Option<u64>)) -> Option<LineCapture<'_>> {
+    first.map(|first_edge_us| LineCapture { first_edge_us, spans: spans.as_slice() })
+}
+
+fn merge(lines: &[(Vec<Span>, Option<u64>); 2]) -> Option<(u64, u32)> {
+    let (d0, d1) = (line(&lines[0]), line(&lines[1]));
+    frame_bits(d0.as_ref(), d1.as_ref(), 1)
+}
+
+// ---------------------------------------------------------------------------
+// M1: merge
+// ---------------------------------------------------------------------------
+
+#[test]
+fn h10301_frame_round_trips() {
+    let bits = encode_26(123, 45678);
+    let lines = capture(bits, 26, 1_000_000, 2000, 50);
+    let (merged, count) = merge(&lines).unwrap();
+    assert_eq!((merged, count), (bits, 26));
+    let read = decode_frame(merged, count).unwrap();
+    assert_eq!((read.facility, read.card), (123, 45678));
+}
+
+#[test]
+fn single_line_frame_merges() {
+    // Only D1 pulsed: all ones.
+    let lines = capture(0b111, 3, 10, 1000, 80);
+    assert!(lines[0].1.is_none());
+    assert_eq!(merge(&lines), Some((0b111, 3)));
+}
+
+proptest! {
+    /// M1: any 26/34-bit frame, at any bit period and pulse width a reader
+    /// uses, merges back into the sent bits.
+    #[test]
+    fn frames_round_trip(
+        facility in 0u32..256,
+        card in 0u32..65536,
+        long in any::<bool>(),
+        start in 0u64..1_000_000_000,
+        period in 200u16..2_900,
+        width in 20u16..100,
+    ) {
+        let (bits, count) = if long { (encode_34(facility, card), 34) } else { (encode_26(facility, card), 26) };
+        let lines = capture(bits, count, start, period, width);
+        prop_assert_eq!(merge(&lines), Some((bits, count)));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// M2: span handling
+// ---------------------------------------------------------------------------
+
+#[test]
+fn leading_idle_is_skipped() {
+    let d0_spans = [span(HIGH, 5000), span(LOW, 50), span(HIGH, 0)];
+    let d1_spans = [span(LOW, 50), span(HIGH, 0)];
+    let d0 = LineCapture { first_edge_us: 1000, spans: &d0_spans };
+    let d1 = LineCapture { first_edge_us: 3000, spans: &d1_spans };
+    assert_eq!(frame_bits(Some(&d0), Some(&d1), 1), Some((0b01, 2)));
+}
+
+#[test]
+fn split_low_is_one_pulse() {
+    let spans = [span(LOW, 30), span(LOW, 30), span(HIGH, 1940), span(LOW, 60), span(HIGH, 0)];
+    let d1 = LineCapture { first_edge_us: 0, spans: &spans };
+    assert_eq!(frame_bits(None, Some(&d1), 1), Some((0b11, 2)));
+}
+
+#[test]
+fn end_marker_stops_the_walk() {
+    // Stale entries after the marker from an earlier, longer frame.
+    let spans = [span(LOW, 50), span(HIGH, 0), span(LOW, 50), span(HIGH, 1950)];
+    let d0 = LineCapture { first_edge_us: 0, spans: &spans };
+    assert_eq!(frame_bits(Some(&d0), None, 1), Some((0, 1)));
+}
+
+#[test]
+fn ticks_scale_by_tick_length() {
+    // 2 µs ticks: D0's second pulse lands at 4000 µs, after D1's at 3000.
+    let d0_spans = [span(LOW, 25), span(HIGH, 1975), span(LOW, 25), span(HIGH, 0)];
+    let d1_spans = [span(LOW, 25), span(HIGH, 0)];
+    let d0 = LineCapture { first_edge_us: 0, spans: &d0_spans };
+    let d1 = LineCapture { first_edge_us: 3000, spans: &d1_spans };
+    assert_eq!(frame_bits(Some(&d0), Some(&d1), 2), Some((0b010, 3)));
+}
+
+// ---------------------------------------------------------------------------
+// M3: rejected captures
+// ---------------------------------------------------------------------------
+
+#[test]
+fn nothing_captured_is_none() {
+    assert_eq!(frame_bits(None, None, 1), None);
+    let idle = [span(HIGH, 0)];
+    let d0 = LineCapture { first_edge_us: 0, spans: &idle };
+    assert_eq!(frame_bits(Some(&d0), None, 1), None);
+}
+
+#[test]
+fn simultaneous_pulses_are_rejected() {
+    let spans = [span(LOW, 50), span(HIGH, 0)];
+    let d0 = LineCapture { first_edge_us: 500, spans: &spans };
+    let d1 = LineCapture { first_edge_us: 500, spans: &spans };
+    assert_eq!(frame_bits(Some(&d0), Some(&d1), 1), None);
+}
+
+#[test]
+fn overlong_frame_is_rejected() {
+    let at_max = capture(0, MAX_BITS, 0, 500, 50);
+    assert_eq!(merge(&at_max), Some((0, MAX_BITS)));
+    let over = capture(0, MAX_BITS + 1, 0, 500, 50);
+    assert_eq!(merge(&over), None);
+}
@@ -0,0 +1,167 @@
+//! Tests for the random number helpers (invariants R1–R5).
+//!
+//!   R1: a seeded `SplitMix64` is reproducible and matches the reference
+//!       generator.
+//!   R2: `below(n)` is in `0..n`, reaches every value, and redraws the
+//!       words that would bias it.
+//!   R3: `range(lo, hi)` is in `lo..hi`, and `lo` when empty.
+//!   R4: `jitter(ms, p)` stays within `p` per cent of `ms`, reaching
+//!       both ends.
+//!   R5: `fill` and `next_u64` use every word drawn, in order; a source
+//!       repeating one word is reported as stuck.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test rng
+
+#![cfg(feature = "sim")]
+
+use std::collections::VecDeque;
+
+use access_controller::rng::{self, RandomSource, SplitMix64};
+use proptest::prelude::*;
+
+/// Replays fixed words, to steer the helpers down specific paths.
+struct Script(VecDeque<u32>);
+
+impl Script {
+    fn new(words: &[u32]) -> Self {
+        Self(words.iter().copied().collect())
+    }
+}
+
+impl RandomSource for Script {
+    fn next_u32(&mut self) -> u32 {
+        self.0.pop_front().expect("script ran out")
+    }
+}
+
+// ---------- R1 ----------
+
+#[test]
+fn r1_splitmix_reference_values() {
+    let mut r = SplitMix64::new(0);
+    assert_eq!(r.next_u64(), 0xE220_A839_7B1D_CDAF);
+    assert_eq!(r.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+    assert_eq!(r.next_u64(), 0x06C4_5D18_8009_454F);
+    assert_eq!(SplitMix64::new(0).next_u32(), 0xE220_A839);
+}
+
+#[test]
+fn r1_same_seed_same_stream() {
+    let draw = |seed| {
+        let mut r = SplitMix64::new(seed);
+        (0..16).map(|_| r.below(1000)).collect::<Vec<_>>()
+    };
+    assert_eq!(draw(42), draw(42));
+    assert_ne!(draw(42), draw(43));
+    let mac = [0x24, 0x6F, 0x28, 0x01, 0x02, 0x03];
+    assert_eq!(rng::mac_seed(mac), 0x0302_0128_6F24);
+}
+
+// ---------- R2 / R3 / R4 ----------
+
+#[test]
+fn r2_below_redraws_the_biased_tail() {
+    // For bound 3, u64::MAX itself is the only biased word (2^64 = 1 mod 3).
+    let mut r = Script::new(&[u32::MAX, u32::MAX, 0, 7]);
+    assert_eq!(r.below(3), 7 % 3);
+    assert!(r.0.is_empty());
+    // Any word is fine for a power of two.
+    let mut r = Script::new(&[u32::MAX, u32::MAX]);
+    assert_eq!(r.below(1 << 8), 0xFF);
+}
+
+#[test]
+fn r2_below_degenerate_bounds() {
+    let mut r = SplitMix64::new(1);
+    assert_eq!(r.below(0), 0);
+    assert_eq!(r.below(1), 0);
+    assert!(r.below(u64::MAX) < u64::MAX);
+}
+
+#[test]
+fn r2_below_reaches_every_value() {
+    let mut r = SplitMix64::new(7);
+    let mut counts = [0u32; 10];
+    for _ in 0..10_000 {
+        counts[r.below(10) as usize] += 1;
+    }
+    // Expect 1000 each; 800 is over six standard deviations out.
+    assert!(counts.iter().all(|&c| c > 800), "{:?}", counts);
+}
+
+#[test]
+fn r3_empty_range_is_lo() {
+    let mut r = SplitMix64::new(3);
+    assert_eq!(r.range(5, 5), 5);
+    assert_eq!(r.range(9, 2), 9);
+}
+
+#[test]
+fn r4_jitter_reaches_both_ends() {
+    let mut r = SplitMix64::new(11);
+    let got: Vec<u64> = (0..1_000).map(|_| r.jitter(10, 20)).collect();
+    assert!(got.iter().all(|&ms| (8..=12).contains(&ms)));
+    assert!(got.contains(&8) && got.contains(&12));
+    assert_eq!(r.jitter(10_000, 0), 10_000);
+    assert!(r.jitter(10, 255) <= 20);
+    // No overflow at the top of the range.
+    let _ = r.jitter(u64::MAX, 100);
+    assert!(r.jitter(u64::MAX, 1) >= u64::MAX - u64::MAX / 100);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn r2_r3_r4_stay_in_bounds(
+        seed in any::<u64>(),
+        bound in 1u64..,
+        lo in any::<u64>(),
+        width in 0u64..1 << 40,
+        ms in 0u64..1 << 40,
+        percent in 0u8..=100,
+    ) {
+        let mut r = SplitMix64::new(seed);
+        prop_assert!(r.below(bound) < bound);
+
+        let hi = lo.saturating_add(width);
+        let x = r.range(lo, hi);
+        prop_assert!(x == lo || (lo..hi).contains(&x));
+
+        let spread = ms * u64::from(percent) / 100;
+        let j = r.jitter(ms, percent);
+        prop_assert!(ms - spread <= j && j <= ms + spread);
+    }
+}
+
+// ---------- R5 ----------
+
+#[test]
+fn r5_fill_uses_words_in_order() {
+    let mut r = Script::new(&[0x0403_0201, 0x0807_0605, 0xFFFF_0A09]);
+    let mut buf = [0u8; 10];
+    r.fill(&mut buf);
+    assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+    assert!(r.0.is_empty());
+
+    let mut r = Script::new(&[0x1111_2222, 0x3333_4444]);
+    assert_eq!(r.next_u64(), 0x1111_2222_3333_4444);
+}
+
+#[test]
+fn r5_stuck_source() {
+    assert!(rng::looks_stuck(&[0, 0, 0, 0]));
+    assert!(rng::looks_stuck(&[0xDEAD_BEEF; 4]));
+    assert!(!rng::looks_stuck(&[0, 0, 0, 1]));
+    let mut r = SplitMix64::new(0);
+    let words = [r.next_u32(), r.next_u32(), r.next_u32(), r.next_u32()];
+    assert!(!rng::looks_stuck(&words));
+}
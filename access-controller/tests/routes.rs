@@ -0,0 +1,342 @@
+//! Tests for admin HTTP routing (invariants R1–R8).
+//!
+//!   R1: `GET /ping` routes to the liveness check in every device mode,
+//!       ahead of the onboarding redirect, and nothing else does.
+//!   R2: the liveness response is the fixed `pong` body.
+//!   R3: the existing routes keep their method and mode rules.
+//!   R4: `HEAD` routes exactly like `GET` and is answered without a body;
+//!       every other method keeps its body.
+//!   R5: an unknown path is 404 for any method other than an onboarding
+//!       `GET`; a known path is 405 exactly for the methods its `Allow`
+//!       value leaves out.
+//!   R6: a request log line names method, path and client address, and
+//!       control characters in the path cannot break the line.
+//!   R7: each routed request bumps exactly its own route's counter, and
+//!       `/metrics` renders every counter.
+//!   R8: `POST /sync` routes in every mode and answers 200 for a sync
+//!       that reached a host, 502 for one that failed, and 504 when none
+//!       finished in time.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test routes
+
+#![cfg(feature = "sim")]
+
+use access_controller::routes::{self, RequestLog, Route, RouteCounts, PING_BODY};
+use proptest::prelude::*;
+
+// ---------- R1 ----------
+
+#[test]
+fn r1_ping_in_every_mode() {
+    for onboarding in [false, true] {
+        assert_eq!(routes::route("GET", "/ping", onboarding), Route::Ping);
+        assert_eq!(
+            routes::route("POST", "/ping", onboarding),
+            Route::MethodNotAllowed("GET, HEAD")
+        );
+        assert_ne!(routes::route("GET", "/ping/", onboarding), Route::Ping);
+        assert_ne!(routes::route("GET", "/PING", onboarding), Route::Ping);
+    }
+}
+
+// ---------- R2 ----------
+
+#[test]
+fn r2_ping_body() {
+    assert_eq!(PING_BODY, b"pong\n");
+}
+
+// ---------- R3 ----------
+
+#[test]
+fn r3_route_table() {
+    use Route::*;
+    // (method, path, onboarding) -> route
+    let table = [
+        (("GET", "/", false), Status),
+        (("GET", "/", true), RedirectToConfig),
+        (("GET", "/status", true), Status),
+        (("GET", "/metrics", true), Metrics),
+        (("GET", "/config", false), ConfigPage),
+        (("POST", "/config", true), ConfigPost),
+        (("GET", "/fobs", false), FobsPage),
+        (("POST", "/fobs", false), FobAdd),
+        (("POST", "/fobs/delete", false), FobDelete),
+        (("GET", "/swipes", false), Swipes),
+        (("GET", "/diag/lastsync", false), LastSync),
+        (("GET", "/diag/events", true), DiagEvents),
+        (("GET", "/generate_204", false), RedirectToConfig),
+        (("GET", "/hotspot-detect.html", true), RedirectToConfig),
+        (("POST", "/ota", false), OtaUpload),
+        (("POST", "/ota/rollback", false), OtaRollback),
+        (("POST", "/unlock", true), Unlock),
+        (("POST", "/sync", false), Sync),
+        (("GET", "/check", false), Check),
+        (("GET", "/counters", true), Counters),
+        (("POST", "/counters/reset", false), CountersReset),
+        (("GET", "/nope", false), NotFound),
+        (("GET", "/nope", true), RedirectToConfig),
+        (
+            ("PUT", "/config", false),
+            MethodNotAllowed("GET, HEAD, POST"),
+        ),
+        (("DELETE", "/nope", true), NotFound),
+    ];
+    for ((method, path, onboarding), want) in table {
+        assert_eq!(
+            routes::route(method, path, onboarding),
+            want,
+            "{} {} onboarding={}",
+            method,
+            path,
+            onboarding
+        );
+    }
+}
+
+// ---------- R4 ----------
+
+#[test]
+fn r4_head_routes_like_get() {
+    let paths = [
+        "/",
+        "/status",
+        "/ping",
+        "/config",
+        "/fobs",
+        "/swipes",
+        "/diag/lastsync",
+        "/diag/events",
+        "/generate_204",
+        "/nope",
+    ];
+    for onboarding in [false, true] {
+        for path in paths {
+            assert_eq!(
+                routes::route("HEAD", path, onboarding),
+                routes::route("GET", path, onboarding),
+                "HEAD {} onboarding={}",
+                path,
+                onboarding
+            );
+        }
+    }
+    assert_eq!(routes::route("HEAD", "/", false), Route::Status);
+}
+
+#[test]
+fn r4_head_has_no_body() {
+    assert!(!routes::has_body("HEAD"));
+    for method in ["GET", "POST", "PUT", "head", ""] {
+        assert!(routes::has_body(method), "{}", method);
+    }
+}
+
+// ---------- R5 ----------
+
+const PATHS: [&str; 20] = [
+    "/",
+    "/status",
+    "/metrics",
+    "/ping",
+    "/config",
+    "/fobs",
+    "/fobs/delete",
+    "/swipes",
+    "/diag/lastsync",
+    "/diag/events",
+    "/ota",
+    "/ota/rollback",
+    "/unlock",
+    "/sync",
+    "/check",
+    "/counters",
+    "/counters/reset",
+    "/generate_204",
+    "/ncsi.txt",
+    "/success.txt",
+];
+
+const METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "DELETE", "PATCH", "OPTIONS"];
+
+#[test]
+fn r5_delete_unlock_is_405_with_allow() {
+    for onboarding in [false, true] {
+        assert_eq!(
+            routes::route("DELETE", "/unlock", onboarding),
+            Route::MethodNotAllowed("POST")
+        );
+        assert_eq!(
+            routes::route("GET", "/unlock", onboarding),
+            Route::MethodNotAllowed("POST")
+        );
+    }
+    assert_eq!(routes::allowed("/unlock"), Some("POST"));
+}
+
+#[test]
+fn r5_allow_agrees_with_routing() {
+    for onboarding in [false, true] {
+        for path in PATHS {
+            let allow = routes::allowed(path).unwrap();
+            for method in METHODS {
+                let listed = allow.split(", ").any(|m| m == method);
+                let got = routes::route(method, path, onboarding);
+                assert_eq!(
+                    got == Route::MethodNotAllowed(allow),
+                    !listed,
+                    "{} {} onboarding={} -> {:?}",
+                    method,
+                    path,
+                    onboarding,
+                    got
+                );
+                assert_ne!(got, Route::NotFound, "{} {}", method, path);
+            }
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn r5_unknown_path_is_404(
+        path in "/[a-z0-9_.]{0,16}",
+        method in prop::sample::select(&METHODS[..]),
+        onboarding in any::<bool>(),
+    ) {
+        prop_assume!(routes::allowed(&path).is_none());
+        let want = if onboarding && (method == "GET" || method == "HEAD") {
+            Route::RedirectToConfig
+        } else {
+            Route::NotFound
+        };
+        prop_assert_eq!(routes::route(method, &path, onboarding), want);
+    }
+}
+
+// ---------- R6 ----------
+
+#[test]
+fn r6_log_line() {
+    let line = RequestLog {
+        method: "POST",
+        path: "/unlock",
+        peer: Some(([192, 168, 1, 20], 51234)),
+    };
+    assert_eq!(line.to_string(), "POST /unlock from 192.168.1.20:51234");
+    let line = RequestLog {
+        method: "GET",
+        path: "/status",
+        peer: None,
+    };
+    assert_eq!(line.to_string(), "GET /status from unknown");
+}
+
+#[test]
+fn r6_control_characters_masked() {
+    let line = RequestLog {
+        method: "GET",
+        path: "/x\rhttp: POST /unlock\x1b[2K",
+        peer: Some(([10, 0, 0, 1], 80)),
+    };
+    assert_eq!(
+        line.to_string(),
+        "GET /x?http: POST /unlock?[2K from 10.0.0.1:80"
+    );
+}
+
+// ---------- R7 ----------
+
+#[test]
+fn r7_counts_per_route() {
+    let counts = RouteCounts::new();
+    let requests = [
+        ("GET", "/status"),
+        ("HEAD", "/"),
+        ("POST", "/unlock"),
+        ("DELETE", "/unlock"),
+        ("GET", "/nope"),
+        ("GET", "/status"),
+    ];
+    for (method, path) in requests {
+        counts.record(routes::route(method, path, false));
+    }
+    counts.record_malformed();
+    assert_eq!(counts.get(Route::Status), 3);
+    assert_eq!(counts.get(Route::Unlock), 1);
+    assert_eq!(counts.get(Route::MethodNotAllowed("GET, HEAD")), 1);
+    assert_eq!(counts.get(Route::NotFound), 1);
+    assert_eq!(counts.get(Route::Ping), 0);
+    assert_eq!(counts.malformed(), 1);
+
+    let mut out = String::new();
+    counts.write_metrics(&mut out).unwrap();
+    assert!(out.starts_with("# TYPE conway_http_requests_total counter\n"));
+    assert!(out.contains("conway_http_requests_total{route=\"status\"} 3\n"));
+    assert!(out.contains("conway_http_requests_total{route=\"unlock\"} 1\n"));
+    assert!(out.contains("conway_http_requests_total{route=\"method_not_allowed\"} 1\n"));
+    assert!(out.contains("conway_http_requests_total{route=\"malformed\"} 1\n"));
+    assert!(out.contains("conway_http_requests_total{route=\"ping\"} 0\n"));
+    assert_eq!(out.lines().count(), 1 + 22);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn r6_r7_any_request(
+        method in prop::sample::select(&METHODS[..]),
+        path in "\\PC{0,24}",
+        octets in any::<[u8; 4]>(),
+        port in any::<u16>(),
+    ) {
+        let line = RequestLog { method, path: &path, peer: Some((octets, port)) }.to_string();
+        prop_assert!(!line.chars().any(char::is_control));
+        prop_assert!(line.starts_with(method));
+        let peer = format!(" from {}.{}.{}.{}:{}", octets[0], octets[1], octets[2], octets[3], port);
+        prop_assert!(line.ends_with(&peer));
+
+        let counts = RouteCounts::new();
+        let route = routes::route(method, &path, false);
+        counts.record(route);
+        prop_assert_eq!(counts.get(route), 1);
+        let mut out = String::new();
+        counts.write_metrics(&mut out).unwrap();
+        prop_assert_eq!(out.lines().filter(|l| l.ends_with(" 1")).count(), 1);
+    }
+}
+
+// ---------- R8 ----------
+
+#[test]
+fn r8_sync_routes_in_every_mode() {
+    for onboarding in [false, true] {
+        assert_eq!(routes::route("POST", "/sync", onboarding), Route::Sync);
+        assert_eq!(
+            routes::route("GET", "/sync", onboarding),
+            Route::MethodNotAllowed("POST")
+        );
+    }
+}
+
+#[test]
+fn r8_sync_reply_reports_outcome() {
+    assert_eq!(routes::sync_reply(Some(true)), ("200 OK", &b"ok: synced\n"[..]));
+    assert_eq!(routes::sync_reply(Some(false)).0, "502 Bad Gateway");
+    assert_eq!(routes::sync_reply(None).0, "504 Gateway Timeout");
+}
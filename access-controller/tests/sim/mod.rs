@@ -0,0 +1,131 @@
+//! The simulation harness for `AccessCore`, shared by the access_core,
+//! passback and authz tests. Each test crate uses part of it.
+
+#![allow(dead_code)]
+
+use access_controller::core::{
+    AccessCore, CardRead, Effect, FailPolicy, Input, MatchOrder, Reader,
+};
+use access_controller::decode::{ByteOrder, CredentialFormat, WiegandRead};
+
+/// Convenience wrapper around `AccessCore` that tracks virtual time and the
+/// fob cache and records the full history of (time, input, effects).
+pub struct Sim {
+    pub core: AccessCore,
+    pub fobs: Vec<u32>,
+    pub local_fobs: Vec<u32>,
+    pub conway_enabled: bool,
+    pub list_loaded: bool,
+    pub now_ms: u64,
+    pub history: Vec<(u64, Input, Vec<Effect>)>,
+}
+
+impl Sim {
+    pub fn new() -> Self {
+        Self {
+            core: AccessCore::new(),
+            fobs: Vec::new(),
+            local_fobs: Vec::new(),
+            conway_enabled: true,
+            list_loaded: true,
+            now_ms: 0,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn with_match_order(order: MatchOrder) -> Self {
+        let mut s = Self::new();
+        s.core = AccessCore::with_match_order(order);
+        s
+    }
+
+    /// Construct a sim that booted with no Conway list loaded yet.
+    pub fn with_fail_policy(policy: FailPolicy) -> Self {
+        let mut s = Self::new();
+        s.core = AccessCore::new().with_fail_policy(policy);
+        s.list_loaded = false;
+        s
+    }
+
+    /// Turn on the anti-passback window, on top of whatever the sim was
+    /// built with.
+    pub fn with_passback_window(self, window_ms: u64) -> Self {
+        Self {
+            core: self.core.with_passback_window(window_ms),
+            ..self
+        }
+    }
+
+    /// Turn on the online check for misses, answered within `timeout_ms`.
+    pub fn with_online_check(self, timeout_ms: u64) -> Self {
+        Self {
+            core: self.core.with_online_check(timeout_ms),
+            ..self
+        }
+    }
+
+    /// Construct a sim for standalone-mode tests: no Conway host configured.
+    pub fn new_standalone() -> Self {
+        let mut s = Self::new();
+        s.conway_enabled = false;
+        s
+    }
+
+    pub fn add_fob(&mut self, f: u32) {
+        if !self.fobs.contains(&f) {
+            self.fobs.push(f);
+        }
+    }
+
+    pub fn remove_fob(&mut self, f: u32) {
+        self.fobs.retain(|&x| x != f);
+    }
+
+    pub fn add_local_fob(&mut self, f: u32) {
+        if !self.local_fobs.contains(&f) {
+            self.local_fobs.push(f);
+        }
+    }
+
+    pub fn tick(&mut self, dt_ms: u64) {
+        self.now_ms = self.now_ms.saturating_add(dt_ms);
+    }
+
+    pub fn input(&mut self, i: Input) -> Vec<Effect> {
+        let eff = self.core.step(
+            self.now_ms,
+            &self.local_fobs,
+            &self.fobs,
+            self.conway_enabled,
+            self.list_loaded,
+            i,
+        );
+        let v: Vec<Effect> = eff.iter().copied().collect();
+        self.history.push((self.now_ms, i, v.clone()));
+        v
+    }
+
+    /// Move the clock to `now_ms` and apply `i`, for tests written
+    /// against absolute times.
+    pub fn input_at(&mut self, now_ms: u64, i: Input) -> Vec<Effect> {
+        self.now_ms = now_ms;
+        self.input(i)
+    }
+
+    pub fn card(&mut self, fob: u32, nfc: u32) -> Vec<Effect> {
+        self.card_at(Reader::Wiegand, fob, nfc)
+    }
+
+    pub fn card_at(&mut self, reader: Reader, fob: u32, nfc: u32) -> Vec<Effect> {
+        self.input(Input::Card(CardRead { fob, nfc, reader }))
+    }
+
+    /// Present `read` the way the firmware does under `hint`.
+    pub fn read(&mut self, read: &WiegandRead, hint: Option<CredentialFormat>) -> Vec<Effect> {
+        self.input(Input::Card(CardRead::from_read(read, hint, ByteOrder::Lsb)))
+    }
+
+    pub fn sync(&mut self) -> Vec<Effect> {
+        self.input(Input::SyncComplete)
+    }
+}
@@ -0,0 +1,83 @@
+//! Tests for the network stack's socket budget (invariants K1–K3).
+//!
+//!   K1: each mode and service set needs the sockets listed in the table
+//!       below; changing a count means revisiting this table.
+//!   K2: the worst case is taken over every runtime combination.
+//!   K3: every build option fits `STACK_SOCKETS`, with an online check
+//!       at every reader too.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test sockets
+
+#![cfg(feature = "sim")]
+
+use access_controller::core::MAX_READERS;
+use access_controller::sockets::{self, Services, STACK_SOCKETS};
+
+fn services(station: bool, sync: bool, push: bool, online_checks: usize) -> Services {
+    Services {
+        station,
+        sync,
+        push,
+        online_checks,
+    }
+}
+
+#[test]
+fn k1_sockets_per_service_set() {
+    // (station, sync, push, online checks) -> sockets
+    let table = [
+        // Onboarding: DNS client, HTTP, captive DHCP and DNS servers.
+        ((false, false, false, 0), 4),
+        ((false, true, true, MAX_READERS), 4),
+        // Standalone station: DNS client, DHCP client, HTTP.
+        ((true, false, false, 0), 3),
+        ((true, false, true, MAX_READERS), 3),
+        // With Conway: + sync, + push, + a checker per reader.
+        ((true, true, false, 0), 4),
+        ((true, true, true, 0), 5),
+        ((true, true, false, 1), 5),
+        ((true, true, true, MAX_READERS), 7),
+    ];
+    for ((station, sync, push, online_checks), want) in table {
+        assert_eq!(
+            sockets::sockets(services(station, sync, push, online_checks)),
+            want,
+            "station={} sync={} push={} online_checks={}",
+            station,
+            sync,
+            push,
+            online_checks
+        );
+    }
+}
+
+#[test]
+fn k2_required_is_the_worst_case() {
+    for push in [false, true] {
+        for online_checks in 0..=MAX_READERS {
+            let worst = [(false, false), (false, true), (true, false), (true, true)]
+                .into_iter()
+                .map(|(station, sync)| {
+                    sockets::sockets(services(station, sync, push, online_checks))
+                })
+                .max()
+                .unwrap();
+            assert_eq!(sockets::required(push, online_checks), worst);
+        }
+    }
+    assert_eq!(sockets::required(false, 0), 4);
+    assert_eq!(sockets::required(true, 0), 5);
+    assert_eq!(sockets::required(true, MAX_READERS), 7);
+}
+
+#[test]
+fn k3_every_build_fits() {
+    for online_checks in 0..=MAX_READERS {
+        assert!(sockets::fits(false, online_checks));
+        assert!(sockets::fits(true, online_checks));
+    }
+    assert!(sockets::required(true, MAX_READERS) <= STACK_SOCKETS);
+}
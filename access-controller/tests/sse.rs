@@ -0,0 +1,130 @@
+//! Tests for the SSE push parser (invariants E1–E3).
+//!
+//!   E1: the events seen are independent of how the stream is split
+//!       into chunks, including CRLF pairs split across chunks.
+//!   E2: `data` lines accumulate until a blank line; comments, unknown
+//!       fields and data-less blocks dispatch nothing.
+//!   E3: overlong lines are dropped without disturbing later events.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test sse
+
+#![cfg(feature = "sim")]
+
+use access_controller::sse::{stream_accepted, write_request, SseParser, MAX_LINE};
+use proptest::prelude::*;
+
+fn events(chunks: &[&[u8]]) -> Vec<(String, String)> {
+    let mut p = SseParser::new();
+    let mut out = Vec::new();
+    for c in chunks {
+        p.feed(c, |e| out.push((e.event.to_string(), e.data.to_string())));
+    }
+    out
+}
+
+fn msg(data: &str) -> (String, String) {
+    ("message".to_string(), data.to_string())
+}
+
+const STREAM: &[u8] = b": keepalive\r\n\r\n\
+data: changed\r\n\r\n\
+event: fobs\ndata:a\ndata: b\n\n\
+id: 7\rretry: 1000\rdata:  two spaces\r\r\
+data\n\n";
+
+fn expected() -> Vec<(String, String)> {
+    vec![
+        msg("changed"),
+        ("fobs".to_string(), "a\nb".to_string()),
+        msg(" two spaces"),
+        msg(""),
+    ]
+}
+
+#[test]
+fn whole_stream() {
+    // E2
+    assert_eq!(events(&[STREAM]), expected());
+}
+
+#[test]
+fn byte_at_a_time() {
+    // E1
+    let chunks: Vec<&[u8]> = STREAM.chunks(1).collect();
+    assert_eq!(events(&chunks), expected());
+}
+
+#[test]
+fn crlf_split_across_chunks() {
+    // E1: the LF of a split CRLF must not end a second (blank) line.
+    assert_eq!(
+        events(&[b"data: x\r", b"\ndata: y\r", b"\n\r", b"\n"]),
+        vec![msg("x\ny")]
+    );
+}
+
+#[test]
+fn no_data_no_event() {
+    // E2
+    assert!(events(&[b": ping\n\nevent: x\n\nid: 1\n\nfoo: bar\n\n"]).is_empty());
+    // Incomplete event is held until its blank line.
+    let mut p = SseParser::new();
+    let mut n = 0;
+    p.feed(b"data: changed\n", |_| n += 1);
+    assert_eq!(n, 0);
+    p.feed(b"\n", |_| n += 1);
+    assert_eq!(n, 1);
+}
+
+#[test]
+fn overlong_line_dropped() {
+    // E3
+    let long = format!("data: {}\n", "x".repeat(MAX_LINE * 2));
+    let stream = format!("{long}\ndata: ok\n\n");
+    assert_eq!(events(&[stream.as_bytes()]), vec![msg("ok")]);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// E1: arbitrary split points.
+    #[test]
+    fn any_chunking(cuts in prop::collection::vec(0..STREAM.len(), 0..12)) {
+        let mut cuts = cuts;
+        cuts.sort_unstable();
+        let mut chunks = Vec::new();
+        let mut at = 0;
+        for c in cuts {
+            chunks.push(&STREAM[at..c]);
+            at = c;
+        }
+        chunks.push(&STREAM[at..]);
+        prop_assert_eq!(events(&chunks), expected());
+    }
+}
+
+#[test]
+fn request_and_response_head() {
+    let mut req = String::new();
+    write_request(&mut req, "/api/events/stream", "10.0.0.1").unwrap();
+    assert_eq!(
+        req,
+        "GET /api/events/stream HTTP/1.0\r\nHost: 10.0.0.1\r\nAccept: text/event-stream\r\n\r\n"
+    );
+    assert!(stream_accepted(
+        "HTTP/1.0 200 OK\r\nContent-Type: text/event-stream; charset=utf-8\r\n"
+    ));
+    assert!(!stream_accepted(
+        "HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n"
+    ));
+    assert!(!stream_accepted(
+        "HTTP/1.0 404 Not Found\r\nContent-Type: text/event-stream\r\n"
+    ));
+}
@@ -0,0 +1,87 @@
+//! Tests for the stack high-water mark (invariants W1–W3).
+//!
+//!   W1: a stack still fully painted is fully unused; one with nothing
+//!       painted, or touched at the bottom, has no headroom.
+//!   W2: on a painted buffer that frames then grew into, the scan counts
+//!       exactly the paint below the deepest frame, whatever was written
+//!       above it (including stray paint values).
+//!   W3: the low-headroom warning fires below `LOW_STACK_BYTES` only.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test stack_watermark
+
+#![cfg(feature = "sim")]
+
+use access_controller::stack_watermark::{self, LOW_STACK_BYTES, PAINT};
+use proptest::prelude::*;
+
+/// A painted `words`-word stack whose frames reached down to index
+/// `deepest` (stack grows towards index 0), filled with `frames` above.
+fn grown(words: usize, deepest: usize, frames: &[u32]) -> Vec<u32> {
+    let mut stack = vec![PAINT; words];
+    for (i, slot) in stack[deepest..].iter_mut().enumerate() {
+        *slot = frames[i % frames.len()];
+    }
+    stack
+}
+
+// ---------- W1 ----------
+
+#[test]
+fn w1_untouched_and_exhausted() {
+    let stack = [PAINT; 64];
+    assert_eq!(stack_watermark::unused_words(stack), 64);
+    assert_eq!(stack_watermark::unused_bytes(stack), 256);
+
+    assert_eq!(stack_watermark::unused_words([0u32; 64]), 0);
+    assert_eq!(stack_watermark::unused_words(std::iter::empty()), 0);
+
+    let mut bottom_touched = [PAINT; 64];
+    bottom_touched[0] = 0;
+    assert_eq!(stack_watermark::unused_words(bottom_touched), 0);
+}
+
+// ---------- W2 ----------
+
+#[test]
+fn w2_scan_stops_at_the_deepest_frame() {
+    // A frame that stored the paint value at its deepest word still
+    // reads as unused there: the overstatement is that word only.
+    let stack = grown(16, 10, &[0xDEAD_BEEF, PAINT, 7]);
+    assert_eq!(stack_watermark::unused_words(stack.iter().copied()), 10);
+    assert_eq!(stack_watermark::unused_bytes(stack), 40);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn w2_unused_is_the_paint_below_the_deepest_frame(
+        words in 1usize..512,
+        deepest_frac in 0.0f64..=1.0,
+        frames in prop::collection::vec(any::<u32>(), 1..16),
+    ) {
+        let deepest = ((words as f64) * deepest_frac) as usize;
+        prop_assume!(deepest == words || frames[0] != PAINT);
+        let stack = grown(words, deepest, &frames);
+        prop_assert_eq!(stack_watermark::unused_words(stack.iter().copied()), deepest);
+        prop_assert_eq!(stack_watermark::unused_bytes(stack), deepest * 4);
+    }
+}
+
+// ---------- W3 ----------
+
+#[test]
+fn w3_low_below_threshold_only() {
+    assert!(stack_watermark::low(0));
+    assert!(stack_watermark::low(LOW_STACK_BYTES - 4));
+    assert!(!stack_watermark::low(LOW_STACK_BYTES));
+    assert!(!stack_watermark::low(16 * 1024));
+}
@@ -0,0 +1,346 @@
+//! Tests for boot/network status pattern selection (invariants S1–S7).
+//!
+//!   S1: no WiFi link or no IPv4 address shows Connecting, whatever else
+//!       short of a critical fault.
+//!   S2: repeated sync failures show SyncError, even with a list loaded.
+//!   S3: otherwise Syncing until a fob list is loaded, then Ready.
+//!   S4: the reader mirrors patterns only until the first Ready, which
+//!       chimes once; later changes never reach the reader.
+//!   S5: once networked, being offline beats every other pattern.
+//!   S6: the controller goes offline once the threshold has passed since
+//!       the last successful sync (or boot), and a sync ends it at once;
+//!       a threshold of 0 never goes offline.
+//!   S7: a critical fault shows Fault over every other pattern, and
+//!       chimes if it comes up during boot.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test status_led
+
+#![cfg(feature = "sim")]
+
+use access_controller::status_led::{
+    self, chime, BootIndicator, NetStatus, Pattern, SyncAge, MAX_OFFLINE_SECS,
+    SYNC_FAILURE_THRESHOLD,
+};
+use proptest::prelude::*;
+
+fn status(wifi_up: bool, ip_configured: bool, fobs_loaded: bool, sync_failures: u8) -> NetStatus {
+    NetStatus {
+        wifi_up,
+        ip_configured,
+        fobs_loaded,
+        sync_failures,
+        offline: false,
+        critical: false,
+    }
+}
+
+fn offline(s: NetStatus) -> NetStatus {
+    NetStatus { offline: true, ..s }
+}
+
+fn arb_status() -> impl Strategy<Value = NetStatus> {
+    (any::<bool>(), any::<bool>(), any::<bool>(), 0u8..8, any::<bool>()).prop_map(
+        |(w, i, f, n, o)| NetStatus {
+            offline: o,
+            ..status(w, i, f, n)
+        },
+    )
+}
+
+#[test]
+fn selection_table() {
+    let n = SYNC_FAILURE_THRESHOLD;
+    assert_eq!(
+        Pattern::select(status(false, false, false, 0)),
+        Pattern::Connecting
+    );
+    assert_eq!(
+        Pattern::select(status(false, true, true, 0)),
+        Pattern::Connecting
+    );
+    assert_eq!(
+        Pattern::select(status(true, false, true, n)),
+        Pattern::Connecting
+    );
+    assert_eq!(
+        Pattern::select(status(true, true, false, 0)),
+        Pattern::Syncing
+    );
+    assert_eq!(
+        Pattern::select(status(true, true, false, n - 1)),
+        Pattern::Syncing
+    );
+    assert_eq!(
+        Pattern::select(status(true, true, false, n)),
+        Pattern::SyncError
+    );
+    assert_eq!(
+        Pattern::select(status(true, true, true, n)),
+        Pattern::SyncError
+    );
+    assert_eq!(
+        Pattern::select(status(true, true, true, n - 1)),
+        Pattern::Ready
+    );
+    assert_eq!(Pattern::select(status(true, true, true, 0)), Pattern::Ready);
+}
+
+#[test]
+fn patterns_are_distinct_and_nonempty() {
+    let all = [
+        Pattern::Connecting,
+        Pattern::Syncing,
+        Pattern::Ready,
+        Pattern::SyncError,
+        Pattern::Offline,
+        Pattern::Fault,
+    ];
+    for (i, a) in all.iter().enumerate() {
+        assert!(!a.steps().is_empty());
+        assert!(a.steps().iter().all(|&(_, ms)| ms > 0));
+        for b in &all[i + 1..] {
+            assert_ne!(a.steps(), b.steps(), "{:?} vs {:?}", a, b);
+        }
+    }
+    // Solid really is solid.
+    assert!(Pattern::Connecting.steps().iter().all(|&(on, _)| on));
+}
+
+#[test]
+fn boot_sequence_on_reader() {
+    let mut ind = BootIndicator::new();
+    let c = ind.update(status(false, false, false, 0)).unwrap();
+    assert_eq!(
+        (c.pattern, c.on_reader, c.chime.len()),
+        (Pattern::Connecting, true, 0)
+    );
+    assert_eq!(ind.update(status(true, false, false, 0)), None);
+
+    let c = ind.update(status(true, true, false, 0)).unwrap();
+    assert_eq!((c.pattern, c.on_reader), (Pattern::Syncing, true));
+
+    let c = ind.update(status(true, true, true, 0)).unwrap();
+    assert_eq!(c.pattern, Pattern::Ready);
+    assert!(!c.on_reader);
+    assert_eq!(c.chime, chime(Pattern::Ready));
+    assert!(!c.chime.is_empty());
+    assert!(ind.booted());
+}
+
+#[test]
+fn sync_error_during_boot_beeps() {
+    let mut ind = BootIndicator::new();
+    ind.update(status(true, true, false, 0));
+    let c = ind
+        .update(status(true, true, false, SYNC_FAILURE_THRESHOLD))
+        .unwrap();
+    assert_eq!(c.pattern, Pattern::SyncError);
+    assert!(c.on_reader);
+    assert_eq!(c.chime, chime(Pattern::SyncError));
+    assert_ne!(c.chime, chime(Pattern::Ready));
+}
+
+#[test]
+fn after_boot_changes_stay_off_the_reader() {
+    let mut ind = BootIndicator::new();
+    ind.update(status(true, true, true, 0));
+    for s in [
+        status(true, true, true, SYNC_FAILURE_THRESHOLD),
+        status(false, false, true, 0),
+        status(true, true, true, 0),
+    ] {
+        let c = ind.update(s).unwrap();
+        assert!(!c.on_reader);
+        assert!(c.chime.is_empty());
+    }
+}
+
+// ---------- S5 ----------
+
+#[test]
+fn offline_selection() {
+    let n = SYNC_FAILURE_THRESHOLD;
+    assert_eq!(
+        Pattern::select(offline(status(true, true, true, 0))),
+        Pattern::Offline
+    );
+    assert_eq!(
+        Pattern::select(offline(status(true, true, true, n))),
+        Pattern::Offline
+    );
+    assert_eq!(
+        Pattern::select(offline(status(true, true, false, 0))),
+        Pattern::Offline
+    );
+    assert_eq!(
+        Pattern::select(offline(status(false, true, true, 0))),
+        Pattern::Connecting
+    );
+    // Not a boot milestone: no chime.
+    assert!(chime(Pattern::Offline).is_empty());
+}
+
+// ---------- S6 ----------
+
+#[test]
+fn offline_after_threshold_since_boot() {
+    let mut age = SyncAge::new(60);
+    assert_eq!(age.update(0), None);
+    assert_eq!(age.update(59_999), None);
+    assert!(!age.offline());
+    assert_eq!(age.update(60_000), Some(true));
+    assert!(age.offline());
+    assert_eq!(age.update(120_000), None);
+    assert_eq!(age.last_ok_ms(), None);
+    assert_eq!(age.since_ms(120_000), 120_000);
+}
+
+#[test]
+fn sync_ends_offline_and_restarts_the_clock() {
+    let mut age = SyncAge::new(60);
+    age.update(70_000);
+    assert!(age.offline());
+    assert!(age.synced(75_000));
+    assert!(!age.offline());
+    assert!(!age.is_offline(75_000));
+    assert_eq!(age.update(75_000), None);
+    assert_eq!(age.update(134_999), None);
+    assert_eq!(age.update(135_000), Some(true));
+    // A sync while online reports no transition.
+    assert!(age.synced(140_000));
+    assert!(!age.synced(150_000));
+    assert_eq!(age.since_ms(160_000), 10_000);
+}
+
+#[test]
+fn zero_threshold_never_offline() {
+    let mut age = SyncAge::new(0);
+    assert_eq!(age.update(u64::MAX), None);
+    assert!(!age.is_offline(u64::MAX));
+}
+
+#[test]
+fn offline_knob() {
+    assert_eq!(status_led::parse_offline_secs("0"), Some(0));
+    assert_eq!(status_led::parse_offline_secs("3600"), Some(3_600));
+    assert_eq!(
+        status_led::parse_offline_secs(&MAX_OFFLINE_SECS.to_string()),
+        Some(MAX_OFFLINE_SECS)
+    );
+    assert_eq!(
+        status_led::parse_offline_secs(&(MAX_OFFLINE_SECS + 1).to_string()),
+        None
+    );
+    assert_eq!(status_led::parse_offline_secs("1h"), None);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// S1–S3 and S5 as properties over every input combination.
+    #[test]
+    fn prop_selection(s in arb_status()) {
+        let p = Pattern::select(s);
+        let networked = s.wifi_up && s.ip_configured;
+        prop_assert_eq!(p == Pattern::Connecting, !networked);
+        if networked {
+            prop_assert_eq!(p == Pattern::Offline, s.offline);
+        }
+        if networked && !s.offline {
+            prop_assert_eq!(p == Pattern::SyncError, s.sync_failures >= SYNC_FAILURE_THRESHOLD);
+            if s.sync_failures < SYNC_FAILURE_THRESHOLD {
+                prop_assert_eq!(p == Pattern::Ready, s.fobs_loaded);
+            }
+        }
+    }
+
+    /// S4: over any status sequence, updates report exactly the pattern
+    /// changes, the reader sees nothing from the first Ready on, and the
+    /// ready chime plays at most once.
+    #[test]
+    fn prop_reader_only_during_boot(seq in prop::collection::vec(arb_status(), 1..40)) {
+        let mut ind = BootIndicator::new();
+        let mut shown = None;
+        let mut ready_chimes = 0;
+        let mut seen_ready = false;
+        for s in seq {
+            let p = Pattern::select(s);
+            match ind.update(s) {
+                None => prop_assert_eq!(shown, Some(p)),
+                Some(c) => {
+                    prop_assert_ne!(shown, Some(p));
+                    prop_assert_eq!(c.pattern, p);
+                    seen_ready |= p == Pattern::Ready;
+                    prop_assert_eq!(c.on_reader, !seen_ready);
+                    if seen_ready && p != Pattern::Ready {
+                        prop_assert!(c.chime.is_empty());
+                    }
+                    if p == Pattern::Ready && !c.chime.is_empty() {
+                        ready_chimes += 1;
+                    }
+                    shown = Some(p);
+                }
+            }
+            prop_assert_eq!(ind.booted(), seen_ready);
+        }
+        prop_assert!(ready_chimes <= 1);
+    }
+
+    /// S6 over any run of syncs and checks: the controller is offline
+    /// exactly when the threshold has passed since the last sync (or
+    /// boot), and updates report exactly the changes.
+    #[test]
+    fn prop_offline_transitions(
+        threshold in 0u64..100,
+        steps in prop::collection::vec((1u64..50_000, any::<bool>()), 1..60),
+    ) {
+        let mut age = SyncAge::new(threshold);
+        let mut now = 0;
+        let mut last_ok = 0;
+        let mut was = false;
+        for (dt, sync) in steps {
+            now += dt;
+            if sync {
+                prop_assert_eq!(age.synced(now), was);
+                last_ok = now;
+                was = false;
+            }
+            let want = threshold > 0 && now - last_ok >= threshold * 1_000;
+            let change = age.update(now);
+            prop_assert_eq!(change, (want != was).then_some(want));
+            prop_assert_eq!(age.offline(), want);
+            was = want;
+        }
+    }
+}
+
+// ---------- S7 ----------
+
+proptest! {
+    #[test]
+    fn s7_fault_beats_everything(s in arb_status()) {
+        prop_assert_eq!(Pattern::select(NetStatus { critical: true, ..s }), Pattern::Fault);
+        prop_assert_ne!(Pattern::select(s), Pattern::Fault);
+    }
+}
+
+#[test]
+fn s7_fault_during_boot_beeps() {
+    let mut ind = BootIndicator::new();
+    ind.update(status(true, true, false, 0));
+    let c = ind
+        .update(NetStatus { critical: true, ..status(true, true, false, 0) })
+        .unwrap();
+    assert_eq!(c.pattern, Pattern::Fault);
+    assert!(c.on_reader);
+    assert_eq!(c.chime, chime(Pattern::Fault));
+    assert_ne!(c.chime, chime(Pattern::SyncError));
+    assert!(!ind.booted());
+}
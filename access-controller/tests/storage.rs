@@ -0,0 +1,385 @@
+//! Tests for the ping-pong record store behind `settings`, `fob_store`
+//! and `cache_store` (invariants P1–P4).
+//!
+//!   P1: saves alternate between the slots with increasing seqs, and a
+//!       load returns the newest record.
+//!   P2: a slot whose header, ciphertext or tag was altered, or that was
+//!       sealed with another key or for another store, does not load;
+//!       the other slot's record is returned instead.
+//!   P3: a power loss anywhere in a save, or in the erase of the other
+//!       slot that follows it, leaves the previous or the new record
+//!       loadable, never neither.
+//!   P4: the save after a power loss uses a seq above every seq written
+//!       before it, so no nonce is reused.
+//!   P5: the boot-time flash check passes on working flash and leaves the
+//!       sector erased; a write that fails, silently does not land, or
+//!       lands altered, or an erase that does not take, fails it.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test storage
+
+#![cfg(feature = "sim")]
+
+use access_controller::crypto;
+use access_controller::flash_layout::SECTOR;
+use access_controller::storage::{
+    check_writable, FlashBackend, MemFlash, Slot, Storage, StoreConfig,
+};
+use proptest::prelude::*;
+
+const BASE: u32 = 0x9000;
+const KEY: [u8; 32] = [7; 32];
+
+const CFG: StoreConfig = StoreConfig {
+    slots: [BASE, BASE + SECTOR],
+    magic: 0x434F4E57,
+    domain: crypto::DOMAIN_SETTINGS,
+    max_payload: 256,
+};
+
+fn store() -> Storage<MemFlash> {
+    Storage::new(MemFlash::new(BASE, 2 * SECTOR), CFG)
+}
+
+fn loaded(s: &mut Storage<MemFlash>) -> Option<(u64, Vec<u8>)> {
+    s.load(&KEY).into_record().map(|r| (r.seq, r.payload))
+}
+
+// ---------- P1 ----------
+
+#[test]
+fn p1_empty_flash_loads_nothing() {
+    let mut s = store();
+    let l = s.load(&KEY);
+    assert_eq!(l.slots, [Slot::Empty, Slot::Empty]);
+    assert_eq!(l.into_record(), None);
+}
+
+#[test]
+fn p1_saves_alternate_and_newest_wins() {
+    let mut s = store();
+    for i in 1..=5u64 {
+        let saved = s.save(&KEY, &[i as u8; 10]).unwrap();
+        assert_eq!(saved.seq, i);
+        assert_eq!(saved.slot, ((i - 1) % 2) as usize);
+        assert_eq!(loaded(&mut s), Some((i, vec![i as u8; 10])));
+    }
+    // Both generations are on flash.
+    let l = s.load(&KEY);
+    assert_eq!(l.slots[0].seq(), Some(5));
+    assert_eq!(l.slots[1].seq(), Some(4));
+    assert_eq!(l.fell_back(), None);
+}
+
+#[test]
+fn p1_erasing_the_other_slot_keeps_one_copy() {
+    let mut s = store();
+    for i in 1..=4u64 {
+        let saved = s.save(&KEY, &[i as u8]).unwrap();
+        s.erase_slot(1 - saved.slot).unwrap();
+        assert_eq!(saved.seq, i);
+        assert_eq!(loaded(&mut s), Some((i, vec![i as u8])));
+        assert_eq!(s.load(&KEY).slots[1 - saved.slot], Slot::Empty);
+    }
+}
+
+#[test]
+fn p1_erase_wipes_both_slots() {
+    let mut s = store();
+    s.save(&KEY, b"a").unwrap();
+    s.save(&KEY, b"b").unwrap();
+    s.erase().unwrap();
+    assert_eq!(loaded(&mut s), None);
+    assert_eq!(s.save(&KEY, b"c").unwrap().seq, 1);
+}
+
+#[test]
+fn p1_oversized_payload_is_refused() {
+    let mut s = store();
+    assert_eq!(s.save(&KEY, &[0; 257]), Err("payload too large"));
+    assert_eq!(loaded(&mut s), None);
+}
+
+// ---------- P2 ----------
+
+fn two_generations() -> Storage<MemFlash> {
+    let mut s = store();
+    s.save(&KEY, b"old").unwrap();
+    s.save(&KEY, b"new").unwrap();
+    s
+}
+
+#[test]
+fn p2_any_flipped_byte_falls_back() {
+    let len = crypto::HEADER_LEN + 3 + crypto::TAG_LEN;
+    for at in 0..len as u32 {
+        let mut s = two_generations();
+        *s.flash_mut().byte_mut(CFG.slots[1] + at) ^= 0x01;
+        assert_eq!(loaded(&mut s), Some((1, b"old".to_vec())), "byte {}", at);
+    }
+}
+
+#[test]
+fn p2_wrong_key_loads_nothing() {
+    let mut s = two_generations();
+    let l = s.load(&[8; 32]);
+    assert_eq!(
+        l.slots,
+        [Slot::Rejected { seq: 1 }, Slot::Rejected { seq: 2 }]
+    );
+    assert_eq!(l.into_record(), None);
+}
+
+#[test]
+fn p2_other_stores_records_are_not_opened() {
+    let cache = StoreConfig {
+        magic: 0x52_46_4F_42,
+        domain: crypto::DOMAIN_CACHE,
+        ..CFG
+    };
+    let mut s = Storage::new(MemFlash::new(BASE, 2 * SECTOR), cache);
+    s.save(&KEY, b"cache").unwrap();
+    let mut s = Storage::new(s.into_inner(), CFG);
+    assert_eq!(s.load(&KEY).slots, [Slot::Empty, Slot::Empty]);
+
+    // Same magic, other domain: the nonce check rejects the header.
+    let spliced = StoreConfig {
+        domain: crypto::DOMAIN_FOBS,
+        ..CFG
+    };
+    let mut s = Storage::new(MemFlash::new(BASE, 2 * SECTOR), spliced);
+    s.save(&KEY, b"fobs").unwrap();
+    let mut s = Storage::new(s.into_inner(), CFG);
+    assert_eq!(loaded(&mut s), None);
+}
+
+#[test]
+fn p2_rejected_newer_slot_is_reported() {
+    let mut s = two_generations();
+    let tag_at = CFG.slots[1] + (crypto::HEADER_LEN + 3) as u32;
+    *s.flash_mut().byte_mut(tag_at) ^= 0x80;
+    let l = s.load(&KEY);
+    assert_eq!(l.slots[1], Slot::Rejected { seq: 2 });
+    assert_eq!(l.fell_back(), Some(2));
+    assert_eq!(l.record().map(|r| r.seq), Some(1));
+}
+
+#[test]
+fn p2_rejected_slot_is_not_overwritten_over_the_good_one() {
+    // The newest header is torn; the save must go to the torn slot, not
+    // over the only readable record.
+    let mut s = two_generations();
+    *s.flash_mut()
+        .byte_mut(CFG.slots[1] + crypto::HEADER_LEN as u32) ^= 0x01;
+    let saved = s.save(&KEY, b"third").unwrap();
+    assert_eq!(saved.slot, 1);
+    assert_eq!(saved.seq, 3);
+    assert_eq!(s.load(&KEY).slots[0].seq(), Some(1));
+}
+
+// ---------- P3 / P4 ----------
+
+/// Save `payload` and erase the other slot, as `settings` and
+/// `fob_store` do, with power cut after `cut` bytes.
+fn save_and_erase(s: &mut Storage<MemFlash>, payload: &[u8], cut: usize) {
+    s.flash_mut().cut_power_after(cut);
+    if let Ok(saved) = s.save(&KEY, payload) {
+        let _ = s.erase_slot(1 - saved.slot);
+    }
+    s.flash_mut().restore_power();
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        // Deterministic seed: failures are reproducible across runs.
+        rng_algorithm: proptest::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn p3_p4_power_loss_keeps_a_record(
+        history in 1usize..4,
+        erase_other in any::<bool>(),
+        cut in 0usize..(2 * SECTOR as usize + 64),
+        len in 0usize..=256,
+    ) {
+        let mut s = store();
+        let mut written = 0u64;
+        for i in 0..history {
+            let saved = s.save(&KEY, &[i as u8; 8]).unwrap();
+            if erase_other {
+                s.erase_slot(1 - saved.slot).unwrap();
+            }
+            written = saved.seq;
+        }
+        let old = (written, vec![(history - 1) as u8; 8]);
+        let new = vec![0xA5; len];
+
+        if erase_other {
+            save_and_erase(&mut s, &new, cut);
+        } else {
+            s.flash_mut().cut_power_after(cut);
+            let _ = s.save(&KEY, &new);
+            s.flash_mut().restore_power();
+        }
+
+        // P3
+        let got = loaded(&mut s);
+        prop_assert!(
+            got == Some(old.clone()) || got == Some((written + 1, new.clone())),
+            "cut at {}: loaded {:?}", cut, got
+        );
+
+        // P4
+        let max_on_flash = s
+            .load(&KEY)
+            .slots
+            .iter()
+            .filter_map(Slot::seq)
+            .max()
+            .unwrap();
+        let next = s.save(&KEY, b"after").unwrap();
+        prop_assert!(next.seq > written);
+        prop_assert!(next.seq > max_on_flash);
+        prop_assert_eq!(loaded(&mut s), Some((next.seq, b"after".to_vec())));
+    }
+}
+
+#[test]
+fn p4_torn_header_still_advances_seq() {
+    // Cut just after the header: the body is blank, the header parses.
+    let mut s = store();
+    s.save(&KEY, b"one").unwrap();
+    s.flash_mut().cut_power_after(crypto::HEADER_LEN);
+    assert_eq!(s.save(&KEY, b"two"), Err("power lost"));
+    s.flash_mut().restore_power();
+    let l = s.load(&KEY);
+    assert_eq!(l.slots[1], Slot::Rejected { seq: 2 });
+    assert_eq!(l.fell_back(), Some(2));
+    assert_eq!(s.save(&KEY, b"two").unwrap().seq, 3);
+}
+
+#[test]
+fn mem_flash_fails_while_off() {
+    let mut f = MemFlash::new(BASE, SECTOR);
+    f.cut_power_after(2);
+    assert_eq!(f.write(BASE, &[1, 2, 3]), Err("power lost"));
+    assert!(f.is_off());
+    let mut buf = [0u8; 3];
+    assert_eq!(f.read(BASE, &mut buf), Err("power lost"));
+    f.restore_power();
+    f.read(BASE, &mut buf).unwrap();
+    assert_eq!(buf, [1, 2, 0xFF]);
+    assert_eq!(f.erase(BASE + 1, SECTOR), Err("erase not sector aligned"));
+    assert_eq!(
+        f.read(BASE + SECTOR, &mut buf),
+        Err("flash offset out of range")
+    );
+}
+
+// ---------- P5 ----------
+
+/// A [`MemFlash`] with one fault injected.
+struct Faulty {
+    inner: MemFlash,
+    fault: Fault,
+}
+
+#[derive(Clone, Copy)]
+enum Fault {
+    /// Writes report an error.
+    WriteError,
+    /// Writes report success but change nothing (write-protected).
+    WriteIgnored,
+    /// One bit stuck at 0 at this offset (worn cell).
+    StuckLow(u32),
+    /// Erases report success but change nothing.
+    EraseIgnored,
+}
+
+impl FlashBackend for Faulty {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), &'static str> {
+        self.inner.read(offset, buf)?;
+        if let Fault::StuckLow(at) = self.fault {
+            if let Some(b) = at.checked_sub(offset).and_then(|i| buf.get_mut(i as usize)) {
+                *b &= !0x10;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), &'static str> {
+        match self.fault {
+            Fault::WriteError => Err("flash write failed"),
+            Fault::WriteIgnored => Ok(()),
+            _ => self.inner.write(offset, data),
+        }
+    }
+
+    fn erase(&mut self, offset: u32, len: u32) -> Result<(), &'static str> {
+        match self.fault {
+            Fault::EraseIgnored => Ok(()),
+            _ => self.inner.erase(offset, len),
+        }
+    }
+}
+
+fn faulty(fault: Fault) -> Faulty {
+    Faulty {
+        inner: MemFlash::new(BASE, SECTOR),
+        fault,
+    }
+}
+
+#[test]
+fn p5_working_flash_passes_and_is_left_erased() {
+    let mut f = MemFlash::new(BASE, SECTOR);
+    for seed in [0, 1, 0xDEAD_BEEF] {
+        assert_eq!(check_writable(&mut f, BASE, seed), Ok(()));
+        let mut buf = vec![0u8; SECTOR as usize];
+        f.read(BASE, &mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b == 0xFF));
+    }
+}
+
+#[test]
+fn p5_faults_fail_the_check() {
+    assert_eq!(
+        check_writable(&mut faulty(Fault::WriteError), BASE, 7),
+        Err("flash write failed")
+    );
+    assert_eq!(
+        check_writable(&mut faulty(Fault::WriteIgnored), BASE, 7),
+        Err("flash read-back mismatch")
+    );
+    assert_eq!(
+        check_writable(&mut faulty(Fault::EraseIgnored), BASE, 7),
+        Err("flash erase did not take")
+    );
+    let mut f = MemFlash::new(BASE, SECTOR);
+    f.cut_power_after(100);
+    assert_eq!(check_writable(&mut f, BASE, 7), Err("power lost"));
+}
+
+#[test]
+fn p5_stale_pattern_from_an_earlier_boot_fails() {
+    // Last boot's pattern is still there and the write no longer lands.
+    let mut f = faulty(Fault::WriteIgnored);
+    f.fault = Fault::EraseIgnored;
+    assert!(check_writable(&mut f, BASE, 1).is_err());
+    f.fault = Fault::WriteIgnored;
+    assert_eq!(check_writable(&mut f, BASE, 2), Err("flash read-back mismatch"));
+}
+
+proptest! {
+    /// P5: a single stuck bit anywhere in the sector is caught, whatever
+    /// the seed.
+    #[test]
+    fn p5_stuck_bit_fails(at in 0u32..SECTOR, seed in any::<u32>()) {
+        let mut f = faulty(Fault::StuckLow(BASE + at));
+        prop_assert!(check_writable(&mut f, BASE, seed).is_err());
+    }
+}
@@ -0,0 +1,1392 @@
+//! End-to-end tests for one sync round-trip over a canned transport
+//! (invariants Y1–Y4).
+//!
+//!   Y1: a 200 replaces the list and ETag and acknowledges the batch; the
+//!       next request sends the ETag back, and a 304 to it keeps the list
+//!       and acknowledges the next batch. Under NDJSON the events are
+//!       streamed a line per write, after a head that gives their length.
+//!   Y2: a response that fails to parse, verify (signature or HMAC) or
+//!       arrive in full, or has an unexpected status, changes nothing:
+//!       the events stay pending and are re-sent under the same
+//!       idempotency key.
+//!   Y3: a chunked 200 is decoded before it is verified and parsed.
+//!   Y4: a redirect is reported without committing anything.
+//!   Y5: a full batch of the longest events fits the JSON body whole.
+//!   Y6: with a per-sync cap, a backlog drains over consecutive syncs,
+//!       each sending the next oldest events under a fresh key.
+//!   Y7: a JSON fob list may hold bare integers, numeric strings and
+//!       objects with an `id`, mixed; other object fields are ignored.
+//!       With `FobFormat::Normalize`, string IDs may also be hex or
+//!       `facility:card`.
+//!   Y8: a 200 sets the credential-format hint from
+//!       `X-Credential-Format`, or clears it; a 304 only changes it when
+//!       it carries one.
+//!   Y9: extended-unlock tags from `X-Extended-Unlock` follow the same
+//!       rules as the hint.
+//!  Y10: a socket error is retried on a fresh connection, at most the
+//!       configured number of times, re-sending the batch under the same
+//!       key; a host that answered badly is not retried.
+//!  Y11: an empty list replacing a non-empty one is applied only as the
+//!       policy allows (at once, on the second sync in a row, or never);
+//!       held, it keeps the list and ETag but acknowledges the events.
+//!  Y12: with a shrink limit, a list that drops more than that share of
+//!       the cached one is held until a second sync sends the same list;
+//!       smaller drops and growth apply at once.
+//!  Y13: with a nonce, the request sends it and a 200 or 304 that
+//!       doesn't echo it commits nothing; a signature or HMAC made over
+//!       the body alone, or for another nonce, fails.
+//!  Y14: renamed event fields are used in both the JSON and the NDJSON
+//!       body, with the same values.
+//!  Y15: compact events go out as `[fob,allowed,seq]` arrays under an
+//!       `events=compact` media type; a 200 or 304 with `Accept-Post`
+//!       reports the encoding the host takes, one without leaves it be.
+//!       Negotiating against Conway's own header switches to compact.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test sync_flow
+
+#![cfg(feature = "sim")]
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use access_controller::body_mac;
+use access_controller::decode::{CredentialFormat, FobFormat};
+use access_controller::etag::HostEtag;
+use access_controller::events::{AccessEvent, EventRing, MAX_EVENTS};
+use access_controller::fob_cache;
+use access_controller::http_client::{SyncTarget, MAX_BODY_BYTES};
+use access_controller::idempotency::BatchKeys;
+use access_controller::signing;
+use access_controller::sync_nonce;
+use access_controller::sync_flow::{
+    build_request, parse_fob_list, parse_fob_list_as, parse_retries, parse_shrink_pct,
+    sync_with_host,
+    try_sync_with_host, Batch, EmptyListPolicy, ListGuard, ListRules, Outcome, Retries,
+    Suspect, SyncConfig, SyncContext, Transport, EVENTS_JSON_MAX, MAX_RETRIES, SHRINK_MIN_DROP,
+};
+use access_controller::wire::{
+    self, EncodingChoice, EventEncoding, EventFields, EventFormat, SyncProtocol, MAX_FIELD_NAME,
+};
+use ed25519_compact::KeyPair;
+
+const HOST: [u8; 4] = [10, 0, 0, 2];
+
+/// The futures under test never wait on anything but the canned
+/// transport, which is always ready.
+fn block_on<F: Future>(f: F) -> F::Output {
+    let mut f = pin!(f);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+            return v;
+        }
+    }
+}
+
+/// Replays `response` in `chunk`-byte reads and keeps what was sent.
+struct Canned {
+    response: Vec<u8>,
+    chunk: usize,
+    pos: usize,
+    refuse: bool,
+    reset: bool,
+    sent: Vec<u8>,
+    writes: usize,
+    closed: bool,
+}
+
+impl Canned {
+    fn new(response: impl AsRef<[u8]>) -> Self {
+        Self {
+            response: response.as_ref().to_vec(),
+            chunk: 7,
+            pos: 0,
+            refuse: false,
+            reset: false,
+            sent: Vec::new(),
+            writes: 0,
+            closed: false,
+        }
+    }
+
+    fn sent(&self) -> &str {
+        std::str::from_utf8(&self.sent).unwrap()
+    }
+}
+
+impl Transport for Canned {
+    async fn connect(&mut self, host: [u8; 4], port: u16) -> Result<(), &'static str> {
+        assert_eq!((host, port), (HOST, 8080));
+        if self.refuse {
+            Err("connect failed")
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        self.sent.extend_from_slice(data);
+        self.writes += 1;
+        Ok(())
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        if self.reset {
+            return Err("connection reset");
+        }
+        let n = self
+            .chunk
+            .min(buf.len())
+            .min(self.response.len() - self.pos);
+        buf[..n].copy_from_slice(&self.response[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+    }
+}
+
+/// The firmware's shared state, minus the mutexes.
+struct State {
+    fobs: Vec<u32>,
+    etag: HostEtag,
+    events: EventRing,
+    keys: BatchKeys,
+    max_events: usize,
+    recorded: Option<Vec<u8>>,
+    credential_format: Option<CredentialFormat>,
+    extended_unlock: Vec<u32>,
+    event_encoding: Option<EventEncoding>,
+    list_rules: ListRules,
+    list_guard: ListGuard,
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            fobs: vec![1, 2, 3],
+            etag: HostEtag::new(),
+            events: EventRing::new(),
+            keys: BatchKeys::new([0xAA; 6], 1),
+            max_events: MAX_EVENTS,
+            recorded: None,
+            credential_format: None,
+            extended_unlock: Vec::new(),
+            event_encoding: None,
+            list_rules: ListRules::default(),
+            list_guard: ListGuard::new(),
+        }
+    }
+
+    fn swipe(&mut self, fob: u32) {
+        self.events.push(AccessEvent {
+            fob,
+            allowed: false,
+        });
+    }
+
+    fn pending(&self) -> usize {
+        self.events.len()
+    }
+}
+
+impl SyncContext for State {
+    async fn batch(&mut self) -> Batch {
+        let mut events = [AccessEvent::default(); MAX_EVENTS];
+        let (pending, first_seq) = self.events.peek_up_to(&mut events, self.max_events);
+        match self.keys.batch(first_seq, pending) {
+            Some((key, n)) => Batch {
+                events: heapless::Vec::from_slice(&events[..n]).unwrap(),
+                first_seq,
+                key: Some(key),
+            },
+            None => Batch {
+                first_seq,
+                ..Batch::default()
+            },
+        }
+    }
+
+    async fn etag(&mut self) -> HostEtag {
+        self.etag.clone()
+    }
+
+    async fn fob_crc(&mut self) -> u32 {
+        fob_cache::sorted_digest(&self.fobs)
+    }
+
+    async fn replace_list(&mut self, host: [u8; 4], fobs: &[u32], etag: Option<&str>) {
+        self.fobs = fobs.to_vec();
+        match etag {
+            Some(v) => {
+                self.etag.set(host, v);
+            }
+            None => self.etag.clear(),
+        }
+    }
+
+    async fn set_credential_format(&mut self, format: Option<CredentialFormat>) {
+        self.credential_format = format;
+    }
+
+    async fn set_extended_unlock(&mut self, tagged: &[u32]) {
+        self.extended_unlock = tagged.to_vec();
+    }
+
+    async fn set_event_encoding(&mut self, encoding: EventEncoding) {
+        self.event_encoding = Some(encoding);
+    }
+
+    async fn accept_list(&mut self, fobs: &[u32]) -> bool {
+        self.list_guard
+            .accept(&self.list_rules, self.fobs.len(), fobs)
+    }
+
+    async fn acked(&mut self, batch: &Batch) {
+        self.events.commit(batch.events.len(), batch.first_seq);
+        self.keys.acked();
+    }
+
+    async fn record_response(&mut self, _host: [u8; 4], raw: &[u8]) {
+        self.recorded = Some(raw.to_vec());
+    }
+}
+
+fn target() -> SyncTarget {
+    SyncTarget {
+        host: HOST,
+        port: 8080,
+        path: "/api/fobs".try_into().unwrap(),
+    }
+}
+
+fn run(
+    state: &mut State,
+    transport: &mut Canned,
+    cfg: &SyncConfig,
+) -> Result<Outcome, &'static str> {
+    block_on(sync_with_host(transport, state, &target(), 0, cfg))
+}
+
+fn ok_json(etag: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nETag: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\n\r\n{}",
+        etag,
+        body.len(),
+        body
+    )
+}
+
+fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request
+        .lines()
+        .take_while(|l| !l.is_empty())
+        .find_map(|l| l.strip_prefix(name)?.strip_prefix(": "))
+}
+
+// ---------- Y1 ----------
+
+#[test]
+fn y1_ok_then_not_modified() {
+    let cfg = SyncConfig::default();
+    let mut state = State::new();
+    state.swipe(7);
+    state.swipe(8);
+
+    let mut t = Canned::new(ok_json("\"v1\"", "[10, 20, 30]"));
+    assert_eq!(
+        run(&mut state, &mut t, &cfg),
+        Ok(Outcome::Updated { fobs: 3 })
+    );
+    assert!(t.closed);
+    assert!(t.sent().starts_with("POST /api/fobs HTTP/1.1\r\n"));
+    assert_eq!(header(t.sent(), "If-None-Match"), None);
+    assert!(header(t.sent(), "Idempotency-Key").is_some());
+    let crc = format!("{:08x}", fob_cache::sorted_digest(&[3, 1, 2]));
+    assert_eq!(header(t.sent(), "X-Fob-CRC"), Some(crc.as_str()));
+    assert!(t
+        .sent()
+        .ends_with(r#"[{"fob":7,"allowed":false,"seq":0},{"fob":8,"allowed":false,"seq":1}]"#));
+    assert_eq!(state.fobs, [10, 20, 30]);
+    assert_eq!(state.etag.for_host(HOST), Some("\"v1\""));
+    assert_eq!(state.pending(), 0);
+    assert_eq!(state.recorded.as_deref(), Some(&t.response[..]));
+
+    state.swipe(9);
+    let mut t = Canned::new("HTTP/1.1 304 Not Modified\r\n\r\n");
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::NotModified));
+    assert_eq!(header(t.sent(), "If-None-Match"), Some("\"v1\""));
+    // The CRC now describes the list the ETag names.
+    let crc = format!("{:08x}", fob_cache::sorted_digest(&[30, 20, 10]));
+    assert_eq!(header(t.sent(), "X-Fob-CRC"), Some(crc.as_str()));
+    assert!(t.sent().ends_with(r#"[{"fob":9,"allowed":false,"seq":2}]"#));
+    assert_eq!(state.fobs, [10, 20, 30]);
+    assert_eq!(state.etag.for_host(HOST), Some("\"v1\""));
+    assert_eq!(state.pending(), 0);
+}
+
+#[test]
+fn y1_binary_protocol_round_trip() {
+    let cfg = SyncConfig {
+        protocol: SyncProtocol::Binary,
+        ..SyncConfig::default()
+    };
+    let mut state = State::new();
+    state.swipe(5);
+    let body = wire::encode_fob_list(&[4, 5, 6, 7]);
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+        wire::CONTENT_TYPE,
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(&body);
+
+    let mut t = Canned::new(response);
+    assert_eq!(
+        run(&mut state, &mut t, &cfg),
+        Ok(Outcome::Updated { fobs: 4 })
+    );
+    let head_end = t.sent.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let sent_body = &t.sent[head_end + 4..];
+    let (first_seq, events) = wire::decode_events::<4>(sent_body).unwrap();
+    assert_eq!(first_seq, 0);
+    assert_eq!(
+        events[..],
+        [AccessEvent {
+            fob: 5,
+            allowed: false
+        }]
+    );
+    assert_eq!(state.fobs, [4, 5, 6, 7]);
+    // No ETag in the response: the old one no longer describes the list.
+    assert!(state.etag.is_empty());
+    assert_eq!(state.pending(), 0);
+}
+
+#[test]
+fn y1_ndjson_streams_one_line_per_event() {
+    let cfg = SyncConfig {
+        protocol: SyncProtocol::Ndjson,
+        ..SyncConfig::default()
+    };
+    let mut state = State::new();
+    for fob in 0..(MAX_EVENTS as u32 - 1) {
+        state.swipe(u32::MAX - fob);
+    }
+
+    let mut t = Canned::new(ok_json("\"v1\"", "[10]"));
+    assert_eq!(
+        run(&mut state, &mut t, &cfg),
+        Ok(Outcome::Updated { fobs: 1 })
+    );
+    let sent = t.sent();
+    assert_eq!(
+        header(sent, "Content-Type"),
+        Some(wire::NDJSON_CONTENT_TYPE)
+    );
+    let (_, body) = sent.split_once("\r\n\r\n").unwrap();
+    assert_eq!(
+        header(sent, "Content-Length"),
+        Some(body.len().to_string().as_str())
+    );
+    // The whole backlog went out, more than a 512-byte body could hold.
+    assert!(body.len() > 512);
+    assert_eq!(body.lines().count(), MAX_EVENTS - 1);
+    assert!(body.starts_with("{\"fob\":4294967295,\"allowed\":false,\"seq\":0}\n"));
+    // Head, then one write per event.
+    assert_eq!(t.writes, MAX_EVENTS);
+    assert_eq!(state.pending(), 0);
+}
+
+// ---------- Y2 ----------
+
+/// Run `response` against a state with two pending events and check
+/// that nothing was committed, then that a retry re-sends the same
+/// batch under the same key.
+fn assert_nothing_committed(response: &[u8], cfg: &SyncConfig, want: &'static str) {
+    let mut state = State::new();
+    state.etag.set(HOST, "\"v0\"");
+    state.swipe(7);
+    state.swipe(8);
+
+    let mut t = Canned::new(response);
+    assert_eq!(run(&mut state, &mut t, cfg), Err(want));
+    assert!(t.closed);
+    assert_eq!(state.fobs, [1, 2, 3], "{}", want);
+    assert_eq!(state.etag.for_host(HOST), Some("\"v0\""), "{}", want);
+    assert_eq!(state.pending(), 2, "{}", want);
+    let key = header(t.sent(), "Idempotency-Key").unwrap().to_string();
+
+    let mut retry = Canned::new("HTTP/1.1 304 Not Modified\r\n\r\n");
+    assert_eq!(run(&mut state, &mut retry, cfg), Ok(Outcome::NotModified));
+    assert_eq!(header(retry.sent(), "Idempotency-Key"), Some(key.as_str()));
+    assert_eq!(state.pending(), 0);
+}
+
+#[test]
+fn y2_parse_error_does_not_commit_events() {
+    let cfg = SyncConfig::default();
+    for (body, want) in [
+        ("<html>502</html>", "not a JSON array"),
+        ("[1, two, 3]", "fob list element is not a u32"),
+        (r#"[{"type":"fob"}]"#, "fob object has no id"),
+    ] {
+        assert_nothing_committed(ok_json("\"v2\"", body).as_bytes(), &cfg, want);
+    }
+    let binary = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\n\r\n\x00\x00\x00",
+        wire::CONTENT_TYPE
+    );
+    let err = wire::decode_fob_list::<4>(b"\x00\x00\x00").unwrap_err();
+    assert_nothing_committed(binary.as_bytes(), &cfg, err);
+}
+
+#[test]
+fn y2_bad_status_or_framing_does_not_commit_events() {
+    let cfg = SyncConfig::default();
+    assert_nothing_committed(
+        b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n",
+        &cfg,
+        "unexpected status",
+    );
+    assert_nothing_committed(
+        b"HTTP/1.1 200 OK\r\nETag: \"v2\"",
+        &cfg,
+        "malformed response headers",
+    );
+    assert_nothing_committed(
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n[1,2]\r\n",
+        &cfg,
+        "truncated chunked body",
+    );
+    let mut huge = b"HTTP/1.1 200 OK\r\n\r\n[".to_vec();
+    huge.resize(huge.len() + MAX_BODY_BYTES, b'1');
+    assert_nothing_committed(&huge, &cfg, "response body too large");
+}
+
+#[test]
+fn y2_oversized_response_is_recorded() {
+    let mut huge = b"HTTP/1.1 200 OK\r\n\r\n[".to_vec();
+    huge.resize(huge.len() + MAX_BODY_BYTES, b'1');
+    let mut state = State::new();
+    let mut t = Canned::new(&huge);
+    t.chunk = 1460;
+    assert_eq!(
+        run(&mut state, &mut t, &SyncConfig::default()),
+        Err("response body too large")
+    );
+    assert!(state.recorded.is_some_and(|r| !r.is_empty()));
+}
+
+#[test]
+fn y2_unreachable_host_commits_nothing() {
+    let mut state = State::new();
+    state.swipe(7);
+    let mut t = Canned::new("");
+    t.refuse = true;
+    assert_eq!(
+        run(&mut state, &mut t, &SyncConfig::default()),
+        Err("connect failed")
+    );
+    assert!(t.closed);
+    assert_eq!(state.recorded, None);
+    assert_eq!(state.pending(), 1);
+}
+
+#[test]
+fn y2_signature_gate() {
+    let kp = KeyPair::from_seed([7u8; 32].into());
+    let mut pk = [0u8; 32];
+    pk.copy_from_slice(kp.pk.as_ref());
+    let cfg = SyncConfig {
+        trusted_pubkey: Some(&pk),
+        ..SyncConfig::default()
+    };
+    let body = "[10,20]";
+    let sig = signing::b64_encode(kp.sk.sign(body, None).as_ref());
+
+    assert_nothing_committed(
+        ok_json("\"v2\"", body).as_bytes(),
+        &cfg,
+        "missing signature",
+    );
+
+    let forged = format!(
+        "HTTP/1.1 200 OK\r\nX-Fob-Signature: {}\r\n\r\n[10,20,666]",
+        sig
+    );
+    assert_nothing_committed(forged.as_bytes(), &cfg, "bad signature");
+
+    let mut state = State::new();
+    let signed = format!(
+        "HTTP/1.1 200 OK\r\nX-Fob-Signature: {}\r\n\r\n{}",
+        sig, body
+    );
+    let mut t = Canned::new(signed);
+    assert_eq!(
+        run(&mut state, &mut t, &cfg),
+        Ok(Outcome::Updated { fobs: 2 })
+    );
+    assert_eq!(state.fobs, [10, 20]);
+}
+
+#[test]
+fn y2_hmac_gate() {
+    let key = b"shared secret";
+    let cfg = SyncConfig {
+        hmac_key: Some(key),
+        ..SyncConfig::default()
+    };
+    let body = "[10,20]";
+    let mac = body_mac::sign_hex(key, body.as_bytes());
+
+    assert_nothing_committed(ok_json("\"v2\"", body).as_bytes(), &cfg, "missing HMAC");
+    let forged = format!("HTTP/1.1 200 OK\r\nX-Fob-HMAC: {}\r\n\r\n[10,20,666]", mac);
+    assert_nothing_committed(forged.as_bytes(), &cfg, "bad HMAC");
+    let wrong_key = body_mac::sign_hex(b"other secret", body.as_bytes());
+    let forged = format!("HTTP/1.1 200 OK\r\nX-Fob-HMAC: {}\r\n\r\n{}", wrong_key, body);
+    assert_nothing_committed(forged.as_bytes(), &cfg, "bad HMAC");
+
+    // The MAC covers the body after chunked decoding.
+    let mut state = State::new();
+    let chunked = format!(
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nX-Fob-HMAC: {}\r\n\r\n\
+         3\r\n[10\r\n4\r\n,20]\r\n0\r\n\r\n",
+        mac
+    );
+    let mut t = Canned::new(chunked);
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 2 }));
+    assert_eq!(state.fobs, [10, 20]);
+
+    // Without a key the header is ignored.
+    let mut state = State::new();
+    let mut t = Canned::new(forged);
+    assert_eq!(
+        run(&mut state, &mut t, &SyncConfig::default()),
+        Ok(Outcome::Updated { fobs: 2 })
+    );
+}
+
+// ---------- Y3 ----------
+
+#[test]
+fn y3_chunked_body_is_decoded() {
+    let kp = KeyPair::from_seed([9u8; 32].into());
+    let mut pk = [0u8; 32];
+    pk.copy_from_slice(kp.pk.as_ref());
+    let cfg = SyncConfig {
+        trusted_pubkey: Some(&pk),
+        ..SyncConfig::default()
+    };
+    let sig = signing::b64_encode(kp.sk.sign("[10,20,30]", None).as_ref());
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nETag: \"c1\"\r\n\
+         X-Fob-Signature: {}\r\n\r\n\
+         4\r\n[10,\r\n3;ext=1\r\n20,\r\n3\r\n30]\r\n0\r\nX-Trailer: 1\r\n\r\n",
+        sig
+    );
+
+    let mut state = State::new();
+    state.swipe(1);
+    let mut t = Canned::new(response);
+    assert_eq!(
+        run(&mut state, &mut t, &cfg),
+        Ok(Outcome::Updated { fobs: 3 })
+    );
+    assert_eq!(state.fobs, [10, 20, 30]);
+    assert_eq!(state.etag.for_host(HOST), Some("\"c1\""));
+    assert_eq!(state.pending(), 0);
+}
+
+// ---------- Y4 ----------
+
+#[test]
+fn y4_redirect_commits_nothing() {
+    let mut state = State::new();
+    state.swipe(7);
+    let mut t = Canned::new("HTTP/1.1 307 Temporary Redirect\r\nLocation: /v2/fobs\r\n\r\n");
+    let Ok(Outcome::Redirect(next)) = run(&mut state, &mut t, &SyncConfig::default()) else {
+        panic!("expected a redirect");
+    };
+    assert_eq!(next.path.as_str(), "/v2/fobs");
+    assert_eq!((next.host, next.port), (HOST, 8080));
+    assert_eq!(state.pending(), 1);
+    assert_eq!(state.fobs, [1, 2, 3]);
+
+    let mut t = Canned::new("HTTP/1.1 302 Found\r\nLocation: http://10.9.9.9/api/fobs\r\n\r\n");
+    assert_eq!(
+        run(&mut state, &mut t, &SyncConfig::default()),
+        Err("cross-host redirect refused")
+    );
+    assert_eq!(state.pending(), 1);
+}
+
+// ---------- Y5 ----------
+
+#[test]
+fn y5_full_batch_of_longest_events_is_complete() {
+    // Any number from 10^19 up has the full 20 digits of u64::MAX.
+    let first_seq = 10_000_000_000_000_000_000;
+    let batch = Batch {
+        events: heapless::Vec::from_slice(
+            &[AccessEvent {
+                fob: u32::MAX,
+                allowed: false,
+            }; MAX_EVENTS],
+        )
+        .unwrap(),
+        first_seq,
+        ..Batch::default()
+    };
+    let objects: Vec<String> = (first_seq..first_seq + MAX_EVENTS as u64)
+        .map(|seq| format!(r#"{{"fob":4294967295,"allowed":false,"seq":{}}}"#, seq))
+        .collect();
+    let request = build_request(
+        &target(),
+        SyncProtocol::Json,
+        &batch,
+        None,
+        None,
+        None,
+        &EventFormat::DEFAULT,
+    )
+    .unwrap();
+    let request = std::str::from_utf8(&request).unwrap();
+    let (_, body) = request.split_once("\r\n\r\n").unwrap();
+    assert_eq!(body, format!("[{}]", objects.join(",")));
+    assert_eq!(
+        header(request, "Content-Length"),
+        Some(body.len().to_string().as_str())
+    );
+    // The old 512-byte buffer would have cut this short.
+    assert!(body.len() > 512);
+
+    // The longest names fill the body exactly.
+    let (a, b, c) = (
+        "a".repeat(MAX_FIELD_NAME),
+        "b".repeat(MAX_FIELD_NAME),
+        "c".repeat(MAX_FIELD_NAME),
+    );
+    let format = EventFormat {
+        fields: EventFields { fob: &a, allowed: &b, seq: &c },
+        ..EventFormat::DEFAULT
+    };
+    let request =
+        build_request(&target(), SyncProtocol::Json, &batch, None, None, None, &format).unwrap();
+    let request = std::str::from_utf8(&request).unwrap();
+    let (_, body) = request.split_once("\r\n\r\n").unwrap();
+    assert_eq!(body.len(), EVENTS_JSON_MAX);
+}
+
+// ---------- Y6 ----------
+
+#[test]
+fn y6_capped_backlog_drains_over_syncs() {
+    let cfg = SyncConfig::default();
+    let mut state = State::new();
+    state.max_events = 5;
+    for fob in 0..12 {
+        state.swipe(fob);
+    }
+    let mut keys = Vec::new();
+    let mut sent = Vec::new();
+    for want in [5, 5, 2] {
+        let mut t = Canned::new("HTTP/1.1 304 Not Modified\r\n\r\n");
+        assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::NotModified));
+        let (_, body) = t.sent().split_once("\r\n\r\n").unwrap();
+        let fobs: Vec<u32> = body
+            .split(r#"{"fob":"#)
+            .skip(1)
+            .map(|o| o.split(',').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(fobs.len(), want);
+        sent.extend(fobs);
+        keys.push(header(t.sent(), "Idempotency-Key").unwrap().to_string());
+    }
+    assert_eq!(sent, (0..12).collect::<Vec<_>>());
+    assert_eq!(state.pending(), 0);
+    keys.dedup();
+    assert_eq!(keys.len(), 3);
+}
+
+// ---------- Y7 ----------
+
+#[test]
+fn y7_fob_list_element_forms() {
+    let parse = |json: &str| parse_fob_list(json).map(|f| f.to_vec());
+    assert_eq!(parse("[123, 4294967295]"), Ok(vec![123, u32::MAX]));
+    assert_eq!(parse(r#"["123", "0"]"#), Ok(vec![123, 0]));
+    assert_eq!(
+        parse(r#"[{"id":123,"type":"fob"}, {"type":"fob","id":"456"}]"#),
+        Ok(vec![123, 456])
+    );
+    // Unknown fields are skipped whole, whatever they hold.
+    assert_eq!(
+        parse(r#"[{"note":"a, b: {c}]","tags":[1,{"id":9}],"meta":{"id":8},"id":7}]"#),
+        Ok(vec![7])
+    );
+    assert_eq!(
+        parse("[\n  1,\n  \"2\",\n  { \"id\" : 3 },\n]"),
+        Ok(vec![1, 2, 3])
+    );
+    assert_eq!(parse(r#"[{"id":1,}]"#), Ok(vec![1]));
+
+    assert_eq!(parse(r#"[{}]"#), Err("fob object has no id"));
+    assert_eq!(parse(r#"[{"id":"x"}]"#), Err("fob object id is not a u32"));
+    assert_eq!(
+        parse(r#"[{"id":{"n":1}}]"#),
+        Err("fob object id is not a u32")
+    );
+    assert_eq!(parse(r#"[{"id":1,"id":1}]"#), Err("fob object has two ids"));
+    assert_eq!(parse(r#"[{"id" 1}]"#), Err("malformed fob object"));
+    assert_eq!(parse(r#"["12 3"]"#), Err("fob list element is not a u32"));
+
+    // A response in object form replaces the list like any other.
+    let mut state = State::new();
+    let mut t = Canned::new(ok_json("\"v1\"", r#"[{"id":10,"type":"fob"},"20",30]"#));
+    assert_eq!(
+        run(&mut state, &mut t, &SyncConfig::default()),
+        Ok(Outcome::Updated { fobs: 3 })
+    );
+    assert_eq!(state.fobs, [10, 20, 30]);
+}
+
+#[test]
+fn y7_normalized_string_ids() {
+    let json = r#"["0xBC614E", {"id":"123:45678"}, "12345678", 12345678]"#;
+    assert_eq!(
+        parse_fob_list_as(json, FobFormat::Normalize).unwrap()[..],
+        [12_345_678; 4]
+    );
+    // Off by default: a hex ID is an error, never a silently wrong fob.
+    assert_eq!(parse_fob_list(json), Err("fob list element is not a u32"));
+
+    let cfg = SyncConfig {
+        fob_format: FobFormat::Normalize,
+        ..SyncConfig::default()
+    };
+    let mut state = State::new();
+    let mut t = Canned::new(ok_json("\"v1\"", r#"["0x10", "1:2"]"#));
+    assert_eq!(
+        run(&mut state, &mut t, &cfg),
+        Ok(Outcome::Updated { fobs: 2 })
+    );
+    assert_eq!(state.fobs, [16, 100_002]);
+}
+
+// ---------- Y8 ----------
+
+#[test]
+fn y8_credential_format_hint() {
+    let cfg = SyncConfig::default();
+    let mut state = State::new();
+    let hinted = |format: &str| {
+        format!(
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nX-Credential-Format: {}\r\n\
+             Content-Length: 4\r\n\r\n[10]",
+            format
+        )
+    };
+
+    let mut t = Canned::new(hinted("nfc7"));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 1 }));
+    assert_eq!(state.credential_format, Some(CredentialFormat::Nfc7));
+
+    // A 304 without the header keeps it; one with the header replaces it.
+    let mut t = Canned::new("HTTP/1.1 304 Not Modified\r\n\r\n");
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::NotModified));
+    assert_eq!(state.credential_format, Some(CredentialFormat::Nfc7));
+    let mut t = Canned::new("HTTP/1.1 304 Not Modified\r\nX-Credential-Format: fob\r\n\r\n");
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::NotModified));
+    assert_eq!(state.credential_format, Some(CredentialFormat::Fob));
+
+    // A new list without a (known) hint goes back to trying both forms.
+    let mut t = Canned::new(hinted("nfc10"));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 1 }));
+    assert_eq!(state.credential_format, None);
+    state.credential_format = Some(CredentialFormat::Nfc4);
+    let mut t = Canned::new(ok_json("\"v2\"", "[10]"));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 1 }));
+    assert_eq!(state.credential_format, None);
+}
+
+// ---------- Y9 ----------
+
+#[test]
+fn y9_extended_unlock_tags() {
+    let cfg = SyncConfig::default();
+    let mut state = State::new();
+    let tagged = |tags: &str| {
+        format!(
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nX-Extended-Unlock: {}\r\n\
+             Content-Length: 8\r\n\r\n[10,20]",
+            tags
+        )
+    };
+
+    let mut t = Canned::new(tagged("20, 30"));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 2 }));
+    assert_eq!(state.extended_unlock, [20, 30]);
+
+    // A 304 without the header keeps them; one with it replaces them.
+    let mut t = Canned::new("HTTP/1.1 304 Not Modified\r\n\r\n");
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::NotModified));
+    assert_eq!(state.extended_unlock, [20, 30]);
+    let mut t = Canned::new("HTTP/1.1 304 Not Modified\r\nX-Extended-Unlock: 10\r\n\r\n");
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::NotModified));
+    assert_eq!(state.extended_unlock, [10]);
+
+    // A new list with a malformed value, or without the header, drops them.
+    let mut t = Canned::new(tagged("10,x"));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 2 }));
+    assert!(state.extended_unlock.is_empty());
+    state.extended_unlock = vec![10];
+    let mut t = Canned::new(ok_json("\"v2\"", "[10]"));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 1 }));
+    assert!(state.extended_unlock.is_empty());
+}
+
+// ---------- Y10 ----------
+
+fn refused() -> Canned {
+    let mut t = Canned::new("");
+    t.refuse = true;
+    t
+}
+
+fn reset() -> Canned {
+    let mut t = Canned::new("");
+    t.reset = true;
+    t
+}
+
+/// The firmware's per-host loop: one transport per attempt, retried while
+/// `retries` allows. Returns the result and how many attempts were made.
+fn run_retrying(
+    state: &mut State,
+    transports: &mut [Canned],
+    retries: u8,
+) -> (Result<Outcome, &'static str>, usize) {
+    let cfg = SyncConfig::default();
+    let mut policy = Retries::new(retries);
+    for (i, t) in transports.iter_mut().enumerate() {
+        match block_on(try_sync_with_host(t, state, &target(), 0, &cfg)) {
+            Err(failure) if policy.retry(&failure) => continue,
+            result => return (result.map_err(|f| f.error), i + 1),
+        }
+    }
+    panic!("retried past the last transport");
+}
+
+#[test]
+fn y10_socket_errors_retried_with_same_batch() {
+    let mut state = State::new();
+    state.swipe(7);
+    let mut ts = [
+        refused(),
+        reset(),
+        Canned::new("HTTP/1.1 304 Not Modified\r\n\r\n"),
+    ];
+    assert_eq!(
+        run_retrying(&mut state, &mut ts, 2),
+        (Ok(Outcome::NotModified), 3)
+    );
+    assert!(ts.iter().all(|t| t.closed));
+    assert_eq!(state.pending(), 0);
+    // The reset attempt had sent the batch; the retry sent it again.
+    let key = header(ts[1].sent(), "Idempotency-Key");
+    assert!(key.is_some());
+    assert_eq!(header(ts[2].sent(), "Idempotency-Key"), key);
+}
+
+#[test]
+fn y10_gives_up_after_retries() {
+    for retries in 0..=MAX_RETRIES {
+        let mut state = State::new();
+        state.swipe(7);
+        let mut ts: Vec<Canned> = (0..=retries).map(|_| refused()).collect();
+        ts.push(Canned::new("HTTP/1.1 304 Not Modified\r\n\r\n"));
+        assert_eq!(
+            run_retrying(&mut state, &mut ts, retries),
+            (Err("connect failed"), usize::from(retries) + 1)
+        );
+        assert!(ts.last().unwrap().sent.is_empty());
+        assert_eq!(state.pending(), 1);
+    }
+}
+
+#[test]
+fn y10_bad_answers_not_retried() {
+    let mut huge = b"HTTP/1.1 200 OK\r\n\r\n[".to_vec();
+    huge.resize(huge.len() + MAX_BODY_BYTES, b'1');
+    for (response, want) in [
+        (
+            b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            "unexpected status",
+        ),
+        (ok_json("\"v2\"", "[1, two]").into_bytes(), "fob list element is not a u32"),
+        (huge, "response body too large"),
+    ] {
+        let mut state = State::new();
+        state.swipe(7);
+        let mut ts = [Canned::new(response), Canned::new("")];
+        assert_eq!(run_retrying(&mut state, &mut ts, MAX_RETRIES), (Err(want), 1));
+        assert_eq!(state.pending(), 1);
+    }
+}
+
+#[test]
+fn y10_parse_retries() {
+    assert_eq!(parse_retries("0"), Some(0));
+    assert_eq!(parse_retries(" 2 "), Some(2));
+    assert_eq!(parse_retries("3"), Some(MAX_RETRIES));
+    assert_eq!(parse_retries("4"), None);
+    assert_eq!(parse_retries("-1"), None);
+    assert_eq!(parse_retries(""), None);
+}
+
+// ---------- Y11 ----------
+
+/// Sync with a swipe pending and a 200 carrying `body`.
+fn sync_list(state: &mut State, etag: &str, body: &str) -> Result<Outcome, &'static str> {
+    state.swipe(9);
+    let mut t = Canned::new(ok_json(etag, body));
+    run(state, &mut t, &SyncConfig::default())
+}
+
+#[test]
+fn y11_empty_list_confirmed_by_second_sync() {
+    let mut state = State::new();
+    state.etag.set(HOST, "\"v1\"");
+    assert_eq!(sync_list(&mut state, "\"v2\"", "[]"), Ok(Outcome::ListHeld));
+    assert_eq!(state.fobs, [1, 2, 3]);
+    assert_eq!(state.etag.for_host(HOST), Some("\"v1\""));
+    assert_eq!(state.pending(), 0);
+    assert!(state.list_guard.holding());
+
+    assert_eq!(sync_list(&mut state, "\"v2\"", "[]"), Ok(Outcome::Updated { fobs: 0 }));
+    assert!(state.fobs.is_empty());
+    assert_eq!(state.etag.for_host(HOST), Some("\"v2\""));
+    assert!(!state.list_guard.holding());
+}
+
+#[test]
+fn y11_non_empty_list_resets_confirmation() {
+    let mut state = State::new();
+    assert_eq!(sync_list(&mut state, "\"v2\"", "[]"), Ok(Outcome::ListHeld));
+    assert_eq!(sync_list(&mut state, "\"v3\"", "[4]"), Ok(Outcome::Updated { fobs: 1 }));
+    assert!(!state.list_guard.holding());
+    // A lone empty list after that is held again.
+    assert_eq!(sync_list(&mut state, "\"v4\"", "[]"), Ok(Outcome::ListHeld));
+    assert_eq!(state.fobs, [4]);
+}
+
+#[test]
+fn y11_keep_and_apply_policies() {
+    let mut state = State::new();
+    state.list_rules.empty = EmptyListPolicy::Keep;
+    for _ in 0..3 {
+        assert_eq!(sync_list(&mut state, "\"v2\"", "[]"), Ok(Outcome::ListHeld));
+        assert_eq!(state.fobs, [1, 2, 3]);
+    }
+    assert_eq!(state.pending(), 0);
+
+    let mut state = State::new();
+    state.list_rules.empty = EmptyListPolicy::Apply;
+    assert_eq!(sync_list(&mut state, "\"v2\"", "[]"), Ok(Outcome::Updated { fobs: 0 }));
+    assert!(state.fobs.is_empty());
+}
+
+#[test]
+fn y11_empty_cache_takes_empty_list() {
+    for policy in [EmptyListPolicy::Confirm, EmptyListPolicy::Keep] {
+        let mut state = State::new();
+        state.fobs.clear();
+        state.list_rules.empty = policy;
+        assert_eq!(sync_list(&mut state, "\"v2\"", "[]"), Ok(Outcome::Updated { fobs: 0 }));
+        assert_eq!(state.etag.for_host(HOST), Some("\"v2\""));
+    }
+}
+
+#[test]
+fn y11_guard_decisions() {
+    use EmptyListPolicy::*;
+    let mut g = ListGuard::new();
+    // (policy, current, new) -> accepted, in sequence.
+    for (policy, current, new, want) in [
+        (Confirm, 3, &[][..], false),
+        (Confirm, 3, &[], true),
+        (Confirm, 3, &[], false),
+        (Confirm, 3, &[5], true),
+        (Confirm, 3, &[], false),
+        (Confirm, 0, &[], true),
+        (Confirm, 3, &[], false),
+        (Apply, 3, &[], true),
+        (Keep, 3, &[], false),
+        (Keep, 3, &[1], true),
+    ] {
+        let rules = ListRules {
+            empty: policy,
+            max_shrink_pct: None,
+        };
+        assert_eq!(g.accept(&rules, current, new), want, "{:?} {} {:?}", policy, current, new);
+    }
+    assert_eq!(EmptyListPolicy::parse("confirm"), Some(Confirm));
+    assert_eq!(EmptyListPolicy::parse("keep"), Some(Keep));
+    assert_eq!(EmptyListPolicy::parse("apply"), Some(Apply));
+    assert_eq!(EmptyListPolicy::parse("Keep"), None);
+    assert_eq!(EmptyListPolicy::default(), Confirm);
+}
+
+// ---------- Y12 ----------
+
+fn fob_list(n: u32) -> String {
+    let ids: Vec<String> = (1..=n).map(|f| f.to_string()).collect();
+    format!("[{}]", ids.join(","))
+}
+
+fn shrink_state(cached: u32, pct: u8) -> State {
+    let mut state = State::new();
+    state.fobs = (1..=cached).collect();
+    state.list_rules.max_shrink_pct = Some(pct);
+    state
+}
+
+#[test]
+fn y12_collapse_held_until_same_list_again() {
+    let mut state = shrink_state(300, 50);
+    state.etag.set(HOST, "\"v1\"");
+    assert_eq!(sync_list(&mut state, "\"v2\"", &fob_list(5)), Ok(Outcome::ListHeld));
+    assert_eq!(state.fobs.len(), 300);
+    assert_eq!(state.etag.for_host(HOST), Some("\"v1\""));
+    assert_eq!(state.pending(), 0);
+
+    // A different short list is held in its place rather than confirming.
+    assert_eq!(sync_list(&mut state, "\"v3\"", &fob_list(6)), Ok(Outcome::ListHeld));
+    assert_eq!(state.fobs.len(), 300);
+
+    // The same list again is taken.
+    assert_eq!(sync_list(&mut state, "\"v3\"", &fob_list(6)), Ok(Outcome::Updated { fobs: 6 }));
+    assert_eq!(state.fobs, [1, 2, 3, 4, 5, 6]);
+    assert_eq!(state.etag.for_host(HOST), Some("\"v3\""));
+    assert!(!state.list_guard.holding());
+}
+
+#[test]
+fn y12_normal_changes_apply_at_once() {
+    // Within the limit, growth, the same size, and a drop too small to
+    // be suspect on a small list.
+    const { assert!(12 - 3 < SHRINK_MIN_DROP) };
+    for (cached, new) in [(300, 150), (300, 299), (300, 400), (300, 300), (12, 3)] {
+        let mut state = shrink_state(cached, 50);
+        assert_eq!(
+            sync_list(&mut state, "\"v2\"", &fob_list(new)),
+            Ok(Outcome::Updated { fobs: new as usize }),
+            "{} -> {}",
+            cached,
+            new
+        );
+    }
+}
+
+#[test]
+fn y12_suspect_thresholds() {
+    let rules = ListRules {
+        empty: EmptyListPolicy::Confirm,
+        max_shrink_pct: Some(50),
+    };
+    assert_eq!(rules.suspect(300, 150), None);
+    assert_eq!(rules.suspect(300, 149), Some(Suspect::Shrunk));
+    assert_eq!(rules.suspect(300, 0), Some(Suspect::Empty));
+    assert_eq!(rules.suspect(0, 0), None);
+    assert_eq!(rules.suspect(20, 10), None);
+    assert_eq!(rules.suspect(20, 9), Some(Suspect::Shrunk));
+    assert_eq!(rules.suspect(21, 11), None, "a drop of 10 is not over half of 21");
+    assert_eq!(rules.suspect(15, 6), None, "a drop of 9 is under the minimum");
+
+    let unlimited = ListRules::default();
+    assert_eq!(unlimited.suspect(300, 1), None);
+    assert_eq!(unlimited.suspect(300, 0), Some(Suspect::Empty));
+
+    assert_eq!(parse_shrink_pct("50"), Some(50));
+    assert_eq!(parse_shrink_pct(" 1 "), Some(1));
+    assert_eq!(parse_shrink_pct("99"), Some(99));
+    for bad in ["0", "100", "-5", "x", ""] {
+        assert_eq!(parse_shrink_pct(bad), None, "{:?}", bad);
+    }
+}
+
+// ---------- Y13 ----------
+
+const NONCE: &str = "0123456789abcdef";
+
+fn nonce_cfg() -> SyncConfig<'static> {
+    SyncConfig {
+        nonce: Some(NONCE),
+        ..SyncConfig::default()
+    }
+}
+
+#[test]
+fn y13_echoed_nonce_is_accepted() {
+    let cfg = nonce_cfg();
+    let mut state = State::new();
+    state.swipe(7);
+    let mut t = Canned::new(format!(
+        "HTTP/1.1 200 OK\r\nX-Sync-Nonce: {}\r\n\r\n[10,20]",
+        NONCE
+    ));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 2 }));
+    assert_eq!(header(t.sent(), "X-Sync-Nonce"), Some(NONCE));
+    assert_eq!(state.pending(), 0);
+
+    state.swipe(8);
+    let mut t = Canned::new(format!("HTTP/1.1 304 Not Modified\r\nX-Sync-Nonce: {}\r\n\r\n", NONCE));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::NotModified));
+    assert_eq!(state.pending(), 0);
+
+    // Off, no header goes out and none is required.
+    let mut t = Canned::new(ok_json("\"v2\"", "[10]"));
+    assert_eq!(run(&mut state, &mut t, &SyncConfig::default()), Ok(Outcome::Updated { fobs: 1 }));
+    assert_eq!(header(t.sent(), "X-Sync-Nonce"), None);
+}
+
+#[test]
+fn y13_stale_or_missing_nonce_commits_nothing() {
+    let cfg = nonce_cfg();
+    for (response, want) in [
+        ("HTTP/1.1 200 OK\r\n\r\n[10,20]".to_string(), "missing nonce"),
+        ("HTTP/1.1 304 Not Modified\r\n\r\n".to_string(), "missing nonce"),
+        (
+            "HTTP/1.1 200 OK\r\nX-Sync-Nonce: fedcba9876543210\r\n\r\n[10,20]".to_string(),
+            "stale nonce",
+        ),
+        (
+            "HTTP/1.1 304 Not Modified\r\nX-Sync-Nonce: fedcba9876543210\r\n\r\n".to_string(),
+            "stale nonce",
+        ),
+        (format!("HTTP/1.1 200 OK\r\nX-Sync-Nonce: {}0\r\n\r\n[10,20]", NONCE), "stale nonce"),
+    ] {
+        let mut state = State::new();
+        state.etag.set(HOST, "\"v0\"");
+        state.swipe(7);
+        let mut t = Canned::new(&response);
+        assert_eq!(run(&mut state, &mut t, &cfg), Err(want), "{}", response);
+        assert_eq!(state.fobs, [1, 2, 3]);
+        assert_eq!(state.etag.for_host(HOST), Some("\"v0\""));
+        assert_eq!(state.pending(), 1);
+    }
+
+    // A redirect commits nothing, so it needn't echo.
+    let mut state = State::new();
+    let mut t = Canned::new("HTTP/1.1 307 Temporary Redirect\r\nLocation: /v2/fobs\r\n\r\n");
+    assert!(matches!(run(&mut state, &mut t, &cfg), Ok(Outcome::Redirect(_))));
+}
+
+#[test]
+fn y13_signature_and_hmac_cover_the_nonce() {
+    let body = "[10,20]";
+    let bound = sync_nonce::bind(NONCE, body.as_bytes());
+    assert_eq!(bound, format!("{}\n{}", NONCE, body).into_bytes());
+
+    let kp = KeyPair::from_seed([5u8; 32].into());
+    let mut pk = [0u8; 32];
+    pk.copy_from_slice(kp.pk.as_ref());
+    let cfg = SyncConfig {
+        trusted_pubkey: Some(&pk),
+        ..nonce_cfg()
+    };
+    let signed = |message: &[u8]| {
+        format!(
+            "HTTP/1.1 200 OK\r\nX-Sync-Nonce: {}\r\nX-Fob-Signature: {}\r\n\r\n{}",
+            NONCE,
+            signing::b64_encode(kp.sk.sign(message, None).as_ref()),
+            body
+        )
+    };
+    let mut state = State::new();
+    let mut t = Canned::new(signed(&bound));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 2 }));
+    // An old response signed over the body alone, or for another nonce,
+    // with the current nonce pasted in.
+    let other = sync_nonce::bind("fedcba9876543210", body.as_bytes());
+    for replayed in [signed(body.as_bytes()), signed(&other)] {
+        let mut state = State::new();
+        let mut t = Canned::new(replayed);
+        assert_eq!(run(&mut state, &mut t, &cfg), Err("bad signature"));
+        assert_eq!(state.fobs, [1, 2, 3]);
+    }
+
+    let key = b"shared secret";
+    let cfg = SyncConfig {
+        hmac_key: Some(key),
+        ..nonce_cfg()
+    };
+    let maced = |message: &[u8]| {
+        format!(
+            "HTTP/1.1 200 OK\r\nX-Sync-Nonce: {}\r\nX-Fob-HMAC: {}\r\n\r\n{}",
+            NONCE,
+            body_mac::sign_hex(key, message),
+            body
+        )
+    };
+    let mut state = State::new();
+    let mut t = Canned::new(maced(&bound));
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 2 }));
+    for replayed in [maced(body.as_bytes()), maced(&other)] {
+        let mut state = State::new();
+        let mut t = Canned::new(replayed);
+        assert_eq!(run(&mut state, &mut t, &cfg), Err("bad HMAC"));
+        assert_eq!(state.fobs, [1, 2, 3]);
+    }
+}
+
+// ---------- Y14 ----------
+
+#[test]
+fn y14_renamed_event_fields() {
+    let fields = EventFields::parse("fob=credential,allowed=granted,seq=id").unwrap();
+    for protocol in [SyncProtocol::Json, SyncProtocol::Ndjson] {
+        let cfg = SyncConfig {
+            protocol,
+            event_fields: fields,
+            ..SyncConfig::default()
+        };
+        let mut state = State::new();
+        state.swipe(7);
+        state.swipe(8);
+        let mut t = Canned::new(ok_json("\"v1\"", "[10]"));
+        assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 1 }));
+        let (_, body) = t.sent().split_once("\r\n\r\n").unwrap();
+        let want = match protocol {
+            SyncProtocol::Ndjson => {
+                "{\"credential\":7,\"granted\":false,\"id\":0}\n\
+                 {\"credential\":8,\"granted\":false,\"id\":1}\n"
+            }
+            _ => {
+                "[{\"credential\":7,\"granted\":false,\"id\":0},\
+                 {\"credential\":8,\"granted\":false,\"id\":1}]"
+            }
+        };
+        assert_eq!(body, want, "{:?}", protocol);
+        assert_eq!(
+            header(t.sent(), "Content-Length"),
+            Some(body.len().to_string().as_str())
+        );
+        assert_eq!(state.pending(), 0);
+    }
+}
+
+// ---------- Y15 ----------
+
+#[test]
+fn y15_compact_events() {
+    for (protocol, want) in [
+        (SyncProtocol::Json, "[[7,false,0],[8,false,1]]"),
+        (SyncProtocol::Ndjson, "[7,false,0]\n[8,false,1]\n"),
+    ] {
+        let cfg = SyncConfig {
+            protocol,
+            event_encoding: EventEncoding::Compact,
+            ..SyncConfig::default()
+        };
+        let mut state = State::new();
+        state.swipe(7);
+        state.swipe(8);
+        let mut t = Canned::new(ok_json("\"v1\"", "[10]"));
+        assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 1 }));
+        let sent = t.sent();
+        let (_, body) = sent.split_once("\r\n\r\n").unwrap();
+        assert_eq!(body, want);
+        assert_eq!(
+            header(sent, "Content-Type"),
+            Some(EventEncoding::Compact.content_type(protocol))
+        );
+        assert_eq!(header(sent, "Content-Length"), Some(body.len().to_string().as_str()));
+        assert_eq!(state.pending(), 0);
+    }
+}
+
+#[test]
+fn y15_accept_post_reports_encoding() {
+    let cfg = SyncConfig::default();
+    let responses = [
+        (
+            "HTTP/1.1 304 Not Modified\r\nAccept-Post: application/json; events=compact\r\n\r\n",
+            Some(EventEncoding::Compact),
+        ),
+        (
+            "HTTP/1.1 200 OK\r\nAccept-Post: application/json\r\n\r\n[4]",
+            Some(EventEncoding::Verbose),
+        ),
+        ("HTTP/1.1 304 Not Modified\r\n\r\n", None),
+    ];
+    for (response, want) in responses {
+        let mut state = State::new();
+        state.swipe(7);
+        let mut t = Canned::new(response);
+        assert!(run(&mut state, &mut t, &cfg).is_ok(), "{:?}", response);
+        assert_eq!(state.event_encoding, want, "{:?}", response);
+        // The request that drew it was verbose, as configured.
+        let sent = t.sent();
+        assert_eq!(header(sent, "Content-Type"), Some(wire::JSON_CONTENT_TYPE));
+        assert!(sent.ends_with(r#"[{"fob":7,"allowed":false,"seq":0}]"#));
+    }
+
+    // A response that fails to verify reports nothing.
+    let cfg = SyncConfig {
+        hmac_key: Some(b"k"),
+        ..SyncConfig::default()
+    };
+    let mut state = State::new();
+    let mut t = Canned::new(
+        "HTTP/1.1 200 OK\r\nAccept-Post: application/json; events=compact\r\n\r\n[4]",
+    );
+    assert_eq!(run(&mut state, &mut t, &cfg), Err("missing HMAC"));
+    assert_eq!(state.event_encoding, None);
+}
+
+/// `Accept-Post` exactly as Conway's fob API sends it
+/// (`modules/fobapi/compact.go`).
+const CONWAY_ACCEPT_POST: &str = "application/json, application/json; events=compact, \
+     application/x-ndjson, application/x-ndjson; events=compact, application/x-conway-fobs";
+
+#[test]
+fn y15_negotiates_compact_with_conway() {
+    let mut state = State::new();
+    state.swipe(7);
+    let negotiate = |state: &State| SyncConfig {
+        event_encoding: EncodingChoice::Negotiate.pick(state.event_encoding),
+        ..SyncConfig::default()
+    };
+    let first = format!(
+        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nAccept-Post: {}\r\n\r\n[10]",
+        CONWAY_ACCEPT_POST
+    );
+    let mut t = Canned::new(&first);
+    let cfg = negotiate(&state);
+    assert_eq!(run(&mut state, &mut t, &cfg), Ok(Outcome::Updated { fobs: 1 }));
+    assert!(t.sent().ends_with(r#"[{"fob":7,"allowed":false,"seq":0}]"#));
+    assert_eq!(state.event_encoding, Some(EventEncoding::Compact));
+
+    // Every sync after that sends compact events.
+    state.swipe(8);
+    let second = format!(
+        "HTTP/1.1 304 Not Modified\r\nAccept-Post: {}\r\n\r\n",
+        CONWAY_ACCEPT_POST
+    );
+    let mut t = Canned::new(&second);
+    let cfg = negotiate(&state);
+    assert!(run(&mut state, &mut t, &cfg).is_ok());
+    let sent = t.sent();
+    assert_eq!(header(sent, "Content-Type"), Some(wire::COMPACT_JSON_CONTENT_TYPE));
+    assert!(sent.ends_with("[[8,false,1]]"), "{}", sent);
+}
@@ -0,0 +1,167 @@
+//! Tests for the one-sync-at-a-time guard (invariants G1–G4).
+//!
+//!   G1: while a sync runs, a second entry is refused; once it ends, the
+//!       next one starts.
+//!   G2: the guard is released on every path out of a sync, including
+//!       an early return or a panic.
+//!   G3: under concurrent triggers, no two syncs ever overlap.
+//!   G4: a `POST /sync` is answered by the first sync to start after it,
+//!       never by one already running, and reports how that sync ended.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test sync_guard
+
+#![cfg(feature = "sim")]
+
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use access_controller::sync_guard::{SyncGuard, SyncRuns};
+
+// ---------- G1 ----------
+
+#[test]
+fn g1_second_entry_is_refused() {
+    let guard = SyncGuard::new();
+    assert!(!guard.is_running());
+    let first = guard.try_begin().expect("idle guard");
+    assert!(guard.is_running());
+    assert!(guard.try_begin().is_none());
+    assert!(guard.try_begin().is_none());
+    drop(first);
+    assert!(!guard.is_running());
+    assert!(guard.try_begin().is_some());
+}
+
+// ---------- G2 ----------
+
+fn sync(guard: &SyncGuard, fail: bool) -> Result<(), &'static str> {
+    let _running = guard.try_begin().ok_or("already running")?;
+    if fail {
+        return Err("connect failed");
+    }
+    Ok(())
+}
+
+#[test]
+fn g2_released_on_every_path() {
+    let guard = SyncGuard::new();
+    assert_eq!(sync(&guard, true), Err("connect failed"));
+    assert_eq!(sync(&guard, false), Ok(()));
+    assert!(!guard.is_running());
+
+    let result = panic::catch_unwind(|| {
+        let _running = guard.try_begin().unwrap();
+        panic!("sync blew up");
+    });
+    assert!(result.is_err());
+    assert!(!guard.is_running());
+}
+
+// ---------- G3 ----------
+
+/// Timer and signal racing: every trigger tries to start a sync at the
+/// same moment, over and over. Exactly one wins each race, and it runs
+/// until all the others have been turned away.
+#[test]
+fn g3_concurrent_triggers_never_overlap() {
+    const TRIGGERS: usize = 8;
+    const ROUNDS: usize = 200;
+    let guard = SyncGuard::new();
+    let active = AtomicUsize::new(0);
+    let ran = AtomicUsize::new(0);
+    let barrier = Barrier::new(TRIGGERS);
+    thread::scope(|s| {
+        for _ in 0..TRIGGERS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    barrier.wait();
+                    let running = guard.try_begin();
+                    if running.is_some() {
+                        assert_eq!(active.fetch_add(1, Ordering::SeqCst), 0);
+                        ran.fetch_add(1, Ordering::SeqCst);
+                    }
+                    // Everyone has tried before the winner finishes.
+                    barrier.wait();
+                    if running.is_some() {
+                        active.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    drop(running);
+                }
+            });
+        }
+    });
+    assert_eq!(ran.load(Ordering::SeqCst), ROUNDS);
+    assert!(!guard.is_running());
+}
+
+// ---------- G4 ----------
+
+#[test]
+fn g4_running_sync_does_not_answer() {
+    let mut runs = SyncRuns::new();
+    let first = runs.start();
+    // Requested while `first` runs.
+    let wanted = runs.next();
+    assert_eq!(runs.outcome(wanted), None);
+    runs.finish(first, true);
+    assert_eq!(runs.outcome(wanted), None);
+
+    let second = runs.start();
+    assert_eq!(second, wanted);
+    assert_eq!(runs.outcome(wanted), None);
+    runs.finish(second, false);
+    assert_eq!(runs.outcome(wanted), Some(false));
+    // A later sync answers too, with its own result.
+    let third = runs.start();
+    runs.finish(third, true);
+    assert_eq!(runs.outcome(wanted), Some(true));
+}
+
+/// Mirror of `handle_sync_now`: take the next run number, trigger, poll
+/// until it ends or `wait` runs out.
+fn request_sync(runs: &Mutex<SyncRuns>, trigger: &mpsc::Sender<()>, wait: Duration) -> Option<bool> {
+    let run = runs.lock().unwrap().next();
+    // Like `Signal::signal`, fine with nobody listening.
+    let _ = trigger.send(());
+    let deadline = Instant::now() + wait;
+    loop {
+        if let Some(ok) = runs.lock().unwrap().outcome(run) {
+            return Some(ok);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn g4_request_triggers_and_reports() {
+    let runs = Mutex::new(SyncRuns::new());
+    let (trigger, triggered) = mpsc::channel::<()>();
+    let results = [true, false];
+    thread::scope(|s| {
+        // Mirror of `sync_task` + `sync_with_conway`: one sync per trigger.
+        let runs = &runs;
+        s.spawn(move || {
+            for ok in results {
+                triggered.recv().unwrap();
+                let run = runs.lock().unwrap().start();
+                thread::sleep(Duration::from_millis(5));
+                runs.lock().unwrap().finish(run, ok);
+            }
+        });
+        let wait = Duration::from_secs(10);
+        assert_eq!(request_sync(runs, &trigger, wait), Some(true));
+        assert_eq!(request_sync(runs, &trigger, wait), Some(false));
+        // Nothing left to run the sync: the request times out.
+        assert_eq!(request_sync(runs, &trigger, Duration::from_millis(20)), None);
+    });
+}
@@ -0,0 +1,67 @@
+//! Tests for sync request nonces (invariants N1–N3).
+//!
+//!   N1: a nonce is 16 lowercase hex digits of one random word, and
+//!       consecutive nonces differ.
+//!   N2: an echo is accepted only if it is the nonce sent; a missing one
+//!       and any other value are told apart.
+//!   N3: the signed message is the nonce, a newline and the body.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test sync_nonce
+
+#![cfg(feature = "sim")]
+
+use std::collections::HashSet;
+
+use access_controller::rng::{RandomSource, SplitMix64};
+use access_controller::sync_nonce::{bind, check, generate, NONCE_LEN};
+use proptest::prelude::*;
+
+// ---------- N1 ----------
+
+#[test]
+fn n1_format() {
+    let mut rng = SplitMix64::new(0);
+    let nonce = generate(&mut rng);
+    assert_eq!(nonce.len(), NONCE_LEN);
+    assert_eq!(nonce.as_str(), format!("{:016x}", SplitMix64::new(0).next_u64()));
+}
+
+#[test]
+fn n1_fresh_per_request() {
+    let mut rng = SplitMix64::new(42);
+    let nonces: HashSet<String> = (0..1000).map(|_| generate(&mut rng).to_string()).collect();
+    assert_eq!(nonces.len(), 1000);
+}
+
+proptest! {
+    #[test]
+    fn n1_always_hex(seed: u64) {
+        let nonce = generate(&mut SplitMix64::new(seed));
+        prop_assert_eq!(nonce.len(), NONCE_LEN);
+        prop_assert!(nonce.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)));
+    }
+}
+
+// ---------- N2 ----------
+
+#[test]
+fn n2_echo_must_match() {
+    let sent = "0123456789abcdef";
+    assert_eq!(check(sent, Some(sent)), Ok(()));
+    assert_eq!(check(sent, Some(" 0123456789abcdef ")), Ok(()));
+    assert_eq!(check(sent, None), Err("missing nonce"));
+    for stale in ["fedcba9876543210", "", "0123456789ABCDEF", "0123456789abcde", "0123456789abcdef0"] {
+        assert_eq!(check(sent, Some(stale)), Err("stale nonce"), "{:?}", stale);
+    }
+}
+
+// ---------- N3 ----------
+
+#[test]
+fn n3_bound_message() {
+    assert_eq!(bind("0123456789abcdef", b"[1,2]"), b"0123456789abcdef\n[1,2]");
+    assert_eq!(bind("0123456789abcdef", b""), b"0123456789abcdef\n");
+}
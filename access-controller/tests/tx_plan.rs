@@ -0,0 +1,128 @@
+//! Tests for the Wiegand pass-through pulse plan (invariants X1–X4).
+//!
+//!   X1: the plan sends every bit MSB first, D0 for 0 and D1 for 1, and
+//!       the bits it sends decode back to the read (parity included).
+//!   X2: pulses start `interval_us` apart from 0 and last `pulse_us`.
+//!   X3: `for_read` picks 26 bits unless the data needs 34; 26-bit
+//!       output keeps only the low 24 data bits.
+//!   X4: `TxFormat::parse` takes "26" and "34" only.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test tx_plan
+
+#![cfg(feature = "sim")]
+
+use access_controller::decode::{decode_26, decode_34, decode_frame, encode_26, WiegandRead};
+use access_controller::tx_plan::{plan, Pulse, TxFormat, TxTiming};
+use proptest::prelude::*;
+
+/// The bits a plan puts on the wire, reassembled MSB first.
+fn sent(pulses: &[Pulse]) -> (u64, u32) {
+    let bits = pulses.iter().fold(0u64, |acc, p| (acc << 1) | u64::from(p.bit));
+    (bits, pulses.len() as u32)
+}
+
+fn pulses(read: &WiegandRead, format: TxFormat) -> Vec<Pulse> {
+    plan(format.frame(read), format.bits(), TxTiming::DEFAULT).collect()
+}
+
+// ---------------------------------------------------------------------------
+// X1: bit order and parity
+// ---------------------------------------------------------------------------
+
+#[test]
+fn h10301_bit_order() {
+    let read = decode_26(encode_26(1, 2)).unwrap();
+    let bits: Vec<u8> = pulses(&read, TxFormat::W26).iter().map(|p| p.bit).collect();
+    // Even parity (upper 12 data bits have one 1), facility 1, card 2,
+    // odd parity (lower 12 have one 1).
+    let mut expected = vec![1];
+    expected.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+    expected.extend([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]);
+    expected.push(0);
+    assert_eq!(bits, expected);
+}
+
+proptest! {
+    /// X1: any 26-bit read goes out as a frame that decodes to it.
+    #[test]
+    fn w26_round_trips(facility in 0u32..256, card in 0u32..65536) {
+        let read = decode_26(encode_26(facility, card)).unwrap();
+        let (bits, count) = sent(&pulses(&read, TxFormat::W26));
+        prop_assert_eq!(count, 26);
+        prop_assert_eq!(decode_frame(bits, count), Some(read));
+    }
+
+    /// X1: any 32-bit payload goes out as a 34-bit frame that decodes to it.
+    #[test]
+    fn w34_round_trips(data in any::<u32>()) {
+        let read = WiegandRead { facility: (data >> 16) & 0xFF, card: data & 0xFFFF, raw_data: data };
+        let (bits, count) = sent(&pulses(&read, TxFormat::W34));
+        prop_assert_eq!(count, 34);
+        prop_assert_eq!(decode_34(bits), Some(read));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// X2: timing
+// ---------------------------------------------------------------------------
+
+#[test]
+fn pulses_follow_timing() {
+    let timing = TxTiming { pulse_us: 80, interval_us: 1_500 };
+    let plan: Vec<Pulse> = plan(0b1010, 4, timing).collect();
+    assert_eq!(
+        plan,
+        vec![
+            Pulse { bit: 1, start_us: 0, width_us: 80 },
+            Pulse { bit: 0, start_us: 1_500, width_us: 80 },
+            Pulse { bit: 1, start_us: 3_000, width_us: 80 },
+            Pulse { bit: 0, start_us: 4_500, width_us: 80 },
+        ]
+    );
+}
+
+#[test]
+fn default_timing_frame_lengths() {
+    let read = WiegandRead { facility: 0, card: 0, raw_data: 0 };
+    let last = |format| pulses(&read, format).last().unwrap().start_us;
+    assert_eq!(last(TxFormat::W26), 25 * 2_000);
+    assert_eq!(last(TxFormat::W34), 33 * 2_000);
+    assert!(pulses(&read, TxFormat::W26).iter().all(|p| p.width_us == 50));
+}
+
+#[test]
+fn plan_caps_at_64_bits() {
+    assert_eq!(plan(u64::MAX, 80, TxTiming::DEFAULT).count(), 64);
+    assert_eq!(plan(0, 0, TxTiming::DEFAULT).count(), 0);
+}
+
+// ---------------------------------------------------------------------------
+// X3 / X4: format selection
+// ---------------------------------------------------------------------------
+
+#[test]
+fn for_read_picks_smallest_format() {
+    let small = WiegandRead { facility: 255, card: 65535, raw_data: 0xFF_FFFF };
+    let big = WiegandRead { facility: 0, card: 0, raw_data: 0x0100_0000 };
+    assert_eq!(TxFormat::for_read(&small), TxFormat::W26);
+    assert_eq!(TxFormat::for_read(&big), TxFormat::W34);
+}
+
+#[test]
+fn w26_truncates_to_24_bits() {
+    let read = WiegandRead { facility: 0x34, card: 0x5678, raw_data: 0x1234_5678 };
+    let (bits, count) = sent(&pulses(&read, TxFormat::W26));
+    let out = decode_frame(bits, count).unwrap();
+    assert_eq!((out.facility, out.card, out.raw_data), (0x34, 0x5678, 0x34_5678));
+}
+
+#[test]
+fn parse_formats() {
+    assert_eq!(TxFormat::parse("26"), Some(TxFormat::W26));
+    assert_eq!(TxFormat::parse("34"), Some(TxFormat::W34));
+    assert_eq!(TxFormat::parse("35"), None);
+    assert_eq!(TxFormat::parse(""), None);
+}
@@ -0,0 +1,267 @@
+//! Tests for the long-operation feed cadence and the feed path's lock
+//! budget (invariants Z1–Z7).
+//!
+//!   Z1: the first check feeds, then one feed per interval however often
+//!       the operation checks in between.
+//!   Z2: the cadence leaves a long operation that checks in often enough
+//!       well inside the hardware timeout.
+//!   Z3: over any run of step lengths, a feed is due exactly when the
+//!       interval has passed since the last one, and the longest gap is
+//!       the longest stretch between feeds.
+//!   Z4: lock holds are tracked as the longest seen and a count of those
+//!       over budget or given up on, and rendered as metrics.
+//!   Z5: a feed period plus a full lock budget stays inside the timeout,
+//!       and a decision that gives up on its locks at the budget denies
+//!       outright.
+//!   Z6: a feed tick needs no lists: it feeds and leaves the decision
+//!       state alone whatever the lists hold, so `access_task` handles it
+//!       without taking any lock.
+//!   Z7: a feed tick that is the first input after a held denial's
+//!       recheck or an online check has run out carries that denial's
+//!       Record after the feed, so it reaches the event buffer without
+//!       holding the feed up.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test watchdog
+
+#![cfg(feature = "sim")]
+
+use access_controller::core::{
+    AccessCore, CardRead, DenialReport, Effect, FailPolicy, Input, Outcome, Reader,
+    RECHECK_DEADLINE_MS,
+};
+use access_controller::events::AccessEvent;
+use access_controller::watchdog::{
+    FeedCadence, LockHolds, FEED_PERIOD_MS, LONG_OP_FEED_INTERVAL_MS, MAX_LOCK_HOLD_MS, TIMEOUT_MS,
+};
+use proptest::prelude::*;
+
+// ---------- Z1 ----------
+
+#[test]
+fn z1_first_check_feeds() {
+    let mut c = FeedCadence::new(5_000);
+    assert!(c.due(1_000));
+    assert!(!c.due(1_000));
+    assert!(!c.due(5_999));
+    assert!(c.due(6_000));
+    assert_eq!(c.feeds(), 2);
+    assert_eq!(c.longest_gap_ms(), 5_000);
+}
+
+#[test]
+fn z1_one_feed_per_interval() {
+    let mut c = FeedCadence::new(5_000);
+    // A chunked write checking in every 100 ms for a minute.
+    let fed: Vec<u64> = (0..=600).map(|i| i * 100).filter(|&t| c.due(t)).collect();
+    let expected: Vec<u64> = (0..=12).map(|i| i * 5_000).collect();
+    assert_eq!(fed, expected);
+}
+
+#[test]
+fn z1_final_feed_counts_its_gap() {
+    let mut c = FeedCadence::new(5_000);
+    assert!(c.due(0));
+    assert!(!c.due(3_000));
+    // One last, slow step, then the wrapper's closing feed.
+    c.fed(21_000);
+    assert_eq!(c.feeds(), 2);
+    assert_eq!(c.longest_gap_ms(), 21_000);
+}
+
+// ---------- Z2 ----------
+
+#[test]
+fn z2_interval_leaves_margin() {
+    const { assert!(LONG_OP_FEED_INTERVAL_MS * 3 <= TIMEOUT_MS) };
+    // Steps of up to the interval each: no gap ever reaches twice it.
+    let mut c = FeedCadence::new(LONG_OP_FEED_INTERVAL_MS);
+    let mut now = 0;
+    for step in [1, 4_999, 5_000, 2_500, 2_500, 4_000, 4_000, 1] {
+        now += step;
+        c.due(now);
+    }
+    c.fed(now);
+    assert!(c.longest_gap_ms() < 2 * LONG_OP_FEED_INTERVAL_MS);
+    assert!(c.longest_gap_ms() < TIMEOUT_MS);
+}
+
+// ---------- Z3 ----------
+
+proptest! {
+    #[test]
+    fn z3_due_exactly_after_the_interval(
+        interval in 1u64..10_000,
+        steps in prop::collection::vec(0u64..20_000, 0..100),
+    ) {
+        let mut c = FeedCadence::new(interval);
+        let mut now = 0u64;
+        let mut last: Option<u64> = None;
+        let mut longest = 0u64;
+        let mut feeds = 0u32;
+        for step in steps {
+            now += step;
+            let expect = last.is_none_or(|l| now - l >= interval);
+            prop_assert_eq!(c.due(now), expect);
+            if expect {
+                if let Some(l) = last {
+                    longest = longest.max(now - l);
+                }
+                last = Some(now);
+                feeds += 1;
+            }
+            prop_assert_eq!(c.feeds(), feeds);
+            prop_assert_eq!(c.longest_gap_ms(), longest);
+        }
+    }
+}
+
+// ---------- Z4 ----------
+
+#[test]
+fn z4_tracks_longest_and_overruns() {
+    let holds = LockHolds::new();
+    assert!(!holds.record(3));
+    assert!(!holds.record(MAX_LOCK_HOLD_MS));
+    assert!(holds.record(MAX_LOCK_HOLD_MS + 1));
+    assert!(!holds.record(40));
+    assert_eq!(holds.longest_ms() as u64, MAX_LOCK_HOLD_MS + 1);
+    assert_eq!(holds.over_budget(), 1);
+    // A hold past u32 milliseconds saturates rather than wrapping.
+    assert!(holds.record(u64::MAX));
+    assert_eq!(holds.longest_ms(), u32::MAX);
+
+    let mut out = String::new();
+    holds.write_metrics(&mut out).unwrap();
+    assert_eq!(
+        out,
+        "# TYPE conway_access_lock_hold_max_ms gauge\n\
+         conway_access_lock_hold_max_ms 4294967295\n\
+         # TYPE conway_access_lock_over_budget_total counter\n\
+         conway_access_lock_over_budget_total 2\n"
+    );
+}
+
+#[test]
+fn z4_giving_up_counts_as_over_budget() {
+    let holds = LockHolds::new();
+    holds.record(40);
+    holds.gave_up();
+    assert_eq!(holds.longest_ms() as u64, MAX_LOCK_HOLD_MS);
+    assert_eq!(holds.over_budget(), 1);
+    holds.gave_up();
+    assert_eq!(holds.over_budget(), 2);
+}
+
+// ---------- Z5 ----------
+
+#[test]
+fn z5_budget_fits_the_timeout() {
+    const { assert!(FEED_PERIOD_MS + MAX_LOCK_HOLD_MS < TIMEOUT_MS) };
+    const { assert!(LONG_OP_FEED_INTERVAL_MS <= MAX_LOCK_HOLD_MS) };
+}
+
+#[test]
+fn z5_locked_out_decision_fails_closed() {
+    // What `access_task` decides when it can't get its locks within the
+    // budget: a step against no lists and no Conway host. A member is
+    // denied outright, even inside a fail-open window and with an online
+    // check configured, and nothing waits on a sync.
+    let member = CardRead {
+        fob: 42,
+        nfc: 0,
+        reader: Reader::Wiegand,
+    };
+    let mut open = AccessCore::default()
+        .with_fail_policy(FailPolicy::OpenFor { window_ms: 60_000 })
+        .with_online_check(1_500);
+    let granted = open.step(0, &[], &[42], true, true, Input::Card(member));
+    assert!(granted.contains(&Effect::OpenDoor));
+
+    let mut core = AccessCore::default()
+        .with_fail_policy(FailPolicy::OpenFor { window_ms: 60_000 })
+        .with_online_check(1_500);
+    let eff = core.step(0, &[], &[], false, false, Input::Card(member));
+    assert_eq!(
+        eff.as_slice(),
+        &[
+            Effect::Record(AccessEvent {
+                fob: 42,
+                allowed: false,
+            }),
+            Effect::Feedback(Outcome::Denied),
+        ][..]
+    );
+}
+
+// ---------- Z6 ----------
+
+proptest! {
+    #[test]
+    fn z6_feed_tick_needs_no_lists(
+        local in prop::collection::vec(1u32..64, 0..8),
+        cache in prop::collection::vec(1u32..64, 0..16),
+        conway_enabled in any::<bool>(),
+        list_loaded in any::<bool>(),
+        fob in 1u32..64,
+    ) {
+        let mut with_lists = AccessCore::default();
+        let mut without = AccessCore::default();
+        let fed = with_lists.step(0, &local, &cache, conway_enabled, list_loaded, Input::WatchdogFeed);
+        let fed_bare = without.step(0, &[], &[], false, false, Input::WatchdogFeed);
+        prop_assert_eq!(fed.as_slice(), &[Effect::FeedWatchdog][..]);
+        prop_assert_eq!(fed_bare.as_slice(), &[Effect::FeedWatchdog][..]);
+
+        // Neither tick changed what the next swipe decides.
+        let read = CardRead {
+            fob,
+            nfc: 0,
+            reader: Reader::Wiegand,
+        };
+        let a = with_lists.step(1, &local, &cache, conway_enabled, list_loaded, Input::Card(read));
+        let b = without.step(1, &local, &cache, conway_enabled, list_loaded, Input::Card(read));
+        prop_assert_eq!(a, b);
+    }
+}
+
+// ---------- Z7 ----------
+
+const STRANGER: CardRead = CardRead {
+    fob: 21,
+    nfc: 22,
+    reader: Reader::Wiegand,
+};
+
+const STRANGER_DENIED: Effect = Effect::Record(AccessEvent {
+    fob: 21,
+    allowed: false,
+});
+
+#[test]
+fn z7_feed_carries_an_expired_held_denial() {
+    let mut core = AccessCore::new().with_denial_report(DenialReport::Unresolved);
+    let eff = core.step(1_000, &[], &[], true, true, Input::Card(STRANGER));
+    assert!(!eff.contains(&STRANGER_DENIED), "held for the recheck");
+    let fed = core.step(
+        1_000 + RECHECK_DEADLINE_MS + 1,
+        &[],
+        &[],
+        false,
+        false,
+        Input::WatchdogFeed,
+    );
+    assert_eq!(fed.as_slice(), &[Effect::FeedWatchdog, STRANGER_DENIED][..]);
+    assert_eq!(core.held_denial(), None);
+}
+
+#[test]
+fn z7_feed_carries_an_unanswered_online_check() {
+    let mut core = AccessCore::new().with_online_check(1_000);
+    let eff = core.step(1_000, &[], &[], true, true, Input::Card(STRANGER));
+    assert!(eff.contains(&Effect::QueryAuthz(STRANGER)));
+    let (_, deadline) = core.pending_query(Reader::Wiegand).unwrap();
+    let fed = core.step(deadline + 1, &[], &[], false, false, Input::WatchdogFeed);
+    assert_eq!(fed.as_slice(), &[Effect::FeedWatchdog, STRANGER_DENIED][..]);
+}
@@ -0,0 +1,220 @@
+//! Tests for the push channel's WebSocket framing (invariants S1–S4).
+//!
+//!   S1: server text/binary/ping/pong/close frames parse with the right
+//!       opcode, FIN bit and payload, at all three length encodings.
+//!   S2: a frame split across reads reports "need more" until complete.
+//!   S3: protocol violations (masked server frame, RSV bits, unknown
+//!       opcode, fragmented or long control frame, oversized payload)
+//!       are errors.
+//!   S4: client frames are masked and unmask to the original payload;
+//!       a ping payload round-trips into the pong reply.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test websocket
+
+#![cfg(feature = "sim")]
+
+use access_controller::websocket::{
+    build_frame, handshake_accepted, parse_frame, write_handshake, Frame, Opcode,
+};
+use proptest::prelude::*;
+
+/// Unmasked server frame, as Conway would send it.
+fn server_frame(first: u8, payload: &[u8]) -> Vec<u8> {
+    let mut v = vec![first];
+    match payload.len() {
+        0..=125 => v.push(payload.len() as u8),
+        126..=0xFFFF => {
+            v.push(126);
+            v.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        n => {
+            v.push(127);
+            v.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    v.extend_from_slice(payload);
+    v
+}
+
+/// Undo client masking: returns (first byte, payload).
+fn unmask(frame: &[u8]) -> (u8, Vec<u8>) {
+    assert!(frame[1] & 0x80 != 0, "client frames must be masked");
+    let (len, hdr) = match frame[1] & 0x7F {
+        126 => (u16::from_be_bytes([frame[2], frame[3]]) as usize, 4),
+        127 => (
+            u64::from_be_bytes(frame[2..10].try_into().unwrap()) as usize,
+            10,
+        ),
+        n => (n as usize, 2),
+    };
+    let mask = &frame[hdr..hdr + 4];
+    assert_eq!(frame.len(), hdr + 4 + len);
+    let payload = frame[hdr + 4..]
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ mask[i % 4])
+        .collect();
+    (frame[0], payload)
+}
+
+#[test]
+fn parses_text_frame() {
+    // S1
+    let buf = server_frame(0x81, b"changed");
+    let (frame, used) = parse_frame(&buf, 256).unwrap().unwrap();
+    assert_eq!(
+        frame,
+        Frame {
+            fin: true,
+            opcode: Opcode::Text,
+            payload: b"changed"
+        }
+    );
+    assert_eq!(used, buf.len());
+}
+
+#[test]
+fn parses_control_frames() {
+    // S1
+    for (first, op) in [
+        (0x89, Opcode::Ping),
+        (0x8A, Opcode::Pong),
+        (0x88, Opcode::Close),
+    ] {
+        let buf = server_frame(first, b"hi");
+        let (frame, _) = parse_frame(&buf, 256).unwrap().unwrap();
+        assert_eq!(frame.opcode, op);
+        assert!(frame.opcode.is_control());
+        assert_eq!(frame.payload, b"hi");
+    }
+    let (frame, _) = parse_frame(&[0x01, 0x00], 256).unwrap().unwrap();
+    assert!(!frame.fin);
+    assert_eq!(frame.opcode, Opcode::Text);
+}
+
+#[test]
+fn extended_lengths() {
+    // S1
+    let p16 = vec![7u8; 300];
+    let buf = server_frame(0x82, &p16);
+    let (frame, used) = parse_frame(&buf, 1024).unwrap().unwrap();
+    assert_eq!(
+        (frame.opcode, frame.payload.len(), used),
+        (Opcode::Binary, 300, 304)
+    );
+
+    // 64-bit length form, even for a short payload.
+    let mut buf = vec![0x81, 127];
+    buf.extend_from_slice(&3u64.to_be_bytes());
+    buf.extend_from_slice(b"abc");
+    let (frame, used) = parse_frame(&buf, 1024).unwrap().unwrap();
+    assert_eq!((frame.payload, used), (&b"abc"[..], 13));
+}
+
+#[test]
+fn trailing_frames_are_left_in_the_buffer() {
+    let mut buf = server_frame(0x89, b"p");
+    buf.extend_from_slice(&server_frame(0x81, b"x"));
+    let (frame, used) = parse_frame(&buf, 256).unwrap().unwrap();
+    assert_eq!(frame.opcode, Opcode::Ping);
+    let (frame, _) = parse_frame(&buf[used..], 256).unwrap().unwrap();
+    assert_eq!(frame.opcode, Opcode::Text);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    })]
+
+    /// S2: every strict prefix is incomplete, never an error.
+    #[test]
+    fn split_reads(payload in prop::collection::vec(any::<u8>(), 0..400)) {
+        let buf = server_frame(0x82, &payload);
+        for cut in 0..buf.len() {
+            prop_assert_eq!(parse_frame(&buf[..cut], 1024), Ok(None));
+        }
+        let (frame, used) = parse_frame(&buf, 1024).unwrap().unwrap();
+        prop_assert_eq!(frame.payload, payload.as_slice());
+        prop_assert_eq!(used, buf.len());
+    }
+
+    /// S4
+    #[test]
+    fn client_frames_unmask(
+        payload in prop::collection::vec(any::<u8>(), 0..70_000),
+        mask in any::<[u8; 4]>(),
+    ) {
+        let mut out = vec![0u8; payload.len() + 14];
+        let n = build_frame(Opcode::Binary, &payload, mask, &mut out).unwrap();
+        let (first, got) = unmask(&out[..n]);
+        prop_assert_eq!(first, 0x82);
+        prop_assert_eq!(got, payload);
+    }
+}
+
+#[test]
+fn protocol_violations() {
+    // S3
+    let masked = [0x81, 0x81, 1, 2, 3, 4, b'x' ^ 1];
+    assert!(parse_frame(&masked, 256).is_err());
+    assert!(parse_frame(&server_frame(0xC1, b"x"), 256).is_err());
+    assert!(parse_frame(&server_frame(0x83, b"x"), 256).is_err());
+    assert!(parse_frame(&server_frame(0x09, b"x"), 256).is_err());
+    assert!(parse_frame(&server_frame(0x89, &[0; 126]), 256).is_err());
+    // Oversized payload is rejected from the header alone.
+    assert!(parse_frame(&server_frame(0x81, &[0; 257])[..4], 256).is_err());
+}
+
+#[test]
+fn ping_pong() {
+    // S4
+    let ping = server_frame(0x89, b"keepalive");
+    let (frame, _) = parse_frame(&ping, 256).unwrap().unwrap();
+    let mut out = [0u8; 131];
+    let n = build_frame(
+        Opcode::Pong,
+        frame.payload,
+        [0xA5, 0x5A, 0x00, 0xFF],
+        &mut out,
+    )
+    .unwrap();
+    assert_eq!(unmask(&out[..n]), (0x8A, b"keepalive".to_vec()));
+
+    let n = build_frame(Opcode::Close, &[], [1, 2, 3, 4], &mut out).unwrap();
+    assert_eq!(&out[..n], &[0x88, 0x80, 1, 2, 3, 4]);
+
+    assert!(build_frame(Opcode::Ping, &[0; 126], [0; 4], &mut [0u8; 256]).is_err());
+    assert!(build_frame(Opcode::Text, b"abc", [0; 4], &mut [0u8; 8]).is_err());
+}
+
+#[test]
+fn handshake() {
+    let mut req = String::new();
+    write_handshake(
+        &mut req,
+        "/api/fobs/ws",
+        "10.0.0.1",
+        "dGhlIHNhbXBsZSBub25jZQ==",
+    )
+    .unwrap();
+    assert!(req.starts_with("GET /api/fobs/ws HTTP/1.1\r\n"));
+    assert!(req.contains("\r\nUpgrade: websocket\r\n"));
+    assert!(req.contains("\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n"));
+    assert!(req.contains("\r\nSec-WebSocket-Version: 13\r\n"));
+    assert!(req.ends_with("\r\n\r\n"));
+
+    assert!(handshake_accepted(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: WebSocket\r\nConnection: Upgrade\r\n"
+    ));
+    assert!(!handshake_accepted(
+        "HTTP/1.1 200 OK\r\nUpgrade: websocket\r\n"
+    ));
+    assert!(!handshake_accepted(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: h2c\r\n"
+    ));
+}
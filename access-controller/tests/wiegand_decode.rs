@@ -1,4 +1,20 @@
-//! Tests for the pure Wiegand decoders (invariants W1–W4).
+//! Tests for the pure Wiegand decoders (invariants W1–W10).
+//!
+//!   W5: with `FobFormat::Normalize`, a fob ID written in decimal, `0x`
+//!       hex or as `facility:card` reads as the number `to_fob` gives;
+//!       `FobFormat::Decimal` takes decimal only.
+//!   W6: a `CredentialFormat` hint picks exactly one of `to_fob`,
+//!       `to_nfc_uid` and `to_nfc7_uid`.
+//!   W7: a `PulseFilter` rejects an edge only when its line was seen to
+//!       rise before the minimum width; off, it accepts every edge.
+//!   W8: `raw_data` is the data bits in wire order; the configured
+//!       `ByteOrder` alone decides how they map to a 4-byte UID.
+//!   W9: a frame of more than MAX_FRAME_BITS bits is rejected whole as
+//!       over-length, however long it runs, and `finish` always resets:
+//!       the frame after any rejected one decodes as if sent alone.
+//!  W10: with a `FrameGap`, a bit within the gap after a completed frame
+//!       is rejected and restarts the gap; once the line has been quiet
+//!       for the gap, the next bit is accepted. Off, every bit is.
 //!
 //! Run with:
 //!   cargo test --no-default-features --features sim \
@@ -7,7 +23,12 @@
 
 #![cfg(feature = "sim")]
 
-use access_controller::decode::{decode_26, decode_34, encode_26, encode_34, WiegandRead};
+use access_controller::decode::{
+    decode_26, decode_34, encode_26, encode_34, encode_34_data, ByteOrder, CredentialFormat,
+    FobFormat, FrameBits, FrameError, FrameGap, PulseFilter, WiegandRead, MAX_FRAME_BITS,
+    MAX_FRAME_GAP_MS, MAX_MIN_PULSE_US,
+};
+use access_controller::core::CardRead;
 use proptest::prelude::*;
 
 // ---------------------------------------------------------------------------
@@ -168,3 +189,333 @@ proptest! {
         }
     }
 }
+
+// ---------------------------------------------------------------------------
+// W5: fob ID formats
+// ---------------------------------------------------------------------------
+
+#[test]
+fn normalize_hex_decimal_and_facility_agree() {
+    let w = WiegandRead { facility: 123, card: 45678, raw_data: 0 };
+    let fob = w.to_fob();
+    for s in ["12345678", "0xBC614E", "0x00bc614e", "0XBC614E", "123:45678", "123-45678"] {
+        assert_eq!(FobFormat::Normalize.read(s), Some(fob), "{:?}", s);
+    }
+    assert_eq!(FobFormat::Normalize.read("0xFFFFFFFF"), Some(u32::MAX));
+    assert_eq!(FobFormat::Normalize.read("0:1"), Some(1));
+}
+
+#[test]
+fn normalize_rejects_ambiguous_or_out_of_range() {
+    for s in [
+        "", "0x", "0x1G", "0x100000000", "+5", " 5", "-5", "5-", ":5",
+        "123:65536", "65536:1", "1:2:3", "12 34", "0b101",
+    ] {
+        assert_eq!(FobFormat::Normalize.read(s), None, "{:?}", s);
+    }
+    // 16-bit facilities past 42949 don't fit `to_fob`'s u32.
+    assert_eq!(FobFormat::Normalize.read("42949:65535"), Some(4_294_965_535));
+    assert_eq!(FobFormat::Normalize.read("42950:0"), None);
+}
+
+#[test]
+fn decimal_format_is_decimal_only() {
+    assert_eq!(FobFormat::Decimal.read("12345678"), Some(12_345_678));
+    assert_eq!(FobFormat::Decimal.read("0xBC614E"), None);
+    assert_eq!(FobFormat::Decimal.read("123:45678"), None);
+    assert_eq!(FobFormat::default(), FobFormat::Decimal);
+    assert_eq!(FobFormat::parse("normalize"), Some(FobFormat::Normalize));
+    assert_eq!(FobFormat::parse("decimal"), Some(FobFormat::Decimal));
+    assert_eq!(FobFormat::parse("hex"), None);
+}
+
+// ---------------------------------------------------------------------------
+// W6: credential-format hints
+// ---------------------------------------------------------------------------
+
+#[test]
+fn credential_format_picks_one_conversion() {
+    let w = decode_34(encode_34(0xAB, 0xCDEF)).unwrap();
+    assert_eq!(CredentialFormat::Fob.extract(&w, ByteOrder::Lsb), w.to_fob());
+    assert_eq!(CredentialFormat::Nfc4.extract(&w, ByteOrder::Lsb), w.to_nfc_uid());
+    assert_eq!(CredentialFormat::Nfc7.extract(&w, ByteOrder::Lsb), w.to_nfc7_uid());
+    assert_eq!(w.to_nfc_uid(), 0xEFCD_AB00);
+    assert_eq!(w.to_nfc7_uid(), 0x00AB_CDEF);
+}
+
+#[test]
+fn credential_format_parse() {
+    assert_eq!(CredentialFormat::parse("fob"), Some(CredentialFormat::Fob));
+    assert_eq!(CredentialFormat::parse("NFC4"), Some(CredentialFormat::Nfc4));
+    assert_eq!(CredentialFormat::parse(" nfc7 "), Some(CredentialFormat::Nfc7));
+    for s in ["", "nfc", "nfc10", "h10301"] {
+        assert_eq!(CredentialFormat::parse(s), None, "{:?}", s);
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_normalized_forms_match_to_fob(facility in 0u32..256, card in 0u32..(1 << 16)) {
+        let fob = WiegandRead { facility, card, raw_data: 0 }.to_fob();
+        prop_assert_eq!(FobFormat::Normalize.read(&fob.to_string()), Some(fob));
+        prop_assert_eq!(FobFormat::Normalize.read(&format!("{:#x}", fob)), Some(fob));
+        prop_assert_eq!(FobFormat::Normalize.read(&format!("{}:{}", facility, card)), Some(fob));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// W7: pulse-width glitch filter
+// ---------------------------------------------------------------------------
+
+#[test]
+fn pulse_filter_rejects_short_pulses() {
+    let filter = PulseFilter { min_low_us: 10 };
+    // A spike already gone when the reader woke, or gone within the window.
+    assert!(!filter.accept(Some(0)));
+    assert!(!filter.accept(Some(9)));
+    // Held for the window (seen rising at its end, or not at all).
+    assert!(filter.accept(Some(10)));
+    assert!(filter.accept(None));
+}
+
+#[test]
+fn pulse_filter_off_accepts_everything() {
+    let off = PulseFilter::default();
+    assert!(!off.enabled());
+    assert!(off.accept(Some(0)));
+    assert!(off.accept(None));
+}
+
+#[test]
+fn pulse_filter_parse_bounds() {
+    assert_eq!(PulseFilter::parse("0"), Some(PulseFilter { min_low_us: 0 }));
+    assert_eq!(PulseFilter::parse("10"), Some(PulseFilter { min_low_us: 10 }));
+    assert_eq!(
+        PulseFilter::parse(&MAX_MIN_PULSE_US.to_string()),
+        Some(PulseFilter { min_low_us: MAX_MIN_PULSE_US })
+    );
+    assert_eq!(PulseFilter::parse(&(MAX_MIN_PULSE_US + 1).to_string()), None);
+    assert_eq!(PulseFilter::parse("-5"), None);
+    assert_eq!(PulseFilter::parse("10us"), None);
+}
+
+proptest! {
+    /// W7: a pulse seen low for `low_us` after the wake passes exactly when
+    /// it lasted the filter's minimum.
+    #[test]
+    fn pulse_filter_threshold(min in 1u32..=MAX_MIN_PULSE_US, low_us in 0u64..200) {
+        let filter = PulseFilter { min_low_us: min };
+        prop_assert_eq!(filter.accept(Some(low_us)), low_us >= u64::from(min));
+        prop_assert!(filter.accept(None));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// W8: UID byte order
+// ---------------------------------------------------------------------------
+
+/// The 34-bit frame a reader sending bytes in `order` produces for `uid`.
+fn uid_frame(uid: [u8; 4], order: ByteOrder) -> WiegandRead {
+    let wire = match order {
+        ByteOrder::Msb => uid,
+        ByteOrder::Lsb => [uid[3], uid[2], uid[1], uid[0]],
+    };
+    decode_34(encode_34_data(u32::from_be_bytes(wire))).unwrap()
+}
+
+#[test]
+fn raw_data_is_wire_order() {
+    // First data byte on the wire is the top byte of raw_data.
+    let w = decode_34(encode_34_data(0x04A1_B2C3)).unwrap();
+    assert_eq!(w.raw_data, 0x04A1_B2C3);
+}
+
+#[test]
+fn both_orders_recover_the_uid() {
+    let uid = [0x04, 0xA1, 0xB2, 0xC3];
+    for order in [ByteOrder::Lsb, ByteOrder::Msb] {
+        let w = uid_frame(uid, order);
+        assert_eq!(w.nfc_uid(order), 0x04A1_B2C3, "{:?}", order);
+        assert_eq!(CredentialFormat::Nfc4.extract(&w, order), 0x04A1_B2C3);
+        assert_eq!(CardRead::from_read(&w, None, order).nfc, 0x04A1_B2C3);
+    }
+    // to_nfc_uid keeps its LSB-first meaning.
+    assert_eq!(uid_frame(uid, ByteOrder::Lsb).to_nfc_uid(), 0x04A1_B2C3);
+}
+
+#[test]
+fn same_bits_differ_by_order() {
+    let w = decode_34(encode_34_data(0x04A1_B2C3)).unwrap();
+    assert_eq!(w.nfc_uid(ByteOrder::Msb), 0x04A1_B2C3);
+    assert_eq!(w.nfc_uid(ByteOrder::Lsb), 0xC3B2_A104);
+    // Neither the fob number nor a truncated 7-byte UID depends on it.
+    for order in [ByteOrder::Lsb, ByteOrder::Msb] {
+        assert_eq!(CredentialFormat::Fob.extract(&w, order), w.to_fob());
+        assert_eq!(CredentialFormat::Nfc7.extract(&w, order), 0x04A1_B2C3);
+    }
+}
+
+#[test]
+fn byte_order_parse() {
+    assert_eq!(ByteOrder::parse("lsb"), Some(ByteOrder::Lsb));
+    assert_eq!(ByteOrder::parse("msb"), Some(ByteOrder::Msb));
+    assert_eq!(ByteOrder::parse("big"), None);
+    assert_eq!(ByteOrder::default(), ByteOrder::Lsb);
+}
+
+proptest! {
+    /// W8: whatever the UID, a reader in either order round-trips it.
+    #[test]
+    fn uid_round_trips_in_both_orders(uid in any::<[u8; 4]>()) {
+        for order in [ByteOrder::Lsb, ByteOrder::Msb] {
+            prop_assert_eq!(uid_frame(uid, order).nfc_uid(order), u32::from_be_bytes(uid));
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// W9: over-length frames
+// ---------------------------------------------------------------------------
+
+/// Record `count` bits of `bits`, MSB first.
+fn record(frame: &mut FrameBits, bits: u64, count: u32) {
+    for i in (0..count).rev() {
+        frame.record_bit((bits >> i) as u8 & 1);
+    }
+}
+
+#[test]
+fn frame_bits_decode_supported_lengths() {
+    let mut frame = FrameBits::new();
+    record(&mut frame, encode_26(12, 3456), 26);
+    assert_eq!(frame.finish(), Ok(decode_26(encode_26(12, 3456)).unwrap()));
+    record(&mut frame, encode_34(12, 3456), 34);
+    assert_eq!(frame.finish(), Ok(decode_34(encode_34(12, 3456)).unwrap()));
+    record(&mut frame, encode_26(12, 3456) ^ 1, 26);
+    assert_eq!(frame.finish(), Err(FrameError::Parity(26)));
+    record(&mut frame, 0, 30);
+    assert_eq!(frame.finish(), Err(FrameError::UnknownFormat(30)));
+}
+
+#[test]
+fn over_length_detected_at_the_first_extra_bit() {
+    let mut frame = FrameBits::new();
+    for _ in 0..MAX_FRAME_BITS {
+        assert!(frame.record_bit(1));
+    }
+    assert!(!frame.overlong());
+    assert!(!frame.record_bit(0));
+    assert!(frame.overlong());
+    assert!(!frame.record_bit(1));
+    assert_eq!(frame.count(), MAX_FRAME_BITS + 2);
+    assert_eq!(frame.finish(), Err(FrameError::Overlong(MAX_FRAME_BITS + 2)));
+    assert_eq!(frame, FrameBits::new());
+}
+
+#[test]
+fn exactly_max_bits_is_not_over_length() {
+    let mut frame = FrameBits::new();
+    record(&mut frame, u64::MAX, MAX_FRAME_BITS);
+    assert_eq!(frame.finish(), Err(FrameError::UnknownFormat(MAX_FRAME_BITS)));
+}
+
+#[test]
+fn valid_read_after_over_length_frame() {
+    let mut frame = FrameBits::new();
+    record(&mut frame, u64::MAX, 64);
+    record(&mut frame, u64::MAX, 40);
+    assert_eq!(frame.finish(), Err(FrameError::Overlong(104)));
+    record(&mut frame, encode_26(1, 2), 26);
+    assert_eq!(frame.finish(), Ok(decode_26(encode_26(1, 2)).unwrap()));
+}
+
+proptest! {
+    /// W9: any over-length burst is rejected with its full length, and
+    /// a valid frame right after it decodes unchanged.
+    #[test]
+    fn over_length_resets_for_the_next_frame(
+        junk in prop::collection::vec(0u8..2, (MAX_FRAME_BITS as usize + 1)..300),
+        facility in 0u32..256,
+        card in 0u32..65_536,
+    ) {
+        let mut frame = FrameBits::new();
+        for &bit in &junk {
+            frame.record_bit(bit);
+        }
+        prop_assert!(frame.overlong());
+        prop_assert_eq!(frame.finish(), Err(FrameError::Overlong(junk.len() as u32)));
+        record(&mut frame, encode_34(facility, card), 34);
+        prop_assert_eq!(frame.finish(), Ok(decode_34(encode_34(facility, card)).unwrap()));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// W10: inter-frame gap
+// ---------------------------------------------------------------------------
+
+#[test]
+fn gap_rejects_bits_too_soon_after_a_read() {
+    let mut gap = FrameGap::new(100);
+    // Nothing read yet: no gap to enforce.
+    assert!(gap.accept(0));
+    gap.frame_ended(1_000_000);
+    assert!(!gap.accept(1_000_001));
+    assert!(!gap.accept(1_099_999));
+    // Each rejected bit restarted the gap.
+    assert!(!gap.accept(1_150_000));
+    assert!(gap.accept(1_250_000));
+    // Accepted: the frame in progress is not checked again.
+    assert!(gap.accept(1_250_010));
+}
+
+#[test]
+fn gap_accepts_after_quiet() {
+    let mut gap = FrameGap::new(100);
+    gap.frame_ended(5_000);
+    assert!(gap.accept(105_000));
+}
+
+#[test]
+fn gap_off_accepts_everything() {
+    let mut gap = FrameGap::default();
+    assert!(!gap.enabled());
+    gap.frame_ended(0);
+    assert!(gap.accept(0));
+    assert!(gap.accept(1));
+}
+
+#[test]
+fn gap_parse_bounds() {
+    assert_eq!(FrameGap::parse("0"), Some(FrameGap::new(0)));
+    assert_eq!(FrameGap::parse("100"), Some(FrameGap::new(100)));
+    assert_eq!(
+        FrameGap::parse(&MAX_FRAME_GAP_MS.to_string()),
+        Some(FrameGap::new(MAX_FRAME_GAP_MS))
+    );
+    assert_eq!(FrameGap::parse(&(MAX_FRAME_GAP_MS + 1).to_string()), None);
+    assert_eq!(FrameGap::parse("100ms"), None);
+}
+
+proptest! {
+    /// W10: after a frame ends, bits keep being rejected until one comes
+    /// at least the gap after the previous bit (or the end of the frame).
+    #[test]
+    fn gap_needs_quiet_since_the_last_bit(
+        min in 1u32..=MAX_FRAME_GAP_MS,
+        ended in 0u64..1_000_000_000,
+        dts in prop::collection::vec(0u64..2_000_000, 1..20),
+    ) {
+        let mut gap = FrameGap::new(min);
+        gap.frame_ended(ended);
+        let mut last = ended;
+        for dt in dts {
+            let now = last + dt;
+            let ok = gap.accept(now);
+            prop_assert_eq!(ok, dt >= u64::from(min) * 1_000);
+            if ok {
+                break;
+            }
+            last = now;
+        }
+    }
+}
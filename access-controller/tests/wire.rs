@@ -0,0 +1,427 @@
+//! Tests for the binary and NDJSON sync encodings (invariants W1–W7).
+//!
+//!   W1: events, with their first sequence number, and fob lists
+//!       round-trip through encode/decode.
+//!   W2: the frame's length prefix must match the message exactly;
+//!       truncated, padded or compressed frames are rejected.
+//!   W3: a fob list longer than the decoder's capacity is an error,
+//!       never a silently truncated list.
+//!   W4: the response protocol follows its Content-Type.
+//!   W5: NDJSON is one `{"fob":N,"allowed":B,"seq":N}` object per line,
+//!       numbered on from the first, any number of events, and
+//!       `ndjson_len` is the exact body length.
+//!   W6: `CONWAY_EVENT_FIELDS` renames any of the three fields to a
+//!       short, JSON-safe, distinct name and nothing else; renamed lines
+//!       carry the same values and still fit `NDJSON_LINE_MAX`.
+//!   W7: compact events are `[fob,allowed,seq]` arrays, shorter than
+//!       verbose ones and marked `events=compact` in the media type; the
+//!       encoding follows the knob, or with `auto` a server's
+//!       `Accept-Post`.
+//!
+//! Run with:
+//!   cargo test --no-default-features --features sim \
+//!              --target x86_64-unknown-linux-gnu \
+//!              --test wire
+
+#![cfg(feature = "sim")]
+
+use access_controller::events::AccessEvent;
+use access_controller::fob_cache::MAX_FOBS;
+use access_controller::wire::{
+    decode_events, decode_fob_list, encode_events, encode_fob_list, ndjson_len, ndjson_line, numbered,
+    EncodingChoice, EventEncoding, EventFields, EventFormat, SyncProtocol, CONTENT_TYPE,
+    JSON_CONTENT_TYPE, MAX_FIELD_NAME, NDJSON_CONTENT_TYPE, NDJSON_LINE_MAX,
+};
+use proptest::prelude::*;
+
+fn cfg() -> ProptestConfig {
+    ProptestConfig {
+        cases: 256,
+        rng_algorithm: prop::test_runner::RngAlgorithm::ChaCha,
+        ..ProptestConfig::default()
+    }
+}
+
+#[test]
+fn fixed_layout() {
+    let ev = [AccessEvent {
+        fob: 0x0102_0304,
+        allowed: true,
+    }];
+    assert_eq!(
+        encode_events(&ev, 0x0102),
+        [0, 0, 0, 0, 15, 1, 0, 2, 1, 0, 0, 0, 0, 0, 0, 4, 3, 2, 1, 1]
+    );
+    assert_eq!(encode_fob_list(&[]), [0, 0, 0, 0, 2, 0, 0]);
+    assert_eq!(
+        encode_fob_list(&[123, 234]),
+        [0, 0, 0, 0, 10, 2, 0, 123, 0, 0, 0, 234, 0, 0, 0]
+    );
+}
+
+proptest! {
+    #![proptest_config(cfg())]
+
+    /// W1.
+    #[test]
+    fn round_trip(
+        events in prop::collection::vec((any::<u32>(), any::<bool>()), 0..20),
+        first_seq in any::<u64>(),
+        fobs in prop::collection::vec(any::<u32>(), 0..=MAX_FOBS),
+    ) {
+        let events: Vec<AccessEvent> = events
+            .into_iter()
+            .map(|(fob, allowed)| AccessEvent { fob, allowed })
+            .collect();
+        let (seq, decoded) = decode_events::<20>(&encode_events(&events, first_seq)).unwrap();
+        prop_assert_eq!(seq, first_seq);
+        prop_assert_eq!(decoded.as_slice(), events.as_slice());
+
+        let decoded = decode_fob_list::<MAX_FOBS>(&encode_fob_list(&fobs)).unwrap();
+        prop_assert_eq!(decoded.as_slice(), fobs.as_slice());
+    }
+
+    /// W2: any single-byte truncation or extension is caught.
+    #[test]
+    fn length_prefix_is_exact(fobs in prop::collection::vec(any::<u32>(), 0..64)) {
+        let buf = encode_fob_list(&fobs);
+        prop_assert!(decode_fob_list::<64>(&buf[..buf.len() - 1]).is_err());
+        let mut longer = buf.clone();
+        longer.push(0);
+        prop_assert!(decode_fob_list::<64>(&longer).is_err());
+    }
+}
+
+#[test]
+fn rejects_malformed_frames() {
+    // W2
+    assert!(decode_fob_list::<4>(&[]).is_err());
+    assert!(decode_fob_list::<4>(&[0, 0, 0]).is_err());
+    let mut compressed = encode_fob_list(&[1]);
+    compressed[0] = 1;
+    assert!(decode_fob_list::<4>(&compressed).is_err());
+    // Frame length agrees with the buffer but the count does not.
+    assert!(decode_fob_list::<4>(&[0, 0, 0, 0, 6, 2, 0, 1, 0, 0, 0]).is_err());
+
+    let mut bad_flag = encode_events(
+        &[AccessEvent {
+            fob: 1,
+            allowed: false,
+        }],
+        0,
+    );
+    *bad_flag.last_mut().unwrap() = 2;
+    assert!(decode_events::<4>(&bad_flag).is_err());
+    // The sequence number is not optional.
+    assert!(decode_events::<4>(&[0, 0, 0, 0, 7, 1, 0, 1, 0, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn over_capacity_is_an_error() {
+    // W3
+    let fobs: Vec<u32> = (0..5).collect();
+    assert!(decode_fob_list::<5>(&encode_fob_list(&fobs)).is_ok());
+    assert!(decode_fob_list::<4>(&encode_fob_list(&fobs)).is_err());
+    let events = [AccessEvent::default(); 3];
+    assert!(decode_events::<2>(&encode_events(&events, 0)).is_err());
+}
+
+#[test]
+fn protocol_from_content_type() {
+    // W4
+    assert_eq!(
+        SyncProtocol::from_content_type(Some(CONTENT_TYPE)),
+        SyncProtocol::Binary
+    );
+    assert_eq!(
+        SyncProtocol::from_content_type(Some("Application/X-Conway-Fobs; v=1")),
+        SyncProtocol::Binary
+    );
+    assert_eq!(
+        SyncProtocol::from_content_type(Some("application/json; charset=utf-8")),
+        SyncProtocol::Json
+    );
+    assert_eq!(SyncProtocol::from_content_type(None), SyncProtocol::Json);
+    assert_eq!(SyncProtocol::parse("binary"), Some(SyncProtocol::Binary));
+    assert_eq!(SyncProtocol::parse("json"), Some(SyncProtocol::Json));
+    assert_eq!(SyncProtocol::parse("protobuf"), None);
+    assert_eq!(SyncProtocol::Binary.content_type(), CONTENT_TYPE);
+    assert_eq!(SyncProtocol::parse("ndjson"), Some(SyncProtocol::Ndjson));
+    assert_eq!(SyncProtocol::Ndjson.content_type(), NDJSON_CONTENT_TYPE);
+    // Only requests are NDJSON; such a response is read as JSON.
+    assert_eq!(
+        SyncProtocol::from_content_type(Some(NDJSON_CONTENT_TYPE)),
+        SyncProtocol::Json
+    );
+}
+
+// ---------- W5 ----------
+
+#[test]
+fn ndjson_line_format() {
+    let line = |fob, allowed, seq| ndjson_line(&AccessEvent { fob, allowed }, seq, &EventFormat::DEFAULT);
+    assert_eq!(line(1234, true, 7), "{\"fob\":1234,\"allowed\":true,\"seq\":7}\n");
+    assert_eq!(line(0, false, 0), "{\"fob\":0,\"allowed\":false,\"seq\":0}\n");
+    let longest = line(u32::MAX, false, u64::MAX);
+    assert_eq!(
+        longest,
+        "{\"fob\":4294967295,\"allowed\":false,\"seq\":18446744073709551615}\n"
+    );
+    assert!(longest.len() <= NDJSON_LINE_MAX);
+    assert_eq!(ndjson_len(&[], 0, &EventFormat::DEFAULT), 0);
+}
+
+/// Stream `events`, numbered from `first_seq`, the way the sync does:
+/// one line per write.
+fn stream(events: &[AccessEvent], first_seq: u64) -> String {
+    stream_as(events, first_seq, &EventFormat::DEFAULT)
+}
+
+fn stream_as(events: &[AccessEvent], first_seq: u64, format: &EventFormat) -> String {
+    let mut body = String::new();
+    for (seq, e) in numbered(events, first_seq) {
+        body.push_str(&ndjson_line(e, seq, format));
+    }
+    body
+}
+
+#[test]
+fn ndjson_streams_more_than_a_ring() {
+    // Far past MAX_EVENTS and the old 512-byte JSON body.
+    let events: Vec<AccessEvent> = (0..500)
+        .map(|i| AccessEvent {
+            fob: u32::MAX - i,
+            allowed: i % 3 == 0,
+        })
+        .collect();
+    let body = stream(&events, 1000);
+    assert_eq!(body.len(), ndjson_len(&events, 1000, &EventFormat::DEFAULT));
+    assert!(body.len() > 500 * 40);
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), events.len());
+    assert_eq!(lines[0], "{\"fob\":4294967295,\"allowed\":true,\"seq\":1000}");
+    assert_eq!(lines[499], "{\"fob\":4294966796,\"allowed\":false,\"seq\":1499}");
+}
+
+proptest! {
+    #![proptest_config(cfg())]
+
+    /// W5: every line parses back to its event.
+    #[test]
+    fn ndjson_lines_round_trip(
+        events in prop::collection::vec((any::<u32>(), any::<bool>()), 0..100),
+        first_seq in 0..u64::MAX - 100,
+    ) {
+        let events: Vec<AccessEvent> = events
+            .into_iter()
+            .map(|(fob, allowed)| AccessEvent { fob, allowed })
+            .collect();
+        let body = stream(&events, first_seq);
+        prop_assert_eq!(body.len(), ndjson_len(&events, first_seq, &EventFormat::DEFAULT));
+        prop_assert!(body.is_empty() || body.ends_with('\n'));
+        for ((line, e), want_seq) in body.lines().zip(&events).zip(first_seq..) {
+            let rest = line.strip_prefix("{\"fob\":").unwrap();
+            let (fob, rest) = rest.strip_suffix('}').unwrap().split_once(",\"allowed\":").unwrap();
+            let (allowed, seq) = rest.split_once(",\"seq\":").unwrap();
+            prop_assert_eq!(fob.parse::<u32>().unwrap(), e.fob);
+            prop_assert_eq!(allowed.parse::<bool>().unwrap(), e.allowed);
+            prop_assert_eq!(seq.parse::<u64>().unwrap(), want_seq);
+        }
+        prop_assert_eq!(body.lines().count(), events.len());
+    }
+}
+
+// ---------- W6 ----------
+
+#[test]
+fn event_fields_parse() {
+    assert_eq!(EventFields::parse(""), Some(EventFields::DEFAULT));
+    assert_eq!(
+        EventFields::parse("fob=credential, allowed=granted"),
+        Some(EventFields {
+            fob: "credential",
+            allowed: "granted",
+            seq: "seq",
+        })
+    );
+    assert_eq!(
+        EventFields::parse("seq=event_id").map(|f| f.seq),
+        Some("event_id")
+    );
+    // Swapping two names is fine; they stay distinct.
+    assert!(EventFields::parse("fob=seq,seq=fob").is_some());
+    let longest = "x".repeat(MAX_FIELD_NAME);
+    assert!(EventFields::parse(&format!("fob={}", longest)).is_some());
+
+    let too_long = format!("fob={}x", longest);
+    for bad in [
+        "fob",
+        "fob=",
+        "card=credential",
+        "fob=a,fob=b",
+        "fob=allowed",
+        "fob=a,seq=a",
+        "fob=cred\"ential",
+        "fob=cred ential",
+        "fob=crédit",
+        "fob=a,",
+        too_long.as_str(),
+    ] {
+        assert_eq!(EventFields::parse(bad), None, "{:?}", bad);
+    }
+}
+
+#[test]
+fn renamed_ndjson_lines() {
+    let format = EventFormat {
+        fields: EventFields::parse("fob=credential,allowed=granted").unwrap(),
+        ..EventFormat::DEFAULT
+    };
+    let events = [
+        AccessEvent { fob: 1234, allowed: true },
+        AccessEvent { fob: 5, allowed: false },
+    ];
+    let body = stream_as(&events, 41, &format);
+    assert_eq!(
+        body,
+        "{\"credential\":1234,\"granted\":true,\"seq\":41}\n\
+         {\"credential\":5,\"granted\":false,\"seq\":42}\n"
+    );
+    assert_eq!(body.len(), ndjson_len(&events, 41, &format));
+
+    let name = "x".repeat(MAX_FIELD_NAME);
+    let (a, b, c) = (name.replace('x', "a"), name.replace('x', "b"), name.replace('x', "c"));
+    let longest_format = EventFormat {
+        fields: EventFields { fob: &a, allowed: &b, seq: &c },
+        ..EventFormat::DEFAULT
+    };
+    let longest = ndjson_line(
+        &AccessEvent { fob: u32::MAX, allowed: false },
+        u64::MAX,
+        &longest_format,
+    );
+    assert_eq!(longest.len(), NDJSON_LINE_MAX);
+    assert!(longest.ends_with("18446744073709551615}\n"));
+}
+
+// ---------- W7 ----------
+
+fn compact() -> EventFormat<'static> {
+    EventFormat {
+        encoding: EventEncoding::Compact,
+        ..EventFormat::DEFAULT
+    }
+}
+
+#[test]
+fn compact_ndjson_lines() {
+    let events = [
+        AccessEvent { fob: 1234, allowed: true },
+        AccessEvent { fob: 5678, allowed: false },
+    ];
+    let body = stream_as(&events, 41, &compact());
+    assert_eq!(body, "[1234,true,41]\n[5678,false,42]\n");
+    assert_eq!(body.len(), ndjson_len(&events, 41, &compact()));
+    // Field names play no part.
+    let renamed = EventFormat {
+        fields: EventFields::parse("fob=credential").unwrap(),
+        ..compact()
+    };
+    assert_eq!(stream_as(&events, 41, &renamed), body);
+    let longest = ndjson_line(&AccessEvent { fob: u32::MAX, allowed: false }, u64::MAX, &compact());
+    assert_eq!(longest, "[4294967295,false,18446744073709551615]\n");
+}
+
+#[test]
+fn compact_content_types() {
+    let verbose = EventEncoding::Verbose;
+    let compact = EventEncoding::Compact;
+    for protocol in [SyncProtocol::Json, SyncProtocol::Binary, SyncProtocol::Ndjson] {
+        assert_eq!(verbose.content_type(protocol), protocol.content_type());
+    }
+    assert_eq!(compact.content_type(SyncProtocol::Json), "application/json; events=compact");
+    assert_eq!(
+        compact.content_type(SyncProtocol::Ndjson),
+        "application/x-ndjson; events=compact"
+    );
+    assert_eq!(compact.content_type(SyncProtocol::Binary), CONTENT_TYPE);
+    // A compact body still reads as JSON on the way back.
+    assert_eq!(
+        SyncProtocol::from_content_type(Some(compact.content_type(SyncProtocol::Json))).content_type(),
+        JSON_CONTENT_TYPE
+    );
+}
+
+#[test]
+fn accept_post_negotiation() {
+    let accept = |v| EventEncoding::from_accept_post(Some(v));
+    assert_eq!(EventEncoding::from_accept_post(None), None);
+    assert_eq!(accept("application/json"), Some(EventEncoding::Verbose));
+    assert_eq!(accept(""), Some(EventEncoding::Verbose));
+    assert_eq!(
+        accept("application/json; events=compact"),
+        Some(EventEncoding::Compact)
+    );
+    assert_eq!(
+        accept("application/x-conway-fobs, application/json;Events=\"Compact\""),
+        Some(EventEncoding::Compact)
+    );
+    assert_eq!(
+        accept("application/json; events=verbose"),
+        Some(EventEncoding::Verbose)
+    );
+    // The parameter, not a look-alike in the media type itself.
+    assert_eq!(accept("application/events=compact"), Some(EventEncoding::Verbose));
+}
+
+#[test]
+fn encoding_choice() {
+    assert_eq!(EncodingChoice::parse("verbose"), Some(EncodingChoice::default()));
+    assert_eq!(
+        EncodingChoice::parse("compact"),
+        Some(EncodingChoice::Fixed(EventEncoding::Compact))
+    );
+    assert_eq!(EncodingChoice::parse("auto"), Some(EncodingChoice::Negotiate));
+    assert_eq!(EncodingChoice::parse("array"), None);
+
+    let all = [None, Some(EventEncoding::Verbose), Some(EventEncoding::Compact)];
+    for accepted in all {
+        for fixed in [EventEncoding::Verbose, EventEncoding::Compact] {
+            assert_eq!(EncodingChoice::Fixed(fixed).pick(accepted), fixed);
+        }
+    }
+    let negotiate = EncodingChoice::Negotiate;
+    assert_eq!(negotiate.pick(None), EventEncoding::Verbose);
+    assert_eq!(negotiate.pick(Some(EventEncoding::Compact)), EventEncoding::Compact);
+    assert_eq!(negotiate.pick(Some(EventEncoding::Verbose)), EventEncoding::Verbose);
+}
+
+proptest! {
+    #![proptest_config(cfg())]
+
+    /// W7: every compact line parses back to its event and is shorter
+    /// than the verbose one.
+    #[test]
+    fn compact_lines_round_trip(
+        events in prop::collection::vec((any::<u32>(), any::<bool>()), 0..50),
+        first_seq in 0..u64::MAX - 50,
+    ) {
+        let events: Vec<AccessEvent> = events
+            .into_iter()
+            .map(|(fob, allowed)| AccessEvent { fob, allowed })
+            .collect();
+        let body = stream_as(&events, first_seq, &compact());
+        for ((line, e), want_seq) in body.lines().zip(&events).zip(first_seq..) {
+            let inner = line.strip_prefix('[').unwrap().strip_suffix(']').unwrap();
+            let mut values = inner.split(',');
+            prop_assert_eq!(values.next().unwrap().parse::<u32>().unwrap(), e.fob);
+            prop_assert_eq!(values.next().unwrap().parse::<bool>().unwrap(), e.allowed);
+            prop_assert_eq!(values.next().unwrap().parse::<u64>().unwrap(), want_seq);
+            prop_assert!(values.next().is_none());
+        }
+        prop_assert_eq!(body.lines().count(), events.len());
+        prop_assert!(
+            events.is_empty() || body.len() < stream(&events, first_seq).len()
+        );
+    }
+}
@@ -77,8 +77,10 @@ fi
 # Extract the BLK3 line(s); espefuse prints something like:
 #   BLK3 (BLOCK3): Variable Block 3
 #      = 00 00 00 00 00 00 00 00 ... R/W
+# The ESP32-S3 names the same block BLOCK_USR_DATA (BLOCK3), and also
+# accepts BLOCK3 as its name for burn_block_data below.
 BLK3_HEX="$(echo "$SUMMARY" | awk '
-    /BLK3 \(BLOCK3\)/ { in_blk = 1; next }
+    /(BLK3|BLOCK_USR_DATA) \(BLOCK3\)/ { in_blk = 1; next }
     in_blk && /^[[:space:]]+= / { print; exit }
 ')"
 
@@ -140,7 +142,7 @@ fi
 
 echo "==> Verifying readback ..."
 VERIFY="$(espefuse.py --port "$PORT" summary 2>/dev/null | awk '
-    /BLK3 \(BLOCK3\)/ { in_blk = 1; next }
+    /(BLK3|BLOCK_USR_DATA) \(BLOCK3\)/ { in_blk = 1; next }
     in_blk && /^[[:space:]]+= / { print; exit }
 ')"
 echo "    $VERIFY"
@@ -1,10 +1,12 @@
 package engine
 
 import (
+	"bufio"
 	"context"
 	"database/sql"
 	"embed"
 	"log/slog"
+	"net"
 	"net/http"
 	"strings"
 	"time"
@@ -150,6 +152,12 @@ func (w *responseWrapper) WriteHeader(status int) {
 	w.ResponseWriter.WriteHeader(status)
 }
 
+// Hijack implements http.Hijacker so handlers can take over the connection
+// (e.g., WebSocket upgrades).
+func (w *responseWrapper) Hijack() (net.Conn, *bufio.ReadWriter, error) {
+	return http.NewResponseController(w.ResponseWriter).Hijack()
+}
+
 // Flush implements http.Flusher to support streaming responses (e.g., MJPEG).
 func (w *responseWrapper) Flush() {
 	if f, ok := w.ResponseWriter.(http.Flusher); ok {
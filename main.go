@@ -30,6 +30,10 @@ type Config struct {
 
 	TurnstileSiteKey string
 	TurnstileSecret  string
+
+	// ResponseHMACKey is the secret shared with access controllers that
+	// check fob API responses by HMAC (CONWAY_RESPONSE_HMAC_KEY on both).
+	ResponseHMACKey string `env:"RESPONSE_HMAC_KEY"`
 }
 
 func main() {
@@ -95,6 +99,7 @@ func newApp(conf Config, self *url.URL) (*engine.App, error) {
 		DiscordIssuer:        engine.NewTokenIssuer("discord-oauth.pem"),
 		GoogleIssuer:         engine.NewTokenIssuer("google-oauth.pem"),
 		FobSigner:            engine.NewEd25519Signer("fob-signing.ed25519"),
+		FobHMACKey:           []byte(conf.ResponseHMACKey),
 		Turnstile:     tso,
 		EmailSender:   sender,
 		SpaceHost:     conf.SpaceHost,
@@ -0,0 +1,100 @@
+package fobapi
+
+import (
+	"encoding/binary"
+	"errors"
+	"fmt"
+	"math"
+	"mime"
+)
+
+// BinaryContentType selects the compact binary encoding of POST /api/fobs.
+// Requests sent with this Content-Type carry binary events and get a binary
+// fob list back; everything else is JSON.
+//
+// Each body is one gRPC-style length-prefixed message: a zero flag byte, the
+// big-endian uint32 message length, then the message. Requests hold a
+// little-endian uint16 event count, the uint64 sequence number of the first
+// event (the rest follow on from it), then (uint32 fob, uint8 allowed)
+// records; responses hold a uint16 fob count followed by packed uint32 fob IDs.
+// A request without the sequence number, from an older controller, is still
+// accepted and its events stored unnumbered.
+const BinaryContentType = "application/x-conway-fobs"
+
+func isBinary(contentType string) bool {
+	mt, _, err := mime.ParseMediaType(contentType)
+	return err == nil && mt == BinaryContentType
+}
+
+func frame(msg []byte) []byte {
+	out := make([]byte, 5, 5+len(msg))
+	binary.BigEndian.PutUint32(out[1:], uint32(len(msg)))
+	return append(out, msg...)
+}
+
+func unframe(buf []byte) ([]byte, error) {
+	if len(buf) < 5 {
+		return nil, errors.New("short frame")
+	}
+	if buf[0] != 0 {
+		return nil, errors.New("compressed frames are not supported")
+	}
+	if n := binary.BigEndian.Uint32(buf[1:5]); uint64(n) != uint64(len(buf)-5) {
+		return nil, errors.New("frame length mismatch")
+	}
+	return buf[5:], nil
+}
+
+func decodeBinaryEvents(buf []byte) ([]*fobEvent, error) {
+	msg, err := unframe(buf)
+	if err != nil {
+		return nil, err
+	}
+	if len(msg) < 2 {
+		return nil, errors.New("short event list")
+	}
+	count := int(binary.LittleEndian.Uint16(msg))
+	var seq *int64
+	switch len(msg) {
+	case 2 + count*5:
+		msg = msg[2:]
+	case 10 + count*5:
+		first := binary.LittleEndian.Uint64(msg[2:])
+		if first > math.MaxInt64-uint64(count) {
+			return nil, fmt.Errorf("sequence number %d out of range", first)
+		}
+		n := int64(first)
+		seq = &n
+		msg = msg[10:]
+	default:
+		return nil, errors.New("event list length mismatch")
+	}
+	events := make([]*fobEvent, 0, count)
+	for off := 0; off < len(msg); off += 5 {
+		event := &fobEvent{
+			FobID:   int64(binary.LittleEndian.Uint32(msg[off:])),
+			Allowed: msg[off+4] != 0,
+		}
+		if seq != nil {
+			n := *seq + int64(off/5)
+			event.Seq = &n
+		}
+		events = append(events, event)
+	}
+	return events, nil
+}
+
+func encodeBinaryFobList(ids []int64) ([]byte, error) {
+	if len(ids) > math.MaxUint16 {
+		return nil, fmt.Errorf("too many fobs for binary encoding: %d", len(ids))
+	}
+	msg := make([]byte, 2, 2+4*len(ids))
+	binary.LittleEndian.PutUint16(msg, uint16(len(ids)))
+	for _, id := range ids {
+		if id < 0 || id > math.MaxUint32 {
+			return nil, fmt.Errorf("fob id %d does not fit in 32 bits", id)
+		}
+		msg = binary.LittleEndian.AppendUint32(msg, uint32(id))
+	}
+	return frame(msg), nil
+}
@@ -0,0 +1,72 @@
+package fobapi
+
+import (
+	"encoding/json"
+	"errors"
+	"mime"
+	"strings"
+)
+
+// AcceptPost lists the request bodies POST /api/fobs takes. It is sent on
+// every poll response so controllers set to negotiate (see
+// CONWAY_EVENT_ENCODING=auto in access-controller/) switch to compact events.
+//
+// A JSON or NDJSON body whose Content-Type carries an events=compact
+// parameter holds each event as an array of its values, [fob, allowed, seq],
+// instead of an object.
+const AcceptPost = "application/json, application/json; events=compact, " +
+	"application/x-ndjson, application/x-ndjson; events=compact, " +
+	BinaryContentType
+
+func isCompact(contentType string) bool {
+	_, params, err := mime.ParseMediaType(contentType)
+	return err == nil && strings.EqualFold(params["events"], "compact")
+}
+
+// compactEvent is a fobEvent in the compact encoding. The sequence number
+// may be left off, as in the verbose encoding.
+type compactEvent fobEvent
+
+func (e *compactEvent) UnmarshalJSON(buf []byte) error {
+	var fields []json.RawMessage
+	if err := json.Unmarshal(buf, &fields); err != nil {
+		return err
+	}
+	if len(fields) < 2 {
+		return errors.New("compact event needs fob and allowed")
+	}
+	if err := json.Unmarshal(fields[0], &e.FobID); err != nil {
+		return err
+	}
+	if err := json.Unmarshal(fields[1], &e.Allowed); err != nil {
+		return err
+	}
+	if len(fields) > 2 {
+		return json.Unmarshal(fields[2], &e.Seq)
+	}
+	return nil
+}
+
+// unmarshalEvent decodes one event object, or one compact event array.
+func unmarshalEvent(buf []byte, compact bool) (*fobEvent, error) {
+	event := &fobEvent{}
+	if compact {
+		return event, json.Unmarshal(buf, (*compactEvent)(event))
+	}
+	return event, json.Unmarshal(buf, event)
+}
+
+func decodeCompactEvents(buf []byte) ([]*fobEvent, error) {
+	compact := []*compactEvent{}
+	if err := json.Unmarshal(buf, &compact); err != nil {
+		return nil, err
+	}
+	events := make([]*fobEvent, 0, len(compact))
+	for _, e := range compact {
+		if e == nil {
+			return nil, errors.New("null event")
+		}
+		events = append(events, (*fobEvent)(e))
+	}
+	return events, nil
+}
@@ -5,6 +5,7 @@ package fobapi
 import (
 	"bytes"
 	"context"
+	"crypto/hmac"
 	"crypto/sha256"
 	"database/sql"
 	"encoding/base64"
@@ -16,6 +17,7 @@ import (
 	"net"
 	"net/http"
 	"net/url"
+	"time"
 
 	"github.com/TheLab-ms/conway/engine"
 	"github.com/TheLab-ms/conway/modules/auth"
@@ -28,6 +30,20 @@ import (
 // header and reject any response that lacks a valid signature.
 const SignatureHeader = "X-Fob-Signature"
 
+// HMACHeader is the HTTP header carrying the hex-encoded HMAC-SHA256 of the
+// response body of POST /api/fobs under a secret shared with the access
+// controllers, for sites whose controllers don't pin the signing key.
+const HMACHeader = "X-Fob-HMAC"
+
+// NonceHeader carries a controller-chosen nonce on a POST /api/fobs request.
+// It is echoed on the 200 or 304, and a 200's signature and HMAC then cover
+// the nonce, a newline and the body, so a recorded response can't be
+// replayed to a later request.
+const NonceHeader = "X-Sync-Nonce"
+
+// maxNonceLen bounds the echoed nonce. Controllers send 16 hex digits.
+const maxNonceLen = 64
+
 const migration = `
 CREATE TABLE IF NOT EXISTS fob_clients (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -35,22 +51,41 @@ CREATE TABLE IF NOT EXISTS fob_clients (
     door_name TEXT NOT NULL DEFAULT '',
     last_seen INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
 ) STRICT;
+
+CREATE TABLE IF NOT EXISTS fob_event_batches (
+    key TEXT PRIMARY KEY,
+    received INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+) STRICT;
 `
 
+// IdempotencyHeader carries a controller-chosen key for the batch of swipe
+// events in a POST /api/fobs request. A batch whose key has already been
+// stored is not stored again, so a controller can safely re-send events
+// after losing the response.
+const IdempotencyHeader = "Idempotency-Key"
+
+// batchKeyTTL bounds how long idempotency keys are remembered. Controllers
+// retry every few seconds, so this only needs to outlast a long outage.
+const batchKeyTTL = 7 * 24 * time.Hour
+
 type Module struct {
-	db     *sql.DB
-	self   *url.URL
-	signer *engine.Ed25519Signer
+	db      *sql.DB
+	self    *url.URL
+	signer  *engine.Ed25519Signer
+	hmacKey []byte
 }
 
-func New(db *sql.DB, self *url.URL, signer *engine.Ed25519Signer) *Module {
+// New builds the module. A nil signer or empty hmacKey leaves the
+// corresponding response header off.
+func New(db *sql.DB, self *url.URL, signer *engine.Ed25519Signer, hmacKey []byte) *Module {
 	engine.MustMigrate(db, migration)
 
 	// Add columns to fob_swipes (idempotent - ignore error if exists)
 	db.Exec("ALTER TABLE fob_swipes ADD COLUMN fob_client INTEGER REFERENCES fob_clients(id)")
 	db.Exec("ALTER TABLE fob_swipes ADD COLUMN allowed INTEGER NOT NULL DEFAULT 1")
+	db.Exec("ALTER TABLE fob_swipes ADD COLUMN seq INTEGER")
 
-	return &Module{db: db, self: self, signer: signer}
+	return &Module{db: db, self: self, signer: signer, hmacKey: hmacKey}
 }
 
 // PublicKeyBase64 returns the base64-encoded Ed25519 public key used to sign
@@ -64,6 +99,8 @@ func (m *Module) PublicKeyBase64() string {
 
 func (m *Module) AttachRoutes(router *engine.Router) {
 	router.HandleFunc("POST /api/fobs", auth.OnlyLAN(m.handle))
+	router.HandleFunc("GET /api/fobs/ws", auth.OnlyLAN(m.handlePush))
+	router.HandleFunc("GET /api/events/stream", auth.OnlyLAN(m.handleStream))
 	router.HandleFunc("POST /admin/doors/{id}", router.WithLeadership(m.handleUpdateDoorName))
 }
 
@@ -95,52 +132,68 @@ func (m *Module) handle(w http.ResponseWriter, r *http.Request) {
 		return
 	}
 
+	nonce := r.Header.Get(NonceHeader)
+	if !validNonce(nonce) {
+		http.Error(w, "invalid nonce", 400)
+		return
+	}
+
 	// Store fob swipe events, if any were provided
+	contentType := r.Header.Get("Content-Type")
+	useBinary := isBinary(contentType)
 	events := []*fobEvent{}
 	buf, _ := io.ReadAll(r.Body)
-	err = json.Unmarshal(buf, &events)
-	if err != nil {
-		http.Error(w, "invalid json", 400)
-		return
-	}
-	for _, event := range events {
-		_, err := m.db.ExecContext(r.Context(),
-			`INSERT INTO fob_swipes (uid, timestamp, fob_id, member, fob_client, allowed)
-			 VALUES ($1, strftime('%s', 'now'), $2, (SELECT id FROM members WHERE fob_id = $2), $3, $4)
-			 ON CONFLICT DO NOTHING`,
-			uuid.NewString(), event.FobID, clientID, event.Allowed)
+	if useBinary {
+		events, err = decodeBinaryEvents(buf)
 		if err != nil {
-			engine.SystemError(w, err.Error())
+			http.Error(w, "invalid binary body: "+err.Error(), 400)
+			return
+		}
+	} else if isNDJSON(contentType) {
+		events, err = decodeNDJSONEvents(buf, isCompact(contentType))
+		if err != nil {
+			http.Error(w, "invalid ndjson body: "+err.Error(), 400)
+			return
+		}
+	} else if isCompact(contentType) {
+		events, err = decodeCompactEvents(buf)
+		if err != nil {
+			http.Error(w, "invalid json", 400)
+			return
+		}
+	} else {
+		err = json.Unmarshal(buf, &events)
+		if err != nil {
+			http.Error(w, "invalid json", 400)
 			return
 		}
 	}
-	if l := len(events); l > 0 {
-		slog.Info("stored fob swipe events", "count", len(events), "client", clientIP)
-	}
-
-	// Query for the current enabled keyfobs
-	const q = "SELECT fob_id FROM active_keyfobs ORDER BY fob_id"
-	rows, err := m.db.QueryContext(r.Context(), q)
+	stored, err := m.storeEvents(r.Context(), r.Header.Get(IdempotencyHeader), clientID, events)
 	if err != nil {
 		engine.SystemError(w, err.Error())
 		return
 	}
-	defer rows.Close()
-
-	// Transform the query response into the json response and etag hash
-	hasher := sha256.New()
-	var ids []int64
-	for rows.Next() {
-		var id int64
-		rows.Scan(&id)
-		fmt.Fprintf(hasher, "%d,", id)
-		ids = append(ids, id)
+	if stored {
+		if l := len(events); l > 0 {
+			slog.Info("stored fob swipe events", "count", len(events), "client", clientIP)
+		}
+	} else {
+		slog.Info("ignored duplicate fob swipe batch", "count", len(events), "client", clientIP)
 	}
-	if err := rows.Err(); err != nil {
+
+	ids, etag, err := m.activeFobs(r.Context())
+	if err != nil {
 		engine.SystemError(w, err.Error())
 		return
 	}
-	etag := hex.EncodeToString(hasher.Sum(nil))
+
+	// Advertise compact events either way, so a controller negotiating
+	// the encoding learns of them from its first poll.
+	w.Header().Set("Accept-Post", AcceptPost)
+	// A 304 echoes the nonce too: the controller rejects one that doesn't.
+	if nonce != "" {
+		w.Header().Set(NonceHeader, nonce)
+	}
 
 	// Return the response, or a 304 if the client already has the latest data
 	if r.Header.Get("If-None-Match") == etag {
@@ -150,20 +203,115 @@ func (m *Module) handle(w http.ResponseWriter, r *http.Request) {
 
 	// Serialize the body into a buffer so we can sign the exact bytes the
 	// client will see. The access-controller verifies the signature against
-	// the raw response body before parsing it.
+	// the raw response body, after the nonce if it sent one, before parsing it.
 	var body bytes.Buffer
-	if err := json.NewEncoder(&body).Encode(&ids); err != nil {
+	if useBinary {
+		bin, err := encodeBinaryFobList(ids)
+		if err != nil {
+			engine.SystemError(w, err.Error())
+			return
+		}
+		body.Write(bin)
+		w.Header().Set("Content-Type", BinaryContentType)
+	} else if err := json.NewEncoder(&body).Encode(&ids); err != nil {
 		engine.SystemError(w, err.Error())
 		return
 	}
+	signed := body.Bytes()
+	if nonce != "" {
+		signed = append([]byte(nonce+"\n"), signed...)
+	}
 	if m.signer != nil {
-		sig := m.signer.Sign(body.Bytes())
+		sig := m.signer.Sign(signed)
 		w.Header().Set(SignatureHeader, base64.StdEncoding.EncodeToString(sig))
 	}
+	if len(m.hmacKey) > 0 {
+		w.Header().Set(HMACHeader, bodyMAC(m.hmacKey, signed))
+	}
 	w.Header().Set("ETag", etag)
 	w.Write(body.Bytes())
 }
 
+// validNonce reports whether nonce is absent or at most maxNonceLen hex
+// digits, in pairs, so it is safe to echo and to sign.
+func validNonce(nonce string) bool {
+	if len(nonce) > maxNonceLen {
+		return false
+	}
+	_, err := hex.DecodeString(nonce)
+	return err == nil
+}
+
+// bodyMAC returns the HMAC-SHA256 of body under key as lowercase hex.
+func bodyMAC(key, body []byte) string {
+	mac := hmac.New(sha256.New, key)
+	mac.Write(body)
+	return hex.EncodeToString(mac.Sum(nil))
+}
+
+// storeEvents records a batch of swipe events. A non-empty key identifies the
+// batch: if it has been seen before, nothing is stored and false is returned.
+func (m *Module) storeEvents(ctx context.Context, key string, clientID int64, events []*fobEvent) (bool, error) {
+	tx, err := m.db.BeginTx(ctx, nil)
+	if err != nil {
+		return false, err
+	}
+	defer tx.Rollback()
+
+	if key != "" {
+		res, err := tx.ExecContext(ctx,
+			"INSERT INTO fob_event_batches (key) VALUES ($1) ON CONFLICT DO NOTHING", key)
+		if err != nil {
+			return false, err
+		}
+		if n, _ := res.RowsAffected(); n == 0 {
+			return false, nil
+		}
+	}
+
+	for _, event := range events {
+		_, err := tx.ExecContext(ctx,
+			`INSERT INTO fob_swipes (uid, timestamp, fob_id, member, fob_client, allowed, seq)
+			 VALUES ($1, strftime('%s', 'now'), $2, (SELECT id FROM members WHERE fob_id = $2), $3, $4, $5)
+			 ON CONFLICT DO NOTHING`,
+			uuid.NewString(), event.FobID, clientID, event.Allowed, event.Seq)
+		if err != nil {
+			return false, err
+		}
+	}
+	return true, tx.Commit()
+}
+
+// activeFobs returns the currently enabled keyfobs in sort order along with
+// their etag: the hex sha256 of the comma-joined IDs.
+func (m *Module) activeFobs(ctx context.Context) ([]int64, string, error) {
+	const q = "SELECT fob_id FROM active_keyfobs ORDER BY fob_id"
+	rows, err := m.db.QueryContext(ctx, q)
+	if err != nil {
+		return nil, "", err
+	}
+	defer rows.Close()
+
+	hasher := sha256.New()
+	var ids []int64
+	for rows.Next() {
+		var id int64
+		rows.Scan(&id)
+		fmt.Fprintf(hasher, "%d,", id)
+		ids = append(ids, id)
+	}
+	if err := rows.Err(); err != nil {
+		return nil, "", err
+	}
+	return ids, hex.EncodeToString(hasher.Sum(nil)), nil
+}
+
+func (m *Module) AttachWorkers(mgr *engine.ProcMgr) {
+	mgr.Add(engine.Poll(time.Hour, engine.Cleanup(m.db, "expired fob event batch keys",
+		`DELETE FROM fob_event_batches WHERE unixepoch() - received > ?`,
+		int64(batchKeyTTL.Seconds()))))
+}
+
 // handleUpdateDoorName allows admins to assign a door name to a fob API client.
 func (m *Module) handleUpdateDoorName(w http.ResponseWriter, r *http.Request) {
 	clientID := r.PathValue("id")
@@ -208,7 +356,10 @@ func (m *Module) listClients(ctx context.Context) ([]*fobClient, error) {
 	return clients, rows.Err()
 }
 
+// fobEvent is one reported swipe. Seq is the controller's sequence number for
+// it, if it sent one.
 type fobEvent struct {
-	FobID   int64 `json:"fob"`
-	Allowed bool  `json:"allowed"`
+	FobID   int64  `json:"fob"`
+	Allowed bool   `json:"allowed"`
+	Seq     *int64 `json:"seq"`
 }
@@ -1,15 +1,20 @@
 package fobapi
 
 import (
+	"bufio"
 	"bytes"
 	"crypto/ed25519"
 	"encoding/base64"
 	"fmt"
+	"net/http"
 	"net/http/httptest"
 	"path/filepath"
+	"strings"
 	"testing"
+	"time"
 
 	"github.com/TheLab-ms/conway/engine"
+	"github.com/gorilla/websocket"
 	"github.com/stretchr/testify/assert"
 	"github.com/stretchr/testify/require"
 )
@@ -47,7 +52,7 @@ func TestListing(t *testing.T) {
 	require.NoError(t, err)
 
 	signer := newTestSigner(t)
-	m := New(db, nil, signer)
+	m := New(db, nil, signer, nil)
 	const etag = "3ac3b3f37064c09f3be2a0b733d93964ef41657dcabd00029149920e1d3939c4"
 
 	// Happy path
@@ -86,7 +91,7 @@ func TestNoSigner(t *testing.T) {
 	_, err := db.Exec(testMigration)
 	require.NoError(t, err)
 
-	m := New(db, nil, nil)
+	m := New(db, nil, nil, nil)
 	r := httptest.NewRequest("GET", "/", bytes.NewBufferString("[]"))
 	w := httptest.NewRecorder()
 	m.handle(w, r)
@@ -95,12 +100,87 @@ func TestNoSigner(t *testing.T) {
 	assert.Empty(t, m.PublicKeyBase64())
 }
 
+func TestHMAC(t *testing.T) {
+	// RFC 4231 test case 2, as in access-controller/tests/body_mac.rs
+	assert.Equal(t, "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+		bodyMAC([]byte("Jefe"), []byte("what do ya want for nothing?")))
+
+	db := engine.OpenTestDB(t)
+	_, err := db.Exec(testMigration)
+	require.NoError(t, err)
+
+	key := []byte("Jefe")
+	m := New(db, nil, nil, key)
+	r := httptest.NewRequest("POST", "/", bytes.NewBufferString("[]"))
+	w := httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+	assert.Equal(t, bodyMAC(key, w.Body.Bytes()), w.Header().Get(HMACHeader))
+	assert.Len(t, w.Header().Get(HMACHeader), 64)
+
+	// No key, no header
+	m = New(db, nil, nil, nil)
+	w = httptest.NewRecorder()
+	m.handle(w, httptest.NewRequest("POST", "/", bytes.NewBufferString("[]")))
+	assert.Empty(t, w.Header().Get(HMACHeader))
+}
+
+func TestNonce(t *testing.T) {
+	db := engine.OpenTestDB(t)
+	_, err := db.Exec(testMigration)
+	require.NoError(t, err)
+
+	signer := newTestSigner(t)
+	key := []byte("Jefe")
+	m := New(db, nil, signer, key)
+	const nonce = "00c0ffee12345678"
+
+	r := httptest.NewRequest("POST", "/", bytes.NewBufferString("[]"))
+	r.Header.Set(NonceHeader, nonce)
+	w := httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+	assert.Equal(t, nonce, w.Header().Get(NonceHeader))
+
+	// Signature and HMAC cover the nonce, a newline, then the body
+	bound := append([]byte(nonce+"\n"), w.Body.Bytes()...)
+	sig, err := base64.StdEncoding.DecodeString(w.Header().Get(SignatureHeader))
+	require.NoError(t, err)
+	pub, err := base64.StdEncoding.DecodeString(signer.PublicKeyBase64())
+	require.NoError(t, err)
+	assert.True(t, ed25519.Verify(ed25519.PublicKey(pub), bound, sig))
+	assert.False(t, ed25519.Verify(ed25519.PublicKey(pub), w.Body.Bytes(), sig))
+	assert.Equal(t, bodyMAC(key, bound), w.Header().Get(HMACHeader))
+
+	// A 304 echoes it too
+	r = httptest.NewRequest("POST", "/", bytes.NewBufferString("[]"))
+	r.Header.Set(NonceHeader, nonce)
+	r.Header.Set("If-None-Match", w.Header().Get("ETag"))
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 304, w.Code)
+	assert.Equal(t, nonce, w.Header().Get(NonceHeader))
+
+	// No nonce, no echo
+	w = httptest.NewRecorder()
+	m.handle(w, httptest.NewRequest("POST", "/", bytes.NewBufferString("[]")))
+	assert.Equal(t, 200, w.Code)
+	assert.Empty(t, w.Header().Get(NonceHeader))
+
+	// Anything but hex is refused rather than echoed
+	r = httptest.NewRequest("POST", "/", bytes.NewBufferString("[]"))
+	r.Header.Set(NonceHeader, "not-a-nonce")
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 400, w.Code)
+}
+
 func TestEvents(t *testing.T) {
 	db := engine.OpenTestDB(t)
 	_, err := db.Exec(testMigration)
 	require.NoError(t, err)
 
-	m := New(db, nil, newTestSigner(t))
+	m := New(db, nil, newTestSigner(t), nil)
 
 	r := httptest.NewRequest("GET", "/", bytes.NewBufferString(`[{ "fob": 123, "ts": 1000 }, { "fob": 123, "ts": 1000 }, { "fob": 123, "ts": 1001 }, { "fob": 345, "ts": 10001 }]`))
 	w := httptest.NewRecorder()
@@ -125,3 +205,246 @@ func TestEvents(t *testing.T) {
 	}, resultStrings)
 }
 
+func TestBinaryProtocol(t *testing.T) {
+	db := engine.OpenTestDB(t)
+	_, err := db.Exec(testMigration)
+	require.NoError(t, err)
+
+	signer := newTestSigner(t)
+	m := New(db, nil, signer, nil)
+
+	// One event: fob 345, denied, numbered 41
+	req := []byte{0, 0, 0, 0, 15, 1, 0, 41, 0, 0, 0, 0, 0, 0, 0, 0x59, 0x01, 0, 0, 0}
+	r := httptest.NewRequest("POST", "/", bytes.NewReader(req))
+	r.Header.Set("Content-Type", BinaryContentType)
+	w := httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+	assert.Equal(t, BinaryContentType, w.Header().Get("Content-Type"))
+	assert.Equal(t, []byte{0, 0, 0, 0, 10, 2, 0, 123, 0, 0, 0, 234, 0, 0, 0}, w.Body.Bytes())
+
+	// Same list, same ETag as the JSON encoding
+	assert.Equal(t, "3ac3b3f37064c09f3be2a0b733d93964ef41657dcabd00029149920e1d3939c4", w.Header().Get("ETag"))
+
+	// The signature covers the binary body
+	sig, err := base64.StdEncoding.DecodeString(w.Header().Get(SignatureHeader))
+	require.NoError(t, err)
+	pub, err := base64.StdEncoding.DecodeString(signer.PublicKeyBase64())
+	require.NoError(t, err)
+	assert.True(t, ed25519.Verify(ed25519.PublicKey(pub), w.Body.Bytes(), sig))
+
+	var fob, seq int64
+	require.NoError(t, db.QueryRow("SELECT fob_id, seq FROM fob_swipes").Scan(&fob, &seq))
+	assert.Equal(t, int64(345), fob)
+	assert.Equal(t, int64(41), seq)
+
+	// Malformed frame
+	r = httptest.NewRequest("POST", "/", bytes.NewReader(req[:len(req)-1]))
+	r.Header.Set("Content-Type", BinaryContentType)
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 400, w.Code)
+}
+
+func TestNDJSONProtocol(t *testing.T) {
+	db := engine.OpenTestDB(t)
+	_, err := db.Exec(testMigration)
+	require.NoError(t, err)
+
+	m := New(db, nil, newTestSigner(t), nil)
+
+	// Two events, one per line; the list comes back as JSON
+	body := "{\"fob\":123,\"allowed\":true,\"seq\":41}\n{\"fob\":345,\"allowed\":false,\"seq\":42}\n"
+	r := httptest.NewRequest("POST", "/", bytes.NewBufferString(body))
+	r.Header.Set("Content-Type", NDJSONContentType)
+	w := httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+	assert.Equal(t, "[123,234]\n", w.Body.String())
+	assert.NotEmpty(t, w.Header().Get(SignatureHeader))
+
+	var n int
+	require.NoError(t, db.QueryRow("SELECT COUNT(*) FROM fob_swipes WHERE fob_id IN (123, 345)").Scan(&n))
+	assert.Equal(t, 2, n)
+	var allowed bool
+	var seq int64
+	require.NoError(t, db.QueryRow("SELECT allowed, seq FROM fob_swipes WHERE fob_id = 345").Scan(&allowed, &seq))
+	assert.False(t, allowed)
+	assert.Equal(t, int64(42), seq)
+
+	// No events is an empty body
+	r = httptest.NewRequest("POST", "/", bytes.NewReader(nil))
+	r.Header.Set("Content-Type", NDJSONContentType)
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+	assert.Equal(t, "[123,234]\n", w.Body.String())
+
+	// Malformed line
+	r = httptest.NewRequest("POST", "/", bytes.NewBufferString("{\"fob\":123}\n[\n"))
+	r.Header.Set("Content-Type", NDJSONContentType)
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 400, w.Code)
+}
+
+func TestCompactEvents(t *testing.T) {
+	db := engine.OpenTestDB(t)
+	_, err := db.Exec(testMigration)
+	require.NoError(t, err)
+
+	m := New(db, nil, nil, nil)
+
+	// Every poll advertises compact events, even one with nothing to send
+	r := httptest.NewRequest("POST", "/", bytes.NewBufferString("[]"))
+	w := httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+	assert.Equal(t, AcceptPost, w.Header().Get("Accept-Post"))
+	assert.Contains(t, w.Header().Get("Accept-Post"), "application/json; events=compact")
+
+	r = httptest.NewRequest("POST", "/", bytes.NewBufferString("[]"))
+	r.Header.Set("If-None-Match", w.Header().Get("ETag"))
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 304, w.Code)
+	assert.Equal(t, AcceptPost, w.Header().Get("Accept-Post"))
+
+	// A JSON array of [fob, allowed, seq] arrays
+	r = httptest.NewRequest("POST", "/", bytes.NewBufferString("[[123,true,41],[345,false,42]]"))
+	r.Header.Set("Content-Type", "application/json; events=compact")
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+	assert.Equal(t, "[123,234]\n", w.Body.String())
+
+	// NDJSON lines of the same
+	r = httptest.NewRequest("POST", "/", bytes.NewBufferString("[456,true,43]\n"))
+	r.Header.Set("Content-Type", "application/x-ndjson; events=compact")
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+
+	rows, err := db.Query("SELECT fob_id, allowed, seq FROM fob_swipes ORDER BY fob_id")
+	require.NoError(t, err)
+	got := []string{}
+	for rows.Next() {
+		var fob, seq int64
+		var allowed bool
+		require.NoError(t, rows.Scan(&fob, &allowed, &seq))
+		got = append(got, fmt.Sprintf("%d %t %d", fob, allowed, seq))
+	}
+	require.NoError(t, rows.Err())
+	assert.Equal(t, []string{"123 true 41", "345 false 42", "456 true 43"}, got)
+
+	// Verbose events under a compact Content-Type are malformed
+	r = httptest.NewRequest("POST", "/", bytes.NewBufferString(`[{"fob": 123}]`))
+	r.Header.Set("Content-Type", "application/json; events=compact")
+	w = httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 400, w.Code)
+}
+
+func TestBinaryRoundTrip(t *testing.T) {
+	events, err := decodeBinaryEvents(frame([]byte{2, 0, 1, 0, 0, 0, 1, 2, 0, 0, 0, 0}))
+	require.NoError(t, err)
+	require.Len(t, events, 2)
+	assert.Equal(t, fobEvent{FobID: 1, Allowed: true}, *events[0])
+	assert.Equal(t, fobEvent{FobID: 2, Allowed: false}, *events[1])
+
+	// Numbered on from the first event's sequence number
+	events, err = decodeBinaryEvents(frame([]byte{2, 0, 7, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 2, 0, 0, 0, 0}))
+	require.NoError(t, err)
+	require.Len(t, events, 2)
+	seq7, seq8 := int64(7), int64(8)
+	assert.Equal(t, fobEvent{FobID: 1, Allowed: true, Seq: &seq7}, *events[0])
+	assert.Equal(t, fobEvent{FobID: 2, Allowed: false, Seq: &seq8}, *events[1])
+
+	_, err = decodeBinaryEvents(frame([]byte{1, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1, 0, 0, 0, 1}))
+	assert.Error(t, err)
+
+	_, err = encodeBinaryFobList([]int64{1 << 32})
+	assert.Error(t, err)
+}
+
+func TestPush(t *testing.T) {
+	db := engine.OpenTestDB(t)
+	_, err := db.Exec(testMigration)
+	require.NoError(t, err)
+
+	m := New(db, nil, nil, nil)
+	srv := httptest.NewServer(http.HandlerFunc(m.handlePush))
+	defer srv.Close()
+
+	conn, _, err := websocket.DefaultDialer.Dial("ws"+strings.TrimPrefix(srv.URL, "http"), nil)
+	require.NoError(t, err)
+	defer conn.Close()
+
+	_, err = db.Exec("INSERT INTO members (fob_id) VALUES (345)")
+	require.NoError(t, err)
+
+	conn.SetReadDeadline(time.Now().Add(5 * time.Second))
+	kind, msg, err := conn.ReadMessage()
+	require.NoError(t, err)
+	assert.Equal(t, websocket.TextMessage, kind)
+	assert.Equal(t, PushMessage, string(msg))
+}
+
+func TestStream(t *testing.T) {
+	db := engine.OpenTestDB(t)
+	_, err := db.Exec(testMigration)
+	require.NoError(t, err)
+
+	m := New(db, nil, nil, nil)
+	srv := httptest.NewServer(http.HandlerFunc(m.handleStream))
+	defer srv.Close()
+
+	resp, err := http.Get(srv.URL)
+	require.NoError(t, err)
+	defer resp.Body.Close()
+	assert.Equal(t, "text/event-stream", resp.Header.Get("Content-Type"))
+
+	_, err = db.Exec("INSERT INTO members (fob_id) VALUES (345)")
+	require.NoError(t, err)
+
+	lines := bufio.NewReader(resp.Body)
+	line, err := lines.ReadString('\n')
+	require.NoError(t, err)
+	assert.Equal(t, "data: "+PushMessage+"\n", line)
+}
+
+func TestIdempotentEvents(t *testing.T) {
+	db := engine.OpenTestDB(t)
+	_, err := db.Exec(testMigration)
+	require.NoError(t, err)
+
+	m := New(db, nil, nil, nil)
+	events := []*fobEvent{{FobID: 123, Allowed: true}}
+
+	stored, err := m.storeEvents(t.Context(), "240ac4123456-00000001-00000000", 1, events)
+	require.NoError(t, err)
+	assert.True(t, stored)
+
+	// Retry after a lost response
+	stored, err = m.storeEvents(t.Context(), "240ac4123456-00000001-00000000", 1, events)
+	require.NoError(t, err)
+	assert.False(t, stored)
+
+	// Next batch, and clients that don't send a key
+	stored, err = m.storeEvents(t.Context(), "240ac4123456-00000001-00000001", 1, events)
+	require.NoError(t, err)
+	assert.True(t, stored)
+	stored, err = m.storeEvents(t.Context(), "", 1, events)
+	require.NoError(t, err)
+	assert.True(t, stored)
+
+	// The header is honored end to end
+	r := httptest.NewRequest("POST", "/", bytes.NewBufferString(`[{"fob": 234, "allowed": true}]`))
+	r.Header.Set(IdempotencyHeader, "240ac4123456-00000001-00000000")
+	w := httptest.NewRecorder()
+	m.handle(w, r)
+	assert.Equal(t, 200, w.Code)
+	var n int
+	require.NoError(t, db.QueryRow("SELECT COUNT(*) FROM fob_swipes WHERE fob_id = 234").Scan(&n))
+	assert.Equal(t, 0, n)
+}
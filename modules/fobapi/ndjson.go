@@ -0,0 +1,34 @@
+package fobapi
+
+import (
+	"bytes"
+	"fmt"
+	"mime"
+)
+
+// NDJSONContentType selects newline-delimited JSON events in POST /api/fobs:
+// one event object per line in place of a JSON array. An empty body carries
+// no events. The response is the usual JSON fob list. Lines may be compact
+// events; see AcceptPost.
+const NDJSONContentType = "application/x-ndjson"
+
+func isNDJSON(contentType string) bool {
+	mt, _, err := mime.ParseMediaType(contentType)
+	return err == nil && mt == NDJSONContentType
+}
+
+func decodeNDJSONEvents(buf []byte, compact bool) ([]*fobEvent, error) {
+	events := []*fobEvent{}
+	for i, line := range bytes.Split(buf, []byte("\n")) {
+		line = bytes.TrimSpace(line)
+		if len(line) == 0 {
+			continue
+		}
+		event, err := unmarshalEvent(line, compact)
+		if err != nil {
+			return nil, fmt.Errorf("line %d: %w", i+1, err)
+		}
+		events = append(events, event)
+	}
+	return events, nil
+}
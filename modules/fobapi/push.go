@@ -0,0 +1,134 @@
+package fobapi
+
+import (
+	"context"
+	"fmt"
+	"io"
+	"log/slog"
+	"net/http"
+	"time"
+
+	"github.com/TheLab-ms/conway/engine"
+	"github.com/gorilla/websocket"
+)
+
+// PushMessage is sent on GET /api/fobs/ws (as a text frame) and
+// GET /api/events/stream (as an SSE data line) whenever the set of active
+// keyfobs changes. It carries no data: controllers react by syncing
+// immediately through POST /api/fobs.
+const PushMessage = "changed"
+
+const (
+	pushCheckInterval = time.Second
+	pushPingInterval  = 30 * time.Second
+	pushWriteTimeout  = 10 * time.Second
+)
+
+var upgrader = websocket.Upgrader{
+	// Controllers are not browsers and send no Origin; the route is
+	// already restricted to the LAN.
+	CheckOrigin: func(r *http.Request) bool { return true },
+}
+
+// watchFobs calls changed whenever the active fob set differs from the one
+// identified by etag, and keepalive every pushPingInterval, until ctx is done
+// or either callback fails.
+func (m *Module) watchFobs(ctx context.Context, etag string, changed, keepalive func() error) {
+	check := time.NewTicker(pushCheckInterval)
+	defer check.Stop()
+	ping := time.NewTicker(pushPingInterval)
+	defer ping.Stop()
+	for {
+		select {
+		case <-ctx.Done():
+			return
+		case <-ping.C:
+			if keepalive() != nil {
+				return
+			}
+		case <-check.C:
+			_, current, err := m.activeFobs(ctx)
+			if err != nil {
+				if ctx.Err() == nil {
+					slog.Error("fob push: unable to read fobs", "error", err)
+				}
+				return
+			}
+			if current == etag {
+				continue
+			}
+			etag = current
+			if changed() != nil {
+				return
+			}
+		}
+	}
+}
+
+// handlePush holds a WebSocket open to an access controller and notifies it
+// when the fob list changes, so revocations take effect without waiting for
+// the controller's next poll.
+func (m *Module) handlePush(w http.ResponseWriter, r *http.Request) {
+	// Snapshot before upgrading so no change can slip in between.
+	_, etag, err := m.activeFobs(r.Context())
+	if err != nil {
+		engine.SystemError(w, err.Error())
+		return
+	}
+
+	conn, err := upgrader.Upgrade(w, r, nil)
+	if err != nil {
+		return // the upgrader has already replied
+	}
+	defer conn.Close()
+
+	ctx, cancel := context.WithCancel(r.Context())
+	defer cancel()
+
+	// Drain incoming frames so pongs and close frames are processed; any
+	// read error means the controller is gone.
+	go func() {
+		defer cancel()
+		for {
+			if _, _, err := conn.NextReader(); err != nil {
+				return
+			}
+		}
+	}()
+
+	m.watchFobs(ctx, etag,
+		func() error {
+			conn.SetWriteDeadline(time.Now().Add(pushWriteTimeout))
+			return conn.WriteMessage(websocket.TextMessage, []byte(PushMessage))
+		},
+		func() error {
+			return conn.WriteControl(websocket.PingMessage, nil, time.Now().Add(pushWriteTimeout))
+		})
+}
+
+// handleStream is the server-sent events flavor of handlePush for controllers
+// that would rather not speak WebSocket: a `data: changed` event per change
+// and a comment line as keepalive.
+func (m *Module) handleStream(w http.ResponseWriter, r *http.Request) {
+	_, etag, err := m.activeFobs(r.Context())
+	if err != nil {
+		engine.SystemError(w, err.Error())
+		return
+	}
+
+	rc := http.NewResponseController(w)
+	w.Header().Set("Content-Type", "text/event-stream")
+	w.Header().Set("Cache-Control", "no-cache")
+	w.WriteHeader(200)
+	rc.Flush()
+
+	send := func(s string) error {
+		if _, err := io.WriteString(w, s); err != nil {
+			return err
+		}
+		return rc.Flush()
+	}
+	m.watchFobs(r.Context(), etag,
+		func() error { return send(fmt.Sprintf("data: %s\n\n", PushMessage)) },
+		func() error { return send(": keepalive\n\n") })
+}
@@ -48,6 +48,10 @@ type Options struct {
 	// controllers can verify authenticity against a pinned public key.
 	FobSigner *engine.Ed25519Signer
 
+	// FobHMACKey, if set, is the secret shared with access controllers
+	// built with response-hmac; POST /api/fobs responses carry its HMAC.
+	FobHMACKey []byte
+
 	// Auth options
 	Turnstile *auth.TurnstileOptions
 
@@ -98,7 +102,7 @@ func Register(a *engine.App, opts Options) *auth.Module {
 	a.Add(waiver.New(opts.Database))
 	a.Add(kiosk.New(opts.Database, opts.Self, opts.FobIssuer, opts.SpaceHost))
 	a.Add(metrics.New(opts.Database))
-	a.Add(fobapi.New(opts.Database, opts.Self, opts.FobSigner))
+	a.Add(fobapi.New(opts.Database, opts.Self, opts.FobSigner, opts.FobHMACKey))
 	a.Add(directory.New(opts.Database))
 
 	// Discord modules registered before machines, since the machines